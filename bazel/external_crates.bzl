@@ -429,6 +429,15 @@ def external_crates_repository(name, annotations):
                 version = "^0.7.1",
                 default_features = False,
             ),
+            "lz4_flex": crate.spec(
+                version = "^0.9.5",
+                default_features = False,
+                features = [
+                    "std",
+                    "safe-encode",
+                    "safe-decode",
+                ],
+            ),
             "maplit": crate.spec(
                 version = "^1.0.2",
             ),
@@ -528,6 +537,7 @@ def external_crates_repository(name, annotations):
                 features = [
                     "bulk",
                     "multi_value",
+                    "simd",
                     "std",
                 ],
             ),
@@ -966,6 +976,9 @@ def external_crates_repository(name, annotations):
             "wasm-bindgen": crate.spec(
                 version = "^0.2",
             ),
+            "wasmparser": crate.spec(
+                version = "^0.86.0",
+            ),
             "wasmtime": crate.spec(
                 version = "^0.39.1",
                 default_features = False,
@@ -0,0 +1,963 @@
+//! A portable, versioned archive format for the validated artifact pools.
+//!
+//! An archive contains the validated consensus, certification and ECDSA
+//! artifacts of a pool, optionally restricted to a height range, and can be
+//! imported into a pool with either persistent backend. This allows migrating
+//! a pool from one backend to another, and attaching pools to bug reports.
+//! DKG messages are not archived separately: the DKG pool is not persisted,
+//! and the dealings that made it into the chain are contained in the blocks.
+//!
+//! An archive consists of
+//!
+//! ```text
+//! · the magic bytes "ICPOOLAR"
+//! · the format version as u32 (little endian)
+//! · a gzip stream of records, each consisting of
+//!   · the length of the record as u32 (little endian)
+//!   · the protobuf encoding of the record
+//! ```
+//!
+//! The first record is the [`ArchiveMetadata`], all following records are
+//! [`ArchivedArtifact`]s. Artifacts are stored in their protobuf encoding, so
+//! that archives remain readable across replica versions, and catch-up
+//! packages are stored with the original bytes they were persisted with.
+//! Readers reject archives with a format version they don't know, so the
+//! version must be bumped whenever the encoding of the records changes.
+
+use crate::{
+    certification_pool::MutablePoolSection as CertificationPoolSection,
+    consensus_pool::{InitializablePoolSection, PoolSectionOps},
+};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use ic_consensus_message::ConsensusMessageHashable;
+use ic_interfaces::{
+    consensus_pool::{HeightIndexedPool, HeightRange, PoolSection, ValidatedConsensusArtifact},
+    ecdsa::{EcdsaChangeAction, EcdsaPoolSection, MutableEcdsaPool},
+};
+use ic_protobuf::types::v1 as pb;
+use ic_types::{
+    consensus::{
+        catchup::CUPWithOriginalProtobuf,
+        certification::CertificationMessage,
+        ecdsa::{EcdsaMessage, EcdsaMessageAttribute},
+        CatchUpPackage, ConsensusMessage,
+    },
+    time::current_time,
+    Height, ReplicaVersion, Time,
+};
+use prost::Message;
+use std::{
+    convert::{TryFrom, TryInto},
+    fmt,
+    io::{self, Read, Write},
+};
+
+/// The version of the archive format written by [`ArchiveWriter`].
+pub const ARCHIVE_FORMAT_VERSION: u32 = 2;
+
+const MAGIC: &[u8; 8] = b"ICPOOLAR";
+
+/// The number of consensus artifacts inserted into the pool at once.
+const IMPORT_BATCH_SIZE: usize = 1000;
+
+#[derive(Debug)]
+pub enum ArchiveError {
+    Io(io::Error),
+    /// The input does not start with the archive magic bytes.
+    NotAnArchive,
+    UnsupportedVersion(u32),
+    /// The archive is truncated, or contains a record that can't be decoded.
+    Malformed(String),
+}
+
+impl From<io::Error> for ArchiveError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "I/O error: {}", err),
+            Self::NotAnArchive => write!(f, "not a pool archive"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported archive format version {}, expected {}",
+                version, ARCHIVE_FORMAT_VERSION
+            ),
+            Self::Malformed(msg) => write!(f, "malformed archive: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+/// Describes the contents of an archive.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ArchiveMetadata {
+    /// The replica version of the pool the archive was exported from.
+    pub replica_version: Option<ReplicaVersion>,
+    /// The inclusive range of heights of the archived artifacts, or `None` if
+    /// all artifacts were archived.
+    pub height_range: Option<(Height, Height)>,
+}
+
+impl ArchiveMetadata {
+    fn contains(&self, height: Height) -> bool {
+        self.height_range
+            .map_or(true, |(min, max)| min <= height && height <= max)
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ArchivedArtifact {
+    /// A consensus artifact other than a catch-up package.
+    Consensus(ValidatedConsensusArtifact),
+    /// A catch-up package as it was persisted in the pool. The protobuf is
+    /// kept as is, so that the signature of the original bytes remains
+    /// verifiable.
+    CatchUpPackage(pb::CatchUpPackage),
+    Certification(CertificationMessage),
+    Ecdsa(EcdsaMessage),
+}
+
+/// The protobuf encoding of [`ArchiveMetadata`].
+#[derive(Clone, PartialEq, Message)]
+struct ArchiveMetadataProto {
+    #[prost(string, optional, tag = "1")]
+    replica_version: Option<String>,
+    #[prost(message, optional, tag = "2")]
+    height_range: Option<HeightRangeProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct HeightRangeProto {
+    #[prost(uint64, tag = "1")]
+    min: u64,
+    #[prost(uint64, tag = "2")]
+    max: u64,
+}
+
+/// The protobuf encoding of [`ArchivedArtifact`].
+#[derive(Clone, PartialEq, Message)]
+struct ArchivedArtifactProto {
+    #[prost(oneof = "archived_artifact_proto::Artifact", tags = "1, 2, 3, 4")]
+    artifact: Option<archived_artifact_proto::Artifact>,
+}
+
+mod archived_artifact_proto {
+    use ic_protobuf::types::v1 as pb;
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub(super) enum Artifact {
+        #[prost(message, tag = "1")]
+        Consensus(super::ValidatedConsensusArtifactProto),
+        #[prost(message, tag = "2")]
+        CatchUpPackage(pb::CatchUpPackage),
+        #[prost(message, tag = "3")]
+        Certification(pb::CertificationMessage),
+        #[prost(message, tag = "4")]
+        Ecdsa(pb::EcdsaMessage),
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ValidatedConsensusArtifactProto {
+    #[prost(message, optional, tag = "1")]
+    msg: Option<pb::ConsensusMessage>,
+    /// The time the artifact was added to the pool, in nanoseconds since the
+    /// Unix epoch.
+    #[prost(uint64, tag = "2")]
+    timestamp: u64,
+}
+
+impl From<&ArchiveMetadata> for ArchiveMetadataProto {
+    fn from(metadata: &ArchiveMetadata) -> Self {
+        Self {
+            replica_version: metadata.replica_version.as_ref().map(String::from),
+            height_range: metadata.height_range.map(|(min, max)| HeightRangeProto {
+                min: min.get(),
+                max: max.get(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<ArchiveMetadataProto> for ArchiveMetadata {
+    type Error = String;
+    fn try_from(metadata: ArchiveMetadataProto) -> Result<Self, Self::Error> {
+        Ok(Self {
+            replica_version: metadata
+                .replica_version
+                .map(|version| {
+                    ReplicaVersion::try_from(version)
+                        .map_err(|err| format!("invalid replica version: {:?}", err))
+                })
+                .transpose()?,
+            height_range: metadata
+                .height_range
+                .map(|range| (Height::from(range.min), Height::from(range.max))),
+        })
+    }
+}
+
+impl From<&ArchivedArtifact> for ArchivedArtifactProto {
+    fn from(artifact: &ArchivedArtifact) -> Self {
+        use archived_artifact_proto::Artifact;
+        Self {
+            artifact: Some(match artifact {
+                ArchivedArtifact::Consensus(artifact) => {
+                    Artifact::Consensus(ValidatedConsensusArtifactProto {
+                        msg: Some((&artifact.msg).into()),
+                        timestamp: artifact.timestamp.as_nanos_since_unix_epoch(),
+                    })
+                }
+                ArchivedArtifact::CatchUpPackage(protobuf) => {
+                    Artifact::CatchUpPackage(protobuf.clone())
+                }
+                ArchivedArtifact::Certification(msg) => Artifact::Certification(msg.into()),
+                ArchivedArtifact::Ecdsa(msg) => Artifact::Ecdsa(msg.into()),
+            }),
+        }
+    }
+}
+
+impl TryFrom<ArchivedArtifactProto> for ArchivedArtifact {
+    type Error = String;
+    fn try_from(artifact: ArchivedArtifactProto) -> Result<Self, Self::Error> {
+        use archived_artifact_proto::Artifact;
+        Ok(
+            match artifact
+                .artifact
+                .ok_or_else(|| "empty artifact".to_string())?
+            {
+                Artifact::Consensus(artifact) => {
+                    ArchivedArtifact::Consensus(ValidatedConsensusArtifact {
+                        msg: artifact
+                            .msg
+                            .ok_or_else(|| "missing consensus message".to_string())?
+                            .try_into()?,
+                        timestamp: Time::from_nanos_since_unix_epoch(artifact.timestamp),
+                    })
+                }
+                Artifact::CatchUpPackage(protobuf) => ArchivedArtifact::CatchUpPackage(protobuf),
+                Artifact::Certification(msg) => ArchivedArtifact::Certification(msg.try_into()?),
+                Artifact::Ecdsa(msg) => ArchivedArtifact::Ecdsa(msg.try_into()?),
+            },
+        )
+    }
+}
+
+/// The number of artifacts exported or imported, by pool.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ArchiveStats {
+    pub consensus: usize,
+    pub certification: usize,
+    pub ecdsa: usize,
+    /// The number of artifacts that were not imported, because the target
+    /// pool has no persistent section for them.
+    pub skipped: usize,
+}
+
+impl fmt::Display for ArchiveStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} consensus, {} certification, {} ECDSA artifacts ({} skipped)",
+            self.consensus, self.certification, self.ecdsa, self.skipped
+        )
+    }
+}
+
+/// Writes an archive to the underlying writer.
+pub struct ArchiveWriter<W: Write> {
+    encoder: GzEncoder<W>,
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    pub fn new(mut writer: W, metadata: &ArchiveMetadata) -> Result<Self, ArchiveError> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&ARCHIVE_FORMAT_VERSION.to_le_bytes())?;
+        let mut archive = Self {
+            encoder: GzEncoder::new(writer, Compression::default()),
+        };
+        archive.write_record(&ArchiveMetadataProto::from(metadata))?;
+        Ok(archive)
+    }
+
+    pub fn write(&mut self, artifact: &ArchivedArtifact) -> Result<(), ArchiveError> {
+        self.write_record(&ArchivedArtifactProto::from(artifact))
+    }
+
+    /// Completes the archive and returns the underlying writer.
+    pub fn finish(self) -> Result<W, ArchiveError> {
+        Ok(self.encoder.finish()?)
+    }
+
+    fn write_record<T: Message>(&mut self, record: &T) -> Result<(), ArchiveError> {
+        let bytes = record.encode_to_vec();
+        self.encoder
+            .write_all(&(bytes.len() as u32).to_le_bytes())?;
+        self.encoder.write_all(&bytes)?;
+        Ok(())
+    }
+}
+
+/// Reads an archive, yielding its artifacts in the order they were written.
+pub struct ArchiveReader<R: Read> {
+    decoder: GzDecoder<R>,
+    metadata: ArchiveMetadata,
+    done: bool,
+}
+
+impl<R: Read> ArchiveReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, ArchiveError> {
+        let mut header = [0; 12];
+        reader
+            .read_exact(&mut header)
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::UnexpectedEof => ArchiveError::NotAnArchive,
+                _ => ArchiveError::Io(err),
+            })?;
+        if header[..8] != MAGIC[..] {
+            return Err(ArchiveError::NotAnArchive);
+        }
+        let version = u32::from_le_bytes(header[8..].try_into().unwrap());
+        if version != ARCHIVE_FORMAT_VERSION {
+            return Err(ArchiveError::UnsupportedVersion(version));
+        }
+
+        let mut decoder = GzDecoder::new(reader);
+        let metadata = read_record::<ArchiveMetadataProto>(&mut decoder)?
+            .ok_or_else(|| ArchiveError::Malformed("missing metadata".to_string()))?
+            .try_into()
+            .map_err(|err| ArchiveError::Malformed(format!("invalid metadata: {}", err)))?;
+        Ok(Self {
+            decoder,
+            metadata,
+            done: false,
+        })
+    }
+
+    pub fn metadata(&self) -> &ArchiveMetadata {
+        &self.metadata
+    }
+}
+
+impl<R: Read> Iterator for ArchiveReader<R> {
+    type Item = Result<ArchivedArtifact, ArchiveError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let record = read_record::<ArchivedArtifactProto>(&mut self.decoder)
+            .and_then(|record| {
+                record
+                    .map(ArchivedArtifact::try_from)
+                    .transpose()
+                    .map_err(|err| ArchiveError::Malformed(format!("invalid artifact: {}", err)))
+            })
+            .transpose();
+        self.done = !matches!(record, Some(Ok(_)));
+        record
+    }
+}
+
+/// Reads the next record, or returns `None` at the end of the archive.
+fn read_record<T: Message + Default>(reader: &mut impl Read) -> Result<Option<T>, ArchiveError> {
+    let mut len = [0; 4];
+    let mut read = 0;
+    while read < len.len() {
+        match reader.read(&mut len[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => return Err(ArchiveError::Malformed("truncated record".to_string())),
+            Ok(n) => read += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    // Don't trust the length for allocating the buffer, it may be corrupted.
+    let len = u32::from_le_bytes(len) as usize;
+    let mut bytes = Vec::new();
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(ArchiveError::Malformed("truncated record".to_string()));
+    }
+    T::decode(bytes.as_slice())
+        .map(Some)
+        .map_err(|err| ArchiveError::Malformed(format!("invalid record: {}", err)))
+}
+
+/// Exports the artifacts of the given pool sections within the height range of
+/// `metadata` to an archive written to `writer`.
+pub fn export_pool<W: Write>(
+    writer: W,
+    metadata: &ArchiveMetadata,
+    consensus: &dyn PoolSection<ValidatedConsensusArtifact>,
+    certification: &dyn CertificationPoolSection,
+    ecdsa: &dyn EcdsaPoolSection,
+) -> Result<(W, ArchiveStats), ArchiveError> {
+    let mut archive = ArchiveWriter::new(writer, metadata)?;
+    let mut stats = ArchiveStats::default();
+
+    let messages = consensus_messages(consensus.random_beacon(), metadata)
+        .chain(consensus_messages(consensus.random_tape(), metadata))
+        .chain(consensus_messages(consensus.block_proposal(), metadata))
+        .chain(consensus_messages(consensus.notarization(), metadata))
+        .chain(consensus_messages(consensus.finalization(), metadata))
+        .chain(consensus_messages(
+            consensus.random_beacon_share(),
+            metadata,
+        ))
+        .chain(consensus_messages(consensus.random_tape_share(), metadata))
+        .chain(consensus_messages(consensus.notarization_share(), metadata))
+        .chain(consensus_messages(consensus.finalization_share(), metadata))
+        .chain(consensus_messages(
+            consensus.catch_up_package_share(),
+            metadata,
+        ));
+    for msg in messages {
+        let timestamp = consensus
+            .get_timestamp(&msg.get_id())
+            .unwrap_or_else(current_time);
+        archive.write(&ArchivedArtifact::Consensus(ValidatedConsensusArtifact {
+            msg,
+            timestamp,
+        }))?;
+        stats.consensus += 1;
+    }
+
+    // Use the original bytes of the CUPs, which may differ from the
+    // re-encoding of the decoded CUPs.
+    let range = match metadata.height_range {
+        Some((min, max)) => HeightRange::new(min, max),
+        None => HeightRange::new(Height::from(0), Height::from(u64::MAX)),
+    };
+    for protobuf in consensus.catch_up_package_protos(range) {
+        archive.write(&ArchivedArtifact::CatchUpPackage(protobuf))?;
+        stats.consensus += 1;
+    }
+
+    let certifications = artifacts(certification.certifications(), metadata)
+        .map(CertificationMessage::Certification)
+        .chain(
+            artifacts(certification.certification_shares(), metadata)
+                .map(CertificationMessage::CertificationShare),
+        );
+    for msg in certifications {
+        archive.write(&ArchivedArtifact::Certification(msg))?;
+        stats.certification += 1;
+    }
+
+    let ecdsa_messages = ecdsa
+        .signed_dealings()
+        .map(|(_, x)| EcdsaMessage::EcdsaSignedDealing(x))
+        .chain(
+            ecdsa
+                .dealing_support()
+                .map(|(_, x)| EcdsaMessage::EcdsaDealingSupport(x)),
+        )
+        .chain(
+            ecdsa
+                .signature_shares()
+                .map(|(_, x)| EcdsaMessage::EcdsaSigShare(x)),
+        )
+        .chain(
+            ecdsa
+                .complaints()
+                .map(|(_, x)| EcdsaMessage::EcdsaComplaint(x)),
+        )
+        .chain(ecdsa.openings().map(|(_, x)| EcdsaMessage::EcdsaOpening(x)))
+        .filter(|msg| metadata.contains(ecdsa_message_height(msg)));
+    for msg in ecdsa_messages {
+        archive.write(&ArchivedArtifact::Ecdsa(msg))?;
+        stats.ecdsa += 1;
+    }
+
+    Ok((archive.finish()?, stats))
+}
+
+/// Imports all artifacts of the archive into the given pool sections. ECDSA
+/// artifacts are skipped if no ECDSA pool is given, e.g., because the backend
+/// of the target pool does not persist them.
+pub fn import_pool<R: Read>(
+    archive: ArchiveReader<R>,
+    consensus: &mut dyn InitializablePoolSection,
+    certification: &dyn CertificationPoolSection,
+    mut ecdsa: Option<&mut dyn MutableEcdsaPool>,
+) -> Result<ArchiveStats, ArchiveError> {
+    let mut stats = ArchiveStats::default();
+    let mut ops = PoolSectionOps::new();
+    let mut batch = 0;
+    for artifact in archive {
+        match artifact? {
+            ArchivedArtifact::Consensus(artifact) => {
+                ops.insert(artifact);
+                batch += 1;
+                if batch == IMPORT_BATCH_SIZE {
+                    consensus.mutate(std::mem::replace(&mut ops, PoolSectionOps::new()));
+                    batch = 0;
+                }
+                stats.consensus += 1;
+            }
+            ArchivedArtifact::CatchUpPackage(protobuf) => {
+                let cup = CatchUpPackage::try_from(&protobuf)
+                    .map_err(|err| ArchiveError::Malformed(format!("invalid CUP: {}", err)))?;
+                consensus.insert_cup_with_proto(CUPWithOriginalProtobuf { cup, protobuf });
+                stats.consensus += 1;
+            }
+            ArchivedArtifact::Certification(msg) => {
+                certification.insert(msg);
+                stats.certification += 1;
+            }
+            ArchivedArtifact::Ecdsa(msg) => match ecdsa.as_mut() {
+                Some(pool) => {
+                    pool.apply_changes(vec![EcdsaChangeAction::AddToValidated(msg)]);
+                    stats.ecdsa += 1;
+                }
+                None => stats.skipped += 1,
+            },
+        }
+    }
+    consensus.mutate(ops);
+    Ok(stats)
+}
+
+fn artifacts<'a, T: 'a>(
+    pool: &'a dyn HeightIndexedPool<T>,
+    metadata: &ArchiveMetadata,
+) -> Box<dyn Iterator<Item = T> + 'a> {
+    match metadata.height_range {
+        Some((min, max)) => pool.get_by_height_range(HeightRange::new(min, max)),
+        None => pool.get_all(),
+    }
+}
+
+fn consensus_messages<'a, T: ConsensusMessageHashable + 'a>(
+    pool: &'a dyn HeightIndexedPool<T>,
+    metadata: &ArchiveMetadata,
+) -> impl Iterator<Item = ConsensusMessage> + 'a {
+    artifacts(pool, metadata).map(|artifact| artifact.into_message())
+}
+
+/// Returns the height at which the protocol instance an ECDSA message belongs
+/// to was started.
+fn ecdsa_message_height(msg: &EcdsaMessage) -> Height {
+    match EcdsaMessageAttribute::from(msg) {
+        EcdsaMessageAttribute::EcdsaSignedDealing(transcript_id)
+        | EcdsaMessageAttribute::EcdsaDealingSupport(transcript_id)
+        | EcdsaMessageAttribute::EcdsaComplaint(transcript_id)
+        | EcdsaMessageAttribute::EcdsaOpening(transcript_id) => transcript_id.source_height(),
+        EcdsaMessageAttribute::EcdsaSigShare(request_id) => request_id.height,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        certification_pool::CertificationPoolImpl,
+        consensus_pool::UncachedConsensusPoolImpl,
+        ecdsa_pool::EcdsaPoolImpl,
+        test_utils::{fake_random_beacon, make_summary, random_beacon_ops},
+    };
+    use ic_config::artifact_pool::ArtifactPoolConfig;
+    use ic_interfaces::{consensus_pool::ConsensusPool, ecdsa::EcdsaPool};
+    use ic_logger::replica_logger::no_op_logger;
+    use ic_metrics::MetricsRegistry;
+    use ic_test_utilities::{
+        consensus::{fake::*, make_genesis},
+        crypto::{dummy_idkg_dealing_for_tests, dummy_idkg_transcript_id_for_tests},
+        mock_time,
+        types::ids::node_test_id,
+    };
+    use ic_types::{
+        consensus::{
+            certification::{Certification, CertificationContent, CertificationShare},
+            ecdsa::{
+                EcdsaComplaintContent, EcdsaOpeningContent, EcdsaSigShare, QuadrupleId, RequestId,
+            },
+            HasHeight, NotarizationContent, NotarizationShare, RandomBeacon, RandomTapeContent,
+            RandomTapeShare,
+        },
+        crypto::{
+            canister_threshold_sig::{
+                idkg::{IDkgComplaint, IDkgDealingSupport, IDkgOpening, SignedIDkgDealing},
+                ThresholdEcdsaSigShare,
+            },
+            CryptoHash, CryptoHashOf, Signed, ThresholdSigShare, ThresholdSigShareOf,
+        },
+        signature::{
+            BasicSignature, MultiSignatureShare, ThresholdSignature, ThresholdSignatureShare,
+        },
+        CryptoHashOfPartialState,
+    };
+
+    struct Pools {
+        consensus: UncachedConsensusPoolImpl,
+        certification: CertificationPoolImpl,
+        ecdsa: EcdsaPoolImpl,
+    }
+
+    fn open_pools(config: ArtifactPoolConfig) -> Pools {
+        Pools {
+            consensus: UncachedConsensusPoolImpl::new(config.clone(), no_op_logger()),
+            certification: CertificationPoolImpl::new(
+                config.clone(),
+                no_op_logger(),
+                MetricsRegistry::new(),
+            ),
+            ecdsa: EcdsaPoolImpl::new(config, no_op_logger(), MetricsRegistry::new()),
+        }
+    }
+
+    fn certification_messages(heights: std::ops::Range<u64>) -> Vec<CertificationMessage> {
+        let content =
+            CertificationContent::new(CryptoHashOfPartialState::from(CryptoHash(Vec::new())));
+        heights
+            .flat_map(|height| {
+                vec![
+                    CertificationMessage::Certification(Certification {
+                        height: Height::from(height),
+                        signed: Signed {
+                            content: content.clone(),
+                            signature: ThresholdSignature::fake(),
+                        },
+                    }),
+                    CertificationMessage::CertificationShare(CertificationShare {
+                        height: Height::from(height),
+                        signed: Signed {
+                            content: content.clone(),
+                            signature: ThresholdSignatureShare::fake(node_test_id(1)),
+                        },
+                    }),
+                ]
+            })
+            .collect()
+    }
+
+    fn populate(pools: &mut Pools) {
+        pools.consensus.validated.mutate(random_beacon_ops());
+        for msg in certification_messages(3..19) {
+            pools.certification.persistent_pool.insert(msg);
+        }
+    }
+
+    fn export(pools: &Pools, height_range: Option<(u64, u64)>) -> (Vec<u8>, ArchiveStats) {
+        let metadata = ArchiveMetadata {
+            replica_version: Some(ReplicaVersion::default()),
+            height_range: height_range.map(|(min, max)| (Height::from(min), Height::from(max))),
+        };
+        export_pool(
+            Vec::new(),
+            &metadata,
+            pools.consensus.validated(),
+            pools.certification.persistent_pool.as_ref(),
+            pools.ecdsa.validated(),
+        )
+        .unwrap()
+    }
+
+    fn random_beacons(pools: &Pools) -> Vec<RandomBeacon> {
+        pools
+            .consensus
+            .validated
+            .random_beacon()
+            .get_all()
+            .collect()
+    }
+
+    #[test]
+    fn archive_roundtrip() {
+        ic_test_utilities::artifact_pool_config::with_test_pool_configs(2, |configs| {
+            let mut source = open_pools(configs[0].clone());
+            populate(&mut source);
+            let (archive, stats) = export(&source, None);
+            assert_eq!(stats.consensus, 16);
+            assert_eq!(stats.certification, 32);
+
+            let reader = ArchiveReader::new(archive.as_slice()).unwrap();
+            assert_eq!(
+                reader.metadata().replica_version,
+                Some(ReplicaVersion::default())
+            );
+            let mut target = open_pools(configs[1].clone());
+            let imported = import_pool(
+                reader,
+                target.consensus.validated.as_mut(),
+                target.certification.persistent_pool.as_ref(),
+                Some(&mut target.ecdsa as &mut dyn MutableEcdsaPool),
+            )
+            .unwrap();
+            assert_eq!(imported, stats);
+
+            assert_eq!(random_beacons(&target), random_beacons(&source));
+            let beacon = fake_random_beacon(Height::from(5));
+            assert_eq!(
+                target.consensus.validated.get_timestamp(&beacon.get_id()),
+                Some(mock_time())
+            );
+            assert_eq!(
+                target
+                    .certification
+                    .persistent_pool
+                    .certification_shares()
+                    .get_all()
+                    .count(),
+                16
+            );
+        })
+    }
+
+    #[test]
+    fn export_respects_height_range() {
+        ic_test_utilities::artifact_pool_config::with_test_pool_config(|config| {
+            let mut pools = open_pools(config);
+            populate(&mut pools);
+            let (archive, stats) = export(&pools, Some((5, 7)));
+            assert_eq!(stats.consensus, 3);
+            assert_eq!(stats.certification, 6);
+
+            let heights: Vec<_> = ArchiveReader::new(archive.as_slice())
+                .unwrap()
+                .map(|artifact| match artifact.unwrap() {
+                    ArchivedArtifact::Consensus(artifact) => artifact.msg.height(),
+                    ArchivedArtifact::Certification(msg) => msg.height(),
+                    other => panic!("Unexpected artifact {:?}", other),
+                })
+                .collect();
+            assert_eq!(heights.len(), 9);
+            assert!(heights
+                .iter()
+                .all(|h| Height::from(5) <= *h && *h <= Height::from(7)));
+        })
+    }
+
+    #[test]
+    fn invalid_archives_are_rejected() {
+        let archive = ArchiveWriter::new(Vec::new(), &ArchiveMetadata::default())
+            .and_then(|mut writer| {
+                writer.write(&ArchivedArtifact::CatchUpPackage(
+                    pb::CatchUpPackage::default(),
+                ))?;
+                writer.finish()
+            })
+            .unwrap();
+        let reader = ArchiveReader::new(archive.as_slice()).unwrap();
+        assert_eq!(reader.count(), 1);
+
+        assert!(matches!(
+            ArchiveReader::new(&b"ICPOOL"[..]),
+            Err(ArchiveError::NotAnArchive)
+        ));
+        assert!(matches!(
+            ArchiveReader::new(&b"not an archive at all"[..]),
+            Err(ArchiveError::NotAnArchive)
+        ));
+
+        // Archives of the first version used bincode for the records.
+        let mut other_version = archive.clone();
+        other_version[8..12].copy_from_slice(&1u32.to_le_bytes());
+        assert!(matches!(
+            ArchiveReader::new(other_version.as_slice()),
+            Err(ArchiveError::UnsupportedVersion(1))
+        ));
+
+        // A record that is not a valid artifact is rejected.
+        let archive = ArchiveWriter::new(Vec::new(), &ArchiveMetadata::default())
+            .and_then(|mut writer| {
+                writer.write_record(&ArchivedArtifactProto { artifact: None })?;
+                writer.finish()
+            })
+            .unwrap();
+        let result: Result<Vec<_>, _> = ArchiveReader::new(archive.as_slice()).unwrap().collect();
+        assert!(matches!(result, Err(ArchiveError::Malformed(_))));
+
+        // A truncated archive yields an error after the complete records.
+        let truncated = &archive[..archive.len() - 10];
+        let result: Result<Vec<_>, _> =
+            ArchiveReader::new(truncated).and_then(|reader| reader.collect::<Result<Vec<_>, _>>());
+        assert!(result.is_err());
+    }
+
+    fn artifacts_of_all_kinds() -> Vec<ArchivedArtifact> {
+        let height = Height::from(5);
+        let block = CryptoHashOf::new(CryptoHash(vec![1, 2, 3]));
+        let transcript_id = dummy_idkg_transcript_id_for_tests(7);
+        let consensus = vec![
+            ConsensusMessage::RandomBeacon(fake_random_beacon(height)),
+            ConsensusMessage::NotarizationShare(NotarizationShare {
+                content: NotarizationContent::new(height, block),
+                signature: MultiSignatureShare::fake(node_test_id(1)),
+            }),
+            ConsensusMessage::RandomTapeShare(RandomTapeShare {
+                content: RandomTapeContent::new(height),
+                signature: ThresholdSignatureShare {
+                    signature: ThresholdSigShareOf::new(ThresholdSigShare(vec![4, 5])),
+                    signer: node_test_id(2),
+                },
+            }),
+        ];
+        let ecdsa = vec![
+            EcdsaMessage::EcdsaSignedDealing(SignedIDkgDealing {
+                content: dummy_idkg_dealing_for_tests(),
+                signature: BasicSignature::fake(node_test_id(1)),
+            }),
+            EcdsaMessage::EcdsaDealingSupport(IDkgDealingSupport {
+                transcript_id,
+                dealer_id: node_test_id(1),
+                dealing_hash: CryptoHashOf::new(CryptoHash(vec![6])),
+                sig_share: BasicSignature::fake(node_test_id(2)),
+            }),
+            EcdsaMessage::EcdsaSigShare(EcdsaSigShare {
+                signer_id: node_test_id(3),
+                request_id: RequestId {
+                    quadruple_id: QuadrupleId(1),
+                    pseudo_random_id: [7; 32],
+                    height,
+                },
+                share: ThresholdEcdsaSigShare {
+                    sig_share_raw: vec![8],
+                },
+            }),
+            EcdsaMessage::EcdsaComplaint(Signed {
+                content: EcdsaComplaintContent {
+                    idkg_complaint: IDkgComplaint {
+                        transcript_id,
+                        dealer_id: node_test_id(1),
+                        internal_complaint_raw: vec![9],
+                    },
+                },
+                signature: BasicSignature::fake(node_test_id(2)),
+            }),
+            EcdsaMessage::EcdsaOpening(Signed {
+                content: EcdsaOpeningContent {
+                    complainer_id: node_test_id(2),
+                    idkg_opening: IDkgOpening {
+                        transcript_id,
+                        dealer_id: node_test_id(1),
+                        internal_opening_raw: vec![10],
+                    },
+                },
+                signature: BasicSignature::fake(node_test_id(3)),
+            }),
+        ];
+        consensus
+            .into_iter()
+            .map(|msg| {
+                ArchivedArtifact::Consensus(ValidatedConsensusArtifact {
+                    msg,
+                    timestamp: mock_time(),
+                })
+            })
+            .chain(
+                certification_messages(5..6)
+                    .into_iter()
+                    .map(ArchivedArtifact::Certification),
+            )
+            .chain(ecdsa.into_iter().map(ArchivedArtifact::Ecdsa))
+            .collect()
+    }
+
+    #[test]
+    fn artifacts_of_all_kinds_roundtrip() {
+        let artifacts = artifacts_of_all_kinds();
+        let mut writer = ArchiveWriter::new(Vec::new(), &ArchiveMetadata::default()).unwrap();
+        for artifact in &artifacts {
+            writer.write(artifact).unwrap();
+        }
+        let archive = writer.finish().unwrap();
+
+        let read: Vec<_> = ArchiveReader::new(archive.as_slice())
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(read, artifacts);
+    }
+
+    #[test]
+    fn original_bytes_of_all_cups_are_exported() {
+        ic_test_utilities::artifact_pool_config::with_test_pool_configs(2, |configs| {
+            let source = open_pools(configs[0].clone());
+            // Append an unknown field to the signed content of each CUP, so
+            // that the original bytes differ from the re-encoding of the CUP.
+            let protobufs: Vec<_> = [0, 10]
+                .iter()
+                .map(|height| {
+                    let cup = make_genesis(make_summary(Height::from(*height)));
+                    let mut protobuf = pb::CatchUpPackage::from(&cup);
+                    protobuf.content.extend_from_slice(&[0x78, 0x01]);
+                    source
+                        .consensus
+                        .validated
+                        .insert_cup_with_proto(CUPWithOriginalProtobuf {
+                            cup,
+                            protobuf: protobuf.clone(),
+                        });
+                    protobuf
+                })
+                .collect();
+
+            let (archive, stats) = export(&source, None);
+            assert_eq!(stats.consensus, 2);
+            let exported: Vec<_> = ArchiveReader::new(archive.as_slice())
+                .unwrap()
+                .map(|artifact| match artifact.unwrap() {
+                    ArchivedArtifact::CatchUpPackage(protobuf) => protobuf,
+                    other => panic!("Unexpected artifact {:?}", other),
+                })
+                .collect();
+            assert_eq!(exported, protobufs);
+
+            let mut target = open_pools(configs[1].clone());
+            import_pool(
+                ArchiveReader::new(archive.as_slice()).unwrap(),
+                target.consensus.validated.as_mut(),
+                target.certification.persistent_pool.as_ref(),
+                None,
+            )
+            .unwrap();
+            let imported: Vec<_> = target
+                .consensus
+                .validated
+                .catch_up_package_protos(HeightRange::new(Height::from(0), Height::from(10)))
+                .collect();
+            assert_eq!(imported, protobufs);
+        })
+    }
+
+    #[cfg(feature = "rocksdb_backend")]
+    #[test]
+    fn pool_can_be_migrated_from_lmdb_to_rocksdb() {
+        use ic_config::artifact_pool::ArtifactPoolTomlConfig;
+
+        ic_test_utilities::artifact_pool_config::with_test_pool_config(|lmdb_config| {
+            let tempdir = tempfile::Builder::new()
+                .prefix("persistent-pool")
+                .tempdir()
+                .unwrap();
+            let mut toml_config = ArtifactPoolTomlConfig::new(tempdir.path().to_path_buf(), None);
+            toml_config.consensus_pool_backend = Some("rocksdb".to_string());
+            let rocksdb_config = ArtifactPoolConfig::from(toml_config);
+
+            let mut source = open_pools(lmdb_config);
+            populate(&mut source);
+            let (archive, stats) = export(&source, None);
+
+            let mut target = open_pools(rocksdb_config);
+            let imported = import_pool(
+                ArchiveReader::new(archive.as_slice()).unwrap(),
+                target.consensus.validated.as_mut(),
+                target.certification.persistent_pool.as_ref(),
+                None,
+            )
+            .unwrap();
+            assert_eq!(imported, stats);
+            assert_eq!(random_beacons(&target), random_beacons(&source));
+        })
+    }
+}
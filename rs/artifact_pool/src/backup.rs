@@ -13,7 +13,18 @@
 //! and since we backup all artifacts instantly after the pool update, there is
 //! no possibility to inject purging (or any other deletion) of artifacts
 //! between the pool update and the backup.
+//!
+//! Artifacts are first written as individual files, grouped by height (see
+//! [`BackupArtifact::file_location`]). Once the backup has moved on to the next
+//! group of heights, the purging thread bundles the files of the completed
+//! group into a single compressed bundle, accompanied by an index file.
+//! Readers should use [`list_artifacts`], which transparently returns artifacts
+//! from both layouts.
+//!
+//! Besides purging artifacts by age, the backup can be given a disk budget, in
+//! which case the oldest artifacts are purged until the backup fits into it.
 
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use ic_config::artifact_pool::BACKUP_GROUP_SIZE;
 use ic_interfaces::{
     consensus_pool::{ConsensusPool, HeightRange},
@@ -34,8 +45,9 @@ use ic_types::{
 use prometheus::IntCounter;
 use prost::Message;
 use std::{
-    fs,
-    io::{self, Write},
+    collections::{BTreeMap, HashMap},
+    fmt, fs,
+    io::{self, BufRead, Read, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
     sync::{
         mpsc::{sync_channel, Receiver, SyncSender},
@@ -140,8 +152,13 @@ enum PurgingRequest {
 struct PurgingThread {
     // Path containing all backups of all versions running on the current node.
     backup_path: PathBuf,
+    // Path containing the backup of the current replica version, whose completed
+    // groups of heights get bundled.
+    version_path: PathBuf,
     // The maximum age backup artifacts can reach before purging.
     age_threshold_secs: Duration,
+    // The maximum number of bytes the backup may occupy on disk.
+    max_size_bytes: Option<u64>,
     metrics: Metrics,
     log: ReplicaLogger,
     age: Box<dyn BackupAge>,
@@ -174,14 +191,18 @@ impl BackupAge for FileSystemAge {
 impl PurgingThread {
     fn new(
         backup_path: PathBuf,
+        version_path: PathBuf,
         age_threshold_secs: Duration,
+        max_size_bytes: Option<u64>,
         metrics: Metrics,
         log: ReplicaLogger,
         age: Box<dyn BackupAge>,
     ) -> Self {
         Self {
             backup_path,
+            version_path,
             age_threshold_secs,
+            max_size_bytes,
             metrics,
             log,
             age,
@@ -202,6 +223,10 @@ impl PurgingThread {
             match rx.recv() {
                 Ok(PurgingRequest::Purge) => {
                     let start = std::time::Instant::now();
+                    if let Err(err) = bundle_completed_groups(&self.version_path) {
+                        error!(self.log, "Backup bundling failed: {:?}", err);
+                        self.metrics.io_errors.inc();
+                    }
                     if let Err(err) = purge(
                         self.age_threshold_secs,
                         &self.backup_path,
@@ -211,6 +236,17 @@ impl PurgingThread {
                         error!(self.log, "Backup purging failed: {:?}", err);
                         self.metrics.io_errors.inc();
                     }
+                    if let Some(max_size_bytes) = self.max_size_bytes {
+                        if let Err(err) = purge_to_size(
+                            max_size_bytes,
+                            &self.backup_path,
+                            self.log.clone(),
+                            self.age.as_ref(),
+                        ) {
+                            error!(self.log, "Backup purging by size failed: {:?}", err);
+                            self.metrics.io_errors.inc();
+                        }
+                    }
                     info!(self.log, "Backup purging finished in {:?}", start.elapsed());
                 }
                 Ok(PurgingRequest::Await(tx)) => tx.send(()).unwrap(),
@@ -251,6 +287,7 @@ impl Backup {
         version_path: PathBuf,
         age_threshold_secs: Duration,
         purge_interval_secs: Duration,
+        max_size_bytes: Option<u64>,
         metrics_registry: MetricsRegistry,
         log: ReplicaLogger,
         age: Box<dyn BackupAge>,
@@ -260,7 +297,9 @@ impl Backup {
             BackupThread::new(version_path.clone(), metrics.clone(), log.clone()).start();
         let (purging_queue, purging_thread) = PurgingThread::new(
             backup_path,
+            version_path.clone(),
             age_threshold_secs,
+            max_size_bytes,
             metrics.clone(),
             log.clone(),
             age,
@@ -298,6 +337,7 @@ impl Backup {
         version_path: PathBuf,
         age_threshold_secs: Duration,
         purge_interval_secs: Duration,
+        max_size_bytes: Option<u64>,
         metrics_registry: MetricsRegistry,
         log: ReplicaLogger,
     ) -> Self {
@@ -307,6 +347,7 @@ impl Backup {
             version_path,
             age_threshold_secs,
             purge_interval_secs,
+            max_size_bytes,
             metrics_registry,
             log,
             Box::new(FileSystemAge {}),
@@ -402,58 +443,212 @@ fn store_artifacts(artifacts: Vec<ConsensusMessage>, path: &Path) -> Result<(),
         .try_for_each(|artifact| artifact.write_to_disk(path))
 }
 
+/// The extension of bundles. A bundle contains the protobuf serializations of
+/// the artifacts of a range of heights, each compressed separately with gzip.
+const BUNDLE_EXTENSION: &str = "bundle";
+
+/// The extension of the index file accompanying each bundle.
+const INDEX_EXTENSION: &str = "index";
+
+/// The first line of every bundle index, identifying its format.
+const INDEX_HEADER: &str = "ic-consensus-backup-index v1";
+
+/// A leaf directory or a bundle of the backup directory, which is purged as a
+/// whole.
+struct BackupUnit {
+    path: PathBuf,
+    is_bundle: bool,
+    // The (first) height of the artifacts in the unit.
+    height: Option<Height>,
+    size: u64,
+    age: Duration,
+}
+
+/// Collects all leaves and bundles of the backup directory, together with
+/// their sizes and ages.
+///
+/// A bundle is created after the heights it contains were backed up, so its
+/// own age can be lower than the age of later heights. Hence, within the backup
+/// of a replica version, every leaf or bundle is considered to be at least as
+/// old as all leaves and bundles containing later heights. Units whose own age
+/// couldn't be determined due to a transient error are considered new.
+fn get_backup_units(
+    path: &Path,
+    log: &ReplicaLogger,
+    age: &dyn BackupAge,
+) -> Result<Vec<BackupUnit>, io::Error> {
+    let mut leaves = Vec::new();
+    let mut bundles = Vec::new();
+    get_leaves(path, &mut leaves, &mut bundles)?;
+    // The units, together with the version directory they belong to.
+    let mut units = Vec::new();
+    for path in leaves {
+        let age = get_age(age, &path, log)?.unwrap_or_default();
+        let size = fs::read_dir(&path)?
+            .map(|entry| Ok(entry?.metadata()?.len()))
+            .sum::<Result<u64, io::Error>>()?;
+        let version_path = path.parent().and_then(Path::parent).map(Path::to_path_buf);
+        units.push((
+            version_path,
+            BackupUnit {
+                height: file_name_to_u64(&path).map(Height::from),
+                size,
+                age,
+                is_bundle: false,
+                path,
+            },
+        ));
+    }
+    for path in bundles {
+        let age = get_age(age, &path, log)?.unwrap_or_default();
+        let index_size = fs::metadata(path.with_extension(INDEX_EXTENSION))
+            .map(|metadata| metadata.len())
+            .unwrap_or_default();
+        let version_path = path.parent().map(Path::to_path_buf);
+        units.push((
+            version_path,
+            BackupUnit {
+                height: bundle_range(&path).map(|(first, _)| first),
+                size: fs::metadata(&path)?.len() + index_size,
+                age,
+                is_bundle: true,
+                path,
+            },
+        ));
+    }
+
+    // Propagate the ages from later to earlier heights of each version.
+    let mut versions: HashMap<PathBuf, Vec<usize>> = HashMap::new();
+    for (i, (version_path, unit)) in units.iter().enumerate() {
+        if let (Some(version_path), Some(_)) = (version_path, unit.height) {
+            versions.entry(version_path.clone()).or_default().push(i);
+        }
+    }
+    for indices in versions.values_mut() {
+        indices.sort_by_key(|i| std::cmp::Reverse(units[*i].1.height));
+        let mut max_age = Duration::ZERO;
+        for i in indices.iter() {
+            max_age = max_age.max(units[*i].1.age);
+            units[*i].1.age = max_age;
+        }
+    }
+    Ok(units.into_iter().map(|(_, unit)| unit).collect())
+}
+
+// Removes the given leaf directory or bundle.
+fn remove_backup_unit(unit: &BackupUnit) -> Result<(), io::Error> {
+    if unit.is_bundle {
+        remove_bundle(&unit.path)
+    } else {
+        fs::remove_dir_all(&unit.path)
+    }
+}
+
 /// Traverses the whole backup directory and finds all leaf directories
-/// (containing no other directories). Then it purges all leaves older than the
-/// specified retention time. Age of a leave is determined by calling the given
-/// implementation of [`BackupAge`]
+/// (containing no other directories or bundles) and all bundles. Then it purges
+/// all leaves and bundles older than the specified retention time. Age of a
+/// leaf or a bundle is determined by calling the given implementation of
+/// [`BackupAge`], and propagated from later heights as described in
+/// [`get_backup_units`].
 fn purge(
     threshold_secs: Duration,
     path: &Path,
     log: ReplicaLogger,
     age: &dyn BackupAge,
 ) -> Result<(), io::Error> {
-    let mut leaves = Vec::new();
-    get_leaves(path, &mut leaves)?;
-    for path in leaves {
-        let age = match age.get_elapsed_time(&path) {
-            Ok(time) => time,
-            // According to the documentation of `elapsed` this function may fail as
-            // "the underlying system clock is susceptible to drift and updates". Those
-            // errors are transient and safe to ignore. As they are very rare it's ok to
-            // log a warning.
-            Err(PurgingError::Transient(err)) => {
-                warn!(
-                    log,
-                    "Skipping {:?}, because the modified timestamp couldn't be computed: {:?}",
-                    &path,
-                    err
-                );
-                continue;
-            }
+    for unit in get_backup_units(path, &log, age)? {
+        if unit.age > threshold_secs {
+            remove_backup_unit(&unit)?;
+        }
+    }
+    Ok(())
+}
 
-            Err(PurgingError::Permanent(err)) => return Err(err),
-        };
-        if age > threshold_secs {
-            fs::remove_dir_all(path)?;
+// Returns the age of the given path, or `None` if it couldn't be determined due
+// to a transient error.
+fn get_age(
+    age: &dyn BackupAge,
+    path: &Path,
+    log: &ReplicaLogger,
+) -> Result<Option<Duration>, io::Error> {
+    match age.get_elapsed_time(path) {
+        Ok(time) => Ok(Some(time)),
+        // According to the documentation of `elapsed` this function may fail as
+        // "the underlying system clock is susceptible to drift and updates". Those
+        // errors are transient and safe to ignore. As they are very rare it's ok to
+        // log a warning.
+        Err(PurgingError::Transient(err)) => {
+            warn!(
+                log,
+                "Skipping {:?}, because the modified timestamp couldn't be computed: {:?}",
+                path,
+                err
+            );
+            Ok(None)
+        }
+        Err(PurgingError::Permanent(err)) => Err(err),
+    }
+}
+
+/// Purges the oldest leaf directories and bundles of the backup directory
+/// until the backup occupies at most `max_size_bytes` bytes. The ages are
+/// determined as described in [`get_backup_units`].
+fn purge_to_size(
+    max_size_bytes: u64,
+    path: &Path,
+    log: ReplicaLogger,
+    age: &dyn BackupAge,
+) -> Result<(), io::Error> {
+    let mut units = get_backup_units(path, &log, age)?;
+    let mut size: u64 = units.iter().map(|unit| unit.size).sum();
+    if size <= max_size_bytes {
+        return Ok(());
+    }
+    units.sort_by(|a, b| b.age.cmp(&a.age).then(a.height.cmp(&b.height)));
+
+    let mut purged = 0;
+    for unit in units {
+        if size <= max_size_bytes {
+            break;
+        }
+        if unit.size == 0 {
+            continue;
         }
+        remove_backup_unit(&unit)?;
+        size -= unit.size;
+        purged += 1;
     }
+    info!(
+        log,
+        "Purged {} backup directories and bundles to stay within the budget of {} bytes",
+        purged,
+        max_size_bytes
+    );
     Ok(())
 }
 
-// Traverses the given path and returns a list of all leaf directories.
-fn get_leaves(dir: &Path, leaves: &mut Vec<PathBuf>) -> std::io::Result<()> {
+// Traverses the given path and collects all leaf directories, i.e. directories
+// containing neither other directories nor bundles, and all bundles.
+fn get_leaves(
+    dir: &Path,
+    leaves: &mut Vec<PathBuf>,
+    bundles: &mut Vec<PathBuf>,
+) -> std::io::Result<()> {
     if !dir.is_dir() {
         return Ok(());
     }
-    let mut sub_directory_found = false;
+    let mut is_leaf = true;
     for entry in fs::read_dir(dir)? {
         let path = entry?.path();
         if path.is_dir() {
-            sub_directory_found = true;
-            get_leaves(&path, leaves)?;
+            is_leaf = false;
+            get_leaves(&path, leaves, bundles)?;
+        } else if is_bundle(&path) {
+            is_leaf = false;
+            bundles.push(path);
         }
     }
-    if !sub_directory_found {
+    if is_leaf {
         if let Some(path_name) = dir.to_str() {
             // We skip the folder lost+found, which is currently present on the backup
             // volume.
@@ -465,6 +660,342 @@ fn get_leaves(dir: &Path, leaves: &mut Vec<PathBuf>) -> std::io::Result<()> {
     Ok(())
 }
 
+/// Bundles the artifacts of all groups of heights in `version_path`, except
+/// for the latest group, which is still being backed up.
+///
+/// The artifacts of a group are written to a bundle named
+/// `<first height>_<last height>.bundle`, in which each artifact is compressed
+/// separately, so that it can be read without decompressing the whole bundle.
+/// The accompanying index `<first height>_<last height>.index` starts with
+/// [`INDEX_HEADER`], followed by one line per artifact, consisting of its
+/// height, its file name, and the offset and length of its compressed bytes in
+/// the bundle, separated by spaces.
+///
+/// Bundles are never modified. Artifacts of a completed group that are backed
+/// up again, e.g. after a restart, are dropped if a bundle already contains
+/// them, and written to an additional bundle otherwise. Files are only deleted
+/// after their bundle and its index have been written, so an interrupted
+/// bundling never makes artifacts unreadable.
+pub(crate) fn bundle_completed_groups(version_path: &Path) -> Result<(), io::Error> {
+    if !version_path.is_dir() {
+        return Ok(());
+    }
+    let mut groups = BTreeMap::new();
+    for entry in fs::read_dir(version_path)? {
+        let path = entry?.path();
+        if let Some(group) = file_name_to_u64(&path).filter(|_| path.is_dir()) {
+            groups.insert(group, path);
+        }
+    }
+    let latest_group = match groups.keys().next_back() {
+        Some(group) => *group,
+        None => return Ok(()),
+    };
+    for (group, group_path) in groups.range(..latest_group) {
+        bundle_group(version_path, *group, group_path)?;
+    }
+    Ok(())
+}
+
+// Bundles all files of the given group directory, which are not yet contained
+// in a bundle, and deletes them.
+fn bundle_group(version_path: &Path, group: u64, group_path: &Path) -> Result<(), io::Error> {
+    let range = HeightRange::new(
+        Height::from(group),
+        Height::from(group + BACKUP_GROUP_SIZE - 1),
+    );
+    let mut bundled = BTreeMap::new();
+    add_bundled_artifacts(version_path, &range, &mut bundled)?;
+
+    let mut height_paths = Vec::new();
+    let mut files = Vec::new();
+    for entry in fs::read_dir(group_path)? {
+        let height_path = entry?.path();
+        let height = match file_name_to_u64(&height_path) {
+            Some(height) if height_path.is_dir() => Height::from(height),
+            _ => continue,
+        };
+        for entry in fs::read_dir(&height_path)? {
+            let path = entry?.path();
+            if let Some(name) = artifact_file_name(&path) {
+                files.push((height, name, path));
+            }
+        }
+        height_paths.push(height_path);
+    }
+
+    let new_files: Vec<_> = files
+        .iter()
+        .filter(|(height, name, _)| {
+            !bundled
+                .get(height)
+                .map_or(false, |artifacts| artifacts.contains_key(name))
+        })
+        .collect();
+    if !new_files.is_empty() {
+        write_bundle(version_path, &new_files)?;
+    }
+
+    // We only delete the files we have bundled, as further artifacts of the group
+    // might have been backed up in the meantime. Directories that are not empty
+    // are therefore kept and bundled in the next round.
+    for (_, _, path) in &files {
+        fs::remove_file(path)?;
+    }
+    for path in height_paths {
+        let _ = fs::remove_dir(path);
+    }
+    let _ = fs::remove_dir(group_path);
+    Ok(())
+}
+
+// Writes the given files into a new bundle and its index.
+fn write_bundle(
+    version_path: &Path,
+    files: &[&(Height, String, PathBuf)],
+) -> Result<(), io::Error> {
+    let first = files.iter().map(|(height, _, _)| *height).min().unwrap();
+    let last = files.iter().map(|(height, _, _)| *height).max().unwrap();
+    // The range of an additional bundle of a group can coincide with the range of
+    // an existing one, in which case we add a counter to its name.
+    let stem = format!("{}_{}", first, last);
+    let mut bundle_path = version_path.join(format!("{}.{}", stem, BUNDLE_EXTENSION));
+    let mut counter = 1;
+    while bundle_path.exists() || bundle_path.with_extension(INDEX_EXTENSION).exists() {
+        bundle_path = version_path.join(format!("{}_{}.{}", stem, counter, BUNDLE_EXTENSION));
+        counter += 1;
+    }
+    let index_path = bundle_path.with_extension(INDEX_EXTENSION);
+    // Remove temporary files left behind by an interrupted bundling.
+    for path in [&bundle_path, &index_path].iter() {
+        let _ = fs::remove_file(ic_utils::fs::get_tmp_for_path(path));
+    }
+
+    let mut index = format!("{}\n", INDEX_HEADER);
+    ic_utils::fs::write_using_tmp_file(&bundle_path, |writer| {
+        let mut offset = 0;
+        for (height, name, path) in files {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&fs::read(path)?)?;
+            let compressed = encoder.finish()?;
+            writer.write_all(&compressed)?;
+            index.push_str(&format!(
+                "{} {} {} {}\n",
+                height,
+                name,
+                offset,
+                compressed.len()
+            ));
+            offset += compressed.len() as u64;
+        }
+        Ok(())
+    })?;
+    ic_utils::fs::write_using_tmp_file(&index_path, |writer| writer.write_all(index.as_bytes()))
+}
+
+// Removes the given bundle and its index. The index is removed first, so that
+// readers never find an index without its bundle.
+fn remove_bundle(path: &Path) -> Result<(), io::Error> {
+    if let Err(err) = fs::remove_file(path.with_extension(INDEX_EXTENSION)) {
+        if err.kind() != io::ErrorKind::NotFound {
+            return Err(err);
+        }
+    }
+    fs::remove_file(path)
+}
+
+fn is_bundle(path: &Path) -> bool {
+    path.is_file() && path.extension().and_then(|ext| ext.to_str()) == Some(BUNDLE_EXTENSION)
+}
+
+// Returns the range of heights contained in the given bundle, parsed from its
+// name.
+fn bundle_range(path: &Path) -> Option<(Height, Height)> {
+    let stem = path.file_stem()?.to_str()?;
+    let mut parts = stem.split('_');
+    let first = parts.next()?.parse().ok()?;
+    let last = parts.next()?.parse().ok()?;
+    Some((Height::from(first), Height::from(last)))
+}
+
+// Returns the file name of the given path, if it is an artifact file.
+fn artifact_file_name(path: &Path) -> Option<String> {
+    if !path.is_file() || path.extension()?.to_str()? != "bin" {
+        return None;
+    }
+    Some(path.file_name()?.to_str()?.to_string())
+}
+
+fn file_name_to_u64(path: &Path) -> Option<u64> {
+    path.file_name()?.to_str()?.parse().ok()
+}
+
+/// The location of a backed up artifact.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ArtifactLocation {
+    /// A file containing the protobuf serialization of the artifact.
+    File(PathBuf),
+    /// The compressed protobuf serialization of the artifact, stored in a
+    /// bundle at the given offset.
+    Bundle {
+        path: PathBuf,
+        offset: u64,
+        length: u64,
+    },
+}
+
+impl ArtifactLocation {
+    /// Reads the protobuf serialization of the artifact.
+    pub fn read(&self) -> Result<Vec<u8>, io::Error> {
+        let mut buffer = Vec::new();
+        match self {
+            ArtifactLocation::File(path) => {
+                fs::File::open(path)?.read_to_end(&mut buffer)?;
+            }
+            ArtifactLocation::Bundle {
+                path,
+                offset,
+                length,
+            } => {
+                let mut file = fs::File::open(path)?;
+                file.seek(SeekFrom::Start(*offset))?;
+                GzDecoder::new(file.take(*length)).read_to_end(&mut buffer)?;
+            }
+        }
+        Ok(buffer)
+    }
+}
+
+impl fmt::Display for ArtifactLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArtifactLocation::File(path) => write!(f, "{}", path.display()),
+            ArtifactLocation::Bundle { path, offset, .. } => {
+                write!(f, "{} at offset {}", path.display(), offset)
+            }
+        }
+    }
+}
+
+/// Lists all artifacts with heights in the given range, which were backed up
+/// to `version_path` (i.e. `<backup_path>/<subnet_id>/<replica_version>`),
+/// regardless of whether they are stored in files or in bundles. The artifacts
+/// are keyed by their height and their file name, as given by
+/// [`BackupArtifact::file_location`].
+pub fn list_artifacts(
+    version_path: &Path,
+    range: &HeightRange,
+) -> Result<BTreeMap<Height, BTreeMap<String, ArtifactLocation>>, io::Error> {
+    let mut artifacts = BTreeMap::new();
+    add_bundled_artifacts(version_path, range, &mut artifacts)?;
+    for entry in fs::read_dir(version_path)? {
+        let group_path = entry?.path();
+        let group = match file_name_to_u64(&group_path) {
+            Some(group) if group_path.is_dir() => group,
+            _ => continue,
+        };
+        if group > range.max.get() || group.saturating_add(BACKUP_GROUP_SIZE) <= range.min.get() {
+            continue;
+        }
+        for entry in fs::read_dir(&group_path)? {
+            let height_path = entry?.path();
+            let height = match file_name_to_u64(&height_path) {
+                Some(height) if height_path.is_dir() => Height::from(height),
+                _ => continue,
+            };
+            if height < range.min || height > range.max {
+                continue;
+            }
+            for entry in fs::read_dir(&height_path)? {
+                let path = entry?.path();
+                if let Some(name) = artifact_file_name(&path) {
+                    // An artifact backed up again after it was bundled is identical
+                    // to the bundled one, so it doesn't matter which one we read.
+                    artifacts
+                        .entry(height)
+                        .or_insert_with(BTreeMap::new)
+                        .insert(name, ArtifactLocation::File(path));
+                }
+            }
+        }
+    }
+    Ok(artifacts)
+}
+
+// Adds the locations of all bundled artifacts with heights in the given range
+// to `artifacts`.
+fn add_bundled_artifacts(
+    version_path: &Path,
+    range: &HeightRange,
+    artifacts: &mut BTreeMap<Height, BTreeMap<String, ArtifactLocation>>,
+) -> Result<(), io::Error> {
+    for entry in fs::read_dir(version_path)? {
+        let path = entry?.path();
+        if !is_bundle(&path) {
+            continue;
+        }
+        match bundle_range(&path) {
+            Some((first, last)) if first <= range.max && last >= range.min => (),
+            _ => continue,
+        }
+        // A bundle without an index has not been written completely.
+        let index_path = path.with_extension(INDEX_EXTENSION);
+        if !index_path.exists() {
+            continue;
+        }
+        for (height, name, offset, length) in read_index(&index_path)? {
+            if height < range.min || height > range.max {
+                continue;
+            }
+            artifacts
+                .entry(height)
+                .or_insert_with(BTreeMap::new)
+                .insert(
+                    name,
+                    ArtifactLocation::Bundle {
+                        path: path.clone(),
+                        offset,
+                        length,
+                    },
+                );
+        }
+    }
+    Ok(())
+}
+
+// Parses the index of a bundle.
+fn read_index(path: &Path) -> Result<Vec<(Height, String, u64, u64)>, io::Error> {
+    fn parse_line(line: &str) -> Option<(Height, String, u64, u64)> {
+        let mut parts = line.split(' ');
+        let height = Height::from(parts.next()?.parse::<u64>().ok()?);
+        let name = parts.next()?.to_string();
+        let offset = parts.next()?.parse().ok()?;
+        let length = parts.next()?.parse().ok()?;
+        match parts.next() {
+            None => Some((height, name, offset, length)),
+            Some(_) => None,
+        }
+    }
+
+    let invalid = |line: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid line in bundle index {:?}: {:?}", path, line),
+        )
+    };
+    let mut lines = io::BufReader::new(fs::File::open(path)?).lines();
+    match lines.next().transpose()? {
+        Some(header) if header == INDEX_HEADER => (),
+        header => return Err(invalid(&header.unwrap_or_default())),
+    }
+    lines
+        .map(|line| {
+            let line = line?;
+            parse_line(&line).ok_or_else(|| invalid(&line))
+        })
+        .collect()
+}
+
 // Returns all artifacts starting from the latest catch-up package height.
 fn get_all_persisted_artifacts(pool: &dyn ConsensusPool) -> Vec<ConsensusMessage> {
     let cup_height = pool.as_cache().catch_up_package().height();
@@ -656,6 +1187,7 @@ pub(super) fn bytes_to_hex_str<T>(hash: &CryptoHashOf<T>) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ic_logger::replica_logger::no_op_logger;
     use ic_test_utilities::{consensus::fake::*, mock_time, types::ids::node_test_id};
     use ic_types::{
         batch::*,
@@ -730,4 +1262,225 @@ mod tests {
             BlockProposal::try_from(pb::BlockProposal::decode(buf.as_slice()).unwrap()).unwrap()
         );
     }
+
+    fn random_beacon(height: u64) -> BackupArtifact {
+        BackupArtifact::RandomBeacon(Box::new(RandomBeacon::fake(RandomBeaconContent::new(
+            Height::from(height),
+            CryptoHashOf::from(CryptoHash(Vec::new())),
+        ))))
+    }
+
+    fn notarization(height: u64, block: u8) -> BackupArtifact {
+        BackupArtifact::Notarization(Box::new(Notarization::fake(NotarizationContent::new(
+            Height::from(height),
+            CryptoHashOf::from(CryptoHash(vec![block])),
+        ))))
+    }
+
+    // Writes the given artifacts to disk and adds their serializations to
+    // `expected`.
+    fn write_artifacts(
+        path: &Path,
+        artifacts: Vec<BackupArtifact>,
+        expected: &mut BTreeMap<Height, BTreeMap<String, Vec<u8>>>,
+    ) {
+        for artifact in artifacts {
+            artifact.write_to_disk(path).unwrap();
+            let (dir, name) = artifact.file_location(path);
+            let height = Height::from(file_name_to_u64(&dir).unwrap());
+            expected
+                .entry(height)
+                .or_default()
+                .insert(name, artifact.serialize().unwrap());
+        }
+    }
+
+    fn read_all(path: &Path, range: &HeightRange) -> BTreeMap<Height, BTreeMap<String, Vec<u8>>> {
+        list_artifacts(path, range)
+            .unwrap()
+            .into_iter()
+            .map(|(height, artifacts)| {
+                let artifacts = artifacts
+                    .into_iter()
+                    .map(|(name, location)| (name, location.read().unwrap()))
+                    .collect();
+                (height, artifacts)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_bundling_of_completed_groups() {
+        let backup_dir = tempfile::Builder::new().tempdir().unwrap();
+        let path = backup_dir.path();
+        let all_heights = HeightRange::new(Height::from(0), Height::from(u64::MAX));
+        let mut expected = BTreeMap::new();
+        write_artifacts(
+            path,
+            vec![random_beacon(1), random_beacon(2), notarization(2, 1)],
+            &mut expected,
+        );
+
+        // Nothing is bundled while the group is the latest one.
+        bundle_completed_groups(path).unwrap();
+        assert!(path.join("0").exists());
+        assert_eq!(read_all(path, &all_heights), expected);
+
+        write_artifacts(
+            path,
+            vec![random_beacon(BACKUP_GROUP_SIZE + 1)],
+            &mut expected,
+        );
+        bundle_completed_groups(path).unwrap();
+        assert!(!path.join("0").exists());
+        assert!(path.join("1_2.bundle").exists());
+        assert!(path.join("1_2.index").exists());
+        assert!(path.join(BACKUP_GROUP_SIZE.to_string()).exists());
+        assert_eq!(read_all(path, &all_heights), expected);
+        let artifacts = list_artifacts(path, &all_heights).unwrap();
+        assert!(matches!(
+            artifacts[&Height::from(2)]["random_beacon.bin"],
+            ArtifactLocation::Bundle { .. }
+        ));
+
+        // Back up artifacts of the bundled group again, as it happens after a restart,
+        // together with an artifact that was not bundled yet.
+        write_artifacts(
+            path,
+            vec![random_beacon(1), notarization(1, 2)],
+            &mut expected,
+        );
+        assert_eq!(read_all(path, &all_heights), expected);
+        bundle_completed_groups(path).unwrap();
+        assert!(!path.join("0").exists());
+        assert!(path.join("1_1.bundle").exists());
+        assert_eq!(read_all(path, &all_heights), expected);
+
+        // Only the bundles overlapping with the requested range are read.
+        let range = HeightRange::new(Height::from(2), Height::from(2));
+        let heights: Vec<_> = read_all(path, &range).into_keys().collect();
+        assert_eq!(heights, vec![Height::from(2)]);
+
+        // A bundle whose index is missing is ignored.
+        fs::remove_file(path.join("1_1.index")).unwrap();
+        assert_eq!(
+            list_artifacts(path, &all_heights).unwrap()[&Height::from(1)].len(),
+            1
+        );
+    }
+
+    struct FakeAge {
+        // Mapping names of leaves and bundles to their emulated age.
+        map: HashMap<String, Duration>,
+    }
+
+    impl BackupAge for FakeAge {
+        fn get_elapsed_time(&self, path: &Path) -> Result<Duration, PurgingError> {
+            let name = path.file_name().unwrap().to_str().unwrap();
+            Ok(self.map.get(name).copied().unwrap_or_default())
+        }
+    }
+
+    #[test]
+    fn test_purging_of_bundles_by_age() {
+        let backup_dir = tempfile::Builder::new().tempdir().unwrap();
+        let path = backup_dir.path();
+        let mut expected = BTreeMap::new();
+        write_artifacts(
+            path,
+            vec![random_beacon(1), random_beacon(2)],
+            &mut expected,
+        );
+        write_artifacts(
+            path,
+            vec![
+                random_beacon(BACKUP_GROUP_SIZE + 1),
+                random_beacon(BACKUP_GROUP_SIZE + 2),
+            ],
+            &mut expected,
+        );
+        bundle_completed_groups(path).unwrap();
+
+        let leaf = |height: u64| {
+            path.join(BACKUP_GROUP_SIZE.to_string())
+                .join(height.to_string())
+        };
+        let age = FakeAge {
+            map: vec![
+                ("1_2.bundle".to_string(), Duration::from_secs(10)),
+                ((BACKUP_GROUP_SIZE + 1).to_string(), Duration::from_secs(20)),
+                ((BACKUP_GROUP_SIZE + 2).to_string(), Duration::from_secs(5)),
+            ]
+            .into_iter()
+            .collect(),
+        };
+
+        // The bundle is purged together with the later heights, although it was
+        // created after them.
+        purge(Duration::from_secs(15), path, no_op_logger(), &age).unwrap();
+        assert!(!path.join("1_2.bundle").exists());
+        assert!(!path.join("1_2.index").exists());
+        assert!(!leaf(BACKUP_GROUP_SIZE + 1).exists());
+        assert!(leaf(BACKUP_GROUP_SIZE + 2).exists());
+    }
+
+    #[test]
+    fn test_purging_by_size() {
+        let backup_dir = tempfile::Builder::new().tempdir().unwrap();
+        let path = backup_dir.path();
+        let mut expected = BTreeMap::new();
+        write_artifacts(
+            path,
+            vec![random_beacon(1), random_beacon(2), notarization(2, 1)],
+            &mut expected,
+        );
+        write_artifacts(
+            path,
+            vec![
+                random_beacon(BACKUP_GROUP_SIZE + 1),
+                random_beacon(BACKUP_GROUP_SIZE + 2),
+            ],
+            &mut expected,
+        );
+        bundle_completed_groups(path).unwrap();
+
+        // The bundle was created after height BACKUP_GROUP_SIZE + 1 was backed up, so it
+        // appears to be younger.
+        let leaf = |height: u64| {
+            path.join(BACKUP_GROUP_SIZE.to_string())
+                .join(height.to_string())
+        };
+        let age = FakeAge {
+            map: vec![
+                ("1_2.bundle".to_string(), Duration::from_secs(10)),
+                ((BACKUP_GROUP_SIZE + 1).to_string(), Duration::from_secs(20)),
+                ((BACKUP_GROUP_SIZE + 2).to_string(), Duration::from_secs(5)),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        let size_of = |path: &Path| {
+            fs::read_dir(path)
+                .unwrap()
+                .map(|entry| entry.unwrap().metadata().unwrap().len())
+                .sum::<u64>()
+        };
+        let leaves_size =
+            size_of(&leaf(BACKUP_GROUP_SIZE + 1)) + size_of(&leaf(BACKUP_GROUP_SIZE + 2));
+
+        // Nothing is purged while the backup fits into the budget.
+        purge_to_size(u64::MAX, path, no_op_logger(), &age).unwrap();
+        assert!(path.join("1_2.bundle").exists());
+
+        // The bundle is purged before the later heights, despite its age.
+        purge_to_size(leaves_size, path, no_op_logger(), &age).unwrap();
+        assert!(!path.join("1_2.bundle").exists());
+        assert!(!path.join("1_2.index").exists());
+        assert!(leaf(BACKUP_GROUP_SIZE + 1).exists());
+        assert!(leaf(BACKUP_GROUP_SIZE + 2).exists());
+
+        purge_to_size(leaves_size - 1, path, no_op_logger(), &age).unwrap();
+        assert!(!leaf(BACKUP_GROUP_SIZE + 1).exists());
+        assert!(leaf(BACKUP_GROUP_SIZE + 2).exists());
+    }
 }
@@ -1,16 +1,24 @@
 use clap::{arg, Arg, Command};
 use ic_artifact_pool::{
+    archive::{export_pool, import_pool, ArchiveMetadata, ArchiveReader},
     certification_pool::CertificationPoolImpl,
     consensus_pool::{PoolSectionOps, UncachedConsensusPoolImpl},
+    consistency::check_persistent_pool,
+    ecdsa_pool::EcdsaPoolImpl,
+    get_replica_version,
 };
-use ic_config::artifact_pool::ArtifactPoolConfig;
+use ic_config::artifact_pool::{ArtifactPoolConfig, ArtifactPoolTomlConfig, PersistentPoolBackend};
 use ic_consensus_message::ConsensusMessageHashable;
-use ic_interfaces::consensus_pool::*;
+use ic_interfaces::{
+    consensus_pool::*,
+    ecdsa::{EcdsaPool, MutableEcdsaPool},
+};
 use ic_logger::{LoggerImpl, ReplicaLogger};
 use ic_metrics::MetricsRegistry;
 use ic_types::{
     consensus::{certification::CertificationMessage, CatchUpPackage},
     time::current_time,
+    Height,
 };
 use prost::Message;
 use serde::{Deserialize, Serialize};
@@ -18,7 +26,7 @@ use serde_bytes_repr::{ByteFmtDeserializer, ByteFmtSerializer};
 use serde_json::{Deserializer, Serializer};
 use std::convert::TryFrom;
 use std::io::BufRead;
-use std::io::Write;
+use std::io::{BufReader, BufWriter, Write};
 use std::path::PathBuf;
 
 fn main() {
@@ -51,6 +59,64 @@ fn main() {
                         .takes_value(true),
                 ),
         )
+        .subcommand(
+            Command::new("export-archive")
+                .about("Export the validated pool to a compressed archive")
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Output filename")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("from")
+                        .long("from")
+                        .value_name("HEIGHT")
+                        .help("Lowest height of the exported artifacts")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .value_name("HEIGHT")
+                        .help("Highest height of the exported artifacts")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            Command::new("import-archive")
+                .about("Import an archive created by export-archive into the validated pool")
+                .arg(
+                    Arg::new("input")
+                        .short('i')
+                        .long("input")
+                        .value_name("FILE")
+                        .help("Input filename")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            Command::new("check")
+                .about("Check the consistency of the validated pool")
+                .arg(
+                    Arg::new("repair")
+                        .long("repair")
+                        .help("Remove inconsistent entries, with the replica stopped"),
+                ),
+        )
+        .arg(
+            Arg::new("backend")
+                .short('b')
+                .long("backend")
+                .value_name("BACKEND")
+                .help("Persistent pool backend, \"lmdb\" or \"rocksdb\"")
+                .default_value("lmdb")
+                .takes_value(true),
+        )
         .arg(arg!(<PATH>       "PATH to the consensus pool directory"));
     let mut help = Vec::new();
     app.write_help(&mut help)
@@ -59,12 +125,19 @@ fn main() {
     let path = matches
         .value_of("PATH")
         .expect("Missing PATH to consensus pool directory");
+    let backend = matches.value_of("backend").unwrap_or("lmdb");
     if let Some(matches) = matches.subcommand_matches("export") {
-        export(path, matches)
+        export(path, backend, matches)
     } else if let Some(_matches) = matches.subcommand_matches("import") {
-        import(path)
+        import(path, backend)
     } else if let Some(matches) = matches.subcommand_matches("export-cup-proto") {
-        export_cup_proto(path, matches)
+        export_cup_proto(path, backend, matches)
+    } else if let Some(matches) = matches.subcommand_matches("export-archive") {
+        export_archive(path, backend, matches)
+    } else if let Some(matches) = matches.subcommand_matches("import-archive") {
+        import_archive(path, backend, matches)
+    } else if let Some(matches) = matches.subcommand_matches("check") {
+        check(path, backend, matches)
     } else {
         eprintln!(
             "{}",
@@ -105,24 +178,41 @@ fn parse_artifact_names<'a, 'b>(names: &'a [&'b str]) -> Vec<&'static str> {
         .collect::<Vec<_>>()
 }
 
-fn open_consensus_pool(path: &str, read_only: bool) -> UncachedConsensusPoolImpl {
+fn pool_config(path: &str, backend: &str, read_only: bool) -> ArtifactPoolConfig {
+    let mut toml_config = ArtifactPoolTomlConfig::new(PathBuf::from(path), None);
+    toml_config.consensus_pool_backend = Some(backend.to_string());
+    let mut config = ArtifactPoolConfig::from(toml_config);
+    config.persistent_pool_read_only = read_only;
+    config
+}
+
+fn open_consensus_pool(path: &str, backend: &str, read_only: bool) -> UncachedConsensusPoolImpl {
     let logger = LoggerImpl::new(&Default::default(), "dump_consensus_pool".to_string());
     let log = ReplicaLogger::new(logger.root.clone().into());
 
-    let path = PathBuf::from(path);
-    let mut config = ArtifactPoolConfig::new(path);
-    config.persistent_pool_read_only = read_only;
-    UncachedConsensusPoolImpl::new(config, log)
+    UncachedConsensusPoolImpl::new(pool_config(path, backend, read_only), log)
 }
 
-fn open_certification_pool(path: &str, read_only: bool) -> CertificationPoolImpl {
+fn open_certification_pool(path: &str, backend: &str, read_only: bool) -> CertificationPoolImpl {
     let logger = LoggerImpl::new(&Default::default(), "dump_consensus_pool".to_string());
     let log = ReplicaLogger::new(logger.root.clone().into());
 
-    let path = PathBuf::from(path);
-    let mut config = ArtifactPoolConfig::new(path);
-    config.persistent_pool_read_only = read_only;
-    CertificationPoolImpl::new(config, log, MetricsRegistry::new())
+    CertificationPoolImpl::new(
+        pool_config(path, backend, read_only),
+        log,
+        MetricsRegistry::new(),
+    )
+}
+
+fn open_ecdsa_pool(path: &str, backend: &str, read_only: bool) -> EcdsaPoolImpl {
+    let logger = LoggerImpl::new(&Default::default(), "dump_consensus_pool".to_string());
+    let log = ReplicaLogger::new(logger.root.clone().into());
+
+    EcdsaPoolImpl::new(
+        pool_config(path, backend, read_only),
+        log,
+        MetricsRegistry::new(),
+    )
 }
 
 fn from_str<'a, T: Deserialize<'a>>(json: &'a str) -> Result<T, serde_json::Error> {
@@ -139,14 +229,14 @@ fn to_string<T: Serialize>(msg: &T) -> String {
     String::from_utf8(out).expect("UTF8 conversion error")
 }
 
-fn export(path: &str, matches: &clap::ArgMatches) {
+fn export(path: &str, backend: &str, matches: &clap::ArgMatches) {
     let artifacts = match matches.values_of("artifact") {
         Some(names) => parse_artifact_names(&names.collect::<Vec<&str>>()),
         None => ALL_ARTIFACT_NAMES.to_vec(),
     };
 
-    let consensus_pool = open_consensus_pool(path, true);
-    let certification_pool = open_certification_pool(path, true);
+    let consensus_pool = open_consensus_pool(path, backend, true);
+    let certification_pool = open_certification_pool(path, backend, true);
 
     for artifact in artifacts {
         match artifact {
@@ -235,9 +325,9 @@ fn export(path: &str, matches: &clap::ArgMatches) {
     }
 }
 
-fn import(path: &str) {
-    let mut consensus_pool = open_consensus_pool(path, false);
-    let certification_pool = open_certification_pool(path, false);
+fn import(path: &str, backend: &str) {
+    let mut consensus_pool = open_consensus_pool(path, backend, false);
+    let certification_pool = open_certification_pool(path, backend, false);
     let stdin = std::io::stdin();
     for line in stdin.lock().lines() {
         let s = line.expect("Cannot read input");
@@ -256,13 +346,13 @@ fn import(path: &str) {
     }
 }
 
-fn export_cup_proto(path: &str, matches: &clap::ArgMatches) {
+fn export_cup_proto(path: &str, backend: &str, matches: &clap::ArgMatches) {
     let filename = matches
         .value_of("output")
         .expect("Expect an output filename");
     let mut file = std::fs::File::create(filename)
         .unwrap_or_else(|err| panic!("Cannot open file {} for write: {:?}", filename, err));
-    let consensus_pool = open_consensus_pool(path, true);
+    let consensus_pool = open_consensus_pool(path, backend, true);
     let mut buf = Vec::<u8>::new();
     let cup_proto = consensus_pool.validated().highest_catch_up_package_proto();
     let cup = CatchUpPackage::try_from(&cup_proto).unwrap_or_else(|err| panic!("{}", err));
@@ -273,3 +363,101 @@ fn export_cup_proto(path: &str, matches: &clap::ArgMatches) {
     file.write_all(&buf)
         .unwrap_or_else(|err| panic!("Cannot write to file {}: {:?}", filename, err));
 }
+
+fn export_archive(path: &str, backend: &str, matches: &clap::ArgMatches) {
+    let filename = matches
+        .value_of("output")
+        .expect("Expect an output filename");
+    let height = |name: &str| {
+        matches.value_of(name).map(|height| {
+            Height::from(
+                height
+                    .parse::<u64>()
+                    .unwrap_or_else(|err| panic!("Invalid height {}: {}", height, err)),
+            )
+        })
+    };
+    let height_range = match (height("from"), height("to")) {
+        (None, None) => None,
+        (from, to) => Some((
+            from.unwrap_or_else(|| Height::from(0)),
+            to.unwrap_or_else(|| Height::from(u64::MAX)),
+        )),
+    };
+    let metadata = ArchiveMetadata {
+        replica_version: get_replica_version(PathBuf::from(path).join("replica_version")),
+        height_range,
+    };
+
+    let consensus_pool = open_consensus_pool(path, backend, true);
+    let certification_pool = open_certification_pool(path, backend, true);
+    let ecdsa_pool = open_ecdsa_pool(path, backend, true);
+    let file = std::fs::File::create(filename)
+        .unwrap_or_else(|err| panic!("Cannot open file {} for write: {:?}", filename, err));
+    let (writer, stats) = export_pool(
+        BufWriter::new(file),
+        &metadata,
+        consensus_pool.validated(),
+        certification_pool.persistent_pool.as_ref(),
+        ecdsa_pool.validated(),
+    )
+    .unwrap_or_else(|err| panic!("Cannot export the pool: {}", err));
+    writer
+        .into_inner()
+        .map_err(|err| err.into_error())
+        .and_then(|file| file.sync_all())
+        .unwrap_or_else(|err| panic!("Cannot write to file {}: {:?}", filename, err));
+    println!("Exported {}", stats);
+}
+
+fn import_archive(path: &str, backend: &str, matches: &clap::ArgMatches) {
+    let filename = matches.value_of("input").expect("Expect an input filename");
+    let file = std::fs::File::open(filename)
+        .unwrap_or_else(|err| panic!("Cannot open file {} for read: {:?}", filename, err));
+    let archive = ArchiveReader::new(BufReader::new(file))
+        .unwrap_or_else(|err| panic!("Cannot read archive {}: {}", filename, err));
+    println!("Importing archive {:?}", archive.metadata());
+
+    let mut consensus_pool = open_consensus_pool(path, backend, false);
+    let certification_pool = open_certification_pool(path, backend, false);
+    let config = pool_config(path, backend, false);
+    // Only the LMDB backend persists the validated ECDSA pool.
+    let mut ecdsa_pool = match config.persistent_pool_backend {
+        PersistentPoolBackend::Lmdb(_) => Some(open_ecdsa_pool(path, backend, false)),
+        _ => None,
+    };
+    let stats = import_pool(
+        archive,
+        consensus_pool.validated.as_mut(),
+        certification_pool.persistent_pool.as_ref(),
+        ecdsa_pool
+            .as_mut()
+            .map(|pool| pool as &mut dyn MutableEcdsaPool),
+    )
+    .unwrap_or_else(|err| panic!("Cannot import archive {}: {}", filename, err));
+    println!("Imported {}", stats);
+    if stats.skipped > 0 {
+        eprintln!(
+            "Skipped {} ECDSA artifacts, the {} backend does not persist them",
+            stats.skipped, backend
+        );
+    }
+}
+
+fn check(path: &str, backend: &str, matches: &clap::ArgMatches) {
+    let logger = LoggerImpl::new(&Default::default(), "dump_consensus_pool".to_string());
+    let log = ReplicaLogger::new(logger.root.clone().into());
+    // The pool is not opened read-only, so that the check takes part in the
+    // locking of a replica that may be using the same LMDB pool. A repair is
+    // refused unless the replica is stopped.
+    let report = check_persistent_pool(
+        pool_config(path, backend, false),
+        matches.is_present("repair"),
+        log,
+    )
+    .unwrap_or_else(|err| panic!("Cannot check pool at {}: {}", path, err));
+    println!("{}", report);
+    if !report.is_consistent() && !report.repaired {
+        std::process::exit(1);
+    }
+}
@@ -4,6 +4,7 @@ use crate::{
         get_highest_catch_up_package, get_highest_finalized_block, update_summary_block,
         ConsensusBlockChainImpl, ConsensusCacheImpl,
     },
+    consistency::PersistentPoolLock,
     inmemory_pool::InMemoryPoolSection,
     metrics::{LABEL_POOL_TYPE, POOL_TYPE_UNVALIDATED, POOL_TYPE_VALIDATED},
 };
@@ -12,8 +13,9 @@ use ic_consensus_message::ConsensusMessageHashable;
 use ic_interfaces::{
     consensus_pool::{
         ChangeAction, ChangeSet, ConsensusBlockCache, ConsensusBlockChain, ConsensusPool,
-        ConsensusPoolCache, HeightIndexedPool, HeightRange, MutableConsensusPool, PoolSection,
-        UnvalidatedConsensusArtifact, ValidatedConsensusArtifact,
+        ConsensusPoolCache, ConsensusTimingsRecorder, HeightIndexedPool, HeightRange,
+        MutableConsensusPool, PoolSection, UnvalidatedConsensusArtifact,
+        ValidatedConsensusArtifact,
     },
     gossip_pool::{ConsensusGossipPool, GossipPool},
     time_source::TimeSource,
@@ -201,10 +203,23 @@ pub struct ConsensusPoolImpl {
 pub struct UncachedConsensusPoolImpl {
     pub validated: Box<dyn InitializablePoolSection + Send + Sync>,
     unvalidated: Box<dyn MutablePoolSection<UnvalidatedConsensusArtifact> + Send + Sync>,
+    // Keeps the pool from being repaired while it is in use.
+    _lock: Option<PersistentPoolLock>,
 }
 
 impl UncachedConsensusPoolImpl {
     pub fn new(config: ArtifactPoolConfig, log: ReplicaLogger) -> UncachedConsensusPoolImpl {
+        let lock = if config.persistent_pool_read_only {
+            None
+        } else {
+            let path = config.persistent_pool_db_path();
+            Some(PersistentPoolLock::shared(&path).unwrap_or_else(|err| {
+                panic!(
+                    "Cannot open pool at {:?} while it is being repaired: {}",
+                    path, err
+                )
+            }))
+        };
         let validated = match config.persistent_pool_backend {
             PersistentPoolBackend::Lmdb(lmdb_config) => Box::new(
                 crate::lmdb_pool::PersistentHeightIndexedPool::new_consensus_pool(
@@ -229,6 +244,7 @@ impl UncachedConsensusPoolImpl {
         UncachedConsensusPoolImpl {
             validated,
             unvalidated: Box::new(InMemoryPoolSection::new(log)),
+            _lock: lock,
         }
     }
 }
@@ -320,6 +336,7 @@ impl ConsensusPoolImpl {
                     .join(ic_types::ReplicaVersion::default().to_string()),
                 Duration::from_secs(config.retention_time_secs),
                 Duration::from_secs(config.purging_interval_secs),
+                config.max_size_bytes,
                 registry,
                 log,
             )
@@ -398,6 +415,11 @@ impl ConsensusPoolImpl {
         Arc::clone(&self.cache) as Arc<_>
     }
 
+    /// Get a copy of ConsensusTimingsRecorder.
+    pub fn get_timings_recorder(&self) -> Arc<dyn ConsensusTimingsRecorder> {
+        Arc::clone(&self.cache) as Arc<_>
+    }
+
     fn apply_changes_validated(&mut self, ops: PoolSectionOps<ValidatedConsensusArtifact>) {
         if !ops.ops.is_empty() {
             self.validated.mutate(ops);
@@ -484,14 +506,19 @@ impl MutableConsensusPool for ConsensusPoolImpl {
             }
         }
 
-        let artifacts_for_backup = validated_ops
+        let inserted: Vec<&ValidatedConsensusArtifact> = validated_ops
             .ops
             .iter()
             .filter_map(|op| match op {
-                PoolSectionOp::Insert(artifact) => Some(artifact.msg.clone()),
+                PoolSectionOp::Insert(artifact) => Some(artifact),
                 _ => None,
             })
             .collect();
+        self.cache.record_timings(self, &inserted);
+        let artifacts_for_backup = inserted
+            .into_iter()
+            .map(|artifact| artifact.msg.clone())
+            .collect();
         self.apply_changes_unvalidated(unvalidated_ops);
         self.apply_changes_validated(validated_ops);
         if let Some(backup) = &self.backup {
@@ -902,6 +929,7 @@ mod tests {
                 Duration::from_millis(100),
                 // We purge every 5 milliseconds.
                 purging_interval,
+                None,
                 MetricsRegistry::new(),
                 no_op_logger(),
             ));
@@ -1204,6 +1232,7 @@ mod tests {
                 // Artifact retention time
                 Duration::from_millis(2700),
                 purging_interval,
+                None,
                 MetricsRegistry::new(),
                 no_op_logger(),
                 Box::new(FakeAge { map: map.clone() }),
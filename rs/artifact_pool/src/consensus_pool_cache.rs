@@ -1,25 +1,34 @@
 //! We define a cache for consensus objects/values that is updated whenever
 //! consensus updates the consensus pool.
+//!
+//! The cache also keeps the timings of the most recent heights, i.e. when the
+//! block proposals, notarizations and finalizations of a height were received
+//! or made, and when its batch was delivered and its state certified.
 use crate::consensus_pool::BlockChainIterator;
 use ic_interfaces::consensus_pool::{
     ChainIterator, ChangeAction, ConsensusBlockCache, ConsensusBlockChain, ConsensusPool,
-    ConsensusPoolCache,
+    ConsensusPoolCache, ConsensusTimingsRecorder, HeightTimings, PayloadSizes,
+    ValidatedConsensusArtifact,
 };
 use ic_types::{
     consensus::{
         catchup::CUPWithOriginalProtobuf, Block, CatchUpPackage, ConsensusMessage, Finalization,
         HasHeight,
     },
-    Height, Time,
+    CountBytes, Height, Time,
 };
 use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::convert::TryFrom;
 use std::sync::{Arc, RwLock};
 
+/// The number of heights for which timings are kept.
+pub(crate) const HEIGHT_TIMINGS_CAPACITY: usize = 500;
+
 /// Implementation of ConsensusCache and ConsensusPoolCache.
 pub(crate) struct ConsensusCacheImpl {
     cache: RwLock<CachedData>,
+    timings: RwLock<HeightTimingsBuffer>,
 }
 
 /// Things that can be updated in the consensus cache.
@@ -83,6 +92,30 @@ impl ConsensusPoolCache for ConsensusCacheImpl {
     fn summary_block(&self) -> Block {
         self.cache.read().unwrap().summary_block.clone()
     }
+
+    fn height_timings(&self) -> Vec<HeightTimings> {
+        self.timings
+            .read()
+            .unwrap()
+            .timings
+            .values()
+            .cloned()
+            .collect()
+    }
+}
+
+impl ConsensusTimingsRecorder for ConsensusCacheImpl {
+    fn record_batch_delivered(&self, height: Height, time: Time) {
+        if let Some(timings) = self.timings.write().unwrap().get_mut(height) {
+            timings.batch_delivered.get_or_insert(time);
+        }
+    }
+
+    fn record_certified(&self, height: Height, time: Time) {
+        if let Some(timings) = self.timings.write().unwrap().get_mut(height) {
+            timings.certified.get_or_insert(time);
+        }
+    }
 }
 
 impl ConsensusBlockCache for ConsensusCacheImpl {
@@ -108,6 +141,63 @@ impl ConsensusCacheImpl {
                 catch_up_package,
                 finalized_chain,
             }),
+            timings: RwLock::new(HeightTimingsBuffer::new(HEIGHT_TIMINGS_CAPACITY)),
+        }
+    }
+
+    /// Record the timings of the given artifacts, which are about to be added
+    /// to the validated section of the pool. The block of a finalization is
+    /// looked up among the given artifacts first, and then in the pool.
+    pub(crate) fn record_timings(
+        &self,
+        pool: &dyn ConsensusPool,
+        artifacts: &[&ValidatedConsensusArtifact],
+    ) {
+        let timings = &mut *self.timings.write().unwrap();
+        for artifact in artifacts {
+            let time = artifact.timestamp;
+            match &artifact.msg {
+                ConsensusMessage::BlockProposal(proposal) => {
+                    if let Some(entry) = timings.get_mut(proposal.height()) {
+                        entry.block_proposal_received.get_or_insert(time);
+                    }
+                }
+                ConsensusMessage::Notarization(notarization) => {
+                    if let Some(entry) = timings.get_mut(notarization.height()) {
+                        entry.notarized.get_or_insert(time);
+                    }
+                }
+                ConsensusMessage::Finalization(finalization) => {
+                    let entry = match timings.get_mut(finalization.height()) {
+                        Some(entry) => entry,
+                        None => continue,
+                    };
+                    entry.finalized.get_or_insert(time);
+                    let hash = &finalization.content.block;
+                    let block_info = artifacts
+                        .iter()
+                        .find_map(|artifact| match &artifact.msg {
+                            ConsensusMessage::BlockProposal(proposal)
+                                if proposal.content.get_hash() == hash =>
+                            {
+                                Some(get_block_info(proposal.content.as_ref()))
+                            }
+                            _ => None,
+                        })
+                        .or_else(|| {
+                            pool.validated()
+                                .block_proposal()
+                                .get_by_height(finalization.height())
+                                .find(|proposal| proposal.content.get_hash() == hash)
+                                .map(|proposal| get_block_info(proposal.content.as_ref()))
+                        });
+                    if let Some((rank, payload_sizes)) = block_info {
+                        entry.block_maker_rank = Some(rank);
+                        entry.payload_sizes = Some(payload_sizes);
+                    }
+                }
+                _ => (),
+            }
         }
     }
 
@@ -160,6 +250,55 @@ impl ConsensusCacheImpl {
     }
 }
 
+/// A bounded buffer of the timings of the most recent heights. When it is
+/// full, recording a new height evicts the lowest one, and heights below the
+/// lowest one are no longer recorded.
+struct HeightTimingsBuffer {
+    timings: BTreeMap<Height, HeightTimings>,
+    capacity: usize,
+}
+
+impl HeightTimingsBuffer {
+    fn new(capacity: usize) -> Self {
+        Self {
+            timings: BTreeMap::new(),
+            capacity,
+        }
+    }
+
+    /// Return the timings of the given height, creating them if necessary.
+    /// Return None if the height is too old to be recorded.
+    fn get_mut(&mut self, height: Height) -> Option<&mut HeightTimings> {
+        if !self.timings.contains_key(&height) {
+            if self.timings.len() >= self.capacity {
+                let lowest = *self.timings.keys().next()?;
+                if height < lowest {
+                    return None;
+                }
+                self.timings.remove(&lowest);
+            }
+            self.timings.insert(height, HeightTimings::new(height));
+        }
+        self.timings.get_mut(&height)
+    }
+}
+
+// Return the rank of the block maker and the payload sizes of the given block.
+fn get_block_info(block: &Block) -> (u64, PayloadSizes) {
+    let payload = block.payload.as_ref();
+    let payload_sizes = if payload.is_summary() {
+        PayloadSizes::default()
+    } else {
+        let batch = &payload.as_data().batch;
+        PayloadSizes {
+            ingress_messages: batch.ingress.message_count(),
+            ingress_bytes: batch.ingress.count_bytes(),
+            xnet_bytes: batch.xnet.count_bytes(),
+        }
+    };
+    (block.rank.0, payload_sizes)
+}
+
 pub(crate) fn get_highest_finalized_block(
     pool: &dyn ConsensusPool,
     catch_up_package: &CatchUpPackage,
@@ -469,4 +608,72 @@ mod test {
             check_finalized_chain(&consensus_cache, &[Height::from(4)]);
         })
     }
+
+    #[test]
+    fn test_height_timings() {
+        ic_test_utilities::artifact_pool_config::with_test_pool_config(|pool_config| {
+            let time_source = FastForwardTimeSource::new();
+            let subnet_id = subnet_test_id(1);
+            let committee = vec![node_test_id(0)];
+            let subnet_records = vec![(
+                1,
+                SubnetRecordBuilder::from(&committee)
+                    .with_dkg_interval_length(3)
+                    .build(),
+            )];
+            let registry = setup_registry(subnet_id, subnet_records);
+            let mut pool = TestConsensusPool::new(
+                subnet_id,
+                pool_config,
+                time_source.clone(),
+                registry,
+                Arc::new(CryptoReturningOk::default()),
+                Arc::new(FakeStateManager::new()),
+                None,
+            );
+            let consensus_cache = pool.get_cache();
+            let timings_recorder = pool.get_timings_recorder();
+
+            let time = mock_time() + Duration::from_secs(10);
+            time_source.set_time(time).unwrap();
+            assert_eq!(pool.advance_round_normal_operation_n(2), Height::from(2));
+            let timings = consensus_cache.height_timings();
+            let heights: Vec<_> = timings.iter().map(|t| t.height.get()).collect();
+            assert_eq!(heights, vec![1, 2]);
+            for t in timings.iter() {
+                assert_eq!(t.block_proposal_received, Some(time));
+                assert_eq!(t.notarized, Some(time));
+                assert_eq!(t.finalized, Some(time));
+                assert_eq!(t.batch_delivered, None);
+                assert_eq!(t.certified, None);
+                assert_eq!(t.block_maker_rank, Some(0));
+                assert_eq!(t.payload_sizes, Some(PayloadSizes::default()));
+            }
+
+            // Batch delivery and certification are recorded only once.
+            let later = time + Duration::from_secs(1);
+            timings_recorder.record_batch_delivered(Height::from(1), later);
+            timings_recorder
+                .record_batch_delivered(Height::from(1), later + Duration::from_secs(1));
+            timings_recorder.record_certified(Height::from(1), later);
+            let timings = consensus_cache.height_timings();
+            assert_eq!(timings[0].batch_delivered, Some(later));
+            assert_eq!(timings[0].certified, Some(later));
+            assert_eq!(timings[1].batch_delivered, None);
+        })
+    }
+
+    #[test]
+    fn test_height_timings_buffer_is_bounded() {
+        let mut buffer = HeightTimingsBuffer::new(3);
+        for height in 1..=4 {
+            assert!(buffer.get_mut(Height::from(height)).is_some());
+        }
+        let heights: Vec<_> = buffer.timings.keys().map(|h| h.get()).collect();
+        assert_eq!(heights, vec![2, 3, 4]);
+        // Heights below the lowest one are no longer recorded.
+        assert!(buffer.get_mut(Height::from(1)).is_none());
+        assert!(buffer.get_mut(Height::from(3)).is_some());
+        assert_eq!(buffer.timings.len(), 3);
+    }
 }
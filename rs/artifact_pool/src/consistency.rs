@@ -0,0 +1,318 @@
+//! Consistency checks for the persistent artifact pools.
+//!
+//! The persistent pools keep artifacts in a key-value store together with
+//! secondary data that must agree with it: height indices and metadata in the
+//! LMDB pool, and separately stored block payloads in both backends. A crash
+//! or a full disk can leave the two out of sync, e.g. with index entries that
+//! point to artifacts that no longer exist.
+//!
+//! [check_persistent_pool] scans the validated consensus, certification and
+//! ECDSA pools described by an [ArtifactPoolConfig], verifies that every stored
+//! artifact deserializes and matches the height and hash of its key, and
+//! reports any index entry, artifact or payload that does not line up. When
+//! asked to repair, it deletes the offending entries and recomputes the
+//! metadata, leaving a pool that can be loaded again. Missing artifacts cannot
+//! be recovered, but they are re-delivered by peers like any other artifact
+//! the replica does not yet have.
+//!
+//! Only the read-only check may run while a replica is using an LMDB pool,
+//! since it runs in a single transaction, unless the pool is opened read-only
+//! (which disables locking). A RocksDB pool can only be opened by one process
+//! at a time, so the replica must be stopped first.
+//!
+//! Repairing a pool requires the replica to be stopped. A replica holds a
+//! shared [PersistentPoolLock] on its pool for as long as the pool is open,
+//! and a repair takes the lock exclusively, so it is refused while a replica
+//! uses the pool, and a replica cannot open a pool that is being repaired.
+use ic_config::artifact_pool::{ArtifactPoolConfig, PersistentPoolBackend};
+use ic_logger::ReplicaLogger;
+use ic_types::Height;
+use nix::fcntl::{flock, FlockArg};
+use std::fmt;
+use std::fs::{self, File};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+/// The name of the lock file in the directory of a persistent pool.
+const POOL_LOCK_FILE: &str = "pool.lock";
+
+/// An advisory lock on the directory of a persistent pool. The lock is
+/// released when it is dropped.
+pub struct PersistentPoolLock {
+    _file: File,
+}
+
+impl PersistentPoolLock {
+    /// Take a shared lock on the pool at `path`, as held by a replica using
+    /// the pool. Fail if the pool is being repaired.
+    pub fn shared(path: &Path) -> Result<Self, String> {
+        Self::lock(path, FlockArg::LockSharedNonblock)
+    }
+
+    /// Take an exclusive lock on the pool at `path`, as needed for a repair.
+    /// Fail if the pool is in use.
+    pub fn exclusive(path: &Path) -> Result<Self, String> {
+        Self::lock(path, FlockArg::LockExclusiveNonblock)
+    }
+
+    fn lock(path: &Path, arg: FlockArg) -> Result<Self, String> {
+        fs::create_dir_all(path)
+            .map_err(|err| format!("Cannot create pool directory {:?}: {:?}", path, err))?;
+        let lock_path = path.join(POOL_LOCK_FILE);
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(&lock_path)
+            .map_err(|err| format!("Cannot open lock file {:?}: {:?}", lock_path, err))?;
+        flock(file.as_raw_fd(), arg)
+            .map_err(|err| format!("Cannot lock {:?}: {:?}", lock_path, err))?;
+        Ok(PersistentPoolLock { _file: file })
+    }
+}
+
+/// The kind of problem found for a single entry of a persistent pool.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InconsistencyKind {
+    /// An index entry refers to an artifact that does not exist.
+    MissingArtifact,
+    /// An artifact is not referred to by any index entry.
+    OrphanedArtifact,
+    /// An artifact cannot be deserialized.
+    CorruptArtifact,
+    /// An artifact does not match the height, hash or type of its key.
+    KeyMismatch,
+    /// A block proposal whose payload is missing or does not match its hash.
+    MissingBlockPayload,
+    /// The height range recorded for an artifact type does not match its index.
+    StaleMetadata,
+}
+
+/// A single problem found by a consistency check.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Inconsistency {
+    /// The pool the entry belongs to, e.g. "consensus".
+    pub pool: &'static str,
+    /// The short name of the artifact type, as used by the storage backend.
+    pub artifact_type: &'static str,
+    pub kind: InconsistencyKind,
+    /// The height of the entry, if its key carries one.
+    pub height: Option<Height>,
+    /// The raw key of the entry in the store.
+    pub key: Vec<u8>,
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}: {:?}", self.pool, self.artifact_type, self.kind)?;
+        if let Some(height) = self.height {
+            write!(f, " at height {}", height)?;
+        }
+        if !self.key.is_empty() {
+            write!(f, " (key ")?;
+            for byte in &self.key {
+                write!(f, "{:02x}", byte)?;
+            }
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of a consistency check.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConsistencyReport {
+    /// Number of entries that were checked.
+    pub checked: u64,
+    pub inconsistencies: Vec<Inconsistency>,
+    /// Whether the inconsistencies have been repaired.
+    pub repaired: bool,
+}
+
+impl ConsistencyReport {
+    /// Return true if no inconsistency was found.
+    pub fn is_consistent(&self) -> bool {
+        self.inconsistencies.is_empty()
+    }
+
+    /// Return the number of inconsistencies of the given kind.
+    pub fn count(&self, kind: InconsistencyKind) -> usize {
+        self.inconsistencies
+            .iter()
+            .filter(|inconsistency| inconsistency.kind == kind)
+            .count()
+    }
+
+    pub(crate) fn add(
+        &mut self,
+        pool: &'static str,
+        artifact_type: &'static str,
+        kind: InconsistencyKind,
+        height: Option<Height>,
+        key: &[u8],
+    ) {
+        self.inconsistencies.push(Inconsistency {
+            pool,
+            artifact_type,
+            kind,
+            height,
+            key: key.to_vec(),
+        })
+    }
+
+    fn merge(&mut self, other: ConsistencyReport) {
+        self.checked += other.checked;
+        self.inconsistencies.extend(other.inconsistencies);
+        self.repaired |= other.repaired;
+    }
+}
+
+impl fmt::Display for ConsistencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for inconsistency in &self.inconsistencies {
+            writeln!(f, "{}", inconsistency)?;
+        }
+        write!(
+            f,
+            "checked {} entries, found {} inconsistencies",
+            self.checked,
+            self.inconsistencies.len()
+        )?;
+        if self.repaired && !self.is_consistent() {
+            write!(f, ", all repaired")?;
+        }
+        Ok(())
+    }
+}
+
+/// Check the validated consensus, certification and ECDSA pools stored at the
+/// location given by `config`, and delete inconsistent entries if `repair` is
+/// true. Repairing requires the pool not to be opened read-only, and fails if
+/// a replica is using the pool.
+///
+/// The ECDSA pool is only persisted by the LMDB backend and is skipped
+/// otherwise.
+pub fn check_persistent_pool(
+    config: ArtifactPoolConfig,
+    repair: bool,
+    log: ReplicaLogger,
+) -> Result<ConsistencyReport, String> {
+    let read_only = config.persistent_pool_read_only;
+    if repair && read_only {
+        return Err("Cannot repair a pool that is opened read-only".to_string());
+    }
+    // Held until all pools are checked, so that no replica opens the pool
+    // while it is being repaired.
+    let _lock = if repair {
+        Some(
+            PersistentPoolLock::exclusive(&config.persistent_pool_db_path()).map_err(|err| {
+                format!(
+                    "The pool is in use, the replica must be stopped before repairing it: {}",
+                    err
+                )
+            })?,
+        )
+    } else {
+        None
+    };
+    let mut report = ConsistencyReport::default();
+    match config.persistent_pool_backend {
+        PersistentPoolBackend::Lmdb(lmdb_config) => {
+            report.merge(
+                crate::lmdb_pool::PersistentHeightIndexedPool::new_consensus_pool(
+                    lmdb_config.clone(),
+                    read_only,
+                    log.clone(),
+                )
+                .check_consistency(repair)?,
+            );
+            report.merge(
+                crate::lmdb_pool::PersistentHeightIndexedPool::new_certification_pool(
+                    lmdb_config.clone(),
+                    read_only,
+                    log.clone(),
+                )
+                .check_consistency(repair)?,
+            );
+            report.merge(
+                crate::lmdb_pool::PersistentEcdsaPoolSection::new_ecdsa_pool(
+                    lmdb_config,
+                    read_only,
+                    log,
+                    ic_metrics::MetricsRegistry::new(),
+                    crate::ecdsa_pool::POOL_ECDSA,
+                    crate::metrics::POOL_TYPE_VALIDATED,
+                )
+                .check_consistency(repair)?,
+            );
+        }
+        #[cfg(feature = "rocksdb_backend")]
+        PersistentPoolBackend::RocksDB(rocksdb_config) => {
+            report.merge(
+                crate::rocksdb_pool::PersistentHeightIndexedPool::new_consensus_pool(
+                    rocksdb_config.clone(),
+                    log.clone(),
+                )
+                .check_consistency(repair)?,
+            );
+            report.merge(
+                crate::rocksdb_pool::PersistentHeightIndexedPool::new_certification_pool(
+                    rocksdb_config,
+                    log,
+                )
+                .check_consistency(repair)?,
+            );
+        }
+        #[allow(unreachable_patterns)]
+        cfg => {
+            unimplemented!("Configuration {:?} is not supported", cfg)
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        consensus_pool::{MutablePoolSection, UncachedConsensusPoolImpl},
+        test_utils::random_beacon_ops,
+    };
+    use ic_logger::replica_logger::no_op_logger;
+    use ic_test_utilities::artifact_pool_config::with_test_pool_config;
+
+    #[test]
+    fn persistent_pool_is_consistent_after_mutations() {
+        with_test_pool_config(|config| {
+            let ops = random_beacon_ops();
+            let num_beacons = ops.ops.len() as u64;
+            {
+                let mut pool = UncachedConsensusPoolImpl::new(config.clone(), no_op_logger());
+                pool.validated.mutate(ops);
+            }
+
+            let report = check_persistent_pool(config.clone(), false, no_op_logger()).unwrap();
+            assert!(report.is_consistent(), "{}", report);
+            assert_eq!(report.checked, num_beacons);
+
+            let mut read_only_config = config;
+            read_only_config.persistent_pool_read_only = true;
+            assert!(check_persistent_pool(read_only_config, true, no_op_logger()).is_err());
+        })
+    }
+
+    #[test]
+    fn repair_is_refused_while_the_pool_is_in_use() {
+        with_test_pool_config(|config| {
+            {
+                let _pool = UncachedConsensusPoolImpl::new(config.clone(), no_op_logger());
+                assert!(check_persistent_pool(config.clone(), false, no_op_logger()).is_ok());
+                assert!(check_persistent_pool(config.clone(), true, no_op_logger()).is_err());
+            }
+            let report = check_persistent_pool(config.clone(), true, no_op_logger()).unwrap();
+            assert!(report.is_consistent(), "{}", report);
+
+            let _lock = PersistentPoolLock::exclusive(&config.persistent_pool_db_path()).unwrap();
+            assert!(PersistentPoolLock::shared(&config.persistent_pool_db_path()).is_err());
+        })
+    }
+}
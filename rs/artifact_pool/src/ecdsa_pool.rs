@@ -32,7 +32,7 @@ use std::convert::TryFrom;
 use std::fmt::Debug;
 use strum::IntoEnumIterator;
 
-const POOL_ECDSA: &str = "ecdsa";
+pub(crate) const POOL_ECDSA: &str = "ecdsa";
 
 /// Workaround for `EcdsaMessage` not implementing `CountBytes`.
 #[allow(dead_code)]
@@ -2,6 +2,7 @@ pub mod canister_http_pool;
 pub mod certification_pool;
 pub mod consensus_pool;
 mod consensus_pool_cache;
+pub mod consistency;
 pub mod dkg_pool;
 pub mod ecdsa_pool;
 mod height_index;
@@ -13,6 +14,7 @@ mod pool_common;
 #[cfg(test)]
 mod test_utils;
 
+pub mod archive;
 pub mod backup;
 mod lmdb_iterator;
 mod lmdb_pool;
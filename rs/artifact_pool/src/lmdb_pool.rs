@@ -1,4 +1,5 @@
 use crate::consensus_pool::{InitializablePoolSection, PoolSectionOp, PoolSectionOps};
+use crate::consistency::{ConsistencyReport, InconsistencyKind};
 use crate::lmdb_iterator::{LMDBEcdsaIterator, LMDBIterator};
 use crate::metrics::EcdsaPoolMetrics;
 use ic_config::artifact_pool::LMDBConfig;
@@ -39,6 +40,7 @@ use lmdb::{
     Transaction, WriteFlags,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 use std::convert::{TryFrom, TryInto};
 use std::fmt::Debug;
 use std::marker::PhantomData;
@@ -147,6 +149,21 @@ pub trait PoolArtifact: Sized {
         tx: &RoTransaction<'a>,
         log: &ReplicaLogger,
     ) -> lmdb::Result<T>;
+
+    /// Check that the bytes stored under the given key deserialize to an
+    /// object whose type, height and hash match the key. Return the keys of
+    /// other entries in the artifacts database the object depends on.
+    fn verify(
+        type_key: &TypeKey,
+        key: &IdKey,
+        bytes: &[u8],
+    ) -> Result<Vec<IdKey>, InconsistencyKind>;
+
+    /// Check that the bytes stored under the given key are a valid dependency
+    /// as returned by `verify`.
+    fn verify_dependency(_key: &IdKey, _bytes: &[u8]) -> bool {
+        false
+    }
 }
 
 /// A unique representation for each type of supported message.
@@ -277,6 +294,13 @@ struct ArtifactKey {
     id_key: IdKey,
 }
 
+/// A change that brings the databases of a pool back to a consistent state.
+enum Repair {
+    DeleteIndexEntry(TypeKey, HeightKey, IdKey),
+    DeleteArtifact(IdKey),
+    UpdateMeta(TypeKey, Option<Meta>),
+}
+
 /// Like log_err, but won't log the error if it matches the given error code.
 macro_rules! log_err_except {
     ($r:expr, $log:expr, $code:pat, $reason:expr) => {
@@ -586,6 +610,163 @@ impl<Artifact: PoolArtifact> PersistentHeightIndexedPool<Artifact> {
         }
         Ok(())
     }
+
+    /// Check the index databases against the artifacts database, and the
+    /// meta data against the index databases. If `repair` is true, the
+    /// inconsistencies are removed in the same transaction.
+    fn check_consistency_of(
+        &self,
+        pool: &'static str,
+        repair: bool,
+    ) -> lmdb::Result<ConsistencyReport> {
+        let mut report = ConsistencyReport::default();
+        if repair {
+            let mut tx = self.db_env.begin_rw_txn()?;
+            for change in self.tx_check_consistency(&mut tx, pool, &mut report)? {
+                self.tx_repair(&mut tx, change)?;
+            }
+            tx.commit()?;
+            report.repaired = true;
+        } else {
+            let mut tx = self.db_env.begin_ro_txn()?;
+            self.tx_check_consistency(&mut tx, pool, &mut report)?;
+        }
+        Ok(report)
+    }
+
+    /// Report all inconsistencies visible to the given transaction, and return
+    /// the changes that would remove them.
+    fn tx_check_consistency<Tx: Transaction>(
+        &self,
+        tx: &mut Tx,
+        pool: &'static str,
+        report: &mut ConsistencyReport,
+    ) -> lmdb::Result<Vec<Repair>> {
+        let mut repairs = Vec::new();
+        // Artifacts referred to by valid index entries, and their dependencies.
+        let mut referenced = BTreeSet::new();
+        // Artifacts whose index entries are removed because of their content.
+        let mut unindexed = BTreeSet::new();
+        for (type_key, index_db) in self.indices.iter() {
+            let entries = {
+                let mut cursor = tx.open_ro_cursor(*index_db)?;
+                cursor
+                    .iter_start()
+                    .map(|entry| {
+                        entry.map(|(key, value)| (HeightKey::from(key), IdKey::from(value)))
+                    })
+                    .collect::<lmdb::Result<Vec<_>>>()?
+            };
+            let mut range: Option<(HeightKey, HeightKey)> = None;
+            for (height_key, id_key) in entries {
+                report.checked += 1;
+                match self.tx_verify_entry(tx, type_key, height_key, &id_key)? {
+                    Ok(deps) => {
+                        range = Some(range.map_or((height_key, height_key), |(min, max)| {
+                            (min.min(height_key), max.max(height_key))
+                        }));
+                        referenced.insert(id_key);
+                        referenced.extend(deps);
+                    }
+                    Err(kind) => {
+                        let height = Some(Height::from(height_key));
+                        report.add(pool, type_key.name, kind, height, id_key.as_ref());
+                        if kind != InconsistencyKind::MissingArtifact {
+                            unindexed.insert(id_key.clone());
+                        }
+                        repairs.push(Repair::DeleteIndexEntry(*type_key, height_key, id_key));
+                    }
+                }
+            }
+            let meta = self.get_meta(tx, type_key).map(|meta| (meta.min, meta.max));
+            if meta != range {
+                report.add(
+                    pool,
+                    type_key.name,
+                    InconsistencyKind::StaleMetadata,
+                    None,
+                    &[],
+                );
+                repairs.push(Repair::UpdateMeta(
+                    *type_key,
+                    range.map(|(min, max)| Meta { min, max }),
+                ));
+            }
+        }
+
+        let mut cursor = tx.open_ro_cursor(self.artifacts)?;
+        for entry in cursor.iter_start() {
+            let (key, bytes) = entry?;
+            let id_key = IdKey::from(key);
+            if referenced.contains(&id_key) {
+                continue;
+            }
+            if unindexed.contains(&id_key) {
+                // Already reported through its index entry.
+                repairs.push(Repair::DeleteArtifact(id_key));
+                continue;
+            }
+            report.checked += 1;
+            // Dependencies of removed artifacts are kept until they are purged.
+            if !Artifact::verify_dependency(&id_key, bytes) {
+                let height = (id_key.0.len() >= 8).then(|| id_key.height());
+                report.add(
+                    pool,
+                    "ARTS",
+                    InconsistencyKind::OrphanedArtifact,
+                    height,
+                    id_key.as_ref(),
+                );
+                repairs.push(Repair::DeleteArtifact(id_key));
+            }
+        }
+        Ok(repairs)
+    }
+
+    /// Verify a single index entry against the artifacts database. Return the
+    /// dependencies of the artifact if it is valid.
+    fn tx_verify_entry<Tx: Transaction>(
+        &self,
+        tx: &Tx,
+        type_key: &TypeKey,
+        height_key: HeightKey,
+        id_key: &IdKey,
+    ) -> lmdb::Result<Result<Vec<IdKey>, InconsistencyKind>> {
+        if id_key.0.len() < 8 || id_key.height() != Height::from(height_key) {
+            return Ok(Err(InconsistencyKind::KeyMismatch));
+        }
+        let bytes = match tx.get(self.artifacts, id_key) {
+            Err(lmdb::Error::NotFound) => return Ok(Err(InconsistencyKind::MissingArtifact)),
+            result => result?,
+        };
+        let deps = match Artifact::verify(type_key, id_key, bytes) {
+            Ok(deps) => deps,
+            Err(kind) => return Ok(Err(kind)),
+        };
+        for dep in deps.iter() {
+            match tx.get(self.artifacts, dep) {
+                Ok(bytes) if Artifact::verify_dependency(dep, bytes) => (),
+                Ok(_) | Err(lmdb::Error::NotFound) => {
+                    return Ok(Err(InconsistencyKind::MissingBlockPayload))
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(Ok(deps))
+    }
+
+    /// Apply a single repair.
+    fn tx_repair<'a>(&self, tx: &mut RwTransaction<'a>, repair: Repair) -> lmdb::Result<()> {
+        match repair {
+            Repair::DeleteIndexEntry(type_key, height_key, id_key) => {
+                let index_db = self.get_index_db(&type_key);
+                tx.del(index_db, &height_key, Some(id_key.as_ref()))
+            }
+            Repair::DeleteArtifact(id_key) => tx.del(self.artifacts, &id_key, None),
+            Repair::UpdateMeta(type_key, Some(meta)) => self.update_meta(tx, &type_key, &meta),
+            Repair::UpdateMeta(type_key, None) => tx.del(self.meta, &type_key, None),
+        }
+    }
 }
 
 impl InitializablePoolSection for PersistentHeightIndexedPool<ConsensusMessage> {
@@ -916,6 +1097,50 @@ impl PoolArtifact for ConsensusMessage {
         )
         .ok_or(lmdb::Error::Panic)
     }
+
+    fn verify(
+        type_key: &TypeKey,
+        key: &IdKey,
+        bytes: &[u8],
+    ) -> Result<Vec<IdKey>, InconsistencyKind> {
+        let artifact = bincode::deserialize::<Self::ObjectType>(bytes)
+            .map_err(|_| InconsistencyKind::CorruptArtifact)?;
+        let msg = ConsensusMessage::try_from(artifact.msg)
+            .map_err(|_| InconsistencyKind::CorruptArtifact)?;
+        let expected = ArtifactKey::from(msg.get_id());
+        if expected.type_key != *type_key || expected.id_key != *key {
+            return Err(InconsistencyKind::KeyMismatch);
+        }
+        match &msg {
+            // The payload of a stored proposal is empty, so only the block hash
+            // can be checked here. The payload itself is checked by
+            // verify_dependency.
+            ConsensusMessage::BlockProposal(proposal) => {
+                if &ic_crypto::crypto_hash(proposal.as_ref()) != proposal.content.get_hash() {
+                    return Err(InconsistencyKind::KeyMismatch);
+                }
+                let block = proposal.as_ref();
+                Ok(vec![IdKey::from((
+                    block.height(),
+                    block.payload.get_hash().get_ref(),
+                ))])
+            }
+            ConsensusMessage::CatchUpPackage(cup) if !cup.check_integrity() => {
+                Err(InconsistencyKind::KeyMismatch)
+            }
+            ConsensusMessage::CatchUpPackageShare(share) if !share.check_integrity() => {
+                Err(InconsistencyKind::KeyMismatch)
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Block payloads are the only dependencies of consensus artifacts.
+    fn verify_dependency(key: &IdKey, bytes: &[u8]) -> bool {
+        bincode::deserialize::<BlockPayload>(bytes).map_or(false, |payload| {
+            key.0.len() > 8 && ic_crypto::crypto_hash(&payload).get_ref() == &key.hash()
+        })
+    }
 }
 
 /// Block payloads are loaded separately on demand.
@@ -945,6 +1170,12 @@ impl PersistentHeightIndexedPool<ConsensusMessage> {
         PersistentHeightIndexedPool::new(path.as_path(), read_only, log)
     }
 
+    /// Check the consistency of the pool, see [crate::consistency].
+    pub(crate) fn check_consistency(&self, repair: bool) -> Result<ConsistencyReport, String> {
+        self.check_consistency_of("consensus", repair)
+            .map_err(|err| format!("Error checking consensus pool: {:?}", err))
+    }
+
     fn tx_mutate(&mut self, ops: PoolSectionOps<ValidatedConsensusArtifact>) -> lmdb::Result<()> {
         let mut tx = self.db_env.begin_rw_txn()?;
         for op in ops.ops {
@@ -1088,15 +1319,32 @@ impl PoolSection<ValidatedConsensusArtifact> for PersistentHeightIndexedPool<Con
             .catch_up_package()
             .max_height()
             .expect("There should always be a CUP in the pool.");
-        let key = HeightKey::from(h);
+        self.catch_up_package_protos(HeightRange::new(h, h))
+            .next()
+            .unwrap_or_else(|| {
+                panic!(
+                    "This should be impossible since we found a max height at {:?}",
+                    h
+                )
+            })
+    }
+
+    fn catch_up_package_protos(
+        &self,
+        range: HeightRange,
+    ) -> Box<dyn Iterator<Item = pb::CatchUpPackage>> {
+        let bounds = match self.catch_up_package().height_range() {
+            Some(bounds) => bounds,
+            None => return Box::new(std::iter::empty()),
+        };
         let index_db = self.get_index_db(&CatchUpPackage::type_key());
         let log = self.log.clone();
         let artifacts = self.artifacts;
-        LMDBIterator::new(
+        Box::new(LMDBIterator::new(
             self.db_env.clone(),
             index_db,
-            key,
-            key,
+            HeightKey::from(range.min.max(bounds.min)),
+            HeightKey::from(range.max.min(bounds.max)),
             move |tx: &RoTransaction<'_>, key: &[u8]| {
                 let bytes = tx.get(artifacts, &key)?;
                 let artifact = log_err!(
@@ -1114,14 +1362,7 @@ impl PoolSection<ValidatedConsensusArtifact> for PersistentHeightIndexedPool<Con
                 }
             },
             self.log.clone(),
-        )
-        .next()
-        .unwrap_or_else(|| {
-            panic!(
-                "This should be impossible since we found a max height at {:?}",
-                h
-            )
-        })
+        ))
     }
 
     /// Number of artifacts in the DB.
@@ -1203,6 +1444,29 @@ impl PoolArtifact for CertificationMessage {
         )
         .ok_or(lmdb::Error::Panic)
     }
+
+    fn verify(
+        type_key: &TypeKey,
+        key: &IdKey,
+        bytes: &[u8],
+    ) -> Result<Vec<IdKey>, InconsistencyKind> {
+        let msg = bincode::deserialize::<Self::ObjectType>(bytes)
+            .map_err(|_| InconsistencyKind::CorruptArtifact)?;
+        let (expected_type_key, expected_key) = match &msg {
+            CertificationMessage::Certification(value) => (
+                CERTIFICATION_KEY,
+                IdKey::from((value.height(), ic_crypto::crypto_hash(value).get_ref())),
+            ),
+            CertificationMessage::CertificationShare(value) => (
+                CERTIFICATION_SHARE_KEY,
+                IdKey::from((value.height(), ic_crypto::crypto_hash(value).get_ref())),
+            ),
+        };
+        if expected_type_key != *type_key || expected_key != *key {
+            return Err(InconsistencyKind::KeyMismatch);
+        }
+        Ok(Vec::new())
+    }
 }
 
 impl PersistentHeightIndexedPool<CertificationMessage> {
@@ -1237,6 +1501,12 @@ impl PersistentHeightIndexedPool<CertificationMessage> {
         self.tx_purge_below(&mut tx, HeightKey::from(height))?;
         tx.commit()
     }
+
+    /// Check the consistency of the pool, see [crate::consistency].
+    pub(crate) fn check_consistency(&self, repair: bool) -> Result<ConsistencyReport, String> {
+        self.check_consistency_of("certification", repair)
+            .map_err(|err| format!("Error checking certification pool: {:?}", err))
+    }
 }
 
 impl crate::certification_pool::MutablePoolSection
@@ -1576,6 +1846,61 @@ impl PersistentEcdsaPoolSection {
             .unwrap()
     }
 
+    /// Check that every stored message deserializes and matches its key and
+    /// database, see [crate::consistency]. If `repair` is true, the messages
+    /// that do not are removed.
+    pub(crate) fn check_consistency(&self, repair: bool) -> Result<ConsistencyReport, String> {
+        self.check_consistency_of(repair)
+            .map_err(|err| format!("Error checking ECDSA pool: {:?}", err))
+    }
+
+    fn check_consistency_of(&self, repair: bool) -> lmdb::Result<ConsistencyReport> {
+        let mut report = ConsistencyReport::default();
+        if repair {
+            let mut tx = self.db_env.begin_rw_txn()?;
+            for (db, key) in self.tx_check_consistency(&tx, &mut report)? {
+                tx.del(db, &key, None)?;
+            }
+            tx.commit()?;
+            report.repaired = true;
+        } else {
+            let tx = self.db_env.begin_ro_txn()?;
+            self.tx_check_consistency(&tx, &mut report)?;
+        }
+        Ok(report)
+    }
+
+    /// Report all invalid messages visible to the given transaction, and
+    /// return their databases and keys.
+    fn tx_check_consistency<Tx: Transaction>(
+        &self,
+        tx: &Tx,
+        report: &mut ConsistencyReport,
+    ) -> lmdb::Result<Vec<(Database, Vec<u8>)>> {
+        let mut invalid = Vec::new();
+        for (message_type, message_db) in self.message_dbs.iter() {
+            let type_key = Self::get_type_key(*message_type);
+            let mut cursor = tx.open_ro_cursor(message_db.db)?;
+            for entry in cursor.iter_start() {
+                let (key, bytes) = entry?;
+                report.checked += 1;
+                let kind = match bincode::deserialize::<EcdsaMessage>(bytes) {
+                    Err(_) => InconsistencyKind::CorruptArtifact,
+                    Ok(message)
+                        if EcdsaMessageType::from(&message) != *message_type
+                            || IdKey::from(ecdsa_msg_id(&message)).as_ref() != key =>
+                    {
+                        InconsistencyKind::KeyMismatch
+                    }
+                    Ok(_) => continue,
+                };
+                report.add("ecdsa", type_key.name, kind, None, key);
+                invalid.push((message_db.db, key.to_vec()));
+            }
+        }
+        Ok(invalid)
+    }
+
     fn get_type_key(message_type: EcdsaMessageType) -> TypeKey {
         match message_type {
             EcdsaMessageType::Dealing => TypeKey::new("ECD"),
@@ -1731,7 +2056,7 @@ mod tests {
     use super::*;
     use crate::{
         consensus_pool::MutablePoolSection,
-        test_utils::{fake_random_beacon, random_beacon_ops, PoolTestHelper},
+        test_utils::{block_proposal_ops, fake_random_beacon, random_beacon_ops, PoolTestHelper},
     };
     use ic_test_utilities::with_test_replica_logger;
     use std::{panic, path::PathBuf};
@@ -1864,4 +2189,59 @@ mod tests {
     fn test_timestamp_survives_reboot() {
         crate::test_utils::test_timestamp_survives_reboot::<LMDBConfig>()
     }
+
+    #[test]
+    fn test_consistency_check_finds_and_repairs_inconsistencies() {
+        run_persistent_pool_test("test_consistency_check", |config, log| {
+            let mut pool = PersistentHeightIndexedPool::new_consensus_pool(config, false, log);
+            let rb_ops = random_beacon_ops();
+            let bp_ops = block_proposal_ops();
+            pool.mutate(rb_ops.clone());
+            pool.mutate(bp_ops.clone());
+            let report = pool.check_consistency(false).unwrap();
+            assert!(report.is_consistent(), "{}", report);
+            assert!(!report.repaired);
+
+            // Remove the highest random beacon and a block payload behind the
+            // back of the indices, and add an artifact that is not indexed.
+            let max_height = pool.random_beacon().max_height().unwrap();
+            let beacon = pool.random_beacon().get_highest().unwrap();
+            let proposal = pool
+                .block_proposal()
+                .get_by_height(Height::from(5))
+                .next()
+                .unwrap();
+            {
+                let mut tx = pool.db_env.begin_rw_txn().unwrap();
+                tx.del(pool.artifacts, &IdKey::from(&beacon.get_id()), None)
+                    .unwrap();
+                let block = proposal.as_ref();
+                let payload_key = IdKey::from((block.height(), block.payload.get_hash().get_ref()));
+                tx.del(pool.artifacts, &payload_key, None).unwrap();
+                let orphan_key = IdKey::from((Height::from(7), &CryptoHash(vec![1; 32])));
+                tx.put(pool.artifacts, &orphan_key, &[0xff], WriteFlags::empty())
+                    .unwrap();
+                tx.commit().unwrap();
+            }
+
+            let report = pool.check_consistency(true).unwrap();
+            assert_eq!(report.count(InconsistencyKind::MissingArtifact), 1);
+            assert_eq!(report.count(InconsistencyKind::StaleMetadata), 1);
+            assert_eq!(report.count(InconsistencyKind::MissingBlockPayload), 1);
+            assert_eq!(report.count(InconsistencyKind::OrphanedArtifact), 1);
+            assert_eq!(report.inconsistencies.len(), 4, "{}", report);
+            assert!(report.repaired);
+
+            let report = pool.check_consistency(false).unwrap();
+            assert!(report.is_consistent(), "{}", report);
+            assert_eq!(
+                pool.random_beacon().max_height(),
+                Some(max_height.decrement())
+            );
+            assert_eq!(pool.random_beacon().get_all().count(), rb_ops.ops.len() - 1);
+            let proposals = pool.block_proposal().get_all().collect::<Vec<_>>();
+            assert_eq!(proposals.len(), bp_ops.ops.len() - 1);
+            assert!(proposals.iter().all(|proposal| proposal.check_integrity()));
+        });
+    }
 }
@@ -2,6 +2,7 @@
 use crate::consensus_pool::{
     InitializablePoolSection, MutablePoolSection, PoolSectionOp, PoolSectionOps,
 };
+use crate::consistency::{ConsistencyReport, InconsistencyKind};
 use crate::rocksdb_iterator::{StandaloneIterator, StandaloneSnapshot};
 use bincode::{deserialize, serialize};
 use byteorder::{BigEndian, ReadBytesExt};
@@ -22,9 +23,9 @@ use ic_types::{
         catchup::CUPWithOriginalProtobuf,
         certification::{Certification, CertificationMessage, CertificationShare},
         dkg::Dealings,
-        BlockProposal, CatchUpPackage, CatchUpPackageShare, ConsensusMessage, ConsensusMessageHash,
-        Finalization, FinalizationShare, HasHeight, Notarization, NotarizationShare, Payload,
-        RandomBeacon, RandomBeaconShare, RandomTape, RandomTapeShare,
+        BlockPayload, BlockProposal, CatchUpPackage, CatchUpPackageShare, ConsensusMessage,
+        ConsensusMessageHash, Finalization, FinalizationShare, HasHeight, Notarization,
+        NotarizationShare, Payload, RandomBeacon, RandomBeaconShare, RandomTape, RandomTapeShare,
     },
     Height, Time,
 };
@@ -32,6 +33,7 @@ use rocksdb::{
     compaction_filter::{CompactionFilterFn, Decision},
     ColumnFamilyDescriptor, DBCompressionType, Options, WriteBatch, DB,
 };
+use std::collections::BTreeSet;
 use std::convert::TryFrom;
 use std::marker::PhantomData;
 use std::path::PathBuf;
@@ -328,6 +330,40 @@ impl<T: HasCFInfos> PersistentHeightIndexedPool<T> {
             })
     }
 
+    /// Verify every entry at or above the watermark in the given column
+    /// family. The entries for which `verify` fails are reported, and their
+    /// removal is added to `batch`.
+    fn check_column_family<F>(
+        &self,
+        pool: &'static str,
+        info: &ArtifactCFInfo,
+        report: &mut ConsistencyReport,
+        batch: &mut WriteBatch,
+        mut verify: F,
+    ) where
+        F: FnMut(&[u8], &[u8]) -> Result<(), InconsistencyKind>,
+    {
+        let cf_handle = check_not_none_uw!(self.db.cf_handle(info.name));
+        let mut read_options = rocksdb::ReadOptions::default();
+        read_options.set_total_order_seek(true);
+        let mut iter = self.db.raw_iterator_cf_opt(cf_handle, read_options);
+        iter.seek(make_min_key(self.watermark.read().unwrap().get()));
+        while let (Some(key), Some(value)) = (iter.key(), iter.value()) {
+            report.checked += 1;
+            let result = if key.len() == KEY_SIZE {
+                verify(key, value)
+            } else {
+                Err(InconsistencyKind::KeyMismatch)
+            };
+            if let Err(kind) = result {
+                let height = (key.len() >= HASH_POS).then(|| Height::new(decompose_key(key).0));
+                report.add(pool, info.name, kind, height, key);
+                batch.delete_cf(cf_handle, key);
+            }
+            iter.next();
+        }
+    }
+
     /// Returns the key to use for looking up the given consensus message
     fn lookup_key(&self, msg_id: &ConsensusMessageId) -> Option<Vec<u8>> {
         let key = make_key(msg_id.height.get(), &msg_id.hash.digest().0);
@@ -383,6 +419,105 @@ impl PersistentHeightIndexedPool<ConsensusMessage> {
         }
         new_pool_snapshot_iterator(self.db.clone(), min_key.max(watermark), max_key)
     }
+
+    /// Check the consistency of the pool, see [crate::consistency]. Every
+    /// artifact must match its key, every block proposal must have a valid
+    /// payload, and every payload must belong to a block proposal.
+    pub(crate) fn check_consistency(&self, repair: bool) -> Result<ConsistencyReport, String> {
+        let mut report = ConsistencyReport::default();
+        let mut batch = WriteBatch::default();
+        let mut payload_keys = BTreeSet::new();
+        let cf_handle_payload = check_not_none_uw!(self.db.cf_handle(BLOCK_PAYLOAD_CF_INFO.name));
+        for info in CONSENSUS_CF_INFOS
+            .iter()
+            .filter(|info| info.name != BLOCK_PAYLOAD_CF_INFO.name)
+        {
+            self.check_column_family("consensus", info, &mut report, &mut batch, |key, bytes| {
+                if let Some(payload_key) = verify_consensus_artifact(info, key, bytes)? {
+                    match check_ok_uw!(self.db.get_cf(cf_handle_payload, &payload_key)) {
+                        Some(bytes) if verify_block_payload(&payload_key, &bytes) => {
+                            payload_keys.insert(payload_key);
+                        }
+                        _ => return Err(InconsistencyKind::MissingBlockPayload),
+                    }
+                }
+                Ok(())
+            });
+        }
+        self.check_column_family(
+            "consensus",
+            &BLOCK_PAYLOAD_CF_INFO,
+            &mut report,
+            &mut batch,
+            |key, _| {
+                if payload_keys.contains(key) {
+                    Ok(())
+                } else {
+                    Err(InconsistencyKind::OrphanedArtifact)
+                }
+            },
+        );
+        if repair {
+            self.db
+                .write(batch)
+                .map_err(|err| format!("Error repairing consensus pool: {}", err))?;
+            report.repaired = true;
+        }
+        Ok(report)
+    }
+}
+
+/// Check that the bytes stored under the given key in the given column family
+/// are a consensus artifact that matches the key. Return the key of its
+/// payload if the artifact is a block proposal.
+fn verify_consensus_artifact(
+    info: &ArtifactCFInfo,
+    key: &[u8],
+    bytes: &[u8],
+) -> Result<Option<Vec<u8>>, InconsistencyKind> {
+    let artifact: ValidatedConsensusArtifact = deserialize(bytes)
+        .ok()
+        .or_else(|| {
+            let artifact = deserialize_catch_up_package(bytes)?;
+            CatchUpPackage::try_from(&artifact.msg)
+                .ok()
+                .map(|cup| ValidatedConsensusArtifact {
+                    timestamp: artifact.timestamp,
+                    msg: ConsensusMessage::CatchUpPackage(cup),
+                })
+        })
+        .ok_or(InconsistencyKind::CorruptArtifact)?;
+    let (expected_info, height) = info_and_height_for_msg(&artifact.msg);
+    if expected_info.name != info.name
+        || make_key(height.get(), &artifact.msg.get_cm_hash().digest().0) != key
+    {
+        return Err(InconsistencyKind::KeyMismatch);
+    }
+    match &artifact.msg {
+        // The payload of a stored proposal is empty, so only the block hash can
+        // be checked here.
+        ConsensusMessage::BlockProposal(proposal) => {
+            if &ic_crypto::crypto_hash(proposal.as_ref()) != proposal.content.get_hash() {
+                return Err(InconsistencyKind::KeyMismatch);
+            }
+            let payload_hash = proposal.as_ref().payload.get_hash();
+            Ok(Some(make_key(height.get(), &payload_hash.get_ref().0)))
+        }
+        ConsensusMessage::CatchUpPackage(cup) if !cup.check_integrity() => {
+            Err(InconsistencyKind::KeyMismatch)
+        }
+        ConsensusMessage::CatchUpPackageShare(share) if !share.check_integrity() => {
+            Err(InconsistencyKind::KeyMismatch)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Check that the given bytes are a block payload whose hash matches the key.
+fn verify_block_payload(key: &[u8], bytes: &[u8]) -> bool {
+    deserialize::<BlockPayload>(bytes).map_or(false, |payload| {
+        ic_crypto::crypto_hash(&payload).get_ref().0 == key[HASH_POS..]
+    })
 }
 
 impl InitializablePoolSection for PersistentHeightIndexedPool<ConsensusMessage> {
@@ -641,19 +776,24 @@ impl PoolSection<ValidatedConsensusArtifact> for PersistentHeightIndexedPool<Con
     }
 
     fn highest_catch_up_package_proto(&self) -> pb::CatchUpPackage {
-        let height_opt = self.max_height::<CatchUpPackage>().unwrap();
-        let min_height_key = make_min_key(height_opt.get());
-        let max_height_key = make_max_key(height_opt.get());
-        let mut iter = check_ok_uw!(StandaloneIterator::new(
+        let height = self.max_height::<CatchUpPackage>().unwrap();
+        self.catch_up_package_protos(HeightRange::new(height, height))
+            .next()
+            .expect("There must be a catch up package in the pool")
+    }
+
+    fn catch_up_package_protos(
+        &self,
+        range: HeightRange,
+    ) -> Box<dyn Iterator<Item = pb::CatchUpPackage>> {
+        let iter = check_ok_uw!(StandaloneIterator::new(
             self.db.clone(),
             CatchUpPackage::info().name,
-            &min_height_key,
-            &max_height_key,
+            &make_min_key(range.min.get()),
+            &make_max_key(range.max.get()),
             deserialize_catch_up_package_fn
         ));
-        iter.next()
-            .expect("There must be a catch up package in the pool")
-            .msg
+        Box::new(iter.map(|artifact| artifact.msg))
     }
 
     // TODO(CON-308): Implement size()
@@ -997,6 +1137,24 @@ fn deserialize_certification_artifact<T: CertificationType + 'static>(
     T::extract(bytes)
 }
 
+/// Check that the given bytes are a certification artifact of type `T` that
+/// matches the key.
+fn verify_certification_artifact<T: CertificationType + CryptoHashable + HasHeight>(
+    key: &[u8],
+    bytes: &[u8],
+) -> Result<(), InconsistencyKind> {
+    let value = T::extract(bytes).ok_or(InconsistencyKind::CorruptArtifact)?;
+    if make_key(
+        value.height().get(),
+        &ic_crypto::crypto_hash(&value).get().0,
+    ) == key
+    {
+        Ok(())
+    } else {
+        Err(InconsistencyKind::KeyMismatch)
+    }
+}
+
 pub trait CertificationType: Sized {
     fn extract(bytes: &[u8]) -> Option<Self>;
 }
@@ -1042,6 +1200,33 @@ impl PersistentHeightIndexedPool<CertificationMessage> {
             .put_cf(cf_handle, key, check_ok_uw!(serialize(value))));
     }
 
+    /// Check that every artifact matches its key, see [crate::consistency].
+    pub(crate) fn check_consistency(&self, repair: bool) -> Result<ConsistencyReport, String> {
+        let mut report = ConsistencyReport::default();
+        let mut batch = WriteBatch::default();
+        self.check_column_family(
+            "certification",
+            &CERTIFICATION_CF_INFO,
+            &mut report,
+            &mut batch,
+            verify_certification_artifact::<Certification>,
+        );
+        self.check_column_family(
+            "certification",
+            &CERTIFICATION_SHARE_CF_INFO,
+            &mut report,
+            &mut batch,
+            verify_certification_artifact::<CertificationShare>,
+        );
+        if repair {
+            self.db
+                .write(batch)
+                .map_err(|err| format!("Error repairing certification pool: {}", err))?;
+            report.repaired = true;
+        }
+        Ok(report)
+    }
+
     pub fn iterate<Message: CertificationType + PerTypeCFInfo + 'static>(
         &self,
         min_key: &[u8],
@@ -1252,6 +1437,53 @@ mod tests {
         crate::test_utils::test_persistent_pool_path_is_cleanedup_after_tests::<RocksDBConfig>()
     }
 
+    #[test]
+    fn test_consistency_check_finds_and_repairs_inconsistencies() {
+        run_persistent_pool_test("test_consistency_check", |config, log| {
+            let mut pool = PersistentHeightIndexedPool::new_consensus_pool(config, log);
+            let rb_ops = random_beacon_ops();
+            let bp_ops = block_proposal_ops();
+            pool.mutate(rb_ops.clone());
+            pool.mutate(bp_ops.clone());
+            let report = pool.check_consistency(false).unwrap();
+            assert!(report.is_consistent(), "{}", report);
+
+            // Remove a block payload, overwrite a random beacon with garbage
+            // and add a payload that no block proposal refers to.
+            let beacon = pool
+                .random_beacon()
+                .get_by_height(Height::from(10))
+                .next()
+                .unwrap();
+            let proposal = pool
+                .block_proposal()
+                .get_by_height(Height::from(5))
+                .next()
+                .unwrap();
+            let cf_handle = check_not_none_uw!(pool.db.cf_handle(RANDOM_BEACON_CF_INFO.name));
+            let beacon_key = make_key(10, &beacon.get_cm_hash().digest().0);
+            check_ok!(pool.db.put_cf(cf_handle, beacon_key, [0xff]));
+            let cf_handle = check_not_none_uw!(pool.db.cf_handle(BLOCK_PAYLOAD_CF_INFO.name));
+            let payload_key = make_key(5, &proposal.as_ref().payload.get_hash().get_ref().0);
+            check_ok!(pool.db.delete_cf(cf_handle, payload_key));
+            check_ok!(pool.db.put_cf(cf_handle, make_key(7, &[1; 32]), [0xff]));
+
+            let report = pool.check_consistency(true).unwrap();
+            assert_eq!(report.count(InconsistencyKind::CorruptArtifact), 1);
+            assert_eq!(report.count(InconsistencyKind::MissingBlockPayload), 1);
+            assert_eq!(report.count(InconsistencyKind::OrphanedArtifact), 1);
+            assert_eq!(report.inconsistencies.len(), 3, "{}", report);
+            assert!(report.repaired);
+
+            let report = pool.check_consistency(false).unwrap();
+            assert!(report.is_consistent(), "{}", report);
+            assert_eq!(pool.random_beacon().get_all().count(), rb_ops.ops.len() - 1);
+            let proposals = pool.block_proposal().get_all().collect::<Vec<_>>();
+            assert_eq!(proposals.len(), bp_ops.ops.len() - 1);
+            assert!(proposals.iter().all(|proposal| proposal.check_integrity()));
+        });
+    }
+
     // Test if purge survives reboot.
     #[test]
     fn test_purge_survives_reboot() {
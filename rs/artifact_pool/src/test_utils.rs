@@ -406,7 +406,7 @@ pub(crate) fn random_beacon_ops() -> PoolSectionOps<ValidatedConsensusArtifact>
     ops
 }
 
-fn block_proposal_ops() -> PoolSectionOps<ValidatedConsensusArtifact> {
+pub(crate) fn block_proposal_ops() -> PoolSectionOps<ValidatedConsensusArtifact> {
     let mut ops = PoolSectionOps::new();
     for i in 1..18 {
         let block_proposal = fake_block_proposal(Height::from(i));
@@ -713,6 +713,7 @@ impl BlockchainManager {
 
             blockchain.prune_blocks(&processed_block_hashes);
             blockchain.prune_blocks_below_height(filter_height);
+            blockchain.prune_headers_below_anchor(&anchor);
 
             self.getdata_request_info.retain(|b, _| {
                 blockchain.get_cached_header(b).map_or(0, |c| c.height) >= filter_height
@@ -767,6 +768,7 @@ pub mod test {
     };
     use hex::FromHex;
     use ic_logger::replica_logger::no_op_logger;
+    use ic_metrics::MetricsRegistry;
     use std::net::SocketAddr;
     use std::str::FromStr;
 
@@ -777,7 +779,8 @@ pub mod test {
         let addr = SocketAddr::from_str("127.0.0.1:8333").expect("bad address format");
         let mut channel = TestChannel::new(vec![addr]);
         let config = ConfigBuilder::new().build();
-        let blockchain_state = BlockchainState::new(&config);
+        let blockchain_state =
+            BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let genesis = blockchain_state.genesis().clone();
         let genesis_hash = genesis.header.block_hash();
         let mut blockchain_manager = BlockchainManager::new(
@@ -836,7 +839,8 @@ pub mod test {
         let sockets = vec![addr1, addr2];
         let mut channel = TestChannel::new(sockets.clone());
         let config = ConfigBuilder::new().with_network(Network::Regtest).build();
-        let blockchain_state = BlockchainState::new(&config);
+        let blockchain_state =
+            BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let genesis = blockchain_state.genesis().clone();
         let genesis_hash = genesis.header.block_hash();
         let mut blockchain_manager = BlockchainManager::new(
@@ -936,7 +940,8 @@ pub mod test {
         let sockets = vec![SocketAddr::from_str("127.0.0.1:8333").expect("bad address format")];
         let mut channel = TestChannel::new(sockets.clone());
         let config = ConfigBuilder::new().with_network(Network::Regtest).build();
-        let blockchain_state = BlockchainState::new(&config);
+        let blockchain_state =
+            BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let genesis = blockchain_state.genesis().clone();
         let genesis_hash = genesis.header.block_hash();
         let mut blockchain_manager = BlockchainManager::new(
@@ -1033,7 +1038,8 @@ pub mod test {
         let sockets = vec![peer_addr];
         let mut channel = TestChannel::new(sockets.clone());
         let config = ConfigBuilder::new().build();
-        let blockchain_state = BlockchainState::new(&config);
+        let blockchain_state =
+            BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let mut blockchain_manager = BlockchainManager::new(
             &config,
             Arc::new(Mutex::new(blockchain_state)),
@@ -1126,7 +1132,8 @@ pub mod test {
         let sockets = vec![addr];
         let mut channel = TestChannel::new(sockets.clone());
         let config = ConfigBuilder::new().with_network(Network::Regtest).build();
-        let blockchain_state = BlockchainState::new(&config);
+        let blockchain_state =
+            BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let genesis = blockchain_state.genesis().clone();
         let genesis_hash = genesis.header.block_hash();
         let mut blockchain_manager = BlockchainManager::new(
@@ -1170,7 +1177,8 @@ pub mod test {
         let sockets = vec![addr];
         let mut channel = TestChannel::new(sockets.clone());
         let config = ConfigBuilder::new().build();
-        let blockchain_state = BlockchainState::new(&config);
+        let blockchain_state =
+            BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let test_state = TestState::setup();
         let block_1_hash = test_state.block_1.block_hash();
         let mut blockchain_manager = BlockchainManager::new(
@@ -1231,7 +1239,8 @@ pub mod test {
         let sockets = vec![addr];
         let mut channel = TestChannel::new(sockets.clone());
         let config = ConfigBuilder::new().build();
-        let blockchain_state = BlockchainState::new(&config);
+        let blockchain_state =
+            BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let test_state = TestState::setup();
         let block_1_hash = test_state.block_1.block_hash();
         let mut blockchain_manager = BlockchainManager::new(
@@ -1295,7 +1304,8 @@ pub mod test {
         let sockets = vec![addr];
         let mut channel = TestChannel::new(sockets.clone());
         let config = ConfigBuilder::new().with_network(Network::Regtest).build();
-        let blockchain_state = BlockchainState::new(&config);
+        let blockchain_state =
+            BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let genesis = blockchain_state.genesis().clone();
 
         let mut blockchain_manager = BlockchainManager::new(
@@ -1383,7 +1393,8 @@ pub mod test {
         let sockets = vec![peer_addr];
         let mut channel = TestChannel::new(sockets.clone());
         let config = ConfigBuilder::new().build();
-        let blockchain_state = BlockchainState::new(&config);
+        let blockchain_state =
+            BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let mut blockchain_manager = BlockchainManager::new(
             &config,
             Arc::new(Mutex::new(blockchain_state)),
@@ -1438,7 +1449,8 @@ pub mod test {
     #[tokio::test]
     async fn test_enqueue_new_blocks_to_download() {
         let config = ConfigBuilder::new().with_network(Network::Regtest).build();
-        let blockchain_state = BlockchainState::new(&config);
+        let blockchain_state =
+            BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let genesis = blockchain_state.genesis().clone();
         let genesis_hash = genesis.header.block_hash();
         let mut blockchain_manager = BlockchainManager::new(
@@ -1474,7 +1486,8 @@ pub mod test {
     #[tokio::test]
     async fn test_enqueue_new_blocks_to_download_no_duplicates() {
         let config = ConfigBuilder::new().with_network(Network::Regtest).build();
-        let blockchain_state = BlockchainState::new(&config);
+        let blockchain_state =
+            BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let genesis = blockchain_state.genesis().clone();
         let genesis_hash = genesis.header.block_hash();
         let mut blockchain_manager = BlockchainManager::new(
@@ -1531,7 +1544,8 @@ pub mod test {
     #[tokio::test]
     async fn test_pruning_blocks_based_on_the_anchor_hash_and_processed_hashes() {
         let config = ConfigBuilder::new().with_network(Network::Regtest).build();
-        let blockchain_state = BlockchainState::new(&config);
+        let blockchain_state =
+            BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let genesis = blockchain_state.genesis().clone();
         let genesis_hash = genesis.header.block_hash();
         let mut blockchain_manager = BlockchainManager::new(
@@ -1599,7 +1613,8 @@ pub mod test {
     #[tokio::test]
     async fn test_pruning_blocks_to_ensure_it_does_not_prune_anchor_adjacent_blocks() {
         let config = ConfigBuilder::new().with_network(Network::Regtest).build();
-        let blockchain_state = BlockchainState::new(&config);
+        let blockchain_state =
+            BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let genesis = blockchain_state.genesis().clone();
         let genesis_hash = genesis.header.block_hash();
         let mut blockchain_manager = BlockchainManager::new(
@@ -1755,7 +1770,8 @@ pub mod test {
         let addr2 = SocketAddr::from_str("127.0.0.1:8444").expect("bad address format");
         let mut channel = TestChannel::new(vec![addr, addr2]);
         let config = ConfigBuilder::new().build();
-        let blockchain_state = BlockchainState::new(&config);
+        let blockchain_state =
+            BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let mut blockchain_manager = BlockchainManager::new(
             &config,
             Arc::new(Mutex::new(blockchain_state)),
@@ -1,10 +1,21 @@
-use crate::{common::BlockHeight, config::Config};
+use crate::{common::BlockHeight, config::Config, header_file::HeaderFile};
 use bitcoin::{blockdata::constants::genesis_block, Block, BlockHash, BlockHeader, Network};
 use ic_btc_validation::{validate_header, HeaderStore, ValidateHeaderError};
+use ic_logger::{error, ReplicaLogger};
+use ic_metrics::MetricsRegistry;
 use parking_lot::Mutex;
-use std::{collections::HashMap, sync::Arc};
+use prometheus::IntCounterVec;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+};
 use thiserror::Error;
 
+/// The number of pruned headers that may remain in the header file before it is
+/// rewritten. Pruned headers left in the file are added to the header cache again
+/// after a restart and pruned once more, so they only cost space.
+const MAX_STALE_HEADERS_IN_HEADER_FILE: usize = 10_000;
+
 /// This field contains the datatype used to store "work" of a Bitcoin blockchain
 pub type Work = bitcoin::util::uint::Uint256;
 
@@ -101,6 +112,44 @@ impl HeaderCache {
 
         Ok(())
     }
+
+    /// Removes the header with the given hash and all of its descendants. Returns
+    /// the hashes of the removed headers.
+    fn remove_subtree(&mut self, hash: &BlockHash) -> HashSet<BlockHash> {
+        let mut removed = HashSet::new();
+        let root = match self.headers.get(hash) {
+            Some(root) => root.clone(),
+            None => return removed,
+        };
+        if let Some(parent) = self.headers.get(&root.header.prev_blockhash) {
+            parent
+                .children
+                .lock()
+                .retain(|child| child.header.block_hash() != *hash);
+        }
+
+        let mut queue = VecDeque::from(vec![root]);
+        while let Some(cached_header) = queue.pop_front() {
+            let block_hash = cached_header.header.block_hash();
+            self.headers.remove(&block_hash);
+            removed.insert(block_hash);
+            queue.extend(cached_header.children.lock().drain(..));
+        }
+        removed
+    }
+
+    /// Returns all headers except the genesis header, with every header following
+    /// its parent.
+    fn non_genesis_headers(&self) -> Vec<BlockHeader> {
+        let mut headers = vec![];
+        let mut queue: VecDeque<CachedHeader> =
+            self.genesis.children.lock().iter().cloned().collect();
+        while let Some(cached_header) = queue.pop_front() {
+            headers.push(cached_header.header);
+            queue.extend(cached_header.children.lock().iter().cloned());
+        }
+        headers
+    }
 }
 
 /// This struct stores a BlockHeader along with its height in the Bitcoin Blockchain.
@@ -163,11 +212,33 @@ pub struct BlockchainState {
 
     /// Used to determine how validation should be handled with `validate_header`.
     network: Network,
+
+    /// The file the header cache is persisted to.
+    header_file: Option<HeaderFile>,
+
+    /// The number of headers in the header file that have been pruned from the
+    /// header cache since the file was last rewritten.
+    stale_headers_in_header_file: usize,
+
+    /// The number of stale headers above which the header file is rewritten.
+    max_stale_headers_in_header_file: usize,
+
+    /// Set if a write to the header file failed. The file may then be missing
+    /// headers or end with a partially written header, so it is rewritten from the
+    /// header cache on the next write.
+    header_file_out_of_sync: bool,
+
+    /// Counts the failed writes to the header file, by operation.
+    header_file_errors: IntCounterVec,
+
+    /// Used to report failed writes to the header file.
+    logger: ReplicaLogger,
 }
 
 impl BlockchainState {
-    /// This function is used to create a new BlockChainState object.  
-    pub fn new(config: &Config) -> Self {
+    /// This function is used to create a new BlockChainState object.
+    /// If a header cache path is configured, the headers persisted there are loaded.
+    pub fn new(config: &Config, logger: ReplicaLogger, metrics_registry: &MetricsRegistry) -> Self {
         // Create a header cache and inserting dummy header corresponding the `adapter_genesis_hash`.
         let header_cache = HeaderCache::new(config.network);
         let block_cache = HashMap::new();
@@ -177,11 +248,95 @@ impl BlockchainState {
             work: header_cache.genesis.work,
         }];
 
-        BlockchainState {
+        let mut state = BlockchainState {
             header_cache,
             block_cache,
             tips,
             network: config.network,
+            header_file: None,
+            stale_headers_in_header_file: 0,
+            max_stale_headers_in_header_file: MAX_STALE_HEADERS_IN_HEADER_FILE,
+            header_file_out_of_sync: false,
+            header_file_errors: metrics_registry.int_counter_vec(
+                "btc_adapter_header_file_errors_total",
+                "The number of failed writes to the header file, by operation.",
+                &["operation"],
+            ),
+            logger,
+        };
+
+        if let Some(path) = &config.header_cache_path {
+            let (header_file, headers) = HeaderFile::open(path).unwrap_or_else(|err| {
+                panic!(
+                    "Failed to open the header cache at {}: {}",
+                    path.display(),
+                    err
+                )
+            });
+            state.header_file = Some(header_file);
+            state.load_headers(&headers);
+        }
+
+        state
+    }
+
+    /// Adds the headers loaded from the header file. The headers are validated like
+    /// headers received from peers, which includes checking them against the
+    /// network's checkpoints. Headers that are rejected are dropped from the file
+    /// together with their descendants.
+    fn load_headers(&mut self, headers: &[BlockHeader]) {
+        let mut all_added = true;
+        for header in headers {
+            if !matches!(
+                self.add_header(*header),
+                Ok(AddHeaderResult::HeaderAdded(_))
+            ) {
+                all_added = false;
+            }
+        }
+        self.tips.sort_unstable_by(|a, b| b.work.cmp(&a.work));
+
+        if !all_added {
+            self.rewrite_header_file();
+        }
+    }
+
+    /// Appends newly added headers to the header file. If an earlier write failed,
+    /// the whole file is rewritten instead.
+    fn persist_headers(&mut self, headers: &[BlockHeader]) {
+        if headers.is_empty() {
+            return;
+        }
+        if self.header_file_out_of_sync {
+            self.rewrite_header_file();
+            return;
+        }
+        if let Some(header_file) = &mut self.header_file {
+            if let Err(err) = header_file.append(headers) {
+                error!(self.logger, "Failed to append to the header file: {}", err);
+                self.header_file_errors.with_label_values(&["append"]).inc();
+                self.header_file_out_of_sync = true;
+            }
+        }
+    }
+
+    /// Replaces the content of the header file with the headers in the cache.
+    fn rewrite_header_file(&mut self) {
+        let headers = self.header_cache.non_genesis_headers();
+        if let Some(header_file) = &mut self.header_file {
+            match header_file.rewrite(&headers) {
+                Ok(()) => {
+                    self.stale_headers_in_header_file = 0;
+                    self.header_file_out_of_sync = false;
+                }
+                Err(err) => {
+                    error!(self.logger, "Failed to rewrite the header file: {}", err);
+                    self.header_file_errors
+                        .with_label_values(&["rewrite"])
+                        .inc();
+                    self.header_file_out_of_sync = true;
+                }
+            }
         }
     }
 
@@ -212,16 +367,26 @@ impl BlockchainState {
                     added_headers.push(cached_header);
                 }
                 Ok(AddHeaderResult::HeaderAlreadyExists(_)) => {}
-                Err(err) => return (added_headers, Some(err)),
+                Err(err) => {
+                    self.persist_added_headers(&added_headers);
+                    return (added_headers, Some(err));
+                }
             }
         }
 
+        self.persist_added_headers(&added_headers);
         // Sort the tips by the total work
         self.tips.sort_unstable_by(|a, b| b.work.cmp(&a.work));
 
         (added_headers, None)
     }
 
+    /// Appends the headers that were added by `add_headers` to the header file.
+    fn persist_added_headers(&mut self, added_headers: &[CachedHeader]) {
+        let headers: Vec<BlockHeader> = added_headers.iter().map(|c| c.header).collect();
+        self.persist_headers(&headers);
+    }
+
     /// This method adds the input header to the `header_cache`.
     #[allow(clippy::indexing_slicing)]
     fn add_header(&mut self, header: BlockHeader) -> Result<AddHeaderResult, AddHeaderError> {
@@ -295,6 +460,9 @@ impl BlockchainState {
         let result = self
             .add_header(block.header)
             .map_err(AddBlockError::Header)?;
+        if let AddHeaderResult::HeaderAdded(_) = result {
+            self.persist_headers(&[block.header]);
+        }
         self.block_cache.insert(block_hash, block);
         Ok(match result {
             AddHeaderResult::HeaderAdded(cached) => cached.height,
@@ -330,6 +498,67 @@ impl BlockchainState {
         self.prune_blocks(&hashes_below_height);
     }
 
+    /// Removes the headers of forks that branch off the anchor's chain at or below
+    /// the anchor's height. As the anchor is stable, these forks can never become
+    /// part of the active chain. The header file is only rewritten once enough pruned
+    /// headers have accumulated in it.
+    pub fn prune_headers_below_anchor(&mut self, anchor: &BlockHash) {
+        let anchor = match self.header_cache.get(anchor) {
+            Some(anchor) => anchor.clone(),
+            None => return,
+        };
+
+        // For every tip that does not descend from the anchor, find the first header
+        // on its branch that is not an ancestor of the anchor.
+        let mut stale_branches = vec![];
+        for tip in &self.tips {
+            let mut branch = None;
+            let mut tip_ancestor = match self.header_cache.get(&tip.header.block_hash()) {
+                Some(cached_header) => cached_header.clone(),
+                None => continue,
+            };
+            let mut anchor_ancestor = anchor.clone();
+            while tip_ancestor.height > anchor_ancestor.height {
+                tip_ancestor = self.parent(&tip_ancestor);
+            }
+            while anchor_ancestor.height > tip_ancestor.height {
+                anchor_ancestor = self.parent(&anchor_ancestor);
+            }
+            while tip_ancestor.header.block_hash() != anchor_ancestor.header.block_hash() {
+                branch = Some(tip_ancestor.header.block_hash());
+                tip_ancestor = self.parent(&tip_ancestor);
+                anchor_ancestor = self.parent(&anchor_ancestor);
+            }
+            if let Some(branch) = branch {
+                stale_branches.push(branch);
+            }
+        }
+
+        if stale_branches.is_empty() {
+            return;
+        }
+
+        let mut removed = HashSet::new();
+        for branch in stale_branches {
+            removed.extend(self.header_cache.remove_subtree(&branch));
+        }
+        self.tips
+            .retain(|tip| !removed.contains(&tip.header.block_hash()));
+
+        self.stale_headers_in_header_file += removed.len();
+        if self.stale_headers_in_header_file > self.max_stale_headers_in_header_file {
+            self.rewrite_header_file();
+        }
+    }
+
+    /// Returns the parent of a header that is not the genesis header.
+    fn parent(&self, cached_header: &CachedHeader) -> CachedHeader {
+        self.header_cache
+            .get(&cached_header.header.prev_blockhash)
+            .expect("The parent of a cached header should be cached.")
+            .clone()
+    }
+
     /// Get the locator hashes for the active chain (the chain with the highest amount of work).
     /// Returns the block hashes corresponding to  tip, tip - 1, tip - 2, tip - 3, tip - 4, tip - 5, tip - 6, tip - 7, tip - 8,
     /// tip - (8 + 2), tip - (8 + 2 + 4), tip - (8 + 2 + 4 + 8), tip - (8 + 2 + 4 + 8 + 16) ..., tip - (8 + 2 + 4 + 8 + ... + 4096), adapter_gensis_hash
@@ -415,13 +644,15 @@ mod test {
         common::test_common::{block_1, block_2, generate_headers, TestState},
         config::test::ConfigBuilder,
     };
+    use ic_logger::replica_logger::no_op_logger;
     use std::collections::HashSet;
+    use tempfile::tempdir;
 
     #[test]
     fn test_get_block() {
         let test_state = TestState::setup();
         let config = ConfigBuilder::new().build();
-        let mut state = BlockchainState::new(&config);
+        let mut state = BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
 
         state
             .add_block(test_state.block_1.clone())
@@ -446,7 +677,7 @@ mod test {
     #[test]
     fn test_adding_headers_successfully() {
         let config = ConfigBuilder::new().with_network(Network::Regtest).build();
-        let mut state = BlockchainState::new(&config);
+        let mut state = BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
 
         let initial_header = state.genesis();
         let chain = generate_headers(
@@ -474,7 +705,7 @@ mod test {
     /// cause 2 forks in the chain. The state should be able to determine what is the active tip.
     fn test_forks_when_adding_headers() {
         let config = ConfigBuilder::new().with_network(Network::Regtest).build();
-        let mut state = BlockchainState::new(&config);
+        let mut state = BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let initial_header = state.genesis();
 
         // Create an arbitrary chain and adding to the BlockchainState
@@ -519,7 +750,7 @@ mod test {
     #[test]
     fn test_adding_an_empty_headers_vector() {
         let config = ConfigBuilder::new().build();
-        let mut state = BlockchainState::new(&config);
+        let mut state = BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let chain = vec![];
         let (added_headers, maybe_err) = state.add_headers(&chain);
         assert!(maybe_err.is_none());
@@ -532,7 +763,7 @@ mod test {
     #[test]
     fn test_adding_headers_that_already_exist() {
         let config = ConfigBuilder::new().with_network(Network::Regtest).build();
-        let mut state = BlockchainState::new(&config);
+        let mut state = BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
 
         let initial_header = state.genesis();
         let chain = generate_headers(
@@ -562,7 +793,7 @@ mod test {
     #[test]
     fn test_adding_headers_with_an_invalid_header() {
         let config = ConfigBuilder::new().with_network(Network::Regtest).build();
-        let mut state = BlockchainState::new(&config);
+        let mut state = BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
 
         let initial_header = state.genesis();
         let mut chain = generate_headers(
@@ -596,7 +827,7 @@ mod test {
         let mut block_2 = block_2();
 
         let config = ConfigBuilder::new().build();
-        let mut state = BlockchainState::new(&config);
+        let mut state = BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
 
         // Attempt to add block 2 to the cache before block 1's header has been added.
         let block_2_hash = block_2.header.block_hash();
@@ -624,7 +855,7 @@ mod test {
     fn test_pruning_blocks_from_the_cache() {
         let test_state = TestState::setup();
         let config = ConfigBuilder::new().build();
-        let mut state = BlockchainState::new(&config);
+        let mut state = BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let block_1_hash = test_state.block_1.block_hash();
         let block_2_hash = test_state.block_2.block_hash();
         state.add_block(test_state.block_1).unwrap();
@@ -641,7 +872,7 @@ mod test {
     fn test_pruning_blocks_below_a_given_height_from_the_cache() {
         let test_state = TestState::setup();
         let config = ConfigBuilder::new().build();
-        let mut state = BlockchainState::new(&config);
+        let mut state = BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let block_1_hash = test_state.block_1.block_hash();
         let block_2_hash = test_state.block_2.block_hash();
         state.add_block(test_state.block_1).unwrap();
@@ -658,7 +889,7 @@ mod test {
     fn test_block_cache_size() {
         let test_state = TestState::setup();
         let config = ConfigBuilder::new().build();
-        let mut state = BlockchainState::new(&config);
+        let mut state = BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
 
         let block_cache_size = state.get_block_cache_size();
         assert_eq!(block_cache_size, 0);
@@ -671,4 +902,186 @@ mod test {
 
         assert_eq!(expected_cache_size, block_cache_size);
     }
+
+    fn hashes(headers: &[BlockHeader]) -> Vec<BlockHash> {
+        headers.iter().map(|header| header.block_hash()).collect()
+    }
+
+    /// Tests that the headers are loaded from the header file when the state is created
+    /// again, e.g. after a restart.
+    #[test]
+    fn test_headers_are_loaded_from_the_header_file() {
+        let dir = tempdir().unwrap();
+        let config = ConfigBuilder::new()
+            .with_network(Network::Regtest)
+            .with_header_cache_path(dir.path().join("headers.bin"))
+            .build();
+        let mut state = BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
+        let genesis = state.genesis().header;
+        let chain = generate_headers(genesis.block_hash(), genesis.time, 10, &[]);
+        let chain_hashes = hashes(&chain);
+        let fork = generate_headers(chain_hashes[4], chain[4].time, 2, &chain_hashes);
+        state.add_headers(&chain);
+        state.add_headers(&fork);
+        let block = Block {
+            header: generate_headers(chain_hashes[9], chain[9].time, 1, &[])[0],
+            txdata: vec![],
+        };
+        state.add_block(block.clone()).unwrap();
+        drop(state);
+
+        let state = BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
+        let tip = state.get_active_chain_tip();
+        assert_eq!(tip.height, 11);
+        assert_eq!(tip.header, block.header);
+        assert_eq!(state.tips.len(), 2);
+        for hash in chain_hashes.iter().chain(hashes(&fork).iter()) {
+            assert!(state.is_block_hash_known(hash));
+        }
+        // Only headers are persisted.
+        assert!(state.get_block(&block.block_hash()).is_none());
+    }
+
+    /// Tests that headers in the header file that fail validation are dropped together
+    /// with their descendants, and that the file is rewritten without them.
+    #[test]
+    fn test_invalid_headers_are_dropped_when_loading() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("headers.bin");
+        let config = ConfigBuilder::new()
+            .with_network(Network::Regtest)
+            .with_header_cache_path(path.clone())
+            .build();
+        let genesis = genesis_block(Network::Regtest).header;
+        let mut chain = generate_headers(genesis.block_hash(), genesis.time, 5, &[]);
+        // The header does not satisfy the proof of work for the mainnet limit.
+        chain[3].bits = 0x1d00ffff;
+        {
+            let (mut header_file, _) = HeaderFile::open(&path).unwrap();
+            header_file.append(&chain).unwrap();
+        }
+
+        let state = BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
+        assert_eq!(state.get_active_chain_tip().height, 3);
+        assert_eq!(state.get_active_chain_tip().header, chain[2]);
+        drop(state);
+
+        let (_, headers) = HeaderFile::open(&path).unwrap();
+        assert_eq!(headers, chain[..3]);
+    }
+
+    /// Tests that forks branching off below the anchor are pruned from the header
+    /// cache and the header file, while forks above the anchor are kept.
+    #[test]
+    fn test_prune_headers_below_anchor() {
+        let dir = tempdir().unwrap();
+        let config = ConfigBuilder::new()
+            .with_network(Network::Regtest)
+            .with_header_cache_path(dir.path().join("headers.bin"))
+            .build();
+        let mut state = BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
+        // Rewrite the header file as soon as a header is pruned.
+        state.max_stale_headers_in_header_file = 0;
+        let genesis = state.genesis().header;
+
+        // 0 -> 1 -> 2 -> 3 -> ... -> 6 (anchor) -> 7 -> 8 -> 9 -> 10
+        //                |-> 4' -> 5'                   |-> 9''
+        let chain = generate_headers(genesis.block_hash(), genesis.time, 10, &[]);
+        let chain_hashes = hashes(&chain);
+        let stale_fork = generate_headers(chain_hashes[2], chain[2].time, 2, &chain_hashes);
+        let fork = generate_headers(chain_hashes[7], chain[7].time, 1, &chain_hashes);
+        state.add_headers(&chain);
+        state.add_headers(&stale_fork);
+        state.add_headers(&fork);
+        assert_eq!(state.tips.len(), 3);
+
+        state.prune_headers_below_anchor(&chain_hashes[5]);
+        assert_eq!(state.tips.len(), 2);
+        for hash in hashes(&stale_fork) {
+            assert!(!state.is_block_hash_known(&hash));
+        }
+        let fork_point = state.get_cached_header(&chain_hashes[2]).unwrap();
+        assert_eq!(fork_point.children.lock().len(), 1);
+        assert!(state.is_block_hash_known(&fork[0].block_hash()));
+        assert_eq!(state.get_active_chain_tip().header, chain[9]);
+        drop(state);
+
+        let state = BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
+        assert_eq!(state.tips.len(), 2);
+        assert!(!state.is_block_hash_known(&stale_fork[0].block_hash()));
+        assert!(state.is_block_hash_known(&fork[0].block_hash()));
+        assert_eq!(state.get_active_chain_tip().header, chain[9]);
+    }
+
+    /// Tests that the header file is only rewritten once the number of pruned headers
+    /// exceeds the limit. Until then, pruned headers are loaded again after a restart.
+    #[test]
+    fn test_header_file_rewrites_are_batched() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("headers.bin");
+        let config = ConfigBuilder::new()
+            .with_network(Network::Regtest)
+            .with_header_cache_path(path.clone())
+            .build();
+        let mut state = BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
+        state.max_stale_headers_in_header_file = 2;
+        let genesis = state.genesis().header;
+
+        // 0 -> 1 -> 2 -> 3 -> ... -> 10
+        //      |-> 2'   |-> 4''
+        let chain = generate_headers(genesis.block_hash(), genesis.time, 10, &[]);
+        let chain_hashes = hashes(&chain);
+        let first_fork = generate_headers(chain_hashes[0], chain[0].time, 1, &chain_hashes);
+        let second_fork = generate_headers(chain_hashes[2], chain[2].time, 2, &chain_hashes);
+        state.add_headers(&chain);
+        state.add_headers(&first_fork);
+
+        // Pruning a single header leaves it in the file.
+        state.prune_headers_below_anchor(&chain_hashes[5]);
+        assert_eq!(state.stale_headers_in_header_file, 1);
+        let (_, headers) = HeaderFile::open(&path).unwrap();
+        assert_eq!(headers.len(), 11);
+
+        // Pruning two more headers exceeds the limit, so the file is rewritten.
+        state.add_headers(&second_fork);
+        state.prune_headers_below_anchor(&chain_hashes[5]);
+        assert_eq!(state.stale_headers_in_header_file, 0);
+        let (_, headers) = HeaderFile::open(&path).unwrap();
+        assert_eq!(headers, chain);
+    }
+
+    /// Tests that a failed write to the header file is counted, and that the file is
+    /// rewritten from the header cache on the next write.
+    #[test]
+    fn test_header_file_is_rewritten_after_a_failed_write() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("headers.bin");
+        let config = ConfigBuilder::new()
+            .with_network(Network::Regtest)
+            .with_header_cache_path(path.clone())
+            .build();
+        let mut state = BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
+        let genesis = state.genesis().header;
+        let chain = generate_headers(genesis.block_hash(), genesis.time, 4, &[]);
+        state.add_headers(&chain[..2]);
+
+        // Make the next append fail by replacing the file handle with a read-only one.
+        state.header_file = Some(HeaderFile::open_read_only(&path).unwrap());
+        state.add_headers(&chain[2..3]);
+        assert!(state.header_file_out_of_sync);
+        assert_eq!(
+            state
+                .header_file_errors
+                .with_label_values(&["append"])
+                .get(),
+            1
+        );
+
+        // Once the file can be written again, it is rewritten with all headers.
+        state.header_file = Some(HeaderFile::open(&path).unwrap().0);
+        state.add_headers(&chain[3..]);
+        assert!(!state.header_file_out_of_sync);
+        let (_, headers) = HeaderFile::open(&path).unwrap();
+        assert_eq!(headers, chain);
+    }
 }
@@ -51,6 +51,10 @@ pub struct Config {
     /// Specifies which unix domain socket should be used for serving incoming requests.
     #[serde(default)]
     pub incoming_source: IncomingSource,
+    /// The file the header cache is persisted to. When set, the headers are loaded
+    /// from the file on startup instead of being downloaded again from genesis.
+    #[serde(default)]
+    pub header_cache_path: Option<PathBuf>,
 }
 
 /// Set the default idle seconds to one hour.
@@ -80,6 +84,7 @@ impl Default for Config {
             ipv6_only: false,
             logger: LoggerConfig::default(),
             incoming_source: Default::default(),
+            header_cache_path: None,
         }
     }
 }
@@ -121,6 +126,11 @@ pub mod test {
             self
         }
 
+        pub fn with_header_cache_path(mut self, header_cache_path: PathBuf) -> Self {
+            self.config.header_cache_path = Some(header_cache_path);
+            self
+        }
+
         pub fn build(self) -> Config {
             self.config
         }
@@ -0,0 +1,674 @@
+//! An in-process fake Bitcoin network for testing the adapter without network access.
+//!
+//! A [FakePeer] listens on localhost and speaks the Bitcoin P2P protocol. It serves
+//! the blocks of a scripted [BlockTree] and can be configured to respond slowly or not
+//! at all. A [TestAdapter] runs the adapter's router against a set of fake peers and
+//! exposes the [GetSuccessorsHandler] so tests can assert on what the adapter returns
+//! to the Bitcoin canister.
+use crate::{
+    common::BlockHeight,
+    config::Config,
+    get_successors_handler::{GetSuccessorsRequest, GetSuccessorsResponse},
+    start_router, AdapterState, BlockchainState, GetSuccessorsHandler,
+};
+use bitcoin::{
+    blockdata::constants::genesis_block,
+    consensus::{encode, serialize},
+    network::{
+        constants::ServiceFlags,
+        message::{NetworkMessage, RawNetworkMessage},
+        message_blockdata::{GetHeadersMessage, Inventory},
+        message_network::VersionMessage,
+        Address,
+    },
+    Block, BlockHash, BlockHeader, Network,
+};
+use ic_btc_test_utils::BlockBuilder;
+use ic_logger::replica_logger::no_op_logger;
+use ic_metrics::MetricsRegistry;
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    io,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{channel, unbounded_channel, UnboundedSender},
+    task::JoinHandle,
+    time::{sleep, timeout},
+};
+
+/// The network the fake peers and the adapter under test run on.
+const NETWORK: Network = Network::Regtest;
+
+/// The maximum number of headers a fake peer returns in a `headers` message.
+const MAX_HEADERS_SIZE: usize = 2_000;
+
+/// How long the `TestAdapter::wait_*` methods wait before failing the test.
+const WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often the `TestAdapter::wait_*` methods poll the adapter.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A tree of blocks rooted at the regtest genesis block. The chain with the most
+/// blocks is the tree's best chain, which is what a fake peer serves in response
+/// to `getheaders` requests.
+#[derive(Clone)]
+pub struct BlockTree {
+    /// The blocks of the tree with their heights.
+    blocks: HashMap<BlockHash, (Block, BlockHeight)>,
+    /// The genesis block's hash.
+    genesis: BlockHash,
+    /// The tip of the best chain. On a tie, the block that was added first wins.
+    tip: BlockHash,
+}
+
+impl BlockTree {
+    /// Creates a tree that only contains the regtest genesis block.
+    pub fn new() -> Self {
+        let genesis = genesis_block(NETWORK);
+        let genesis_hash = genesis.block_hash();
+        let mut blocks = HashMap::new();
+        blocks.insert(genesis_hash, (genesis, 0));
+        Self {
+            blocks,
+            genesis: genesis_hash,
+            tip: genesis_hash,
+        }
+    }
+
+    /// Returns the genesis block's hash.
+    pub fn genesis(&self) -> BlockHash {
+        self.genesis
+    }
+
+    /// Returns the tip of the best chain.
+    pub fn tip(&self) -> BlockHash {
+        self.tip
+    }
+
+    /// Returns the height of a block in the tree.
+    pub fn height(&self, block_hash: &BlockHash) -> Option<BlockHeight> {
+        self.blocks.get(block_hash).map(|(_, height)| *height)
+    }
+
+    /// Appends a chain of `count` valid blocks to `parent` and returns their hashes.
+    pub fn extend(&mut self, parent: &BlockHash, count: usize) -> Vec<BlockHash> {
+        let mut prev_header = self.blocks.get(parent).expect("unknown parent").0.header;
+        let mut hashes = vec![];
+        for _ in 0..count {
+            let block = BlockBuilder::with_prev_header(prev_header).build();
+            prev_header = block.header;
+            hashes.push(self.insert(block));
+        }
+        hashes
+    }
+
+    /// Appends a block whose header does not satisfy the regtest proof of work to
+    /// `parent` and returns its hash.
+    pub fn extend_with_invalid_header(&mut self, parent: &BlockHash) -> BlockHash {
+        let prev_header = self.blocks.get(parent).expect("unknown parent").0.header;
+        let mut block = BlockBuilder::with_prev_header(prev_header).build();
+        // The mainnet proof of work limit is far below the regtest one.
+        block.header.bits = 0x1d00ffff;
+        self.insert(block)
+    }
+
+    fn insert(&mut self, block: Block) -> BlockHash {
+        let block_hash = block.block_hash();
+        let height = self
+            .height(&block.header.prev_blockhash)
+            .expect("unknown parent")
+            + 1;
+        if height > self.height(&self.tip).unwrap_or_default() {
+            self.tip = block_hash;
+        }
+        self.blocks.insert(block_hash, (block, height));
+        block_hash
+    }
+
+    /// Returns the requested blocks that are part of the tree.
+    fn blocks(&self, inventory: &[Inventory]) -> Vec<Block> {
+        inventory
+            .iter()
+            .filter_map(|inv| match inv {
+                Inventory::Block(hash) | Inventory::WitnessBlock(hash) => {
+                    self.blocks.get(hash).map(|(block, _)| block.clone())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the hashes of the best chain, starting with the genesis block.
+    fn best_chain(&self) -> Vec<BlockHash> {
+        let mut chain = vec![self.tip];
+        let mut current = self.tip;
+        while current != self.genesis {
+            current = self.blocks[&current].0.header.prev_blockhash;
+            chain.push(current);
+        }
+        chain.reverse();
+        chain
+    }
+
+    /// Answers a `getheaders` request the way Bitcoin Core does: the headers of the
+    /// best chain following the first locator on the best chain, up to and including
+    /// the stop hash.
+    fn headers(&self, request: &GetHeadersMessage) -> Vec<BlockHeader> {
+        let chain = self.best_chain();
+        let start = request
+            .locator_hashes
+            .iter()
+            .find_map(|locator| chain.iter().position(|hash| hash == locator))
+            .unwrap_or(0)
+            + 1;
+
+        let mut headers = vec![];
+        for block_hash in chain.iter().skip(start).take(MAX_HEADERS_SIZE) {
+            headers.push(self.blocks[block_hash].0.header);
+            if *block_hash == request.stop_hash {
+                break;
+            }
+        }
+        headers
+    }
+}
+
+impl Default for BlockTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How a fake peer responds to `getheaders` and `getdata` requests.
+#[derive(Clone, Copy, Debug)]
+pub enum PeerBehavior {
+    /// Responds to every request right away.
+    Honest,
+    /// Waits for the given duration before responding to a request.
+    Slow(Duration),
+    /// Completes the version handshake, but never responds to a request.
+    Stalled,
+}
+
+/// The state of a fake peer that is shared with its connection tasks.
+struct PeerState {
+    tree: BlockTree,
+    behavior: PeerBehavior,
+    /// The messages received from the adapter.
+    received: Vec<NetworkMessage>,
+    /// The number of connections the adapter has opened.
+    connection_count: usize,
+    /// Senders for the messages to write to the open connections.
+    writers: Vec<UnboundedSender<NetworkMessage>>,
+}
+
+/// A Bitcoin node on localhost that serves a scripted [BlockTree].
+pub struct FakePeer {
+    address: SocketAddr,
+    state: Arc<Mutex<PeerState>>,
+    handle: JoinHandle<()>,
+}
+
+impl FakePeer {
+    /// Starts a peer that serves `tree` on a free localhost port.
+    pub async fn spawn(tree: BlockTree, behavior: PeerBehavior) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("failed to bind the fake peer");
+        let address = listener.local_addr().unwrap();
+        let state = Arc::new(Mutex::new(PeerState {
+            tree,
+            behavior,
+            received: vec![],
+            connection_count: 0,
+            writers: vec![],
+        }));
+
+        let peer_state = state.clone();
+        let handle = tokio::task::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                peer_state.lock().connection_count += 1;
+                tokio::task::spawn(handle_connection(stream, peer_state.clone()));
+            }
+        });
+
+        Self {
+            address,
+            state,
+            handle,
+        }
+    }
+
+    /// Returns the address the peer listens on.
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Gives access to the peer's block tree, e.g. to script a reorg.
+    pub fn with_tree<R>(&self, f: impl FnOnce(&mut BlockTree) -> R) -> R {
+        f(&mut self.state.lock().tree)
+    }
+
+    /// Announces the tip of the peer's best chain to all open connections.
+    pub fn announce_tip(&self) {
+        let mut state = self.state.lock();
+        let inv = NetworkMessage::Inv(vec![Inventory::Block(state.tree.tip())]);
+        state
+            .writers
+            .retain(|writer| writer.send(inv.clone()).is_ok());
+    }
+
+    /// Returns the number of connections the adapter has opened to the peer.
+    pub fn connection_count(&self) -> usize {
+        self.state.lock().connection_count
+    }
+
+    /// Returns the messages the peer has received from the adapter.
+    pub fn received_messages(&self) -> Vec<NetworkMessage> {
+        self.state.lock().received.clone()
+    }
+}
+
+impl Drop for FakePeer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// Reads messages from the adapter until the connection is closed, and answers them
+/// according to the peer's behavior.
+async fn handle_connection(stream: TcpStream, state: Arc<Mutex<PeerState>>) {
+    let (mut read_half, mut write_half) = stream.into_split();
+    let (writer, mut messages) = unbounded_channel::<NetworkMessage>();
+    state.lock().writers.push(writer.clone());
+
+    tokio::task::spawn(async move {
+        while let Some(payload) = messages.recv().await {
+            let bytes = serialize(&RawNetworkMessage {
+                magic: NETWORK.magic(),
+                payload,
+            });
+            if write_half.write_all(&bytes).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut unparsed = vec![];
+    let mut data = vec![0u8; 64 * 1024];
+    loop {
+        let message = match encode::deserialize_partial::<RawNetworkMessage>(&unparsed) {
+            Ok((message, index)) => {
+                unparsed.drain(..index);
+                message.payload
+            }
+            Err(encode::Error::Io(ref err)) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                match read_half.read(&mut data).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(count) => {
+                        unparsed.extend_from_slice(&data[..count]);
+                        continue;
+                    }
+                }
+            }
+            Err(_) => return,
+        };
+
+        let (behavior, start_height) = {
+            let mut state = state.lock();
+            state.received.push(message.clone());
+            let start_height = state.tree.height(&state.tree.tip()).unwrap_or_default();
+            (state.behavior, start_height)
+        };
+
+        let responses = match message {
+            NetworkMessage::Version(_) => {
+                vec![version_message(start_height), NetworkMessage::Verack]
+            }
+            NetworkMessage::Ping(nonce) => vec![NetworkMessage::Pong(nonce)],
+            NetworkMessage::GetHeaders(request) => {
+                if !respond(behavior).await {
+                    continue;
+                }
+                let headers = state.lock().tree.headers(&request);
+                vec![NetworkMessage::Headers(headers)]
+            }
+            NetworkMessage::GetData(inventory) => {
+                if !respond(behavior).await {
+                    continue;
+                }
+                let blocks = state.lock().tree.blocks(&inventory);
+                blocks.into_iter().map(NetworkMessage::Block).collect()
+            }
+            _ => vec![],
+        };
+
+        for response in responses {
+            if writer.send(response).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Applies the peer's behavior before a request is answered. Returns false if the
+/// request should not be answered.
+async fn respond(behavior: PeerBehavior) -> bool {
+    match behavior {
+        PeerBehavior::Honest => true,
+        PeerBehavior::Slow(delay) => {
+            sleep(delay).await;
+            true
+        }
+        PeerBehavior::Stalled => false,
+    }
+}
+
+fn version_message(start_height: BlockHeight) -> NetworkMessage {
+    let services = ServiceFlags::NETWORK | ServiceFlags::WITNESS;
+    let unspecified = SocketAddr::from(([0, 0, 0, 0], 0));
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    NetworkMessage::Version(VersionMessage::new(
+        services,
+        timestamp as i64,
+        Address::new(&unspecified, ServiceFlags::NONE),
+        Address::new(&unspecified, services),
+        rand::random(),
+        String::from("fake-peer"),
+        start_height as i32,
+    ))
+}
+
+/// An adapter connected to a set of fake peers.
+pub struct TestAdapter {
+    handler: GetSuccessorsHandler,
+    blockchain_state: Arc<tokio::sync::Mutex<BlockchainState>>,
+}
+
+impl TestAdapter {
+    /// Starts the adapter's router with the given peers as the only known nodes.
+    pub fn start(peers: &[&FakePeer]) -> Self {
+        let config = Config {
+            network: NETWORK,
+            nodes: peers.iter().map(|peer| peer.address()).collect(),
+            ..Default::default()
+        };
+        let (blockchain_manager_tx, blockchain_manager_rx) = channel(10);
+        let (_transaction_manager_tx, transaction_manager_rx) = channel(10);
+        let adapter_state = AdapterState::new(config.idle_seconds);
+        // The adapter only connects to peers after it received a request.
+        adapter_state.received_now();
+        let blockchain_state = Arc::new(tokio::sync::Mutex::new(BlockchainState::new(
+            &config,
+            no_op_logger(),
+            &MetricsRegistry::default(),
+        )));
+        let handler =
+            GetSuccessorsHandler::new(&config, blockchain_state.clone(), blockchain_manager_tx);
+
+        start_router(
+            &config,
+            no_op_logger(),
+            blockchain_state.clone(),
+            transaction_manager_rx,
+            adapter_state,
+            blockchain_manager_rx,
+        );
+
+        Self {
+            handler,
+            blockchain_state,
+        }
+    }
+
+    /// Returns the hash and height of the adapter's active tip.
+    pub async fn tip(&self) -> (BlockHash, BlockHeight) {
+        let state = self.blockchain_state.lock().await;
+        let tip = state.get_active_chain_tip();
+        (tip.header.block_hash(), tip.height)
+    }
+
+    /// Returns true if the adapter has accepted the header with the given hash.
+    pub async fn has_header(&self, block_hash: &BlockHash) -> bool {
+        self.blockchain_state
+            .lock()
+            .await
+            .get_cached_header(block_hash)
+            .is_some()
+    }
+
+    /// Waits until the adapter has accepted the header with the given hash.
+    pub async fn wait_for_header(&self, block_hash: &BlockHash) {
+        timeout(WAIT_TIMEOUT, async {
+            while !self.has_header(block_hash).await {
+                sleep(POLL_INTERVAL).await;
+            }
+        })
+        .await
+        .unwrap_or_else(|_| {
+            panic!("timed out waiting for header {}", block_hash);
+        });
+    }
+
+    /// Sends a `GetSuccessorsRequest` to the adapter.
+    pub async fn get_successors(
+        &self,
+        anchor: BlockHash,
+        processed_block_hashes: &[BlockHash],
+    ) -> GetSuccessorsResponse {
+        self.handler
+            .get_successors(GetSuccessorsRequest {
+                anchor,
+                processed_block_hashes: processed_block_hashes.to_vec(),
+            })
+            .await
+            .expect("get_successors failed")
+    }
+
+    /// Waits until the adapter's active tip is `block_hash`.
+    pub async fn wait_for_tip(&self, block_hash: BlockHash) {
+        timeout(WAIT_TIMEOUT, async {
+            while self.tip().await.0 != block_hash {
+                sleep(POLL_INTERVAL).await;
+            }
+        })
+        .await
+        .unwrap_or_else(|_| {
+            panic!("timed out waiting for tip {}", block_hash);
+        });
+    }
+
+    /// Repeatedly sends the same `GetSuccessorsRequest` until the response contains
+    /// exactly the `expected` blocks, and returns that response.
+    pub async fn wait_for_blocks(
+        &self,
+        anchor: BlockHash,
+        processed_block_hashes: &[BlockHash],
+        expected: &[BlockHash],
+    ) -> GetSuccessorsResponse {
+        timeout(WAIT_TIMEOUT, async {
+            loop {
+                let response = self.get_successors(anchor, processed_block_hashes).await;
+                if block_hashes(&response.blocks) == expected {
+                    return response;
+                }
+                sleep(POLL_INTERVAL).await;
+            }
+        })
+        .await
+        .unwrap_or_else(|_| {
+            panic!("timed out waiting for blocks {:?}", expected);
+        })
+    }
+}
+
+fn block_hashes(blocks: &[Block]) -> Vec<BlockHash> {
+    blocks.iter().map(|block| block.block_hash()).collect()
+}
+
+mod test {
+    use super::*;
+
+    fn header_hashes(headers: &[BlockHeader]) -> Vec<BlockHash> {
+        headers.iter().map(|header| header.block_hash()).collect()
+    }
+
+    /// Waits until `condition` holds, failing the test with `message` otherwise.
+    async fn wait_until(condition: impl Fn() -> bool, message: &str) {
+        timeout(WAIT_TIMEOUT, async {
+            while !condition() {
+                sleep(POLL_INTERVAL).await;
+            }
+        })
+        .await
+        .expect(message);
+    }
+
+    fn has_received_getheaders(peer: &FakePeer) -> bool {
+        peer.received_messages()
+            .iter()
+            .any(|message| matches!(message, NetworkMessage::GetHeaders(_)))
+    }
+
+    /// Tests that the adapter downloads the headers and blocks of a single chain.
+    #[tokio::test]
+    async fn test_syncs_chain_from_a_single_peer() {
+        let mut tree = BlockTree::new();
+        let genesis = tree.genesis();
+        let chain = tree.extend(&genesis, 5);
+        let peer = FakePeer::spawn(tree, PeerBehavior::Honest).await;
+        let adapter = TestAdapter::start(&[&peer]);
+
+        adapter.wait_for_tip(chain[4]).await;
+        let response = adapter.get_successors(genesis, &[]).await;
+        assert_eq!(header_hashes(&response.next), chain);
+
+        let response = adapter.wait_for_blocks(genesis, &[], &chain).await;
+        assert!(response.next.is_empty());
+        assert!(peer
+            .received_messages()
+            .iter()
+            .any(|message| matches!(message, NetworkMessage::GetData(_))));
+    }
+
+    /// Tests that the adapter follows a reorg announced by a peer and returns the
+    /// blocks of the new best chain.
+    #[tokio::test]
+    async fn test_follows_reorg() {
+        let mut tree = BlockTree::new();
+        let genesis = tree.genesis();
+        let main_chain = tree.extend(&genesis, 3);
+        let peer = FakePeer::spawn(tree, PeerBehavior::Honest).await;
+        let adapter = TestAdapter::start(&[&peer]);
+
+        adapter.wait_for_tip(main_chain[2]).await;
+        adapter.wait_for_blocks(genesis, &[], &main_chain).await;
+
+        // 0 -> 1 -> 2 -> 3
+        //      |--> 2' -> 3' -> 4' -> 5'
+        let fork = peer.with_tree(|tree| tree.extend(&main_chain[0], 4));
+        peer.announce_tip();
+        adapter.wait_for_tip(fork[3]).await;
+
+        let response = adapter
+            .wait_for_blocks(main_chain[0], &main_chain[1..], &fork)
+            .await;
+        assert!(response.next.is_empty());
+    }
+
+    /// Tests that headers of competing forks served by different peers are all
+    /// returned, and that the fork with the most work becomes the active chain.
+    #[tokio::test]
+    async fn test_returns_forks_from_multiple_peers() {
+        let mut tree = BlockTree::new();
+        let genesis = tree.genesis();
+        let common = tree.extend(&genesis, 1);
+        let mut tree_a = tree.clone();
+        let chain_a = tree_a.extend(&common[0], 2);
+        let chain_b = tree.extend(&common[0], 3);
+        let peer_a = FakePeer::spawn(tree_a, PeerBehavior::Honest).await;
+        let peer_b = FakePeer::spawn(tree, PeerBehavior::Honest).await;
+        let adapter = TestAdapter::start(&[&peer_a, &peer_b]);
+
+        adapter.wait_for_tip(chain_b[2]).await;
+        for block_hash in &chain_a {
+            adapter.wait_for_header(block_hash).await;
+        }
+
+        let mut expected = vec![common[0]];
+        expected.extend(&chain_a);
+        expected.extend(&chain_b);
+        let response = adapter.get_successors(genesis, &[]).await;
+        let mut next = header_hashes(&response.next);
+        next.sort();
+        expected.sort();
+        assert_eq!(next, expected);
+    }
+
+    /// Tests that a peer serving an invalid header is disconnected, and that the
+    /// adapter keeps syncing from the remaining peers.
+    #[tokio::test]
+    async fn test_disconnects_peer_serving_invalid_headers() {
+        let mut tree = BlockTree::new();
+        let genesis = tree.genesis();
+        let mut bad_tree = tree.clone();
+        let bad_chain = bad_tree.extend(&genesis, 2);
+        let invalid = bad_tree.extend_with_invalid_header(&bad_chain[1]);
+        let chain = tree.extend(&genesis, 4);
+        let bad_peer = FakePeer::spawn(bad_tree, PeerBehavior::Honest).await;
+        let peer = FakePeer::spawn(tree, PeerBehavior::Honest).await;
+        let adapter = TestAdapter::start(&[&bad_peer, &peer]);
+
+        adapter.wait_for_tip(chain[3]).await;
+        wait_until(
+            || bad_peer.connection_count() > 1,
+            "the peer serving an invalid header was not disconnected",
+        )
+        .await;
+        assert!(!adapter.has_header(&invalid).await);
+        adapter.wait_for_blocks(genesis, &[], &chain).await;
+    }
+
+    /// Tests that the adapter syncs from a peer that responds slowly.
+    #[tokio::test]
+    async fn test_syncs_from_slow_peer() {
+        let mut tree = BlockTree::new();
+        let genesis = tree.genesis();
+        let chain = tree.extend(&genesis, 3);
+        let peer = FakePeer::spawn(tree, PeerBehavior::Slow(Duration::from_millis(500))).await;
+        let adapter = TestAdapter::start(&[&peer]);
+
+        adapter.wait_for_tip(chain[2]).await;
+        adapter.wait_for_blocks(genesis, &[], &chain).await;
+    }
+
+    /// Tests that a stalled peer does not keep the adapter from syncing the
+    /// headers of another peer.
+    #[tokio::test]
+    async fn test_syncs_headers_despite_stalled_peer() {
+        let mut tree = BlockTree::new();
+        let genesis = tree.genesis();
+        let chain = tree.extend(&genesis, 3);
+        let stalled_peer = FakePeer::spawn(tree.clone(), PeerBehavior::Stalled).await;
+        let peer = FakePeer::spawn(tree, PeerBehavior::Honest).await;
+        let adapter = TestAdapter::start(&[&stalled_peer, &peer]);
+
+        adapter.wait_for_tip(chain[2]).await;
+        wait_until(
+            || has_received_getheaders(&stalled_peer),
+            "the stalled peer was not asked for headers",
+        )
+        .await;
+        let response = adapter.get_successors(genesis, &[]).await;
+        assert_eq!(header_hashes(&response.next), chain);
+    }
+}
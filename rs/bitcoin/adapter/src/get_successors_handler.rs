@@ -235,6 +235,8 @@ mod test {
     use std::sync::Arc;
 
     use bitcoin::Network;
+    use ic_logger::replica_logger::no_op_logger;
+    use ic_metrics::MetricsRegistry;
     use tokio::sync::{mpsc::channel, Mutex};
 
     use crate::{
@@ -249,7 +251,8 @@ mod test {
     #[tokio::test]
     async fn test_get_successors() {
         let config = ConfigBuilder::new().with_network(Network::Regtest).build();
-        let blockchain_state = BlockchainState::new(&config);
+        let blockchain_state =
+            BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let genesis = blockchain_state.genesis().clone();
         let genesis_hash = genesis.header.block_hash();
         let (blockchain_manager_tx, _) = channel::<BlockchainManagerRequest>(10);
@@ -356,7 +359,8 @@ mod test {
     #[tokio::test]
     async fn test_get_successors_wait_header_sync_testnet() {
         let config = ConfigBuilder::new().with_network(Network::Testnet).build();
-        let blockchain_state = BlockchainState::new(&config);
+        let blockchain_state =
+            BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let genesis = blockchain_state.genesis().clone();
         let genesis_hash = genesis.header.block_hash();
         let (blockchain_manager_tx, _) = channel::<BlockchainManagerRequest>(10);
@@ -398,7 +402,8 @@ mod test {
     #[tokio::test]
     async fn test_get_successors_wait_header_sync_regtest() {
         let config = ConfigBuilder::new().with_network(Network::Regtest).build();
-        let blockchain_state = BlockchainState::new(&config);
+        let blockchain_state =
+            BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let genesis = blockchain_state.genesis().clone();
         let genesis_hash = genesis.header.block_hash();
         let (blockchain_manager_tx, _) = channel::<BlockchainManagerRequest>(10);
@@ -445,7 +450,8 @@ mod test {
     #[tokio::test]
     async fn test_get_successors_multiple_blocks() {
         let config = ConfigBuilder::new().with_network(Network::Regtest).build();
-        let blockchain_state = BlockchainState::new(&config);
+        let blockchain_state =
+            BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let genesis = blockchain_state.genesis().clone();
         let genesis_hash = genesis.header.block_hash();
         let (blockchain_manager_tx, _) = channel::<BlockchainManagerRequest>(10);
@@ -516,7 +522,8 @@ mod test {
     #[tokio::test]
     async fn test_get_successors_multiple_blocks_out_of_order() {
         let config = ConfigBuilder::new().with_network(Network::Regtest).build();
-        let blockchain_state = BlockchainState::new(&config);
+        let blockchain_state =
+            BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let genesis = blockchain_state.genesis().clone();
         let genesis_hash = genesis.header.block_hash();
         let (blockchain_manager_tx, _) = channel::<BlockchainManagerRequest>(10);
@@ -607,7 +614,8 @@ mod test {
     #[tokio::test]
     async fn test_get_successors_large_block() {
         let config = ConfigBuilder::new().with_network(Network::Regtest).build();
-        let blockchain_state = BlockchainState::new(&config);
+        let blockchain_state =
+            BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let genesis = blockchain_state.genesis().clone();
         let genesis_hash = genesis.header.block_hash();
         let (blockchain_manager_tx, _) = channel::<BlockchainManagerRequest>(10);
@@ -668,7 +676,8 @@ mod test {
     #[tokio::test]
     async fn test_get_successors_many_blocks_until_size_cap_is_met() {
         let config = ConfigBuilder::new().with_network(Network::Regtest).build();
-        let blockchain_state = BlockchainState::new(&config);
+        let blockchain_state =
+            BlockchainState::new(&config, no_op_logger(), &MetricsRegistry::default());
         let genesis = blockchain_state.genesis().clone();
         let genesis_hash = genesis.header.block_hash();
         let (blockchain_manager_tx, _) = channel::<BlockchainManagerRequest>(10);
@@ -0,0 +1,166 @@
+//! The file the header cache is persisted to, so the adapter does not have to download
+//! all headers again after a restart.
+//!
+//! The file is a sequence of consensus-encoded block headers. Every header is appended
+//! after its parent, so the headers can be added to a fresh header cache in file order.
+use bitcoin::{
+    consensus::{deserialize, serialize},
+    BlockHeader,
+};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// The size of a consensus-encoded block header.
+const HEADER_SIZE: usize = 80;
+
+/// An append-only file of block headers.
+#[derive(Debug)]
+pub struct HeaderFile {
+    /// The path of the file.
+    path: PathBuf,
+    /// The file, opened for appending.
+    file: File,
+}
+
+impl HeaderFile {
+    /// Opens the file at `path`, creating it if it does not exist, and returns the
+    /// headers it contains in the order they were appended.
+    ///
+    /// A partially written header at the end of the file, e.g. from a crash during
+    /// a write, is discarded.
+    pub fn open(path: &Path) -> io::Result<(Self, Vec<BlockHeader>)> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes)?;
+
+        let complete_len = bytes.len() - bytes.len() % HEADER_SIZE;
+        if complete_len != bytes.len() {
+            file.set_len(complete_len as u64)?;
+        }
+
+        let headers = bytes[..complete_len]
+            .chunks(HEADER_SIZE)
+            .map(deserialize)
+            .collect::<Result<Vec<BlockHeader>, _>>()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        Ok((
+            Self {
+                path: path.to_path_buf(),
+                file,
+            },
+            headers,
+        ))
+    }
+
+    /// Opens the file at `path` without write access, so that writes to it fail.
+    #[cfg(test)]
+    pub fn open_read_only(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: File::open(path)?,
+        })
+    }
+
+    /// Appends the given headers to the file.
+    pub fn append(&mut self, headers: &[BlockHeader]) -> io::Result<()> {
+        self.file.write_all(&encode(headers))
+    }
+
+    /// Replaces the content of the file with the given headers. The new content is
+    /// written to a temporary file first, which is then renamed, so the file is
+    /// never left half-written.
+    pub fn rewrite(&mut self, headers: &[BlockHeader]) -> io::Result<()> {
+        let tmp_path = self.path.with_extension("tmp");
+        {
+            let mut writer = BufWriter::new(File::create(&tmp_path)?);
+            for header in headers {
+                writer.write_all(&serialize(header))?;
+            }
+            writer
+                .into_inner()
+                .map_err(|err| err.into_error())?
+                .sync_all()?;
+        }
+        fs::rename(&tmp_path, &self.path)?;
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        Ok(())
+    }
+}
+
+fn encode(headers: &[BlockHeader]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(headers.len() * HEADER_SIZE);
+    for header in headers {
+        bytes.extend(serialize(header));
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::test_common::generate_headers;
+    use bitcoin::{blockdata::constants::genesis_block, Network};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_append_and_reopen() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("headers.bin");
+        let genesis = genesis_block(Network::Regtest).header;
+        let headers = generate_headers(genesis.block_hash(), genesis.time, 5, &[]);
+
+        let (mut file, loaded) = HeaderFile::open(&path).unwrap();
+        assert!(loaded.is_empty());
+        file.append(&headers[..2]).unwrap();
+        file.append(&headers[2..]).unwrap();
+        drop(file);
+
+        let (_, loaded) = HeaderFile::open(&path).unwrap();
+        assert_eq!(loaded, headers);
+    }
+
+    #[test]
+    fn test_partial_header_is_discarded() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("headers.bin");
+        let genesis = genesis_block(Network::Regtest).header;
+        let headers = generate_headers(genesis.block_hash(), genesis.time, 2, &[]);
+
+        let mut bytes = encode(&headers);
+        bytes.truncate(HEADER_SIZE + 10);
+        fs::write(&path, bytes).unwrap();
+
+        let (mut file, loaded) = HeaderFile::open(&path).unwrap();
+        assert_eq!(loaded, headers[..1]);
+        file.append(&headers[1..]).unwrap();
+        drop(file);
+
+        let (_, loaded) = HeaderFile::open(&path).unwrap();
+        assert_eq!(loaded, headers);
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("headers.bin");
+        let genesis = genesis_block(Network::Regtest).header;
+        let headers = generate_headers(genesis.block_hash(), genesis.time, 3, &[]);
+
+        let (mut file, _) = HeaderFile::open(&path).unwrap();
+        file.append(&headers).unwrap();
+        file.rewrite(&headers[..1]).unwrap();
+        file.append(&headers[1..2]).unwrap();
+        drop(file);
+
+        let (_, loaded) = HeaderFile::open(&path).unwrap();
+        assert_eq!(loaded, headers[..2]);
+    }
+}
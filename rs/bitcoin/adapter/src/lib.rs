@@ -31,6 +31,10 @@ mod connection;
 /// This module contains code that is used to manage multiple connections to
 /// BTC nodes.
 mod connectionmanager;
+/// This module contains an in-process network of fake BTC nodes used to test
+/// the sync logic against scripted block trees and misbehaving peers.
+#[cfg(test)]
+mod fake_peer;
 /// The module is responsible for awaiting messages from bitcoin peers and dispaching them
 /// to the correct component.
 mod router;
@@ -41,6 +45,8 @@ mod stream;
 mod transaction_manager;
 
 mod get_successors_handler;
+/// This module contains the file the header cache is persisted to.
+mod header_file;
 
 pub use blockchainmanager::BlockchainManager;
 pub use blockchainstate::BlockchainState;
@@ -0,0 +1,196 @@
+use crate::{
+    state::{AddressHistory, UtxoSet},
+    types::Storable,
+    utxos::UtxosTrait,
+};
+use bitcoin::{Address, Block, Network, OutPoint, Script, Transaction};
+use ic_btc_types::{Address as AddressStr, Height};
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+/// Adds a transaction of a stable block at the given height to the address
+/// history.
+///
+/// The addresses spent from are looked up in `utxo_set`, so this must be
+/// called before the transaction is inserted into `utxo_set`.
+pub fn insert_tx(
+    address_history: &mut AddressHistory,
+    utxo_set: &UtxoSet,
+    tx: &Transaction,
+    height: Height,
+) {
+    let txid = tx.txid();
+    for address in tx_addresses(utxo_set, tx) {
+        address_history
+            .index
+            .insert((address, height, txid).to_bytes(), vec![])
+            .expect("insertion must succeed");
+    }
+}
+
+/// Returns the transactions of `chain` that pay to or spend from `address`,
+/// where `chain` is a chain of unstable blocks that starts at `height`.
+///
+/// The transactions are encoded as (`Height`, `Txid`) to match the ordering
+/// of the entries in the address history index.
+pub fn get_unstable_txs(
+    utxo_set: &UtxoSet,
+    address: &str,
+    chain: Vec<&Block>,
+    height: Height,
+) -> BTreeSet<Vec<u8>> {
+    // The outputs paying to `address` that were created in `chain`.
+    let mut added_outpoints = BTreeSet::new();
+    let mut txs = BTreeSet::new();
+
+    for (i, block) in chain.into_iter().enumerate() {
+        let block_height = height + i as u32;
+        for tx in &block.txdata {
+            let txid = tx.txid();
+            let mut is_relevant = false;
+
+            if !tx.is_coin_base() {
+                for input in &tx.input {
+                    if added_outpoints.contains(&input.previous_output)
+                        || spent_address(utxo_set, &input.previous_output).as_deref()
+                            == Some(address)
+                    {
+                        is_relevant = true;
+                    }
+                }
+            }
+
+            for (vout, output) in tx.output.iter().enumerate() {
+                if to_address(&output.script_pubkey, utxo_set.network).as_deref() == Some(address) {
+                    added_outpoints.insert(OutPoint::new(txid, vout as u32));
+                    is_relevant = true;
+                }
+            }
+
+            if is_relevant {
+                txs.insert((block_height, txid).to_bytes());
+            }
+        }
+    }
+
+    txs
+}
+
+// Returns the addresses that a transaction pays to or spends from.
+fn tx_addresses(utxo_set: &UtxoSet, tx: &Transaction) -> BTreeSet<AddressStr> {
+    let mut addresses = BTreeSet::new();
+
+    if !tx.is_coin_base() {
+        for input in &tx.input {
+            addresses.extend(spent_address(utxo_set, &input.previous_output));
+        }
+    }
+
+    for output in &tx.output {
+        addresses.extend(to_address(&output.script_pubkey, utxo_set.network));
+    }
+
+    addresses
+}
+
+// Returns the address of a stable UTXO, if any.
+fn spent_address(utxo_set: &UtxoSet, outpoint: &OutPoint) -> Option<AddressStr> {
+    let (txout, _) = utxo_set.utxos.get(outpoint)?;
+    to_address(&txout.script_pubkey, utxo_set.network)
+}
+
+// Returns the address of a script, if any.
+//
+// As in `utxoset::insert_utxo`, the address is verified to be valid to work
+// around https://github.com/rust-bitcoin/rust-bitcoin/issues/995.
+fn to_address(script: &Script, network: Network) -> Option<AddressStr> {
+    let address = Address::from_script(script, network)?.to_string();
+    Address::from_str(&address).ok().map(|_| address)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utxoset;
+    use bitcoin::Txid;
+    use ic_btc_test_utils::{random_p2pkh_address, BlockBuilder, TransactionBuilder};
+
+    fn history(address_history: &AddressHistory, address: &str) -> Vec<(Height, Txid)> {
+        address_history
+            .index
+            .range(address.to_string().to_bytes(), None)
+            .map(|(k, _)| {
+                let (_, height, txid) = <(AddressStr, Height, Txid)>::from_bytes(k);
+                (height, txid)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn indexes_outputs_and_spent_inputs() {
+        let network = Network::Regtest;
+        let address_1 = random_p2pkh_address(network);
+        let address_2 = random_p2pkh_address(network);
+
+        let mut utxo_set = UtxoSet::new(network);
+        let mut address_history = AddressHistory::new(true);
+
+        let coinbase_tx = TransactionBuilder::coinbase()
+            .with_output(&address_1, 1000)
+            .build();
+        insert_tx(&mut address_history, &utxo_set, &coinbase_tx, 0);
+        utxoset::insert_tx(&mut utxo_set, &coinbase_tx, 0);
+
+        let tx = TransactionBuilder::new()
+            .with_input(OutPoint::new(coinbase_tx.txid(), 0))
+            .with_output(&address_2, 1000)
+            .build();
+        insert_tx(&mut address_history, &utxo_set, &tx, 1);
+        utxoset::insert_tx(&mut utxo_set, &tx, 1);
+
+        // The history is sorted from newest to oldest.
+        assert_eq!(
+            history(&address_history, &address_1.to_string()),
+            vec![(1, tx.txid()), (0, coinbase_tx.txid())]
+        );
+        assert_eq!(
+            history(&address_history, &address_2.to_string()),
+            vec![(1, tx.txid())]
+        );
+    }
+
+    #[test]
+    fn unstable_txs_spending_unstable_outputs() {
+        let network = Network::Regtest;
+        let address_1 = random_p2pkh_address(network);
+        let address_2 = random_p2pkh_address(network);
+        let utxo_set = UtxoSet::new(network);
+
+        let coinbase_tx = TransactionBuilder::coinbase()
+            .with_output(&address_1, 1000)
+            .build();
+        let block_0 = BlockBuilder::genesis()
+            .with_transaction(coinbase_tx.clone())
+            .build();
+        let tx = TransactionBuilder::new()
+            .with_input(OutPoint::new(coinbase_tx.txid(), 0))
+            .with_output(&address_2, 1000)
+            .build();
+        let block_1 = BlockBuilder::with_prev_header(block_0.header)
+            .with_transaction(tx.clone())
+            .build();
+
+        let txs = get_unstable_txs(
+            &utxo_set,
+            &address_1.to_string(),
+            vec![&block_0, &block_1],
+            5,
+        );
+        assert_eq!(
+            txs.into_iter()
+                .map(<(Height, Txid)>::from_bytes)
+                .collect::<Vec<_>>(),
+            vec![(6, tx.txid()), (5, coinbase_tx.txid())]
+        );
+    }
+}
@@ -1,16 +1,12 @@
 pub use crate::fees::get_current_fee_percentiles;
-use crate::{metrics::BitcoinCanisterMetrics, state::State, store};
-use bitcoin::{util::psbt::serialize::Deserialize, Transaction};
+use crate::{metrics::BitcoinCanisterMetrics, outgoing_transactions, state::State, store};
 use ic_btc_types::{
-    GetBalanceError, GetUtxosError, GetUtxosResponse, SendTransactionError, SendTransactionRequest,
-    UtxosFilter,
-};
-use ic_btc_types_internal::{
-    BitcoinAdapterRequestWrapper, SendTransactionRequest as InternalSendTransactionRequest,
+    GetAddressHistoryError, GetAddressHistoryResponse, GetBalanceError, GetBlockHeadersError,
+    GetBlockHeadersResponse, GetUtxosError, GetUtxosResponse, Height, Page, SendTransactionError,
+    SendTransactionRequest, UtxosFilter,
 };
 use ic_logger::ReplicaLogger;
 use ic_metrics::MetricsRegistry;
-use ic_replicated_state::bitcoin_state::BitcoinStateError;
 
 // The maximum number of UTXOs that are allowed to be included in a single
 // `GetUtxosResponse`.
@@ -24,6 +20,15 @@ use ic_replicated_state::bitcoin_state::BitcoinStateError;
 // than 100_000 `Utxo`s are returned in a single response.
 const MAX_UTXOS_PER_RESPONSE: usize = 10_000;
 
+// The maximum number of transactions that are allowed to be included in a
+// single `GetAddressHistoryResponse`. A transaction takes ~40 bytes.
+const MAX_TRANSACTIONS_PER_RESPONSE: usize = 10_000;
+
+// The maximum number of block headers that are allowed to be included in a
+// single `GetBlockHeadersResponse`. A header takes 80 bytes, so a response is
+// at most ~800KiB.
+const MAX_BLOCK_HEADERS_PER_RESPONSE: usize = 10_000;
+
 /// The Bitcoin Canister component.
 ///
 /// Maintains information that is needed to be accessed at the bitcoin canister's
@@ -83,32 +88,43 @@ pub fn get_utxos(
     }
 }
 
+/// Retrieves the transactions that pay to or spend from the given Bitcoin
+/// address, newest first.
+pub fn get_address_history(
+    state: &State,
+    address: &str,
+    page: Option<Page>,
+) -> Result<GetAddressHistoryResponse, GetAddressHistoryError> {
+    store::get_address_history(
+        state,
+        address,
+        page.map(|page| page.to_vec()),
+        Some(MAX_TRANSACTIONS_PER_RESPONSE),
+    )
+}
+
+/// Retrieves the headers of the main chain blocks in the given height range.
+pub fn get_block_headers(
+    state: &State,
+    start_height: Height,
+    end_height: Option<Height>,
+) -> Result<GetBlockHeadersResponse, GetBlockHeadersError> {
+    store::get_block_headers(
+        state,
+        start_height,
+        end_height,
+        Some(MAX_BLOCK_HEADERS_PER_RESPONSE),
+    )
+}
+
+/// Validates a transaction and sends it to the Bitcoin network.
+///
+/// The transaction is rebroadcast until it's mined or expires.
 pub fn send_transaction(
     state: &mut State,
     request: SendTransactionRequest,
 ) -> Result<(), SendTransactionError> {
-    if Transaction::deserialize(&request.transaction).is_err() {
-        return Err(SendTransactionError::MalformedTransaction);
-    }
-
-    match state
-        .adapter_queues
-        .push_request(BitcoinAdapterRequestWrapper::SendTransactionRequest(
-            InternalSendTransactionRequest {
-                transaction: request.transaction,
-            },
-        )) {
-        Ok(()) => {}
-        Err(_err @ BitcoinStateError::QueueFull { .. }) => {
-            return Err(SendTransactionError::QueueFull);
-        }
-        // TODO(EXC-1098): Refactor the `push_request` method to not return these
-        // errors to avoid this `unreachable` statement.
-        Err(BitcoinStateError::FeatureNotEnabled)
-        | Err(BitcoinStateError::NonMatchingResponse { .. }) => unreachable!(),
-    }
-
-    Ok(())
+    outgoing_transactions::send(state, request.transaction)
 }
 
 #[cfg(test)]
@@ -525,24 +541,58 @@ mod test {
 
     #[test]
     fn send_transaction_adds_request_to_adapter_queue() {
-        let mut state = default_state();
+        let address = random_p2tr_address(Network::Regtest);
+        let coinbase_tx = TransactionBuilder::coinbase()
+            .with_output(&address, 1_000)
+            .build();
+        let block_0 = BlockBuilder::genesis()
+            .with_transaction(coinbase_tx.clone())
+            .build();
+        let mut state = State::new(1, Network::Regtest, block_0);
 
-        // Create a fake transaction that passes verification check.
-        let tx = TransactionBuilder::coinbase()
-            .with_output(&random_p2tr_address(Network::Testnet), 1_000)
+        // Create a transaction that spends the output of the coinbase transaction.
+        let tx = TransactionBuilder::new()
+            .with_input(bitcoin::OutPoint::new(coinbase_tx.txid(), 0))
+            .with_output(&random_p2tr_address(Network::Regtest), 1_000)
             .build();
 
         assert_eq!(state.adapter_queues.num_requests(), 0);
 
-        let _result = send_transaction(
-            &mut state,
-            SendTransactionRequest {
-                transaction: tx.serialize(),
-                network: BtcTypesNetwork::Testnet,
-            },
+        assert_eq!(
+            send_transaction(
+                &mut state,
+                SendTransactionRequest {
+                    transaction: tx.serialize(),
+                    network: BtcTypesNetwork::Regtest,
+                },
+            ),
+            Ok(())
         );
 
         assert_eq!(state.adapter_queues.num_requests(), 1);
+        assert_eq!(state.outgoing_transactions.len(), 1);
+    }
+
+    #[test]
+    fn send_transaction_rejects_spent_inputs() {
+        let mut state = default_state();
+
+        // A coinbase transaction doesn't spend any existing output.
+        let tx = TransactionBuilder::coinbase()
+            .with_output(&random_p2tr_address(Network::Regtest), 1_000)
+            .build();
+
+        assert_eq!(
+            send_transaction(
+                &mut state,
+                SendTransactionRequest {
+                    transaction: tx.serialize(),
+                    network: BtcTypesNetwork::Regtest,
+                },
+            ),
+            Err(SendTransactionError::InputNotFound)
+        );
+        assert_eq!(state.adapter_queues.num_requests(), 0);
     }
 
     #[test]
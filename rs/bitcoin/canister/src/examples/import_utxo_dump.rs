@@ -0,0 +1,222 @@
+//! A script for building the Bitcoin canister's state from a UTXO set dump of
+//! Bitcoin Core, and for inspecting a state built this way.
+//!
+//! The dump is created with `bitcoin-cli dumptxoutset <path>`. The state also
+//! needs the block following the dump's base block, as returned by
+//! `bitcoin-cli getblock <hash> 0`, as the root of its unstable blocks.
+//!
+//! The headers of the blocks up to the dump's height are not part of the dump,
+//! so `get_block_headers` can only return headers of later blocks.
+//!
+//! Example run:
+//!
+//! cargo run --release --example import-utxo-dump -- import \
+//!     --dump-path ./utxo.dat \
+//!     --next-block-path ./next_block.hex \
+//!     --network testnet \
+//!     --height 2400000 \
+//!     --state-path ./state-path \
+//!     --check-balance tb1qxyz...:100000
+//!
+//! cargo run --release --example import-utxo-dump -- inspect \
+//!     --state-path ./state-path
+use bitcoin::{consensus::deserialize, hashes::hex::FromHex, Block, Network};
+use clap::Parser;
+use ic_btc_canister::{
+    state::State,
+    store::get_balance,
+    utxo_dump::{insert_utxo, stats, UtxoDumpReader},
+};
+use ic_btc_types::{Height, Satoshi};
+use std::{fs::File, io::BufReader, path::PathBuf, time::SystemTime};
+
+#[derive(Parser, Debug)]
+enum Command {
+    /// Builds the state from a UTXO set dump.
+    Import(ImportArgs),
+
+    /// Prints the statistics of a state.
+    Inspect(InspectArgs),
+}
+
+#[derive(Parser, Debug)]
+struct ImportArgs {
+    /// The path of the file created by `dumptxoutset`.
+    #[clap(long, parse(from_os_str), value_hint = clap::ValueHint::FilePath)]
+    dump_path: PathBuf,
+
+    /// The path of a file with the hex-encoded block that follows the dump's
+    /// base block.
+    #[clap(long, parse(from_os_str), value_hint = clap::ValueHint::FilePath)]
+    next_block_path: PathBuf,
+
+    /// The bitcoin network.
+    #[clap(long)]
+    network: Network,
+
+    /// The height of the dump's base block.
+    #[clap(long)]
+    height: Height,
+
+    /// The number of confirmations a block needs to be considered stable.
+    #[clap(long, default_value_t = 0)]
+    stability_threshold: u32,
+
+    /// A path to store the state. Must not exist.
+    #[clap(long, parse(from_os_str), value_hint = clap::ValueHint::DirPath)]
+    state_path: PathBuf,
+
+    /// Verifies the balance of an address after the import, given as
+    /// `<address>:<satoshis>`. Can be given multiple times.
+    #[clap(long, parse(try_from_str = parse_balance))]
+    check_balance: Vec<(String, Satoshi)>,
+}
+
+#[derive(Parser, Debug)]
+struct InspectArgs {
+    /// A path to load the state from.
+    #[clap(long, parse(from_os_str), value_hint = clap::ValueHint::DirPath)]
+    state_path: PathBuf,
+
+    /// Verifies the balance of an address, given as `<address>:<satoshis>`.
+    /// Can be given multiple times.
+    #[clap(long, parse(try_from_str = parse_balance))]
+    check_balance: Vec<(String, Satoshi)>,
+}
+
+fn parse_balance(arg: &str) -> Result<(String, Satoshi), String> {
+    let (address, balance) = arg
+        .rsplit_once(':')
+        .ok_or_else(|| format!("expected <address>:<satoshis>, got {}", arg))?;
+    let balance = balance
+        .parse()
+        .map_err(|err| format!("invalid balance {}: {}", balance, err))?;
+    Ok((address.to_string(), balance))
+}
+
+fn main() {
+    let (state, check_balance) = match Command::parse() {
+        Command::Import(args) => (import(&args), args.check_balance),
+        Command::Inspect(args) => {
+            print!("Loading state at {:?}... ", args.state_path.to_str());
+            let state = State::load(&args.state_path).expect("Failed to load state");
+            println!("Done.");
+            (state, args.check_balance)
+        }
+    };
+
+    print_stats(&state);
+
+    if !check_balances(&state, &check_balance) {
+        std::process::exit(1);
+    }
+}
+
+fn import(args: &ImportArgs) -> State {
+    assert!(
+        !args.state_path.exists(),
+        "The state path {:?} already exists",
+        args.state_path
+    );
+
+    let next_block_hex =
+        std::fs::read_to_string(&args.next_block_path).expect("Failed to read the next block");
+    let next_block: Block = deserialize(
+        &Vec::<u8>::from_hex(next_block_hex.trim()).expect("The next block is not valid hex"),
+    )
+    .expect("Failed to deserialize the next block");
+
+    let dump = UtxoDumpReader::new(
+        BufReader::new(File::open(&args.dump_path).expect("Failed to open the dump")),
+        args.network,
+    )
+    .expect("Failed to read the dump's metadata");
+    println!(
+        "Importing {} coins at block {}",
+        dump.coins_count, dump.base_block_hash
+    );
+    assert_eq!(
+        next_block.header.prev_blockhash, dump.base_block_hash,
+        "The next block doesn't extend the dump's base block"
+    );
+
+    let mut state = State::new(args.stability_threshold, args.network, next_block);
+    state.height = args.height + 1;
+
+    let start = SystemTime::now();
+    let mut skipped = 0;
+    for (i, coin) in dump.enumerate() {
+        let (outpoint, txout, height) = coin.expect("Failed to read a coin");
+        assert!(
+            height <= args.height,
+            "Coin {} was created at height {}, after the dump's height",
+            outpoint,
+            height
+        );
+
+        if !insert_utxo(&mut state.utxos, outpoint, txout, height) {
+            skipped += 1;
+        }
+
+        if (i + 1) % 1_000_000 == 0 {
+            println!(
+                "Imported {} coins (took {} seconds so far)",
+                i + 1,
+                start.elapsed().expect("Time went backwards").as_secs()
+            );
+        }
+    }
+    println!(
+        "Done. Skipped {} unspendable coins. Took {} seconds",
+        skipped,
+        start.elapsed().expect("Time went backwards").as_secs()
+    );
+
+    print!("Serializing state to {:?}... ", args.state_path.to_str());
+    state
+        .serialize(&args.state_path)
+        .expect("Serialization failed");
+    println!("Done.");
+
+    state
+}
+
+fn print_stats(state: &State) {
+    let stats = stats(&state.utxos);
+    println!("Network: {}", state.utxos.network);
+    println!("Stable height: {}", state.height);
+    println!("UTXOs: {}", stats.num_utxos);
+    println!(
+        "  small: {}, medium: {}, large: {}",
+        stats.num_small_utxos, stats.num_medium_utxos, stats.num_large_utxos
+    );
+    for (script_type, count) in &stats.utxos_by_script_type {
+        println!("  {}: {}", script_type, count);
+    }
+    println!("Total value: {} satoshis", stats.total_value);
+    println!("Address index entries: {}", stats.num_address_entries);
+}
+
+// Returns whether all the addresses have the expected balance.
+fn check_balances(state: &State, expected: &[(String, Satoshi)]) -> bool {
+    let mut ok = true;
+    for (address, expected_balance) in expected {
+        match get_balance(state, address, 0) {
+            Ok(balance) if balance == *expected_balance => {
+                println!("Balance of {} is {} as expected", address, balance);
+            }
+            Ok(balance) => {
+                println!(
+                    "Balance of {} is {}, expected {}",
+                    address, balance, expected_balance
+                );
+                ok = false;
+            }
+            Err(err) => {
+                println!("Failed to get the balance of {}: {:?}", address, err);
+                ok = false;
+            }
+        }
+    }
+    ok
+}
@@ -1,4 +1,6 @@
-use crate::{blocktree::BlockDoesNotExtendTree, state::State, store, BitcoinCanister};
+use crate::{
+    blocktree::BlockDoesNotExtendTree, outgoing_transactions, state::State, store, BitcoinCanister,
+};
 use bitcoin::{
     hash_types::{BlockHash, TxMerkleNode},
     hashes::Hash,
@@ -19,7 +21,8 @@ impl BitcoinCanister {
     /// The heartbeat of the Bitcoin canister.
     ///
     /// The heartbeat sends and processes `GetSuccessor` requests/responses, which
-    /// is needed to fetch new blocks from the network.
+    /// is needed to fetch new blocks from the network, and rebroadcasts the
+    /// outgoing transactions.
     pub fn heartbeat(
         &self,
         bitcoin_state: ReplicatedBitcoinState,
@@ -71,6 +74,8 @@ impl BitcoinCanister {
                         | Err(BitcoinStateError::NonMatchingResponse { .. }) => unreachable!(),
                     }
                 }
+
+                outgoing_transactions::rebroadcast(&mut state, &self.log);
             }
             BitcoinFeatureStatus::Paused | BitcoinFeatureStatus::Disabled => {
                 // Don't send requests to the adapter.
@@ -148,11 +153,17 @@ fn maybe_reset_state(
         BitcoinNetwork::Regtest => Network::Regtest,
     };
 
-    // If the bitcoin feature is set for a different network than what's in the state
-    // or if the feature has been disabled, reset the state.
-    if state.utxo_set.network != feature_network || feature.status == BitcoinFeatureStatus::Disabled
+    // If the bitcoin feature is set for a different network than what's in the state,
+    // if the feature has been disabled, or if the address history has been toggled,
+    // reset the state. The address history is only complete if it is maintained from
+    // the genesis block onwards.
+    if state.utxo_set.network != feature_network
+        || feature.status == BitcoinFeatureStatus::Disabled
+        || state.block_index.address_history_enabled != feature.address_history
     {
-        ReplicatedBitcoinState::new(feature.network)
+        let mut state = ReplicatedBitcoinState::new(feature.network);
+        state.block_index.address_history_enabled = feature.address_history;
+        state
     } else {
         // Return state as-is.
         state
@@ -223,6 +234,7 @@ mod tests {
             BitcoinFeature {
                 network: BitcoinNetwork::Testnet,
                 status: BitcoinFeatureStatus::Disabled,
+                address_history: false,
             },
         );
         assert_eq!(state.adapter_queues.num_requests(), 0);
@@ -232,6 +244,7 @@ mod tests {
             BitcoinFeature {
                 network: BitcoinNetwork::Mainnet,
                 status: BitcoinFeatureStatus::Disabled,
+                address_history: false,
             },
         );
         assert_eq!(state.adapter_queues.num_requests(), 0);
@@ -247,6 +260,7 @@ mod tests {
             BitcoinFeature {
                 network: BitcoinNetwork::Testnet,
                 status: BitcoinFeatureStatus::Paused,
+                address_history: false,
             },
         );
         assert_eq!(state.adapter_queues.num_requests(), 0);
@@ -256,6 +270,7 @@ mod tests {
             BitcoinFeature {
                 network: BitcoinNetwork::Mainnet,
                 status: BitcoinFeatureStatus::Paused,
+                address_history: false,
             },
         );
         assert_eq!(state.adapter_queues.num_requests(), 0);
@@ -271,6 +286,7 @@ mod tests {
             BitcoinFeature {
                 network: BitcoinNetwork::Testnet,
                 status: BitcoinFeatureStatus::Enabled,
+                address_history: false,
             },
         );
         assert_eq!(state.adapter_queues.num_requests(), 1);
@@ -288,6 +304,7 @@ mod tests {
             BitcoinFeature {
                 network: BitcoinNetwork::Mainnet,
                 status: BitcoinFeatureStatus::Disabled,
+                address_history: false,
             },
         );
         assert_eq!(state, ReplicatedBitcoinState::new(BitcoinNetwork::Mainnet));
@@ -297,6 +314,7 @@ mod tests {
             BitcoinFeature {
                 network: BitcoinNetwork::Testnet,
                 status: BitcoinFeatureStatus::Disabled,
+                address_history: false,
             },
         );
         assert_eq!(state, ReplicatedBitcoinState::new(BitcoinNetwork::Testnet));
@@ -314,6 +332,7 @@ mod tests {
             BitcoinFeature {
                 network: BitcoinNetwork::Mainnet,
                 status: BitcoinFeatureStatus::Paused,
+                address_history: false,
             },
         );
 
@@ -326,6 +345,7 @@ mod tests {
             BitcoinFeature {
                 network: BitcoinNetwork::Testnet,
                 status: BitcoinFeatureStatus::Paused,
+                address_history: false,
             },
         );
 
@@ -335,4 +355,51 @@ mod tests {
             State::from(ReplicatedBitcoinState::new(BitcoinNetwork::Testnet)).into()
         );
     }
+
+    #[test]
+    fn state_is_reset_if_address_history_is_toggled() {
+        let mut state = ReplicatedBitcoinState::new(BitcoinNetwork::Testnet);
+        // Mutate the state in some way to later verify that the state has been reset.
+        state.stable_height = 17;
+
+        let bitcoin_canister = BitcoinCanister::new(&MetricsRegistry::new(), no_op_logger());
+        let state = bitcoin_canister.heartbeat(
+            state,
+            BitcoinFeature {
+                network: BitcoinNetwork::Testnet,
+                status: BitcoinFeatureStatus::Paused,
+                address_history: true,
+            },
+        );
+
+        // The state has been reset with the address history enabled.
+        assert_eq!(state.stable_height, 0);
+        assert!(state.block_index.address_history_enabled);
+        assert!(State::from(state.clone()).address_history.enabled);
+
+        let mut state = state;
+        state.stable_height = 17;
+
+        // Keeping the address history enabled leaves the state as-is.
+        let state = bitcoin_canister.heartbeat(
+            state,
+            BitcoinFeature {
+                network: BitcoinNetwork::Testnet,
+                status: BitcoinFeatureStatus::Paused,
+                address_history: true,
+            },
+        );
+        assert_eq!(state.stable_height, 17);
+
+        // Disabling the address history resets the state again.
+        let state = bitcoin_canister.heartbeat(
+            state,
+            BitcoinFeature {
+                network: BitcoinNetwork::Testnet,
+                status: BitcoinFeatureStatus::Paused,
+                address_history: false,
+            },
+        );
+        assert_eq!(state, ReplicatedBitcoinState::new(BitcoinNetwork::Testnet));
+    }
 }
@@ -1,8 +1,10 @@
+mod address_history;
 mod address_utxoset;
 mod blocktree;
 mod canister;
 mod heartbeat;
 mod metrics;
+mod outgoing_transactions;
 mod page_map_memory;
 use page_map_memory::PageMapMemory;
 pub mod fees;
@@ -10,6 +12,7 @@ pub mod state;
 pub mod store;
 mod types;
 mod unstable_blocks;
+pub mod utxo_dump;
 mod utxos;
 mod utxoset;
 
@@ -0,0 +1,457 @@
+//! Transactions sent with `bitcoin_send_transaction`.
+//!
+//! A transaction is only accepted if it spends outputs that are unspent at the
+//! tip of the main chain, or that are created by other outgoing transactions.
+//! Accepted transactions are kept in the state and sent to the adapter again
+//! whenever the main chain grows, as the adapter's peers may drop them, until
+//! they are mined or expire.
+//!
+//! Scripts and signatures are not verified, so anyone can send a transaction
+//! that spends any output. Such a transaction must not keep others from being
+//! sent: a transaction that spends the same outputs as outgoing transactions
+//! is accepted too, and the Bitcoin network decides which of them is mined.
+//! The others are dropped once their inputs are spent. If there are too many
+//! outgoing transactions, the oldest one is dropped, as it was broadcast the
+//! most often.
+use crate::{state::State, store, unstable_blocks, utxos::UtxosTrait};
+use bitcoin::{util::psbt::serialize::Deserialize, OutPoint, Transaction, TxOut, Txid};
+use ic_btc_types::{Height, SendTransactionError};
+use ic_btc_types_internal::{BitcoinAdapterRequestWrapper, SendTransactionRequest};
+use ic_logger::{debug, error, ReplicaLogger};
+use ic_replicated_state::bitcoin_state::{AdapterQueues, BitcoinStateError, OutgoingTransaction};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The maximum number of outgoing transactions.
+pub const MAX_OUTGOING_TRANSACTIONS: usize = 100;
+
+/// The number of blocks after which a transaction that isn't mined is dropped,
+/// which is about a day on mainnet.
+pub const EXPIRY_BLOCKS: Height = 144;
+
+/// Validates a transaction, sends it to the adapter and adds it to the
+/// outgoing transactions.
+pub fn send(state: &mut State, transaction: Vec<u8>) -> Result<(), SendTransactionError> {
+    let tx = Transaction::deserialize(&transaction)
+        .map_err(|_| SendTransactionError::MalformedTransaction)?;
+    let txid = tx.txid();
+
+    let mut view = UtxoView::new(state, tx.input.iter().map(|input| input.previous_output));
+    for outgoing in &state.outgoing_transactions {
+        let outgoing_tx = decode(outgoing);
+        if outgoing_tx.txid() == txid {
+            // The transaction was sent before and is rebroadcast already.
+            return Ok(());
+        }
+        view.add_outgoing(&outgoing_tx);
+    }
+    view.validate(&tx)?;
+
+    match push_request(&mut state.adapter_queues, transaction.clone()) {
+        Ok(()) => {}
+        Err(_err @ BitcoinStateError::QueueFull { .. }) => {
+            return Err(SendTransactionError::QueueFull);
+        }
+        // TODO(EXC-1098): Refactor the `push_request` method to not return these
+        // errors to avoid this `unreachable` statement.
+        Err(BitcoinStateError::FeatureNotEnabled)
+        | Err(BitcoinStateError::NonMatchingResponse { .. }) => unreachable!(),
+    }
+
+    if state.outgoing_transactions.len() >= MAX_OUTGOING_TRANSACTIONS {
+        // Transactions spending the outputs of the dropped transaction are
+        // dropped by the next rebroadcast.
+        state.outgoing_transactions.remove(0);
+    }
+    let height = store::main_chain_height(state);
+    state.outgoing_transactions.push(OutgoingTransaction {
+        transaction,
+        expiry_height: height + EXPIRY_BLOCKS,
+        last_broadcast_height: height,
+    });
+    Ok(())
+}
+
+/// Drops the outgoing transactions that are mined, conflict with the main
+/// chain, spend outputs that no longer exist or expired, and sends the others to the adapter again if the main
+/// chain grew since they were last sent.
+///
+/// The transactions are only checked again once the height of the main chain
+/// changed since they were last sent.
+pub fn rebroadcast(state: &mut State, log: &ReplicaLogger) {
+    if state.outgoing_transactions.is_empty() {
+        return;
+    }
+
+    let height = store::main_chain_height(state);
+    if state
+        .outgoing_transactions
+        .iter()
+        .all(|outgoing| outgoing.last_broadcast_height == height && outgoing.expiry_height > height)
+    {
+        return;
+    }
+
+    let outgoing_transactions = std::mem::take(&mut state.outgoing_transactions);
+    let transactions: Vec<Transaction> = outgoing_transactions.iter().map(decode).collect();
+
+    let mut view = UtxoView::new(
+        state,
+        transactions
+            .iter()
+            .flat_map(|tx| tx.input.iter().map(|input| input.previous_output)),
+    );
+    let mut retained = vec![];
+    for (outgoing, tx) in outgoing_transactions.into_iter().zip(transactions) {
+        if height >= outgoing.expiry_height {
+            debug!(log, "Outgoing transaction {} expired", tx.txid());
+            continue;
+        }
+        // Once a transaction is mined, its inputs are spent, so it's dropped here too.
+        if let Err(err) = view.validate(&tx) {
+            debug!(log, "Dropping outgoing transaction {}: {}", tx.txid(), err);
+            continue;
+        }
+        view.add_outgoing(&tx);
+        retained.push(outgoing);
+    }
+
+    for outgoing in retained.iter_mut() {
+        if outgoing.last_broadcast_height >= height {
+            continue;
+        }
+        match push_request(&mut state.adapter_queues, outgoing.transaction.clone()) {
+            Ok(()) => outgoing.last_broadcast_height = height,
+            Err(err) => {
+                // The remaining transactions are sent in a later heartbeat.
+                error!(
+                    log,
+                    "Could not rebroadcast outgoing transactions. Error: {:?}", err
+                );
+                break;
+            }
+        }
+    }
+
+    state.outgoing_transactions = retained;
+}
+
+fn push_request(
+    adapter_queues: &mut AdapterQueues,
+    transaction: Vec<u8>,
+) -> Result<(), BitcoinStateError> {
+    adapter_queues.push_request(BitcoinAdapterRequestWrapper::SendTransactionRequest(
+        SendTransactionRequest { transaction },
+    ))
+}
+
+// Outgoing transactions are validated before they are added, so they can
+// always be decoded.
+fn decode(outgoing: &OutgoingTransaction) -> Transaction {
+    Transaction::deserialize(&outgoing.transaction).expect("outgoing transaction must be valid")
+}
+
+// The UTXOs at the tip of the main chain, together with the outputs that are
+// created and spent by outgoing transactions.
+//
+// Only the outputs the view is created for are tracked, so that a transaction
+// can be checked without collecting all the outputs of the unstable blocks.
+struct UtxoView<'a> {
+    state: &'a State,
+
+    // The outputs that can be looked up.
+    outpoints: BTreeSet<OutPoint>,
+
+    // The transactions that created the outputs that can be looked up.
+    txids: BTreeSet<Txid>,
+
+    // Outputs created by the unstable blocks of the main chain or by outgoing
+    // transactions.
+    created: BTreeMap<OutPoint, TxOut>,
+
+    // Stable outputs spent by the unstable blocks of the main chain.
+    spent: BTreeSet<OutPoint>,
+}
+
+impl<'a> UtxoView<'a> {
+    fn new(state: &'a State, outpoints: impl IntoIterator<Item = OutPoint>) -> Self {
+        let outpoints: BTreeSet<OutPoint> = outpoints.into_iter().collect();
+        let txids = outpoints.iter().map(|outpoint| outpoint.txid).collect();
+        let mut view = Self {
+            state,
+            outpoints,
+            txids,
+            created: BTreeMap::new(),
+            spent: BTreeSet::new(),
+        };
+
+        for block in unstable_blocks::get_main_chain(&state.unstable_blocks).into_chain() {
+            for tx in &block.txdata {
+                if !tx.is_coin_base() {
+                    for input in &tx.input {
+                        if !view.outpoints.contains(&input.previous_output) {
+                            continue;
+                        }
+                        if view.created.remove(&input.previous_output).is_none() {
+                            view.spent.insert(input.previous_output);
+                        }
+                    }
+                }
+                view.insert_outputs(tx);
+            }
+        }
+
+        view
+    }
+
+    // Outgoing transactions may conflict with each other, so the outputs
+    // they spend can still be looked up.
+    fn add_outgoing(&mut self, tx: &Transaction) {
+        self.insert_outputs(tx);
+    }
+
+    fn insert_outputs(&mut self, tx: &Transaction) {
+        let txid = tx.txid();
+        if !self.txids.contains(&txid) {
+            return;
+        }
+        for (vout, output) in tx.output.iter().enumerate() {
+            let outpoint = OutPoint::new(txid, vout as u32);
+            if self.outpoints.contains(&outpoint) {
+                self.created.insert(outpoint, output.clone());
+            }
+        }
+    }
+
+    fn get(&self, outpoint: &OutPoint) -> Option<TxOut> {
+        if self.spent.contains(outpoint) {
+            return None;
+        }
+        match self.created.get(outpoint) {
+            Some(output) => Some(output.clone()),
+            None => self
+                .state
+                .utxos
+                .utxos
+                .get(outpoint)
+                .map(|(output, _)| output),
+        }
+    }
+
+    // Checks that the transaction only spends unspent outputs, and not more
+    // than their value.
+    fn validate(&self, tx: &Transaction) -> Result<(), SendTransactionError> {
+        if tx.input.is_empty() || tx.output.is_empty() {
+            return Err(SendTransactionError::MalformedTransaction);
+        }
+
+        let mut inputs = BTreeSet::new();
+        let mut input_value: u64 = 0;
+        for input in &tx.input {
+            if !inputs.insert(input.previous_output) {
+                // The same output is spent twice.
+                return Err(SendTransactionError::MalformedTransaction);
+            }
+            match self.get(&input.previous_output) {
+                Some(output) => input_value += output.value,
+                None => return Err(SendTransactionError::InputNotFound),
+            }
+        }
+
+        let output_value = tx
+            .output
+            .iter()
+            .fold(0u64, |sum, output| sum.saturating_add(output.value));
+        if output_value > input_value {
+            return Err(SendTransactionError::InsufficientInputValue);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bitcoin::{util::psbt::serialize::Serialize, Address, Block, Network};
+    use ic_btc_test_utils::{random_p2pkh_address, BlockBuilder, TransactionBuilder};
+    use ic_logger::replica_logger::no_op_logger;
+    use ic_replicated_state::bitcoin_state::UnstableBlocks;
+
+    // Returns a state whose only unstable block pays 1000 satoshis to `address`.
+    fn state_with_output(address: &Address) -> (State, Block) {
+        let coinbase_tx = TransactionBuilder::coinbase()
+            .with_output(address, 1000)
+            .build();
+        let block = BlockBuilder::genesis()
+            .with_transaction(coinbase_tx)
+            .build();
+        (State::new(2, Network::Regtest, block.clone()), block)
+    }
+
+    fn spend(outpoint: OutPoint, address: &Address, value: u64) -> Transaction {
+        TransactionBuilder::new()
+            .with_input(outpoint)
+            .with_output(address, value)
+            .build()
+    }
+
+    fn num_send_requests(state: &State) -> usize {
+        state.adapter_queues.num_requests()
+    }
+
+    #[test]
+    fn rejects_invalid_transactions() {
+        let address = random_p2pkh_address(Network::Regtest);
+        let (mut state, block) = state_with_output(&address);
+        let outpoint = OutPoint::new(block.txdata[0].txid(), 0);
+
+        assert_eq!(
+            send(&mut state, vec![1, 2, 3]),
+            Err(SendTransactionError::MalformedTransaction)
+        );
+        assert_eq!(
+            send(
+                &mut state,
+                spend(OutPoint::new(block.txdata[0].txid(), 1), &address, 1000).serialize()
+            ),
+            Err(SendTransactionError::InputNotFound)
+        );
+        assert_eq!(
+            send(&mut state, spend(outpoint, &address, 1001).serialize()),
+            Err(SendTransactionError::InsufficientInputValue)
+        );
+        assert_eq!(
+            send(
+                &mut state,
+                TransactionBuilder::new()
+                    .with_input(outpoint)
+                    .with_input(outpoint)
+                    .with_output(&address, 1000)
+                    .build()
+                    .serialize()
+            ),
+            Err(SendTransactionError::MalformedTransaction)
+        );
+        assert!(state.outgoing_transactions.is_empty());
+        assert_eq!(num_send_requests(&state), 0);
+    }
+
+    #[test]
+    fn accepts_transactions_spending_outgoing_transactions() {
+        let address = random_p2pkh_address(Network::Regtest);
+        let (mut state, block) = state_with_output(&address);
+
+        let tx_1 = spend(OutPoint::new(block.txdata[0].txid(), 0), &address, 900);
+        let tx_2 = spend(OutPoint::new(tx_1.txid(), 0), &address, 800);
+        assert_eq!(send(&mut state, tx_1.serialize()), Ok(()));
+        assert_eq!(send(&mut state, tx_2.serialize()), Ok(()));
+        // Sending the same transaction again is a no-op.
+        assert_eq!(send(&mut state, tx_1.serialize()), Ok(()));
+
+        assert_eq!(state.outgoing_transactions.len(), 2);
+        assert_eq!(num_send_requests(&state), 2);
+    }
+
+    #[test]
+    fn accepts_conflicting_transactions_until_one_is_mined() {
+        let address = random_p2pkh_address(Network::Regtest);
+        let (mut state, block_0) = state_with_output(&address);
+        let outpoint = OutPoint::new(block_0.txdata[0].txid(), 0);
+
+        // Signatures aren't verified, so an earlier transaction spending the
+        // same output doesn't keep another one from being sent.
+        let tx_1 = spend(outpoint, &address, 900);
+        let tx_2 = spend(outpoint, &address, 800);
+        assert_eq!(send(&mut state, tx_1.serialize()), Ok(()));
+        assert_eq!(send(&mut state, tx_2.serialize()), Ok(()));
+        assert_eq!(state.outgoing_transactions.len(), 2);
+        assert_eq!(num_send_requests(&state), 2);
+
+        let block_1 = BlockBuilder::with_prev_header(block_0.header)
+            .with_transaction(tx_2.clone())
+            .build();
+        store::insert_block(&mut state, block_1).unwrap();
+        rebroadcast(&mut state, &no_op_logger());
+        assert!(state.outgoing_transactions.is_empty());
+    }
+
+    #[test]
+    fn drops_the_oldest_transaction_when_full() {
+        let address = random_p2pkh_address(Network::Regtest);
+        let (mut state, block) = state_with_output(&address);
+        let outpoint = OutPoint::new(block.txdata[0].txid(), 0);
+
+        let mut transactions = vec![];
+        for value in 0..=MAX_OUTGOING_TRANSACTIONS as u64 {
+            let tx = spend(outpoint, &address, value);
+            assert_eq!(send(&mut state, tx.serialize()), Ok(()));
+            transactions.push(tx.serialize());
+        }
+
+        assert_eq!(state.outgoing_transactions.len(), MAX_OUTGOING_TRANSACTIONS);
+        assert_eq!(
+            state
+                .outgoing_transactions
+                .iter()
+                .map(|outgoing| outgoing.transaction.clone())
+                .collect::<Vec<_>>(),
+            transactions[1..]
+        );
+    }
+
+    #[test]
+    fn rebroadcasts_until_mined() {
+        let address = random_p2pkh_address(Network::Regtest);
+        let (mut state, block_0) = state_with_output(&address);
+        let tx = spend(OutPoint::new(block_0.txdata[0].txid(), 0), &address, 1000);
+        assert_eq!(send(&mut state, tx.serialize()), Ok(()));
+        assert_eq!(num_send_requests(&state), 1);
+
+        // The transaction isn't sent again while the main chain doesn't grow.
+        rebroadcast(&mut state, &no_op_logger());
+        assert_eq!(num_send_requests(&state), 1);
+
+        let block_1 = BlockBuilder::with_prev_header(block_0.header).build();
+        store::insert_block(&mut state, block_1.clone()).unwrap();
+        rebroadcast(&mut state, &no_op_logger());
+        assert_eq!(num_send_requests(&state), 2);
+        assert_eq!(state.outgoing_transactions[0].last_broadcast_height, 1);
+
+        // Once the transaction is mined, it's dropped.
+        let block_2 = BlockBuilder::with_prev_header(block_1.header)
+            .with_transaction(tx)
+            .build();
+        store::insert_block(&mut state, block_2).unwrap();
+        rebroadcast(&mut state, &no_op_logger());
+        assert!(state.outgoing_transactions.is_empty());
+        assert_eq!(num_send_requests(&state), 2);
+    }
+
+    #[test]
+    fn transactions_are_not_checked_again_while_the_height_is_unchanged() {
+        let address = random_p2pkh_address(Network::Regtest);
+        let (mut state, block) = state_with_output(&address);
+        let tx = spend(OutPoint::new(block.txdata[0].txid(), 0), &address, 1000);
+        assert_eq!(send(&mut state, tx.serialize()), Ok(()));
+
+        // Remove the spent output behind the view's back. As the height of the
+        // main chain didn't change, the transaction isn't checked again.
+        state.unstable_blocks = UnstableBlocks::new(2, BlockBuilder::genesis().build());
+        rebroadcast(&mut state, &no_op_logger());
+        assert_eq!(state.outgoing_transactions.len(), 1);
+    }
+
+    #[test]
+    fn drops_expired_transactions_and_their_descendants() {
+        let address = random_p2pkh_address(Network::Regtest);
+        let (mut state, block) = state_with_output(&address);
+        let tx_1 = spend(OutPoint::new(block.txdata[0].txid(), 0), &address, 1000);
+        let tx_2 = spend(OutPoint::new(tx_1.txid(), 0), &address, 1000);
+        assert_eq!(send(&mut state, tx_1.serialize()), Ok(()));
+        assert_eq!(send(&mut state, tx_2.serialize()), Ok(()));
+
+        state.outgoing_transactions[0].expiry_height = 0;
+        rebroadcast(&mut state, &no_op_logger());
+        assert!(state.outgoing_transactions.is_empty());
+    }
+}
@@ -3,8 +3,8 @@ use bitcoin::{hashes::Hash, Block, Network, OutPoint, Script, TxOut, Txid};
 use ic_btc_types::Height;
 use ic_protobuf::bitcoin::v1;
 use ic_replicated_state::bitcoin_state::{
-    AdapterQueues, BitcoinState as ReplicatedBitcoinState, FeePercentilesCache, UnstableBlocks,
-    UtxoSet as ReplicatedUtxoSet,
+    AdapterQueues, BitcoinState as ReplicatedBitcoinState, BlockIndex, FeePercentilesCache,
+    OutgoingTransaction, UnstableBlocks, UtxoSet as ReplicatedUtxoSet,
 };
 use ic_replicated_state::page_map::PersistenceError;
 use ic_state_layout::{AccessPolicy, ProtoFileWith, RwPolicy};
@@ -28,6 +28,15 @@ pub struct State {
 
     // Cache for the current fee percentiles.
     pub fee_percentiles_cache: Option<FeePercentilesCache>,
+
+    // The headers of all stable blocks, keyed by height.
+    pub block_headers: StableBTreeMap<PageMapMemory, Vec<u8>, Vec<u8>>,
+
+    // An optional index of the transactions of each address in stable blocks.
+    pub address_history: AddressHistory,
+
+    // Transactions that are rebroadcast until they are mined or expire.
+    pub outgoing_transactions: Vec<OutgoingTransaction>,
 }
 
 impl State {
@@ -43,9 +52,25 @@ impl State {
             unstable_blocks: UnstableBlocks::new(stability_threshold, genesis_block),
             adapter_queues: AdapterQueues::default(),
             fee_percentiles_cache: None,
+            block_headers: StableBTreeMap::new(
+                PageMapMemory::default(),
+                BLOCK_HEADER_KEY_SIZE,
+                BLOCK_HEADER_SIZE,
+            ),
+            address_history: AddressHistory::new(false),
+            outgoing_transactions: Vec::new(),
         }
     }
 
+    /// Enables the index of transactions by address.
+    ///
+    /// Only blocks that become stable after the index is enabled are indexed,
+    /// so it should be enabled before any block is inserted.
+    pub fn with_address_history(mut self) -> Self {
+        self.address_history.enabled = true;
+        self
+    }
+
     /// Serializes the state to disk at the given path.
     // TODO(EXC-1093): Guard this function with a rust feature. It's only needed in local scripts.
     pub fn serialize(&self, root: &Path) -> Result<(), PersistenceError> {
@@ -73,7 +98,16 @@ impl State {
             .utxos
             .medium_utxos
             .get_memory()
-            .persist_and_sync_delta(&root.join("medium_utxos.bin"))
+            .persist_and_sync_delta(&root.join("medium_utxos.bin"))?;
+
+        self.block_headers
+            .get_memory()
+            .persist_and_sync_delta(&root.join("block_headers.bin"))?;
+
+        self.address_history
+            .index
+            .get_memory()
+            .persist_and_sync_delta(&root.join("address_history.bin"))
     }
 
     // TODO(EXC-1093): Guard this function with a rust feature. It's only needed in local scripts.
@@ -81,6 +115,8 @@ impl State {
         let small_utxos_memory = PageMapMemory::open(&root.join("small_utxos.bin"))?;
         let medium_utxos_memory = PageMapMemory::open(&root.join("medium_utxos.bin"))?;
         let address_to_outpoints_memory = PageMapMemory::open(&root.join("address_outpoints.bin"))?;
+        let block_headers_memory = PageMapMemory::open(&root.join("block_headers.bin"))?;
+        let address_history_memory = PageMapMemory::open(&root.join("address_history.bin"))?;
 
         let state_file: ProtoFileWith<proto::State, RwPolicy> = root.join("state.pbuf").into();
         let proto_state = state_file.deserialize_opt().unwrap().unwrap();
@@ -97,6 +133,12 @@ impl State {
             unstable_blocks: UnstableBlocks::try_from(proto_state.unstable_blocks.unwrap())
                 .unwrap(),
             fee_percentiles_cache: None,
+            block_headers: StableBTreeMap::load(block_headers_memory),
+            address_history: AddressHistory {
+                enabled: proto_state.address_history_enabled,
+                index: StableBTreeMap::load(address_history_memory),
+            },
+            outgoing_transactions: Vec::new(),
         })
     }
 }
@@ -133,6 +175,20 @@ impl From<ReplicatedBitcoinState> for State {
                 ),
             },
             fee_percentiles_cache: state.fee_percentiles_cache,
+            block_headers: StableBTreeMap::init(
+                PageMapMemory::new(state.block_index.block_headers),
+                BLOCK_HEADER_KEY_SIZE,
+                BLOCK_HEADER_SIZE,
+            ),
+            address_history: AddressHistory {
+                enabled: state.block_index.address_history_enabled,
+                index: StableBTreeMap::init(
+                    PageMapMemory::new(state.block_index.address_history),
+                    MAX_ADDRESS_HISTORY_KEY_SIZE,
+                    0,
+                ),
+            },
+            outgoing_transactions: state.outgoing_transactions,
         }
     }
 }
@@ -155,6 +211,12 @@ impl From<State> for ReplicatedBitcoinState {
                 network: state.utxos.network,
             },
             fee_percentiles_cache: state.fee_percentiles_cache,
+            block_index: BlockIndex {
+                block_headers: state.block_headers.get_memory().into_page_map(),
+                address_history: state.address_history.index.get_memory().into_page_map(),
+                address_history_enabled: state.address_history.enabled,
+            },
+            outgoing_transactions: state.outgoing_transactions,
         }
     }
 }
@@ -165,6 +227,7 @@ impl From<&State> for proto::State {
             height: state.height,
             utxos: Some(state.utxos.to_proto()),
             unstable_blocks: Some(v1::UnstableBlocks::from(&state.unstable_blocks)),
+            address_history_enabled: state.address_history.enabled,
         }
     }
 }
@@ -250,6 +313,18 @@ pub const UTXO_VALUE_MAX_SIZE_MEDIUM: u32 = TX_OUT_MAX_SIZE_MEDIUM + HEIGHT_SIZE
 const MAX_ADDRESS_SIZE: u32 = 90;
 const MAX_ADDRESS_OUTPOINT_SIZE: u32 = MAX_ADDRESS_SIZE + OUTPOINT_SIZE;
 
+// The size of a transaction ID in bytes.
+const TXID_SIZE: u32 = 32;
+
+// An address is stored with a one-byte length prefix.
+const MAX_ADDRESS_HISTORY_KEY_SIZE: u32 = 1 + MAX_ADDRESS_SIZE + HEIGHT_SIZE + TXID_SIZE;
+
+/// The size of a key in the block headers map, which is a height.
+pub const BLOCK_HEADER_KEY_SIZE: u32 = HEIGHT_SIZE;
+
+/// The size of a block header in the Bitcoin wire format.
+pub const BLOCK_HEADER_SIZE: u32 = 80;
+
 impl Default for Utxos {
     fn default() -> Self {
         Self {
@@ -374,3 +449,31 @@ impl UtxoSet {
         }
     }
 }
+
+/// An index of the transactions that pay to or spend from each address in the
+/// stable blocks.
+///
+/// The index is optional because it stores an entry for every address touched
+/// by every transaction since genesis, and most users only need the UTXOs of
+/// an address. Entries are
+/// keyed by (address, height, txid), where the height is encoded such that
+/// the transactions of an address are sorted from newest to oldest.
+pub struct AddressHistory {
+    // Whether the index is maintained when blocks become stable.
+    pub enabled: bool,
+
+    pub index: StableBTreeMap<PageMapMemory, Vec<u8>, Vec<u8>>,
+}
+
+impl AddressHistory {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            index: StableBTreeMap::new(
+                PageMapMemory::default(),
+                MAX_ADDRESS_HISTORY_KEY_SIZE,
+                0, // No values are stored in the map.
+            ),
+        }
+    }
+}
@@ -1,11 +1,16 @@
 use crate::{
+    address_history,
     blocktree::{BlockChain, BlockDoesNotExtendTree},
     state::State,
-    types::Page,
+    types::{AddressHistoryPage, Page, Storable},
     unstable_blocks, utxoset,
 };
-use bitcoin::{hashes::Hash, Address, Block, OutPoint, Txid};
-use ic_btc_types::{GetBalanceError, GetUtxosError, GetUtxosResponse, Height, Satoshi};
+use bitcoin::{consensus::serialize, hashes::Hash, Address, Block, OutPoint, Txid};
+use ic_btc_types::{
+    Address as AddressStr, AddressTransaction, GetAddressHistoryError, GetAddressHistoryResponse,
+    GetBalanceError, GetBlockHeadersError, GetBlockHeadersResponse, GetUtxosError,
+    GetUtxosResponse, Height, Satoshi,
+};
 use lazy_static::lazy_static;
 use serde_bytes::ByteBuf;
 use std::str::FromStr;
@@ -179,6 +184,175 @@ fn get_utxos_from_chain(
     })
 }
 
+/// Returns the transactions that pay to or spend from a bitcoin address, sorted
+/// from newest to oldest.
+///
+/// Requires the address history index to be enabled, see
+/// [`State::with_address_history`]. On a subnet, it is enabled through the
+/// `address_history` flag of the bitcoin subnet feature.
+///
+/// If the optional `page` is set, then it will be used to return the next chunk
+/// of transactions starting from that page reference.
+///
+/// The optional `tx_limit` restricts the number of transactions that can be
+/// included in the response. A `page` reference will be returned along the
+/// transactions if there are more of them, which can be used in a subsequent
+/// request to retrieve the remaining transactions.
+pub fn get_address_history(
+    state: &State,
+    address: &str,
+    page: Option<Vec<u8>>,
+    tx_limit: Option<usize>,
+) -> Result<GetAddressHistoryResponse, GetAddressHistoryError> {
+    if Address::from_str(address).is_err() {
+        return Err(GetAddressHistoryError::MalformedAddress);
+    }
+
+    if !state.address_history.enabled {
+        return Err(GetAddressHistoryError::AddressHistoryDisabled);
+    }
+
+    let (chain, offset) = match page {
+        Some(page) => {
+            let AddressHistoryPage {
+                tip_block_hash,
+                height,
+                txid,
+            } = AddressHistoryPage::from_bytes(page)
+                .map_err(|err| GetAddressHistoryError::MalformedPage { err })?;
+            let chain =
+                unstable_blocks::get_chain_with_tip(&state.unstable_blocks, &tip_block_hash)
+                    .ok_or(GetAddressHistoryError::UnknownTipBlockHash {
+                        tip_block_hash: tip_block_hash.to_vec(),
+                    })?;
+            (chain, Some((height, txid)))
+        }
+        None => (
+            unstable_blocks::get_main_chain(&state.unstable_blocks),
+            None,
+        ),
+    };
+
+    let tip_block_hash = chain.tip().block_hash();
+    let tip_height = state.height + (chain.len() as u32) - 1;
+
+    // All the transactions in unstable blocks are newer than the ones in the
+    // index, so they come first.
+    let mut unstable_txs =
+        address_history::get_unstable_txs(&state.utxos, address, chain.into_chain(), state.height);
+    if let Some(offset) = offset {
+        unstable_txs = unstable_txs.split_off(&offset.to_bytes());
+    }
+
+    let stable_txs = state
+        .address_history
+        .index
+        .range(
+            address.to_string().to_bytes(),
+            offset.map(|offset| offset.to_bytes()),
+        )
+        .map(|(k, _)| {
+            let (_, height, txid) = <(AddressStr, Height, Txid)>::from_bytes(k);
+            (height, txid)
+        });
+
+    let mut txs = unstable_txs
+        .into_iter()
+        .map(<(Height, Txid)>::from_bytes)
+        .chain(stable_txs);
+
+    let transactions = txs
+        .by_ref()
+        .take(tx_limit.unwrap_or(usize::MAX))
+        .map(|(height, txid)| AddressTransaction {
+            txid: txid.to_vec(),
+            height,
+        })
+        .collect();
+
+    let next_page = txs.next().map(|(height, txid)| {
+        ByteBuf::from(
+            AddressHistoryPage {
+                tip_block_hash,
+                height,
+                txid,
+            }
+            .to_bytes(),
+        )
+    });
+
+    Ok(GetAddressHistoryResponse {
+        transactions,
+        tip_block_hash: tip_block_hash.to_vec(),
+        tip_height,
+        next_page,
+    })
+}
+
+/// Returns the headers of the blocks in the main chain with heights in
+/// `[start_height, end_height]`, in the Bitcoin wire format.
+///
+/// If `end_height` is not set, the range ends at the tip of the main chain.
+///
+/// The optional `header_limit` restricts the number of headers that can be
+/// included in the response. The remaining headers can be retrieved in a
+/// subsequent request starting at the height after the last returned header.
+pub fn get_block_headers(
+    state: &State,
+    start_height: Height,
+    end_height: Option<Height>,
+    header_limit: Option<usize>,
+) -> Result<GetBlockHeadersResponse, GetBlockHeadersError> {
+    let chain = unstable_blocks::get_main_chain(&state.unstable_blocks).into_chain();
+    let tip_height = state.height + (chain.len() as u32) - 1;
+
+    if start_height > tip_height {
+        return Err(GetBlockHeadersError::StartHeightDoesNotExist {
+            requested: start_height,
+            chain_height: tip_height,
+        });
+    }
+
+    let end_height = match end_height {
+        Some(end_height) if end_height > tip_height => {
+            return Err(GetBlockHeadersError::EndHeightDoesNotExist {
+                requested: end_height,
+                chain_height: tip_height,
+            });
+        }
+        Some(end_height) if end_height < start_height => {
+            return Err(GetBlockHeadersError::StartHeightLargerThanEndHeight {
+                start_height,
+                end_height,
+            });
+        }
+        Some(end_height) => end_height,
+        None => tip_height,
+    };
+
+    let block_headers = (start_height..=end_height)
+        .take(header_limit.unwrap_or(usize::MAX))
+        .map(|height| {
+            let header = if height < state.height {
+                // Blocks that became stable before headers were stored have
+                // no header.
+                state
+                    .block_headers
+                    .get(&height.to_bytes())
+                    .ok_or(GetBlockHeadersError::HeaderNotAvailable { height })?
+            } else {
+                serialize(&chain[(height - state.height) as usize].header)
+            };
+            Ok(ByteBuf::from(header))
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(GetBlockHeadersResponse {
+        tip_height,
+        block_headers,
+    })
+}
+
 /// Inserts a block into the state.
 /// Returns an error if the block doesn't extend any known block in the state.
 pub fn insert_block(state: &mut State, block: Block) -> Result<(), BlockDoesNotExtendTree> {
@@ -189,9 +363,22 @@ pub fn insert_block(state: &mut State, block: Block) -> Result<(), BlockDoesNotE
     // TODO(EXC-932): Process all stable blocks, not just one.
     if let Some(new_stable_block) = unstable_blocks::pop(&mut state.unstable_blocks) {
         for tx in &new_stable_block.txdata {
+            if state.address_history.enabled {
+                address_history::insert_tx(
+                    &mut state.address_history,
+                    &state.utxos,
+                    tx,
+                    state.height,
+                );
+            }
             utxoset::insert_tx(&mut state.utxos, tx, state.height);
         }
 
+        state
+            .block_headers
+            .insert(state.height.to_bytes(), serialize(&new_stable_block.header))
+            .expect("insertion must succeed");
+
         state.height += 1;
     }
 
@@ -215,7 +402,7 @@ mod test {
     use bitcoin::secp256k1::Secp256k1;
     use bitcoin::{consensus::Decodable, Address, BlockHash, Network, PublicKey};
     use byteorder::{LittleEndian, ReadBytesExt};
-    use ic_btc_test_utils::{BlockBuilder, TransactionBuilder};
+    use ic_btc_test_utils::{random_p2pkh_address, BlockBuilder, TransactionBuilder};
     use ic_btc_types::{OutPoint, Utxo};
     use proptest::prelude::*;
     use std::fs::File;
@@ -292,7 +479,7 @@ mod test {
         let mut block = BlockBuilder::genesis()
             .with_transaction(TransactionBuilder::coinbase().build())
             .build();
-        let mut state = State::new(2, Network::Bitcoin, block.clone());
+        let mut state = State::new(2, Network::Bitcoin, block.clone()).with_address_history();
 
         for _ in 0..100 {
             block = BlockBuilder::with_prev_header(block.header)
@@ -330,6 +517,29 @@ mod test {
         {
             assert_eq!(new_entry, old_entry);
         }
+
+        assert_eq!(new_state.block_headers.len(), state.block_headers.len());
+        for (new_entry, old_entry) in new_state
+            .block_headers
+            .iter()
+            .zip(state.block_headers.iter())
+        {
+            assert_eq!(new_entry, old_entry);
+        }
+
+        assert!(new_state.address_history.enabled);
+        assert_eq!(
+            new_state.address_history.index.len(),
+            state.address_history.index.len()
+        );
+        for (new_entry, old_entry) in new_state
+            .address_history
+            .index
+            .iter()
+            .zip(state.address_history.index.iter())
+        {
+            assert_eq!(new_entry, old_entry);
+        }
     }
 
     #[test]
@@ -958,4 +1168,213 @@ mod test {
             assert_eq!(utxo_set, utxos_chunked);
         }
     }
+
+    // Builds a chain of `num_blocks` blocks on top of a genesis block, where
+    // each block pays to `address`.
+    fn chain_paying_to(address: &Address, num_blocks: u64) -> Vec<Block> {
+        let mut blocks = vec![BlockBuilder::genesis()
+            .with_transaction(
+                TransactionBuilder::coinbase()
+                    .with_output(address, 1000)
+                    .build(),
+            )
+            .build()];
+        for i in 1..num_blocks {
+            let block = BlockBuilder::with_prev_header(blocks[blocks.len() - 1].header)
+                .with_transaction(
+                    TransactionBuilder::coinbase()
+                        .with_output(address, 1000 + i)
+                        .build(),
+                )
+                .build();
+            blocks.push(block);
+        }
+        blocks
+    }
+
+    #[test]
+    fn get_block_headers_of_stable_and_unstable_blocks() {
+        let network = Network::Regtest;
+        let blocks = chain_paying_to(&random_p2pkh_address(network), 6);
+        let mut state = State::new(2, network, blocks[0].clone());
+        for block in blocks[1..].iter() {
+            insert_block(&mut state, block.clone()).unwrap();
+        }
+        // Some of the blocks are stable and some are not.
+        assert!(state.height > 0);
+        assert!(state.height < 5);
+
+        let headers = |start: usize, end: usize| -> Vec<ByteBuf> {
+            blocks[start..=end]
+                .iter()
+                .map(|block| ByteBuf::from(serialize(&block.header)))
+                .collect()
+        };
+
+        assert_eq!(
+            get_block_headers(&state, 0, None, None),
+            Ok(GetBlockHeadersResponse {
+                tip_height: 5,
+                block_headers: headers(0, 5),
+            })
+        );
+        assert_eq!(
+            get_block_headers(&state, 1, Some(4), None),
+            Ok(GetBlockHeadersResponse {
+                tip_height: 5,
+                block_headers: headers(1, 4),
+            })
+        );
+        assert_eq!(
+            get_block_headers(&state, 2, None, Some(2)),
+            Ok(GetBlockHeadersResponse {
+                tip_height: 5,
+                block_headers: headers(2, 3),
+            })
+        );
+    }
+
+    #[test]
+    fn get_block_headers_rejects_invalid_ranges() {
+        let network = Network::Regtest;
+        let blocks = chain_paying_to(&random_p2pkh_address(network), 3);
+        let mut state = State::new(1, network, blocks[0].clone());
+        for block in blocks[1..].iter() {
+            insert_block(&mut state, block.clone()).unwrap();
+        }
+
+        assert_eq!(
+            get_block_headers(&state, 3, None, None),
+            Err(GetBlockHeadersError::StartHeightDoesNotExist {
+                requested: 3,
+                chain_height: 2
+            })
+        );
+        assert_eq!(
+            get_block_headers(&state, 0, Some(3), None),
+            Err(GetBlockHeadersError::EndHeightDoesNotExist {
+                requested: 3,
+                chain_height: 2
+            })
+        );
+        assert_eq!(
+            get_block_headers(&state, 2, Some(1), None),
+            Err(GetBlockHeadersError::StartHeightLargerThanEndHeight {
+                start_height: 2,
+                end_height: 1
+            })
+        );
+    }
+
+    #[test]
+    fn get_block_headers_reports_missing_stable_headers() {
+        let network = Network::Regtest;
+        let blocks = chain_paying_to(&random_p2pkh_address(network), 4);
+        let mut state = State::new(1, network, blocks[0].clone());
+        for block in blocks[1..].iter() {
+            insert_block(&mut state, block.clone()).unwrap();
+        }
+        // Simulates a block that became stable before headers were stored.
+        state.block_headers.remove(&1u32.to_bytes());
+
+        assert_eq!(
+            get_block_headers(&state, 0, None, None),
+            Err(GetBlockHeadersError::HeaderNotAvailable { height: 1 })
+        );
+        assert!(get_block_headers(&state, 2, None, None).is_ok());
+    }
+
+    #[test]
+    fn get_address_history_pages_through_stable_and_unstable_blocks() {
+        let network = Network::Regtest;
+        let address = random_p2pkh_address(network);
+        let blocks = chain_paying_to(&address, 7);
+        let mut state = State::new(2, network, blocks[0].clone()).with_address_history();
+        for block in blocks[1..].iter() {
+            insert_block(&mut state, block.clone()).unwrap();
+        }
+        assert!(state.height > 0);
+
+        // The history is returned newest first, two transactions at a time.
+        let mut transactions = vec![];
+        let mut page = None;
+        loop {
+            let response =
+                get_address_history(&state, &address.to_string(), page, Some(2)).unwrap();
+            assert_eq!(response.tip_height, 6);
+            assert_eq!(response.tip_block_hash, blocks[6].block_hash().to_vec());
+            assert!(response.transactions.len() <= 2);
+            transactions.extend(response.transactions);
+            match response.next_page {
+                Some(next_page) => page = Some(next_page.to_vec()),
+                None => break,
+            }
+        }
+
+        assert_eq!(
+            transactions,
+            blocks
+                .iter()
+                .enumerate()
+                .rev()
+                .map(|(height, block)| AddressTransaction {
+                    txid: block.txdata[0].txid().to_vec(),
+                    height: height as u32,
+                })
+                .collect::<Vec<_>>()
+        );
+
+        // Other addresses have no history.
+        assert_eq!(
+            get_address_history(
+                &state,
+                &random_p2pkh_address(network).to_string(),
+                None,
+                None
+            )
+            .unwrap()
+            .transactions,
+            vec![]
+        );
+    }
+
+    #[test]
+    fn get_address_history_errors() {
+        let network = Network::Regtest;
+        let address = random_p2pkh_address(network).to_string();
+        let state = State::new(2, network, genesis_block(network));
+
+        assert_eq!(
+            get_address_history(&state, "not an address", None, None),
+            Err(GetAddressHistoryError::MalformedAddress)
+        );
+        assert_eq!(
+            get_address_history(&state, &address, None, None),
+            Err(GetAddressHistoryError::AddressHistoryDisabled)
+        );
+
+        let state = State::new(2, network, genesis_block(network)).with_address_history();
+        assert!(matches!(
+            get_address_history(&state, &address, Some(vec![1, 2, 3]), None),
+            Err(GetAddressHistoryError::MalformedPage { .. })
+        ));
+        assert_eq!(
+            get_address_history(
+                &state,
+                &address,
+                Some(
+                    AddressHistoryPage {
+                        tip_block_hash: BlockHash::from_hash(Hash::from_slice(&[1; 32]).unwrap()),
+                        height: 0,
+                        txid: Txid::from_hash(Hash::from_slice(&[2; 32]).unwrap()),
+                    }
+                    .to_bytes()
+                ),
+                None
+            ),
+            Err(GetAddressHistoryError::UnknownTipBlockHash {
+                tip_block_hash: vec![1; 32]
+            })
+        );
+    }
 }
@@ -61,6 +61,56 @@ impl Page {
     }
 }
 
+/// Used to signal the cut-off point for returning chunked address history
+/// results.
+pub struct AddressHistoryPage {
+    pub tip_block_hash: BlockHash,
+    pub height: Height,
+    pub txid: Txid,
+}
+
+impl AddressHistoryPage {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        vec![
+            self.tip_block_hash.to_vec(),
+            (self.height, self.txid).to_bytes(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    pub fn from_bytes(mut bytes: Vec<u8>) -> Result<Self, String> {
+        // The page consists of 68 bytes and is the concatenation of the following:
+        //
+        //   1) A `BlockHash` (32 bytes)
+        //   2) A `Height` (4 bytes)
+        //   3) A `Txid` (32 bytes)
+        if bytes.len() != 68 {
+            return Err(format!("Invalid length {} != 68 for page", bytes.len()));
+        }
+
+        let txid_bytes = bytes.split_off(36);
+        let height_bytes = bytes.split_off(32);
+
+        let tip_block_hash = BlockHash::from_hash(
+            Hash::from_slice(&bytes)
+                .map_err(|err| format!("Could not parse tip block hash: {}", err))?,
+        );
+        // The length of `height_bytes` was checked above, so decoding it cannot panic.
+        let height = Height::from_bytes(height_bytes);
+        let txid = Txid::from_hash(
+            Hash::from_slice(&txid_bytes)
+                .map_err(|err| format!("Could not parse txid: {}", err))?,
+        );
+        Ok(AddressHistoryPage {
+            tip_block_hash,
+            height,
+            txid,
+        })
+    }
+}
+
 fn outpoint_from_bytes(bytes: Vec<u8>) -> Result<OutPoint, String> {
     if bytes.len() != 36 {
         return Err(format!("Invalid length {} != 36 for outpoint", bytes.len()));
@@ -171,6 +221,33 @@ impl Storable for (Address, Height, OutPoint) {
     }
 }
 
+impl Storable for (Address, Height, Txid) {
+    fn to_bytes(&self) -> Vec<u8> {
+        vec![
+            Address::to_bytes(&self.0),
+            self.1.to_bytes(),
+            self.2.to_vec(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    fn from_bytes(mut bytes: Vec<u8>) -> Self {
+        let address_len = bytes[0] as usize;
+        let height_offset = address_len + 1;
+        let txid_offset = address_len + 5;
+        let txid_bytes = bytes.split_off(txid_offset);
+        let height_bytes = bytes.split_off(height_offset);
+
+        (
+            Address::from_bytes(bytes),
+            Height::from_bytes(height_bytes),
+            Txid::from_hash(Hash::from_slice(&txid_bytes).unwrap()),
+        )
+    }
+}
+
 impl Storable for Height {
     fn to_bytes(&self) -> Vec<u8> {
         // The height is represented as an XOR'ed big endian byte array
@@ -209,6 +286,24 @@ impl Storable for (Height, OutPoint) {
     }
 }
 
+impl Storable for (Height, Txid) {
+    fn to_bytes(&self) -> Vec<u8> {
+        vec![self.0.to_bytes(), self.1.to_vec()]
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    fn from_bytes(mut bytes: Vec<u8>) -> Self {
+        let txid_bytes = bytes.split_off(4);
+
+        (
+            Height::from_bytes(bytes),
+            Txid::from_hash(Hash::from_slice(&txid_bytes).unwrap()),
+        )
+    }
+}
+
 #[test]
 fn parsing_empty_page_fails() {
     assert!(Page::from_bytes(vec![]).is_err());
@@ -225,3 +320,18 @@ fn parsing_page_with_invalid_length_fails() {
 fn parsing_page_with_exact_length_succeeds() {
     assert!(Page::from_bytes(vec![0; 72]).is_ok());
 }
+
+#[test]
+fn address_history_page_roundtrip() {
+    let page = AddressHistoryPage {
+        tip_block_hash: BlockHash::from_hash(Hash::from_slice(&[1; 32]).unwrap()),
+        height: 42,
+        txid: Txid::from_hash(Hash::from_slice(&[2; 32]).unwrap()),
+    };
+    let decoded = AddressHistoryPage::from_bytes(page.to_bytes()).unwrap();
+    assert_eq!(decoded.tip_block_hash, page.tip_block_hash);
+    assert_eq!(decoded.height, page.height);
+    assert_eq!(decoded.txid, page.txid);
+
+    assert!(AddressHistoryPage::from_bytes(vec![0; 72]).is_err());
+}
@@ -0,0 +1,565 @@
+//! Reading of the UTXO set dumps created by the `dumptxoutset` RPC of Bitcoin
+//! Core, which allows bootstrapping a UTXO set at a given height without
+//! processing all the blocks up to that height.
+//!
+//! Two formats are supported: the format with a metadata header written by
+//! Bitcoin Core 28 and later, where the coins are grouped by transaction, and
+//! the format written by earlier versions, where every coin is preceded by its
+//! full outpoint.
+use crate::{state::UtxoSet, utxos::UtxosTrait, utxoset};
+use bitcoin::{
+    blockdata::{
+        opcodes::all::{OP_CHECKSIG, OP_DUP, OP_EQUAL, OP_EQUALVERIFY, OP_HASH160, OP_RETURN},
+        script::Builder,
+    },
+    consensus::{encode::VarInt, Decodable},
+    hashes::Hash,
+    BlockHash, Network, OutPoint, PublicKey, Script, TxOut, Txid,
+};
+use byteorder::ReadBytesExt;
+use ic_btc_types::{Height, Satoshi};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::io::{self, Read};
+
+/// The magic bytes at the start of a dump that has a metadata header.
+const SNAPSHOT_MAGIC: [u8; 5] = *b"utxo\xff";
+
+/// The version of the metadata header that is supported.
+const SNAPSHOT_VERSION: u16 = 2;
+
+/// The number of script sizes that are reserved for compressed scripts.
+const NUM_SPECIAL_SCRIPTS: u64 = 6;
+
+/// Scripts larger than this are unspendable and are not stored in a dump.
+const MAX_SCRIPT_SIZE: u64 = 10_000;
+
+/// An iterator over the coins of a `dumptxoutset` file.
+pub struct UtxoDumpReader<R> {
+    reader: R,
+
+    /// The hash of the block at which the dump was taken.
+    pub base_block_hash: BlockHash,
+
+    /// The number of coins in the dump.
+    pub coins_count: u64,
+
+    // Whether the coins are grouped by transaction.
+    grouped: bool,
+
+    coins_read: u64,
+
+    // The transaction of the current group and the number of its coins that
+    // haven't been read yet.
+    group: Option<(Txid, u64)>,
+}
+
+impl<R: Read> UtxoDumpReader<R> {
+    /// Reads the metadata of the dump. If the dump has a metadata header, it
+    /// is verified to be a dump of the given network.
+    pub fn new(mut reader: R, network: Network) -> io::Result<Self> {
+        let mut prefix = [0; 5];
+        reader.read_exact(&mut prefix)?;
+
+        let (base_block_hash, grouped) = if prefix == SNAPSHOT_MAGIC {
+            let version: u16 = decode(&mut reader)?;
+            if version != SNAPSHOT_VERSION {
+                return Err(invalid_data(format!(
+                    "unsupported snapshot version {}",
+                    version
+                )));
+            }
+            let magic: u32 = decode(&mut reader)?;
+            if magic != network.magic() {
+                return Err(invalid_data(format!(
+                    "the snapshot is not a snapshot of {}",
+                    network
+                )));
+            }
+            (decode(&mut reader)?, true)
+        } else {
+            // Without a metadata header, the dump starts with the block hash.
+            let mut bytes = prefix.to_vec();
+            bytes.extend(read_bytes(&mut reader, 32 - prefix.len() as u64)?);
+            (BlockHash::from_slice(&bytes).unwrap(), false)
+        };
+
+        Ok(Self {
+            coins_count: decode(&mut reader)?,
+            reader,
+            base_block_hash,
+            grouped,
+            coins_read: 0,
+            group: None,
+        })
+    }
+
+    fn read_coin(&mut self) -> io::Result<(OutPoint, TxOut, Height)> {
+        let outpoint = if self.grouped {
+            let (txid, remaining) = match self.group {
+                Some((txid, remaining)) if remaining > 0 => (txid, remaining),
+                _ => {
+                    let txid = decode(&mut self.reader)?;
+                    let coins: VarInt = decode(&mut self.reader)?;
+                    if coins.0 == 0 {
+                        return Err(invalid_data(format!("transaction {} has no coins", txid)));
+                    }
+                    (txid, coins.0)
+                }
+            };
+            self.group = Some((txid, remaining - 1));
+
+            let vout: VarInt = decode(&mut self.reader)?;
+            let vout = u32::try_from(vout.0).map_err(|_| invalid_data("vout is too large"))?;
+            OutPoint::new(txid, vout)
+        } else {
+            decode(&mut self.reader)?
+        };
+
+        // The code is the height shifted left by one, with the lowest bit set
+        // for coinbase outputs, which the UTXO set doesn't distinguish.
+        let code = read_varint(&mut self.reader)?;
+        let height =
+            Height::try_from(code >> 1).map_err(|_| invalid_data("height is too large"))?;
+        let value = decompress_amount(read_varint(&mut self.reader)?);
+        let script_pubkey = read_script(&mut self.reader)?;
+
+        Ok((
+            outpoint,
+            TxOut {
+                value,
+                script_pubkey,
+            },
+            height,
+        ))
+    }
+}
+
+impl<R: Read> Iterator for UtxoDumpReader<R> {
+    type Item = io::Result<(OutPoint, TxOut, Height)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.coins_read == self.coins_count {
+            return None;
+        }
+        self.coins_read += 1;
+        Some(self.read_coin())
+    }
+}
+
+/// Inserts a UTXO read from a dump into the UTXO set. Outputs that can never be
+/// spent are skipped, as they are when a block is inserted.
+///
+/// Returns whether the UTXO was inserted.
+pub fn insert_utxo(
+    utxo_set: &mut UtxoSet,
+    outpoint: OutPoint,
+    txout: TxOut,
+    height: Height,
+) -> bool {
+    if txout.script_pubkey.is_provably_unspendable() {
+        return false;
+    }
+    utxoset::insert_utxo(utxo_set, outpoint, txout, height);
+    true
+}
+
+/// Statistics of a UTXO set.
+#[derive(Debug, Default, PartialEq)]
+pub struct UtxoSetStats {
+    pub num_utxos: u64,
+    pub total_value: Satoshi,
+    pub num_small_utxos: u64,
+    pub num_medium_utxos: u64,
+    pub num_large_utxos: u64,
+    // The number of entries in the index of outpoints by address.
+    pub num_address_entries: u64,
+    // The number of UTXOs by type of their script.
+    pub utxos_by_script_type: BTreeMap<&'static str, u64>,
+}
+
+/// Computes the statistics of a UTXO set. This iterates over all UTXOs.
+pub fn stats(utxo_set: &UtxoSet) -> UtxoSetStats {
+    let mut stats = UtxoSetStats {
+        num_utxos: utxo_set.utxos.len(),
+        num_small_utxos: utxo_set.utxos.small_utxos.len(),
+        num_medium_utxos: utxo_set.utxos.medium_utxos.len(),
+        num_large_utxos: utxo_set.utxos.large_utxos.len() as u64,
+        num_address_entries: utxo_set.address_to_outpoints.len(),
+        ..Default::default()
+    };
+
+    for (_, (txout, _)) in utxo_set.utxos.iter() {
+        stats.total_value += txout.value;
+        *stats
+            .utxos_by_script_type
+            .entry(script_type(&txout.script_pubkey))
+            .or_default() += 1;
+    }
+
+    stats
+}
+
+fn script_type(script: &Script) -> &'static str {
+    if script.is_p2pkh() {
+        "p2pkh"
+    } else if script.is_p2sh() {
+        "p2sh"
+    } else if script.is_v0_p2wpkh() {
+        "p2wpkh"
+    } else if script.is_v0_p2wsh() {
+        "p2wsh"
+    } else if script.is_v1_p2tr() {
+        "p2tr"
+    } else if script.is_p2pk() {
+        "p2pk"
+    } else {
+        "other"
+    }
+}
+
+// Reads an integer in the variable-length encoding that Bitcoin Core uses for
+// the coins database (`VARINT`), which differs from the `CompactSize` encoding
+// of the peer-to-peer protocol.
+fn read_varint<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut n: u64 = 0;
+    loop {
+        let byte = reader.read_u8()?;
+        if n > (u64::MAX >> 7) {
+            return Err(invalid_data("VARINT is too large"));
+        }
+        n = (n << 7) | u64::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            return Ok(n);
+        }
+        n = n
+            .checked_add(1)
+            .ok_or_else(|| invalid_data("VARINT is too large"))?;
+    }
+}
+
+// Reverses the amount compression of Bitcoin Core, which exploits that most
+// amounts are round numbers.
+fn decompress_amount(x: u64) -> Satoshi {
+    if x == 0 {
+        return 0;
+    }
+    let mut x = x - 1;
+    // The exponent of the amount.
+    let mut e = x % 10;
+    x /= 10;
+    let mut n = if e < 9 {
+        let d = (x % 9) + 1;
+        x /= 9;
+        x * 10 + d
+    } else {
+        x + 1
+    };
+    while e > 0 {
+        n *= 10;
+        e -= 1;
+    }
+    n
+}
+
+// Reads a script in the compressed encoding of Bitcoin Core. The script sizes
+// below `NUM_SPECIAL_SCRIPTS` encode standard scripts by their hash or key.
+fn read_script<R: Read>(reader: &mut R) -> io::Result<Script> {
+    let size = read_varint(reader)?;
+    let script = match size {
+        0 => Builder::new()
+            .push_opcode(OP_DUP)
+            .push_opcode(OP_HASH160)
+            .push_slice(&read_bytes(reader, 20)?)
+            .push_opcode(OP_EQUALVERIFY)
+            .push_opcode(OP_CHECKSIG)
+            .into_script(),
+        1 => Builder::new()
+            .push_opcode(OP_HASH160)
+            .push_slice(&read_bytes(reader, 20)?)
+            .push_opcode(OP_EQUAL)
+            .into_script(),
+        2 | 3 => {
+            let mut key = vec![size as u8];
+            key.extend(read_bytes(reader, 32)?);
+            Builder::new()
+                .push_slice(&key)
+                .push_opcode(OP_CHECKSIG)
+                .into_script()
+        }
+        4 | 5 => {
+            // An uncompressed public key, stored in its compressed form.
+            let mut key = vec![size as u8 - 2];
+            key.extend(read_bytes(reader, 32)?);
+            let mut key = PublicKey::from_slice(&key)
+                .map_err(|err| invalid_data(format!("invalid public key: {}", err)))?;
+            key.compressed = false;
+            Builder::new()
+                .push_slice(&key.to_bytes())
+                .push_opcode(OP_CHECKSIG)
+                .into_script()
+        }
+        _ => {
+            let len = size - NUM_SPECIAL_SCRIPTS;
+            if len > MAX_SCRIPT_SIZE {
+                // Bitcoin Core replaces oversized scripts with an unspendable one.
+                io::copy(&mut reader.take(len), &mut io::sink())?;
+                Builder::new().push_opcode(OP_RETURN).into_script()
+            } else {
+                Script::from(read_bytes(reader, len)?)
+            }
+        }
+    };
+    Ok(script)
+}
+
+fn read_bytes<R: Read>(reader: &mut R, len: u64) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![0; len as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn decode<T: Decodable, R: Read>(reader: &mut R) -> io::Result<T> {
+    T::consensus_decode(reader).map_err(|err| invalid_data(err.to_string()))
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{state::State, store};
+    use bitcoin::consensus::Encodable;
+    use bitcoin::hashes::hex::FromHex;
+    use ic_btc_test_utils::{random_p2pkh_address, BlockBuilder};
+
+    // Writes an integer in Bitcoin Core's `VARINT` encoding.
+    fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+        let mut bytes = vec![];
+        loop {
+            let marker = if bytes.is_empty() { 0 } else { 0x80 };
+            bytes.push((n & 0x7f) as u8 | marker);
+            if n <= 0x7f {
+                break;
+            }
+            n = (n >> 7) - 1;
+        }
+        out.extend(bytes.iter().rev());
+    }
+
+    // Writes a P2PKH coin with the given compressed amount.
+    fn write_coin(out: &mut Vec<u8>, height: Height, compressed_amount: u64, script: &Script) {
+        assert!(script.is_p2pkh());
+        write_varint(out, u64::from(height) << 1);
+        write_varint(out, compressed_amount);
+        write_varint(out, 0);
+        out.extend(&script.as_bytes()[3..23]);
+    }
+
+    #[test]
+    fn varint_roundtrip() {
+        for n in [
+            0,
+            1,
+            127,
+            128,
+            255,
+            16_511,
+            16_512,
+            u32::MAX as u64,
+            u64::MAX,
+        ] {
+            let mut bytes = vec![];
+            write_varint(&mut bytes, n);
+            assert_eq!(read_varint(&mut bytes.as_slice()).unwrap(), n);
+        }
+    }
+
+    #[test]
+    fn decompresses_amounts() {
+        // Test vectors from Bitcoin Core's `compress_tests.cpp`.
+        assert_eq!(decompress_amount(0x0), 0);
+        assert_eq!(decompress_amount(0x1), 1);
+        assert_eq!(decompress_amount(0x7), 1_000_000);
+        assert_eq!(decompress_amount(0x9), 100_000_000);
+        assert_eq!(decompress_amount(0x32), 5_000_000_000);
+        assert_eq!(decompress_amount(0x1406f40), 2_100_000_000_000_000);
+    }
+
+    #[test]
+    fn decompresses_scripts() {
+        let network = Network::Regtest;
+        let p2pkh = random_p2pkh_address(network).script_pubkey();
+        let mut bytes = vec![0];
+        bytes.extend(&p2pkh.as_bytes()[3..23]);
+        assert_eq!(read_script(&mut bytes.as_slice()).unwrap(), p2pkh);
+
+        let mut bytes = vec![1];
+        bytes.extend(&[7; 20]);
+        assert!(read_script(&mut bytes.as_slice()).unwrap().is_p2sh());
+
+        // The generator point of secp256k1, in compressed form.
+        let key = PublicKey::from_slice(
+            &Vec::<u8>::from_hex(
+                "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let mut bytes = key.to_bytes();
+        bytes[0] += 2;
+        let mut uncompressed_key = key;
+        uncompressed_key.compressed = false;
+        assert_eq!(
+            read_script(&mut bytes.as_slice()).unwrap(),
+            Builder::new()
+                .push_slice(&uncompressed_key.to_bytes())
+                .push_opcode(OP_CHECKSIG)
+                .into_script()
+        );
+
+        let mut bytes = vec![];
+        write_varint(&mut bytes, NUM_SPECIAL_SCRIPTS + 3);
+        bytes.extend(&[1, 2, 3]);
+        assert_eq!(
+            read_script(&mut bytes.as_slice()).unwrap(),
+            Script::from(vec![1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn reads_legacy_dump() {
+        let script = random_p2pkh_address(Network::Regtest).script_pubkey();
+        let block_hash = BlockHash::from_slice(&[1; 32]).unwrap();
+        let outpoints = [
+            OutPoint::new(Txid::from_slice(&[2; 32]).unwrap(), 0),
+            OutPoint::new(Txid::from_slice(&[3; 32]).unwrap(), 5),
+        ];
+
+        let mut bytes = vec![];
+        block_hash.consensus_encode(&mut bytes).unwrap();
+        2u64.consensus_encode(&mut bytes).unwrap();
+        for outpoint in &outpoints {
+            outpoint.consensus_encode(&mut bytes).unwrap();
+            write_coin(&mut bytes, 10, 0x9, &script);
+        }
+
+        let reader = UtxoDumpReader::new(bytes.as_slice(), Network::Regtest).unwrap();
+        assert_eq!(reader.base_block_hash, block_hash);
+        assert_eq!(reader.coins_count, 2);
+        let txout = TxOut {
+            value: 100_000_000,
+            script_pubkey: script,
+        };
+        assert_eq!(
+            reader.collect::<io::Result<Vec<_>>>().unwrap(),
+            vec![(outpoints[0], txout.clone(), 10), (outpoints[1], txout, 10)]
+        );
+    }
+
+    #[test]
+    fn reads_dump_with_metadata() {
+        let script = random_p2pkh_address(Network::Regtest).script_pubkey();
+        let block_hash = BlockHash::from_slice(&[1; 32]).unwrap();
+        let txid_1 = Txid::from_slice(&[2; 32]).unwrap();
+        let txid_2 = Txid::from_slice(&[3; 32]).unwrap();
+
+        let mut bytes = SNAPSHOT_MAGIC.to_vec();
+        SNAPSHOT_VERSION.consensus_encode(&mut bytes).unwrap();
+        Network::Regtest
+            .magic()
+            .consensus_encode(&mut bytes)
+            .unwrap();
+        block_hash.consensus_encode(&mut bytes).unwrap();
+        3u64.consensus_encode(&mut bytes).unwrap();
+        txid_1.consensus_encode(&mut bytes).unwrap();
+        VarInt(2).consensus_encode(&mut bytes).unwrap();
+        VarInt(0).consensus_encode(&mut bytes).unwrap();
+        write_coin(&mut bytes, 3, 0x1, &script);
+        VarInt(4).consensus_encode(&mut bytes).unwrap();
+        write_coin(&mut bytes, 3, 0x7, &script);
+        txid_2.consensus_encode(&mut bytes).unwrap();
+        VarInt(1).consensus_encode(&mut bytes).unwrap();
+        VarInt(1).consensus_encode(&mut bytes).unwrap();
+        write_coin(&mut bytes, 7, 0x9, &script);
+
+        assert!(UtxoDumpReader::new(bytes.as_slice(), Network::Bitcoin).is_err());
+
+        let reader = UtxoDumpReader::new(bytes.as_slice(), Network::Regtest).unwrap();
+        assert_eq!(reader.base_block_hash, block_hash);
+        assert_eq!(
+            reader
+                .map(|coin| coin.map(|(outpoint, txout, height)| (outpoint, txout.value, height)))
+                .collect::<io::Result<Vec<_>>>()
+                .unwrap(),
+            vec![
+                (OutPoint::new(txid_1, 0), 1, 3),
+                (OutPoint::new(txid_1, 4), 1_000_000, 3),
+                (OutPoint::new(txid_2, 1), 100_000_000, 7),
+            ]
+        );
+    }
+
+    #[test]
+    fn truncated_dump_is_an_error() {
+        let script = random_p2pkh_address(Network::Regtest).script_pubkey();
+        let mut bytes = vec![];
+        BlockHash::from_slice(&[1; 32])
+            .unwrap()
+            .consensus_encode(&mut bytes)
+            .unwrap();
+        2u64.consensus_encode(&mut bytes).unwrap();
+        OutPoint::new(Txid::from_slice(&[2; 32]).unwrap(), 0)
+            .consensus_encode(&mut bytes)
+            .unwrap();
+        write_coin(&mut bytes, 10, 0x9, &script);
+
+        let mut reader = UtxoDumpReader::new(bytes.as_slice(), Network::Regtest).unwrap();
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn imported_utxos_are_queryable() {
+        let network = Network::Regtest;
+        let address = random_p2pkh_address(network);
+        let mut state = State::new(0, network, BlockBuilder::genesis().build());
+
+        for vout in 0..3 {
+            assert!(insert_utxo(
+                &mut state.utxos,
+                OutPoint::new(Txid::from_slice(&[2; 32]).unwrap(), vout),
+                TxOut {
+                    value: 1000,
+                    script_pubkey: address.script_pubkey(),
+                },
+                0,
+            ));
+        }
+        assert!(!insert_utxo(
+            &mut state.utxos,
+            OutPoint::new(Txid::from_slice(&[3; 32]).unwrap(), 0),
+            TxOut {
+                value: 0,
+                script_pubkey: Builder::new().push_opcode(OP_RETURN).into_script(),
+            },
+            0,
+        ));
+
+        assert_eq!(
+            store::get_balance(&state, &address.to_string(), 0),
+            Ok(3000)
+        );
+
+        let stats = stats(&state.utxos);
+        assert_eq!(stats.num_utxos, 3);
+        assert_eq!(stats.total_value, 3000);
+        assert_eq!(stats.num_address_entries, 3);
+        assert_eq!(
+            stats.utxos_by_script_type,
+            vec![("p2pkh", 3)].into_iter().collect()
+        );
+    }
+}
@@ -0,0 +1,336 @@
+//! Parsing of the Bitcoin addresses the minter can send BTC to.
+use bech32::{u5, FromBase32, Variant};
+use candid::{CandidType, Deserialize};
+use ic_btc_types::Network;
+use ic_crypto_sha::Sha256;
+use ripemd::{Digest, Ripemd160};
+use serde::Serialize;
+
+/// A destination of a Bitcoin transaction output.
+#[derive(CandidType, Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum BitcoinAddress {
+    /// Pay to witness public key hash, the 20-byte hash of the public key.
+    P2wpkhV0([u8; 20]),
+    /// Pay to witness script hash, the 32-byte hash of the witness script.
+    P2wshV0([u8; 32]),
+    /// Pay to taproot, the 32-byte output key.
+    P2trV1([u8; 32]),
+    /// Legacy pay to public key hash.
+    P2pkh([u8; 20]),
+    /// Legacy pay to script hash.
+    P2sh([u8; 20]),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseAddressError {
+    /// The address is well-formed but belongs to another network.
+    WrongNetwork { expected: Network },
+    /// The address type is not supported by the minter.
+    UnsupportedAddressType,
+    /// The address could not be decoded.
+    MalformedAddress(String),
+}
+
+impl std::fmt::Display for ParseAddressError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::WrongNetwork { expected } => {
+                write!(f, "address does not belong to the {} network", expected)
+            }
+            Self::UnsupportedAddressType => write!(f, "unsupported address type"),
+            Self::MalformedAddress(msg) => write!(f, "malformed address: {}", msg),
+        }
+    }
+}
+
+const OP_0: u8 = 0x00;
+const OP_1: u8 = 0x51;
+const OP_DUP: u8 = 0x76;
+const OP_EQUAL: u8 = 0x87;
+const OP_EQUALVERIFY: u8 = 0x88;
+const OP_HASH160: u8 = 0xa9;
+const OP_CHECKSIG: u8 = 0xac;
+
+impl BitcoinAddress {
+    /// Parses a bech32/bech32m (segwit) or base58check (legacy) encoded
+    /// address and checks that it belongs to `network`.
+    pub fn parse(address: &str, network: Network) -> Result<Self, ParseAddressError> {
+        // Base58 addresses never start with a segwit human-readable part.
+        let lowercase = address.to_ascii_lowercase();
+        if !["bc1", "tb1", "bcrt1"]
+            .iter()
+            .any(|prefix| lowercase.starts_with(prefix))
+        {
+            return parse_base58(address, network);
+        }
+        match bech32::decode(address) {
+            Ok((hrp, data, variant)) => parse_segwit(&hrp, &data, variant, network),
+            Err(err) => Err(ParseAddressError::MalformedAddress(err.to_string())),
+        }
+    }
+
+    /// Returns the P2WPKH address of the given compressed public key.
+    pub fn p2wpkh(public_key: &[u8]) -> Self {
+        Self::P2wpkhV0(hash160(public_key))
+    }
+
+    /// Returns the script that locks an output to this address.
+    pub fn script_pubkey(&self) -> Vec<u8> {
+        match self {
+            Self::P2wpkhV0(hash) => push_program(OP_0, hash),
+            Self::P2wshV0(hash) => push_program(OP_0, hash),
+            Self::P2trV1(key) => push_program(OP_1, key),
+            Self::P2pkh(hash) => {
+                let mut script = vec![OP_DUP, OP_HASH160, hash.len() as u8];
+                script.extend_from_slice(hash);
+                script.extend_from_slice(&[OP_EQUALVERIFY, OP_CHECKSIG]);
+                script
+            }
+            Self::P2sh(hash) => {
+                let mut script = vec![OP_HASH160, hash.len() as u8];
+                script.extend_from_slice(hash);
+                script.push(OP_EQUAL);
+                script
+            }
+        }
+    }
+}
+
+fn push_program(version: u8, program: &[u8]) -> Vec<u8> {
+    let mut script = vec![version, program.len() as u8];
+    script.extend_from_slice(program);
+    script
+}
+
+/// Computes RIPEMD160(SHA256(bytes)).
+pub fn hash160(bytes: &[u8]) -> [u8; 20] {
+    Ripemd160::digest(&Sha256::hash(bytes)).into()
+}
+
+fn hrp(network: Network) -> &'static str {
+    match network {
+        Network::Mainnet => "bc",
+        Network::Testnet => "tb",
+        Network::Regtest => "bcrt",
+    }
+}
+
+/// Parses a segwit address as described in
+/// [BIP-0173](https://github.com/bitcoin/bips/blob/master/bip-0173.mediawiki) and
+/// [BIP-0350](https://github.com/bitcoin/bips/blob/master/bip-0350.mediawiki).
+fn parse_segwit(
+    hrp_str: &str,
+    data: &[u5],
+    variant: Variant,
+    network: Network,
+) -> Result<BitcoinAddress, ParseAddressError> {
+    if hrp_str != hrp(network) {
+        return Err(ParseAddressError::WrongNetwork { expected: network });
+    }
+    let (version, program) = match data.split_first() {
+        Some((version, program)) => (version.to_u8(), program),
+        None => {
+            return Err(ParseAddressError::MalformedAddress(
+                "missing witness version".to_string(),
+            ))
+        }
+    };
+    let program = Vec::<u8>::from_base32(program)
+        .map_err(|err| ParseAddressError::MalformedAddress(err.to_string()))?;
+    match (version, variant, program.len()) {
+        (0, Variant::Bech32, 20) => Ok(BitcoinAddress::P2wpkhV0(to_array(&program))),
+        (0, Variant::Bech32, 32) => Ok(BitcoinAddress::P2wshV0(to_array(&program))),
+        (1, Variant::Bech32m, 32) => Ok(BitcoinAddress::P2trV1(to_array(&program))),
+        (0, Variant::Bech32, _) | (1, Variant::Bech32m, _) => {
+            Err(ParseAddressError::MalformedAddress(format!(
+                "invalid witness program length {}",
+                program.len()
+            )))
+        }
+        (0, Variant::Bech32m, _) | (1..=16, Variant::Bech32, _) => Err(
+            ParseAddressError::MalformedAddress("invalid checksum variant".to_string()),
+        ),
+        _ => Err(ParseAddressError::UnsupportedAddressType),
+    }
+}
+
+/// Parses a legacy base58check encoded P2PKH or P2SH address.
+fn parse_base58(address: &str, network: Network) -> Result<BitcoinAddress, ParseAddressError> {
+    let bytes = base58_decode(address).ok_or_else(|| {
+        ParseAddressError::MalformedAddress("invalid base58 character".to_string())
+    })?;
+    if bytes.len() != 25 {
+        return Err(ParseAddressError::MalformedAddress(format!(
+            "invalid length {}",
+            bytes.len()
+        )));
+    }
+    let (payload, checksum) = bytes.split_at(21);
+    if Sha256::hash(&Sha256::hash(payload))[..4] != *checksum {
+        return Err(ParseAddressError::MalformedAddress(
+            "invalid checksum".to_string(),
+        ));
+    }
+    let (p2pkh_prefix, p2sh_prefix) = match network {
+        Network::Mainnet => (0x00, 0x05),
+        Network::Testnet | Network::Regtest => (0x6f, 0xc4),
+    };
+    let hash = to_array(&payload[1..]);
+    match payload[0] {
+        prefix if prefix == p2pkh_prefix => Ok(BitcoinAddress::P2pkh(hash)),
+        prefix if prefix == p2sh_prefix => Ok(BitcoinAddress::P2sh(hash)),
+        0x00 | 0x05 | 0x6f | 0xc4 => Err(ParseAddressError::WrongNetwork { expected: network }),
+        _ => Err(ParseAddressError::UnsupportedAddressType),
+    }
+}
+
+/// Decodes a base58 string using the Bitcoin alphabet.
+fn base58_decode(s: &str) -> Option<Vec<u8>> {
+    const ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+    // Big-endian digits of the decoded number, without leading zeros.
+    let mut number: Vec<u8> = Vec::new();
+    for c in s.bytes() {
+        let mut carry = ALPHABET.iter().position(|a| *a == c)? as u32;
+        for byte in number.iter_mut().rev() {
+            carry += *byte as u32 * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            number.insert(0, carry as u8);
+            carry >>= 8;
+        }
+    }
+    // Every leading '1' encodes a leading zero byte.
+    let zeros = s.bytes().take_while(|c| *c == b'1').count();
+    let mut bytes = vec![0; zeros];
+    bytes.extend(number);
+    Some(bytes)
+}
+
+fn to_array<const N: usize>(bytes: &[u8]) -> [u8; N] {
+    let mut array = [0; N];
+    array.copy_from_slice(bytes);
+    array
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_segwit_addresses() {
+        // examples taken from https://en.bitcoin.it/wiki/BIP_0173 and BIP-0350
+        assert_eq!(
+            BitcoinAddress::parse(
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+                Network::Mainnet
+            ),
+            Ok(BitcoinAddress::P2wpkhV0(to_array(
+                &hex::decode("751e76e8199196d454941c45d1b3a323f1433bd6").unwrap()
+            )))
+        );
+        assert_eq!(
+            BitcoinAddress::parse(
+                "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4",
+                Network::Mainnet
+            ),
+            BitcoinAddress::parse(
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+                Network::Mainnet
+            ),
+        );
+        assert_eq!(
+            BitcoinAddress::parse(
+                "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7",
+                Network::Testnet
+            ),
+            Ok(BitcoinAddress::P2wshV0(to_array(
+                &hex::decode("1863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262")
+                    .unwrap()
+            )))
+        );
+        assert_eq!(
+            BitcoinAddress::parse(
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+                Network::Mainnet
+            ),
+            Ok(BitcoinAddress::P2trV1(to_array(
+                &hex::decode("79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798")
+                    .unwrap()
+            )))
+        );
+    }
+
+    #[test]
+    fn parses_legacy_addresses() {
+        assert_eq!(
+            BitcoinAddress::parse("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2", Network::Mainnet),
+            Ok(BitcoinAddress::P2pkh(to_array(
+                &hex::decode("77bff20c60e522dfaa3350c39b030a5d004e839a").unwrap()
+            )))
+        );
+        assert_eq!(
+            BitcoinAddress::parse("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy", Network::Mainnet),
+            Ok(BitcoinAddress::P2sh(to_array(
+                &hex::decode("b472a266d0bd89c13706a4132ccfb16f7c3b9fcb").unwrap()
+            )))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_addresses() {
+        assert_eq!(
+            BitcoinAddress::parse(
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+                Network::Testnet
+            ),
+            Err(ParseAddressError::WrongNetwork {
+                expected: Network::Testnet
+            })
+        );
+        assert_eq!(
+            BitcoinAddress::parse("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2", Network::Testnet),
+            Err(ParseAddressError::WrongNetwork {
+                expected: Network::Testnet
+            })
+        );
+        for address in [
+            "",
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5",
+            "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN3",
+            "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN0",
+        ] {
+            assert!(
+                matches!(
+                    BitcoinAddress::parse(address, Network::Mainnet),
+                    Err(ParseAddressError::MalformedAddress(_))
+                ),
+                "{} should be malformed",
+                address
+            );
+        }
+    }
+
+    #[test]
+    fn script_pubkeys() {
+        let hash = [0x11; 20];
+        assert_eq!(
+            hex::encode(BitcoinAddress::P2wpkhV0(hash).script_pubkey()),
+            format!("0014{}", hex::encode(hash))
+        );
+        assert_eq!(
+            hex::encode(BitcoinAddress::P2pkh(hash).script_pubkey()),
+            format!("76a914{}88ac", hex::encode(hash))
+        );
+        assert_eq!(
+            hex::encode(BitcoinAddress::P2sh(hash).script_pubkey()),
+            format!("a914{}87", hex::encode(hash))
+        );
+        assert_eq!(
+            hex::encode(BitcoinAddress::P2trV1([0x22; 32]).script_pubkey()),
+            format!("5120{}", hex::encode([0x22; 32]))
+        );
+    }
+}
@@ -5,6 +5,10 @@ use std::marker::PhantomData;
 
 const MAX_CONCURRENT: usize = 100;
 
+/// A heartbeat that holds the lock for longer than this is assumed to have
+/// trapped, in which case its guard was never dropped.
+pub const HEARTBEAT_LOCK_TIMEOUT_NANOS: u64 = 30 * 60 * 1_000_000_000;
+
 #[derive(Debug, PartialEq)]
 pub enum GuardError {
     AlreadyProcessing,
@@ -23,6 +27,14 @@ impl PendingRequests for PendingBalanceUpdates {
     }
 }
 
+pub struct PendingRetrieveBtcRequests;
+
+impl PendingRequests for PendingRetrieveBtcRequests {
+    fn pending_requests(state: &mut CkBtcMinterState) -> &mut BTreeSet<Principal> {
+        &mut state.retrieve_btc_principals
+    }
+}
+
 /// Guards a block from executing twice when called by the same user and from being
 /// executed [MAX_CONCURRENT] or more times in parallel.
 pub struct Guard<PR: PendingRequests> {
@@ -62,11 +74,55 @@ pub fn balance_update_guard(p: Principal) -> Result<Guard<PendingBalanceUpdates>
     Guard::new(p)
 }
 
+pub fn retrieve_btc_guard(p: Principal) -> Result<Guard<PendingRetrieveBtcRequests>, GuardError> {
+    Guard::new(p)
+}
+
+/// Prevents heartbeats from submitting requests concurrently. A heartbeat
+/// that awaits a call would otherwise race with the next one.
+///
+/// The lock is taken before the first await, so it stays taken if the
+/// heartbeat traps after an await and the guard is never dropped. It
+/// therefore expires after [HEARTBEAT_LOCK_TIMEOUT_NANOS].
+pub struct HeartbeatGuard {
+    acquired_at: u64,
+}
+
+impl HeartbeatGuard {
+    /// Returns [None] if another heartbeat holds the lock and it has not
+    /// expired at time `now`.
+    pub fn new(now: u64) -> Option<Self> {
+        mutate_state(|s| {
+            if let Some(acquired_at) = s.heartbeat_lock_acquired_at {
+                if now < acquired_at.saturating_add(HEARTBEAT_LOCK_TIMEOUT_NANOS) {
+                    return None;
+                }
+            }
+            s.heartbeat_lock_acquired_at = Some(now);
+            Some(Self { acquired_at: now })
+        })
+    }
+}
+
+impl Drop for HeartbeatGuard {
+    fn drop(&mut self) {
+        // The lock may have expired and been taken by another heartbeat.
+        mutate_state(|s| {
+            if s.heartbeat_lock_acquired_at == Some(self.acquired_at) {
+                s.heartbeat_lock_acquired_at = None;
+            }
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::guard::{GuardError, MAX_CONCURRENT};
+    use crate::guard::{GuardError, HeartbeatGuard, HEARTBEAT_LOCK_TIMEOUT_NANOS, MAX_CONCURRENT};
+    use crate::lifecycle::init::InitArgs;
+    use crate::state::read_state;
     use crate::state::replace_state;
     use crate::state::CkBtcMinterState;
+    use ic_base_types::CanisterId;
     use ic_btc_types::Network;
     use ic_cdk::export::Principal;
 
@@ -77,12 +133,13 @@ mod tests {
     }
 
     fn test_state() -> CkBtcMinterState {
-        CkBtcMinterState {
+        CkBtcMinterState::from(InitArgs {
             btc_network: Network::Regtest,
             ecdsa_key_name: "".to_string(),
-            ecdsa_public_key: None,
-            update_balance_principals: Default::default(),
-        }
+            min_confirmations: None,
+            ledger_id: CanisterId::from_u64(1),
+            retrieve_btc_min_amount: None,
+        })
     }
 
     #[test]
@@ -119,4 +176,40 @@ mod tests {
         let res = balance_update_guard(pid).err();
         assert_eq!(res, Some(GuardError::TooManyConcurrentRequests));
     }
+
+    #[test]
+    fn heartbeat_guard_is_exclusive() {
+        replace_state(test_state());
+        {
+            let _guard = HeartbeatGuard::new(0).unwrap();
+            assert!(HeartbeatGuard::new(1).is_none());
+        }
+        assert!(HeartbeatGuard::new(1).is_some());
+    }
+
+    #[test]
+    fn heartbeat_lock_expires_if_the_heartbeat_trapped() {
+        replace_state(test_state());
+        // A heartbeat that traps after an await never drops its guard, but
+        // the lock it took before the await is committed.
+        std::mem::forget(HeartbeatGuard::new(100).unwrap());
+        assert!(HeartbeatGuard::new(100 + HEARTBEAT_LOCK_TIMEOUT_NANOS - 1).is_none());
+
+        let guard = HeartbeatGuard::new(100 + HEARTBEAT_LOCK_TIMEOUT_NANOS).unwrap();
+        drop(guard);
+        assert_eq!(read_state(|s| s.heartbeat_lock_acquired_at), None);
+    }
+
+    #[test]
+    fn expired_heartbeat_guard_does_not_release_the_lock_of_another() {
+        replace_state(test_state());
+        let expired = HeartbeatGuard::new(0).unwrap();
+        let _guard = HeartbeatGuard::new(HEARTBEAT_LOCK_TIMEOUT_NANOS).unwrap();
+        drop(expired);
+        assert_eq!(
+            read_state(|s| s.heartbeat_lock_acquired_at),
+            Some(HEARTBEAT_LOCK_TIMEOUT_NANOS)
+        );
+        assert!(HeartbeatGuard::new(HEARTBEAT_LOCK_TIMEOUT_NANOS + 1).is_none());
+    }
 }
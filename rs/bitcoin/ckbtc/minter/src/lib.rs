@@ -1,14 +1,947 @@
+use crate::address::{hash160, BitcoinAddress};
+use crate::guard::HeartbeatGuard;
+use crate::management::{
+    get_current_fee_percentiles, get_utxos, send_transaction, sign_with_ecdsa,
+};
+use crate::runtime::{CdkRuntime, Runtime};
+use crate::signature::sec1_to_der;
+use crate::state::{
+    mutate_state, read_state, CkBtcMinterState, InFlightStatus, RetrieveBtcRequest, SigningBatch,
+    UnsentBtcTransaction, RESUBMIT_INTERVAL_NANOS,
+};
+use crate::tx::{TxOut, UnsignedInput, UnsignedTransaction, SEQUENCE_RBF_ENABLED, SIGHASH_ALL};
+use crate::updates::get_btc_address::{
+    account_to_p2wpkh_address, derivation_path, derive_public_key, init_ecdsa_public_key,
+};
+use crate::updates::get_withdrawal_account::compute_subaccount;
+use crate::updates::update_balance::mint;
 use candid::{CandidType, Deserialize};
+use ic_base_types::PrincipalId;
+use ic_btc_types::{MillisatoshiPerByte, Network, OutPoint, Utxo};
+use ic_icrc1::Account;
+use num_traits::ToPrimitive;
 use serde::Serialize;
+use std::collections::BTreeSet;
 
+pub mod address;
 pub mod guard;
 pub mod lifecycle;
+pub mod management;
 pub mod metrics;
+pub mod runtime;
+pub mod signature;
 pub mod state;
+pub mod tx;
 pub mod updates;
 
+#[cfg(test)]
+mod test_utils;
+
+/// The maximum number of requests paid out by a single transaction.
+pub const MAX_REQUESTS_PER_BATCH: usize = 100;
+
+/// The fee rate used while the Bitcoin canister has no fee percentiles, e.g.
+/// on a fresh regtest network.
+const DEFAULT_FEE_PER_VBYTE: MillisatoshiPerByte = 2_000;
+
+/// Outputs below this value are not relayed by Bitcoin nodes.
+const DUST_THRESHOLD: u64 = 546;
+
 #[derive(CandidType, Clone, Debug, Deserialize, Serialize)]
 pub struct ECDSAPublicKey {
     pub public_key: Vec<u8>,
     pub chain_code: Vec<u8>,
 }
+
+/// Submits a transaction for the pending retrieve_btc requests, sends the
+/// unconfirmed transactions again and refunds the dropped requests.
+pub async fn heartbeat() {
+    let now = ic_cdk::api::time();
+    let _guard = match HeartbeatGuard::new(now) {
+        Some(guard) => guard,
+        None => return,
+    };
+    if read_state(|s| {
+        s.pending_retrieve_btc_requests.is_empty()
+            && s.unsent_transactions.is_empty()
+            && s.rejected_retrieve_btc_requests.is_empty()
+            && s.signing_batch.is_none()
+            && !has_transactions_to_resubmit(s, now)
+    }) {
+        return;
+    }
+    init_ecdsa_public_key().await;
+    submit_pending_requests(&CdkRuntime).await;
+}
+
+/// A set of requests paid out by one transaction.
+struct Batch {
+    requests: Vec<RetrieveBtcRequest>,
+    utxos: Vec<Utxo>,
+    tx: UnsignedTransaction,
+}
+
+/// Sends the transactions whose sending failed before, refunds the dropped
+/// requests and sends the transactions that stay unconfirmed again. Then
+/// builds, signs and sends one transaction that pays out the oldest pending
+/// requests. If building or signing fails, the requests and UTXOs are
+/// returned and the next heartbeat tries again.
+pub async fn submit_pending_requests<R: Runtime>(runtime: &R) {
+    // Heartbeats only overlap once the lock of a previous heartbeat expired,
+    // so a batch left from it belongs to a signing that trapped or is too
+    // slow. A slow heartbeat checks that it still owns its batch.
+    mutate_state(|s| s.return_signing_batch());
+
+    let network = read_state(|s| s.btc_network);
+    for tx in mutate_state(|s| std::mem::take(&mut s.unsent_transactions)) {
+        send_or_keep(runtime, network, tx).await;
+    }
+    refund_rejected_requests(runtime).await;
+
+    // The change goes to the address of the minter's main account.
+    let main_account = Account {
+        owner: PrincipalId(runtime.id()),
+        subaccount: None,
+    };
+    let now = runtime.time();
+    let resubmit = read_state(|s| has_transactions_to_resubmit(s, now));
+    if resubmit || read_state(|s| !s.pending_retrieve_btc_requests.is_empty()) {
+        collect_confirmed_change(runtime, &main_account).await;
+    }
+    if resubmit {
+        resubmit_transactions(runtime, network, now).await;
+    }
+    if read_state(|s| s.pending_retrieve_btc_requests.is_empty()) {
+        return;
+    }
+
+    let fee_per_vbyte = match get_current_fee_percentiles(runtime, network).await {
+        Ok(percentiles) => median_fee(&percentiles),
+        Err((code, msg)) => {
+            runtime.print(&format!(
+                "failed to fetch fee percentiles (code {}): {}",
+                code, msg
+            ));
+            return;
+        }
+    };
+    let change_address = BitcoinAddress::p2wpkh(&derive_public_key(&main_account).public_key);
+
+    let batch = mutate_state(|s| build_batch(s, change_address, fee_per_vbyte));
+    let Batch {
+        requests,
+        utxos,
+        tx,
+    } = match batch {
+        Ok(Some(batch)) => batch,
+        Ok(None) => return,
+        Err(err) => {
+            runtime.print(&format!("failed to build a transaction: {:?}", err));
+            return;
+        }
+    };
+
+    let accounts: Result<Vec<Account>, OutPoint> = read_state(|s| {
+        utxos
+            .iter()
+            .map(|utxo| {
+                s.outpoint_account
+                    .get(&utxo.outpoint)
+                    .cloned()
+                    .ok_or_else(|| utxo.outpoint.clone())
+            })
+            .collect()
+    });
+    let accounts = match accounts {
+        Ok(accounts) => accounts,
+        Err(outpoint) => {
+            // The UTXO cannot be signed for, so it is not returned.
+            runtime.print(&format!(
+                "bug: minter UTXO {:?} without an account, it is no longer spent",
+                outpoint
+            ));
+            let utxos = utxos
+                .into_iter()
+                .filter(|utxo| utxo.outpoint != outpoint)
+                .collect();
+            mutate_state(|s| {
+                s.signing_batch = None;
+                s.return_batch(requests, utxos);
+            });
+            return;
+        }
+    };
+
+    let key_name = read_state(|s| s.ecdsa_key_name.clone());
+    let mut witnesses = Vec::with_capacity(utxos.len());
+    for (index, account) in accounts.iter().enumerate() {
+        let public_key = derive_public_key(account).public_key;
+        let sighash = tx.sighash(index, &hash160(&public_key));
+        match sign_with_ecdsa(runtime, key_name.clone(), derivation_path(account), sighash).await {
+            Ok(signature) => {
+                let mut signature = sec1_to_der(&signature);
+                signature.push(SIGHASH_ALL as u8);
+                witnesses.push((signature, public_key));
+            }
+            Err((code, msg)) => {
+                runtime.print(&format!(
+                    "failed to sign a transaction (code {}): {}",
+                    code, msg
+                ));
+                mutate_state(|s| {
+                    if s.is_signing_batch(&requests, &utxos) {
+                        s.return_signing_batch();
+                    }
+                });
+                return;
+            }
+        }
+    }
+
+    let signed = tx.sign(witnesses);
+    let txid = signed.txid();
+    let owns_batch = mutate_state(|s| {
+        // Another heartbeat returned the batch after our lock expired, so
+        // its requests may be paid out by another transaction.
+        if !s.is_signing_batch(&requests, &utxos) {
+            return false;
+        }
+        s.signing_batch = None;
+        for req in &requests {
+            s.requests_in_flight
+                .insert(req.block_index, InFlightStatus::Sending { txid });
+        }
+        true
+    });
+    if !owns_batch {
+        runtime.print("dropping a signed transaction whose batch was returned");
+        return;
+    }
+    let tx = UnsentBtcTransaction {
+        // The change output follows the outputs of the requests.
+        change_vout: requests.len() as u32,
+        requests,
+        txid,
+        used_utxos: utxos,
+        fee_per_vbyte,
+        signed_tx: signed.serialize(),
+    };
+    send_or_keep(runtime, network, tx).await;
+}
+
+/// Sends `tx` to the Bitcoin network. If sending fails, the transaction is
+/// kept and sent again by the next heartbeat: the Bitcoin canister may have
+/// accepted it anyway, so its requests and UTXOs must not be paid out by
+/// another transaction.
+async fn send_or_keep<R: Runtime>(runtime: &R, network: Network, tx: UnsentBtcTransaction) {
+    match send_transaction(runtime, network, tx.signed_tx.clone()).await {
+        Ok(()) => mutate_state(|s| s.record_submitted_transaction(tx, runtime.time())),
+        Err((code, msg)) => {
+            runtime.print(&format!(
+                "failed to send a transaction (code {}): {}",
+                code, msg
+            ));
+            mutate_state(|s| s.unsent_transactions.push(tx));
+        }
+    }
+}
+
+/// Returns true if a submitted transaction was last sent more than
+/// [RESUBMIT_INTERVAL_NANOS] before `now`.
+fn has_transactions_to_resubmit(state: &CkBtcMinterState, now: u64) -> bool {
+    state
+        .submitted_transactions
+        .iter()
+        .any(|tx| tx.last_sent_at.saturating_add(RESUBMIT_INTERVAL_NANOS) <= now)
+}
+
+/// Sends the submitted transactions that are still unconfirmed
+/// [RESUBMIT_INTERVAL_NANOS] after they were last sent again, so that they
+/// are not lost if the Bitcoin network drops them.
+async fn resubmit_transactions<R: Runtime>(runtime: &R, network: Network, now: u64) {
+    let due: Vec<([u8; 32], Vec<u8>)> = read_state(|s| {
+        s.submitted_transactions
+            .iter()
+            .filter(|tx| tx.last_sent_at.saturating_add(RESUBMIT_INTERVAL_NANOS) <= now)
+            .map(|tx| (tx.txid, tx.signed_tx.clone()))
+            .collect()
+    });
+    for (txid, signed_tx) in due {
+        match send_transaction(runtime, network, signed_tx).await {
+            Ok(()) => mutate_state(|s| {
+                if let Some(tx) = s
+                    .submitted_transactions
+                    .iter_mut()
+                    .find(|tx| tx.txid == txid)
+                {
+                    tx.last_sent_at = now;
+                }
+            }),
+            Err((code, msg)) => runtime.print(&format!(
+                "failed to send a transaction again (code {}): {}",
+                code, msg
+            )),
+        }
+    }
+}
+
+/// Mints the ckBTC of dropped requests back to the withdrawal accounts it
+/// was burned from. The requests are removed from the state before minting,
+/// so that a refund is never minted twice, and returned if minting fails.
+async fn refund_rejected_requests<R: Runtime>(runtime: &R) {
+    let requests = mutate_state(|s| std::mem::take(&mut s.rejected_retrieve_btc_requests));
+    for req in requests {
+        let withdrawal_account = Account {
+            owner: PrincipalId(runtime.id()),
+            subaccount: Some(compute_subaccount(req.caller, 0)),
+        };
+        match mint(runtime, req.amount, withdrawal_account).await {
+            Ok(mint_index) => match mint_index.0.to_u64() {
+                Some(mint_index) => mutate_state(|s| {
+                    s.refunded_retrieve_btc_requests
+                        .insert(req.block_index, mint_index)
+                }),
+                None => runtime.print(&format!(
+                    "bug: refunded request {} at block index {}, which does not fit into u64",
+                    req.block_index, mint_index
+                )),
+            },
+            Err(err) => {
+                runtime.print(&format!(
+                    "failed to refund request {}: {:?}",
+                    req.block_index, err
+                ));
+                mutate_state(|s| s.rejected_retrieve_btc_requests.push(req));
+            }
+        }
+    }
+}
+
+/// Confirms the submitted transactions whose change outputs have enough
+/// confirmations. Their change becomes spendable and is recorded with the
+/// height of the block that contains it.
+async fn collect_confirmed_change<R: Runtime>(runtime: &R, main_account: &Account) {
+    if read_state(|s| s.submitted_transactions.is_empty()) {
+        return;
+    }
+    let (network, min_confirmations) = read_state(|s| (s.btc_network, s.min_confirmations));
+    let address = account_to_p2wpkh_address(network, main_account);
+    match get_utxos(runtime, network, &address, min_confirmations).await {
+        Ok(utxos) => mutate_state(|s| {
+            for utxo in utxos {
+                s.record_confirmed_change(utxo, main_account);
+            }
+        }),
+        Err((code, msg)) => runtime.print(&format!(
+            "failed to fetch the change UTXOs (code {}): {}",
+            code, msg
+        )),
+    }
+}
+
+/// Returns the median of the fee percentiles, or [DEFAULT_FEE_PER_VBYTE] if
+/// there are none.
+fn median_fee(percentiles: &[MillisatoshiPerByte]) -> MillisatoshiPerByte {
+    percentiles
+        .get(percentiles.len() / 2)
+        .copied()
+        .unwrap_or(DEFAULT_FEE_PER_VBYTE)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BuildTxError {
+    /// The share of the fee leaves the output of a request below the dust threshold.
+    AmountTooLow { block_index: u64 },
+}
+
+/// Takes the oldest pending requests that the available UTXOs can pay for
+/// and builds the transaction that pays them out. The batch is recorded as
+/// [CkBtcMinterState::signing_batch]. Returns `Ok(None)` if no request can be
+/// paid for.
+fn build_batch(
+    state: &mut CkBtcMinterState,
+    change_address: BitcoinAddress,
+    fee_per_vbyte: MillisatoshiPerByte,
+) -> Result<Option<Batch>, BuildTxError> {
+    // Every transaction has a change output, which tells when it is confirmed.
+    let mut available: u64 = state
+        .available_utxos
+        .iter()
+        .map(|utxo| utxo.value)
+        .sum::<u64>()
+        .saturating_sub(DUST_THRESHOLD);
+    let mut count = 0;
+    for req in state
+        .pending_retrieve_btc_requests
+        .iter()
+        .take(MAX_REQUESTS_PER_BATCH)
+    {
+        if req.amount > available {
+            break;
+        }
+        available -= req.amount;
+        count += 1;
+    }
+    if count == 0 {
+        return Ok(None);
+    }
+
+    let requests: Vec<_> = state.pending_retrieve_btc_requests.drain(..count).collect();
+    let target = requests.iter().map(|req| req.amount).sum::<u64>() + DUST_THRESHOLD;
+    let utxos = greedy(target, &mut state.available_utxos);
+    match build_unsigned_transaction(&requests, &utxos, change_address, fee_per_vbyte) {
+        Ok(tx) => {
+            for req in &requests {
+                state
+                    .requests_in_flight
+                    .insert(req.block_index, InFlightStatus::Signing);
+            }
+            state.signing_batch = Some(SigningBatch {
+                requests: requests.clone(),
+                utxos: utxos.clone(),
+            });
+            Ok(Some(Batch {
+                requests,
+                utxos,
+                tx,
+            }))
+        }
+        Err(BuildTxError::AmountTooLow { block_index }) => {
+            // Returning the request would block all requests behind it, so
+            // it is dropped and refunded.
+            let (rejected, requests): (Vec<_>, Vec<_>) = requests
+                .into_iter()
+                .partition(|req| req.block_index == block_index);
+            state.rejected_retrieve_btc_requests.extend(rejected);
+            state.return_batch(requests, utxos);
+            Err(BuildTxError::AmountTooLow { block_index })
+        }
+    }
+}
+
+/// Selects UTXOs with a total value of at least `target` and removes them
+/// from `available`. At each step, it takes the smallest UTXO that covers
+/// the rest of the target or, if there is none, the largest UTXO.
+///
+/// Returns an empty vector and leaves `available` unchanged if the UTXOs do
+/// not cover `target`.
+pub fn greedy(target: u64, available: &mut BTreeSet<Utxo>) -> Vec<Utxo> {
+    if available.iter().map(|utxo| utxo.value).sum::<u64>() < target {
+        return vec![];
+    }
+    let mut selected = vec![];
+    let mut remaining = target;
+    while remaining > 0 {
+        let utxo = available
+            .iter()
+            .filter(|utxo| utxo.value >= remaining)
+            .min_by_key(|utxo| utxo.value)
+            .or_else(|| available.iter().max_by_key(|utxo| utxo.value))
+            .cloned()
+            .expect("bug: the available UTXOs do not cover the target");
+        remaining = remaining.saturating_sub(utxo.value);
+        available.remove(&utxo);
+        selected.push(utxo);
+    }
+    selected
+}
+
+/// Builds a transaction that spends `utxos` and pays out `requests`. The fee
+/// is shared equally by the requests, the remaining value goes to a change
+/// output to `change_address`, which must not be dust.
+pub fn build_unsigned_transaction(
+    requests: &[RetrieveBtcRequest],
+    utxos: &[Utxo],
+    change_address: BitcoinAddress,
+    fee_per_vbyte: MillisatoshiPerByte,
+) -> Result<UnsignedTransaction, BuildTxError> {
+    let inputs_value: u64 = utxos.iter().map(|utxo| utxo.value).sum();
+    let requested: u64 = requests.iter().map(|req| req.amount).sum();
+    assert!(inputs_value >= requested + DUST_THRESHOLD);
+
+    let mut outputs: Vec<TxOut> = requests
+        .iter()
+        .map(|req| TxOut {
+            value: req.amount,
+            address: req.address.clone(),
+        })
+        .collect();
+    outputs.push(TxOut {
+        value: inputs_value - requested,
+        address: change_address,
+    });
+    let mut tx = UnsignedTransaction {
+        inputs: utxos
+            .iter()
+            .map(|utxo| UnsignedInput {
+                previous_output: utxo.outpoint.clone(),
+                value: utxo.value,
+                sequence: SEQUENCE_RBF_ENABLED,
+            })
+            .collect(),
+        outputs,
+        lock_time: 0,
+    };
+
+    let fee = tx.fake_sign().vsize() as u64 * fee_per_vbyte / 1000;
+    let share = fee / requests.len() as u64;
+    let mut remainder = fee % requests.len() as u64;
+    for (output, req) in tx.outputs.iter_mut().zip(requests) {
+        let mut payment = share;
+        if remainder > 0 {
+            payment += 1;
+            remainder -= 1;
+        }
+        if output.value < payment + DUST_THRESHOLD {
+            return Err(BuildTxError::AmountTooLow {
+                block_index: req.block_index,
+            });
+        }
+        output.value -= payment;
+    }
+    Ok(tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::guard::HEARTBEAT_LOCK_TIMEOUT_NANOS;
+    use crate::state::{RetrieveBtcStatus, MAX_CONFIRMED_TRANSACTIONS};
+    use crate::test_utils::{init_state, FakeCanisters, MINTER_ID};
+    use candid::Nat;
+    use futures::executor::block_on;
+    use ic_icrc1::endpoints::TransferError;
+
+    fn utxo(txid: u8, value: u64) -> Utxo {
+        Utxo {
+            outpoint: OutPoint {
+                txid: vec![txid; 32],
+                vout: 0,
+            },
+            value,
+            height: 1,
+        }
+    }
+
+    fn request(block_index: u64, amount: u64) -> RetrieveBtcRequest {
+        RetrieveBtcRequest {
+            amount,
+            address: BitcoinAddress::P2wpkhV0([block_index as u8; 20]),
+            block_index,
+            caller: PrincipalId::new_user_test_id(block_index),
+        }
+    }
+
+    #[test]
+    fn greedy_prefers_the_smallest_covering_utxo() {
+        let mut available: BTreeSet<_> = vec![utxo(1, 10), utxo(2, 50), utxo(3, 100)]
+            .into_iter()
+            .collect();
+        assert_eq!(greedy(40, &mut available), vec![utxo(2, 50)]);
+        assert_eq!(available.len(), 2);
+    }
+
+    #[test]
+    fn greedy_combines_the_largest_utxos() {
+        let mut available: BTreeSet<_> = vec![utxo(1, 10), utxo(2, 50), utxo(3, 100)]
+            .into_iter()
+            .collect();
+        assert_eq!(greedy(120, &mut available), vec![utxo(3, 100), utxo(2, 50)]);
+        assert_eq!(available.into_iter().collect::<Vec<_>>(), vec![utxo(1, 10)]);
+    }
+
+    #[test]
+    fn greedy_leaves_utxos_if_insufficient() {
+        let mut available: BTreeSet<_> = vec![utxo(1, 10), utxo(2, 50)].into_iter().collect();
+        assert!(greedy(100, &mut available).is_empty());
+        assert_eq!(available.len(), 2);
+    }
+
+    #[test]
+    fn fee_is_shared_by_the_requests() {
+        let change_address = BitcoinAddress::P2wpkhV0([0xcc; 20]);
+        let requests = vec![request(0, 30_000), request(1, 40_000)];
+        let utxos = vec![utxo(1, 100_000)];
+        let tx =
+            build_unsigned_transaction(&requests, &utxos, change_address.clone(), 10_000).unwrap();
+
+        assert_eq!(tx.outputs.len(), 3);
+        assert_eq!(tx.outputs[2].address, change_address);
+        assert_eq!(tx.outputs[2].value, 30_000);
+        let fee = tx.fake_sign().vsize() as u64 * 10;
+        let paid: u64 = tx.outputs.iter().map(|output| output.value).sum();
+        assert_eq!(paid + fee, 100_000);
+        assert!(tx.outputs[0].value <= 30_000 - fee / 2);
+        assert!(tx.outputs[1].value >= 40_000 - fee / 2 - 1);
+    }
+
+    #[test]
+    fn every_transaction_has_a_change_output() {
+        let requests = vec![request(0, 30_000)];
+        let utxos = vec![utxo(1, 30_600)];
+        let tx = build_unsigned_transaction(
+            &requests,
+            &utxos,
+            BitcoinAddress::P2wpkhV0([0xcc; 20]),
+            1_000,
+        )
+        .unwrap();
+        assert_eq!(tx.outputs.len(), 2);
+        assert_eq!(tx.outputs[1].value, 600);
+    }
+
+    #[test]
+    fn rejects_requests_below_the_fee() {
+        let requests = vec![request(0, 1_000)];
+        let utxos = vec![utxo(1, 2_000)];
+        assert_eq!(
+            build_unsigned_transaction(
+                &requests,
+                &utxos,
+                BitcoinAddress::P2wpkhV0([0xcc; 20]),
+                100_000,
+            ),
+            Err(BuildTxError::AmountTooLow { block_index: 0 })
+        );
+    }
+
+    /// Adds a deposit of `owner` to the minter UTXOs.
+    fn deposit(owner: PrincipalId, txid: u8, value: u64) {
+        let deposit = utxo(txid, value);
+        mutate_state(|s| {
+            s.outpoint_account.insert(
+                deposit.outpoint.clone(),
+                Account {
+                    owner,
+                    subaccount: None,
+                },
+            );
+            s.available_utxos.insert(deposit);
+        });
+    }
+
+    #[test]
+    fn submits_pending_requests() {
+        init_state();
+        let canisters = FakeCanisters::default();
+        deposit(PrincipalId::new_user_test_id(1), 1, 60_000);
+        deposit(PrincipalId::new_user_test_id(2), 2, 70_000);
+        mutate_state(|s| {
+            s.pending_retrieve_btc_requests = vec![request(0, 50_000), request(1, 50_000)]
+        });
+
+        block_on(submit_pending_requests(&canisters));
+
+        let sent = canisters.sent_transactions.lock().unwrap().clone();
+        assert_eq!(sent.len(), 1);
+        // Each input is signed with the key of the account that owns it.
+        let signatures = canisters.signature_requests.lock().unwrap();
+        assert_eq!(signatures.len(), 2);
+        assert_ne!(signatures[0].derivation_path, signatures[1].derivation_path);
+
+        let tx = read_state(|s| s.submitted_transactions.clone());
+        assert_eq!(tx.len(), 1);
+        assert_eq!(tx[0].requests.len(), 2);
+        assert_eq!(tx[0].used_utxos.len(), 2);
+        for block_index in 0..2 {
+            assert_eq!(
+                read_state(|s| s.retrieve_btc_status(block_index)),
+                RetrieveBtcStatus::Submitted { txid: tx[0].txid }
+            );
+        }
+
+        // The change output is only spent once it has enough confirmations,
+        // which confirms the transaction.
+        let change = Utxo {
+            outpoint: OutPoint {
+                txid: tx[0].txid.to_vec(),
+                vout: 2,
+            },
+            value: 30_000,
+            height: 7,
+        };
+        read_state(|s| {
+            assert!(s.available_utxos.is_empty());
+            assert_eq!(
+                s.submitted_transactions[0].change_vout,
+                change.outpoint.vout
+            );
+        });
+        let main_account = Account {
+            owner: MINTER_ID.get(),
+            subaccount: None,
+        };
+        canisters
+            .utxos
+            .lock()
+            .unwrap()
+            .entry(account_to_p2wpkh_address(Network::Regtest, &main_account))
+            .or_default()
+            .push(change.clone());
+        mutate_state(|s| s.pending_retrieve_btc_requests = vec![request(2, 20_000)]);
+
+        block_on(submit_pending_requests(&canisters));
+
+        read_state(|s| {
+            assert_eq!(s.submitted_transactions.len(), 1);
+            assert_eq!(s.submitted_transactions[0].used_utxos, vec![change.clone()]);
+            assert_eq!(
+                s.outpoint_account.get(&change.outpoint),
+                Some(&main_account)
+            );
+            for block_index in 0..2 {
+                assert_eq!(
+                    s.retrieve_btc_status(block_index),
+                    RetrieveBtcStatus::Confirmed { txid: tx[0].txid }
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn drops_and_refunds_requests_below_the_fee() {
+        init_state();
+        let canisters = FakeCanisters::default();
+        deposit(PrincipalId::new_user_test_id(1), 1, 100_000);
+        mutate_state(|s| {
+            s.pending_retrieve_btc_requests = vec![request(0, 600), request(1, 50_000)]
+        });
+
+        block_on(submit_pending_requests(&canisters));
+
+        assert!(canisters.sent_transactions.lock().unwrap().is_empty());
+        read_state(|s| {
+            assert_eq!(s.retrieve_btc_status(0), RetrieveBtcStatus::AmountTooLow);
+            assert_eq!(s.retrieve_btc_status(1), RetrieveBtcStatus::Pending);
+            assert_eq!(s.available_utxos.len(), 1);
+        });
+
+        // The request behind it is not blocked, and the refund is retried if
+        // the ledger fails.
+        *canisters.ledger_error.lock().unwrap() = Some(TransferError::TemporarilyUnavailable);
+        block_on(submit_pending_requests(&canisters));
+
+        assert_eq!(canisters.sent_transactions.lock().unwrap().len(), 1);
+        assert!(matches!(
+            read_state(|s| s.retrieve_btc_status(1)),
+            RetrieveBtcStatus::Submitted { .. }
+        ));
+        assert_eq!(
+            read_state(|s| s.retrieve_btc_status(0)),
+            RetrieveBtcStatus::AmountTooLow
+        );
+
+        *canisters.ledger_error.lock().unwrap() = None;
+        block_on(submit_pending_requests(&canisters));
+
+        assert_eq!(
+            read_state(|s| s.retrieve_btc_status(0)),
+            RetrieveBtcStatus::Refunded { block_index: 0 }
+        );
+        let transfers = canisters.transfers.lock().unwrap();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].amount, Nat::from(600_u64));
+        assert_eq!(
+            transfers[0].to,
+            Account {
+                owner: MINTER_ID.get(),
+                subaccount: Some(compute_subaccount(PrincipalId::new_user_test_id(0), 0)),
+            }
+        );
+    }
+
+    #[test]
+    fn returns_a_batch_whose_signing_did_not_complete() {
+        init_state();
+        let canisters = FakeCanisters::default();
+        deposit(PrincipalId::new_user_test_id(1), 1, 60_000);
+        mutate_state(|s| s.pending_retrieve_btc_requests = vec![request(0, 50_000)]);
+
+        // A heartbeat that traps while signing leaves its batch and its lock
+        // in the state, since its guard is never dropped.
+        std::mem::forget(HeartbeatGuard::new(0).unwrap());
+        let batch = mutate_state(|s| build_batch(s, BitcoinAddress::P2wpkhV0([0xcc; 20]), 1_000));
+        assert!(matches!(batch, Ok(Some(_))));
+        read_state(|s| {
+            assert_eq!(s.retrieve_btc_status(0), RetrieveBtcStatus::Signing);
+            assert!(s.available_utxos.is_empty());
+        });
+
+        // The next heartbeat runs once the lock expired.
+        assert!(HeartbeatGuard::new(HEARTBEAT_LOCK_TIMEOUT_NANOS - 1).is_none());
+        let _guard = HeartbeatGuard::new(HEARTBEAT_LOCK_TIMEOUT_NANOS).unwrap();
+        block_on(submit_pending_requests(&canisters));
+
+        read_state(|s| {
+            assert!(matches!(
+                s.retrieve_btc_status(0),
+                RetrieveBtcStatus::Submitted { .. }
+            ));
+            assert_eq!(s.signing_batch, None);
+            assert_eq!(
+                s.submitted_transactions[0].used_utxos,
+                vec![utxo(1, 60_000)]
+            );
+        });
+    }
+
+    #[test]
+    fn sends_unconfirmed_transactions_again() {
+        init_state();
+        let canisters = FakeCanisters::default();
+        deposit(PrincipalId::new_user_test_id(1), 1, 60_000);
+        mutate_state(|s| s.pending_retrieve_btc_requests = vec![request(0, 50_000)]);
+
+        block_on(submit_pending_requests(&canisters));
+        assert_eq!(canisters.sent_transactions.lock().unwrap().len(), 1);
+
+        *canisters.time.lock().unwrap() = RESUBMIT_INTERVAL_NANOS - 1;
+        block_on(submit_pending_requests(&canisters));
+        assert_eq!(canisters.sent_transactions.lock().unwrap().len(), 1);
+
+        *canisters.time.lock().unwrap() = RESUBMIT_INTERVAL_NANOS;
+        block_on(submit_pending_requests(&canisters));
+        let sent = canisters.sent_transactions.lock().unwrap().clone();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0], sent[1]);
+
+        // A confirmed transaction is not sent again.
+        let tx = read_state(|s| s.submitted_transactions[0].clone());
+        let main_account = Account {
+            owner: MINTER_ID.get(),
+            subaccount: None,
+        };
+        canisters
+            .utxos
+            .lock()
+            .unwrap()
+            .entry(account_to_p2wpkh_address(Network::Regtest, &main_account))
+            .or_default()
+            .push(Utxo {
+                outpoint: OutPoint {
+                    txid: tx.txid.to_vec(),
+                    vout: tx.change_vout,
+                },
+                value: 10_000,
+                height: 7,
+            });
+        *canisters.time.lock().unwrap() = 2 * RESUBMIT_INTERVAL_NANOS;
+        block_on(submit_pending_requests(&canisters));
+
+        assert_eq!(canisters.sent_transactions.lock().unwrap().len(), 2);
+        read_state(|s| {
+            assert!(s.submitted_transactions.is_empty());
+            assert_eq!(
+                s.retrieve_btc_status(0),
+                RetrieveBtcStatus::Confirmed { txid: tx.txid }
+            );
+        });
+    }
+
+    #[test]
+    fn keeps_requests_that_cannot_be_paid_for() {
+        init_state();
+        let canisters = FakeCanisters::default();
+        deposit(PrincipalId::new_user_test_id(1), 1, 60_000);
+        mutate_state(|s| {
+            s.pending_retrieve_btc_requests = vec![request(0, 50_000), request(1, 50_000)]
+        });
+
+        block_on(submit_pending_requests(&canisters));
+
+        assert_eq!(canisters.sent_transactions.lock().unwrap().len(), 1);
+        assert_eq!(
+            read_state(|s| s.retrieve_btc_status(1)),
+            RetrieveBtcStatus::Pending
+        );
+    }
+
+    #[test]
+    fn sends_the_same_transaction_again_if_sending_fails() {
+        init_state();
+        let canisters = FakeCanisters::default();
+        *canisters.reject_transactions.lock().unwrap() = true;
+        deposit(PrincipalId::new_user_test_id(1), 1, 60_000);
+        mutate_state(|s| s.pending_retrieve_btc_requests = vec![request(0, 50_000)]);
+
+        block_on(submit_pending_requests(&canisters));
+
+        // The transaction may have been accepted anyway, so its UTXOs must not
+        // be spent by another transaction.
+        let unsent = read_state(|s| {
+            assert!(matches!(
+                s.retrieve_btc_status(0),
+                RetrieveBtcStatus::Sending { .. }
+            ));
+            assert!(s.submitted_transactions.is_empty());
+            assert!(s.available_utxos.is_empty());
+            s.unsent_transactions.clone()
+        });
+        assert_eq!(unsent.len(), 1);
+
+        *canisters.reject_transactions.lock().unwrap() = false;
+        block_on(submit_pending_requests(&canisters));
+
+        assert_eq!(
+            *canisters.sent_transactions.lock().unwrap(),
+            vec![unsent[0].signed_tx.clone()]
+        );
+        read_state(|s| {
+            assert_eq!(
+                s.retrieve_btc_status(0),
+                RetrieveBtcStatus::Submitted {
+                    txid: unsent[0].txid
+                }
+            );
+            assert!(s.unsent_transactions.is_empty());
+            assert!(s.requests_in_flight.is_empty());
+        });
+        assert_eq!(canisters.signature_requests.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn only_the_most_recent_confirmed_transactions_are_kept() {
+        init_state();
+        let main_account = Account {
+            owner: MINTER_ID.get(),
+            subaccount: None,
+        };
+        mutate_state(|s| {
+            for i in 0..=MAX_CONFIRMED_TRANSACTIONS as u64 {
+                let mut txid = [0; 32];
+                txid[..8].copy_from_slice(&i.to_le_bytes());
+                s.record_submitted_transaction(
+                    UnsentBtcTransaction {
+                        requests: vec![request(i, 50_000)],
+                        txid,
+                        used_utxos: vec![],
+                        fee_per_vbyte: 1_000,
+                        change_vout: 1,
+                        signed_tx: vec![],
+                    },
+                    0,
+                );
+                s.record_confirmed_change(
+                    Utxo {
+                        outpoint: OutPoint {
+                            txid: txid.to_vec(),
+                            vout: 1,
+                        },
+                        value: 1_000,
+                        height: 1,
+                    },
+                    &main_account,
+                );
+            }
+        });
+        read_state(|s| {
+            assert!(s.submitted_transactions.is_empty());
+            assert_eq!(s.confirmed_transactions.len(), MAX_CONFIRMED_TRANSACTIONS);
+            assert_eq!(s.retrieve_btc_status(0), RetrieveBtcStatus::Unknown);
+            assert!(matches!(
+                s.retrieve_btc_status(MAX_CONFIRMED_TRANSACTIONS as u64),
+                RetrieveBtcStatus::Confirmed { .. }
+            ));
+        });
+    }
+}
@@ -1,8 +1,15 @@
 use crate::state::{replace_state, CkBtcMinterState};
 use candid::{CandidType, Deserialize};
+use ic_base_types::CanisterId;
 use ic_btc_types::Network;
 use serde::Serialize;
 
+/// The number of confirmations required for a deposit if not specified otherwise.
+pub const DEFAULT_MIN_CONFIRMATIONS: u32 = 6;
+
+/// The minimum amount of a retrieve_btc request if not specified otherwise, in satoshi.
+pub const DEFAULT_RETRIEVE_BTC_MIN_AMOUNT: u64 = 100_000;
+
 #[derive(CandidType, Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub struct InitArgs {
     /// The bitcoin network that the minter will connect to
@@ -11,13 +18,48 @@ pub struct InitArgs {
     /// The name of the [EcdsaKeyId]. Use "dfx_test_key" for local replica and "test_key_1" for
     /// a testing key for testnet and mainnet
     pub ecdsa_key_name: String,
+
+    /// The minimum number of confirmations a deposit needs before ckBTC is
+    /// minted for it. Defaults to [DEFAULT_MIN_CONFIRMATIONS].
+    pub min_confirmations: Option<u32>,
+
+    /// The canister id of the ckBTC ledger
+    pub ledger_id: CanisterId,
+
+    /// The minimum amount of a retrieve_btc request, in satoshi. It must
+    /// cover the transaction fee. Defaults to [DEFAULT_RETRIEVE_BTC_MIN_AMOUNT].
+    pub retrieve_btc_min_amount: Option<u64>,
+}
+
+impl From<InitArgs> for CkBtcMinterState {
+    fn from(args: InitArgs) -> Self {
+        Self {
+            btc_network: args.btc_network,
+            ecdsa_key_name: args.ecdsa_key_name,
+            ecdsa_public_key: None,
+            min_confirmations: args.min_confirmations.unwrap_or(DEFAULT_MIN_CONFIRMATIONS),
+            ledger_id: args.ledger_id,
+            processed_outpoints: Default::default(),
+            retrieve_btc_min_amount: args
+                .retrieve_btc_min_amount
+                .unwrap_or(DEFAULT_RETRIEVE_BTC_MIN_AMOUNT),
+            available_utxos: Default::default(),
+            outpoint_account: Default::default(),
+            pending_retrieve_btc_requests: Default::default(),
+            requests_in_flight: Default::default(),
+            signing_batch: None,
+            unsent_transactions: Default::default(),
+            submitted_transactions: Default::default(),
+            confirmed_transactions: Default::default(),
+            rejected_retrieve_btc_requests: Default::default(),
+            refunded_retrieve_btc_requests: Default::default(),
+            update_balance_principals: Default::default(),
+            retrieve_btc_principals: Default::default(),
+            heartbeat_lock_acquired_at: None,
+        }
+    }
 }
 
 pub fn init(args: InitArgs) {
-    replace_state(CkBtcMinterState {
-        btc_network: args.btc_network,
-        ecdsa_key_name: args.ecdsa_key_name,
-        ecdsa_public_key: None,
-        update_balance_principals: Default::default(),
-    });
+    replace_state(CkBtcMinterState::from(args));
 }
@@ -1,15 +1,133 @@
+use crate::state::{replace_state, take_state, CkBtcMinterState};
 use candid::{CandidType, Deserialize};
 use serde::Serialize;
 
-#[derive(CandidType, Clone, Debug, Deserialize, Serialize, PartialEq)]
-pub struct UpgradeArgs {}
+#[derive(CandidType, Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct UpgradeArgs {
+    /// Overrides the minimum number of confirmations of a deposit if set.
+    pub min_confirmations: Option<u32>,
+
+    /// Overrides the minimum amount of a retrieve_btc request if set.
+    pub retrieve_btc_min_amount: Option<u64>,
+}
 
 pub fn pre_upgrade() {
     ic_cdk::println!("Executing pre upgrade");
-    todo!()
+    take_state(|state| {
+        ic_cdk::storage::stable_save((state,)).expect("failed to save the minter state")
+    });
 }
 
-pub fn post_upgrade(_args: UpgradeArgs) {
+pub fn post_upgrade(args: UpgradeArgs) {
     ic_cdk::println!("Executing post upgrade");
-    todo!()
+    let (state,): (CkBtcMinterState,) =
+        ic_cdk::storage::stable_restore().expect("failed to restore the minter state");
+    replace_state(upgrade_state(state, args));
+}
+
+/// Applies `args` to the restored state and releases the locks of calls
+/// that did not complete before the upgrade. A batch whose signing did not
+/// complete is returned.
+fn upgrade_state(mut state: CkBtcMinterState, args: UpgradeArgs) -> CkBtcMinterState {
+    if let Some(min_confirmations) = args.min_confirmations {
+        state.min_confirmations = min_confirmations;
+    }
+    if let Some(min_amount) = args.retrieve_btc_min_amount {
+        state.retrieve_btc_min_amount = min_amount;
+    }
+    state.update_balance_principals.clear();
+    state.retrieve_btc_principals.clear();
+    state.heartbeat_lock_acquired_at = None;
+    state.return_signing_batch();
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::BitcoinAddress;
+    use crate::lifecycle::init::InitArgs;
+    use crate::state::{InFlightStatus, RetrieveBtcRequest, RetrieveBtcStatus, SigningBatch};
+    use candid::{Decode, Encode};
+    use ic_base_types::{CanisterId, PrincipalId};
+    use ic_btc_types::{Network, OutPoint, Utxo};
+    use ic_icrc1::Account;
+
+    #[test]
+    fn state_survives_upgrade() {
+        let mut state = CkBtcMinterState::from(InitArgs {
+            btc_network: Network::Testnet,
+            ecdsa_key_name: "key".to_string(),
+            min_confirmations: None,
+            ledger_id: CanisterId::from_u64(1),
+            retrieve_btc_min_amount: None,
+        });
+        let outpoint = OutPoint {
+            txid: vec![1; 32],
+            vout: 2,
+        };
+        state.available_utxos.insert(Utxo {
+            outpoint: outpoint.clone(),
+            value: 100,
+            height: 3,
+        });
+        state.outpoint_account.insert(
+            outpoint.clone(),
+            Account {
+                owner: PrincipalId::new_user_test_id(1),
+                subaccount: Some([4; 32]),
+            },
+        );
+        state.processed_outpoints.insert(outpoint);
+        state
+            .pending_retrieve_btc_requests
+            .push(RetrieveBtcRequest {
+                amount: 50_000,
+                address: BitcoinAddress::P2wshV0([5; 32]),
+                block_index: 6,
+                caller: PrincipalId::new_user_test_id(2),
+            });
+        state
+            .requests_in_flight
+            .insert(7, InFlightStatus::Sending { txid: [8; 32] });
+        // A heartbeat trapped while signing this batch.
+        state.requests_in_flight.insert(9, InFlightStatus::Signing);
+        state.signing_batch = Some(SigningBatch {
+            requests: vec![RetrieveBtcRequest {
+                amount: 20_000,
+                address: BitcoinAddress::P2wpkhV0([10; 20]),
+                block_index: 9,
+                caller: PrincipalId::new_user_test_id(3),
+            }],
+            utxos: vec![],
+        });
+        state.heartbeat_lock_acquired_at = Some(11);
+
+        // The state is stored in stable memory as Candid.
+        let bytes = Encode!(&state).unwrap();
+        let restored = upgrade_state(
+            Decode!(&bytes, CkBtcMinterState).unwrap(),
+            UpgradeArgs {
+                min_confirmations: Some(2),
+                retrieve_btc_min_amount: None,
+            },
+        );
+
+        assert_eq!(restored.min_confirmations, 2);
+        assert_eq!(
+            restored.retrieve_btc_min_amount,
+            state.retrieve_btc_min_amount
+        );
+        assert_eq!(restored.available_utxos, state.available_utxos);
+        assert_eq!(restored.outpoint_account, state.outpoint_account);
+        assert_eq!(restored.processed_outpoints, state.processed_outpoints);
+        assert_eq!(restored.retrieve_btc_status(6), RetrieveBtcStatus::Pending);
+        assert_eq!(
+            restored.retrieve_btc_status(7),
+            RetrieveBtcStatus::Sending { txid: [8; 32] }
+        );
+        assert_eq!(restored.retrieve_btc_status(9), RetrieveBtcStatus::Pending);
+        assert_eq!(restored.signing_batch, None);
+        assert_eq!(restored.heartbeat_lock_acquired_at, None);
+    }
 }
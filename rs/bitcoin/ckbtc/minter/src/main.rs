@@ -1,11 +1,14 @@
 use candid::candid_method;
-use ic_cdk_macros::{init, post_upgrade, pre_upgrade, update};
+use ic_cdk_macros::{heartbeat, init, post_upgrade, pre_upgrade, query, update};
 use ic_ckbtc_minter::lifecycle::{self, init::InitArgs, upgrade::UpgradeArgs};
 use ic_ckbtc_minter::metrics::encode_metrics;
+use ic_ckbtc_minter::state::RetrieveBtcStatus;
 use ic_ckbtc_minter::updates::{
     self,
     get_btc_address::{GetBtcAddressArgs, GetBtcAddressResult},
     get_withdrawal_account::GetWithdrawalAccountResult,
+    retrieve_btc::{RetrieveBtcArgs, RetrieveBtcError, RetrieveBtcOk, RetrieveBtcStatusRequest},
+    update_balance::{UpdateBalanceArgs, UpdateBalanceError, UpdateBalanceResult},
 };
 
 #[init]
@@ -23,6 +26,11 @@ fn post_upgrade(args: UpgradeArgs) {
     lifecycle::upgrade::post_upgrade(args)
 }
 
+#[heartbeat]
+fn heartbeat() {
+    ic_cdk::spawn(ic_ckbtc_minter::heartbeat())
+}
+
 #[candid_method(update)]
 #[update]
 async fn get_btc_address(args: GetBtcAddressArgs) -> GetBtcAddressResult {
@@ -35,6 +43,26 @@ async fn get_withdrawal_account() -> GetWithdrawalAccountResult {
     updates::get_withdrawal_account::get_withdrawal_account().await
 }
 
+#[candid_method(update)]
+#[update]
+async fn update_balance(
+    args: UpdateBalanceArgs,
+) -> Result<UpdateBalanceResult, UpdateBalanceError> {
+    updates::update_balance::update_balance(args).await
+}
+
+#[candid_method(update)]
+#[update]
+async fn retrieve_btc(args: RetrieveBtcArgs) -> Result<RetrieveBtcOk, RetrieveBtcError> {
+    updates::retrieve_btc::retrieve_btc(args).await
+}
+
+#[candid_method(query)]
+#[query]
+fn retrieve_btc_status(args: RetrieveBtcStatusRequest) -> RetrieveBtcStatus {
+    updates::retrieve_btc::retrieve_btc_status(args)
+}
+
 #[export_name = "canister_query http_request"]
 fn http_request() {
     dfn_http_metrics::serve_metrics(encode_metrics);
@@ -0,0 +1,118 @@
+//! Calls to the management canister.
+use crate::runtime::Runtime;
+use candid::Principal;
+use ic_btc_types::{
+    GetCurrentFeePercentilesRequest, GetUtxosRequest, GetUtxosResponse, MillisatoshiPerByte,
+    Network, SendTransactionRequest, Utxo, UtxosFilter,
+};
+use ic_ic00_types::{EcdsaCurve, EcdsaKeyId, SignWithECDSAArgs, SignWithECDSAReply};
+
+/// Cycles that have to be attached to a `bitcoin_get_utxos` call.
+const GET_UTXOS_COST_CYCLES: u64 = 100_000_000;
+
+/// Cycles that have to be attached to a `bitcoin_get_current_fee_percentiles` call.
+const GET_CURRENT_FEE_PERCENTILES_COST_CYCLES: u64 = 10_000_000;
+
+/// Cycles that have to be attached to a `bitcoin_send_transaction` call, in
+/// addition to [SEND_TRANSACTION_COST_CYCLES_PER_BYTE] per transaction byte.
+const SEND_TRANSACTION_BASE_COST_CYCLES: u64 = 5_000_000_000;
+const SEND_TRANSACTION_COST_CYCLES_PER_BYTE: u64 = 20_000_000;
+
+/// Cycles that have to be attached to a `sign_with_ecdsa` call.
+const SIGN_WITH_ECDSA_COST_CYCLES: u64 = 10_000_000_000;
+
+/// Fetches all UTXOs of `address` that have at least `min_confirmations`
+/// confirmations, following the pagination of the Bitcoin API.
+pub async fn get_utxos<R: Runtime>(
+    runtime: &R,
+    network: Network,
+    address: &str,
+    min_confirmations: u32,
+) -> Result<Vec<Utxo>, (i32, String)> {
+    let mut utxos = Vec::new();
+    let mut filter = Some(UtxosFilter::MinConfirmations(min_confirmations));
+    loop {
+        let (response,): (GetUtxosResponse,) = runtime
+            .call(
+                Principal::management_canister(),
+                "bitcoin_get_utxos",
+                GET_UTXOS_COST_CYCLES,
+                (GetUtxosRequest {
+                    address: address.to_string(),
+                    network,
+                    filter,
+                },),
+            )
+            .await?;
+        utxos.extend(response.utxos);
+        match response.next_page {
+            Some(page) => filter = Some(UtxosFilter::Page(page)),
+            None => return Ok(utxos),
+        }
+    }
+}
+
+/// Fetches the fee percentiles of the recent transactions, in millisatoshi
+/// per virtual byte. The list is empty if the Bitcoin canister has not seen
+/// enough transactions yet.
+pub async fn get_current_fee_percentiles<R: Runtime>(
+    runtime: &R,
+    network: Network,
+) -> Result<Vec<MillisatoshiPerByte>, (i32, String)> {
+    let (percentiles,): (Vec<MillisatoshiPerByte>,) = runtime
+        .call(
+            Principal::management_canister(),
+            "bitcoin_get_current_fee_percentiles",
+            GET_CURRENT_FEE_PERCENTILES_COST_CYCLES,
+            (GetCurrentFeePercentilesRequest { network },),
+        )
+        .await?;
+    Ok(percentiles)
+}
+
+/// Signs `message_hash` with the threshold ECDSA key `key_name` derived at
+/// `derivation_path` and returns the 64-byte `r || s` signature.
+pub async fn sign_with_ecdsa<R: Runtime>(
+    runtime: &R,
+    key_name: String,
+    derivation_path: Vec<Vec<u8>>,
+    message_hash: [u8; 32],
+) -> Result<Vec<u8>, (i32, String)> {
+    let (reply,): (SignWithECDSAReply,) = runtime
+        .call(
+            Principal::management_canister(),
+            "sign_with_ecdsa",
+            SIGN_WITH_ECDSA_COST_CYCLES,
+            (SignWithECDSAArgs {
+                message_hash: message_hash.to_vec(),
+                derivation_path,
+                key_id: EcdsaKeyId {
+                    curve: EcdsaCurve::Secp256k1,
+                    name: key_name,
+                },
+            },),
+        )
+        .await?;
+    Ok(reply.signature)
+}
+
+/// Submits a serialized transaction to the Bitcoin network.
+pub async fn send_transaction<R: Runtime>(
+    runtime: &R,
+    network: Network,
+    transaction: Vec<u8>,
+) -> Result<(), (i32, String)> {
+    let cycles = SEND_TRANSACTION_BASE_COST_CYCLES
+        + SEND_TRANSACTION_COST_CYCLES_PER_BYTE * transaction.len() as u64;
+    runtime
+        .call(
+            Principal::management_canister(),
+            "bitcoin_send_transaction",
+            cycles,
+            (SendTransactionRequest {
+                transaction,
+                network,
+            },),
+        )
+        .await
+}
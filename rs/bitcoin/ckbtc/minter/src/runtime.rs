@@ -0,0 +1,66 @@
+//! Abstraction over inter-canister calls.
+//!
+//! The minter logic is generic over [Runtime] so that it can be exercised
+//! against fake Bitcoin and ledger canisters in unit tests.
+use async_trait::async_trait;
+use candid::utils::{ArgumentDecoder, ArgumentEncoder};
+use candid::Principal;
+
+#[async_trait]
+pub trait Runtime {
+    /// Returns the principal of the minter canister.
+    fn id(&self) -> Principal;
+
+    /// Prints a message to the canister log.
+    fn print(&self, msg: &str);
+
+    /// Returns the current time in nanoseconds since the epoch.
+    fn time(&self) -> u64;
+
+    /// Calls `method` on canister `id` with `args`, attaching `cycles`.
+    async fn call<In, Out>(
+        &self,
+        id: Principal,
+        method: &str,
+        cycles: u64,
+        args: In,
+    ) -> Result<Out, (i32, String)>
+    where
+        In: ArgumentEncoder + Send,
+        Out: for<'a> ArgumentDecoder<'a>;
+}
+
+/// [Runtime] that performs the calls using the CDK.
+#[derive(Debug)]
+pub struct CdkRuntime;
+
+#[async_trait]
+impl Runtime for CdkRuntime {
+    fn id(&self) -> Principal {
+        ic_cdk::id()
+    }
+
+    fn print(&self, msg: &str) {
+        ic_cdk::api::print(msg)
+    }
+
+    fn time(&self) -> u64 {
+        ic_cdk::api::time()
+    }
+
+    async fn call<In, Out>(
+        &self,
+        id: Principal,
+        method: &str,
+        cycles: u64,
+        args: In,
+    ) -> Result<Out, (i32, String)>
+    where
+        In: ArgumentEncoder + Send,
+        Out: for<'a> ArgumentDecoder<'a>,
+    {
+        ic_cdk::api::call::call_with_payment(id, method, args, cycles)
+            .await
+            .map_err(|(code, msg)| (code as i32, msg))
+    }
+}
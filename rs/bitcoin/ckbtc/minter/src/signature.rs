@@ -0,0 +1,134 @@
+//! Encoding of threshold ECDSA signatures for Bitcoin transactions.
+
+/// The order of the secp256k1 group, big-endian.
+const SECP256K1_ORDER: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+    0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36, 0x41, 0x41,
+];
+
+/// Half of [SECP256K1_ORDER], rounded down, big-endian.
+const SECP256K1_HALF_ORDER: [u8; 32] = [
+    0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0x5d, 0x57, 0x6e, 0x73, 0x57, 0xa4, 0x50, 0x1d, 0xdf, 0xe9, 0x2f, 0x46, 0x68, 0x1b, 0x20, 0xa0,
+];
+
+/// Converts a signature in the 64-byte `r || s` format returned by
+/// `sign_with_ecdsa` into the DER format Bitcoin expects.
+///
+/// Bitcoin only relays signatures with a low `s` value (BIP-0146), so a high
+/// `s` is replaced by the equivalent `order - s`.
+///
+/// Panics if `signature` is not 64 bytes long.
+pub fn sec1_to_der(signature: &[u8]) -> Vec<u8> {
+    assert_eq!(signature.len(), 64, "expected a 64-byte signature");
+    let r = &signature[..32];
+    let mut s = [0u8; 32];
+    s.copy_from_slice(&signature[32..]);
+    if s > SECP256K1_HALF_ORDER {
+        s = sub(&SECP256K1_ORDER, &s);
+    }
+
+    let r = der_integer(r);
+    let s = der_integer(&s);
+    let mut der = Vec::with_capacity(6 + r.len() + s.len());
+    der.push(0x30);
+    der.push((4 + r.len() + s.len()) as u8);
+    der.push(0x02);
+    der.push(r.len() as u8);
+    der.extend_from_slice(&r);
+    der.push(0x02);
+    der.push(s.len() as u8);
+    der.extend_from_slice(&s);
+    der
+}
+
+/// Encodes a big-endian unsigned integer as the content of a DER INTEGER:
+/// minimal length and a leading zero byte if the high bit is set.
+fn der_integer(bytes: &[u8]) -> Vec<u8> {
+    let first_nonzero = bytes
+        .iter()
+        .position(|b| *b != 0)
+        .unwrap_or(bytes.len() - 1);
+    let bytes = &bytes[first_nonzero..];
+    let mut integer = Vec::with_capacity(bytes.len() + 1);
+    if bytes[0] & 0x80 != 0 {
+        integer.push(0);
+    }
+    integer.extend_from_slice(bytes);
+    integer
+}
+
+/// Computes `a - b` for big-endian numbers with `a >= b`.
+fn sub(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let mut result = [0u8; 32];
+    let mut borrow = 0i16;
+    for i in (0..32).rev() {
+        let mut diff = a[i] as i16 - b[i] as i16 - borrow;
+        borrow = 0;
+        if diff < 0 {
+            diff += 256;
+            borrow = 1;
+        }
+        result[i] = diff as u8;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_low_s_signature() {
+        let mut signature = [0u8; 64];
+        signature[31] = 0x01;
+        signature[32] = 0x70;
+        signature[63] = 0x02;
+        let mut expected = vec![0x30, 0x25, 0x02, 0x01, 0x01, 0x02, 0x20];
+        expected.extend_from_slice(&signature[32..]);
+        assert_eq!(sec1_to_der(&signature), expected);
+    }
+
+    #[test]
+    fn pads_high_bit_integers() {
+        let mut signature = [0u8; 64];
+        signature[0] = 0x80;
+        signature[63] = 0x01;
+        let der = sec1_to_der(&signature);
+        assert_eq!(&der[..5], &[0x30, 0x26, 0x02, 0x21, 0x00]);
+        assert_eq!(&der[37..], &[0x02, 0x01, 0x01]);
+    }
+
+    #[test]
+    fn normalizes_high_s() {
+        let mut signature = [0u8; 64];
+        signature[31] = 0x01;
+        // s = order - 1 is normalized to 1.
+        signature[32..].copy_from_slice(&sub(&SECP256K1_ORDER, &{
+            let mut one = [0u8; 32];
+            one[31] = 1;
+            one
+        }));
+        assert_eq!(
+            sec1_to_der(&signature),
+            vec![0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x01]
+        );
+    }
+
+    #[test]
+    fn half_order_is_half_of_order() {
+        let twice_half = {
+            let mut result = [0u8; 32];
+            let mut carry = 0u16;
+            for i in (0..32).rev() {
+                let sum = 2 * SECP256K1_HALF_ORDER[i] as u16 + carry;
+                result[i] = sum as u8;
+                carry = sum >> 8;
+            }
+            result
+        };
+        let mut one = [0u8; 32];
+        one[31] = 1;
+        assert_eq!(sub(&SECP256K1_ORDER, &twice_half), one);
+    }
+}
@@ -3,21 +3,140 @@
 ///! The state is stored in the global thread-level variable `__STATE`.
 ///! This module provides utility functions to manage the state. Most
 ///! code should use those functions instead of touching `__STATE` directly.
-use std::{cell::RefCell, collections::BTreeSet};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+};
 
-use candid::Principal;
-use ic_btc_types::Network;
+use candid::{CandidType, Deserialize, Principal};
+use ic_base_types::{CanisterId, PrincipalId};
+use ic_btc_types::{Network, OutPoint, Utxo};
+use ic_icrc1::Account;
+use serde::Serialize;
 
-use crate::ECDSAPublicKey;
+use crate::{address::BitcoinAddress, ECDSAPublicKey};
+
+/// The maximum number of confirmed transactions the minter remembers. The
+/// requests of older transactions have status [RetrieveBtcStatus::Unknown].
+pub const MAX_CONFIRMED_TRANSACTIONS: usize = 1000;
+
+/// How long a submitted transaction may stay unconfirmed before it is sent
+/// again, in nanoseconds.
+pub const RESUBMIT_INTERVAL_NANOS: u64 = 10 * 60 * 1_000_000_000;
 
 thread_local! {
     static __STATE: RefCell<Option<CkBtcMinterState>> = RefCell::default();
 }
 
+/// A request to send BTC that has been paid for by burning ckBTC.
+#[derive(CandidType, Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct RetrieveBtcRequest {
+    /// The amount of burned ckBTC, in satoshi. The transaction fee is
+    /// deducted from it.
+    pub amount: u64,
+    /// The destination of the BTC.
+    pub address: BitcoinAddress,
+    /// The index of the burn transaction on the ckBTC ledger, identifies the request.
+    pub block_index: u64,
+    /// The principal whose withdrawal account the ckBTC was burned from. It
+    /// is refunded if the request is dropped.
+    pub caller: PrincipalId,
+}
+
+/// The status of a batch of requests whose transaction is being submitted.
+#[derive(CandidType, Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum InFlightStatus {
+    /// The inputs of the transaction are being signed.
+    Signing,
+    /// The transaction is being sent to the Bitcoin network.
+    Sending { txid: [u8; 32] },
+}
+
+/// A batch of requests whose transaction is being signed, together with the
+/// UTXOs it spends. It is stored before signing starts so that the batch is
+/// returned if the signing does not complete.
+#[derive(CandidType, Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct SigningBatch {
+    /// The requests paid out by the transaction.
+    pub requests: Vec<RetrieveBtcRequest>,
+    /// The minter UTXOs spent by the transaction.
+    pub utxos: Vec<Utxo>,
+}
+
+/// A transaction that has been accepted by the Bitcoin canister but whose
+/// change output does not have enough confirmations yet.
+#[derive(CandidType, Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct SubmittedBtcTransaction {
+    /// The requests paid out by the transaction.
+    pub requests: Vec<RetrieveBtcRequest>,
+    /// The transaction id in internal byte order.
+    pub txid: [u8; 32],
+    /// The minter UTXOs spent by the transaction.
+    pub used_utxos: Vec<Utxo>,
+    /// The fee rate of the transaction, in millisatoshi per vbyte.
+    pub fee_per_vbyte: u64,
+    /// The index of the change output.
+    pub change_vout: u32,
+    /// The serialized signed transaction, sent again until it is confirmed.
+    pub signed_tx: Vec<u8>,
+    /// The time the transaction was last sent, in nanoseconds since the epoch.
+    pub last_sent_at: u64,
+}
+
+/// A transaction whose change output has enough confirmations.
+#[derive(CandidType, Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ConfirmedBtcTransaction {
+    /// The requests paid out by the transaction.
+    pub requests: Vec<RetrieveBtcRequest>,
+    /// The transaction id in internal byte order.
+    pub txid: [u8; 32],
+}
+
+/// A signed transaction that could not be sent yet. The Bitcoin canister may
+/// have accepted it anyway, so it is sent again instead of being rebuilt.
+#[derive(CandidType, Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct UnsentBtcTransaction {
+    /// The requests paid out by the transaction.
+    pub requests: Vec<RetrieveBtcRequest>,
+    /// The transaction id in internal byte order.
+    pub txid: [u8; 32],
+    /// The minter UTXOs spent by the transaction.
+    pub used_utxos: Vec<Utxo>,
+    /// The fee rate of the transaction, in millisatoshi per vbyte.
+    pub fee_per_vbyte: u64,
+    /// The index of the change output.
+    pub change_vout: u32,
+    /// The serialized signed transaction.
+    pub signed_tx: Vec<u8>,
+}
+
+/// The status of a [RetrieveBtcRequest], as returned by `retrieve_btc_status`.
+#[derive(CandidType, Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum RetrieveBtcStatus {
+    /// The minter has no request with the given block index.
+    Unknown,
+    /// The request waits to be included in a transaction.
+    Pending,
+    /// The transaction that pays out the request is being signed.
+    Signing,
+    /// The transaction that pays out the request is being sent.
+    Sending { txid: [u8; 32] },
+    /// The transaction that pays out the request has been submitted.
+    Submitted { txid: [u8; 32] },
+    /// The transaction that pays out the request has enough confirmations.
+    Confirmed { txid: [u8; 32] },
+    /// The amount of the request does not cover its share of the transaction
+    /// fee. The request was dropped and the burned ckBTC is being refunded.
+    AmountTooLow,
+    /// The request was dropped and the burned ckBTC was minted back to the
+    /// withdrawal account at `block_index`.
+    Refunded { block_index: u64 },
+}
+
 /// The state of the ckBTC Minter.
 ///
 /// Every piece of state of the Minter should be stored as field of this struct.
-#[derive(Clone, Debug)]
+#[derive(CandidType, Clone, Debug, Deserialize)]
 pub struct CkBtcMinterState {
     /// The bitcoin network that the minter will connect to
     pub btc_network: Network,
@@ -29,8 +148,183 @@ pub struct CkBtcMinterState {
     /// The Minter ECDSA public key
     pub ecdsa_public_key: Option<ECDSAPublicKey>,
 
+    /// The minimum number of confirmations a deposit needs before ckBTC is minted for it
+    pub min_confirmations: u32,
+
+    /// The ckBTC ledger, the minter is its minting account
+    pub ledger_id: CanisterId,
+
+    /// Outpoints of the deposited UTXOs that ckBTC has already been minted for
+    pub processed_outpoints: BTreeSet<OutPoint>,
+
+    /// The minimum amount of a retrieve_btc request, in satoshi
+    pub retrieve_btc_min_amount: u64,
+
+    /// The UTXOs the minter can spend to pay out retrieve_btc requests
+    pub available_utxos: BTreeSet<Utxo>,
+
+    /// The account whose derived key controls a minter UTXO
+    pub outpoint_account: BTreeMap<OutPoint, Account>,
+
+    /// Requests waiting to be included in a transaction, oldest first
+    pub pending_retrieve_btc_requests: Vec<RetrieveBtcRequest>,
+
+    /// Requests whose transaction is being signed or sent, by block index
+    pub requests_in_flight: BTreeMap<u64, InFlightStatus>,
+
+    /// The batch whose transaction is being signed, if any
+    pub signing_batch: Option<SigningBatch>,
+
+    /// Signed transactions whose sending failed, they are sent again
+    pub unsent_transactions: Vec<UnsentBtcTransaction>,
+
+    /// Transactions accepted by the Bitcoin canister that are not confirmed
+    /// yet, they are sent again until they are
+    pub submitted_transactions: Vec<SubmittedBtcTransaction>,
+
+    /// Confirmed transactions, at most [MAX_CONFIRMED_TRANSACTIONS] of the
+    /// most recent ones
+    pub confirmed_transactions: Vec<ConfirmedBtcTransaction>,
+
+    /// Requests that were dropped because their amount did not cover their
+    /// share of the fee, they wait for their refund
+    pub rejected_retrieve_btc_requests: Vec<RetrieveBtcRequest>,
+
+    /// The mint block index of the refund of dropped requests, by the block
+    /// index of their burn
+    pub refunded_retrieve_btc_requests: BTreeMap<u64, u64>,
+
     /// Per-principal lock for update_balance
     pub update_balance_principals: BTreeSet<Principal>,
+
+    /// Per-principal lock for retrieve_btc
+    pub retrieve_btc_principals: BTreeSet<Principal>,
+
+    /// The time at which a heartbeat took the lock for submitting requests,
+    /// in nanoseconds since the epoch, if it holds the lock
+    pub heartbeat_lock_acquired_at: Option<u64>,
+}
+
+impl CkBtcMinterState {
+    /// Returns the status of the retrieve_btc request identified by the
+    /// index of its burn transaction.
+    pub fn retrieve_btc_status(&self, block_index: u64) -> RetrieveBtcStatus {
+        if self
+            .pending_retrieve_btc_requests
+            .iter()
+            .any(|req| req.block_index == block_index)
+        {
+            return RetrieveBtcStatus::Pending;
+        }
+        match self.requests_in_flight.get(&block_index) {
+            Some(InFlightStatus::Signing) => return RetrieveBtcStatus::Signing,
+            Some(InFlightStatus::Sending { txid }) => {
+                return RetrieveBtcStatus::Sending { txid: *txid }
+            }
+            None => {}
+        }
+        if self
+            .rejected_retrieve_btc_requests
+            .iter()
+            .any(|req| req.block_index == block_index)
+        {
+            return RetrieveBtcStatus::AmountTooLow;
+        }
+        if let Some(mint_index) = self.refunded_retrieve_btc_requests.get(&block_index) {
+            return RetrieveBtcStatus::Refunded {
+                block_index: *mint_index,
+            };
+        }
+        let pays_out = |requests: &[RetrieveBtcRequest]| {
+            requests.iter().any(|req| req.block_index == block_index)
+        };
+        if let Some(tx) = self
+            .submitted_transactions
+            .iter()
+            .find(|tx| pays_out(&tx.requests))
+        {
+            return RetrieveBtcStatus::Submitted { txid: tx.txid };
+        }
+        match self
+            .confirmed_transactions
+            .iter()
+            .find(|tx| pays_out(&tx.requests))
+        {
+            Some(tx) => RetrieveBtcStatus::Confirmed { txid: tx.txid },
+            None => RetrieveBtcStatus::Unknown,
+        }
+    }
+
+    /// Records that the Bitcoin canister accepted `tx` at time `now`. It is
+    /// tracked until its change output has enough confirmations.
+    pub fn record_submitted_transaction(&mut self, tx: UnsentBtcTransaction, now: u64) {
+        for req in &tx.requests {
+            self.requests_in_flight.remove(&req.block_index);
+        }
+        self.submitted_transactions.push(SubmittedBtcTransaction {
+            requests: tx.requests,
+            txid: tx.txid,
+            used_utxos: tx.used_utxos,
+            fee_per_vbyte: tx.fee_per_vbyte,
+            change_vout: tx.change_vout,
+            signed_tx: tx.signed_tx,
+            last_sent_at: now,
+        });
+    }
+
+    /// Records that `utxo` has enough confirmations if it is the change
+    /// output of a submitted transaction: the transaction is confirmed and
+    /// the change becomes spendable with the key of `change_account`.
+    pub fn record_confirmed_change(&mut self, utxo: Utxo, change_account: &Account) {
+        let position = self
+            .submitted_transactions
+            .iter()
+            .position(|tx| utxo.outpoint.txid == tx.txid && utxo.outpoint.vout == tx.change_vout);
+        let tx = match position {
+            Some(position) => self.submitted_transactions.remove(position),
+            None => return,
+        };
+        self.confirmed_transactions.push(ConfirmedBtcTransaction {
+            requests: tx.requests,
+            txid: tx.txid,
+        });
+        if self.confirmed_transactions.len() > MAX_CONFIRMED_TRANSACTIONS {
+            let excess = self.confirmed_transactions.len() - MAX_CONFIRMED_TRANSACTIONS;
+            self.confirmed_transactions.drain(..excess);
+        }
+        self.outpoint_account
+            .insert(utxo.outpoint.clone(), change_account.clone());
+        self.available_utxos.insert(utxo);
+    }
+
+    /// Returns true if the batch being signed pays out `requests` with
+    /// `utxos`.
+    pub fn is_signing_batch(&self, requests: &[RetrieveBtcRequest], utxos: &[Utxo]) -> bool {
+        self.signing_batch.as_ref().map_or(false, |batch| {
+            batch.requests == requests && batch.utxos == utxos
+        })
+    }
+
+    /// Returns the requests and UTXOs of the batch whose signing did not
+    /// complete, if there is one.
+    pub fn return_signing_batch(&mut self) {
+        if let Some(batch) = self.signing_batch.take() {
+            self.return_batch(batch.requests, batch.utxos);
+        }
+    }
+
+    /// Returns the requests and UTXOs of a batch that could not be submitted.
+    /// The requests go back to the front of the queue so that they keep
+    /// their priority.
+    pub fn return_batch(&mut self, requests: Vec<RetrieveBtcRequest>, utxos: Vec<Utxo>) {
+        for req in &requests {
+            self.requests_in_flight.remove(&req.block_index);
+        }
+        let mut pending = requests;
+        pending.append(&mut self.pending_retrieve_btc_requests);
+        self.pending_retrieve_btc_requests = pending;
+        self.available_utxos.extend(utxos);
+    }
 }
 
 /// Take the current state.
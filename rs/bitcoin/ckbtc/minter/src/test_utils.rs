@@ -0,0 +1,194 @@
+//! Fake canisters for testing the minter logic without a replica.
+use crate::lifecycle::init::InitArgs;
+use crate::runtime::Runtime;
+use crate::state::{replace_state, CkBtcMinterState};
+use crate::ECDSAPublicKey;
+use async_trait::async_trait;
+use candid::utils::{decode_args, encode_args, ArgumentDecoder, ArgumentEncoder};
+use candid::{Decode, Encode, Nat, Principal};
+use ic_base_types::CanisterId;
+use ic_btc_types::{
+    GetCurrentFeePercentilesRequest, GetUtxosRequest, GetUtxosResponse, Network, OutPoint, Page,
+    SendTransactionRequest, Utxo, UtxosFilter,
+};
+use ic_ic00_types::{SignWithECDSAArgs, SignWithECDSAReply};
+use ic_icrc1::endpoints::{TransferArg, TransferError};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+pub const MINTER_ID: CanisterId = CanisterId::from_u64(10);
+pub const LEDGER_ID: CanisterId = CanisterId::from_u64(1);
+pub const MIN_CONFIRMATIONS: u32 = 6;
+pub const RETRIEVE_BTC_MIN_AMOUNT: u64 = 10_000;
+
+/// Installs a minter state with a known ECDSA public key.
+pub fn init_state() {
+    let mut state = CkBtcMinterState::from(InitArgs {
+        btc_network: Network::Regtest,
+        ecdsa_key_name: "test_key".to_string(),
+        min_confirmations: Some(MIN_CONFIRMATIONS),
+        ledger_id: LEDGER_ID,
+        retrieve_btc_min_amount: Some(RETRIEVE_BTC_MIN_AMOUNT),
+    });
+    state.ecdsa_public_key = Some(ECDSAPublicKey {
+        public_key: hex::decode(
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        )
+        .unwrap(),
+        chain_code: vec![1; 32],
+    });
+    replace_state(state);
+}
+
+/// Fakes the Bitcoin and threshold ECDSA APIs of the management canister
+/// and the ckBTC ledger.
+#[derive(Default)]
+pub struct FakeCanisters {
+    /// UTXOs with enough confirmations, by address. The Bitcoin API returns
+    /// them one per page.
+    pub utxos: Mutex<BTreeMap<String, Vec<Utxo>>>,
+    /// The fee percentiles returned by the Bitcoin API.
+    pub fee_percentiles: Mutex<Vec<u64>>,
+    /// Transactions sent to the Bitcoin API.
+    pub sent_transactions: Mutex<Vec<Vec<u8>>>,
+    /// If set, the Bitcoin API rejects transactions.
+    pub reject_transactions: Mutex<bool>,
+    /// Signature requests received by the ECDSA API.
+    pub signature_requests: Mutex<Vec<SignWithECDSAArgs>>,
+    /// Transfers received by the ledger.
+    pub transfers: Mutex<Vec<TransferArg>>,
+    /// If set, the ledger rejects transfers with this error.
+    pub ledger_error: Mutex<Option<TransferError>>,
+    /// If set, the ledger returns this block index instead of the position of
+    /// the transfer.
+    pub block_index_override: Mutex<Option<Nat>>,
+    /// The current time, in nanoseconds since the epoch.
+    pub time: Mutex<u64>,
+}
+
+impl FakeCanisters {
+    pub fn add_utxo(&self, address: &str, txid: u8, value: u64) {
+        self.utxos
+            .lock()
+            .unwrap()
+            .entry(address.to_string())
+            .or_default()
+            .push(Utxo {
+                outpoint: OutPoint {
+                    txid: vec![txid; 32],
+                    vout: 0,
+                },
+                value,
+                height: 1,
+            });
+    }
+
+    fn get_utxos(&self, request: GetUtxosRequest) -> GetUtxosResponse {
+        assert_eq!(request.network, Network::Regtest);
+        let index = match request.filter {
+            Some(UtxosFilter::MinConfirmations(min_confirmations)) => {
+                assert_eq!(min_confirmations, MIN_CONFIRMATIONS);
+                0
+            }
+            Some(UtxosFilter::Page(page)) => page[0] as usize,
+            None => panic!("expected a filter"),
+        };
+        let all_utxos = self.utxos.lock().unwrap();
+        let utxos = all_utxos.get(&request.address).cloned().unwrap_or_default();
+        GetUtxosResponse {
+            utxos: utxos.get(index).cloned().into_iter().collect(),
+            tip_block_hash: vec![],
+            tip_height: 100,
+            next_page: if index + 1 < utxos.len() {
+                Some(Page::from(vec![index as u8 + 1]))
+            } else {
+                None
+            },
+        }
+    }
+
+    fn sign_with_ecdsa(&self, args: SignWithECDSAArgs) -> SignWithECDSAReply {
+        // The signature is not verified, any 64 bytes will do.
+        let mut signature = args.message_hash.clone();
+        signature.extend_from_slice(&[1; 32]);
+        self.signature_requests.lock().unwrap().push(args);
+        SignWithECDSAReply { signature }
+    }
+
+    fn icrc1_transfer(&self, arg: TransferArg) -> Result<Nat, TransferError> {
+        if let Some(err) = self.ledger_error.lock().unwrap().clone() {
+            return Err(err);
+        }
+        let mut transfers = self.transfers.lock().unwrap();
+        transfers.push(arg);
+        if let Some(block_index) = self.block_index_override.lock().unwrap().clone() {
+            return Ok(block_index);
+        }
+        Ok(Nat::from(transfers.len() as u64 - 1))
+    }
+}
+
+#[async_trait]
+impl Runtime for FakeCanisters {
+    fn id(&self) -> Principal {
+        MINTER_ID.get().0
+    }
+
+    fn print(&self, msg: &str) {
+        println!("{}", msg);
+    }
+
+    fn time(&self) -> u64 {
+        *self.time.lock().unwrap()
+    }
+
+    async fn call<In, Out>(
+        &self,
+        id: Principal,
+        method: &str,
+        _cycles: u64,
+        args: In,
+    ) -> Result<Out, (i32, String)>
+    where
+        In: ArgumentEncoder + Send,
+        Out: for<'a> ArgumentDecoder<'a>,
+    {
+        let args = encode_args(args).unwrap();
+        let reply = match method {
+            "bitcoin_get_utxos" => {
+                assert_eq!(id, Principal::management_canister());
+                let request = Decode!(&args, GetUtxosRequest).unwrap();
+                Encode!(&self.get_utxos(request)).unwrap()
+            }
+            "bitcoin_get_current_fee_percentiles" => {
+                assert_eq!(id, Principal::management_canister());
+                Decode!(&args, GetCurrentFeePercentilesRequest).unwrap();
+                Encode!(&*self.fee_percentiles.lock().unwrap()).unwrap()
+            }
+            "bitcoin_send_transaction" => {
+                assert_eq!(id, Principal::management_canister());
+                if *self.reject_transactions.lock().unwrap() {
+                    return Err((4, "MalformedTransaction".to_string()));
+                }
+                let request = Decode!(&args, SendTransactionRequest).unwrap();
+                self.sent_transactions
+                    .lock()
+                    .unwrap()
+                    .push(request.transaction);
+                Encode!().unwrap()
+            }
+            "sign_with_ecdsa" => {
+                assert_eq!(id, Principal::management_canister());
+                let request = Decode!(&args, SignWithECDSAArgs).unwrap();
+                Encode!(&self.sign_with_ecdsa(request)).unwrap()
+            }
+            "icrc1_transfer" => {
+                assert_eq!(id, LEDGER_ID.get().0);
+                let arg = Decode!(&args, TransferArg).unwrap();
+                Encode!(&self.icrc1_transfer(arg)).unwrap()
+            }
+            _ => return Err((3, format!("unknown method {}", method))),
+        };
+        Ok(decode_args(&reply).unwrap())
+    }
+}
@@ -0,0 +1,368 @@
+//! Encoding of the Bitcoin transactions built by the minter.
+//!
+//! The minter only spends P2WPKH outputs, so every input is signed with a
+//! [BIP-0143](https://github.com/bitcoin/bips/blob/master/bip-0143.mediawiki)
+//! signature hash and carries a `<signature> <public key>` witness.
+use crate::address::BitcoinAddress;
+use ic_btc_types::{OutPoint, Satoshi};
+use ic_crypto_sha::Sha256;
+
+/// Version 2 transactions enable relative lock-times (BIP-0068).
+pub const TX_VERSION: u32 = 2;
+
+/// Signals that the transaction can be replaced (BIP-0125).
+pub const SEQUENCE_RBF_ENABLED: u32 = 0xfffffffd;
+
+/// The signature commits to all inputs and outputs.
+pub const SIGHASH_ALL: u32 = 1;
+
+/// Upper bound on the size of a DER signature followed by the sighash type.
+const MAX_SIGNATURE_LEN: usize = 73;
+
+/// The size of a compressed public key.
+const PUBLIC_KEY_LEN: usize = 33;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnsignedInput {
+    pub previous_output: OutPoint,
+    /// The value of the spent output, committed to by the signature hash.
+    pub value: Satoshi,
+    pub sequence: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TxOut {
+    pub value: Satoshi,
+    pub address: BitcoinAddress,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnsignedTransaction {
+    pub inputs: Vec<UnsignedInput>,
+    pub outputs: Vec<TxOut>,
+    pub lock_time: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignedInput {
+    pub previous_output: OutPoint,
+    pub sequence: u32,
+    /// The DER signature followed by the sighash type.
+    pub signature: Vec<u8>,
+    /// The compressed public key of the spent P2WPKH output.
+    pub public_key: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignedTransaction {
+    pub inputs: Vec<SignedInput>,
+    pub outputs: Vec<TxOut>,
+    pub lock_time: u32,
+}
+
+impl UnsignedTransaction {
+    /// Returns the transaction id in internal byte order.
+    pub fn txid(&self) -> [u8; 32] {
+        let inputs: Vec<_> = self
+            .inputs
+            .iter()
+            .map(|input| (&input.previous_output, input.sequence))
+            .collect();
+        double_sha256(&encode_without_witness(
+            &inputs,
+            &self.outputs,
+            self.lock_time,
+        ))
+    }
+
+    /// Computes the BIP-0143 signature hash of the input at `index`, which
+    /// spends a P2WPKH output locked to `public_key_hash`.
+    pub fn sighash(&self, index: usize, public_key_hash: &[u8; 20]) -> [u8; 32] {
+        let input = &self.inputs[index];
+
+        let mut prevouts = Vec::new();
+        let mut sequences = Vec::new();
+        for input in &self.inputs {
+            write_outpoint(&mut prevouts, &input.previous_output);
+            sequences.extend_from_slice(&input.sequence.to_le_bytes());
+        }
+        let mut outputs = Vec::new();
+        for output in &self.outputs {
+            write_output(&mut outputs, output);
+        }
+
+        let mut preimage = Vec::new();
+        preimage.extend_from_slice(&TX_VERSION.to_le_bytes());
+        preimage.extend_from_slice(&double_sha256(&prevouts));
+        preimage.extend_from_slice(&double_sha256(&sequences));
+        write_outpoint(&mut preimage, &input.previous_output);
+        // The script code of a P2WPKH input is the equivalent P2PKH script.
+        write_bytes(
+            &mut preimage,
+            &BitcoinAddress::P2pkh(*public_key_hash).script_pubkey(),
+        );
+        preimage.extend_from_slice(&input.value.to_le_bytes());
+        preimage.extend_from_slice(&input.sequence.to_le_bytes());
+        preimage.extend_from_slice(&double_sha256(&outputs));
+        preimage.extend_from_slice(&self.lock_time.to_le_bytes());
+        preimage.extend_from_slice(&SIGHASH_ALL.to_le_bytes());
+        double_sha256(&preimage)
+    }
+
+    /// Attaches the witnesses, given as `(signature, public_key)` pairs in
+    /// input order.
+    pub fn sign(self, witnesses: Vec<(Vec<u8>, Vec<u8>)>) -> SignedTransaction {
+        assert_eq!(self.inputs.len(), witnesses.len());
+        SignedTransaction {
+            inputs: self
+                .inputs
+                .into_iter()
+                .zip(witnesses)
+                .map(|(input, (signature, public_key))| SignedInput {
+                    previous_output: input.previous_output,
+                    sequence: input.sequence,
+                    signature,
+                    public_key,
+                })
+                .collect(),
+            outputs: self.outputs,
+            lock_time: self.lock_time,
+        }
+    }
+
+    /// Signs the transaction with placeholder witnesses of the maximum size,
+    /// for estimating the size of the signed transaction.
+    pub fn fake_sign(&self) -> SignedTransaction {
+        let witnesses = self
+            .inputs
+            .iter()
+            .map(|_| (vec![0; MAX_SIGNATURE_LEN], vec![0; PUBLIC_KEY_LEN]))
+            .collect();
+        self.clone().sign(witnesses)
+    }
+}
+
+impl SignedTransaction {
+    /// Returns the transaction id in internal byte order.
+    pub fn txid(&self) -> [u8; 32] {
+        double_sha256(&self.encode_without_witness())
+    }
+
+    /// Serializes the transaction in the segwit format (BIP-0144).
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&TX_VERSION.to_le_bytes());
+        // Segwit marker and flag.
+        buf.extend_from_slice(&[0x00, 0x01]);
+        write_compact_size(&mut buf, self.inputs.len());
+        for input in &self.inputs {
+            write_input(&mut buf, &input.previous_output, input.sequence);
+        }
+        write_compact_size(&mut buf, self.outputs.len());
+        for output in &self.outputs {
+            write_output(&mut buf, output);
+        }
+        for input in &self.inputs {
+            write_compact_size(&mut buf, 2);
+            write_bytes(&mut buf, &input.signature);
+            write_bytes(&mut buf, &input.public_key);
+        }
+        buf.extend_from_slice(&self.lock_time.to_le_bytes());
+        buf
+    }
+
+    /// Returns the virtual size in vbytes that determines the fee (BIP-0141).
+    pub fn vsize(&self) -> usize {
+        let base_size = self.encode_without_witness().len();
+        let total_size = self.serialize().len();
+        let weight = base_size * 3 + total_size;
+        (weight + 3) / 4
+    }
+
+    fn encode_without_witness(&self) -> Vec<u8> {
+        let inputs: Vec<_> = self
+            .inputs
+            .iter()
+            .map(|input| (&input.previous_output, input.sequence))
+            .collect();
+        encode_without_witness(&inputs, &self.outputs, self.lock_time)
+    }
+}
+
+fn encode_without_witness(
+    inputs: &[(&OutPoint, u32)],
+    outputs: &[TxOut],
+    lock_time: u32,
+) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&TX_VERSION.to_le_bytes());
+    write_compact_size(&mut buf, inputs.len());
+    for (outpoint, sequence) in inputs {
+        write_input(&mut buf, outpoint, *sequence);
+    }
+    write_compact_size(&mut buf, outputs.len());
+    for output in outputs {
+        write_output(&mut buf, output);
+    }
+    buf.extend_from_slice(&lock_time.to_le_bytes());
+    buf
+}
+
+fn write_input(buf: &mut Vec<u8>, outpoint: &OutPoint, sequence: u32) {
+    write_outpoint(buf, outpoint);
+    // Segwit inputs have an empty script_sig.
+    write_compact_size(buf, 0);
+    buf.extend_from_slice(&sequence.to_le_bytes());
+}
+
+fn write_outpoint(buf: &mut Vec<u8>, outpoint: &OutPoint) {
+    buf.extend_from_slice(&outpoint.txid);
+    buf.extend_from_slice(&outpoint.vout.to_le_bytes());
+}
+
+fn write_output(buf: &mut Vec<u8>, output: &TxOut) {
+    buf.extend_from_slice(&output.value.to_le_bytes());
+    write_bytes(buf, &output.address.script_pubkey());
+}
+
+fn write_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    write_compact_size(buf, bytes.len());
+    buf.extend_from_slice(bytes);
+}
+
+fn write_compact_size(buf: &mut Vec<u8>, n: usize) {
+    match n {
+        0..=0xfc => buf.push(n as u8),
+        0xfd..=0xffff => {
+            buf.push(0xfd);
+            buf.extend_from_slice(&(n as u16).to_le_bytes());
+        }
+        0x10000..=0xffff_ffff => {
+            buf.push(0xfe);
+            buf.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+        _ => {
+            buf.push(0xff);
+            buf.extend_from_slice(&(n as u64).to_le_bytes());
+        }
+    }
+}
+
+fn double_sha256(bytes: &[u8]) -> [u8; 32] {
+    Sha256::hash(&Sha256::hash(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outpoint(txid: &str, vout: u32) -> OutPoint {
+        OutPoint {
+            txid: hex::decode(txid).unwrap(),
+            vout,
+        }
+    }
+
+    /// The transaction of the native P2WPKH example of BIP-0143, with the
+    /// version bumped to [TX_VERSION].
+    fn bip143_transaction() -> UnsignedTransaction {
+        let mut pkh1 = [0u8; 20];
+        pkh1.copy_from_slice(&hex::decode("8280b37df378db99f66f85c95a783a76ac7a6d59").unwrap());
+        let mut pkh2 = [0u8; 20];
+        pkh2.copy_from_slice(&hex::decode("3bde42dbee7e4dbe6a21b2d50ce2f0167faa8159").unwrap());
+        UnsignedTransaction {
+            inputs: vec![
+                UnsignedInput {
+                    previous_output: outpoint(
+                        "fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f",
+                        0,
+                    ),
+                    value: 625_000_000,
+                    sequence: 0xffffffee,
+                },
+                UnsignedInput {
+                    previous_output: outpoint(
+                        "ef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a",
+                        1,
+                    ),
+                    value: 600_000_000,
+                    sequence: 0xffffffff,
+                },
+            ],
+            outputs: vec![
+                TxOut {
+                    value: 112_340_000,
+                    address: BitcoinAddress::P2pkh(pkh1),
+                },
+                TxOut {
+                    value: 223_450_000,
+                    address: BitcoinAddress::P2pkh(pkh2),
+                },
+            ],
+            lock_time: 17,
+        }
+    }
+
+    #[test]
+    fn encodes_unsigned_transaction() {
+        let tx = bip143_transaction();
+        let inputs: Vec<_> = tx
+            .inputs
+            .iter()
+            .map(|input| (&input.previous_output, input.sequence))
+            .collect();
+        assert_eq!(
+            hex::encode(encode_without_witness(&inputs, &tx.outputs, tx.lock_time)),
+            "0200000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000"
+        );
+    }
+
+    #[test]
+    fn computes_p2wpkh_sighash() {
+        let mut pkh = [0u8; 20];
+        pkh.copy_from_slice(&hex::decode("1d0f172a0ecb48aee1be1f2687d2963ae33f71a1").unwrap());
+        assert_eq!(
+            hex::encode(bip143_transaction().sighash(1, &pkh)),
+            "dce56defd543a3be752a5f409c190b3a0bab22447386c3b89e261c86e71076f5"
+        );
+    }
+
+    #[test]
+    fn signed_transaction_has_witnesses() {
+        let unsigned = bip143_transaction();
+        let txid = unsigned.txid();
+        let signed = unsigned.sign(vec![(vec![1; 71], vec![2; 33]), (vec![3; 72], vec![4; 33])]);
+        // The witness does not change the transaction id.
+        assert_eq!(signed.txid(), txid);
+
+        let bytes = signed.serialize();
+        assert_eq!(&bytes[4..6], &[0x00, 0x01]);
+        let base_size = signed.encode_without_witness().len();
+        let witness_size = 2 + (1 + 1 + 71 + 1 + 33) + (1 + 1 + 72 + 1 + 33);
+        assert_eq!(bytes.len(), base_size + witness_size);
+        assert_eq!(signed.vsize(), (base_size * 4 + witness_size + 3) / 4);
+    }
+
+    #[test]
+    fn fake_signature_is_an_upper_bound() {
+        let unsigned = bip143_transaction();
+        let fake = unsigned.fake_sign();
+        let real = unsigned.sign(vec![(vec![1; 71], vec![2; 33]), (vec![3; 72], vec![4; 33])]);
+        assert!(fake.vsize() >= real.vsize());
+    }
+
+    #[test]
+    fn encodes_compact_size() {
+        for (n, expected) in [
+            (0, "00"),
+            (0xfc, "fc"),
+            (0xfd, "fdfd00"),
+            (0xffff, "fdffff"),
+            (0x10000, "fe00000100"),
+        ] {
+            let mut buf = Vec::new();
+            write_compact_size(&mut buf, n);
+            assert_eq!(hex::encode(buf), expected);
+        }
+    }
+}
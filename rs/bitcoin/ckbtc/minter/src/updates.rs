@@ -1,5 +1,18 @@
 pub mod get_btc_address;
 pub mod get_withdrawal_account;
+pub mod retrieve_btc;
+pub mod update_balance;
 
 pub use get_btc_address::get_btc_address;
 pub use get_withdrawal_account::get_withdrawal_account;
+pub use retrieve_btc::retrieve_btc;
+pub use update_balance::update_balance;
+
+/// The `error_code`s of the `GenericError` variants of the update endpoints.
+pub mod error_code {
+    /// The ledger rejected the transaction.
+    pub const LEDGER_REJECTED: u64 = 1;
+    /// The ledger executed the transaction, but returned a block index that
+    /// does not fit into 64 bits.
+    pub const BLOCK_INDEX_OUT_OF_RANGE: u64 = 2;
+}
@@ -26,17 +26,26 @@ pub struct GetBtcAddressResult {
     pub address: String,
 }
 
+/// Returns the derivation path of the key that controls the deposits of an
+/// Account (Principal + subaccount)
+pub fn derivation_path(account: &Account) -> Vec<Vec<u8>> {
+    vec![
+        vec![SCHEMA_V1],
+        account.owner.as_slice().to_vec(),
+        account.effective_subaccount().to_vec(),
+    ]
+}
+
 /// Returns a valid extended BIP-32 derivation path from an Account (Principal + subaccount)
-fn derive_public_key(account: Account) -> ECDSAPublicKey {
+pub fn derive_public_key(account: &Account) -> ECDSAPublicKey {
     let ECDSAPublicKey {
         public_key,
         chain_code,
     } = read_state(|s| s.ecdsa_public_key.clone().unwrap());
-    let derivation_schema = vec![
-        DerivationIndex(vec![SCHEMA_V1]),
-        DerivationIndex(account.owner.as_slice().to_vec()),
-        DerivationIndex(account.effective_subaccount().to_vec()),
-    ];
+    let derivation_schema = derivation_path(account)
+        .into_iter()
+        .map(DerivationIndex)
+        .collect();
     let ExtendedBip32DerivationOutput {
         derived_public_key,
         derived_chain_code,
@@ -71,15 +80,23 @@ fn network_and_public_key_to_p2wpkh(network: Network, public_key: Vec<u8>) -> St
     bech32::encode(hrp, data, Variant::Bech32).unwrap()
 }
 
+/// Returns the p2wpkh deposit address of the given account.
+///
+/// The Minter ECDSA public key must be initialized.
+pub fn account_to_p2wpkh_address(network: Network, account: &Account) -> String {
+    network_and_public_key_to_p2wpkh(network, derive_public_key(account).public_key)
+}
+
 pub async fn get_btc_address(args: GetBtcAddressArgs) -> GetBtcAddressResult {
     init_ecdsa_public_key().await;
     let caller = PrincipalId(ic_cdk::caller());
-    let public_key = derive_public_key(Account {
-        owner: caller,
-        subaccount: args.subaccount,
-    })
-    .public_key;
-    let address = network_and_public_key_to_p2wpkh(read_state(|s| s.btc_network), public_key);
+    let address = account_to_p2wpkh_address(
+        read_state(|s| s.btc_network),
+        &Account {
+            owner: caller,
+            subaccount: args.subaccount,
+        },
+    );
     GetBtcAddressResult { address }
 }
 
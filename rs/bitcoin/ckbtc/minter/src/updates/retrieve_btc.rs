@@ -0,0 +1,316 @@
+use crate::{
+    address::BitcoinAddress,
+    guard::{retrieve_btc_guard, GuardError},
+    runtime::{CdkRuntime, Runtime},
+    state::{mutate_state, read_state, RetrieveBtcRequest, RetrieveBtcStatus},
+};
+use candid::{CandidType, Deserialize, Nat};
+use ic_base_types::PrincipalId;
+use ic_icrc1::{
+    endpoints::{TransferArg, TransferError},
+    Account,
+};
+use num_traits::ToPrimitive;
+use serde::Serialize;
+
+use super::error_code;
+use super::get_btc_address::init_ecdsa_public_key;
+use super::get_withdrawal_account::compute_subaccount;
+
+/// The maximum number of requests waiting to be included in a transaction.
+const MAX_PENDING_REQUESTS: usize = 5000;
+
+#[derive(CandidType, Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct RetrieveBtcArgs {
+    /// The amount to withdraw, in satoshi. The transaction fee is deducted
+    /// from it.
+    pub amount: u64,
+    /// The Bitcoin address to send the BTC to.
+    pub address: String,
+}
+
+#[derive(CandidType, Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct RetrieveBtcOk {
+    /// The index of the burn transaction on the ckBTC ledger, identifies the
+    /// request in `retrieve_btc_status`.
+    pub block_index: u64,
+}
+
+#[derive(CandidType, Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub enum RetrieveBtcError {
+    /// The address is malformed or belongs to another network.
+    MalformedAddress(String),
+    /// There is already a retrieve_btc call in progress for the caller.
+    AlreadyProcessing,
+    /// The amount is below the minimum withdrawal amount.
+    AmountTooLow(u64),
+    /// The withdrawal account does not hold enough ckBTC.
+    InsufficientFunds { balance: u64 },
+    /// The minter or one of the canisters it depends on is overloaded or
+    /// unreachable, the call can be retried later.
+    TemporarilyUnavailable(String),
+    /// The ledger rejected the burn transaction.
+    GenericError {
+        error_code: u64,
+        error_message: String,
+    },
+}
+
+impl From<GuardError> for RetrieveBtcError {
+    fn from(err: GuardError) -> Self {
+        match err {
+            GuardError::AlreadyProcessing => Self::AlreadyProcessing,
+            GuardError::TooManyConcurrentRequests => {
+                Self::TemporarilyUnavailable("too many concurrent requests".to_string())
+            }
+        }
+    }
+}
+
+#[derive(CandidType, Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct RetrieveBtcStatusRequest {
+    pub block_index: u64,
+}
+
+/// Burns `amount` ckBTC from the caller's withdrawal account and queues a
+/// request to send the BTC to `address`. The request is paid out by a later
+/// heartbeat.
+pub async fn retrieve_btc(args: RetrieveBtcArgs) -> Result<RetrieveBtcOk, RetrieveBtcError> {
+    let caller = PrincipalId(ic_cdk::caller());
+    init_ecdsa_public_key().await;
+    retrieve_btc_with_runtime(&CdkRuntime, caller, args).await
+}
+
+/// Implements [retrieve_btc] for the given `caller`, making all calls through `runtime`.
+pub async fn retrieve_btc_with_runtime<R: Runtime>(
+    runtime: &R,
+    caller: PrincipalId,
+    args: RetrieveBtcArgs,
+) -> Result<RetrieveBtcOk, RetrieveBtcError> {
+    let _guard = retrieve_btc_guard(caller.0)?;
+
+    let (btc_network, min_amount, queue_len) = read_state(|s| {
+        (
+            s.btc_network,
+            s.retrieve_btc_min_amount,
+            s.pending_retrieve_btc_requests.len(),
+        )
+    });
+    let address = BitcoinAddress::parse(&args.address, btc_network)
+        .map_err(|err| RetrieveBtcError::MalformedAddress(err.to_string()))?;
+    if args.amount < min_amount {
+        return Err(RetrieveBtcError::AmountTooLow(min_amount));
+    }
+    if queue_len >= MAX_PENDING_REQUESTS {
+        return Err(RetrieveBtcError::TemporarilyUnavailable(
+            "too many pending withdrawals".to_string(),
+        ));
+    }
+
+    let burn_index = burn(runtime, caller, args.amount).await?;
+
+    // The ckBTC is burned at this point, so the request is queued even if the
+    // block index does not fit into u64. Such a request cannot be looked up
+    // by its index, but it is still paid out.
+    let block_index = burn_index.0.to_u64();
+    mutate_state(|s| {
+        s.pending_retrieve_btc_requests.push(RetrieveBtcRequest {
+            amount: args.amount,
+            address,
+            block_index: block_index.unwrap_or(u64::MAX),
+            caller,
+        })
+    });
+    let block_index = block_index.ok_or_else(|| RetrieveBtcError::GenericError {
+        error_code: error_code::BLOCK_INDEX_OUT_OF_RANGE,
+        error_message: format!(
+            "burned {} satoshi at block index {}, which does not fit into u64, the withdrawal is queued",
+            args.amount, burn_index
+        ),
+    })?;
+    Ok(RetrieveBtcOk { block_index })
+}
+
+/// Returns the status of the request whose burn transaction is at `block_index`.
+pub fn retrieve_btc_status(args: RetrieveBtcStatusRequest) -> RetrieveBtcStatus {
+    read_state(|s| s.retrieve_btc_status(args.block_index))
+}
+
+/// Burns `amount` ckBTC from the withdrawal account of `caller` and returns
+/// the block index.
+async fn burn<R: Runtime>(
+    runtime: &R,
+    caller: PrincipalId,
+    amount: u64,
+) -> Result<Nat, RetrieveBtcError> {
+    let ledger_id = read_state(|s| s.ledger_id);
+    // The minter is the minting account of the ledger, so a transfer to it is a burn.
+    let minting_account = Account {
+        owner: PrincipalId(runtime.id()),
+        subaccount: None,
+    };
+    let (result,): (Result<Nat, TransferError>,) = runtime
+        .call(
+            ledger_id.get().0,
+            "icrc1_transfer",
+            0,
+            (TransferArg {
+                from_subaccount: Some(compute_subaccount(caller, 0)),
+                to: minting_account,
+                fee: None,
+                created_at_time: None,
+                memo: None,
+                amount: Nat::from(amount),
+            },),
+        )
+        .await
+        .map_err(|(code, msg)| {
+            RetrieveBtcError::TemporarilyUnavailable(format!(
+                "failed to call the ledger (code {}): {}",
+                code, msg
+            ))
+        })?;
+    match result {
+        Ok(block_index) => Ok(block_index),
+        Err(TransferError::InsufficientFunds { balance }) => {
+            Err(RetrieveBtcError::InsufficientFunds {
+                balance: balance.0.to_u64().unwrap_or(u64::MAX),
+            })
+        }
+        Err(TransferError::TemporarilyUnavailable) => Err(
+            RetrieveBtcError::TemporarilyUnavailable("the ledger is busy".to_string()),
+        ),
+        Err(err) => Err(RetrieveBtcError::GenericError {
+            error_code: error_code::LEDGER_REJECTED,
+            error_message: format!("failed to burn ckBTC: {:?}", err),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{init_state, FakeCanisters, MINTER_ID, RETRIEVE_BTC_MIN_AMOUNT};
+    use futures::executor::block_on;
+
+    const ADDRESS: &str = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+
+    fn caller() -> PrincipalId {
+        PrincipalId::new_user_test_id(42)
+    }
+
+    fn retrieve_btc(
+        canisters: &FakeCanisters,
+        address: &str,
+        amount: u64,
+    ) -> Result<RetrieveBtcOk, RetrieveBtcError> {
+        block_on(retrieve_btc_with_runtime(
+            canisters,
+            caller(),
+            RetrieveBtcArgs {
+                amount,
+                address: address.to_string(),
+            },
+        ))
+    }
+
+    #[test]
+    fn burns_and_queues_request() {
+        init_state();
+        let canisters = FakeCanisters::default();
+        assert_eq!(
+            retrieve_btc(&canisters, ADDRESS, 50_000),
+            Ok(RetrieveBtcOk { block_index: 0 })
+        );
+
+        let transfers = canisters.transfers.lock().unwrap();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(
+            transfers[0].from_subaccount,
+            Some(compute_subaccount(caller(), 0))
+        );
+        assert_eq!(
+            transfers[0].to,
+            Account {
+                owner: MINTER_ID.get(),
+                subaccount: None
+            }
+        );
+        assert_eq!(transfers[0].amount, Nat::from(50_000_u64));
+        assert_eq!(
+            retrieve_btc_status(RetrieveBtcStatusRequest { block_index: 0 }),
+            RetrieveBtcStatus::Pending
+        );
+        assert_eq!(
+            retrieve_btc_status(RetrieveBtcStatusRequest { block_index: 1 }),
+            RetrieveBtcStatus::Unknown
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_requests() {
+        init_state();
+        let canisters = FakeCanisters::default();
+        // A mainnet address on a regtest minter.
+        assert!(matches!(
+            retrieve_btc(
+                &canisters,
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+                50_000
+            ),
+            Err(RetrieveBtcError::MalformedAddress(_))
+        ));
+        assert_eq!(
+            retrieve_btc(&canisters, ADDRESS, RETRIEVE_BTC_MIN_AMOUNT - 1),
+            Err(RetrieveBtcError::AmountTooLow(RETRIEVE_BTC_MIN_AMOUNT))
+        );
+        assert!(canisters.transfers.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn failed_burn_is_not_queued() {
+        init_state();
+        let canisters = FakeCanisters::default();
+        *canisters.ledger_error.lock().unwrap() = Some(TransferError::InsufficientFunds {
+            balance: Nat::from(10_u64),
+        });
+        assert_eq!(
+            retrieve_btc(&canisters, ADDRESS, 50_000),
+            Err(RetrieveBtcError::InsufficientFunds { balance: 10 })
+        );
+        assert!(read_state(|s| s.pending_retrieve_btc_requests.is_empty()));
+    }
+
+    #[test]
+    fn burn_is_queued_if_block_index_is_out_of_range() {
+        init_state();
+        let canisters = FakeCanisters::default();
+        *canisters.block_index_override.lock().unwrap() = Some(Nat::from(u64::MAX as u128 + 1));
+        assert!(matches!(
+            retrieve_btc(&canisters, ADDRESS, 50_000),
+            Err(RetrieveBtcError::GenericError {
+                error_code: error_code::BLOCK_INDEX_OUT_OF_RANGE,
+                ..
+            })
+        ));
+        let pending = read_state(|s| s.pending_retrieve_btc_requests.clone());
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].amount, 50_000);
+        assert_eq!(pending[0].caller, caller());
+    }
+
+    #[test]
+    fn rejected_burn_returns_error_code() {
+        init_state();
+        let canisters = FakeCanisters::default();
+        *canisters.ledger_error.lock().unwrap() = Some(TransferError::TooOld);
+        assert!(matches!(
+            retrieve_btc(&canisters, ADDRESS, 50_000),
+            Err(RetrieveBtcError::GenericError {
+                error_code: error_code::LEDGER_REJECTED,
+                ..
+            })
+        ));
+        assert!(read_state(|s| s.pending_retrieve_btc_requests.is_empty()));
+    }
+}
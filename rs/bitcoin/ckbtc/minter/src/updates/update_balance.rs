@@ -0,0 +1,331 @@
+use crate::{
+    guard::{balance_update_guard, GuardError},
+    management::get_utxos,
+    runtime::{CdkRuntime, Runtime},
+    state::{mutate_state, read_state},
+};
+use candid::{CandidType, Deserialize, Nat};
+use ic_base_types::PrincipalId;
+use ic_btc_types::Utxo;
+use ic_icrc1::{
+    endpoints::{TransferArg, TransferError},
+    Account, Subaccount,
+};
+use num_traits::ToPrimitive;
+use serde::Serialize;
+
+use super::error_code;
+use super::get_btc_address::{account_to_p2wpkh_address, init_ecdsa_public_key};
+
+#[derive(CandidType, Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct UpdateBalanceArgs {
+    pub subaccount: Option<Subaccount>,
+}
+
+#[derive(CandidType, Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct UpdateBalanceResult {
+    /// The amount of ckBTC minted, in satoshi.
+    pub amount: u64,
+    /// The index of the mint transaction on the ckBTC ledger.
+    pub block_index: u64,
+}
+
+#[derive(CandidType, Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub enum UpdateBalanceError {
+    /// There are no new deposits with enough confirmations.
+    NoNewUtxos,
+    /// There is already an update_balance call in progress for the caller.
+    AlreadyProcessing,
+    /// The minter or one of the canisters it depends on is overloaded or
+    /// unreachable, the call can be retried later.
+    TemporarilyUnavailable(String),
+    /// The ledger rejected the mint transaction.
+    GenericError {
+        error_code: u64,
+        error_message: String,
+    },
+}
+
+impl From<GuardError> for UpdateBalanceError {
+    fn from(err: GuardError) -> Self {
+        match err {
+            GuardError::AlreadyProcessing => Self::AlreadyProcessing,
+            GuardError::TooManyConcurrentRequests => {
+                Self::TemporarilyUnavailable("too many concurrent requests".to_string())
+            }
+        }
+    }
+}
+
+/// Mints ckBTC for all deposits to the caller's deposit address that have
+/// enough confirmations and have not been minted yet.
+pub async fn update_balance(
+    args: UpdateBalanceArgs,
+) -> Result<UpdateBalanceResult, UpdateBalanceError> {
+    let caller = PrincipalId(ic_cdk::caller());
+    init_ecdsa_public_key().await;
+    update_balance_with_runtime(&CdkRuntime, caller, args).await
+}
+
+/// Implements [update_balance] for the given `caller`, making all calls through `runtime`.
+pub async fn update_balance_with_runtime<R: Runtime>(
+    runtime: &R,
+    caller: PrincipalId,
+    args: UpdateBalanceArgs,
+) -> Result<UpdateBalanceResult, UpdateBalanceError> {
+    let _guard = balance_update_guard(caller.0)?;
+
+    let account = Account {
+        owner: caller,
+        subaccount: args.subaccount,
+    };
+    let (btc_network, min_confirmations) = read_state(|s| (s.btc_network, s.min_confirmations));
+    let address = account_to_p2wpkh_address(btc_network, &account);
+
+    let utxos = get_utxos(runtime, btc_network, &address, min_confirmations)
+        .await
+        .map_err(|(code, msg)| {
+            UpdateBalanceError::TemporarilyUnavailable(format!(
+                "failed to fetch UTXOs of {} (code {}): {}",
+                address, code, msg
+            ))
+        })?;
+    let new_utxos: Vec<Utxo> = read_state(|s| {
+        utxos
+            .into_iter()
+            .filter(|utxo| !s.processed_outpoints.contains(&utxo.outpoint))
+            .collect()
+    });
+    if new_utxos.is_empty() {
+        return Err(UpdateBalanceError::NoNewUtxos);
+    }
+
+    let amount = new_utxos.iter().map(|utxo| utxo.value).sum();
+    let block_index = mint(runtime, amount, account.clone()).await?;
+
+    // The UTXOs are only marked after the mint succeeded so that a failed
+    // mint can be retried. The guard prevents concurrent mints for the same
+    // deposits in the meantime. Minted UTXOs back the ckBTC supply and can
+    // be spent to pay out withdrawals.
+    mutate_state(|s| {
+        for utxo in new_utxos {
+            s.processed_outpoints.insert(utxo.outpoint.clone());
+            s.outpoint_account
+                .insert(utxo.outpoint.clone(), account.clone());
+            s.available_utxos.insert(utxo);
+        }
+    });
+
+    // The deposits are minted at this point, so a block index that cannot be
+    // converted must not trap and roll back the marking above.
+    let block_index = block_index
+        .0
+        .to_u64()
+        .ok_or_else(|| UpdateBalanceError::GenericError {
+            error_code: error_code::BLOCK_INDEX_OUT_OF_RANGE,
+            error_message: format!(
+                "minted {} satoshi at block index {}, which does not fit into u64",
+                amount, block_index
+            ),
+        })?;
+
+    Ok(UpdateBalanceResult {
+        amount,
+        block_index,
+    })
+}
+
+/// Mints `amount` ckBTC to `to` on the ckBTC ledger and returns the block index.
+pub(crate) async fn mint<R: Runtime>(
+    runtime: &R,
+    amount: u64,
+    to: Account,
+) -> Result<Nat, UpdateBalanceError> {
+    let ledger_id = read_state(|s| s.ledger_id);
+    // The minter is the minting account of the ledger, so a transfer from it is a mint.
+    let (result,): (Result<Nat, TransferError>,) = runtime
+        .call(
+            ledger_id.get().0,
+            "icrc1_transfer",
+            0,
+            (TransferArg {
+                from_subaccount: None,
+                to,
+                fee: None,
+                created_at_time: None,
+                memo: None,
+                amount: Nat::from(amount),
+            },),
+        )
+        .await
+        .map_err(|(code, msg)| {
+            UpdateBalanceError::TemporarilyUnavailable(format!(
+                "failed to call the ledger (code {}): {}",
+                code, msg
+            ))
+        })?;
+    match result {
+        Ok(block_index) => Ok(block_index),
+        Err(TransferError::TemporarilyUnavailable) => Err(
+            UpdateBalanceError::TemporarilyUnavailable("the ledger is busy".to_string()),
+        ),
+        Err(err) => Err(UpdateBalanceError::GenericError {
+            error_code: error_code::LEDGER_REJECTED,
+            error_message: format!("failed to mint ckBTC: {:?}", err),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{init_state, FakeCanisters};
+    use futures::executor::block_on;
+    use ic_btc_types::Network;
+
+    fn caller() -> PrincipalId {
+        PrincipalId::new_user_test_id(42)
+    }
+
+    fn deposit_address() -> String {
+        account_to_p2wpkh_address(
+            Network::Regtest,
+            &Account {
+                owner: caller(),
+                subaccount: None,
+            },
+        )
+    }
+
+    fn update_balance(
+        canisters: &FakeCanisters,
+    ) -> Result<UpdateBalanceResult, UpdateBalanceError> {
+        block_on(update_balance_with_runtime(
+            canisters,
+            caller(),
+            UpdateBalanceArgs { subaccount: None },
+        ))
+    }
+
+    #[test]
+    fn mints_new_deposits_once() {
+        init_state();
+        let canisters = FakeCanisters::default();
+        assert_eq!(
+            update_balance(&canisters),
+            Err(UpdateBalanceError::NoNewUtxos)
+        );
+
+        canisters.add_utxo(&deposit_address(), 1, 100);
+        canisters.add_utxo(&deposit_address(), 2, 20);
+        assert_eq!(
+            update_balance(&canisters),
+            Ok(UpdateBalanceResult {
+                amount: 120,
+                block_index: 0
+            })
+        );
+        {
+            let transfers = canisters.transfers.lock().unwrap();
+            assert_eq!(transfers.len(), 1);
+            assert_eq!(transfers[0].amount, Nat::from(120_u64));
+            assert_eq!(
+                transfers[0].to,
+                Account {
+                    owner: caller(),
+                    subaccount: None
+                }
+            );
+        }
+        // The minted deposits can be spent by the minter.
+        assert_eq!(read_state(|s| s.available_utxos.len()), 2);
+
+        // Processed deposits are not minted twice.
+        assert_eq!(
+            update_balance(&canisters),
+            Err(UpdateBalanceError::NoNewUtxos)
+        );
+
+        canisters.add_utxo(&deposit_address(), 3, 5);
+        assert_eq!(
+            update_balance(&canisters),
+            Ok(UpdateBalanceResult {
+                amount: 5,
+                block_index: 1
+            })
+        );
+    }
+
+    #[test]
+    fn failed_mint_can_be_retried() {
+        init_state();
+        let canisters = FakeCanisters::default();
+        canisters.add_utxo(&deposit_address(), 1, 100);
+        *canisters.ledger_error.lock().unwrap() = Some(TransferError::TemporarilyUnavailable);
+        assert!(matches!(
+            update_balance(&canisters),
+            Err(UpdateBalanceError::TemporarilyUnavailable(_))
+        ));
+        assert!(read_state(|s| s.available_utxos.is_empty()));
+
+        *canisters.ledger_error.lock().unwrap() = None;
+        assert_eq!(
+            update_balance(&canisters),
+            Ok(UpdateBalanceResult {
+                amount: 100,
+                block_index: 0
+            })
+        );
+    }
+
+    #[test]
+    fn rejected_mint_returns_error_code() {
+        init_state();
+        let canisters = FakeCanisters::default();
+        canisters.add_utxo(&deposit_address(), 1, 100);
+        *canisters.ledger_error.lock().unwrap() = Some(TransferError::TooOld);
+        assert!(matches!(
+            update_balance(&canisters),
+            Err(UpdateBalanceError::GenericError {
+                error_code: error_code::LEDGER_REJECTED,
+                ..
+            })
+        ));
+        assert!(read_state(|s| s.available_utxos.is_empty()));
+    }
+
+    #[test]
+    fn minted_deposits_are_marked_if_block_index_is_out_of_range() {
+        init_state();
+        let canisters = FakeCanisters::default();
+        canisters.add_utxo(&deposit_address(), 1, 100);
+        *canisters.block_index_override.lock().unwrap() = Some(Nat::from(u64::MAX as u128 + 1));
+        assert!(matches!(
+            update_balance(&canisters),
+            Err(UpdateBalanceError::GenericError {
+                error_code: error_code::BLOCK_INDEX_OUT_OF_RANGE,
+                ..
+            })
+        ));
+        // The deposits are not minted again.
+        assert_eq!(read_state(|s| s.available_utxos.len()), 1);
+        assert_eq!(
+            update_balance(&canisters),
+            Err(UpdateBalanceError::NoNewUtxos)
+        );
+        assert_eq!(canisters.transfers.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn concurrent_calls_are_rejected() {
+        init_state();
+        let canisters = FakeCanisters::default();
+        canisters.add_utxo(&deposit_address(), 1, 100);
+        let _guard = balance_update_guard(caller().0).unwrap();
+        assert_eq!(
+            update_balance(&canisters),
+            Err(UpdateBalanceError::AlreadyProcessing)
+        );
+        assert!(canisters.transfers.lock().unwrap().is_empty());
+    }
+}
@@ -29,7 +29,7 @@ impl std::fmt::Display for Network {
 }
 
 /// A reference to a transaction output.
-#[derive(CandidType, Clone, Debug, Deserialize, PartialEq, Eq, Hash)]
+#[derive(CandidType, Clone, Debug, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct OutPoint {
     #[serde(with = "serde_bytes")]
     pub txid: Vec<u8>,
@@ -37,7 +37,7 @@ pub struct OutPoint {
 }
 
 /// An unspent transaction output.
-#[derive(CandidType, Debug, Deserialize, PartialEq, Clone, Hash, Eq)]
+#[derive(CandidType, Debug, Deserialize, PartialEq, Clone, Hash, Eq, PartialOrd, Ord)]
 pub struct Utxo {
     pub outpoint: OutPoint,
     pub value: Satoshi,
@@ -153,6 +153,10 @@ pub enum SendTransactionError {
     MalformedTransaction,
     /// Enqueueing a request failed due to full queue to the Bitcoin adapter.
     QueueFull,
+    /// The transaction spends an output that doesn't exist or is already spent.
+    InputNotFound,
+    /// The outputs of the transaction are worth more than its inputs.
+    InsufficientInputValue,
 }
 
 impl std::fmt::Display for SendTransactionError {
@@ -167,6 +171,164 @@ impl std::fmt::Display for SendTransactionError {
                     "Request can not be enqueued because the queue has reached its capacity. Please retry later."
                 )
             }
+            Self::InputNotFound => {
+                write!(
+                    f,
+                    "The transaction spends an output that doesn't exist or is already spent."
+                )
+            }
+            Self::InsufficientInputValue => {
+                write!(
+                    f,
+                    "The outputs of the transaction are worth more than its inputs."
+                )
+            }
+        }
+    }
+}
+
+/// A request for getting the transactions that pay to or spend from a given
+/// address, newest first.
+#[derive(CandidType, Debug, Deserialize, PartialEq)]
+pub struct GetAddressHistoryRequest {
+    pub address: Address,
+    pub network: Network,
+    pub page: Option<Page>,
+}
+
+/// A transaction that pays to or spends from an address.
+#[derive(CandidType, Debug, Deserialize, PartialEq, Clone, Hash, Eq)]
+pub struct AddressTransaction {
+    #[serde(with = "serde_bytes")]
+    pub txid: Vec<u8>,
+    pub height: Height,
+}
+
+/// The response returned for a request to get the transaction history of a
+/// given address.
+#[derive(CandidType, Debug, Deserialize, PartialEq, Clone)]
+pub struct GetAddressHistoryResponse {
+    pub transactions: Vec<AddressTransaction>,
+    pub tip_block_hash: BlockHash,
+    pub tip_height: Height,
+    pub next_page: Option<Page>,
+}
+
+/// Errors when processing a `get_address_history` request.
+#[derive(CandidType, Debug, Deserialize, PartialEq, Clone)]
+pub enum GetAddressHistoryError {
+    MalformedAddress,
+    /// The address index is not maintained by this canister.
+    AddressHistoryDisabled,
+    UnknownTipBlockHash {
+        tip_block_hash: BlockHash,
+    },
+    MalformedPage {
+        err: String,
+    },
+}
+
+impl std::fmt::Display for GetAddressHistoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MalformedAddress => {
+                write!(f, "Malformed address.")
+            }
+            Self::AddressHistoryDisabled => {
+                write!(f, "The address history index is disabled.")
+            }
+            Self::UnknownTipBlockHash { tip_block_hash } => {
+                write!(
+                    f,
+                    "The provided tip block hash {:?} is unknown.",
+                    tip_block_hash
+                )
+            }
+            Self::MalformedPage { err } => {
+                write!(f, "The provided page is malformed {}", err)
+            }
+        }
+    }
+}
+
+/// A request for getting the headers of the main chain blocks in the height
+/// range `[start_height, end_height]`. If `end_height` is not set, the range
+/// ends at the tip of the main chain.
+#[derive(CandidType, Debug, Deserialize, PartialEq)]
+pub struct GetBlockHeadersRequest {
+    pub start_height: Height,
+    pub end_height: Option<Height>,
+    pub network: Network,
+}
+
+/// The response returned for a request to get block headers.
+///
+/// Each header is in the 80-byte Bitcoin wire format. If the requested range
+/// does not fit in a single response, only a prefix of it is returned and the
+/// remaining headers can be requested starting at
+/// `start_height + block_headers.len()`.
+#[derive(CandidType, Debug, Deserialize, PartialEq, Clone)]
+pub struct GetBlockHeadersResponse {
+    pub tip_height: Height,
+    pub block_headers: Vec<ByteBuf>,
+}
+
+/// Errors when processing a `get_block_headers` request.
+#[derive(CandidType, Debug, Deserialize, PartialEq, Clone)]
+pub enum GetBlockHeadersError {
+    StartHeightDoesNotExist {
+        requested: Height,
+        chain_height: Height,
+    },
+    EndHeightDoesNotExist {
+        requested: Height,
+        chain_height: Height,
+    },
+    StartHeightLargerThanEndHeight {
+        start_height: Height,
+        end_height: Height,
+    },
+    /// Headers are only stored for blocks that became stable after header
+    /// storage was introduced.
+    HeaderNotAvailable { height: Height },
+}
+
+impl std::fmt::Display for GetBlockHeadersError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::StartHeightDoesNotExist {
+                requested,
+                chain_height,
+            } => {
+                write!(
+                    f,
+                    "The requested start_height is larger than the height of the chain. Given: {}, height of chain: {}",
+                    requested, chain_height
+                )
+            }
+            Self::EndHeightDoesNotExist {
+                requested,
+                chain_height,
+            } => {
+                write!(
+                    f,
+                    "The requested end_height is larger than the height of the chain. Given: {}, height of chain: {}",
+                    requested, chain_height
+                )
+            }
+            Self::StartHeightLargerThanEndHeight {
+                start_height,
+                end_height,
+            } => {
+                write!(
+                    f,
+                    "The requested start_height is larger than the requested end_height. start_height: {}, end_height: {}",
+                    start_height, end_height
+                )
+            }
+            Self::HeaderNotAvailable { height } => {
+                write!(f, "The header at height {} is not available.", height)
+            }
         }
     }
 }
@@ -69,7 +69,7 @@ impl Cli {
 #[cfg(test)]
 pub mod test {
     use super::*;
-    use crate::IncomingSource;
+    use crate::{EgressPolicy, IncomingSource};
     use std::io::Write;
     use std::path::PathBuf;
     use std::str::FromStr;
@@ -307,7 +307,12 @@ pub mod test {
                 "enabled_tags": [],
                 "block_on_overflow": true
             },
-            "socks_proxy": "socks5://notaproxy.com:1080"        
+            "socks_proxy": "socks5://notaproxy.com:1080",
+            "egress_policy": {
+                "deny_private_ips": false,
+                "allowed_hosts": ["example.com"],
+                "denied_hosts": ["internal.example.com"]
+            }
         }       
         "#;
 
@@ -334,6 +339,11 @@ pub mod test {
                 ..Default::default()
             },
             socks_proxy: Some("socks5://notaproxy.com:1080".to_string()),
+            egress_policy: EgressPolicy {
+                deny_private_ips: false,
+                allowed_hosts: vec!["example.com".to_string()],
+                denied_hosts: vec!["internal.example.com".to_string()],
+            },
         };
         assert_eq!(config, expected_config);
     }
@@ -33,6 +33,35 @@ pub struct Config {
     /// Testing environment shared socks proxy address: socks5://socks5.testnet.dfinity.network:1080
     /// Proxy url is validated and needs to have scheme, host and port specified. I.e socks5://socksproxy.com:1080.
     pub socks_proxy: Option<String>,
+    /// Restricts which destinations canisters are allowed to reach through the adapter.
+    pub egress_policy: EgressPolicy,
+}
+
+/// Egress policy applied to every outgoing request before a connection is made.
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct EgressPolicy {
+    /// Reject destinations that are, or resolve to, loopback, link-local, private or
+    /// otherwise non-globally routable addresses. The addresses obtained from DNS are
+    /// pinned for the connection so a second lookup cannot redirect it.
+    /// When a socks proxy is configured only IP literals can be checked, resolving
+    /// the destination is then up to the proxy.
+    pub deny_private_ips: bool,
+    /// If non-empty, only these hosts and their subdomains may be contacted.
+    pub allowed_hosts: Vec<String>,
+    /// Hosts and their subdomains that may never be contacted. Takes precedence
+    /// over `allowed_hosts`.
+    pub denied_hosts: Vec<String>,
+}
+
+impl Default for EgressPolicy {
+    fn default() -> Self {
+        EgressPolicy {
+            deny_private_ips: true,
+            allowed_hosts: Vec::new(),
+            denied_hosts: Vec::new(),
+        }
+    }
 }
 
 impl Default for Config {
@@ -43,6 +72,7 @@ impl Default for Config {
             incoming_source: IncomingSource::default(),
             logger: LoggerConfig::default(),
             socks_proxy: None,
+            egress_policy: EgressPolicy::default(),
         }
     }
 }
@@ -0,0 +1,314 @@
+use crate::config::EgressPolicy;
+use http::{uri::Scheme, Uri};
+use hyper::client::connect::dns::Name;
+use std::{
+    error::Error,
+    future::Future,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use thiserror::Error;
+use tokio::net::lookup_host;
+use tower::Service;
+
+/// A request was rejected because its destination is not permitted by the
+/// adapter's [`EgressPolicy`].
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+#[error("Egress policy violation: {0}")]
+pub struct EgressPolicyViolation(String);
+
+impl EgressPolicy {
+    /// Checks the parts of the policy that can be decided from the URL alone:
+    /// the scheme, the host allow/deny lists and hosts given as IP literals.
+    pub(crate) fn check_uri(
+        &self,
+        uri: &Uri,
+        enforce_https: bool,
+    ) -> Result<(), EgressPolicyViolation> {
+        if enforce_https && uri.scheme() != Some(&Scheme::HTTPS) {
+            return Err(EgressPolicyViolation(
+                "Url need to specify https scheme".to_string(),
+            ));
+        }
+
+        let host = match uri.host() {
+            Some(host) => host.to_ascii_lowercase(),
+            None => return Ok(()),
+        };
+        if self
+            .denied_hosts
+            .iter()
+            .any(|entry| host_matches(&host, entry))
+        {
+            return Err(EgressPolicyViolation(format!("Host {} is denied", host)));
+        }
+        if !self.allowed_hosts.is_empty()
+            && !self
+                .allowed_hosts
+                .iter()
+                .any(|entry| host_matches(&host, entry))
+        {
+            return Err(EgressPolicyViolation(format!(
+                "Host {} is not allowed",
+                host
+            )));
+        }
+
+        // IPv6 literals are enclosed in brackets. Hosts that are IP literals
+        // never reach the resolver and have to be checked here.
+        match host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+        {
+            Ok(ip) => self.check_ip(ip),
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// Checks whether the adapter may connect to `ip`.
+    pub(crate) fn check_ip(&self, ip: IpAddr) -> Result<(), EgressPolicyViolation> {
+        if self.deny_private_ips && !is_globally_routable(ip) {
+            return Err(EgressPolicyViolation(format!(
+                "Address {} is not publicly routable",
+                ip
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Returns true if `host` is `entry` or one of its subdomains.
+fn host_matches(host: &str, entry: &str) -> bool {
+    let entry = entry.to_ascii_lowercase();
+    host == entry || host.ends_with(&format!(".{}", entry))
+}
+
+fn is_globally_routable(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_globally_routable_v4(ip),
+        IpAddr::V6(ip) => is_globally_routable_v6(ip),
+    }
+}
+
+fn is_globally_routable_v4(ip: Ipv4Addr) -> bool {
+    let octets = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // 0.0.0.0/8 "this network".
+        || octets[0] == 0
+        // 100.64.0.0/10 shared address space (carrier-grade NAT).
+        || (octets[0] == 100 && (octets[1] & 0b1100_0000) == 0b0100_0000)
+        // 192.0.0.0/24 IETF protocol assignments.
+        || (octets[0] == 192 && octets[1] == 0 && octets[2] == 0)
+        // 198.18.0.0/15 benchmarking.
+        || (octets[0] == 198 && (octets[1] & 0xfe) == 18)
+        // 240.0.0.0/4 reserved.
+        || octets[0] >= 240)
+}
+
+fn is_globally_routable_v6(ip: Ipv6Addr) -> bool {
+    if let Some(embedded) = embedded_ipv4(ip) {
+        return is_globally_routable_v4(embedded);
+    }
+    let segments = ip.segments();
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // 64:ff9b:1::/48 local-use IPv4/IPv6 translation.
+        || (segments[0] == 0x0064 && segments[1] == 0xff9b && segments[2] == 0x0001)
+        // fc00::/7 unique local.
+        || (segments[0] & 0xfe00) == 0xfc00
+        // fe80::/10 link-local.
+        || (segments[0] & 0xffc0) == 0xfe80
+        // 2001:db8::/32 documentation.
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8))
+}
+
+/// Returns the IPv4 address that `ip` reaches if it embeds one, so that it can
+/// be checked as IPv4.
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let segments = ip.segments();
+    let from_segments = |high: u16, low: u16| {
+        let [a, b] = high.to_be_bytes();
+        let [c, d] = low.to_be_bytes();
+        Ipv4Addr::new(a, b, c, d)
+    };
+    match segments {
+        // ::ffff:a.b.c.d IPv4-mapped.
+        [0, 0, 0, 0, 0, 0xffff, high, low]
+        // ::a.b.c.d IPv4-compatible (deprecated). This includes `::` and
+        // `::1`, which map to addresses in 0.0.0.0/8.
+        | [0, 0, 0, 0, 0, 0, high, low]
+        // 64:ff9b::/96 well-known NAT64 prefix.
+        | [0x0064, 0xff9b, 0, 0, 0, 0, high, low] => Some(from_segments(high, low)),
+        // 2002::/16 6to4.
+        [0x2002, high, low, ..] => Some(from_segments(high, low)),
+        _ => None,
+    }
+}
+
+/// Returns the policy violation that caused `err`, if any.
+pub(crate) fn find_egress_policy_violation<'a>(
+    err: &'a (dyn Error + 'static),
+) -> Option<&'a EgressPolicyViolation> {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if let Some(violation) = err.downcast_ref::<EgressPolicyViolation>() {
+            return Some(violation);
+        }
+        source = err.source();
+    }
+    None
+}
+
+/// DNS resolver for the http connector that drops all resolved addresses the
+/// policy does not permit. The connector only dials the returned addresses,
+/// which pins the connection to addresses that passed the check.
+#[derive(Clone)]
+pub(crate) struct EgressPolicyResolver {
+    policy: Arc<EgressPolicy>,
+}
+
+impl EgressPolicyResolver {
+    pub(crate) fn new(policy: EgressPolicy) -> Self {
+        Self {
+            policy: Arc::new(policy),
+        }
+    }
+}
+
+impl Service<Name> for EgressPolicyResolver {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = Box<dyn Error + Send + Sync>;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let policy = self.policy.clone();
+        Box::pin(async move {
+            let permitted: Vec<SocketAddr> = lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| policy.check_ip(addr.ip()).is_ok())
+                .collect();
+            if permitted.is_empty() {
+                return Err(Box::new(EgressPolicyViolation(format!(
+                    "Host {} does not resolve to any publicly routable address",
+                    name
+                ))) as Self::Error);
+            }
+            Ok(permitted.into_iter())
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn check(policy: &EgressPolicy, uri: &str) -> Result<(), EgressPolicyViolation> {
+        policy.check_uri(&uri.parse::<Uri>().unwrap(), true)
+    }
+
+    #[test]
+    fn test_private_addresses_are_denied() {
+        let policy = EgressPolicy::default();
+        for uri in [
+            "https://127.0.0.1/",
+            "https://10.1.2.3/",
+            "https://172.16.0.1/",
+            "https://192.168.1.1/",
+            "https://169.254.169.254/latest/meta-data",
+            "https://100.64.0.1/",
+            "https://0.0.0.0/",
+            "https://[::1]/",
+            "https://[fe80::1]/",
+            "https://[fd00::1]/",
+            "https://[::ffff:127.0.0.1]/",
+            "https://192.0.0.1/",
+            "https://[::127.0.0.1]/",
+            "https://[::a9fe:a9fe]/",
+            "https://[64:ff9b::10.0.0.1]/",
+            "https://[64:ff9b:1::1]/",
+            "https://[2002:c0a8:101::1]/",
+        ] {
+            assert!(check(&policy, uri).is_err(), "{} should be denied", uri);
+        }
+        for uri in [
+            "https://8.8.8.8/",
+            "https://[2606:4700:4700::1111]/",
+            "https://[::ffff:8.8.8.8]/",
+            "https://[64:ff9b::8.8.8.8]/",
+            "https://[2002:808:808::1]/",
+            "https://example.com/",
+        ] {
+            assert_eq!(check(&policy, uri), Ok(()), "{} should be allowed", uri);
+        }
+    }
+
+    #[test]
+    fn test_private_addresses_allowed_if_configured() {
+        let policy = EgressPolicy {
+            deny_private_ips: false,
+            ..Default::default()
+        };
+        assert_eq!(check(&policy, "https://127.0.0.1/"), Ok(()));
+        assert_eq!(check(&policy, "https://[::1]/"), Ok(()));
+    }
+
+    #[test]
+    fn test_https_is_required() {
+        let policy = EgressPolicy::default();
+        assert!(check(&policy, "http://example.com/").is_err());
+        assert_eq!(
+            policy.check_uri(&"http://example.com/".parse::<Uri>().unwrap(), false),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_host_lists() {
+        let policy = EgressPolicy {
+            allowed_hosts: vec!["example.com".to_string()],
+            denied_hosts: vec!["internal.example.com".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(check(&policy, "https://example.com/"), Ok(()));
+        assert_eq!(check(&policy, "https://api.EXAMPLE.com/"), Ok(()));
+        assert!(check(&policy, "https://notexample.com/").is_err());
+        assert!(check(&policy, "https://other.org/").is_err());
+        assert!(check(&policy, "https://internal.example.com/").is_err());
+        assert!(check(&policy, "https://a.internal.example.com/").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolver_drops_private_addresses() {
+        let mut resolver = EgressPolicyResolver::new(EgressPolicy::default());
+        let err = resolver
+            .call("localhost".parse::<Name>().unwrap())
+            .await
+            .unwrap_err();
+        assert!(find_egress_policy_violation(err.as_ref()).is_some());
+
+        let mut resolver = EgressPolicyResolver::new(EgressPolicy {
+            deny_private_ips: false,
+            ..Default::default()
+        });
+        let addrs: Vec<_> = resolver
+            .call("localhost".parse::<Name>().unwrap())
+            .await
+            .unwrap()
+            .collect();
+        assert!(addrs.iter().all(|addr| addr.ip().is_loopback()));
+    }
+}
@@ -8,10 +8,14 @@ mod rpc_server;
 /// This module contains the basic configuration struct used to start up an adapter instance.
 mod config;
 
+/// Enforces the egress policy on outgoing requests.
+mod egress;
+
 pub use cli::Cli;
-pub use config::{Config, IncomingSource};
+pub use config::{Config, EgressPolicy, IncomingSource};
 pub use rpc_server::CanisterHttp;
 
+use egress::EgressPolicyResolver;
 use futures::Future;
 use futures_core::stream::Stream;
 use hyper::{
@@ -43,12 +47,15 @@ impl AdapterServer {
     // client. This complicates unnecessary the production code. For now we decide
     // to keep the 'enforce_https' flag.
     pub fn new(config: Config, logger: ReplicaLogger, enforce_https: bool) -> Self {
-        let mut http_connector = HttpConnector::new();
-        http_connector.enforce_http(false);
-        http_connector
-            .set_connect_timeout(Some(Duration::from_secs(config.http_connect_timeout_secs)));
         match &config.socks_proxy {
             Some(url) => {
+                // The connector only connects to the proxy which resolves the destination itself.
+                // Therefore the egress policy can only be checked on the requested URL.
+                let mut http_connector = HttpConnector::new();
+                http_connector.enforce_http(false);
+                http_connector.set_connect_timeout(Some(Duration::from_secs(
+                    config.http_connect_timeout_secs,
+                )));
                 // The proxy connnector requires a the URL scheme to be specified. I.e socks5://
                 // Config validity check ensures that url includes scheme, host and port.
                 // Therefore the parse 'Uri' will be in the correct format. I.e socks5://somehost.com:1080
@@ -60,13 +67,21 @@ impl AdapterServer {
                 let mut https_connector = HttpsConnector::new_with_connector(proxy_connector);
                 https_connector.https_only(enforce_https);
                 let https_client = Client::builder().build::<_, hyper::Body>(https_connector);
-                Self::new_with_client(https_client, config, logger)
+                Self::new_with_client(https_client, config, logger, enforce_https)
             }
             None => {
+                // Resolved addresses are filtered by the egress policy before connecting.
+                let mut http_connector = HttpConnector::new_with_resolver(
+                    EgressPolicyResolver::new(config.egress_policy.clone()),
+                );
+                http_connector.enforce_http(false);
+                http_connector.set_connect_timeout(Some(Duration::from_secs(
+                    config.http_connect_timeout_secs,
+                )));
                 let mut https_connector = HttpsConnector::new_with_connector(http_connector);
                 https_connector.https_only(enforce_https);
                 let https_client = Client::builder().build::<_, hyper::Body>(https_connector);
-                Self::new_with_client(https_client, config, logger)
+                Self::new_with_client(https_client, config, logger, enforce_https)
             }
         }
     }
@@ -75,8 +90,9 @@ impl AdapterServer {
         client: Client<C>,
         config: Config,
         logger: ReplicaLogger,
+        enforce_https: bool,
     ) -> Self {
-        let canister_http = CanisterHttp::new(client, logger);
+        let canister_http = CanisterHttp::new(client, config.egress_policy, enforce_https, logger);
         Self(
             Server::builder()
                 .timeout(Duration::from_secs(config.http_request_timeout_secs))
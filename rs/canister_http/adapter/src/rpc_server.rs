@@ -1,28 +1,48 @@
+use crate::{config::EgressPolicy, egress::find_egress_policy_violation};
 use byte_unit::Byte;
-use core::convert::TryFrom;
-use http::Uri;
+use http::{uri::PathAndQuery, StatusCode, Uri};
 use hyper::{
     client::connect::Connect,
-    header::{HeaderMap, ToStrError},
+    header::{
+        HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, COOKIE,
+        LOCATION, PROXY_AUTHORIZATION,
+    },
     Body, Client, Method,
 };
-use ic_async_utils::receive_body_without_timeout;
+use ic_async_utils::{receive_body_without_timeout, BodyReceiveError};
 use ic_canister_http_service::{
     canister_http_service_server::CanisterHttpService, CanisterHttpSendRequest,
     CanisterHttpSendResponse, HttpHeader, HttpMethod,
 };
 use ic_logger::{debug, ReplicaLogger};
+use ic_types::messages::MAX_INTER_CANISTER_PAYLOAD_IN_BYTES;
 use tonic::{Request, Response, Status};
 
+/// Hard upper bound on the number of redirects the adapter follows for a single
+/// request, regardless of the limit requested by the replica.
+const MAX_REDIRECTS: u32 = 10;
+
 /// implements RPC
 pub struct CanisterHttp<C: Clone + Connect + Send + Sync + 'static> {
     client: Client<C>,
+    egress_policy: EgressPolicy,
+    enforce_https: bool,
     logger: ReplicaLogger,
 }
 
 impl<C: Clone + Connect + Send + Sync + 'static> CanisterHttp<C> {
-    pub fn new(client: Client<C>, logger: ReplicaLogger) -> Self {
-        Self { client, logger }
+    pub fn new(
+        client: Client<C>,
+        egress_policy: EgressPolicy,
+        enforce_https: bool,
+        logger: ReplicaLogger,
+    ) -> Self {
+        Self {
+            client,
+            egress_policy,
+            enforce_https,
+            logger,
+        }
     }
 }
 
@@ -34,7 +54,7 @@ impl<C: Clone + Connect + Send + Sync + 'static> CanisterHttpService for Caniste
     ) -> Result<Response<CanisterHttpSendResponse>, Status> {
         let req = request.into_inner();
 
-        let uri = req.url.parse::<Uri>().map_err(|err| {
+        let mut uri = req.url.parse::<Uri>().map_err(|err| {
             debug!(self.logger, "Failed to parse URL: {}", err);
             Status::new(
                 tonic::Code::InvalidArgument,
@@ -42,7 +62,7 @@ impl<C: Clone + Connect + Send + Sync + 'static> CanisterHttpService for Caniste
             )
         })?;
 
-        let method = HttpMethod::from_i32(req.method)
+        let mut method = HttpMethod::from_i32(req.method)
             .ok_or_else(|| {
                 Status::new(
                     tonic::Code::InvalidArgument,
@@ -53,68 +73,134 @@ impl<C: Clone + Connect + Send + Sync + 'static> CanisterHttpService for Caniste
                 HttpMethod::Get => Ok(Method::GET),
                 HttpMethod::Post => Ok(Method::POST),
                 HttpMethod::Head => Ok(Method::HEAD),
-                _ => Err(Status::new(
+                HttpMethod::Put => Ok(Method::PUT),
+                HttpMethod::Patch => Ok(Method::PATCH),
+                HttpMethod::Delete => Ok(Method::DELETE),
+                HttpMethod::Unspecified => Err(Status::new(
                     tonic::Code::InvalidArgument,
                     format!("Unsupported HTTP method {:?}", method),
                 )),
             })?;
 
-        // Build Http Request.
-        let mut http_req = hyper::Request::new(Body::from(req.body));
-        let headers: HeaderMap =
-            HeaderMap::try_from(&req.headers.into_iter().map(|h| (h.name, h.value)).collect())
-                .map_err(|err| {
+        // Header values are passed on as raw bytes and do not need to be valid UTF-8.
+        let mut headers = HeaderMap::new();
+        for header in req.headers {
+            let name =
+                HeaderName::from_bytes(header.name.as_bytes()).map_err(|err| err.to_string());
+            let value = HeaderValue::from_bytes(&header.value).map_err(|err| err.to_string());
+            match (name, value) {
+                (Ok(name), Ok(value)) => {
+                    headers.append(name, value);
+                }
+                (Err(err), _) | (_, Err(err)) => {
                     debug!(self.logger, "Failed to parse headers: {}", err);
-                    Status::new(
+                    return Err(Status::new(
                         tonic::Code::InvalidArgument,
                         format!("Failed to parse headers: {}", err),
-                    )
+                    ));
+                }
+            }
+        }
+        let mut body = req.body;
+
+        let max_redirects = req.max_redirects.min(MAX_REDIRECTS);
+        let mut redirects = 0;
+        let http_resp = loop {
+            // Every hop is checked since a redirect can point anywhere.
+            self.egress_policy
+                .check_uri(&uri, self.enforce_https)
+                .map_err(|err| {
+                    debug!(self.logger, "Rejected request: {}", err);
+                    Status::new(tonic::Code::PermissionDenied, err.to_string())
                 })?;
-        *http_req.headers_mut() = headers;
-        *http_req.method_mut() = method;
-        *http_req.uri_mut() = uri;
 
-        let http_resp = self.client.request(http_req).await.map_err(|err| {
-            debug!(self.logger, "Failed to connect: {}", err);
-            Status::new(
-                tonic::Code::Unavailable,
-                format!("Failed to connect: {}", err),
-            )
-        })?;
+            // Build Http Request.
+            let mut http_req = hyper::Request::new(Body::from(body.clone()));
+            *http_req.headers_mut() = headers.clone();
+            *http_req.method_mut() = method.clone();
+            *http_req.uri_mut() = uri.clone();
+
+            let http_resp = self.client.request(http_req).await.map_err(|err| {
+                // The resolver rejects hosts that only resolve to addresses denied by the egress policy.
+                if let Some(violation) = find_egress_policy_violation(&err) {
+                    debug!(self.logger, "Rejected request: {}", violation);
+                    return Status::new(tonic::Code::PermissionDenied, violation.to_string());
+                }
+                debug!(self.logger, "Failed to connect: {}", err);
+                Status::new(
+                    tonic::Code::Unavailable,
+                    format!("Failed to connect: {}", err),
+                )
+            })?;
+
+            // Once the redirect limit is reached the redirect response itself is returned.
+            if redirects == max_redirects || !is_followed_redirect(http_resp.status()) {
+                break http_resp;
+            }
+            let location = match http_resp.headers().get(LOCATION) {
+                Some(location) => location,
+                None => break http_resp,
+            };
+            let next_uri = resolve_redirect(&uri, location).map_err(|err| {
+                debug!(self.logger, "Failed to follow redirect: {}", err);
+                Status::new(
+                    tonic::Code::Unavailable,
+                    format!("Failed to follow redirect: {}", err),
+                )
+            })?;
+
+            // Mirror what browsers do: 303 and a redirected POST continue as a GET without body.
+            let status = http_resp.status();
+            if (status == StatusCode::SEE_OTHER && method != Method::HEAD)
+                || (method == Method::POST
+                    && (status == StatusCode::MOVED_PERMANENTLY || status == StatusCode::FOUND))
+            {
+                method = Method::GET;
+                body = Vec::new();
+                headers.remove(CONTENT_TYPE);
+                headers.remove(CONTENT_LENGTH);
+            }
+            // Do not leak credentials to a different origin.
+            if next_uri.scheme() != uri.scheme() || next_uri.authority() != uri.authority() {
+                headers.remove(AUTHORIZATION);
+                headers.remove(COOKIE);
+                headers.remove(PROXY_AUTHORIZATION);
+            }
+            uri = next_uri;
+            redirects += 1;
+        };
 
         let status = http_resp.status().as_u16() as u32;
 
-        // Parse received headers.
+        // Header values are returned as raw bytes.
         let headers = http_resp
             .headers()
             .iter()
-            .map(|(k, v)| {
-                Ok(HttpHeader {
-                    name: k.to_string(),
-                    value: v.to_str()?.to_string(),
-                })
+            .map(|(k, v)| HttpHeader {
+                name: k.to_string(),
+                value: v.as_bytes().to_vec(),
             })
-            .collect::<Result<Vec<_>, ToStrError>>()
-            .map_err(|err| {
-                debug!(self.logger, "Failed to parse headers: {}", err);
-                Status::new(
-                    tonic::Code::Unavailable,
-                    format!("Failed to parse headers: {}", err),
-                )
-            })?;
+            .collect();
 
+        // The body is streamed and the download is aborted as soon as the limit is
+        // exceeded, or immediately if the 'Content-length' header already exceeds it.
         // We don't need a timeout here because there is a global timeout on the entire request.
+        let max_response_size_bytes = req
+            .max_response_size_bytes
+            .min(MAX_INTER_CANISTER_PAYLOAD_IN_BYTES.get());
         let body_bytes = receive_body_without_timeout(
             http_resp.into_body(),
-            Byte::from(req.max_response_size_bytes),
+            Byte::from(max_response_size_bytes),
         )
         .await
         .map_err(|err| {
             debug!(self.logger, "Failed to fetch body: {}", err);
-            Status::new(
-                tonic::Code::Unavailable,
-                format!("Failed to fetch body: {}", err),
-            )
+            // Exceeding the limit is deterministic, retrying the request will not help.
+            let code = match err {
+                BodyReceiveError::TooLarge(_) => tonic::Code::OutOfRange,
+                _ => tonic::Code::Unavailable,
+            };
+            Status::new(code, format!("Failed to fetch body: {}", err))
         })?;
 
         Ok(Response::new(CanisterHttpSendResponse {
@@ -124,3 +210,81 @@ impl<C: Clone + Connect + Send + Sync + 'static> CanisterHttpService for Caniste
         }))
     }
 }
+
+/// Returns true for the redirect status codes the adapter follows.
+fn is_followed_redirect(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::MOVED_PERMANENTLY
+            | StatusCode::FOUND
+            | StatusCode::SEE_OTHER
+            | StatusCode::TEMPORARY_REDIRECT
+            | StatusCode::PERMANENT_REDIRECT
+    )
+}
+
+/// Resolves the `Location` header of a redirect response relative to the
+/// uri of the request that was redirected.
+fn resolve_redirect(base: &Uri, location: &HeaderValue) -> Result<Uri, String> {
+    let location = location
+        .to_str()
+        .map_err(|err| format!("Invalid Location header: {}", err))?;
+    let scheme = base.scheme_str().unwrap_or("https");
+    let resolved = if location.starts_with("//") {
+        // Scheme relative reference.
+        format!("{}:{}", scheme, location)
+    } else if location.starts_with('/') {
+        // Absolute path reference.
+        let authority = base
+            .authority()
+            .ok_or_else(|| "Redirected request has no authority".to_string())?;
+        format!("{}://{}{}", scheme, authority, location)
+    } else if location
+        .parse::<Uri>()
+        .map_or(false, |uri| uri.scheme().is_some())
+    {
+        location.to_string()
+    } else {
+        // Relative path reference, resolved against the directory of the base path.
+        let authority = base
+            .authority()
+            .ok_or_else(|| "Redirected request has no authority".to_string())?;
+        let base_path = base.path_and_query().map_or("/", PathAndQuery::path);
+        let directory = &base_path[..base_path.rfind('/').map_or(0, |i| i + 1)];
+        format!("{}://{}{}{}", scheme, authority, directory, location)
+    };
+    resolved
+        .parse::<Uri>()
+        .map_err(|err| format!("Invalid Location header: {}", err))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn resolve(base: &str, location: &str) -> Result<Uri, String> {
+        resolve_redirect(
+            &base.parse::<Uri>().unwrap(),
+            &HeaderValue::from_str(location).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_resolve_redirect() {
+        let base = "https://example.com/a/b?q=1";
+        assert_eq!(
+            resolve(base, "https://other.org/c").unwrap(),
+            "https://other.org/c"
+        );
+        assert_eq!(
+            resolve(base, "//other.org/c").unwrap(),
+            "https://other.org/c"
+        );
+        assert_eq!(
+            resolve(base, "/c?x=2").unwrap(),
+            "https://example.com/c?x=2"
+        );
+        assert_eq!(resolve(base, "c").unwrap(), "https://example.com/a/c");
+        assert!(resolve(base, "https://exa mple.com").is_err());
+    }
+}
@@ -2,7 +2,7 @@ use fast_socks5::server::{Config as SocksConfig, Socks5Server};
 use futures::{StreamExt, TryFutureExt};
 use http::StatusCode;
 use hyper::{client::HttpConnector, Client};
-use ic_canister_http_adapter::{AdapterServer, Config};
+use ic_canister_http_adapter::{AdapterServer, Config, EgressPolicy};
 use ic_canister_http_service::{
     canister_http_service_client::CanisterHttpServiceClient, CanisterHttpSendRequest, HttpMethod,
 };
@@ -32,9 +32,7 @@ async fn test_canister_http_server() {
         .mount(&mock_server)
         .await;
 
-    let server_config = Config {
-        ..Default::default()
-    };
+    let server_config = test_config();
     // Spawn grpc server and return client.
     let mut client = spawn_grpc_server(server_config);
 
@@ -44,6 +42,7 @@ async fn test_canister_http_server() {
         method: HttpMethod::Get as i32,
         body: "hello".to_string().as_bytes().to_vec(),
         max_response_size_bytes: 512,
+        max_redirects: 0,
     });
     let response = client.canister_http_send(request).await;
     assert!(response.is_ok());
@@ -64,9 +63,7 @@ async fn test_canister_http_server_post() {
         .mount(&mock_server)
         .await;
 
-    let server_config = Config {
-        ..Default::default()
-    };
+    let server_config = test_config();
     // Spawn grpc server and return client.
     let mut client = spawn_grpc_server(server_config);
 
@@ -76,6 +73,7 @@ async fn test_canister_http_server_post() {
         method: HttpMethod::Post as i32,
         body: "hello".to_string().as_bytes().to_vec(),
         max_response_size_bytes: 512,
+        max_redirects: 0,
     });
 
     let response = client.canister_http_send(request).await;
@@ -97,9 +95,7 @@ async fn test_canister_http_server_head() {
         .mount(&mock_server)
         .await;
 
-    let server_config = Config {
-        ..Default::default()
-    };
+    let server_config = test_config();
     // Spawn grpc server and return client.
     let mut client = spawn_grpc_server(server_config);
 
@@ -109,6 +105,7 @@ async fn test_canister_http_server_head() {
         method: HttpMethod::Head as i32,
         body: "hello".to_string().as_bytes().to_vec(),
         max_response_size_bytes: 512,
+        max_redirects: 0,
     });
 
     let response = client.canister_http_send(request).await;
@@ -122,9 +119,7 @@ async fn test_canister_http_server_head() {
 #[tokio::test]
 async fn test_response_limit_exceeded() {
     // Check if response with higher than allowed response limit is rejected.
-    let server_config = Config {
-        ..Default::default()
-    };
+    let server_config = test_config();
     let listener = std::net::TcpListener::bind("127.0.0.1:20004").unwrap();
     let mock_server = MockServer::builder().listener(listener).start().await;
     let response_limit: u64 = 512;
@@ -143,12 +138,13 @@ async fn test_response_limit_exceeded() {
         method: HttpMethod::Get as i32,
         body: "hello".to_string().as_bytes().to_vec(),
         max_response_size_bytes: response_limit,
+        max_redirects: 0,
     });
     let response = client.canister_http_send(request).await;
     assert!(response.is_err());
     assert_eq!(
         response.as_ref().unwrap_err().code(),
-        tonic::Code::Unavailable
+        tonic::Code::OutOfRange
     );
     assert!(response
         .unwrap_err()
@@ -156,12 +152,39 @@ async fn test_response_limit_exceeded() {
         .contains(&"header exceeds http body size".to_string()));
 }
 
+#[tokio::test]
+async fn test_response_limit_is_capped() {
+    // Check that the requested response limit cannot exceed the adapter's hard limit.
+    let server_config = test_config();
+    let mock_server = MockServer::start().await;
+    let payload: Vec<u8> = vec![0u8; 2 * 1024 * 1024 + 1];
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(payload))
+        .mount(&mock_server)
+        .await;
+
+    let mut client = spawn_grpc_server(server_config);
+    let request = tonic::Request::new(CanisterHttpSendRequest {
+        url: format!("{}/hello", &mock_server.uri()),
+        headers: Vec::new(),
+        method: HttpMethod::Get as i32,
+        body: "hello".to_string().as_bytes().to_vec(),
+        max_response_size_bytes: u64::MAX,
+        max_redirects: 0,
+    });
+    let response = client.canister_http_send(request).await;
+    assert!(response.is_err());
+    assert_eq!(
+        response.as_ref().unwrap_err().code(),
+        tonic::Code::OutOfRange
+    );
+}
+
 #[tokio::test]
 async fn test_within_response_limit() {
     // Check that everything works fine if response limit is specified.
-    let server_config = Config {
-        ..Default::default()
-    };
+    let server_config = test_config();
     let mock_server = MockServer::start().await;
     let response_limit: u64 = 512;
     let payload: Vec<u8> = vec![0u8; (response_limit - 1) as usize];
@@ -178,6 +201,7 @@ async fn test_within_response_limit() {
         method: HttpMethod::Get as i32,
         body: "hello".to_string().as_bytes().to_vec(),
         max_response_size_bytes: response_limit,
+        max_redirects: 0,
     });
     let response = client.canister_http_send(request).await;
     assert!(response.is_ok());
@@ -203,7 +227,7 @@ async fn test_request_timeout() {
         // Set connect timeout to high value to make sure request timeout is triggered.
         http_connect_timeout_secs: 6000,
         http_request_timeout_secs: 3,
-        ..Default::default()
+        ..test_config()
     };
     let mut client = spawn_grpc_server(server_config);
     let request = tonic::Request::new(CanisterHttpSendRequest {
@@ -212,6 +236,7 @@ async fn test_request_timeout() {
         method: HttpMethod::Get as i32,
         body: "hello".to_string().as_bytes().to_vec(),
         max_response_size_bytes: 512,
+        max_redirects: 0,
     });
     let response = client.canister_http_send(request).await;
     assert!(response.is_err());
@@ -232,7 +257,7 @@ async fn test_connect_timeout() {
         http_connect_timeout_secs: 1,
         // Set to high value to make sure connnect timeout kicks in.
         http_request_timeout_secs: 6000,
-        ..Default::default()
+        ..test_config()
     };
     let mut client = spawn_grpc_server(server_config);
 
@@ -243,6 +268,7 @@ async fn test_connect_timeout() {
         method: HttpMethod::Head as i32,
         body: "hello".to_string().as_bytes().to_vec(),
         max_response_size_bytes: 64,
+        max_redirects: 0,
     });
     let response = client.canister_http_send(request).await;
     assert!(response.is_err());
@@ -271,9 +297,7 @@ async fn test_nonascii_header() {
             .await;
     }
 
-    let server_config = Config {
-        ..Default::default()
-    };
+    let server_config = test_config();
     let mut client = spawn_grpc_server(server_config);
 
     let request = tonic::Request::new(CanisterHttpSendRequest {
@@ -282,17 +306,126 @@ async fn test_nonascii_header() {
         method: HttpMethod::Get as i32,
         body: "hello".to_string().as_bytes().to_vec(),
         max_response_size_bytes: 512,
+        max_redirects: 0,
     });
     let response = client.canister_http_send(request).await;
-    assert!(response.is_err());
+    // Header values are passed on as bytes and don't need to be valid UTF-8.
+    let header = response
+        .unwrap()
+        .into_inner()
+        .headers
+        .into_iter()
+        .find(|h| h.name == "invalid-ascii-value")
+        .unwrap();
+    assert_eq!(header.value, "x√ab c".as_bytes().to_vec());
 }
 
 #[tokio::test]
-async fn test_missing_protocol() {
-    // Test that missing http protocol specification returns error.
+async fn test_put_patch_delete() {
+    let mock_server = MockServer::start().await;
+    for http_method in ["PUT", "PATCH", "DELETE"] {
+        Mock::given(method(http_method))
+            .and(path("/hello"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(http_method))
+            .mount(&mock_server)
+            .await;
+    }
+
+    let mut client = spawn_grpc_server(test_config());
+    for (http_method, expected_body) in [
+        (HttpMethod::Put, "PUT"),
+        (HttpMethod::Patch, "PATCH"),
+        (HttpMethod::Delete, "DELETE"),
+    ] {
+        let request = tonic::Request::new(CanisterHttpSendRequest {
+            url: format!("{}/hello", &mock_server.uri()),
+            headers: Vec::new(),
+            method: http_method as i32,
+            body: "hello".to_string().as_bytes().to_vec(),
+            max_response_size_bytes: 512,
+            max_redirects: 0,
+        });
+        let response = client.canister_http_send(request).await.unwrap();
+        assert_eq!(response.into_inner().content, expected_body.as_bytes());
+    }
+}
+
+#[tokio::test]
+async fn test_redirects() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/first"))
+        .respond_with(ResponseTemplate::new(302).insert_header("Location", "/second"))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/second"))
+        .respond_with(ResponseTemplate::new(301).insert_header("Location", "hello"))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let mut client = spawn_grpc_server(test_config());
+    // Redirects are only followed up to the requested limit.
+    for (max_redirects, expected_status) in [(0, 302), (1, 301), (2, 200), (5, 200)] {
+        let request = tonic::Request::new(CanisterHttpSendRequest {
+            url: format!("{}/first", &mock_server.uri()),
+            headers: Vec::new(),
+            method: HttpMethod::Get as i32,
+            body: Vec::new(),
+            max_response_size_bytes: 512,
+            max_redirects,
+        });
+        let response = client.canister_http_send(request).await.unwrap();
+        assert_eq!(response.into_inner().status, expected_status);
+    }
+}
+
+#[tokio::test]
+async fn test_redirect_to_denied_host_rejected() {
+    // Check that the egress policy is applied to redirect targets.
     let server_config = Config {
+        egress_policy: EgressPolicy {
+            deny_private_ips: false,
+            denied_hosts: vec!["localhost".to_string()],
+            ..Default::default()
+        },
         ..Default::default()
     };
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/redirect"))
+        .respond_with(ResponseTemplate::new(307).insert_header(
+            "Location",
+            format!("http://localhost:{}/hello", mock_server.address().port()).as_str(),
+        ))
+        .mount(&mock_server)
+        .await;
+
+    let mut client = spawn_grpc_server(server_config);
+    let request = tonic::Request::new(CanisterHttpSendRequest {
+        url: format!("{}/redirect", &mock_server.uri()),
+        headers: Vec::new(),
+        method: HttpMethod::Get as i32,
+        body: Vec::new(),
+        max_response_size_bytes: 512,
+        max_redirects: 1,
+    });
+    let response = client.canister_http_send(request).await;
+    assert_eq!(
+        response.as_ref().unwrap_err().code(),
+        tonic::Code::PermissionDenied
+    );
+}
+
+#[tokio::test]
+async fn test_missing_protocol() {
+    // Test that missing http protocol specification returns error.
+    let server_config = test_config();
     let mut client = spawn_grpc_server(server_config);
 
     let request = tonic::Request::new(CanisterHttpSendRequest {
@@ -301,6 +434,7 @@ async fn test_missing_protocol() {
         method: HttpMethod::Get as i32,
         body: "hello".to_string().as_bytes().to_vec(),
         max_response_size_bytes: 512,
+        max_redirects: 0,
     });
     let response = client.canister_http_send(request).await;
     assert!(response.is_err());
@@ -312,7 +446,7 @@ async fn test_bad_socks() {
     let socks_url = "socks5://doesnotexist:8088".to_string();
     let server_config = Config {
         socks_proxy: Some(socks_url),
-        ..Default::default()
+        ..test_config()
     };
     // Spawn grpc server and return client.
     let mut client = spawn_grpc_server(server_config);
@@ -323,6 +457,7 @@ async fn test_bad_socks() {
         method: HttpMethod::Get as i32,
         body: "hello".to_string().as_bytes().to_vec(),
         max_response_size_bytes: 512,
+        max_redirects: 0,
     });
 
     let response = client.canister_http_send(request).await;
@@ -334,7 +469,7 @@ async fn test_socks() {
     // Spawn socks proxy on localhost and connect thourgh poxy.
     let server_config = Config {
         socks_proxy: Some("socks5://127.0.0.1:8088".to_string()),
-        ..Default::default()
+        ..test_config()
     };
     // Spawn grpc server and return client.
     let mut client = spawn_grpc_server(server_config);
@@ -357,6 +492,7 @@ async fn test_socks() {
         method: HttpMethod::Get as i32,
         body: "hello".to_string().as_bytes().to_vec(),
         max_response_size_bytes: 512,
+        max_redirects: 0,
     });
     let response = client.canister_http_send(request).await;
     assert!(response.is_ok());
@@ -406,6 +542,120 @@ async fn test_socks_fallback() {
     assert!(response.status() == 200);
 }
 
+#[tokio::test]
+async fn test_private_ip_literal_rejected() {
+    // Check that the egress policy rejects requests to private addresses.
+    let server_config = Config {
+        ..Default::default()
+    };
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let mut client = spawn_grpc_server(server_config);
+    let request = tonic::Request::new(CanisterHttpSendRequest {
+        url: format!("{}/hello", &mock_server.uri()),
+        headers: Vec::new(),
+        method: HttpMethod::Get as i32,
+        body: "hello".to_string().as_bytes().to_vec(),
+        max_response_size_bytes: 512,
+        max_redirects: 0,
+    });
+    let response = client.canister_http_send(request).await;
+    assert_eq!(
+        response.as_ref().unwrap_err().code(),
+        tonic::Code::PermissionDenied
+    );
+    assert!(response
+        .unwrap_err()
+        .message()
+        .contains(&"not publicly routable".to_string()));
+}
+
+#[tokio::test]
+async fn test_private_ip_after_dns_resolution_rejected() {
+    // Check that hosts resolving to private addresses are rejected before connecting.
+    let server_config = Config {
+        ..Default::default()
+    };
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let mut client = spawn_grpc_server(server_config);
+    let request = tonic::Request::new(CanisterHttpSendRequest {
+        url: format!("http://localhost:{}/hello", mock_server.address().port()),
+        headers: Vec::new(),
+        method: HttpMethod::Get as i32,
+        body: "hello".to_string().as_bytes().to_vec(),
+        max_response_size_bytes: 512,
+        max_redirects: 0,
+    });
+    let response = client.canister_http_send(request).await;
+    assert_eq!(
+        response.as_ref().unwrap_err().code(),
+        tonic::Code::PermissionDenied
+    );
+    assert!(response
+        .unwrap_err()
+        .message()
+        .contains(&"does not resolve to any publicly routable address".to_string()));
+}
+
+#[tokio::test]
+async fn test_denied_host_rejected() {
+    // Check that hosts on the deny list are rejected even if private addresses are allowed.
+    let server_config = Config {
+        egress_policy: EgressPolicy {
+            deny_private_ips: false,
+            denied_hosts: vec!["localhost".to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&mock_server)
+        .await;
+
+    let mut client = spawn_grpc_server(server_config);
+    let request = tonic::Request::new(CanisterHttpSendRequest {
+        url: format!("http://localhost:{}/hello", mock_server.address().port()),
+        headers: Vec::new(),
+        method: HttpMethod::Get as i32,
+        body: "hello".to_string().as_bytes().to_vec(),
+        max_response_size_bytes: 512,
+        max_redirects: 0,
+    });
+    let response = client.canister_http_send(request).await;
+    assert_eq!(
+        response.as_ref().unwrap_err().code(),
+        tonic::Code::PermissionDenied
+    );
+
+    // The same server is reachable through a host that is not denied.
+    let request = tonic::Request::new(CanisterHttpSendRequest {
+        url: format!("{}/hello", &mock_server.uri()),
+        headers: Vec::new(),
+        method: HttpMethod::Get as i32,
+        body: "hello".to_string().as_bytes().to_vec(),
+        max_response_size_bytes: 512,
+        max_redirects: 0,
+    });
+    let response = client.canister_http_send(request).await;
+    assert!(response.is_ok());
+}
+
 async fn spawn_socks5_server(listen_addr: String) {
     let mut listener = Socks5Server::bind(listen_addr).await.unwrap();
     let socks_config = SocksConfig::default();
@@ -428,6 +678,17 @@ async fn spawn_socks5_server(listen_addr: String) {
     }
 }
 
+// Config that allows connecting to the local mock servers.
+fn test_config() -> Config {
+    Config {
+        egress_policy: EgressPolicy {
+            deny_private_ips: false,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
 // Spawn grpc server and return canister http client
 fn spawn_grpc_server(config: Config) -> CanisterHttpServiceClient<Channel> {
     let uuid = Uuid::new_v4();
@@ -125,6 +125,7 @@ impl NonBlockingChannel<CanisterHttpRequest> for CanisterHttpAdapterClientImpl {
                         body: request_body,
                         http_method: request_http_method,
                         max_response_bytes: request_max_response_bytes,
+                        max_redirects: request_max_redirects,
                         transform_method_name: request_transform_method,
                         ..
                     },
@@ -138,13 +139,17 @@ impl NonBlockingChannel<CanisterHttpRequest> for CanisterHttpAdapterClientImpl {
                         CanisterHttpMethod::GET => HttpMethod::Get.into(),
                         CanisterHttpMethod::POST => HttpMethod::Post.into(),
                         CanisterHttpMethod::HEAD => HttpMethod::Head.into(),
+                        CanisterHttpMethod::PUT => HttpMethod::Put.into(),
+                        CanisterHttpMethod::PATCH => HttpMethod::Patch.into(),
+                        CanisterHttpMethod::DELETE => HttpMethod::Delete.into(),
                     },
                     max_response_size_bytes: request_max_response_bytes.unwrap_or(CANISTER_HTTP_ADAPTER_MAX_RESPONSE_SIZE).get(),
+                    max_redirects: request_max_redirects,
                     headers: request_headers
                         .into_iter()
                         .map(|h| HttpHeader {
                             name: h.name,
-                            value: h.value,
+                            value: h.value.into_bytes(),
                         })
                         .collect(),
                     body: request_body.unwrap_or_default(),
@@ -170,13 +175,7 @@ impl NonBlockingChannel<CanisterHttpRequest> for CanisterHttpAdapterClientImpl {
                         }
                         None => Encode!(&ic_ic00_types::CanisterHttpResponsePayload {
                             status: adapter_response.status as u64,
-                            headers: adapter_response
-                                .headers
-                                .into_iter()
-                                .map(|HttpHeader { name, value }| {
-                                    ic_ic00_types::HttpHeader { name, value }
-                                })
-                                .collect(),
+                            headers: to_ic00_http_headers(adapter_response.headers),
                             body: adapter_response.content,
                         })
                         .map_err(|encode_error| {
@@ -251,11 +250,7 @@ async fn transform_adapter_response(
     // CanisterHttpResponsePayload type is part of the public API and need to encode the adapter response into the public API candid.
     let method_payload = Encode!(&ic_ic00_types::CanisterHttpResponsePayload {
         status: adapter_response.status as u64,
-        headers: adapter_response
-            .headers
-            .into_iter()
-            .map(|HttpHeader { name, value }| { ic_ic00_types::HttpHeader { name, value } })
-            .collect(),
+        headers: to_ic00_http_headers(adapter_response.headers),
         body: adapter_response.content,
     })
     .map_err(|encode_error| {
@@ -293,11 +288,43 @@ async fn transform_adapter_response(
     }
 }
 
+/// The adapter passes header values on as raw bytes while the public `http_header`
+/// candid type uses text. Header values that are not valid UTF-8 are percent-encoded
+/// so that the response can still be delivered without losing their bytes.
+fn to_ic00_http_headers(headers: Vec<HttpHeader>) -> Vec<ic_ic00_types::HttpHeader> {
+    headers
+        .into_iter()
+        .map(|HttpHeader { name, value }| {
+            let value = match String::from_utf8(value) {
+                Ok(value) => value,
+                Err(err) => percent_encode(err.as_bytes()),
+            };
+            ic_ic00_types::HttpHeader { name, value }
+        })
+        .collect()
+}
+
+/// Percent-encodes `%` and every byte that is not printable ASCII.
+fn percent_encode(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&b| {
+            if b != b'%' && (b.is_ascii_graphic() || b == b' ') {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect()
+}
+
 fn grpc_status_code_to_reject(code: Code) -> RejectCode {
     match code {
         // TODO: Is unavailable really transient
         Code::Unavailable => RejectCode::SysTransient,
         Code::InvalidArgument => RejectCode::SysFatal,
+        // Destination not permitted by the adapter's egress policy.
+        Code::PermissionDenied => RejectCode::DestinationInvalid,
         _ => RejectCode::SysFatal,
     }
 }
@@ -429,6 +456,7 @@ mod tests {
                     .build(),
                 url: "http://notused.com".to_string(),
                 max_response_bytes: None,
+                max_redirects: 0,
                 headers: Vec::new(),
                 body: None,
                 http_method: CanisterHttpMethod::GET,
@@ -469,12 +497,7 @@ mod tests {
             content: CanisterHttpResponseContent::Success(
                 Encode!(&ic_ic00_types::CanisterHttpResponsePayload {
                     status,
-                    headers: headers
-                        .into_iter()
-                        .map(|HttpHeader { name, value }| {
-                            ic_ic00_types::HttpHeader { name, value }
-                        })
-                        .collect(),
+                    headers: to_ic00_http_headers(headers),
                     body,
                 })
                 .unwrap(),
@@ -496,7 +519,7 @@ mod tests {
             .to_vec();
         let adapter_headers = vec![HttpHeader {
             name: "Content-Type".to_string(),
-            value: "text/html; charset=utf-8".to_string(),
+            value: b"text/html; charset=utf-8".to_vec(),
         }];
 
         // Adapter mock setup
@@ -553,6 +576,72 @@ mod tests {
         assert_eq!(client.try_receive(), Err(TryReceiveError::Empty));
     }
 
+    /// Test case where the adapter response has a header value that is not valid UTF-8.
+    /// The value is percent-encoded and the response is delivered.
+    #[tokio::test]
+    async fn test_client_non_utf8_header_value() {
+        // Adapter mock setup
+        let mock_grpc_channel = setup_adapter_mock(Ok(CanisterHttpSendResponse {
+            status: 200,
+            headers: vec![HttpHeader {
+                name: "X-Binary".to_string(),
+                value: vec![0xff, 0xfe],
+            }],
+            content: Vec::new(),
+        }))
+        .await;
+        // Asynchronous query handler mock setup. Does not serve any purpose in this test case.
+        let mock_anon_svc =
+            SingleResponseAnonymousQueryService::new(AnonymousQueryResponse::Rejected {
+                reject_code: RejectCode::SysFatal,
+                reject_message: "dsf".to_string(),
+            });
+        let base_service = BoxCloneService::new(ServiceBuilder::new().service(mock_anon_svc));
+        let svc = ServiceBuilder::new()
+            .concurrency_limit(1)
+            .service(base_service);
+
+        let mut client = CanisterHttpAdapterClientImpl::new(
+            tokio::runtime::Handle::current(),
+            mock_grpc_channel,
+            svc,
+            100,
+        );
+
+        assert_eq!(
+            client.send(build_mock_canister_http_request(420, mock_time(), None)),
+            Ok(())
+        );
+        // Yield to execute the request on the client.
+        loop {
+            match client.try_receive() {
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+                Ok(r) => {
+                    assert_eq!(
+                        r.content,
+                        CanisterHttpResponseContent::Success(
+                            Encode!(&ic_ic00_types::CanisterHttpResponsePayload {
+                                status: 200,
+                                headers: vec![ic_ic00_types::HttpHeader {
+                                    name: "X-Binary".to_string(),
+                                    value: "%FF%FE".to_string(),
+                                }],
+                                body: Vec::new(),
+                            })
+                            .unwrap()
+                        )
+                    );
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_percent_encode_header_value() {
+        assert_eq!(percent_encode(b"a b%\xff\n"), "a b%25%FF%0A");
+    }
+
     /// Test case where adapter encounters an UNAVAILABLE  error in executing the http request.
     /// This should be reported as a transient error.
     #[tokio::test]
@@ -715,6 +804,59 @@ mod tests {
         assert_eq!(client.try_receive(), Err(TryReceiveError::Empty));
     }
 
+    /// Test case where the adapter rejects the request because of its egress policy.
+    /// This should be reported as an invalid destination.
+    #[tokio::test]
+    async fn test_client_egress_policy_violation_adapter_response() {
+        // Adapter mock setup. Return a PERMISSION_DENIED error.
+        let mock_grpc_channel = setup_adapter_mock(Err((
+            Code::PermissionDenied,
+            "Egress policy violation".to_string(),
+        )))
+        .await;
+        // Asynchronous query handler mock setup. Does not serve any purpose in this test case.
+        let mock_anon_svc =
+            SingleResponseAnonymousQueryService::new(AnonymousQueryResponse::Rejected {
+                reject_code: RejectCode::SysFatal,
+                reject_message: "dsf".to_string(),
+            });
+        let base_service = BoxCloneService::new(ServiceBuilder::new().service(mock_anon_svc));
+        let svc = ServiceBuilder::new()
+            .concurrency_limit(1)
+            .service(base_service);
+
+        let mut client = CanisterHttpAdapterClientImpl::new(
+            tokio::runtime::Handle::current(),
+            mock_grpc_channel,
+            svc,
+            100,
+        );
+
+        assert_eq!(
+            client.send(build_mock_canister_http_request(420, mock_time(), None)),
+            Ok(())
+        );
+        // Yield to execute the request on the client.
+        loop {
+            match client.try_receive() {
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+                Ok(r) => {
+                    assert_eq!(
+                        r,
+                        build_mock_canister_http_response_reject(
+                            420,
+                            mock_time(),
+                            RejectCode::DestinationInvalid,
+                            "Egress policy violation".to_string()
+                        )
+                    );
+                    break;
+                }
+            }
+        }
+        assert_eq!(client.try_receive(), Err(TryReceiveError::Empty));
+    }
+
     /// Test client with a specified transform function.
     #[tokio::test]
     async fn test_client_no_op_transform() {
@@ -728,7 +870,7 @@ mod tests {
             .to_vec();
         let adapter_headers = vec![HttpHeader {
             name: "Content-Type".to_string(),
-            value: "text/html; charset=utf-8".to_string(),
+            value: b"text/html; charset=utf-8".to_vec(),
         }];
 
         // Adapter mock setup.
@@ -745,13 +887,7 @@ mod tests {
                     arg: Blob(
                         Encode!(&ic_ic00_types::CanisterHttpResponsePayload {
                             status: 200_u64,
-                            headers: adapter_headers
-                                .clone()
-                                .into_iter()
-                                .map(|HttpHeader { name, value }| {
-                                    ic_ic00_types::HttpHeader { name, value }
-                                })
-                                .collect(),
+                            headers: to_ic00_http_headers(adapter_headers.clone()),
                             body: adapter_body.clone(),
                         })
                         .unwrap(),
@@ -815,7 +951,7 @@ mod tests {
             .to_vec();
         let adapter_headers = vec![HttpHeader {
             name: "Content-Type".to_string(),
-            value: "text/html; charset=utf-8".to_string(),
+            value: b"text/html; charset=utf-8".to_vec(),
         }];
 
         // Adapter mock setup. Not relevant for client response in this test case.
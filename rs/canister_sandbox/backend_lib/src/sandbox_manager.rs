@@ -137,6 +137,7 @@ impl Execution {
                 allocated_bytes,
                 allocated_message_bytes,
                 instance_stats,
+                instruction_profile,
             },
             deltas,
             instance_or_system_api,
@@ -200,6 +201,7 @@ impl Execution {
                     allocated_message_bytes,
                     num_instructions_left,
                     instance_stats,
+                    instruction_profile,
                 };
                 self.sandbox_manager.controller.execution_finished(
                     protocol::ctlsvc::ExecutionFinishedRequest {
@@ -221,6 +223,7 @@ impl Execution {
                     allocated_bytes,
                     allocated_message_bytes,
                     instance_stats,
+                    instruction_profile,
                 };
 
                 self.sandbox_manager.controller.execution_finished(
@@ -206,6 +206,8 @@ mod tests {
             0,
             ic00_aliases,
             SMALL_APP_SUBNET_MAX_SIZE,
+            BTreeSet::from([user_test_id(0).get()]),
+            0,
         )
     }
 
@@ -517,6 +517,7 @@ impl WasmExecutor for SandboxedExecutionController {
                                 accessed_pages: 0,
                                 dirty_pages: 0,
                             },
+                            instruction_profile: vec![],
                         },
                         None,
                     ),
@@ -59,6 +59,12 @@ pub struct BackupConfig {
     pub retention_time_secs: u64,
     /// Time interval between purges.
     pub purging_interval_secs: u64,
+    /// The maximum number of bytes the backup may occupy on disk. If the
+    /// backup grows beyond this budget, the oldest artifacts are purged
+    /// regardless of their age. If this field is not specified, artifacts are
+    /// only purged by age.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size_bytes: Option<u64>,
 }
 
 /// The configuration for the ingress and consensus artifact pools, both the
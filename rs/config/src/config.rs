@@ -17,6 +17,7 @@ use crate::{
     message_routing::Config as MessageRoutingConfig,
     metrics::Config as MetricsConfig,
     nns_registry_replicator::Config as NnsRegistryReplicatorConfig,
+    p2p::Config as P2PConfig,
     registration::Config as RegistrationConfig,
     registry_client::Config as RegistryClientConfig,
     state_manager::Config as StateManagerConfig,
@@ -52,6 +53,7 @@ pub struct Config {
     pub registration: RegistrationConfig,
     pub nns_registry_replicator: NnsRegistryReplicatorConfig,
     pub adapters_config: AdaptersConfig,
+    pub p2p: P2PConfig,
 }
 
 /// Mirrors the Config struct except that fields are made optional. This is
@@ -76,6 +78,7 @@ pub struct ConfigOptional {
     pub registration: Option<RegistrationConfig>,
     pub nns_registry_replicator: Option<NnsRegistryReplicatorConfig>,
     pub adapters_config: Option<AdaptersConfig>,
+    pub p2p: Option<P2PConfig>,
 }
 
 impl Config {
@@ -105,6 +108,7 @@ impl Config {
             registration: RegistrationConfig::default(),
             nns_registry_replicator: NnsRegistryReplicatorConfig::default(),
             adapters_config: AdaptersConfig::default(),
+            p2p: P2PConfig::default(),
         }
     }
 
@@ -163,6 +167,7 @@ impl Config {
                 .nns_registry_replicator
                 .unwrap_or(default.nns_registry_replicator),
             adapters_config: cfg.adapters_config.unwrap_or(default.adapters_config),
+            p2p: cfg.p2p.unwrap_or(default.p2p),
         })
     }
 
@@ -83,6 +83,9 @@ pub const SAMPLE_CONFIG: &str = r#"
         send_queue_size: 1024,
         // The field will be removed after the new transport implementation is rolled out.
         legacy_flow_tag: 1,
+        // Whether to send a hello after the TLS handshake, so that peers compress the
+        // payloads they send. Only enable once every node of the subnet accepts hellos.
+        send_hello: false,
     },
     // ============================================
     // Configuration of registry client
@@ -109,7 +112,10 @@ pub const SAMPLE_CONFIG: &str = r#"
             // How long the backup artifact stay on the disk before they get purged.
             retention_time_secs: 3600,
             // How often we purge.
-            purging_interval_secs: 3600
+            purging_interval_secs: 3600,
+            // The maximum size of the backup on disk, in bytes. Optional: when
+            // exceeded, the oldest artifacts are purged regardless of their age.
+            max_size_bytes: 10737418240
         }
     },
     // ============================================
@@ -369,6 +375,15 @@ pub const SAMPLE_CONFIG: &str = r#"
         // The canister http adapter socket file is: /ic-os/guestos/rootfs/systemd/system/ic-canister-http-adapter.socket
         canister_http_uds_path: "/run/ic-node/canister-http-adapter/socket",
     },
+    // ====================================
+    // Configuration of P2P.
+    // ====================================
+    p2p: {
+        // The strategy to choose the peer to download each chunk of an artifact from:
+        // "first_available", "round_robin", "latency_aware" or "bandwidth_weighted".
+        // The default is "first_available".
+        download_policy: "first_available",
+    },
 }
 "#;
 
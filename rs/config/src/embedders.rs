@@ -27,10 +27,29 @@ pub(crate) const DEFAULT_COST_TO_COMPILE_WASM_INSTRUCTION: NumInstructions =
 /// The number of rayon threads used by wasmtime to compile wasm binaries
 const DEFAULT_WASMTIME_RAYON_COMPILATION_THREADS: usize = 10;
 
+/// The table of static instruction costs used to meter canister code.
+///
+/// The costs of an existing version must never change, since that would
+/// change the number of instructions executed by canisters that are already
+/// deployed. New costs are introduced in a new version instead.
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub enum InstructionCostSchedule {
+    /// Every instruction costs one instruction, except for the instructions
+    /// that only delimit blocks, which are free.
+    V1,
+    /// Like `V1`, but bulk memory and SIMD instructions are charged according
+    /// to their fixed overhead.
+    V2,
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
 pub struct FeatureFlags {
     pub rate_limiting_of_debug_prints: FlagStatus,
     pub module_sharing: FlagStatus,
+    /// Injects per-function instruction counters into canister modules. Only
+    /// meant for test environments, since the counters become part of the
+    /// canister state.
+    pub instruction_profiling: FlagStatus,
 }
 
 impl Default for FeatureFlags {
@@ -38,6 +57,7 @@ impl Default for FeatureFlags {
         Self {
             rate_limiting_of_debug_prints: FlagStatus::Enabled,
             module_sharing: FlagStatus::Enabled,
+            instruction_profiling: FlagStatus::Disabled,
         }
     }
 }
@@ -63,6 +83,9 @@ pub struct Config {
     /// this many instructions.
     pub cost_to_compile_wasm_instruction: NumInstructions,
 
+    /// The table of static instruction costs used to meter canister code.
+    pub instruction_cost_schedule: InstructionCostSchedule,
+
     /// The number of rayon threads used by wasmtime to compile wasm binaries
     pub num_rayon_compilation_threads: usize,
 
@@ -80,6 +103,7 @@ impl Config {
             max_custom_sections: MAX_CUSTOM_SECTIONS,
             max_custom_sections_size: MAX_CUSTOM_SECTIONS_SIZE,
             cost_to_compile_wasm_instruction: DEFAULT_COST_TO_COMPILE_WASM_INSTRUCTION,
+            instruction_cost_schedule: InstructionCostSchedule::V1,
             num_rayon_compilation_threads: DEFAULT_WASMTIME_RAYON_COMPILATION_THREADS,
             feature_flags: FeatureFlags::default(),
         }
@@ -1,5 +1,5 @@
 use crate::{
-    embedders::{self, QUERY_EXECUTION_THREADS},
+    embedders::{self, InstructionCostSchedule, QUERY_EXECUTION_THREADS},
     flag_status::FlagStatus,
     subnet_config::MAX_INSTRUCTIONS_PER_MESSAGE,
 };
@@ -8,6 +8,7 @@ use ic_types::{
     Cycles, NumBytes, NumInstructions, MAX_STABLE_MEMORY_IN_BYTES, MAX_WASM_MEMORY_IN_BYTES,
 };
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
 const GB: u64 = 1024 * 1024 * 1024;
 
@@ -51,6 +52,11 @@ const INGRESS_HISTORY_MEMORY_CAPACITY: NumBytes = NumBytes::new(10 * GB);
 /// memory can succeed.
 pub(crate) const SUBNET_HEAP_DELTA_CAPACITY: NumBytes = NumBytes::new(150 * GB);
 
+/// The upper limit on the disk space used by the persistent compilation cache.
+/// A serialized module is typically a few MiB, so this is enough to hold the
+/// compiled code of several thousand canisters.
+const COMPILATION_CACHE_CAPACITY: NumBytes = NumBytes::new(10 * GB);
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
 #[serde(default)]
 pub struct Config {
@@ -118,6 +124,24 @@ pub struct Config {
     /// Compiling a single WASM instruction should cost as much as executing
     /// this many instructions.
     pub cost_to_compile_wasm_instruction: NumInstructions,
+
+    /// The table of static instruction costs used to meter canister code.
+    /// Switching to another version changes the number of instructions
+    /// executed by existing canisters.
+    pub instruction_cost_schedule: InstructionCostSchedule,
+
+    /// The directory of the persistent compilation cache. If `None`, compiled
+    /// modules are only cached in memory and are lost on restart.
+    pub compilation_cache_dir: Option<PathBuf>,
+
+    /// The maximum disk space used by the persistent compilation cache.
+    pub compilation_cache_capacity: NumBytes,
+
+    /// If this flag is enabled, then canister modules are instrumented with
+    /// per-function instruction counters. The counters are part of the
+    /// canister state, so the flag must not be toggled on a subnet with
+    /// existing canisters.
+    pub instruction_profiling: FlagStatus,
 }
 
 impl Default for Config {
@@ -147,6 +171,10 @@ impl Default for Config {
             deterministic_time_slicing: FlagStatus::Disabled,
             module_sharing: FlagStatus::Enabled,
             cost_to_compile_wasm_instruction: embedders::DEFAULT_COST_TO_COMPILE_WASM_INSTRUCTION,
+            instruction_cost_schedule: InstructionCostSchedule::V1,
+            compilation_cache_dir: None,
+            compilation_cache_capacity: COMPILATION_CACHE_CAPACITY,
+            instruction_profiling: FlagStatus::Disabled,
         }
     }
 }
@@ -19,6 +19,7 @@ pub mod logger;
 pub mod message_routing;
 pub mod metrics;
 pub mod nns_registry_replicator;
+pub mod p2p;
 pub mod registration;
 pub mod registry_client;
 pub mod state_manager;
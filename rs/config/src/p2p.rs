@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+/// The strategy P2P uses to choose from which of the peers that advertised an
+/// artifact each chunk of the artifact is downloaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadPolicyType {
+    /// Any advertiser with free capacity downloads the chunk as soon as it
+    /// is asked for work.
+    FirstAvailable,
+    /// The chunks are assigned to the advertisers in turn.
+    RoundRobin,
+    /// The chunks are downloaded from the advertisers that delivered chunks
+    /// the fastest so far.
+    LatencyAware,
+    /// The chunks are downloaded from the advertisers with the highest
+    /// observed throughput, relative to the requests they already serve.
+    BandwidthWeighted,
+}
+
+impl Default for DownloadPolicyType {
+    fn default() -> Self {
+        Self::FirstAvailable
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+/// P2P replica config.
+pub struct Config {
+    pub download_policy: DownloadPolicyType,
+}
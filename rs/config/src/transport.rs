@@ -12,6 +12,13 @@ pub struct TransportConfig {
 
     /// This field is deprecated and will be deleted once NET-1086 is rolled out.
     pub legacy_flow_tag: u32,
+
+    /// Whether to send a hello right after the TLS handshake, so that peers
+    /// compress the payloads they send to this node. Hellos from peers are
+    /// always accepted, but older versions cannot parse them, so this must
+    /// only be enabled once every node of the subnet accepts hellos.
+    #[serde(default)]
+    pub send_hello: bool,
 }
 
 impl Default for TransportConfig {
@@ -21,6 +28,7 @@ impl Default for TransportConfig {
             node_ip: String::default(),
             listening_port: u16::default(),
             legacy_flow_tag: u32::default(),
+            send_hello: false,
         }
     }
 }
@@ -525,6 +525,7 @@ pub mod test {
                     request: ic_test_utilities::types::messages::RequestBuilder::new().build(),
                     url: "".to_string(),
                     max_response_bytes: None,
+                    max_redirects: 0,
                     headers: vec![],
                     body: None,
                     http_method: CanisterHttpMethod::GET,
@@ -671,6 +672,7 @@ pub mod test {
                     request: ic_test_utilities::types::messages::RequestBuilder::new().build(),
                     url: "".to_string(),
                     max_response_bytes: None,
+                    max_redirects: 0,
                     headers: vec![],
                     body: None,
                     http_method: CanisterHttpMethod::GET,
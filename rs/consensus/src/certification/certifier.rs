@@ -6,7 +6,8 @@ use ic_interfaces::{
         CertificationPool, Certifier, CertifierGossip, ChangeAction, ChangeSet, Verifier,
         VerifierError,
     },
-    consensus_pool::ConsensusPoolCache,
+    consensus_pool::{ConsensusPoolCache, ConsensusTimingsRecorder},
+    time_source::TimeSource,
     validation::ValidationError,
 };
 use ic_interfaces_state_manager::StateManager;
@@ -38,6 +39,8 @@ pub struct CertifierImpl {
     membership: Arc<Membership>,
     crypto: Arc<dyn CertificationCrypto>,
     state_manager: Arc<dyn StateManager<State = ReplicatedState>>,
+    timings_recorder: Arc<dyn ConsensusTimingsRecorder>,
+    time_source: Arc<dyn TimeSource>,
     metrics: CertifierMetrics,
     /// The highest height that has been purged. Used to avoid redudant purging.
     highest_purged_height: RefCell<Height>,
@@ -116,6 +119,8 @@ pub fn setup(
     membership: Arc<Membership>,
     crypto: Arc<dyn CertificationCrypto>,
     state_manager: Arc<dyn StateManager<State = ReplicatedState>>,
+    timings_recorder: Arc<dyn ConsensusTimingsRecorder>,
+    time_source: Arc<dyn TimeSource>,
     metrics_registry: MetricsRegistry,
     log: ReplicaLogger,
 ) -> (CertifierImpl, CertifierGossipImpl) {
@@ -125,6 +130,8 @@ pub fn setup(
             membership,
             crypto,
             state_manager.clone(),
+            timings_recorder,
+            time_source,
             metrics_registry,
             log,
         ),
@@ -158,6 +165,8 @@ impl Certifier for CertifierImpl {
                     Some(certification) => {
                         self.state_manager
                             .deliver_state_certification(certification);
+                        self.timings_recorder
+                            .record_certified(height, self.time_source.get_relative_time());
                         self.metrics.last_certified_height.set(height.get() as i64);
                         debug!(&self.log, "Delivered certification for height {}", height);
                         None
@@ -267,6 +276,8 @@ impl CertifierImpl {
         membership: Arc<Membership>,
         crypto: Arc<dyn CertificationCrypto>,
         state_manager: Arc<dyn StateManager<State = ReplicatedState>>,
+        timings_recorder: Arc<dyn ConsensusTimingsRecorder>,
+        time_source: Arc<dyn TimeSource>,
         metrics_registry: MetricsRegistry,
         log: ReplicaLogger,
     ) -> Self {
@@ -275,6 +286,8 @@ impl CertifierImpl {
             membership,
             crypto,
             state_manager,
+            timings_recorder,
+            time_source,
             metrics: CertifierMetrics {
                 shares_created: metrics_registry.int_counter(
                     "certification_shares_created",
@@ -682,6 +695,7 @@ mod tests {
                     membership,
                     crypto,
                     state_manager,
+                    time_source,
                     ..
                 } = dependencies(pool_config.clone(), 4);
                 pool.advance_round_normal_operation();
@@ -697,6 +711,8 @@ mod tests {
                     membership,
                     crypto,
                     state_manager.clone(),
+                    pool.get_timings_recorder(),
+                    time_source,
                     metrics_registry,
                     log,
                 );
@@ -746,6 +762,7 @@ mod tests {
                     membership,
                     crypto,
                     state_manager,
+                    time_source,
                     ..
                 } = dependencies(pool_config.clone(), 4);
 
@@ -762,6 +779,8 @@ mod tests {
                     membership,
                     crypto,
                     state_manager.clone(),
+                    pool.get_timings_recorder(),
+                    time_source,
                     metrics_registry,
                     log,
                 );
@@ -863,6 +882,7 @@ mod tests {
                 membership,
                 crypto,
                 state_manager,
+                time_source,
                 ..
             } = dependencies(pool_config.clone(), 6);
             // make the mock state manager return empty hashes for heights 3, 4 and 5
@@ -880,6 +900,8 @@ mod tests {
                     membership,
                     crypto,
                     state_manager.clone(),
+                    pool.get_timings_recorder(),
+                    time_source,
                     metrics_registry,
                     log,
                 );
@@ -944,6 +966,7 @@ mod tests {
                 membership,
                 crypto,
                 state_manager,
+                time_source,
                 ..
             } = dependencies(pool_config.clone(), 7);
             pool.insert_beacon_chain(&pool.make_next_beacon(), Height::from(10));
@@ -962,6 +985,8 @@ mod tests {
                     membership,
                     crypto,
                     state_manager.clone(),
+                    pool.get_timings_recorder(),
+                    time_source,
                     metrics_registry,
                     log,
                 );
@@ -1014,6 +1039,7 @@ mod tests {
                 membership,
                 crypto,
                 state_manager,
+                time_source,
                 ..
             } = dependencies(pool_config.clone(), 4);
             pool.advance_round_normal_operation_n(10);
@@ -1032,6 +1058,8 @@ mod tests {
                     membership,
                     crypto,
                     state_manager,
+                    pool.get_timings_recorder(),
+                    time_source,
                     metrics_registry,
                     log,
                 );
@@ -1084,6 +1112,7 @@ mod tests {
                     membership,
                     crypto,
                     state_manager,
+                    time_source,
                     ..
                 } = dependencies(pool_config.clone(), 1);
                 pool.advance_round_normal_operation_n(10);
@@ -1095,6 +1124,8 @@ mod tests {
                     membership,
                     crypto,
                     state_manager.clone(),
+                    pool.get_timings_recorder(),
+                    time_source,
                     metrics_registry.clone(),
                     log,
                 );
@@ -1151,6 +1182,7 @@ mod tests {
                     membership,
                     crypto,
                     state_manager,
+                    time_source,
                     ..
                 } = dependencies(pool_config.clone(), 1);
 
@@ -1159,6 +1191,8 @@ mod tests {
                     membership,
                     crypto,
                     state_manager,
+                    pool.get_timings_recorder(),
+                    time_source,
                     MetricsRegistry::new(),
                     log,
                 );
@@ -1199,6 +1233,7 @@ mod tests {
                 membership,
                 crypto,
                 state_manager,
+                time_source,
                 ..
             } = dependencies(pool_config.clone(), 4);
             // make the mock state manager return empty hashes for heights 4 and 5
@@ -1216,6 +1251,8 @@ mod tests {
                     membership,
                     crypto,
                     state_manager.clone(),
+                    pool.get_timings_recorder(),
+                    time_source,
                     metrics_registry,
                     log,
                 );
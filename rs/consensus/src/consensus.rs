@@ -53,7 +53,7 @@ use ic_config::consensus::ConsensusConfig;
 use ic_interfaces::{
     canister_http::CanisterHttpPayloadBuilder,
     consensus::{Consensus, ConsensusGossip},
-    consensus_pool::ConsensusPool,
+    consensus_pool::{ConsensusPool, ConsensusTimingsRecorder},
     dkg::DkgPool,
     ecdsa::EcdsaPool,
     ingress_manager::IngressSelector,
@@ -143,6 +143,7 @@ impl ConsensusImpl {
         dkg_key_manager: Arc<Mutex<DkgKeyManager>>,
         message_routing: Arc<dyn MessageRouting>,
         state_manager: Arc<dyn StateManager<State = ReplicatedState>>,
+        timings_recorder: Arc<dyn ConsensusTimingsRecorder>,
         time_source: Arc<dyn TimeSource>,
         stable_registry_version_age: Duration,
         malicious_flags: MaliciousFlags,
@@ -185,6 +186,7 @@ impl ConsensusImpl {
                 logger.clone(),
             ),
             finalizer: Finalizer::new(
+                Arc::clone(&time_source) as Arc<_>,
                 replica_config.clone(),
                 registry_client.clone(),
                 membership.clone(),
@@ -192,6 +194,7 @@ impl ConsensusImpl {
                 message_routing.clone(),
                 ingress_selector,
                 state_manager.clone(),
+                timings_recorder,
                 logger.clone(),
                 metrics_registry.clone(),
             ),
@@ -629,6 +632,7 @@ pub fn setup(
     dkg_key_manager: Arc<Mutex<DkgKeyManager>>,
     message_routing: Arc<dyn MessageRouting>,
     state_manager: Arc<dyn StateManager<State = ReplicatedState>>,
+    timings_recorder: Arc<dyn ConsensusTimingsRecorder>,
     time_source: Arc<dyn TimeSource>,
     malicious_flags: MaliciousFlags,
     metrics_registry: MetricsRegistry,
@@ -663,6 +667,7 @@ pub fn setup(
             dkg_key_manager,
             message_routing.clone(),
             state_manager,
+            timings_recorder,
             time_source,
             stable_registry_version_age,
             malicious_flags,
@@ -760,6 +765,7 @@ mod tests {
             ))),
             Arc::new(FakeMessageRouting::new()),
             state_manager,
+            pool.get_timings_recorder(),
             time_source.clone(),
             Duration::from_secs(0),
             MaliciousFlags::default(),
@@ -24,9 +24,11 @@ use crate::consensus::{
     prelude::*,
 };
 use ic_interfaces::{
+    consensus_pool::ConsensusTimingsRecorder,
     ingress_manager::IngressSelector,
     messaging::{MessageRouting, MessageRoutingError},
     registry::RegistryClient,
+    time_source::TimeSource,
 };
 use ic_interfaces_state_manager::StateManager;
 use ic_logger::{debug, trace, ReplicaLogger};
@@ -37,6 +39,7 @@ use std::cell::RefCell;
 use std::sync::Arc;
 
 pub struct Finalizer {
+    time_source: Arc<dyn TimeSource>,
     replica_config: ReplicaConfig,
     registry_client: Arc<dyn RegistryClient>,
     membership: Arc<Membership>,
@@ -44,6 +47,7 @@ pub struct Finalizer {
     message_routing: Arc<dyn MessageRouting>,
     ingress_selector: Arc<dyn IngressSelector>,
     state_manager: Arc<dyn StateManager<State = ReplicatedState>>,
+    timings_recorder: Arc<dyn ConsensusTimingsRecorder>,
     log: ReplicaLogger,
     metrics: FinalizerMetrics,
     prev_finalized_height: RefCell<Height>,
@@ -52,6 +56,7 @@ pub struct Finalizer {
 impl Finalizer {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
+        time_source: Arc<dyn TimeSource>,
         replica_config: ReplicaConfig,
         registry_client: Arc<dyn RegistryClient>,
         membership: Arc<Membership>,
@@ -59,10 +64,12 @@ impl Finalizer {
         message_routing: Arc<dyn MessageRouting>,
         ingress_selector: Arc<dyn IngressSelector>,
         state_manager: Arc<dyn StateManager<State = ReplicatedState>>,
+        timings_recorder: Arc<dyn ConsensusTimingsRecorder>,
         log: ReplicaLogger,
         metrics_registry: MetricsRegistry,
     ) -> Self {
         Self {
+            time_source,
             replica_config,
             registry_client,
             membership,
@@ -70,6 +77,7 @@ impl Finalizer {
             message_routing,
             ingress_selector,
             state_manager,
+            timings_recorder,
             log,
             metrics: FinalizerMetrics::new(metrics_registry),
             prev_finalized_height: RefCell::new(Height::from(0)),
@@ -125,6 +133,10 @@ impl Finalizer {
     ) {
         match result {
             Ok(()) => {
+                self.timings_recorder.record_batch_delivered(
+                    Height::from(batch_stats.batch_height),
+                    self.time_source.get_relative_time(),
+                );
                 self.metrics.process(&block_stats, &batch_stats);
                 for ingress in batch_stats.ingress_ids.iter() {
                     debug!(
@@ -345,6 +357,7 @@ mod tests {
                 membership,
                 registry,
                 crypto,
+                time_source,
                 ..
             } = dependencies(pool_config, 1);
             let message_routing = FakeMessageRouting::new();
@@ -359,6 +372,7 @@ mod tests {
             let state_manager = Arc::new(FakeStateManager::new());
 
             let finalizer = Finalizer::new(
+                time_source.clone(),
                 replica_config,
                 registry,
                 membership,
@@ -366,6 +380,7 @@ mod tests {
                 message_routing.clone(),
                 ingress_selector,
                 state_manager,
+                pool.get_timings_recorder(),
                 no_op_logger(),
                 MetricsRegistry::new(),
             );
@@ -383,6 +398,13 @@ mod tests {
             assert!(!b.is_empty());
             // First block, nothing to remove.
             assert!(shares.is_empty());
+            // The delivery of the batch is recorded with the time of the time source.
+            let timings = pool.get_cache().height_timings();
+            assert_eq!(timings[0].height, Height::from(1));
+            assert_eq!(
+                timings[0].batch_delivered,
+                Some(time_source.get_relative_time())
+            );
 
             // 3. When notarization exists, create a finalization share
             pool.insert_validated(pool.make_next_beacon());
@@ -424,6 +446,7 @@ mod tests {
                 membership,
                 registry,
                 crypto,
+                time_source,
                 ..
             } = dependencies_with_subnet_params(
                 pool_config,
@@ -450,6 +473,7 @@ mod tests {
             *message_routing.next_batch_height.write().unwrap() = Height::from(2);
             let ingress_selector = Arc::new(FakeIngressSelector::new());
             let finalizer = Finalizer::new(
+                time_source,
                 replica_config,
                 registry,
                 membership,
@@ -457,6 +481,7 @@ mod tests {
                 message_routing.clone(),
                 ingress_selector,
                 Arc::new(FakeStateManager::new()),
+                pool.get_timings_recorder(),
                 no_op_logger(),
                 metrics_registry,
             );
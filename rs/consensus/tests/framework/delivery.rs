@@ -50,8 +50,7 @@ impl DeliveryStrategy for Sequential {
                             other.deps.replica_config.node_id,
                             msg,
                         );
-                        let mut in_queue = other.in_queue.borrow_mut();
-                        in_queue.push(Input::Message(msg.clone()));
+                        runner.deliver(instance, other, msg.clone());
                     }
                 }
                 return true;
@@ -85,7 +84,6 @@ impl DeliveryStrategy for RandomReceive {
                 let mut rng = runner.rng();
                 for other in instances.iter() {
                     if other.deps.replica_config.node_id != instance.deps.replica_config.node_id {
-                        let mut in_queue = other.in_queue.borrow_mut();
                         let delay = rng.gen_range(UNIT_TIME_STEP..self.max_delta);
                         let msg = Message {
                             message: x.message.clone(),
@@ -98,7 +96,7 @@ impl DeliveryStrategy for RandomReceive {
                             other.deps.replica_config.node_id,
                             msg,
                         );
-                        in_queue.push(Input::Message(msg));
+                        runner.deliver(instance, other, msg);
                     }
                 }
                 return true;
@@ -125,7 +123,6 @@ impl DeliveryStrategy for RandomGraph {
             if let Some(x) = instance.out_queue.borrow_mut().pop() {
                 for other in instances.iter() {
                     if other.deps.replica_config.node_id != instance.deps.replica_config.node_id {
-                        let mut in_queue = other.in_queue.borrow_mut();
                         let delay =
                             self.distances[instance.index][other.index] as u32 * self.unit_latency;
                         let msg = Message {
@@ -139,7 +136,7 @@ impl DeliveryStrategy for RandomGraph {
                             other.deps.replica_config.node_id,
                             msg,
                         );
-                        in_queue.push(Input::Message(msg));
+                        runner.deliver(instance, other, msg);
                     }
                 }
                 return true;
@@ -0,0 +1,137 @@
+#![allow(dead_code)]
+//! Faults injected into the simulated network.
+//!
+//! All fault windows are given relative to the start of the simulation in
+//! virtual time. Messages crossing a partition are held back until the
+//! partition heals, which mimics gossip re-transmitting artifacts once peers
+//! become reachable again.
+
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::time::Duration;
+
+/// Separates the instances with the given indices from all other instances
+/// while the window is active.
+#[derive(Clone, Debug)]
+pub struct Partition {
+    pub nodes: BTreeSet<usize>,
+    pub window: Range<Duration>,
+}
+
+impl Partition {
+    fn separates(&self, from: usize, to: usize) -> bool {
+        self.nodes.contains(&from) != self.nodes.contains(&to)
+    }
+}
+
+/// Adds the given extra latency to all messages sent by or to the instances
+/// with the given indices, if they would arrive while the window is active.
+#[derive(Clone, Debug)]
+pub struct Delay {
+    pub nodes: BTreeSet<usize>,
+    pub extra: Duration,
+    pub window: Range<Duration>,
+}
+
+/// The set of partitions and delays of a simulation.
+#[derive(Clone, Debug, Default)]
+pub struct NetworkFaults {
+    partitions: Vec<Partition>,
+    delays: Vec<Delay>,
+}
+
+impl NetworkFaults {
+    /// Partition the given instances from the rest of the network during the
+    /// given window.
+    pub fn with_partition(mut self, nodes: &[usize], window: Range<Duration>) -> Self {
+        self.partitions.push(Partition {
+            nodes: nodes.iter().copied().collect(),
+            window,
+        });
+        self
+    }
+
+    /// Delay all messages sent by or to the given instances by `extra` during
+    /// the given window.
+    pub fn with_delay(mut self, nodes: &[usize], extra: Duration, window: Range<Duration>) -> Self {
+        self.delays.push(Delay {
+            nodes: nodes.iter().copied().collect(),
+            extra,
+            window,
+        });
+        self
+    }
+
+    /// Return the arrival time of a message sent from instance `from` to
+    /// instance `to`, which would arrive at `arrival` in a fault-free network.
+    pub fn arrival(&self, from: usize, to: usize, arrival: Duration) -> Duration {
+        let mut result = arrival;
+        for delay in &self.delays {
+            if delay.window.contains(&arrival)
+                && (delay.nodes.contains(&from) || delay.nodes.contains(&to))
+            {
+                result += delay.extra;
+            }
+        }
+        while let Some(partition) = self
+            .partitions
+            .iter()
+            .find(|partition| partition.window.contains(&result) && partition.separates(from, to))
+        {
+            result = partition.window.end;
+        }
+        result
+    }
+
+    /// Return true if any fault is active at the given time.
+    pub fn is_active(&self, now: Duration) -> bool {
+        self.windows().any(|window| window.contains(&now))
+    }
+
+    /// Return the latest time before `now` at which a fault ended.
+    pub fn last_healed(&self, now: Duration) -> Option<Duration> {
+        self.windows()
+            .filter(|window| window.end <= now)
+            .map(|window| window.end)
+            .max()
+    }
+
+    fn windows(&self) -> impl Iterator<Item = &Range<Duration>> {
+        self.partitions
+            .iter()
+            .map(|partition| &partition.window)
+            .chain(self.delays.iter().map(|delay| &delay.window))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_partition_holds_messages_until_healed() {
+        let faults = NetworkFaults::default().with_partition(&[0], secs(10)..secs(20));
+        assert_eq!(faults.arrival(0, 1, secs(5)), secs(5));
+        assert_eq!(faults.arrival(0, 1, secs(15)), secs(20));
+        assert_eq!(faults.arrival(1, 0, secs(15)), secs(20));
+        assert_eq!(faults.arrival(1, 2, secs(15)), secs(15));
+        assert!(faults.is_active(secs(10)));
+        assert!(!faults.is_active(secs(20)));
+        assert_eq!(faults.last_healed(secs(15)), None);
+        assert_eq!(faults.last_healed(secs(25)), Some(secs(20)));
+    }
+
+    #[test]
+    fn test_delayed_message_can_run_into_partition() {
+        let faults = NetworkFaults::default()
+            .with_delay(&[2], secs(6), secs(0)..secs(10))
+            .with_partition(&[1], secs(10)..secs(20));
+        assert_eq!(faults.arrival(2, 0, secs(5)), secs(11));
+        assert_eq!(faults.arrival(2, 1, secs(5)), secs(20));
+        assert_eq!(faults.arrival(0, 1, secs(5)), secs(5));
+    }
+}
@@ -1,8 +1,11 @@
 mod delivery;
 mod driver;
 mod execution;
+mod faults;
+mod properties;
 mod runner;
 mod types;
 
+pub use faults::NetworkFaults;
 pub use runner::ConsensusRunner;
 pub use types::{ConsensusDependencies, ConsensusDriver, ConsensusInstance, ConsensusRunnerConfig};
@@ -0,0 +1,138 @@
+#![allow(dead_code)]
+//! Safety and liveness properties, checked by the ConsensusRunner after every
+//! step of the simulation.
+//!
+//! Only honest instances, i.e. those running without malicious flags, are
+//! taken into account.
+
+use super::faults::NetworkFaults;
+use super::types::ConsensusInstance;
+use ic_consensus::consensus::pool_reader::PoolReader;
+use ic_interfaces::certification::CertificationPool;
+use ic_types::{consensus::Block, crypto::CryptoHashOf, CryptoHashOfPartialState, Height};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Keeps track of what the honest instances agreed on so far.
+pub(crate) struct PropertyChecker {
+    // The hashes of the finalized blocks by height.
+    finalized: RefCell<BTreeMap<Height, CryptoHashOf<Block>>>,
+    // The certified state hashes by height.
+    certified: RefCell<BTreeMap<Height, CryptoHashOfPartialState>>,
+    // The highest finalized height of any honest instance, and the time since
+    // the start of the simulation at which it was reached.
+    progress: RefCell<(Height, Duration)>,
+}
+
+impl PropertyChecker {
+    pub(crate) fn new() -> Self {
+        Self {
+            finalized: Default::default(),
+            certified: Default::default(),
+            progress: RefCell::new((Height::from(0), Duration::ZERO)),
+        }
+    }
+
+    /// Check that all honest instances agree on the finalized chain and on the
+    /// certified state hashes.
+    pub(crate) fn check_safety(&self, instances: &[ConsensusInstance<'_>]) -> Result<(), String> {
+        for instance in instances.iter().filter(|instance| instance.is_honest()) {
+            self.check_finalized_chain(instance)?;
+            self.check_certifications(instance)?;
+        }
+        Ok(())
+    }
+
+    // Walk down the finalized chain of the instance until reaching a height
+    // whose block we know already, which must be the same.
+    fn check_finalized_chain(&self, instance: &ConsensusInstance<'_>) -> Result<(), String> {
+        let pool = instance.driver.consensus_pool.read().unwrap();
+        let pool_reader = PoolReader::new(&*pool);
+        let mut finalized = self.finalized.borrow_mut();
+        let mut block = pool_reader.get_finalized_tip();
+        loop {
+            let hash = ic_crypto::crypto_hash(&block);
+            match finalized.get(&block.height) {
+                Some(known) if *known == hash => return Ok(()),
+                Some(known) => {
+                    return Err(format!(
+                        "Instance {} finalized {:?} at height {}, not {:?}",
+                        instance.node_id, hash, block.height, known
+                    ))
+                }
+                None => {
+                    finalized.insert(block.height, hash);
+                }
+            }
+            block = match pool_reader.get_parent(&block) {
+                Some(parent) => parent,
+                None => return Ok(()),
+            };
+        }
+    }
+
+    fn check_certifications(&self, instance: &ConsensusInstance<'_>) -> Result<(), String> {
+        let pool = instance.driver.certification_pool.read().unwrap();
+        let mut certified = self.certified.borrow_mut();
+        for height in pool.certified_heights() {
+            let hash = match pool.certification_at_height(height) {
+                Some(certification) => certification.signed.content.hash,
+                None => continue,
+            };
+            match certified.get(&height) {
+                Some(known) if *known != hash => {
+                    return Err(format!(
+                        "Instance {} certified {:?} at height {}, not {:?}",
+                        instance.node_id, hash, height, known
+                    ))
+                }
+                Some(_) => (),
+                None => {
+                    certified.insert(height, hash);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the finalized height of the honest instances increased
+    /// within the last `timeout`, not counting the time during which faults
+    /// were active. `now` is the time since the start of the simulation.
+    pub(crate) fn check_liveness(
+        &self,
+        instances: &[ConsensusInstance<'_>],
+        faults: &NetworkFaults,
+        now: Duration,
+        timeout: Duration,
+    ) -> Result<(), String> {
+        let height = instances
+            .iter()
+            .filter(|instance| instance.is_honest())
+            .map(|instance| {
+                PoolReader::new(&*instance.driver.consensus_pool.read().unwrap())
+                    .get_finalized_height()
+            })
+            .max()
+            .unwrap_or_else(|| Height::from(0));
+        let mut progress = self.progress.borrow_mut();
+        if height > progress.0 {
+            *progress = (height, now);
+            return Ok(());
+        }
+        if faults.is_active(now) {
+            return Ok(());
+        }
+        let since = faults
+            .last_healed(now)
+            .map_or(progress.1, |healed| healed.max(progress.1));
+        if now > since + timeout {
+            return Err(format!(
+                "No progress beyond finalized height {} for {:?}",
+                progress.0,
+                now - since
+            ));
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,7 @@
 use super::delivery::*;
 use super::execution::*;
+use super::faults::NetworkFaults;
+use super::properties::PropertyChecker;
 use super::types::*;
 use ic_config::artifact_pool::ArtifactPoolConfig;
 use ic_consensus::consensus::dkg_key_manager::DkgKeyManager;
@@ -10,7 +12,7 @@ use ic_consensus::{
     dkg,
 };
 use ic_interfaces::time_source::TimeSource;
-use ic_logger::{info, warn, ReplicaLogger};
+use ic_logger::{error, info, warn, ReplicaLogger};
 use ic_test_utilities::{crypto::CryptoReturningOk, FastForwardTimeSource};
 use ic_types::malicious_flags::MaliciousFlags;
 use ic_types::Time;
@@ -39,8 +41,15 @@ enum NetworkStatus {
 // used to detect stalling.
 const MAX_IDLE_TIME: u64 = 50000;
 
+// Maximum lapsed time in milliseconds (in virtual clock) allowed without the
+// finalized height of honest instances increasing, while no network faults are
+// active.
+const LIVENESS_TIMEOUT: u64 = 60000;
+
 pub struct ConsensusRunner<'a> {
     idle_since: RefCell<Time>,
+    start_time: Time,
+    properties: PropertyChecker,
     pub time: Arc<FastForwardTimeSource>,
     pub instances: Vec<ConsensusInstance<'a>>,
     pub(crate) stop_predicate: StopPredicate<'a>,
@@ -62,6 +71,16 @@ impl<'a> ConsensusInstances<'a> for ConsensusRunner<'a> {
     fn time_source(&self) -> &dyn TimeSource {
         self.time.as_ref()
     }
+    fn deliver(&self, from: &ConsensusInstance<'a>, to: &ConsensusInstance<'a>, message: Message) {
+        let arrival =
+            self.config
+                .faults
+                .arrival(from.index, to.index, message.timestamp - self.start_time);
+        to.in_queue.borrow_mut().push(Input::Message(Message {
+            message: message.message,
+            timestamp: self.start_time + arrival,
+        }));
+    }
 }
 
 const SLOG_ASYNC_CHAN_SIZE: usize = 10000;
@@ -99,6 +118,8 @@ impl<'a> ConsensusRunner<'a> {
         let rng = RefCell::new(ChaChaRng::seed_from_u64(config.random_seed));
         ConsensusRunner {
             idle_since: RefCell::new(now),
+            start_time: now,
+            properties: PropertyChecker::new(),
             instances: Vec::new(),
             stop_predicate: &stop_immediately,
             time: time_source,
@@ -120,6 +141,30 @@ impl<'a> ConsensusRunner<'a> {
         deps: &'a ConsensusDependencies,
         pool_config: ArtifactPoolConfig,
         pool_reader: &PoolReader<'_>,
+    ) {
+        self.add_malicious_instance(
+            membership,
+            fake_crypto,
+            deps,
+            pool_config,
+            pool_reader,
+            MaliciousFlags::default(),
+        )
+    }
+
+    /// Add a new consensus instance like [`Self::add_instance`], which
+    /// behaves according to the given malicious flags. Note that the flags
+    /// only take effect when the `malicious_code` feature is enabled.
+    ///
+    /// Malicious instances are excluded from the safety and liveness checks.
+    pub fn add_malicious_instance(
+        &mut self,
+        membership: Arc<Membership>,
+        fake_crypto: Arc<CryptoReturningOk>,
+        deps: &'a ConsensusDependencies,
+        pool_config: ArtifactPoolConfig,
+        pool_reader: &PoolReader<'_>,
+        malicious_flags: MaliciousFlags,
     ) {
         let node_id = deps.replica_config.node_id;
 
@@ -150,9 +195,10 @@ impl<'a> ConsensusRunner<'a> {
             dkg_key_manager.clone(),
             deps.message_routing.clone(),
             deps.state_manager.clone(),
+            deps.consensus_pool.read().unwrap().get_timings_recorder(),
             Arc::clone(&self.time) as Arc<_>,
             Duration::from_secs(0),
-            MaliciousFlags::default(),
+            malicious_flags.clone(),
             deps.metrics_registry.clone(),
             replica_logger.clone(),
             None,
@@ -170,6 +216,8 @@ impl<'a> ConsensusRunner<'a> {
             membership,
             fake_crypto,
             deps.state_manager.clone(),
+            deps.consensus_pool.read().unwrap().get_timings_recorder(),
+            Arc::clone(&self.time) as Arc<_>,
             deps.metrics_registry.clone(),
             replica_logger.clone(),
         );
@@ -195,6 +243,7 @@ impl<'a> ConsensusRunner<'a> {
             ),
             clock: RefCell::new(now),
             index: self.instances.len(),
+            malicious_flags,
         });
     }
 
@@ -219,7 +268,9 @@ impl<'a> ConsensusRunner<'a> {
         }
     }
 
-    /// Run a single step of all instances to finish processing their messages.
+    /// Run a single step of all instances to finish processing their messages,
+    /// and check the safety and liveness properties afterwards. Panic if safety
+    /// is violated.
     /// Return the updated NetworkStatus.
     fn process(&self) -> NetworkStatus {
         let delivered = self.config.delivery.deliver_next(self);
@@ -232,6 +283,19 @@ impl<'a> ConsensusRunner<'a> {
         }
         let now = self.time.get_relative_time();
 
+        if let Err(violation) = self.properties.check_safety(&self.instances) {
+            panic!("Safety violated with {}: {}", self.config, violation);
+        }
+        if let Err(violation) = self.properties.check_liveness(
+            &self.instances,
+            &self.config.faults,
+            now - self.start_time,
+            Duration::from_millis(LIVENESS_TIMEOUT),
+        ) {
+            error!(self.logger, "Liveness violated: {}", violation);
+            return NetworkStatus::Stalled;
+        }
+
         let mut stopped = true;
         for instance in self.instances.iter() {
             // only stop when all instances satisfy StopPredicate
@@ -266,6 +330,7 @@ impl Default for ConsensusRunnerConfig {
             degree: 9,
             execution: GlobalMessage::new(),
             delivery: Sequential::new(),
+            faults: NetworkFaults::default(),
         }
     }
 }
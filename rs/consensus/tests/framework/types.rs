@@ -1,4 +1,5 @@
 #![allow(dead_code)]
+use super::faults::NetworkFaults;
 use ic_artifact_pool::{
     canister_http_pool, certification_pool::CertificationPoolImpl,
     consensus_pool::ConsensusPoolImpl, dkg_pool, ecdsa_pool,
@@ -31,6 +32,7 @@ use ic_types::{
         certification::CertificationMessage, dkg::Message as DkgMessage, CatchUpPackage,
         ConsensusMessage,
     },
+    malicious_flags::MaliciousFlags,
     replica_config::ReplicaConfig,
     time::Time,
     NodeId, SubnetId,
@@ -211,6 +213,14 @@ pub struct ConsensusInstance<'a> {
     pub(crate) out_queue: Queue<Output>,
     pub(crate) clock: RefCell<Time>,
     pub(crate) index: usize,
+    pub(crate) malicious_flags: MaliciousFlags,
+}
+
+impl ConsensusInstance<'_> {
+    /// Return true if the instance runs without any malicious flags.
+    pub fn is_honest(&self) -> bool {
+        self.malicious_flags == MaliciousFlags::default()
+    }
 }
 
 impl fmt::Display for ConsensusInstance<'_> {
@@ -265,6 +275,9 @@ pub trait ConsensusInstances<'a> {
     fn logger(&self) -> &ReplicaLogger;
     fn rng(&self) -> RefMut<'_, ChaChaRng>;
     fn time_source(&self) -> &dyn TimeSource;
+    /// Deliver a message from one instance to another, subject to the network
+    /// faults of the simulation.
+    fn deliver(&self, from: &ConsensusInstance<'a>, to: &ConsensusInstance<'a>, message: Message);
 }
 
 /// Configuration parameters that will be read from command line argument or
@@ -277,6 +290,7 @@ pub struct ConsensusRunnerConfig {
     pub degree: usize,
     pub execution: Box<dyn ExecutionStrategy>,
     pub delivery: Box<dyn DeliveryStrategy>,
+    pub faults: NetworkFaults,
 }
 
 impl fmt::Display for ConsensusRunnerConfig {
@@ -284,14 +298,16 @@ impl fmt::Display for ConsensusRunnerConfig {
         write!(
             f,
             "ConsensusRunnerConfig {{ max_delta: {}, random_seed: {}, \
-             num_nodes: {}, num_rounds: {}, degree: {}, execution: {}, delivery: {} }}",
+             num_nodes: {}, num_rounds: {}, degree: {}, execution: {}, delivery: {}, \
+             faults: {:?} }}",
             self.max_delta,
             self.random_seed,
             self.num_nodes,
             self.num_rounds,
             self.degree,
             get_name(&self.execution),
-            get_name(&self.delivery)
+            get_name(&self.delivery),
+            self.faults,
         )
     }
 }
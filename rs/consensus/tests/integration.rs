@@ -1,6 +1,6 @@
 mod framework;
 use crate::framework::{
-    ConsensusDependencies, ConsensusInstance, ConsensusRunner, ConsensusRunnerConfig,
+    ConsensusDependencies, ConsensusInstance, ConsensusRunner, ConsensusRunnerConfig, NetworkFaults,
 };
 use ic_consensus::consensus::{pool_reader::PoolReader, Membership};
 use ic_interfaces::{consensus_pool::ConsensusPool, registry::RegistryClient};
@@ -11,10 +11,14 @@ use ic_test_utilities::{
     FastForwardTimeSource,
 };
 use ic_test_utilities_registry::{setup_registry_non_final, SubnetRecordBuilder};
-use ic_types::{crypto::CryptoHash, replica_config::ReplicaConfig, Height, RegistryVersion};
+use ic_types::{
+    crypto::CryptoHash, malicious_flags::MaliciousFlags, replica_config::ReplicaConfig, Height,
+    RegistryVersion,
+};
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::sync::Arc;
+use std::time::Duration;
 
 #[test]
 fn multiple_nodes_are_live() -> Result<(), String> {
@@ -22,7 +26,7 @@ fn multiple_nodes_are_live() -> Result<(), String> {
     ConsensusRunnerConfig::new_from_env(4, 0)
         .and_then(|config| config.parse_extra_config())
         .map(|config| {
-            run_n_rounds_and_collect_hashes(config);
+            run_n_rounds_and_collect_hashes(config, &[]);
         })
 }
 
@@ -33,7 +37,7 @@ fn single_node_is_live() {
         num_rounds: 126,
         ..Default::default()
     };
-    run_n_rounds_and_collect_hashes(config);
+    run_n_rounds_and_collect_hashes(config, &[]);
 }
 
 #[test]
@@ -44,12 +48,83 @@ fn multiple_nodes_are_deterministic() {
             num_rounds: 10,
             ..Default::default()
         };
-        run_n_rounds_and_collect_hashes(config)
+        run_n_rounds_and_collect_hashes(config, &[])
     };
     assert_eq!(run(), run());
 }
 
-fn run_n_rounds_and_collect_hashes(config: ConsensusRunnerConfig) -> Rc<RefCell<Vec<CryptoHash>>> {
+#[test]
+fn minority_partition_does_not_stop_progress() {
+    let config = ConsensusRunnerConfig {
+        num_nodes: 4,
+        num_rounds: 20,
+        faults: NetworkFaults::default()
+            .with_partition(&[3], Duration::from_secs(5)..Duration::from_secs(25)),
+        ..Default::default()
+    };
+    run_n_rounds_and_collect_hashes(config, &[]);
+}
+
+#[test]
+fn nodes_recover_from_partition_without_quorum() {
+    let config = ConsensusRunnerConfig {
+        num_nodes: 4,
+        num_rounds: 20,
+        faults: NetworkFaults::default()
+            .with_partition(&[0, 1], Duration::from_secs(5)..Duration::from_secs(25)),
+        ..Default::default()
+    };
+    run_n_rounds_and_collect_hashes(config, &[]);
+}
+
+#[test]
+fn multiple_nodes_are_live_with_delays() {
+    let config = ConsensusRunnerConfig {
+        num_nodes: 4,
+        num_rounds: 20,
+        faults: NetworkFaults::default()
+            .with_delay(
+                &[0],
+                Duration::from_secs(3),
+                Duration::from_secs(0)..Duration::from_secs(20),
+            )
+            .with_delay(
+                &[1, 2],
+                Duration::from_millis(500),
+                Duration::from_secs(10)..Duration::from_secs(30),
+            ),
+        ..Default::default()
+    };
+    run_n_rounds_and_collect_hashes(config, &[]);
+}
+
+// Run by the `integration_malicious_code_test` target, or with
+// `cargo test --features malicious_code`.
+#[cfg(feature = "malicious_code")]
+#[test]
+fn byzantine_node_does_not_violate_safety() {
+    let config = ConsensusRunnerConfig {
+        num_nodes: 4,
+        num_rounds: 20,
+        ..Default::default()
+    };
+    let malicious_flags = MaliciousFlags {
+        maliciously_propose_equivocating_blocks: true,
+        maliciously_notarize_all: true,
+        maliciously_finalize_all: true,
+        ..Default::default()
+    };
+    run_n_rounds_and_collect_hashes(config, &[(0, malicious_flags)]);
+}
+
+/// Run consensus until all instances reach the configured number of rounds,
+/// where the instances with the given indices behave according to the given
+/// malicious flags. Safety and liveness are checked by the runner after every
+/// step.
+fn run_n_rounds_and_collect_hashes(
+    config: ConsensusRunnerConfig,
+    malicious_flags: &[(usize, MaliciousFlags)],
+) -> Rc<RefCell<Vec<CryptoHash>>> {
     let nodes = config.num_nodes;
     ic_test_utilities::artifact_pool_config::with_test_pool_configs(nodes, |pool_configs| {
         let rounds = config.num_rounds;
@@ -116,19 +191,25 @@ fn run_n_rounds_and_collect_hashes(config: ConsensusRunnerConfig) -> Rc<RefCell<
 
         let mut framework = ConsensusRunner::new_with_config(config, time_source);
 
-        for (pool_config, deps) in pool_configs.iter().zip(inst_deps.iter()) {
+        for (index, (pool_config, deps)) in pool_configs.iter().zip(inst_deps.iter()).enumerate() {
             let membership = Membership::new(
                 deps.consensus_pool.read().unwrap().get_cache(),
                 Arc::clone(&registry_client) as Arc<dyn RegistryClient>,
                 subnet_id,
             );
             let membership = Arc::new(membership);
-            framework.add_instance(
+            let flags = malicious_flags
+                .iter()
+                .find(|(malicious_index, _)| *malicious_index == index)
+                .map(|(_, flags)| flags.clone())
+                .unwrap_or_default();
+            framework.add_malicious_instance(
                 membership.clone(),
                 crypto.clone(),
                 deps,
                 pool_config.clone(),
                 &PoolReader::new(&*deps.consensus_pool.read().unwrap()),
+                flags,
             );
         }
         assert!(framework.run_until(&reach_n_rounds));
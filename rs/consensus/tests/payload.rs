@@ -116,6 +116,7 @@ fn consensus_produces_expected_batches() {
             ),
         ));
         let consensus_cache = consensus_pool.read().unwrap().get_cache();
+        let timings_recorder = consensus_pool.read().unwrap().get_timings_recorder();
         let membership = ic_consensus::consensus::Membership::new(
             consensus_cache.clone(),
             registry_client.clone(),
@@ -144,6 +145,7 @@ fn consensus_produces_expected_batches() {
             dkg_key_manager.clone(),
             Arc::clone(&router) as Arc<_>,
             Arc::clone(&state_manager) as Arc<_>,
+            Arc::clone(&timings_recorder),
             Arc::clone(&time) as Arc<_>,
             Duration::from_secs(0),
             MaliciousFlags::default(),
@@ -164,6 +166,8 @@ fn consensus_produces_expected_batches() {
             Arc::clone(&membership) as Arc<_>,
             Arc::clone(&fake_crypto) as Arc<_>,
             Arc::clone(&state_manager) as Arc<_>,
+            timings_recorder,
+            Arc::clone(&time) as Arc<_>,
             metrics_registry.clone(),
             no_op_logger(),
         );
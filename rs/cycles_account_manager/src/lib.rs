@@ -21,6 +21,7 @@ use ic_logger::{error, info, ReplicaLogger};
 use ic_nns_constants::CYCLES_MINTING_CANISTER_ID;
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::{CanisterState, SystemState};
+use ic_types::canister_http::MAX_CANISTER_HTTP_REDIRECTS;
 use ic_types::messages::MAX_INTER_CANISTER_PAYLOAD_IN_BYTES_U64;
 use ic_types::{
     messages::{Request, Response, SignedIngressContent, MAX_INTER_CANISTER_PAYLOAD_IN_BYTES},
@@ -776,10 +777,14 @@ impl CyclesAccountManager {
         Ok(())
     }
 
+    /// Returns the fee for an http request. Every redirect the request may
+    /// follow, up to [`MAX_CANISTER_HTTP_REDIRECTS`], is charged as another
+    /// request.
     pub fn http_request_fee(
         &self,
         request_size: NumBytes,
         response_size_limit: Option<NumBytes>,
+        max_redirects: u32,
         subnet_size: usize,
     ) -> Cycles {
         let response_size = match response_size_limit {
@@ -787,10 +792,11 @@ impl CyclesAccountManager {
             // Defaults to maximum response size.
             None => MAX_INTER_CANISTER_PAYLOAD_IN_BYTES_U64,
         };
-        let total_bytes = response_size + request_size.get();
+        let hops = 1 + max_redirects.min(MAX_CANISTER_HTTP_REDIRECTS) as u64;
+        let request_fee = self.config.http_request_baseline_fee
+            + self.config.http_request_per_byte_fee * request_size.get();
         self.scale_cost(
-            self.config.http_request_baseline_fee
-                + self.config.http_request_per_byte_fee * total_bytes,
+            request_fee * hops + self.config.http_request_per_byte_fee * response_size,
             subnet_size,
         )
     }
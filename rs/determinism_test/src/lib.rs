@@ -22,7 +22,6 @@ use ic_types::{
 };
 use setup::setup;
 use std::{collections::BTreeMap, convert::TryFrom, sync::Arc, thread::sleep, time::Duration};
-use wabt::wat2wasm;
 
 fn build_batch(message_routing: &dyn MessageRouting, msgs: Vec<SignedIngress>) -> Batch {
     Batch {
@@ -147,7 +146,7 @@ fn get_state_hash(
 }
 
 fn install_canister(
-    wasm: &str,
+    wasm: &[u8],
     message_routing: &MessageRoutingImpl,
     ingress_history_reader: &dyn IngressHistoryReader,
     mut nonce: u64,
@@ -169,7 +168,6 @@ fn install_canister(
         Err(err) => panic!("{}", err),
     };
 
-    let wasm = wat2wasm(wasm).unwrap();
     let signed_ingress = SignedIngressBuilder::new()
         .canister_id(IC_00)
         .expiry_time(UNIX_EPOCH + Duration::from_secs(60))
@@ -179,7 +177,7 @@ fn install_canister(
             InstallCodeArgs::new(
                 CanisterInstallMode::try_from("install".to_string()).unwrap(),
                 canister_id,
-                wasm,
+                wasm.to_vec(),
                 vec![],
                 None,
                 None,
@@ -227,13 +225,196 @@ const WASM: &str = r#"(module
   (call $msg_reply)
 )
 
+(func $bulk
+  (memory.fill (i32.const 8192) (i32.const 7) (i32.const 20000))
+  (memory.copy (i32.const 40000) (i32.const 8000) (i32.const 20000))
+  (memory.init 0 (i32.const 60000) (i32.const 0) (i32.const 8))
+  (call $msg_reply)
+)
+
 (memory $memory 1)
+(data "abcdefgh")
 (export "canister_update dirty1" (func $dirty1))
 (export "canister_update dirty2" (func $dirty2))
+(export "canister_update bulk" (func $bulk))
 (export "memory" (memory $memory)))
 "#;
 
+// SIMD opcodes, see https://webassembly.github.io/spec/core/binary/instructions.html#vector-instructions
+const V128_STORE: u32 = 11;
+const V128_CONST: u32 = 12;
+const F32X4_SQRT: u32 = 227;
+const F32X4_ADD: u32 = 228;
+const F32X4_SUB: u32 = 229;
+const F32X4_MUL: u32 = 230;
+const F32X4_DIV: u32 = 231;
+const F64X2_SQRT: u32 = 239;
+const F64X2_ADD: u32 = 240;
+const F64X2_DIV: u32 = 243;
+
+fn write_u32(mut value: u32, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_bytes(bytes: &[u8], out: &mut Vec<u8>) {
+    write_u32(bytes.len() as u32, out);
+    out.extend_from_slice(bytes);
+}
+
+fn write_simd(opcode: u32, out: &mut Vec<u8>) {
+    out.push(0xfd);
+    write_u32(opcode, out);
+}
+
+fn write_v128_const(lanes: &[u8], out: &mut Vec<u8>) {
+    assert_eq!(lanes.len(), 16);
+    write_simd(V128_CONST, out);
+    out.extend_from_slice(lanes);
+}
+
+fn f32x4(lanes: [f32; 4]) -> Vec<u8> {
+    lanes
+        .iter()
+        .flat_map(|x| x.to_bits().to_le_bytes())
+        .collect()
+}
+
+fn f64x2(lanes: [f64; 2]) -> Vec<u8> {
+    lanes
+        .iter()
+        .flat_map(|x| x.to_bits().to_le_bytes())
+        .collect()
+}
+
+/// Returns a canister whose `simd` method stores the results of SIMD float
+/// operations that produce NaNs in its memory, so that the NaN bit patterns
+/// become part of the state hash. The module is assembled by hand because
+/// `wabt` only knows a pre-standard encoding of the SIMD instructions.
+fn simd_wasm() -> Vec<u8> {
+    // A NaN with a non-canonical payload, which hardware may propagate.
+    let f32_nan = f32::from_bits(0x7fa0_0001);
+    let f64_nan = f64::from_bits(0x7ff4_0000_0000_0001);
+    let operations = [
+        // 0 / 0, inf / inf, 0 / inf, NaN / 1
+        (
+            F32X4_DIV,
+            vec![
+                f32x4([0.0, f32::INFINITY, 0.0, f32_nan]),
+                f32x4([0.0, f32::INFINITY, f32::INFINITY, 1.0]),
+            ],
+        ),
+        // inf - inf, NaN - 1
+        (
+            F32X4_SUB,
+            vec![
+                f32x4([f32::INFINITY, f32_nan, 1.0, 0.0]),
+                f32x4([f32::INFINITY, 1.0, 1.0, 0.0]),
+            ],
+        ),
+        // 0 * inf, NaN * 0
+        (
+            F32X4_MUL,
+            vec![
+                f32x4([0.0, f32_nan, -0.0, 2.0]),
+                f32x4([f32::INFINITY, 0.0, f32::NEG_INFINITY, 2.0]),
+            ],
+        ),
+        // inf + -inf, NaN + NaN
+        (
+            F32X4_ADD,
+            vec![
+                f32x4([f32::INFINITY, f32_nan, f32::NAN, 1.0]),
+                f32x4([f32::NEG_INFINITY, f32_nan, f32_nan, 1.0]),
+            ],
+        ),
+        // sqrt(-1), sqrt(-inf), sqrt(NaN)
+        (
+            F32X4_SQRT,
+            vec![f32x4([-1.0, f32::NEG_INFINITY, f32_nan, 4.0])],
+        ),
+        (F64X2_DIV, vec![f64x2([0.0, f64_nan]), f64x2([0.0, 1.0])]),
+        (
+            F64X2_ADD,
+            vec![
+                f64x2([f64::INFINITY, f64_nan]),
+                f64x2([f64::NEG_INFINITY, f64::NAN]),
+            ],
+        ),
+        (F64X2_SQRT, vec![f64x2([-1.0, f64_nan])]),
+    ];
+
+    // (func $simd
+    //   (v128.store offset=<16 * i> (i32.const 0) (<op> (v128.const ...) ...))
+    //   ...
+    //   (call $msg_reply))
+    let mut body = vec![0x00];
+    for (i, (opcode, operands)) in operations.iter().enumerate() {
+        body.extend_from_slice(&[0x41, 0x00]);
+        for operand in operands {
+            write_v128_const(operand, &mut body);
+        }
+        write_simd(*opcode, &mut body);
+        write_simd(V128_STORE, &mut body);
+        body.push(0x04);
+        write_u32(16 * i as u32, &mut body);
+    }
+    body.extend_from_slice(&[0x10, 0x00, 0x0b]);
+
+    let mut sections: Vec<(u8, Vec<u8>)> = vec![];
+    // (type (func))
+    sections.push((1, vec![0x01, 0x60, 0x00, 0x00]));
+    // (import "ic0" "msg_reply" (func $msg_reply))
+    let mut imports = vec![0x01];
+    write_bytes(b"ic0", &mut imports);
+    write_bytes(b"msg_reply", &mut imports);
+    imports.extend_from_slice(&[0x00, 0x00]);
+    sections.push((2, imports));
+    sections.push((3, vec![0x01, 0x00]));
+    // (memory $memory 1)
+    sections.push((5, vec![0x01, 0x00, 0x01]));
+    // (export "canister_update simd" (func $simd))
+    // (export "memory" (memory $memory))
+    let mut exports = vec![0x02];
+    write_bytes(b"canister_update simd", &mut exports);
+    exports.extend_from_slice(&[0x00, 0x01]);
+    write_bytes(b"memory", &mut exports);
+    exports.extend_from_slice(&[0x02, 0x00]);
+    sections.push((7, exports));
+    let mut code = vec![0x01];
+    write_bytes(&body, &mut code);
+    sections.push((10, code));
+
+    let mut wasm = b"\0asm".to_vec();
+    wasm.extend_from_slice(&1_u32.to_le_bytes());
+    for (id, content) in sections {
+        wasm.push(id);
+        write_bytes(&content, &mut wasm);
+    }
+    wasm
+}
+
 pub fn determinism_test(msgs: Vec<&str>) {
+    let mut features = wabt::Features::new();
+    features.enable_bulk_memory();
+    let wasm = wabt::wat2wasm_with_features(WASM, features).unwrap();
+    run_determinism_test(&wasm, msgs);
+}
+
+/// Like [`determinism_test`], but with a canister that executes SIMD float
+/// operations producing NaNs.
+pub fn simd_determinism_test(msgs: Vec<&str>) {
+    run_determinism_test(&simd_wasm(), msgs);
+}
+
+fn run_determinism_test(wasm: &[u8], msgs: Vec<&str>) {
     let rt = tokio::runtime::Runtime::new().unwrap();
     let _enter_guard = rt.enter();
     let mut hashes = vec![];
@@ -247,7 +428,7 @@ pub fn determinism_test(msgs: Vec<&str>) {
             * num_canisters_per_core)
             .map(|_index| {
                 let (canister, inner_nonce) = install_canister(
-                    WASM,
+                    wasm,
                     &message_routing,
                     ingress_history_reader.as_ref(),
                     nonce,
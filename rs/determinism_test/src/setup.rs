@@ -1,4 +1,5 @@
 use ic_config::{
+    embedders::InstructionCostSchedule,
     subnet_config::{SubnetConfig, SubnetConfigs},
     Config,
 };
@@ -97,7 +98,9 @@ pub(crate) fn setup() -> (
     let subnet_type = SubnetType::System;
     let subnet_id = subnet_test_id(1);
     let root_subnet_id = subnet_test_id(2);
-    let (config, _) = Config::temp_config();
+    let (mut config, _) = Config::temp_config();
+    // SIMD instructions are only accepted with the `V2` schedule.
+    config.hypervisor.instruction_cost_schedule = InstructionCostSchedule::V2;
     let subnet_config = SubnetConfigs::default().own_subnet_config(subnet_type);
     let replica_config = ReplicaConfig {
         node_id: NodeId::from(PrincipalId::new_node_test_id(27)),
@@ -1,4 +1,4 @@
-use ic_determinism_test::determinism_test;
+use ic_determinism_test::{determinism_test, simd_determinism_test};
 
 #[test]
 fn test_process_batches_deterministically() {
@@ -6,3 +6,13 @@ fn test_process_batches_deterministically() {
         "dirty1", "dirty2", "dirty1", "dirty2", "dirty1", "dirty2",
     ]);
 }
+
+#[test]
+fn test_process_bulk_memory_batches_deterministically() {
+    determinism_test(vec!["bulk", "dirty1", "bulk", "dirty2", "bulk"]);
+}
+
+#[test]
+fn test_process_simd_batches_deterministically() {
+    simd_determinism_test(vec!["simd", "simd"]);
+}
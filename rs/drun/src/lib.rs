@@ -2,8 +2,12 @@
 
 use crate::message::{msg_stream_from_file, Message};
 use hex::encode;
-use ic_config::{subnet_config::SubnetConfigs, Config};
+use ic_config::{flag_status::FlagStatus, subnet_config::SubnetConfigs, Config};
 use ic_cycles_account_manager::CyclesAccountManager;
+use ic_embedders::wasm_utils::{
+    decoding::decode_wasm,
+    profiling::{instruction_profile, to_folded_stacks},
+};
 use ic_error_types::{ErrorCode, UserError};
 use ic_execution_environment::ExecutionServices;
 use ic_interfaces::{execution_environment::IngressHistoryReader, messaging::MessageRouting};
@@ -25,6 +29,7 @@ use ic_registry_proto_data_provider::ProtoRegistryDataProvider;
 use ic_registry_provisional_whitelist::ProvisionalWhitelist;
 use ic_registry_routing_table::{routing_table_insert_subnet, RoutingTable};
 use ic_registry_subnet_type::SubnetType;
+use ic_replicated_state::ReplicatedState;
 use ic_state_manager::StateManagerImpl;
 use ic_test_utilities::consensus::fake::FakeVerifier;
 use ic_test_utilities_registry::{
@@ -41,7 +46,7 @@ use ic_types::{
 use slog::{Drain, Logger};
 use std::collections::BTreeMap;
 use std::fs::OpenOptions;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::{thread::sleep, time::Duration};
 
@@ -58,6 +63,9 @@ pub struct DrunOptions {
     pub cfg: Config,
     pub extra_batches: u64,
     pub log_file: Option<PathBuf>,
+    /// If set, instruction profiling is enabled and the profile of the run is
+    /// written to this file.
+    pub instruction_profile: Option<PathBuf>,
 }
 
 /// Deliver a single message to the Message Routing layer
@@ -152,10 +160,14 @@ fn get_registry(
 pub fn run_drun(uo: DrunOptions) -> Result<(), String> {
     let DrunOptions {
         msg_filename,
-        cfg,
+        mut cfg,
         extra_batches,
         log_file,
+        instruction_profile,
     } = uo;
+    if instruction_profile.is_some() {
+        cfg.hypervisor.instruction_profiling = FlagStatus::Enabled;
+    }
     // Hardcoded magic values to create a ReplicaConfig that parses.
     let subnet_type = SubnetType::System;
     let subnet_config = SubnetConfigs::default().own_subnet_config(subnet_type);
@@ -231,6 +243,9 @@ pub fn run_drun(uo: DrunOptions) -> Result<(), String> {
         Arc::clone(&registry) as _,
     );
 
+    // Queries do not persist their profiling counters, so their profiles are
+    // collected separately.
+    let mut query_profiles = String::new();
     msg_stream.try_for_each(|parse_result| {
         parse_result.map(|msg| match msg {
             Message::Install(msg) => {
@@ -246,11 +261,13 @@ pub fn run_drun(uo: DrunOptions) -> Result<(), String> {
                 // NOTE: Data certificates aren't supported in drun yet.
                 // To support them, we'd need to do something similar to
                 // http_handler::get_latest_certified_state_and_data_certificate
-                print_query_result(query_handler.query(
+                let (result, profile) = query_handler.query_with_instruction_profile(
                     q,
                     state_manager.get_latest_state().take(),
                     Vec::new(),
-                ));
+                );
+                query_profiles.push_str(&profile);
+                print_query_result(result);
             }
 
             Message::Ingress(msg) => {
@@ -270,6 +287,47 @@ pub fn run_drun(uo: DrunOptions) -> Result<(), String> {
                 );
             }
         })
+    })?;
+
+    match instruction_profile {
+        Some(path) => write_instruction_profile(
+            &path,
+            &state_manager.get_latest_state().take(),
+            &query_profiles,
+        ),
+        None => Ok(()),
+    }
+}
+
+// Writes the instructions executed by each function of every canister to the
+// given file in the folded stack format. The profile consists of the counters
+// persisted in the state, which cover all messages except queries, followed by
+// the profiles of the queries.
+fn write_instruction_profile(
+    path: &Path,
+    state: &ReplicatedState,
+    query_profiles: &str,
+) -> Result<(), String> {
+    let mut report = String::new();
+    for canister in state.canisters_iter() {
+        let execution_state = match &canister.execution_state {
+            Some(execution_state) => execution_state,
+            None => continue,
+        };
+        let canister_id = canister.canister_id();
+        let wasm = decode_wasm(execution_state.wasm_binary.binary.to_shared_vec())
+            .map_err(|err| format!("Failed to decode the module of {}: {}", canister_id, err))?;
+        let profile = instruction_profile(&wasm, &execution_state.exported_globals)
+            .map_err(|err| format!("Failed to profile {}: {}", canister_id, err))?;
+        report.push_str(&to_folded_stacks(&canister_id.to_string(), &profile));
+    }
+    report.push_str(query_profiles);
+    std::fs::write(path, report).map_err(|err| {
+        format!(
+            "Failed to write the instruction profile to {}: {}",
+            path.display(),
+            err
+        )
     })
 }
 
@@ -13,6 +13,7 @@ const ARG_CONF: &str = "config";
 const ARG_LOG_FILE: &str = "log-file";
 const ARG_MESSAGES: &str = "messages";
 const ARG_EXTRA_BATCHES: &str = "extra-batches";
+const ARG_INSTRUCTION_PROFILE: &str = "instruction-profile";
 
 fn main() -> Result<(), String> {
     // Check if `drun` is running in the canister sandbox mode where it waits
@@ -44,6 +45,7 @@ async fn drun_main() -> Result<(), String> {
         });
 
         let log_file = matches.value_of(ARG_LOG_FILE).map(PathBuf::from);
+        let instruction_profile = matches.value_of(ARG_INSTRUCTION_PROFILE).map(PathBuf::from);
 
         let extra_batches = matches
             .value_of(ARG_EXTRA_BATCHES)
@@ -60,6 +62,7 @@ async fn drun_main() -> Result<(), String> {
             cfg,
             extra_batches,
             log_file,
+            instruction_profile,
         };
         run_drun(uo)
     })
@@ -108,5 +111,16 @@ fn get_arg_matches() -> ArgMatches {
                 .help("Log file for the run (default: None).")
                 .takes_value(true),
         )
+        .arg(
+            Arg::new(ARG_INSTRUCTION_PROFILE)
+                .long(ARG_INSTRUCTION_PROFILE)
+                .value_name("profile_file")
+                .help(
+                    "Enables instruction profiling and writes the instructions executed by \
+                     each canister function to this file in the folded stack format \
+                     (default: None).",
+                )
+                .takes_value(true),
+        )
         .get_matches()
 }
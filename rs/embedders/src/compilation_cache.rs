@@ -1,18 +1,34 @@
 use std::{
     collections::HashMap,
+    path::Path,
     sync::{Arc, RwLock},
 };
 
 use crate::SerializedModule;
-use ic_config::flag_status::FlagStatus;
+use ic_config::{embedders::Config as EmbeddersConfig, flag_status::FlagStatus};
 use ic_interfaces::execution_environment::HypervisorResult;
+use ic_logger::{warn, ReplicaLogger};
+use ic_metrics::MetricsRegistry;
+use ic_types::NumBytes;
 use ic_wasm_types::{CanisterModule, WasmHash};
 
+mod disk_cache;
+
+#[cfg(test)]
+mod tests;
+
+use disk_cache::DiskCache;
+
 /// Stores the serialized modules of wasm code that has already been compiled so
 /// that it can be used again without recompiling.
+///
+/// Successfully compiled modules can optionally be persisted on disk, in which
+/// case they survive replica restarts. Compilation errors are only kept in
+/// memory.
 pub struct CompilationCache {
     enabled: FlagStatus,
     cache: RwLock<HashMap<WasmHash, HypervisorResult<Arc<SerializedModule>>>>,
+    disk_cache: Option<DiskCache>,
 }
 
 impl CompilationCache {
@@ -20,6 +36,47 @@ impl CompilationCache {
         Self {
             enabled,
             cache: RwLock::new(HashMap::new()),
+            disk_cache: None,
+        }
+    }
+
+    /// Creates a cache that is backed by a persistent store in `dir` holding at
+    /// most `capacity` bytes. If the store cannot be opened, the error is
+    /// logged and the cache falls back to memory only.
+    pub fn new_persistent(
+        enabled: FlagStatus,
+        dir: &Path,
+        capacity: NumBytes,
+        embedder_config: &EmbeddersConfig,
+        metrics_registry: &MetricsRegistry,
+        log: ReplicaLogger,
+    ) -> Self {
+        let disk_cache = if enabled == FlagStatus::Enabled {
+            match DiskCache::open(
+                dir,
+                capacity,
+                embedder_config,
+                metrics_registry,
+                log.clone(),
+            ) {
+                Ok(disk_cache) => Some(disk_cache),
+                Err(err) => {
+                    warn!(
+                        log,
+                        "Failed to open persistent compilation cache at {}: {}",
+                        dir.display(),
+                        err
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        Self {
+            enabled,
+            cache: RwLock::new(HashMap::new()),
+            disk_cache,
         }
     }
 
@@ -29,10 +86,16 @@ impl CompilationCache {
         serialized_module: HypervisorResult<Arc<SerializedModule>>,
     ) {
         if self.enabled == FlagStatus::Enabled {
+            let wasm_hash = WasmHash::from(canister_module);
+            if let (Some(disk_cache), Ok(serialized_module)) =
+                (&self.disk_cache, &serialized_module)
+            {
+                disk_cache.insert(&wasm_hash, serialized_module);
+            }
             self.cache
                 .write()
                 .unwrap()
-                .insert(WasmHash::from(canister_module), serialized_module);
+                .insert(wasm_hash, serialized_module);
         }
     }
 
@@ -41,11 +104,25 @@ impl CompilationCache {
         canister_module: &CanisterModule,
     ) -> Option<HypervisorResult<Arc<SerializedModule>>> {
         if self.enabled == FlagStatus::Enabled {
-            self.cache
+            let wasm_hash = WasmHash::from(canister_module);
+            let cached = self
+                .cache
                 .read()
                 .unwrap()
-                .get(&WasmHash::from(canister_module))
-                .map(|o| o.as_ref().map(Arc::clone).map_err(|e| e.clone()))
+                .get(&wasm_hash)
+                .map(|o| o.as_ref().map(Arc::clone).map_err(|e| e.clone()));
+            if cached.is_some() {
+                if let Some(disk_cache) = &self.disk_cache {
+                    disk_cache.touch(&wasm_hash);
+                }
+                return cached;
+            }
+            let serialized_module = self.disk_cache.as_ref()?.get(&wasm_hash)?;
+            self.cache
+                .write()
+                .unwrap()
+                .insert(wasm_hash, Ok(Arc::clone(&serialized_module)));
+            Some(Ok(serialized_module))
         } else {
             None
         }
@@ -53,6 +130,9 @@ impl CompilationCache {
 
     #[doc(hidden)]
     pub fn clear_for_testing(&self) {
-        self.cache.write().unwrap().clear()
+        self.cache.write().unwrap().clear();
+        if let Some(disk_cache) = &self.disk_cache {
+            disk_cache.clear();
+        }
     }
 }
@@ -0,0 +1,476 @@
+//! An on-disk, size-bounded store of serialized modules that backs the
+//! in-memory `CompilationCache` so that compiled canisters survive replica
+//! restarts and upgrades.
+//!
+//! Entries live in `<root>/compiled_modules/<config hash>/<wasm hash>.bin`,
+//! where the config hash covers the replica version, the replica binary hash
+//! and the embedder configuration. Entries written by a different replica
+//! binary or configuration are never read and are removed when the cache is
+//! opened. Only config directories inside `compiled_modules` are ever removed,
+//! so the cache root can be shared with other data. If the binary hash is
+//! unknown, the cache cannot tell replica builds apart and refuses to open.
+//!
+//! Entries are written to a unique temporary file without holding the index
+//! lock and are only added to the index once they are complete.
+//!
+//! Every file starts with a fixed header followed by the bincode-encoded
+//! `SerializedModule`:
+//!
+//! ```text
+//! magic (4 bytes) | format version (u32 LE) | SHA-256 of payload (32 bytes) | payload
+//! ```
+//!
+//! The checksum is verified on every read. A file that fails the check is
+//! deleted and reported as a miss, so the module gets recompiled.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryInto,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::SystemTime,
+};
+
+use ic_config::{embedders::Config as EmbeddersConfig, flag_status::FlagStatus};
+use ic_crypto_sha::Sha256;
+use ic_logger::{info, warn, ReplicaLogger};
+use ic_metrics::MetricsRegistry;
+use ic_types::{replica_version::REPLICA_BINARY_HASH, NumBytes, ReplicaVersion};
+use ic_utils::fs::write_atomically_using_tmp_file;
+use ic_wasm_types::WasmHash;
+use prometheus::{IntCounter, IntCounterVec, IntGauge};
+
+use crate::SerializedModule;
+
+const MAGIC: &[u8; 4] = b"ICMC";
+const FORMAT_VERSION: u32 = 1;
+const HEADER_LEN: usize = MAGIC.len() + std::mem::size_of::<u32>() + 32;
+const ENTRY_EXTENSION: &str = "bin";
+const CACHE_SUBDIR: &str = "compiled_modules";
+
+const LOOKUP_HIT: &str = "hit";
+const LOOKUP_MISS: &str = "miss";
+const LOOKUP_CORRUPTED: &str = "corrupted";
+
+struct DiskCacheMetrics {
+    lookups: IntCounterVec,
+    evictions: IntCounter,
+    write_errors: IntCounter,
+    size_bytes: IntGauge,
+    entries: IntGauge,
+}
+
+impl DiskCacheMetrics {
+    fn new(metrics_registry: &MetricsRegistry) -> Self {
+        Self {
+            lookups: metrics_registry.int_counter_vec(
+                "compilation_cache_disk_lookups_total",
+                "Number of lookups in the persistent compilation cache, by result.",
+                &["result"],
+            ),
+            evictions: metrics_registry.int_counter(
+                "compilation_cache_disk_evictions_total",
+                "Number of entries evicted from the persistent compilation cache.",
+            ),
+            write_errors: metrics_registry.int_counter(
+                "compilation_cache_disk_write_errors_total",
+                "Number of failed writes to the persistent compilation cache.",
+            ),
+            size_bytes: metrics_registry.int_gauge(
+                "compilation_cache_disk_size_bytes",
+                "Total size of the entries in the persistent compilation cache.",
+            ),
+            entries: metrics_registry.int_gauge(
+                "compilation_cache_disk_entries",
+                "Number of entries in the persistent compilation cache.",
+            ),
+        }
+    }
+}
+
+struct Entry {
+    size: u64,
+    last_used: u64,
+}
+
+/// Bookkeeping needed for LRU eviction. The files themselves are the source of
+/// truth, this index only mirrors their sizes and access order.
+#[derive(Default)]
+struct Index {
+    entries: HashMap<WasmHash, Entry>,
+    by_last_used: BTreeMap<u64, WasmHash>,
+    total_size: u64,
+    clock: u64,
+}
+
+impl Index {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn touch(&mut self, wasm_hash: &WasmHash) {
+        let now = self.tick();
+        if let Some(entry) = self.entries.get_mut(wasm_hash) {
+            self.by_last_used.remove(&entry.last_used);
+            entry.last_used = now;
+            self.by_last_used.insert(now, wasm_hash.clone());
+        }
+    }
+
+    fn insert(&mut self, wasm_hash: WasmHash, size: u64) {
+        self.remove(&wasm_hash);
+        let last_used = self.tick();
+        self.by_last_used.insert(last_used, wasm_hash.clone());
+        self.entries.insert(wasm_hash, Entry { size, last_used });
+        self.total_size += size;
+    }
+
+    fn remove(&mut self, wasm_hash: &WasmHash) {
+        if let Some(entry) = self.entries.remove(wasm_hash) {
+            self.by_last_used.remove(&entry.last_used);
+            self.total_size -= entry.size;
+        }
+    }
+
+    fn least_recently_used(&self) -> Option<WasmHash> {
+        self.by_last_used.values().next().cloned()
+    }
+}
+
+/// Persistent store of serialized modules with LRU eviction.
+pub(crate) struct DiskCache {
+    dir: PathBuf,
+    capacity: u64,
+    index: Mutex<Index>,
+    // Makes the names of temporary files unique among concurrent writers.
+    next_tmp_id: AtomicU64,
+    metrics: DiskCacheMetrics,
+    log: ReplicaLogger,
+}
+
+impl DiskCache {
+    /// Opens the cache rooted at `root`, creating it if necessary. Entries
+    /// written under a different replica binary or embedder configuration
+    /// are deleted, and the remaining ones are trimmed to `capacity`. Fails
+    /// if the replica binary hash is not known.
+    pub(crate) fn open(
+        root: &Path,
+        capacity: NumBytes,
+        embedder_config: &EmbeddersConfig,
+        metrics_registry: &MetricsRegistry,
+        log: ReplicaLogger,
+    ) -> io::Result<Self> {
+        let config_dir = match config_hash(embedder_config) {
+            Some(config_hash) => hex::encode(config_hash),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "the replica binary hash is unknown",
+                ))
+            }
+        };
+        let cache_dir = root.join(CACHE_SUBDIR);
+        remove_stale_config_dirs(&cache_dir, &config_dir)?;
+        let dir = cache_dir.join(config_dir);
+        fs::create_dir_all(&dir)?;
+
+        let cache = Self {
+            index: Mutex::new(load_index(&dir, &log)?),
+            next_tmp_id: AtomicU64::new(0),
+            dir,
+            capacity: capacity.get(),
+            metrics: DiskCacheMetrics::new(metrics_registry),
+            log,
+        };
+        {
+            let mut index = cache.index.lock().unwrap();
+            cache.evict(&mut index, 0);
+            cache.update_gauges(&index);
+            info!(
+                cache.log,
+                "Opened persistent compilation cache at {} with {} entries ({} bytes)",
+                cache.dir.display(),
+                index.entries.len(),
+                index.total_size
+            );
+        }
+        Ok(cache)
+    }
+
+    /// Returns the module stored for `wasm_hash`, if any. Entries that cannot
+    /// be read or fail the integrity check are removed.
+    pub(crate) fn get(&self, wasm_hash: &WasmHash) -> Option<Arc<SerializedModule>> {
+        if !self.index.lock().unwrap().entries.contains_key(wasm_hash) {
+            self.metrics.lookups.with_label_values(&[LOOKUP_MISS]).inc();
+            return None;
+        }
+
+        // The file is read without holding the lock. If it gets evicted in
+        // the meantime the read fails and the lookup is counted as a miss.
+        let path = self.entry_path(wasm_hash);
+        let result = fs::read(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| decode_entry(&bytes));
+
+        let mut index = self.index.lock().unwrap();
+        match result {
+            Ok(serialized_module) => {
+                index.touch(wasm_hash);
+                self.metrics.lookups.with_label_values(&[LOOKUP_HIT]).inc();
+                Some(Arc::new(serialized_module))
+            }
+            Err(err) => {
+                if index.entries.contains_key(wasm_hash) {
+                    warn!(
+                        self.log,
+                        "Removing invalid compilation cache entry {}: {}",
+                        path.display(),
+                        err
+                    );
+                    let _ = fs::remove_file(&path);
+                    index.remove(wasm_hash);
+                    self.update_gauges(&index);
+                    self.metrics
+                        .lookups
+                        .with_label_values(&[LOOKUP_CORRUPTED])
+                        .inc();
+                } else {
+                    self.metrics.lookups.with_label_values(&[LOOKUP_MISS]).inc();
+                }
+                None
+            }
+        }
+    }
+
+    /// Marks the entry for `wasm_hash` as recently used without reading it,
+    /// e.g. because it was served from memory.
+    pub(crate) fn touch(&self, wasm_hash: &WasmHash) {
+        self.index.lock().unwrap().touch(wasm_hash);
+    }
+
+    /// Stores `serialized_module` under `wasm_hash`, evicting least recently
+    /// used entries if the cache would exceed its capacity. Modules without
+    /// serialized bytes and modules larger than the whole cache are skipped.
+    pub(crate) fn insert(&self, wasm_hash: &WasmHash, serialized_module: &SerializedModule) {
+        if serialized_module.bytes.is_empty() {
+            return;
+        }
+        let bytes = match encode_entry(serialized_module) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                warn!(
+                    self.log,
+                    "Failed to encode compilation cache entry: {}", err
+                );
+                self.metrics.write_errors.inc();
+                return;
+            }
+        };
+        let size = bytes.len() as u64;
+        if size > self.capacity {
+            return;
+        }
+
+        // The file is written without holding the lock, so that lookups and
+        // other inserts are not blocked by the write. Space is made for the
+        // entry once it is complete.
+        let path = self.entry_path(wasm_hash);
+        let tmp_path = path.with_extension(format!(
+            "{}.tmp",
+            self.next_tmp_id.fetch_add(1, Ordering::Relaxed)
+        ));
+        if let Err(err) = write_atomically_using_tmp_file(&path, &tmp_path, |w| w.write_all(&bytes))
+        {
+            warn!(
+                self.log,
+                "Failed to write compilation cache entry {}: {}",
+                path.display(),
+                err
+            );
+            self.metrics.write_errors.inc();
+            return;
+        }
+
+        let mut index = self.index.lock().unwrap();
+        index.remove(wasm_hash);
+        self.evict(&mut index, size);
+        index.insert(wasm_hash.clone(), size);
+        self.update_gauges(&index);
+    }
+
+    /// Removes all entries from disk.
+    pub(crate) fn clear(&self) {
+        let mut index = self.index.lock().unwrap();
+        while let Some(wasm_hash) = index.least_recently_used() {
+            let _ = fs::remove_file(self.entry_path(&wasm_hash));
+            index.remove(&wasm_hash);
+        }
+        self.update_gauges(&index);
+    }
+
+    /// Evicts least recently used entries until `additional` bytes fit into
+    /// the capacity.
+    fn evict(&self, index: &mut Index, additional: u64) {
+        while index.total_size + additional > self.capacity {
+            let wasm_hash = match index.least_recently_used() {
+                Some(wasm_hash) => wasm_hash,
+                None => break,
+            };
+            let path = self.entry_path(&wasm_hash);
+            if let Err(err) = fs::remove_file(&path) {
+                if err.kind() != io::ErrorKind::NotFound {
+                    warn!(
+                        self.log,
+                        "Failed to evict compilation cache entry {}: {}",
+                        path.display(),
+                        err
+                    );
+                }
+            }
+            index.remove(&wasm_hash);
+            self.metrics.evictions.inc();
+        }
+    }
+
+    fn update_gauges(&self, index: &Index) {
+        self.metrics.size_bytes.set(index.total_size as i64);
+        self.metrics.entries.set(index.entries.len() as i64);
+    }
+
+    fn entry_path(&self, wasm_hash: &WasmHash) -> PathBuf {
+        self.dir
+            .join(hex::encode(wasm_hash.to_vec()))
+            .with_extension(ENTRY_EXTENSION)
+    }
+}
+
+/// Hash identifying the code that produced a serialized module. Modules are
+/// only valid for the exact wasmtime build and instrumentation that produced
+/// them, both of which are pinned by the replica binary hash. The replica
+/// version alone is not enough, as it falls back to the package version.
+/// Returns `None` if the binary hash is unknown.
+fn config_hash(embedder_config: &EmbeddersConfig) -> Option<[u8; 32]> {
+    let binary_hash = REPLICA_BINARY_HASH.get()?;
+    let mut hasher = Sha256::new();
+    hasher.write(ReplicaVersion::default().as_ref().as_bytes());
+    hasher.write(binary_hash.as_bytes());
+    // Instruction profiling changes the exported globals of the compiled
+    // modules, so entries must never be shared across the two settings. The
+    // flag is also part of the serialized config, but is hashed explicitly so
+    // that this does not depend on the config's serialization.
+    hasher.write(&[
+        (embedder_config.feature_flags.instruction_profiling == FlagStatus::Enabled) as u8,
+    ]);
+    hasher
+        .write(&bincode::serialize(embedder_config).expect("Failed to serialize embedder config"));
+    Some(hasher.finish())
+}
+
+/// Removes the config directories in `cache_dir` other than `current`. Only
+/// directories named like a config hash are touched.
+fn remove_stale_config_dirs(cache_dir: &Path, current: &str) -> io::Result<()> {
+    if !cache_dir.exists() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(cache_dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let is_config_dir = name
+            .to_str()
+            .map_or(false, |name| name.len() == 64 && hex::decode(name).is_ok());
+        if is_config_dir && name != current && entry.path().is_dir() {
+            fs::remove_dir_all(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// Rebuilds the LRU index from the files in `dir`, using modification times as
+/// the initial access order. Leftover temporary files are deleted. Anything
+/// that is not a file is skipped, and failing to delete a file is not fatal.
+fn load_index(dir: &Path, log: &ReplicaLogger) -> io::Result<Index> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let path = entry.path();
+        match parse_entry_name(&path) {
+            Some(wasm_hash) => {
+                let metadata = entry.metadata()?;
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                files.push((modified, wasm_hash, metadata.len()));
+            }
+            None => {
+                if let Err(err) = fs::remove_file(&path) {
+                    warn!(
+                        log,
+                        "Failed to remove {} from the compilation cache: {}",
+                        path.display(),
+                        err
+                    );
+                }
+            }
+        }
+    }
+    files.sort_by_key(|(modified, _, _)| *modified);
+
+    let mut index = Index::default();
+    for (_, wasm_hash, size) in files {
+        index.insert(wasm_hash, size);
+    }
+    Ok(index)
+}
+
+fn parse_entry_name(path: &Path) -> Option<WasmHash> {
+    if path.extension()? != ENTRY_EXTENSION {
+        return None;
+    }
+    let bytes = hex::decode(path.file_stem()?.to_str()?).ok()?;
+    let bytes: [u8; 32] = bytes.as_slice().try_into().ok()?;
+    Some(WasmHash::from(bytes))
+}
+
+fn encode_entry(serialized_module: &SerializedModule) -> Result<Vec<u8>, String> {
+    let payload = bincode::serialize(serialized_module).map_err(|err| err.to_string())?;
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&Sha256::hash(&payload));
+    bytes.extend_from_slice(&payload);
+    Ok(bytes)
+}
+
+fn decode_entry(bytes: &[u8]) -> Result<SerializedModule, String> {
+    if bytes.len() < HEADER_LEN {
+        return Err(format!("entry is too short: {} bytes", bytes.len()));
+    }
+    let (magic, rest) = bytes.split_at(MAGIC.len());
+    if magic != MAGIC {
+        return Err("bad magic".to_string());
+    }
+    let (version, rest) = rest.split_at(std::mem::size_of::<u32>());
+    let version = u32::from_le_bytes(version.try_into().unwrap());
+    if version != FORMAT_VERSION {
+        return Err(format!("unsupported format version {}", version));
+    }
+    let (checksum, payload) = rest.split_at(32);
+    if checksum != Sha256::hash(payload) {
+        return Err("checksum mismatch".to_string());
+    }
+    let serialized_module: SerializedModule =
+        bincode::deserialize(payload).map_err(|err| err.to_string())?;
+    // An empty module is never written, so finding one means the entry was
+    // not produced by `insert`.
+    if serialized_module.bytes.is_empty() {
+        return Err("entry contains no module bytes".to_string());
+    }
+    Ok(serialized_module)
+}
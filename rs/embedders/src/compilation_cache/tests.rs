@@ -0,0 +1,207 @@
+use std::{fs, path::Path, sync::Arc};
+
+use super::CompilationCache;
+use crate::{wasm_utils::compile, SerializedModule, WasmtimeEmbedder};
+use ic_config::{embedders::Config as EmbeddersConfig, flag_status::FlagStatus};
+use ic_logger::replica_logger::no_op_logger;
+use ic_metrics::MetricsRegistry;
+use ic_test_utilities_metrics::{fetch_int_counter, fetch_int_counter_vec, metric_vec};
+use ic_types::{replica_version::REPLICA_BINARY_HASH, NumBytes};
+use ic_wasm_types::{BinaryEncodedWasm, CanisterModule};
+
+fn canister_module(wat: &str) -> CanisterModule {
+    CanisterModule::new(wabt::wat2wasm(wat).unwrap())
+}
+
+fn compile_module(canister_module: &CanisterModule) -> Arc<SerializedModule> {
+    let embedder = WasmtimeEmbedder::new(EmbeddersConfig::default(), no_op_logger());
+    let wasm = BinaryEncodedWasm::new(canister_module.as_slice().to_vec());
+    let (_, result) = compile(&embedder, &wasm);
+    Arc::new(result.unwrap().1)
+}
+
+fn persistent_cache(
+    dir: &Path,
+    capacity: NumBytes,
+    metrics_registry: &MetricsRegistry,
+) -> CompilationCache {
+    // The persistent cache refuses to open without a binary hash.
+    let _ = REPLICA_BINARY_HASH.set("test".to_string());
+    CompilationCache::new_persistent(
+        FlagStatus::Enabled,
+        dir,
+        capacity,
+        &EmbeddersConfig::default(),
+        metrics_registry,
+        no_op_logger(),
+    )
+}
+
+fn entry_files(dir: &Path) -> Vec<std::path::PathBuf> {
+    let mut files = vec![];
+    for config_dir in fs::read_dir(dir.join("compiled_modules")).unwrap() {
+        for entry in fs::read_dir(config_dir.unwrap().path()).unwrap() {
+            files.push(entry.unwrap().path());
+        }
+    }
+    files
+}
+
+const MODULE_A: &str = r#"(module (func (export "canister_update a")))"#;
+const MODULE_B: &str = r#"(module (func (export "canister_update b")))"#;
+const MODULE_C: &str = r#"(module (func (export "canister_update c")))"#;
+
+#[test]
+fn module_survives_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let module = canister_module(MODULE_A);
+    let compiled = compile_module(&module);
+
+    {
+        let cache = persistent_cache(dir.path(), NumBytes::new(1 << 30), &MetricsRegistry::new());
+        cache.insert(&module, Ok(Arc::clone(&compiled)));
+    }
+
+    let metrics_registry = MetricsRegistry::new();
+    let cache = persistent_cache(dir.path(), NumBytes::new(1 << 30), &metrics_registry);
+    let reloaded = cache.get(&module).unwrap().unwrap();
+    assert_eq!(reloaded.exported_functions, compiled.exported_functions);
+    assert_eq!(reloaded.compilation_cost, compiled.compilation_cost);
+
+    // The reloaded bytes must be loadable by wasmtime.
+    let embedder = WasmtimeEmbedder::new(EmbeddersConfig::default(), no_op_logger());
+    embedder.deserialize_module(&reloaded.bytes).unwrap();
+
+    assert_eq!(
+        fetch_int_counter_vec(&metrics_registry, "compilation_cache_disk_lookups_total"),
+        metric_vec(&[(&[("result", "hit")], 1)]),
+    );
+}
+
+#[test]
+fn corrupted_entry_is_removed() {
+    let dir = tempfile::tempdir().unwrap();
+    let module = canister_module(MODULE_A);
+    {
+        let cache = persistent_cache(dir.path(), NumBytes::new(1 << 30), &MetricsRegistry::new());
+        cache.insert(&module, Ok(compile_module(&module)));
+    }
+
+    let files = entry_files(dir.path());
+    assert_eq!(files.len(), 1);
+    let mut bytes = fs::read(&files[0]).unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xff;
+    fs::write(&files[0], bytes).unwrap();
+
+    let metrics_registry = MetricsRegistry::new();
+    let cache = persistent_cache(dir.path(), NumBytes::new(1 << 30), &metrics_registry);
+    assert!(cache.get(&module).is_none());
+    assert!(entry_files(dir.path()).is_empty());
+    assert_eq!(
+        fetch_int_counter_vec(&metrics_registry, "compilation_cache_disk_lookups_total"),
+        metric_vec(&[(&[("result", "corrupted")], 1)]),
+    );
+}
+
+#[test]
+fn least_recently_used_entry_is_evicted() {
+    let dir = tempfile::tempdir().unwrap();
+    let (a, b, c) = (
+        canister_module(MODULE_A),
+        canister_module(MODULE_B),
+        canister_module(MODULE_C),
+    );
+
+    // Measure the size of a single entry to size the cache for two of them.
+    {
+        let cache = persistent_cache(dir.path(), NumBytes::new(1 << 30), &MetricsRegistry::new());
+        cache.insert(&a, Ok(compile_module(&a)));
+    }
+    let entry_size = fs::metadata(&entry_files(dir.path())[0]).unwrap().len();
+    let capacity = NumBytes::new(entry_size * 5 / 2);
+
+    let metrics_registry = MetricsRegistry::new();
+    let cache = persistent_cache(dir.path(), capacity, &metrics_registry);
+    cache.insert(&b, Ok(compile_module(&b)));
+    // Reading `a` makes `b` the least recently used entry.
+    assert!(cache.get(&a).is_some());
+    cache.insert(&c, Ok(compile_module(&c)));
+    assert_eq!(
+        fetch_int_counter(&metrics_registry, "compilation_cache_disk_evictions_total"),
+        Some(1)
+    );
+
+    let cache = persistent_cache(dir.path(), capacity, &MetricsRegistry::new());
+    assert!(cache.get(&a).is_some());
+    assert!(cache.get(&b).is_none());
+    assert!(cache.get(&c).is_some());
+}
+
+#[test]
+fn disabled_cache_does_not_write_to_disk() {
+    let dir = tempfile::tempdir().unwrap();
+    let module = canister_module(MODULE_A);
+    let cache = CompilationCache::new_persistent(
+        FlagStatus::Disabled,
+        dir.path(),
+        NumBytes::new(1 << 30),
+        &EmbeddersConfig::default(),
+        &MetricsRegistry::new(),
+        no_op_logger(),
+    );
+    cache.insert(&module, Ok(compile_module(&module)));
+    assert!(fs::read_dir(dir.path()).unwrap().next().is_none());
+}
+
+#[test]
+fn opening_keeps_unrelated_files_in_root() {
+    let dir = tempfile::tempdir().unwrap();
+    let unrelated_file = dir.path().join("unrelated");
+    fs::write(&unrelated_file, b"keep me").unwrap();
+    let stale_config_dir = dir.path().join("compiled_modules").join("ab".repeat(32));
+    fs::create_dir_all(&stale_config_dir).unwrap();
+
+    let _cache = persistent_cache(dir.path(), NumBytes::new(1 << 30), &MetricsRegistry::new());
+    assert!(unrelated_file.exists());
+    assert!(!stale_config_dir.exists());
+}
+
+#[test]
+fn opening_skips_directories_in_the_cache() {
+    let dir = tempfile::tempdir().unwrap();
+    let module = canister_module(MODULE_A);
+    {
+        let cache = persistent_cache(dir.path(), NumBytes::new(1 << 30), &MetricsRegistry::new());
+        cache.insert(&module, Ok(compile_module(&module)));
+    }
+    let config_dir = entry_files(dir.path())[0].parent().unwrap().to_path_buf();
+    let stray_dir = config_dir.join("stray");
+    fs::create_dir(&stray_dir).unwrap();
+
+    let cache = persistent_cache(dir.path(), NumBytes::new(1 << 30), &MetricsRegistry::new());
+    assert!(cache.get(&module).is_some());
+    assert!(stray_dir.exists());
+}
+
+#[test]
+fn toggling_instruction_profiling_does_not_reuse_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    let module = canister_module(MODULE_A);
+    {
+        let cache = persistent_cache(dir.path(), NumBytes::new(1 << 30), &MetricsRegistry::new());
+        cache.insert(&module, Ok(compile_module(&module)));
+    }
+
+    let mut config = EmbeddersConfig::default();
+    config.feature_flags.instruction_profiling = FlagStatus::Enabled;
+    let cache = CompilationCache::new_persistent(
+        FlagStatus::Enabled,
+        dir.path(),
+        NumBytes::new(1 << 30),
+        &config,
+        &MetricsRegistry::new(),
+        no_op_logger(),
+    );
+    assert!(cache.get(&module).is_none());
+}
@@ -14,6 +14,12 @@ use crate::wasm_utils::{
 };
 
 /// A `wasmtime::Module` that has been serialized.
+///
+/// Instances obtained through `Deserialize` (e.g. from the sandbox IPC or the
+/// persistent compilation cache) must only come from a matching `Serialize`
+/// of an instance created by the `TryFrom` impl, since wasmtime trusts these
+/// bytes when loading the module. The persistent compilation cache enforces
+/// this with a checksum over every entry.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SerializedModuleBytes(#[serde(with = "serde_bytes")] Vec<u8>);
 
@@ -35,6 +41,12 @@ impl SerializedModuleBytes {
         Self(vec![])
     }
 
+    /// Returns true if this was created by `empty` rather than by serializing
+    /// a module.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     /// It is guaranteed to be safe to deserialize this array into a `wasmtime::Module`.
     pub fn as_slice(&self) -> &[u8] {
         // Serializing a module always includes the header "wasmtime-aot", so
@@ -215,6 +215,7 @@ impl WasmExecutor for WasmExecutorImpl {
                                 accessed_pages: 0,
                                 dirty_pages: 0,
                             },
+                            instruction_profile: vec![],
                         },
                         None,
                     ),
@@ -608,6 +609,7 @@ pub fn process(
                         accessed_pages: 0,
                         dirty_pages: 0,
                     },
+                    instruction_profile: vec![],
                 },
                 None,
                 Err(system_api),
@@ -618,11 +620,33 @@ pub fn process(
     // Set the instruction limit for the first slice.
     instance.set_instruction_counter(first_slice_instruction_limit.get() as i64);
 
+    // The profiling counters accumulate over all executions, so the profile
+    // of this execution is the difference of their values.
+    let instruction_profiling =
+        embedder.config().feature_flags.instruction_profiling == FlagStatus::Enabled;
+    let profiling_counters_before = if instruction_profiling {
+        instance.profiling_counters()
+    } else {
+        vec![]
+    };
+
     // Execute Wasm code until it finishes or exceeds the message instruction
     // limit. With deterministic time slicing, this call may execute multiple
     // slices before it returns.
     let run_result = instance.run(func_ref);
 
+    let instruction_profile = if instruction_profiling {
+        instance
+            .profiling_counters()
+            .into_iter()
+            .zip(profiling_counters_before)
+            .map(|((func_ix, after), (_, before))| (func_ix, after.wrapping_sub(before)))
+            .filter(|(_, instructions)| *instructions > 0)
+            .collect()
+    } else {
+        vec![]
+    };
+
     // Get the executed/remaining instructions for the message and the slice.
     let instruction_counter = instance.instruction_counter();
     let system_api = &instance.store_data().system_api;
@@ -707,6 +731,7 @@ pub fn process(
             allocated_bytes,
             allocated_message_bytes,
             instance_stats,
+            instruction_profile,
         },
         wasm_state_changes,
         Ok(instance),
@@ -11,6 +11,8 @@ use crate::{serialized_module::SerializedModule, CompilationResult, WasmtimeEmbe
 pub mod decoding;
 pub mod errors;
 pub mod instrumentation;
+pub mod profiling;
+mod simd;
 pub mod validation;
 mod wasm_module_builder;
 
@@ -24,7 +26,12 @@ fn validate_and_instrument(
     config: &EmbeddersConfig,
 ) -> HypervisorResult<(WasmValidationDetails, InstrumentationOutput)> {
     let wasm_validation_details = validate_wasm_binary(wasm, config)?;
-    let instrumentation_output = instrument(wasm, config.cost_to_compile_wasm_instruction)?;
+    let instrumentation_output = instrument(
+        wasm,
+        config.cost_to_compile_wasm_instruction,
+        config.feature_flags.instruction_profiling,
+        config.instruction_cost_schedule,
+    )?;
     Ok((wasm_validation_details, instrumentation_output))
 }
 
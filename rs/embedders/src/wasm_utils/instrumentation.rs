@@ -87,14 +87,39 @@
 //!
 //! Before every bulk memory operation, a call is made to the function which
 //! will decrement the instruction counter by the "size" argument of the bulk
-//! memory instruction.
+//! memory instruction. The fixed overhead of bulk memory instructions is part
+//! of the static cost of their basic block like for any other instruction.
 //!
 //! Note that we omit checking for the counter overflow at the non-reentrant
 //! blocks to optimize for performance. The maximal overflow in that case is
 //! bound by the length of the longest execution path consisting of
 //! non-reentrant basic blocks.
+//!
+//! If instruction profiling is enabled, every function additionally gets its
+//! own mutable `i64` global which is exported as
+//! `canister profiling_counter <function index>` and incremented by the same
+//! amounts as the instruction counter is decremented within that function:
+//!
+//! ```wasm
+//! global.get 7
+//! i64.const 2
+//! i64.add
+//! global.set 7
+//! ```
+//!
+//! The profiling code itself is not metered, so it does not change the number
+//! of instructions a message executes.
+//!
+//! The counters are followed by an immutable `i64` global holding their number
+//! and exported as `canister profiling_counter_count`. This allows recognizing
+//! persisted counters once profiling has been disabled again.
 
-use super::{errors::into_parity_wasm_error, wasm_module_builder::WasmModuleBuilder};
+use super::{
+    errors::into_parity_wasm_error,
+    simd::{deserialize_module, serialize_module, simd_opcode},
+    wasm_module_builder::WasmModuleBuilder,
+};
+use ic_config::{embedders::InstructionCostSchedule, flag_status::FlagStatus};
 use ic_replicated_state::canister_state::WASM_PAGE_SIZE_IN_BYTES;
 use ic_replicated_state::NumWasmPages;
 use ic_sys::{PageBytes, PageIndex, PAGE_SIZE};
@@ -105,13 +130,23 @@ use ic_wasm_types::{BinaryEncodedWasm, WasmInstrumentationError};
 use parity_wasm::builder;
 use parity_wasm::elements::{
     BlockType, BulkInstruction, ExportEntry, FuncBody, FunctionType, GlobalEntry, GlobalType,
-    InitExpr, Instruction, Instructions, Internal, Local, Module, Section, Type, ValueType,
+    ImportCountType, InitExpr, Instruction, Instructions, Internal, Local, Module, Section, Type,
+    ValueType,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeSet, HashMap};
 use std::convert::TryFrom;
 use std::iter::FromIterator;
 
+/// Prefix of the names under which the per-function instruction counters are
+/// exported if instruction profiling is enabled. The prefix is followed by the
+/// index of the function in the uninstrumented module.
+pub const PROFILING_COUNTER_PREFIX: &str = "canister profiling_counter ";
+
+/// Name under which the number of profiling counters is exported if
+/// instruction profiling is enabled. It is the last exported global.
+pub const PROFILING_COUNTER_COUNT: &str = "canister profiling_counter_count";
+
 // The indicies of injected functions.
 enum InjectedImports {
     OutOfInstructionsFn = 0,
@@ -119,8 +154,12 @@ enum InjectedImports {
     Count = 2,
 }
 
-// Gets the cost of an instruction.
-fn instruction_to_cost(i: &Instruction) -> u64 {
+// Gets the static cost of an instruction in the given schedule.
+//
+// Bulk memory instructions whose amount of work depends on their operands are
+// additionally charged one instruction for every byte (or table element) they
+// process in all schedules, see `injections`.
+fn instruction_to_cost(i: &Instruction, schedule: InstructionCostSchedule) -> u64 {
     match i {
         // The following instructions are mostly signaling the start/end of code blocks,
         // so we assign 0 cost to them.
@@ -129,7 +168,69 @@ fn instruction_to_cost(i: &Instruction) -> u64 {
         Instruction::End => 0,
         Instruction::Loop(_bt) => 0,
 
-        // Default cost of an instruction is 1.
+        _ => match schedule {
+            // Default cost of an instruction is 1.
+            InstructionCostSchedule::V1 => 1,
+            InstructionCostSchedule::V2 => instruction_to_cost_v2(i),
+        },
+    }
+}
+
+// Gets the static cost of an instruction that does not delimit a block in the
+// `V2` schedule.
+//
+// The static cost of the bulk memory instructions covers their fixed overhead:
+// all of them leave the compiled code to call into the runtime, and they
+// differ in how much they check and look up before doing any work.
+fn instruction_to_cost_v2(i: &Instruction) -> u64 {
+    if let Some(opcode) = simd_opcode(i) {
+        return simd_instruction_to_cost(opcode);
+    }
+    match i {
+        // Bulk memory instructions with a dynamic cost. `memory.copy` has to
+        // handle overlapping ranges, and the `init` instructions have to look up
+        // a passive segment and check whether it was dropped.
+        Instruction::Bulk(BulkInstruction::MemoryFill) => 8,
+        Instruction::Bulk(BulkInstruction::MemoryCopy) => 10,
+        Instruction::Bulk(BulkInstruction::MemoryInit(_)) => 12,
+        // Table elements are references that the runtime may have to
+        // initialize lazily, so the table instructions are more expensive.
+        Instruction::Bulk(BulkInstruction::TableCopy) => 16,
+        Instruction::Bulk(BulkInstruction::TableInit(_)) => 20,
+        // Dropping a segment only marks it as dropped.
+        Instruction::Bulk(BulkInstruction::MemoryDrop(_))
+        | Instruction::Bulk(BulkInstruction::TableDrop(_)) => 4,
+
+        _ => 1,
+    }
+}
+
+// Gets the static cost of the SIMD instruction with the given standard opcode
+// in the `V2` schedule.
+//
+// Most SIMD instructions compile to a single native instruction and cost one
+// like scalar instructions. The ones that compile to a sequence of native
+// instructions on common targets, or whose native instruction is slow, cost
+// more.
+fn simd_instruction_to_cost(opcode: u32) -> u64 {
+    match opcode {
+        // `f32x4.sqrt`, `f32x4.div`, `f64x2.sqrt` and `f64x2.div`.
+        0xe3 | 0xe7 | 0xef | 0xf3 => 4,
+        // `i8x16.shuffle` and `i8x16.swizzle`.
+        0x0d | 0x0e => 2,
+        // `i8x16.popcnt` and the `bitmask` instructions.
+        0x62 | 0x64 | 0x84 | 0xa4 | 0xc4 => 2,
+        // The shifts of 8-bit lanes.
+        0x6b..=0x6d => 2,
+        // The multiplications, including `i16x8.q15mulr_sat_s`,
+        // `i32x4.dot_i16x8_s` and the extending multiplications.
+        0x82 | 0x95 | 0x9c..=0x9f | 0xb5 | 0xba | 0xbc..=0xbf | 0xd5 | 0xdc..=0xdf => 2,
+        // The narrowing instructions.
+        0x65 | 0x66 | 0x85 | 0x86 => 2,
+        // `min` and `max` of floating point lanes, which have to handle NaNs.
+        0xe8 | 0xe9 | 0xf4 | 0xf5 => 2,
+        // The conversions between integer and floating point lanes.
+        0xf8..=0xff => 2,
         _ => 1,
     }
 }
@@ -355,16 +456,28 @@ pub struct ExportModuleData {
     pub start_fn_ix: Option<u32>,
 }
 
+// The global counting the instructions executed by a single function and a
+// scratch `i32` local of that function used to count dynamic costs.
+#[derive(Copy, Clone, Debug)]
+struct ProfilingCounter {
+    global_ix: u32,
+    scratch_local_ix: u32,
+}
+
 /// Takes a Wasm binary and inserts the instructions metering and memory grow
-/// instrumentation.
+/// instrumentation, charging instructions according to
+/// `instruction_cost_schedule`. If `instruction_profiling` is enabled, it also
+/// inserts per-function instruction counters.
 ///
 /// Returns an [`InstrumentationOutput`] or an error if the input binary could
 /// not be instrumented.
 pub(super) fn instrument(
     wasm: &BinaryEncodedWasm,
     cost_to_compile_wasm_instruction: NumInstructions,
+    instruction_profiling: FlagStatus,
+    instruction_cost_schedule: InstructionCostSchedule,
 ) -> Result<InstrumentationOutput, WasmInstrumentationError> {
-    let module = parity_wasm::deserialize_buffer::<Module>(wasm.as_slice()).map_err(|err| {
+    let (module, simd) = deserialize_module(wasm.as_slice()).map_err(|err| {
         WasmInstrumentationError::ParityDeserializeError(into_parity_wasm_error(err))
     })?;
     let mut module = inject_helper_functions(module);
@@ -384,39 +497,51 @@ pub(super) fn instrument(
         module.clear_start_section();
     }
 
-    // inject instructions counter decrementation
-    {
-        if let Some(code_section) = module.code_section_mut() {
-            for func_body in code_section.bodies_mut().iter_mut() {
-                let code = func_body.code_mut();
-                inject_metering(code, &export_module_data);
-            }
+    // Collect all the function types of the locally defined functions inside the
+    // module.
+    //
+    // The main reason to create this vector of function types is because we can't
+    // mix a mutable (to inject instructions) and immutable (to look up the function
+    // type) reference to the `code_section`.
+    let mut func_types = Vec::new();
+    if let Some(code_section) = module.code_section() {
+        let functions = module.function_section().unwrap().entries();
+        let types = module.type_section().unwrap().types();
+        for i in 0..code_section.bodies().len() {
+            let Type::Function(t) = &types[functions[i].type_ref() as usize];
+            func_types.push(t.clone());
         }
     }
 
-    {
-        // Collect all the function types of the locally defined functions inside the
-        // module.
-        //
-        // The main reason to create this vector of function types is because we can't
-        // mix a mutable (to inject instructions) and immutable (to look up the function
-        // type) reference to the `code_section`.
-        let mut func_types = Vec::new();
-        if let Some(code_section) = module.code_section() {
-            let functions = module.function_section().unwrap().entries();
-            let types = module.type_section().unwrap().types();
-            for i in 0..code_section.bodies().len() {
-                let Type::Function(t) = &types[functions[i].type_ref() as usize];
-                func_types.push(t.clone());
-            }
+    // inject instructions counter decrementation
+    let mut profiling_instruction_count = 0;
+    if let Some(code_section) = module.code_section_mut() {
+        for (func_ix, func_body) in code_section.bodies_mut().iter_mut().enumerate() {
+            let profiling_counter = match instruction_profiling {
+                FlagStatus::Enabled => Some(ProfilingCounter {
+                    // The profiling counters are added right after the
+                    // instructions counter.
+                    global_ix: export_module_data.instructions_counter_ix + 1 + func_ix as u32,
+                    scratch_local_ix: push_local(func_body, &func_types[func_ix], ValueType::I32),
+                }),
+                FlagStatus::Disabled => None,
+            };
+            let code = func_body.code_mut();
+            profiling_instruction_count += inject_metering(
+                code,
+                &export_module_data,
+                profiling_counter,
+                instruction_cost_schedule,
+            );
         }
-        // Inject `update_available_memory` to functions with `memory.grow`
-        // instructions.
-        if !func_types.is_empty() {
-            let func_bodies = module.code_section_mut().unwrap().bodies_mut();
-            for (func_ix, func_type) in func_types.into_iter().enumerate() {
-                inject_update_available_memory(&mut func_bodies[func_ix], &func_type);
-            }
+    }
+
+    // Inject `update_available_memory` to functions with `memory.grow`
+    // instructions.
+    if !func_types.is_empty() {
+        let func_bodies = module.code_section_mut().unwrap().bodies_mut();
+        for (func_ix, func_type) in func_types.iter().enumerate() {
+            inject_update_available_memory(&mut func_bodies[func_ix], func_type);
         }
     }
 
@@ -449,6 +574,9 @@ pub(super) fn instrument(
     let data = get_data(module.sections_mut());
     data.validate(NumWasmPages::from(initial_limit as usize))?;
 
+    // The injected profiling code is excluded, and the profiling counters are
+    // only added below, so that the compilation cost does not depend on whether
+    // profiling is enabled.
     let wasm_instruction_count = (module
         .code_section()
         .map(|code| {
@@ -467,9 +595,14 @@ pub(super) fn instrument(
                     .map(|global| global.init_expr().code().len())
                     .sum()
             })
-            .unwrap_or(0)) as u64;
+            .unwrap_or(0)
+        - profiling_instruction_count) as u64;
+
+    if instruction_profiling == FlagStatus::Enabled {
+        module = export_profiling_counters(module, func_types.len());
+    }
 
-    let result = parity_wasm::serialize(module).map_err(|err| {
+    let result = serialize_module(module, &simd).map_err(|err| {
         WasmInstrumentationError::ParitySerializeError(into_parity_wasm_error(err))
     })?;
     Ok(InstrumentationOutput {
@@ -544,6 +677,50 @@ pub fn export_additional_symbols(
     Ok(module)
 }
 
+// Adds and exports one profiling counter per locally defined function
+// followed by the number of counters. The counters are added right after the
+// instructions counter, which must be the last global of the module at this
+// point.
+fn export_profiling_counters(module: Module, num_local_functions: usize) -> Module {
+    let num_imported_functions =
+        module.import_count(ImportCountType::Function) as u32 - InjectedImports::Count as u32;
+    let first_global_ix = module.globals_space() as u32;
+    let mut mbuilder = builder::from_module(module);
+    for func_ix in 0..num_local_functions as u32 {
+        mbuilder = mbuilder.with_global(GlobalEntry::new(
+            GlobalType::new(ValueType::I64, true),
+            InitExpr::new(vec![Instruction::I64Const(0), Instruction::End]),
+        ));
+        mbuilder.push_export(ExportEntry::new(
+            format!(
+                "{}{}",
+                PROFILING_COUNTER_PREFIX,
+                num_imported_functions + func_ix
+            ),
+            Internal::Global(first_global_ix + func_ix),
+        ));
+    }
+    mbuilder = mbuilder.with_global(GlobalEntry::new(
+        GlobalType::new(ValueType::I64, false),
+        InitExpr::new(vec![
+            Instruction::I64Const(num_local_functions as i64),
+            Instruction::End,
+        ]),
+    ));
+    mbuilder.push_export(ExportEntry::new(
+        PROFILING_COUNTER_COUNT.to_string(),
+        Internal::Global(first_global_ix + num_local_functions as u32),
+    ));
+    mbuilder.build()
+}
+
+// Appends a local of the given type to the function and returns its index.
+fn push_local(func_body: &mut FuncBody, func_type: &FunctionType, value_type: ValueType) -> u32 {
+    let n_locals: u32 = func_body.locals().iter().map(Local::count).sum();
+    func_body.locals_mut().push(Local::new(1, value_type));
+    func_type.params().len() as u32 + n_locals
+}
+
 // Represents a hint about the context of each static cost injection point in
 // wasm.
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -606,8 +783,16 @@ impl InjectionPoint {
 // - we insert a function call before each dynamic cost instruction which
 //   performs an overflow check and then decrements the counter by the value at
 //   the top of the stack.
-fn inject_metering(code: &mut Instructions, export_data_module: &ExportModuleData) {
-    let points = injections(code.elements());
+//
+// If a profiling counter is given, it is incremented by the same amounts. The
+// function returns the number of injected profiling instructions.
+fn inject_metering(
+    code: &mut Instructions,
+    export_data_module: &ExportModuleData,
+    profiling_counter: Option<ProfilingCounter>,
+    schedule: InstructionCostSchedule,
+) -> usize {
+    let points = injections(code.elements(), schedule);
     let points = points.iter().filter(|point| match point.cost_detail {
         InjectionPointCostDetail::StaticCost {
             scope: Scope::ReentrantBlockStart,
@@ -619,8 +804,31 @@ fn inject_metering(code: &mut Instructions, export_data_module: &ExportModuleDat
     let orig_elems = code.elements();
     let mut elems: Vec<Instruction> = Vec::new();
     let mut last_injection_position = 0;
+    let mut profiling_instruction_count = 0;
     for point in points {
         elems.extend_from_slice(&orig_elems[last_injection_position..point.position]);
+        let profiling_elems = match (profiling_counter, point.cost_detail) {
+            (None, _) => vec![],
+            (Some(_), InjectionPointCostDetail::StaticCost { scope: _, cost: 0 }) => vec![],
+            (Some(counter), InjectionPointCostDetail::StaticCost { scope: _, cost }) => vec![
+                Instruction::GetGlobal(counter.global_ix),
+                Instruction::I64Const(cost as i64),
+                Instruction::I64Add,
+                Instruction::SetGlobal(counter.global_ix),
+            ],
+            // The dynamic cost is on top of the stack and has to stay there
+            // for the call that follows.
+            (Some(counter), InjectionPointCostDetail::DynamicCost) => vec![
+                Instruction::TeeLocal(counter.scratch_local_ix),
+                Instruction::GetLocal(counter.scratch_local_ix),
+                Instruction::I64ExtendUI32,
+                Instruction::GetGlobal(counter.global_ix),
+                Instruction::I64Add,
+                Instruction::SetGlobal(counter.global_ix),
+            ],
+        };
+        profiling_instruction_count += profiling_elems.len();
+        elems.extend(profiling_elems);
         match point.cost_detail {
             InjectionPointCostDetail::StaticCost { scope, cost } => {
                 elems.extend_from_slice(&[
@@ -650,6 +858,7 @@ fn inject_metering(code: &mut Instructions, export_data_module: &ExportModuleDat
     }
     elems.extend_from_slice(&orig_elems[last_injection_position..]);
     *code.elements_mut() = elems;
+    profiling_instruction_count
 }
 
 // Scans through a function and adds instrumentation after each `memory.grow`
@@ -672,9 +881,7 @@ fn inject_update_available_memory(func_body: &mut FuncBody, func_type: &Function
     // If we found any injection points, we need to instrument the code.
     if !injection_points.is_empty() {
         // We inject a local to cache the argument to `memory.grow`.
-        let n_locals: u32 = func_body.locals().iter().map(Local::count).sum();
-        let memory_local_ix = func_type.params().len() as u32 + n_locals;
-        func_body.locals_mut().push(Local::new(1, ValueType::I32));
+        let memory_local_ix = push_local(func_body, func_type, ValueType::I32);
         let code = func_body.code_mut();
         let orig_elems = code.elements_mut();
         let mut elems: Vec<Instruction> = Vec::new();
@@ -703,14 +910,15 @@ fn inject_update_available_memory(func_body: &mut FuncBody, func_type: &Function
 // with no branches) and before each bulk memory instruction. An injection point
 // contains a "hint" about the context of every basic block, specifically if
 // it's re-entrant or not.
-fn injections(code: &[Instruction]) -> Vec<InjectionPoint> {
+fn injections(code: &[Instruction], schedule: InstructionCostSchedule) -> Vec<InjectionPoint> {
     let mut res = Vec::new();
     let mut stack = Vec::new();
     use Instruction::*;
     // The function itself is a re-entrant code block.
     let mut curr = InjectionPoint::new_static_cost(0, Scope::ReentrantBlockStart);
     for (position, i) in code.iter().enumerate() {
-        curr.cost_detail.increment_cost(instruction_to_cost(i));
+        curr.cost_detail
+            .increment_cost(instruction_to_cost(i, schedule));
         match i {
             // Start of a re-entrant code block.
             Loop(_) => {
@@ -753,20 +961,28 @@ fn injections(code: &[Instruction]) -> Vec<InjectionPoint> {
     res
 }
 
-// Looks for the data section and if it is present, converts it to a vector of
-// tuples (heap offset, bytes) and then deletes the section.
+// Looks for the data section and if it is present, converts its active
+// segments to a vector of tuples (heap offset, bytes).
+//
+// If there are no passive segments, the data section is deleted together with
+// the data count section. Otherwise, it is kept for the passive segments, and
+// the active segments are replaced by empty passive segments so that the
+// indices of the segments do not change. The active segments are dropped right
+// after instantiation anyway, so the replaced segments behave the same.
 fn get_data(sections: &mut Vec<Section>) -> Segments {
     let mut res = Segments::default();
-    let mut data_section_idx = sections.len();
-    for (i, section) in sections.iter_mut().enumerate() {
+    let mut has_passive_segments = false;
+    for section in sections.iter_mut() {
         if let Section::Data(section) = section {
-            data_section_idx = i;
             res = section
                 .entries_mut()
                 .iter_mut()
-                .map(|segment| {
+                .filter_map(|segment| {
                     let offset = match segment.offset() {
-                        None => panic!("no offset found for the data segment"),
+                        None => {
+                            has_passive_segments = true;
+                            return None;
+                        }
                         Some(exp) => {
                             match exp.code() {
                                 [
@@ -779,13 +995,16 @@ fn get_data(sections: &mut Vec<Section>) -> Segments {
                             }
                         }
                     };
-                    (offset, std::mem::take(segment.value_mut()))
+                    let value = std::mem::take(segment.value_mut());
+                    *segment.offset_mut() = None;
+                    segment.set_passive(true);
+                    Some((offset, value))
                 })
                 .collect();
         }
     }
-    if data_section_idx < sections.len() {
-        sections.remove(data_section_idx);
+    if !has_passive_segments {
+        sections.retain(|section| !matches!(section, Section::Data(_) | Section::DataCount(_)));
     }
     res
 }
@@ -0,0 +1,128 @@
+//! Turns the per-function instruction counters injected by instrumentation
+//! when `FeatureFlags::instruction_profiling` is enabled into a report.
+//!
+//! The counters are exported mutable globals, so they are persisted together
+//! with the other exported globals of the canister and accumulate over all
+//! messages executed since the module was installed. Each counter holds the
+//! number of instructions executed by the function itself, excluding its
+//! callees and the cost of system API calls.
+
+use ic_config::{embedders::InstructionCostSchedule, flag_status::FlagStatus};
+use ic_replicated_state::Global;
+use ic_types::NumInstructions;
+use ic_wasm_types::{BinaryEncodedWasm, WasmInstrumentationError};
+use parity_wasm::elements::Internal;
+
+use super::errors::into_parity_wasm_error;
+use super::instrumentation::{instrument, PROFILING_COUNTER_PREFIX};
+use super::simd::deserialize_module;
+
+/// The number of instructions executed by a single function.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionProfile {
+    /// The function name from the `name` custom section, or `func[<index>]`
+    /// if the module does not name the function.
+    pub name: String,
+    pub instructions: NumInstructions,
+}
+
+/// Extracts the instruction counts of all functions that executed at least one
+/// instruction, ordered by decreasing count.
+///
+/// `wasm` is the uninstrumented module and `exported_globals` are the exported
+/// globals of a canister running that module with instruction profiling
+/// enabled.
+pub fn instruction_profile(
+    wasm: &BinaryEncodedWasm,
+    exported_globals: &[Global],
+) -> Result<Vec<FunctionProfile>, WasmInstrumentationError> {
+    // Instrument the module again to learn the positions of the counters
+    // among the exported globals.
+    // The cost schedule does not affect the positions.
+    let instrumented = instrument(
+        wasm,
+        NumInstructions::from(0),
+        FlagStatus::Enabled,
+        InstructionCostSchedule::V1,
+    )?;
+    let (instrumented, _) = deserialize_module(instrumented.binary.as_slice()).map_err(|err| {
+        WasmInstrumentationError::ParityDeserializeError(into_parity_wasm_error(err))
+    })?;
+    let counters: Vec<(u32, u64)> = instrumented
+        .export_section()
+        .map(|section| section.entries())
+        .unwrap_or_default()
+        .iter()
+        .filter(|export| matches!(export.internal(), Internal::Global(_)))
+        .enumerate()
+        .filter_map(|(position, export)| {
+            let func_ix: u32 = export
+                .field()
+                .strip_prefix(PROFILING_COUNTER_PREFIX)?
+                .parse()
+                .ok()?;
+            match exported_globals.get(position)? {
+                Global::I64(count) if *count > 0 => Some((func_ix, *count as u64)),
+                _ => None,
+            }
+        })
+        .collect();
+    function_profile(wasm, &counters)
+}
+
+/// Names the given per-function instruction counts and orders them like
+/// [`instruction_profile`]. Functions that executed no instructions are left
+/// out.
+///
+/// `wasm` is the uninstrumented module and `counters` are pairs of a function
+/// index and the number of instructions the function executed, e.g. the
+/// increments of the profiling counters during a single message.
+pub fn function_profile(
+    wasm: &BinaryEncodedWasm,
+    counters: &[(u32, u64)],
+) -> Result<Vec<FunctionProfile>, WasmInstrumentationError> {
+    let (names, _) = deserialize_module(wasm.as_slice()).map_err(|err| {
+        WasmInstrumentationError::ParityDeserializeError(into_parity_wasm_error(err))
+    })?;
+    let names = names.parse_names().unwrap_or_else(|(_, module)| module);
+    let function_names = names.names_section().and_then(|names| names.functions());
+
+    let mut profile: Vec<FunctionProfile> = counters
+        .iter()
+        .filter(|(_, instructions)| *instructions > 0)
+        .map(|(func_ix, instructions)| {
+            let name = function_names
+                .and_then(|names| names.names().get(*func_ix))
+                .cloned()
+                .unwrap_or_else(|| format!("func[{}]", func_ix));
+            FunctionProfile {
+                name,
+                instructions: NumInstructions::from(*instructions),
+            }
+        })
+        .collect();
+    profile.sort_by(|a, b| {
+        b.instructions
+            .cmp(&a.instructions)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    Ok(profile)
+}
+
+/// Renders a profile in the folded stack format understood by flamegraph
+/// tools, e.g. `inferno-flamegraph`. Every function becomes a single-frame
+/// stack below `root`.
+pub fn to_folded_stacks(root: &str, profile: &[FunctionProfile]) -> String {
+    profile
+        .iter()
+        .map(|function| {
+            format!(
+                "{};{} {}\n",
+                root,
+                // Frames are separated by `;` and the count by a space.
+                function.name.replace(';', ":").replace(' ', "_"),
+                function.instructions.get()
+            )
+        })
+        .collect()
+}
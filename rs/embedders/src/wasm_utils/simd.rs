@@ -0,0 +1,259 @@
+//! Support for SIMD instructions on top of `parity_wasm`.
+//!
+//! `parity_wasm` only knows a pre-standard encoding of the SIMD proposal, in
+//! which most instructions have different opcodes and immediates than in the
+//! standard encoding emitted by current toolchains. Modules are therefore
+//! decoded with [`deserialize_module`], which first replaces every SIMD
+//! instruction in the global and code sections by a placeholder, and encoded
+//! with [`serialize_module`], which writes the original instructions back in
+//! place of their placeholders.
+//!
+//! A placeholder is a pre-standard `v128.const` whose immediate holds the
+//! index of the original instruction and its standard opcode. It decodes to a
+//! single [`Instruction`]. SIMD instructions neither branch nor refer to
+//! functions or globals, so the instrumentation can treat placeholders like
+//! any other instruction.
+
+use parity_wasm::elements::{
+    Error, GlobalEntry, Instruction, Module, Section, Serialize, SimdInstruction, VarUint32,
+};
+use std::convert::TryInto;
+use wasmparser::{CodeSectionReader, GlobalSectionReader, OperatorsReader};
+
+/// All SIMD instructions start with this prefix.
+const SIMD_PREFIX: u8 = 0xfd;
+/// The opcode of `v128.const` in the encoding known to `parity_wasm`.
+const PLACEHOLDER_OPCODE: u8 = 0x02;
+
+const GLOBAL_SECTION_ID: u8 = 6;
+const CODE_SECTION_ID: u8 = 10;
+/// The size of the magic number and version preceding the sections.
+const HEADER_SIZE: usize = 8;
+const WASM_VERSION: u32 = 1;
+
+/// The original encodings of the SIMD instructions that were replaced by
+/// placeholders, in the order of their indices.
+#[derive(Default)]
+pub(crate) struct SimdInstructions(Vec<Vec<u8>>);
+
+impl SimdInstructions {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // Stores the given SIMD instruction and returns the encoding of its
+    // placeholder.
+    fn replace(&mut self, instruction: &[u8]) -> Result<Vec<u8>, Error> {
+        let (opcode, _) = read_u32(&instruction[1..])?;
+        let index = self.0.len() as u32;
+        self.0.push(instruction.to_vec());
+        let mut immediate = [0; 16];
+        immediate[..4].copy_from_slice(&index.to_le_bytes());
+        immediate[4..8].copy_from_slice(&opcode.to_le_bytes());
+        let mut placeholder = vec![SIMD_PREFIX, PLACEHOLDER_OPCODE];
+        placeholder.extend_from_slice(&immediate);
+        Ok(placeholder)
+    }
+
+    // Returns the original encoding of the SIMD instruction replaced by the
+    // given placeholder.
+    fn original(&self, index: u32) -> Result<&[u8], Error> {
+        self.0
+            .get(index as usize)
+            .map(Vec::as_slice)
+            .ok_or(Error::Other("unknown SIMD placeholder"))
+    }
+}
+
+// Returns the index of the original instruction and its standard opcode if the
+// given instruction is a placeholder.
+fn placeholder(instruction: &Instruction) -> Option<(u32, u32)> {
+    match instruction {
+        Instruction::Simd(SimdInstruction::V128Const(immediate)) => Some((
+            u32::from_le_bytes(immediate[..4].try_into().unwrap()),
+            u32::from_le_bytes(immediate[4..8].try_into().unwrap()),
+        )),
+        _ => None,
+    }
+}
+
+/// Returns the standard opcode of the SIMD instruction that the given
+/// instruction is a placeholder for, or `None` if it is not a placeholder.
+pub(crate) fn simd_opcode(instruction: &Instruction) -> Option<u32> {
+    placeholder(instruction).map(|(_, opcode)| opcode)
+}
+
+/// Decodes a module with `parity_wasm` after replacing its SIMD instructions
+/// by placeholders. The returned instructions are needed to encode the module
+/// again with [`serialize_module`].
+pub(crate) fn deserialize_module(wasm: &[u8]) -> Result<(Module, SimdInstructions), Error> {
+    let mut simd = SimdInstructions::default();
+    let wasm = replace_simd_instructions(wasm, &mut simd)?;
+    let module = parity_wasm::deserialize_buffer::<Module>(&wasm)?;
+    Ok((module, simd))
+}
+
+/// Encodes a module decoded with [`deserialize_module`], writing the original
+/// SIMD instructions in place of their placeholders.
+pub(crate) fn serialize_module(module: Module, simd: &SimdInstructions) -> Result<Vec<u8>, Error> {
+    if simd.is_empty() {
+        return parity_wasm::serialize(module);
+    }
+    let mut out = b"\0asm".to_vec();
+    out.extend_from_slice(&WASM_VERSION.to_le_bytes());
+    for section in module.into_sections() {
+        match section {
+            Section::Global(globals) => {
+                let mut content = vec![];
+                VarUint32::from(globals.entries().len()).serialize(&mut content)?;
+                for global in globals.entries() {
+                    write_global(global, simd, &mut content)?;
+                }
+                write_section(GLOBAL_SECTION_ID, &content, &mut out)?;
+            }
+            Section::Code(code) => {
+                let mut content = vec![];
+                VarUint32::from(code.bodies().len()).serialize(&mut content)?;
+                for body in code.bodies() {
+                    let mut encoded_body = vec![];
+                    VarUint32::from(body.locals().len()).serialize(&mut encoded_body)?;
+                    for local in body.locals().iter().copied() {
+                        local.serialize(&mut encoded_body)?;
+                    }
+                    write_instructions(body.code().elements(), simd, &mut encoded_body)?;
+                    VarUint32::from(encoded_body.len()).serialize(&mut content)?;
+                    content.extend(encoded_body);
+                }
+                write_section(CODE_SECTION_ID, &content, &mut out)?;
+            }
+            section => section.serialize(&mut out)?,
+        }
+    }
+    Ok(out)
+}
+
+fn write_global(
+    global: &GlobalEntry,
+    simd: &SimdInstructions,
+    out: &mut Vec<u8>,
+) -> Result<(), Error> {
+    let global_type = *global.global_type();
+    global_type.serialize(out)?;
+    write_instructions(global.init_expr().code(), simd, out)
+}
+
+fn write_instructions(
+    instructions: &[Instruction],
+    simd: &SimdInstructions,
+    out: &mut Vec<u8>,
+) -> Result<(), Error> {
+    for instruction in instructions {
+        match placeholder(instruction) {
+            Some((index, _)) => out.extend_from_slice(simd.original(index)?),
+            None => instruction.clone().serialize(out)?,
+        }
+    }
+    Ok(())
+}
+
+fn write_section(id: u8, content: &[u8], out: &mut Vec<u8>) -> Result<(), Error> {
+    out.push(id);
+    VarUint32::from(content.len()).serialize(out)?;
+    out.extend_from_slice(content);
+    Ok(())
+}
+
+// Copies the module, replacing the SIMD instructions in the global and code
+// sections by placeholders. All other sections are copied as they are.
+fn replace_simd_instructions(wasm: &[u8], simd: &mut SimdInstructions) -> Result<Vec<u8>, Error> {
+    if wasm.len() < HEADER_SIZE {
+        // Leave it to `parity_wasm` to report the error.
+        return Ok(wasm.to_vec());
+    }
+    let mut out = wasm[..HEADER_SIZE].to_vec();
+    let mut position = HEADER_SIZE;
+    while position < wasm.len() {
+        let id = wasm[position];
+        let (size, size_len) = read_u32(&wasm[position + 1..])?;
+        let start = position + 1 + size_len;
+        let end = start
+            .checked_add(size as usize)
+            .filter(|end| *end <= wasm.len())
+            .ok_or(Error::Other("section exceeds the module"))?;
+        let content = &wasm[start..end];
+        match id {
+            GLOBAL_SECTION_ID => write_section(id, &replace_in_globals(content, simd)?, &mut out)?,
+            CODE_SECTION_ID => write_section(id, &replace_in_code(content, simd)?, &mut out)?,
+            _ => out.extend_from_slice(&wasm[position..end]),
+        }
+        position = end;
+    }
+    Ok(out)
+}
+
+fn replace_in_globals(content: &[u8], simd: &mut SimdInstructions) -> Result<Vec<u8>, Error> {
+    let mut reader = GlobalSectionReader::new(content, 0).map_err(into_error)?;
+    let mut out = vec![];
+    VarUint32::from(reader.get_count()).serialize(&mut out)?;
+    for _ in 0..reader.get_count() {
+        let start = reader.original_position();
+        let global = reader.read().map_err(into_error)?;
+        let mut operators = global.init_expr.get_operators_reader();
+        // The global type precedes the init expression.
+        out.extend_from_slice(&content[start..operators.original_position()]);
+        replace_in_operators(content, &mut operators, simd, &mut out)?;
+    }
+    Ok(out)
+}
+
+fn replace_in_code(content: &[u8], simd: &mut SimdInstructions) -> Result<Vec<u8>, Error> {
+    let mut reader = CodeSectionReader::new(content, 0).map_err(into_error)?;
+    let mut out = vec![];
+    VarUint32::from(reader.get_count()).serialize(&mut out)?;
+    for _ in 0..reader.get_count() {
+        let body = reader.read().map_err(into_error)?;
+        let mut operators = body.get_operators_reader().map_err(into_error)?;
+        // The locals precede the instructions.
+        let mut encoded_body = content[body.range().start..operators.original_position()].to_vec();
+        replace_in_operators(content, &mut operators, simd, &mut encoded_body)?;
+        VarUint32::from(encoded_body.len()).serialize(&mut out)?;
+        out.extend(encoded_body);
+    }
+    Ok(out)
+}
+
+fn replace_in_operators(
+    content: &[u8],
+    operators: &mut OperatorsReader,
+    simd: &mut SimdInstructions,
+    out: &mut Vec<u8>,
+) -> Result<(), Error> {
+    while !operators.eof() {
+        let start = operators.original_position();
+        operators.read().map_err(into_error)?;
+        let instruction = &content[start..operators.original_position()];
+        if instruction[0] == SIMD_PREFIX {
+            out.extend(simd.replace(instruction)?);
+        } else {
+            out.extend_from_slice(instruction);
+        }
+    }
+    Ok(())
+}
+
+// Reads an unsigned LEB128 encoded `u32` and returns it together with the
+// number of bytes it occupies.
+fn read_u32(bytes: &[u8]) -> Result<(u32, usize), Error> {
+    let mut value: u32 = 0;
+    for (i, byte) in bytes.iter().take(5).enumerate() {
+        value |= ((byte & 0x7f) as u32) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    Err(Error::Other("invalid LEB128 encoded integer"))
+}
+
+fn into_error(err: wasmparser::BinaryReaderError) -> Error {
+    Error::HeapOther(err.to_string())
+}
@@ -2,8 +2,9 @@
 //! installed on the Internet Computer.
 
 use super::errors::into_parity_wasm_error;
+use super::simd::deserialize_module;
 
-use ic_config::embedders::Config as EmbeddersConfig;
+use ic_config::embedders::{Config as EmbeddersConfig, InstructionCostSchedule};
 use ic_replicated_state::canister_state::execution_state::{
     CustomSection, CustomSectionType, WasmMetadata,
 };
@@ -218,6 +219,26 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
                 },
             )],
         ),
+        (
+            "is_controller",
+            vec![(
+                API_VERSION_IC0,
+                FunctionSignature {
+                    param_types: vec![ValueType::I32, ValueType::I32],
+                    return_type: vec![ValueType::I32],
+                },
+            )],
+        ),
+        (
+            "canister_version",
+            vec![(
+                API_VERSION_IC0,
+                FunctionSignature {
+                    param_types: vec![],
+                    return_type: vec![ValueType::I64],
+                },
+            )],
+        ),
         // Inter-canister method calls
         (
             "public",
@@ -845,15 +866,14 @@ fn validate_export_section(module: &Module) -> Result<usize, WasmValidationError
     Ok(reserved_exports)
 }
 
-// Checks that offset-expressions in data sections consist of only one constant
-// expression. Required because of OP. See also:
+// Checks that offset-expressions in active data segments consist of only one
+// constant expression. Required because of OP. See also:
 // src/hypervisor/metering_injector/mod.rs
 fn validate_data_section(module: &Module) -> Result<(), WasmValidationError> {
     fn validate_segment(s: &DataSegment) -> Result<(), WasmValidationError> {
         match s.offset() {
-            None => Err(WasmValidationError::InvalidDataSection(
-                "Empty offset in data segment.".to_string(),
-            )),
+            // Passive segments are only copied by `memory.init`.
+            None => Ok(()),
             Some(expr) => match expr.code() {
                 [Instruction::I32Const(_), Instruction::End] => Ok(()),
                 _ => Err(WasmValidationError::InvalidDataSection(
@@ -874,7 +894,8 @@ fn validate_data_section(module: &Module) -> Result<(), WasmValidationError> {
         .try_for_each(validate_segment)
 }
 
-// Checks that no more than `max_globals` are defined in the module.
+// Checks that no more than `max_globals` are defined in the module and that
+// none of them is a `v128`, because globals are persisted as scalar values.
 fn validate_global_section(module: &Module, max_globals: usize) -> Result<(), WasmValidationError> {
     if let Some(section) = module.global_section() {
         let globals_defined = section.entries().len();
@@ -884,6 +905,15 @@ fn validate_global_section(module: &Module, max_globals: usize) -> Result<(), Wa
                 allowed: max_globals,
             });
         }
+        if section
+            .entries()
+            .iter()
+            .any(|global| global.global_type().content_type() == ValueType::V128)
+        {
+            return Err(WasmValidationError::InvalidGlobalSection(
+                "Globals of type v128 are not supported.".to_string(),
+            ));
+        }
     }
     Ok(())
 }
@@ -1048,10 +1078,12 @@ fn validate_code_section(module: &Module) -> Result<NumInstructions, WasmValidat
 }
 
 /// Sets Wasmtime flags to ensure deterministic execution.
+///
+/// NaN canonicalization also covers the floating point lanes of SIMD values.
 pub fn ensure_determinism(config: &mut Config) {
     config
         .wasm_threads(false)
-        .wasm_simd(false)
+        .wasm_simd(true)
         .cranelift_nan_canonicalization(true);
 }
 
@@ -1114,8 +1146,15 @@ pub(super) fn validate_wasm_binary(
     config: &EmbeddersConfig,
 ) -> Result<WasmValidationDetails, WasmValidationError> {
     can_compile(wasm)?;
-    let module = parity_wasm::deserialize_buffer::<Module>(wasm.as_slice())
+    let (module, simd) = deserialize_module(wasm.as_slice())
         .map_err(|err| WasmValidationError::ParityDeserializeError(into_parity_wasm_error(err)))?;
+    // The `V1` schedule charges one instruction for every SIMD instruction,
+    // which would under-meter the expensive ones.
+    if !simd.is_empty() && config.instruction_cost_schedule == InstructionCostSchedule::V1 {
+        return Err(WasmValidationError::UnsupportedWasmFeature(
+            "SIMD requires the V2 instruction cost schedule".to_string(),
+        ));
+    }
     let imports_details = validate_import_section(&module)?;
     let reserved_exports = validate_export_section(&module)?;
     validate_data_section(&module)?;
@@ -14,11 +14,11 @@ use wasmtime::{unix::StoreExt, Engine, Memory, Module, Mutability, OptLevel, Sto
 
 use host_memory::MmapMemoryCreator;
 pub use host_memory::WasmtimeMemoryCreator;
-use ic_config::embedders::Config as EmbeddersConfig;
+use ic_config::{embedders::Config as EmbeddersConfig, flag_status::FlagStatus};
 use ic_interfaces::execution_environment::{
     HypervisorError, HypervisorResult, InstanceStats, SystemApi, TrapCode,
 };
-use ic_logger::{debug, error, fatal, ReplicaLogger};
+use ic_logger::{debug, error, fatal, warn, ReplicaLogger};
 use ic_replicated_state::{EmbedderCache, Global, NumWasmPages, PageIndex, PageMap};
 use ic_sys::PAGE_SIZE;
 use ic_types::{
@@ -29,7 +29,10 @@ use ic_wasm_types::{BinaryEncodedWasm, WasmEngineError};
 use memory_tracker::{DirtyPageTracking, SigsegvMemoryTracker};
 use signal_stack::WasmtimeSignalStack;
 
-use crate::{serialized_module::SerializedModuleBytes, wasm_utils::validation::ensure_determinism};
+use crate::{
+    serialized_module::SerializedModuleBytes,
+    wasm_utils::{instrumentation::PROFILING_COUNTER_PREFIX, validation::ensure_determinism},
+};
 
 use super::InstanceRunResult;
 
@@ -235,6 +238,25 @@ impl WasmtimeEmbedder {
             .filter_map(|e| e.into_global())
             .collect();
 
+        // The profiling counters are always the last exported globals. If they
+        // were persisted while instruction profiling was enabled, they are
+        // dropped now that the module no longer exports them. Any other
+        // mismatch is fatal below.
+        let exported_globals = if exported_globals.len() > instance_globals.len()
+            && self.config.feature_flags.instruction_profiling == FlagStatus::Disabled
+            && are_profiling_counters(&exported_globals[instance_globals.len()..])
+        {
+            warn!(
+                self.log,
+                "Dropping {} persisted exported globals of {} that were exported while instruction profiling was enabled",
+                exported_globals.len() - instance_globals.len(),
+                canister_id
+            );
+            &exported_globals[..instance_globals.len()]
+        } else {
+            exported_globals
+        };
+
         if exported_globals.len() > instance_globals.len() {
             fatal!(
                 self.log,
@@ -363,6 +385,21 @@ impl WasmtimeEmbedder {
     }
 }
 
+// Returns true if the given exported globals are the profiling counters
+// followed by their number, as exported by instrumentation when instruction
+// profiling is enabled.
+fn are_profiling_counters(globals: &[Global]) -> bool {
+    match globals {
+        [counters @ .., Global::I64(count)] => {
+            *count as usize == counters.len()
+                && counters
+                    .iter()
+                    .all(|counter| matches!(counter, Global::I64(_)))
+        }
+        _ => false,
+    }
+}
+
 struct StoreRef(*mut wasmtime::Store<()>);
 
 /// SAFETY: The users of `StoreRef` are required to only dereference the pointer
@@ -606,6 +643,30 @@ impl<S: SystemApi> WasmtimeInstance<S> {
             .collect()
     }
 
+    /// Returns the values of the profiling counters, which are only exported
+    /// if instruction profiling is enabled, by function index.
+    pub fn profiling_counters(&mut self) -> Vec<(u32, u64)> {
+        let counters: Vec<_> = self
+            .instance
+            .exports(&mut self.store)
+            .filter_map(|e| {
+                let func_ix = e
+                    .name()
+                    .strip_prefix(PROFILING_COUNTER_PREFIX)?
+                    .parse::<u32>()
+                    .ok()?;
+                Some((func_ix, e.into_global()?))
+            })
+            .collect();
+        counters
+            .into_iter()
+            .filter_map(|(func_ix, counter)| {
+                let count = counter.get(&mut self.store).i64()?;
+                Some((func_ix, count as u64))
+            })
+            .collect()
+    }
+
     /// Return the heap address. If the Instance does not contain any memory,
     /// the pointer is null.
     ///
@@ -608,6 +608,37 @@ pub(crate) fn syscalls<S: SystemApi>(
         })
         .unwrap();
 
+    linker
+        .func_wrap("ic0", "is_controller", {
+            let log = log.clone();
+            move |mut caller: Caller<'_, StoreData<S>>, src: u32, size: u32| {
+                observe_execution_complexity(
+                    &log,
+                    canister_id,
+                    &mut caller,
+                    &ExecutionComplexity {
+                        cpu: system_api_complexity::cpu::IS_CONTROLLER,
+                        memory: (size as u64).into(),
+                        disk: 0.into(),
+                        network: 0.into(),
+                    },
+                )?;
+                with_memory_and_system_api(caller, |system_api, memory| {
+                    system_api.ic0_is_controller(src, size, memory)
+                })
+            }
+        })
+        .unwrap();
+
+    linker
+        .func_wrap("ic0", "canister_version", {
+            move |mut caller: Caller<'_, StoreData<S>>| {
+                with_system_api(&mut caller, |s| s.ic0_canister_version())
+                    .map_err(|e| process_err(caller, e))
+            }
+        })
+        .unwrap();
+
     linker
         .func_wrap("ic0", "debug_print", {
             let log = log.clone();
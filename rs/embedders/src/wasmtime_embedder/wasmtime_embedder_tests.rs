@@ -1,13 +1,13 @@
 use std::sync::Arc;
 
-use super::{system_api, StoreData, NUM_INSTRUCTION_GLOBAL_NAME};
+use super::{are_profiling_counters, system_api, StoreData, NUM_INSTRUCTION_GLOBAL_NAME};
 use crate::{wasm_utils::validate_and_instrument_for_testing, WasmtimeEmbedder};
 use ic_config::embedders::Config as EmbeddersConfig;
 use ic_config::flag_status::FlagStatus;
 use ic_interfaces::execution_environment::{AvailableMemory, ExecutionMode};
 use ic_logger::replica_logger::no_op_logger;
 use ic_registry_subnet_type::SubnetType;
-use ic_replicated_state::{Memory, NetworkTopology, SystemState};
+use ic_replicated_state::{Global, Memory, NetworkTopology, SystemState};
 use ic_system_api::{
     sandbox_safe_system_state::SandboxSafeSystemState, ApiType, DefaultOutOfInstructionsHandler,
     ExecutionParameters, InstructionLimits, SystemApiImpl,
@@ -113,3 +113,17 @@ fn test_wasmtime_system_api() {
         .call(&mut store, &[], &mut [])
         .expect("call failed");
 }
+
+#[test]
+fn only_profiling_counters_followed_by_their_number_are_recognized() {
+    assert!(are_profiling_counters(&[
+        Global::I64(10),
+        Global::I64(0),
+        Global::I64(2)
+    ]));
+    assert!(are_profiling_counters(&[Global::I64(0)]));
+    assert!(!are_profiling_counters(&[]));
+    assert!(!are_profiling_counters(&[Global::I64(10), Global::I64(2)]));
+    assert!(!are_profiling_counters(&[Global::I32(10), Global::I64(1)]));
+    assert!(!are_profiling_counters(&[Global::F64(1.0)]));
+}
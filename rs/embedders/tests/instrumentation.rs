@@ -1,17 +1,23 @@
-use ic_config::embedders::Config as EmbeddersConfig;
+use ic_config::{embedders::Config as EmbeddersConfig, flag_status::FlagStatus};
 use ic_embedders::{
     wasm_utils::{
-        instrumentation::{export_additional_symbols, ExportModuleData, Segments},
+        instrumentation::{
+            export_additional_symbols, ExportModuleData, Segments, PROFILING_COUNTER_COUNT,
+            PROFILING_COUNTER_PREFIX,
+        },
+        profiling::{function_profile, instruction_profile, to_folded_stacks, FunctionProfile},
         validate_and_instrument_for_testing,
         validation::RESERVED_SYMBOLS,
     },
     WasmtimeEmbedder,
 };
 use ic_logger::replica_logger::no_op_logger;
+use ic_replicated_state::Global;
 use ic_sys::{PageIndex, PAGE_SIZE};
+use ic_types::NumInstructions;
 use ic_wasm_types::BinaryEncodedWasm;
 use insta::assert_snapshot;
-use parity_wasm::elements::{self, Module, Section};
+use parity_wasm::elements::{self, Internal, Module, Section};
 use pretty_assertions::assert_eq;
 use std::fs;
 use wabt::{wat2wasm, Features};
@@ -217,3 +223,124 @@ fn test_exports_only_reserved_symbols() {
         assert!(RESERVED_SYMBOLS.contains(&export.field()))
     }
 }
+
+const PROFILED_WAT: &str = r#"
+    (module
+        (import "ic0" "msg_reply" (func $msg_reply))
+        (global $g (mut i32) (i32.const 0))
+        (func $hot (export "canister_update hot")
+            (call $cold)
+            (call $msg_reply)
+        )
+        (func $cold)
+    )"#;
+
+#[test]
+fn profiling_is_disabled_by_default() {
+    let wasm = BinaryEncodedWasm::new(wat2wasm(PROFILED_WAT).unwrap());
+    let output = validate_and_instrument_for_testing(
+        &WasmtimeEmbedder::new(EmbeddersConfig::default(), no_op_logger()),
+        &wasm,
+    )
+    .unwrap()
+    .1;
+    let module = parity_wasm::deserialize_buffer::<Module>(output.binary.as_slice()).unwrap();
+    assert!(!module
+        .export_section()
+        .unwrap()
+        .entries()
+        .iter()
+        .any(|export| export.field().starts_with(PROFILING_COUNTER_PREFIX)));
+}
+
+#[test]
+fn profiling_exports_one_counter_per_function_after_persisted_globals() {
+    let wasm = BinaryEncodedWasm::new(wat2wasm(PROFILED_WAT).unwrap());
+    let mut config = EmbeddersConfig::default();
+    config.feature_flags.instruction_profiling = FlagStatus::Enabled;
+    let output =
+        validate_and_instrument_for_testing(&WasmtimeEmbedder::new(config, no_op_logger()), &wasm)
+            .unwrap()
+            .1;
+    let module = parity_wasm::deserialize_buffer::<Module>(output.binary.as_slice()).unwrap();
+    let exported_globals: Vec<&str> = module
+        .export_section()
+        .unwrap()
+        .entries()
+        .iter()
+        .filter(|export| matches!(export.internal(), Internal::Global(_)))
+        .map(|export| export.field())
+        .collect();
+    // Function indices refer to the original module, where `msg_reply` is
+    // function 0. The counters follow the instructions counter, which is
+    // always exported, and are followed by their number.
+    assert_eq!(
+        exported_globals,
+        vec![
+            "__persistent_mutable_global_0",
+            "canister counter_instructions",
+            "canister profiling_counter 1",
+            "canister profiling_counter 2",
+            PROFILING_COUNTER_COUNT,
+        ]
+    );
+}
+
+#[test]
+fn instruction_profile_reads_counters_from_exported_globals() {
+    let wasm = BinaryEncodedWasm::new(wat2wasm(PROFILED_WAT).unwrap());
+    // The persisted mutable global, the instructions counter, the counters
+    // of `hot` and `cold` and their number, in the order of their exports.
+    let exported_globals = [
+        Global::I32(7),
+        Global::I64(1234),
+        Global::I64(10),
+        Global::I64(0),
+        Global::I64(2),
+    ];
+    let profile = instruction_profile(&wasm, &exported_globals).unwrap();
+    assert_eq!(
+        profile,
+        vec![FunctionProfile {
+            name: "hot".to_string(),
+            instructions: NumInstructions::from(10),
+        }]
+    );
+    assert_eq!(to_folded_stacks("canister", &profile), "canister;hot 10\n");
+}
+
+#[test]
+fn function_profile_names_and_orders_counters() {
+    let wasm = BinaryEncodedWasm::new(wat2wasm(PROFILED_WAT).unwrap());
+    let profile = function_profile(&wasm, &[(1, 3), (2, 5), (0, 0)]).unwrap();
+    assert_eq!(
+        profile,
+        vec![
+            FunctionProfile {
+                name: "cold".to_string(),
+                instructions: NumInstructions::from(5),
+            },
+            FunctionProfile {
+                name: "hot".to_string(),
+                instructions: NumInstructions::from(3),
+            }
+        ]
+    );
+}
+
+#[test]
+fn compilation_cost_does_not_depend_on_profiling() {
+    let wasm = BinaryEncodedWasm::new(wat2wasm(PROFILED_WAT).unwrap());
+    let compilation_cost = |instruction_profiling| {
+        let mut config = EmbeddersConfig::default();
+        config.feature_flags.instruction_profiling = instruction_profiling;
+        validate_and_instrument_for_testing(&WasmtimeEmbedder::new(config, no_op_logger()), &wasm)
+            .unwrap()
+            .1
+            .compilation_cost
+    };
+    assert_eq!(
+        compilation_cost(FlagStatus::Enabled),
+        compilation_cost(FlagStatus::Disabled)
+    );
+}
@@ -1,5 +1,5 @@
 use assert_matches::assert_matches;
-use ic_config::embedders::Config as EmbeddersConfig;
+use ic_config::embedders::{Config as EmbeddersConfig, InstructionCostSchedule};
 use ic_embedders::{
     wasm_utils::{
         validate_and_instrument_for_testing,
@@ -773,3 +773,99 @@ fn function_with_result_is_invalid() {
         ))
     );
 }
+
+// `wabt` only knows a pre-standard encoding of the SIMD instructions, so the
+// modules using them are assembled by hand.
+fn wasm_with_sections(sections: &[(u8, &[u8])]) -> BinaryEncodedWasm {
+    let mut wasm = b"\0asm".to_vec();
+    wasm.extend_from_slice(&1_u32.to_le_bytes());
+    for (id, content) in sections {
+        wasm.push(*id);
+        // All sections are shorter than 128 bytes.
+        wasm.push(content.len() as u8);
+        wasm.extend_from_slice(content);
+    }
+    BinaryEncodedWasm::new(wasm)
+}
+
+fn simd_wasm() -> BinaryEncodedWasm {
+    wasm_with_sections(&[
+        // (type (func))
+        (1, &[0x01, 0x60, 0x00, 0x00]),
+        (3, &[0x01, 0x00]),
+        // (func (local v128)
+        //   (local.set 0 (i32x4.splat (i32.const 1)))
+        //   (drop (i32x4.extract_lane 3 (i32x4.add (local.get 0) (local.get 0)))))
+        (
+            10,
+            &[
+                0x01, 0x15, 0x01, 0x01, 0x7b, 0x41, 0x01, 0xfd, 0x11, 0x21, 0x00, 0x20, 0x00, 0x20,
+                0x00, 0xfd, 0xae, 0x01, 0xfd, 0x1b, 0x03, 0x1a, 0x0b,
+            ],
+        ),
+    ])
+}
+
+fn v2_config() -> EmbeddersConfig {
+    EmbeddersConfig {
+        instruction_cost_schedule: InstructionCostSchedule::V2,
+        ..EmbeddersConfig::default()
+    }
+}
+
+#[test]
+fn can_validate_module_with_simd_instructions() {
+    assert_eq!(
+        validate_wasm_binary(&simd_wasm(), &v2_config()),
+        Ok(WasmValidationDetails {
+            largest_function_instruction_count: NumInstructions::new(9),
+            ..Default::default()
+        })
+    );
+}
+
+#[test]
+fn simd_instructions_are_rejected_with_v1_schedule() {
+    assert_eq!(
+        validate_wasm_binary(&simd_wasm(), &EmbeddersConfig::default()),
+        Err(WasmValidationError::UnsupportedWasmFeature(
+            "SIMD requires the V2 instruction cost schedule".to_string()
+        ))
+    );
+}
+
+#[test]
+fn v128_globals_are_rejected() {
+    let mut global = vec![0x01, 0x7b, 0x00, 0xfd, 0x0c];
+    global.extend_from_slice(&[0; 16]);
+    global.push(0x0b);
+    // (global v128 (v128.const i64x2 0 0))
+    let wasm = wasm_with_sections(&[(6, &global)]);
+    assert_eq!(
+        validate_wasm_binary(&wasm, &v2_config()),
+        Err(WasmValidationError::InvalidGlobalSection(
+            "Globals of type v128 are not supported.".to_string()
+        ))
+    );
+}
+
+#[test]
+fn can_validate_module_with_passive_data_segment() {
+    let mut features = wabt::Features::new();
+    features.enable_bulk_memory();
+    let wasm = wabt::wat2wasm_with_features(
+        r#"
+        (module
+            (memory 1)
+            (data (i32.const 0) "active")
+            (data "passive")
+        )"#,
+        features,
+    )
+    .map(BinaryEncodedWasm::new)
+    .unwrap();
+    assert_eq!(
+        validate_wasm_binary(&wasm, &EmbeddersConfig::default()),
+        Ok(WasmValidationDetails::default())
+    );
+}
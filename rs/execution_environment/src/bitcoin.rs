@@ -3,8 +3,9 @@ use candid::Encode;
 use ic_btc_canister::state::State as BitcoinCanisterState;
 use ic_error_types::{ErrorCode, UserError};
 use ic_ic00_types::{
-    BitcoinGetBalanceArgs, BitcoinGetCurrentFeePercentilesArgs, BitcoinGetUtxosArgs,
-    BitcoinNetwork, BitcoinSendTransactionArgs, EmptyBlob, Method as Ic00Method, Payload,
+    BitcoinGetAddressHistoryArgs, BitcoinGetBalanceArgs, BitcoinGetBlockHeadersArgs,
+    BitcoinGetCurrentFeePercentilesArgs, BitcoinGetUtxosArgs, BitcoinNetwork,
+    BitcoinSendTransactionArgs, EmptyBlob, Method as Ic00Method, Payload,
 };
 use ic_registry_subnet_features::BitcoinFeatureStatus;
 use ic_replicated_state::ReplicatedState;
@@ -20,6 +21,8 @@ const NUMBER_OF_TRANSACTIONS_FOR_CALCULATING_FEES: u32 = 10_000;
 const GET_BALANCE_FEE: Cycles = Cycles::new(100_000_000);
 const GET_UTXOS_FEE: Cycles = Cycles::new(100_000_000);
 const GET_CURRENT_FEE_PERCENTILES_FEE: Cycles = Cycles::new(100_000_000);
+const GET_ADDRESS_HISTORY_FEE: Cycles = Cycles::new(100_000_000);
+const GET_BLOCK_HEADERS_FEE: Cycles = Cycles::new(100_000_000);
 const SEND_TRANSACTION_FEE_BASE: Cycles = Cycles::new(5_000_000_000);
 const SEND_TRANSACTION_FEE_PER_BYTE: Cycles = Cycles::new(20_000_000);
 
@@ -101,6 +104,86 @@ pub fn get_utxos(
     )
 }
 
+/// Handles a `bitcoin_get_address_history` request.
+pub fn get_address_history(
+    payload: &[u8],
+    state: &mut ReplicatedState,
+    payment: Cycles,
+) -> (Result<Vec<u8>, UserError>, Cycles) {
+    execute_bitcoin_endpoint(
+        payload,
+        state,
+        payment,
+        GET_ADDRESS_HISTORY_FEE,
+        |payload: &[u8], state: &mut ReplicatedState| -> Result<Vec<u8>, UserError> {
+            match BitcoinGetAddressHistoryArgs::decode(payload) {
+                Err(err) => Err(candid_error_to_user_error(err)),
+                Ok(args) => {
+                    // Verify that the request is for the expected network.
+                    verify_network(args.network, state.bitcoin().network())?;
+
+                    let btc_canister_state = BitcoinCanisterState::from(state.take_bitcoin_state());
+                    let history_response = ic_btc_canister::get_address_history(
+                        &btc_canister_state,
+                        &args.address,
+                        args.page,
+                    );
+                    state.put_bitcoin_state(btc_canister_state.into());
+
+                    history_response
+                        .map(|response| Encode!(&response).unwrap())
+                        .map_err(|err| {
+                            UserError::new(
+                                ErrorCode::CanisterRejectedMessage,
+                                format!("{} failed: {}", Ic00Method::BitcoinGetAddressHistory, err),
+                            )
+                        })
+                }
+            }
+        },
+    )
+}
+
+/// Handles a `bitcoin_get_block_headers` request.
+pub fn get_block_headers(
+    payload: &[u8],
+    state: &mut ReplicatedState,
+    payment: Cycles,
+) -> (Result<Vec<u8>, UserError>, Cycles) {
+    execute_bitcoin_endpoint(
+        payload,
+        state,
+        payment,
+        GET_BLOCK_HEADERS_FEE,
+        |payload: &[u8], state: &mut ReplicatedState| -> Result<Vec<u8>, UserError> {
+            match BitcoinGetBlockHeadersArgs::decode(payload) {
+                Err(err) => Err(candid_error_to_user_error(err)),
+                Ok(args) => {
+                    // Verify that the request is for the expected network.
+                    verify_network(args.network, state.bitcoin().network())?;
+
+                    let btc_canister_state = BitcoinCanisterState::from(state.take_bitcoin_state());
+                    let headers_response = ic_btc_canister::get_block_headers(
+                        &btc_canister_state,
+                        args.start_height,
+                        args.end_height,
+                    );
+                    state.put_bitcoin_state(btc_canister_state.into());
+
+                    headers_response
+                        .map(|response| Encode!(&response).unwrap())
+                        .map_err(|err| {
+                            UserError::new(
+                                ErrorCode::CanisterRejectedMessage,
+                                format!("{} failed: {}", Ic00Method::BitcoinGetBlockHeaders, err),
+                            )
+                        })
+                }
+            }
+        },
+    )
+}
+
 /// Handles a `get_current_fee_percentiles` request.
 pub fn get_current_fee_percentiles(
     payload: &[u8],
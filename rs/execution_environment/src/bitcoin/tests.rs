@@ -1,12 +1,17 @@
 use bitcoin::{
-    blockdata::constants::genesis_block, util::psbt::serialize::Serialize, Address, Network,
+    blockdata::constants::genesis_block, consensus::serialize, util::psbt::serialize::Serialize,
+    Address, Network,
 };
 use candid::Encode;
 use ic_btc_test_utils::{random_p2pkh_address, BlockBuilder, TransactionBuilder};
-use ic_btc_types::{GetUtxosResponse, OutPoint, Satoshi, Utxo, UtxosFilter};
+use ic_btc_types::{
+    AddressTransaction, GetAddressHistoryResponse, GetBlockHeadersResponse, GetUtxosResponse,
+    OutPoint, Satoshi, Utxo, UtxosFilter,
+};
 use ic_ic00_types::{
-    BitcoinGetBalanceArgs, BitcoinGetCurrentFeePercentilesArgs, BitcoinGetUtxosArgs,
-    BitcoinNetwork, BitcoinSendTransactionArgs, EmptyBlob, Method, Payload as Ic00Payload,
+    BitcoinGetAddressHistoryArgs, BitcoinGetBalanceArgs, BitcoinGetBlockHeadersArgs,
+    BitcoinGetCurrentFeePercentilesArgs, BitcoinGetUtxosArgs, BitcoinNetwork,
+    BitcoinSendTransactionArgs, EmptyBlob, Method, Payload as Ic00Payload,
 };
 use ic_interfaces::execution_environment::AvailableMemory;
 use ic_interfaces::execution_environment::SubnetAvailableMemory;
@@ -828,15 +833,25 @@ fn send_transaction_malformed_transaction() {
 
 #[test]
 fn send_transaction_succeeds() {
-    // Create a fake transaction that passes verification check.
-    let transaction = TransactionBuilder::coinbase()
+    let address = random_p2pkh_address(Network::Testnet);
+    let coinbase_tx = TransactionBuilder::coinbase()
+        .with_output(&address, 1_000)
+        .build();
+    let block_0 = BlockBuilder::genesis()
+        .with_transaction(coinbase_tx.clone())
+        .build();
+    let state = ic_btc_canister::state::State::new(1, Network::Testnet, block_0);
+
+    // Create a transaction that spends the output of the coinbase transaction.
+    let transaction = TransactionBuilder::new()
+        .with_input(bitcoin::OutPoint::new(coinbase_tx.txid(), 0))
         .with_output(&random_p2pkh_address(Network::Testnet), 1_000)
         .build()
         .serialize();
     let payment = calculate_send_transaction_payment(transaction.len());
 
     execute_check_payload_and_refund(
-        fake_state(),
+        BitcoinState::from(state),
         Method::BitcoinSendTransaction,
         BitcoinSendTransactionArgs {
             transaction,
@@ -848,3 +863,167 @@ fn send_transaction_succeeds() {
         Payload::Data(EmptyBlob::encode()),
     );
 }
+
+#[test]
+fn send_transaction_rejects_transaction_with_unknown_inputs() {
+    // A coinbase transaction doesn't spend any existing output.
+    let transaction = TransactionBuilder::coinbase()
+        .with_output(&random_p2pkh_address(Network::Testnet), 1_000)
+        .build()
+        .serialize();
+    let payment = calculate_send_transaction_payment(transaction.len());
+    let expected_refund = Cycles::new(123);
+    reject_and_check_refund(
+        fake_state(),
+        Method::BitcoinSendTransaction,
+        BitcoinSendTransactionArgs {
+            transaction,
+            network: BitcoinNetwork::Testnet,
+        }
+        .encode(),
+        payment + expected_refund,
+        expected_refund,
+        "bitcoin_send_transaction failed: The transaction spends an output that doesn't exist or is already spent.",
+    );
+}
+
+fn fake_get_block_headers_args() -> BitcoinGetBlockHeadersArgs {
+    BitcoinGetBlockHeadersArgs {
+        start_height: 0,
+        end_height: None,
+        network: BitcoinNetwork::Testnet,
+    }
+}
+
+#[test]
+fn get_block_headers_rejects_feature_not_enabled() {
+    reject_feature_not_enabled(
+        Method::BitcoinGetBlockHeaders,
+        fake_get_block_headers_args().encode(),
+    );
+}
+
+#[test]
+fn get_block_headers_succeeds() {
+    let block_0 = BlockBuilder::genesis().build();
+    let block_1 = BlockBuilder::with_prev_header(block_0.header).build();
+    let mut state = ic_btc_canister::state::State::new(1, Network::Testnet, block_0.clone());
+    // With a stability threshold of 1, `block_0` becomes stable.
+    ic_btc_canister::store::insert_block(&mut state, block_1.clone()).unwrap();
+
+    execute_check_payload_and_refund(
+        BitcoinState::from(state),
+        Method::BitcoinGetBlockHeaders,
+        fake_get_block_headers_args().encode(),
+        Cycles::new(100_000_000),
+        Cycles::zero(),
+        Payload::Data(
+            Encode!(&GetBlockHeadersResponse {
+                tip_height: 1,
+                block_headers: vec![
+                    serialize(&block_0.header).into(),
+                    serialize(&block_1.header).into()
+                ],
+            })
+            .unwrap(),
+        ),
+    );
+}
+
+#[test]
+fn get_block_headers_rejects_large_start_height() {
+    reject_and_check_refund(
+        fake_state(),
+        Method::BitcoinGetBlockHeaders,
+        BitcoinGetBlockHeadersArgs {
+            start_height: 100,
+            ..fake_get_block_headers_args()
+        }
+        .encode(),
+        Cycles::new(100_000_000),
+        Cycles::zero(),
+        "bitcoin_get_block_headers failed: The requested start_height is larger than the height of the chain. Given: 100, height of chain: 1",
+    );
+}
+
+fn fake_get_address_history_args() -> BitcoinGetAddressHistoryArgs {
+    BitcoinGetAddressHistoryArgs {
+        address: random_p2pkh_address(Network::Testnet).to_string(),
+        network: BitcoinNetwork::Testnet,
+        page: None,
+    }
+}
+
+#[test]
+fn get_address_history_rejects_feature_not_enabled() {
+    reject_feature_not_enabled(
+        Method::BitcoinGetAddressHistory,
+        fake_get_address_history_args().encode(),
+    );
+}
+
+#[test]
+fn get_address_history_rejects_if_index_is_disabled() {
+    reject_and_check_refund(
+        fake_state(),
+        Method::BitcoinGetAddressHistory,
+        fake_get_address_history_args().encode(),
+        Cycles::new(100_000_000),
+        Cycles::zero(),
+        "bitcoin_get_address_history failed: The address history index is disabled.",
+    );
+}
+
+#[test]
+fn get_address_history_succeeds() {
+    let amount: Satoshi = 123;
+    let address_1 = random_p2pkh_address(Network::Testnet);
+    let address_2 = random_p2pkh_address(Network::Testnet);
+    let coinbase_tx = TransactionBuilder::coinbase()
+        .with_output(&address_1, amount)
+        .build();
+    let block_0 = BlockBuilder::genesis()
+        .with_transaction(coinbase_tx.clone())
+        .build();
+    let tx = TransactionBuilder::new()
+        .with_input(bitcoin::OutPoint::new(coinbase_tx.txid(), 0))
+        .with_output(&address_2, amount)
+        .build();
+    let block_1 = BlockBuilder::with_prev_header(block_0.header)
+        .with_transaction(tx.clone())
+        .build();
+    let mut state =
+        ic_btc_canister::state::State::new(1, Network::Testnet, block_0).with_address_history();
+    // With a stability threshold of 1, `block_0` is indexed and `block_1` is unstable.
+    ic_btc_canister::store::insert_block(&mut state, block_1.clone()).unwrap();
+
+    execute_check_payload_and_refund(
+        BitcoinState::from(state),
+        Method::BitcoinGetAddressHistory,
+        BitcoinGetAddressHistoryArgs {
+            address: address_1.to_string(),
+            ..fake_get_address_history_args()
+        }
+        .encode(),
+        Cycles::new(100_000_000),
+        Cycles::zero(),
+        Payload::Data(
+            Encode!(&GetAddressHistoryResponse {
+                transactions: vec![
+                    AddressTransaction {
+                        txid: tx.txid().to_vec(),
+                        height: 1,
+                    },
+                    AddressTransaction {
+                        txid: coinbase_tx.txid().to_vec(),
+                        height: 0,
+                    },
+                ],
+                tip_block_hash: block_1.block_hash().to_vec(),
+                tip_height: 1,
+                next_page: None,
+            })
+            .unwrap(),
+        ),
+    );
+}
@@ -307,7 +307,9 @@ impl CanisterManager {
             | Ok(Ic00Method::BitcoinGetBalance)
             | Ok(Ic00Method::BitcoinGetUtxos)
             | Ok(Ic00Method::BitcoinSendTransaction)
-            | Ok(Ic00Method::BitcoinGetCurrentFeePercentiles) => Err(UserError::new(
+            | Ok(Ic00Method::BitcoinGetCurrentFeePercentiles)
+            | Ok(Ic00Method::BitcoinGetAddressHistory)
+            | Ok(Ic00Method::BitcoinGetBlockHeaders) => Err(UserError::new(
                 ErrorCode::CanisterRejectedMessage,
                 format!("Only canisters can call ic00 method {}", method_name),
             )),
@@ -479,6 +481,7 @@ impl CanisterManager {
             .max(old_usage);
 
         self.do_update_settings(validated_settings, canister);
+        canister.system_state.canister_version += 1;
 
         let new_usage = old_usage;
         let new_mem = canister
@@ -1672,6 +1675,8 @@ pub fn uninstall_canister(
     // Drop its certified data.
     canister.system_state.certified_data = Vec::new();
 
+    canister.system_state.canister_version += 1;
+
     truncate_canister_heap(log, state_path, canister.canister_id());
     truncate_canister_stable_memory(log, state_path, canister.canister_id());
 
@@ -1833,6 +1838,7 @@ fn finish_install_code(
             if config.rate_limiting_of_instructions == FlagStatus::Enabled {
                 new_canister.scheduler_state.install_code_debit += instructions_consumed;
             }
+            new_canister.system_state.canister_version += 1;
 
             // We managed to create a new canister and will be dropping the
             // older one. So we get rid of the previous heap to make sure it
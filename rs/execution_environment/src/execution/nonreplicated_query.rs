@@ -16,6 +16,10 @@ use ic_types::methods::{FuncRef, WasmMethod};
 use ic_types::{Cycles, NumInstructions, Time};
 
 // Execute non replicated query.
+//
+// Besides the resulting canister, the remaining instructions and the result,
+// it returns the instructions executed per function if instruction profiling
+// is enabled.
 #[allow(clippy::too_many_arguments)]
 pub fn execute_non_replicated_query(
     query_kind: NonReplicatedQueryKind,
@@ -31,6 +35,7 @@ pub fn execute_non_replicated_query(
 ) -> (
     CanisterState,
     NumInstructions,
+    Vec<(u32, u64)>,
     Result<Option<WasmResult>, UserError>,
 ) {
     // Validate that the canister is running.
@@ -38,6 +43,7 @@ pub fn execute_non_replicated_query(
         return (
             canister,
             execution_parameters.instruction_limits.message(),
+            vec![],
             Err(err),
         );
     }
@@ -51,6 +57,7 @@ pub fn execute_non_replicated_query(
         return (
             canister,
             execution_parameters.instruction_limits.message(),
+            vec![],
             Err(err.into_user_error(&canister_id)),
         );
     }
@@ -112,5 +119,10 @@ pub fn execute_non_replicated_query(
     let result = output
         .wasm_result
         .map_err(|err| err.into_user_error(&canister.canister_id()));
-    (canister, output.num_instructions_left, result)
+    (
+        canister,
+        output.num_instructions_left,
+        output.instruction_profile,
+        result,
+    )
 }
@@ -607,6 +607,7 @@ impl ExecutionEnvironment {
                                         self.cycles_account_manager.http_request_fee(
                                             request.payload_size_bytes(),
                                             canister_http_request_context.max_response_bytes,
+                                            canister_http_request_context.max_redirects,
                                             registry_settings.subnet_size
                                         );
                                     if request.payment < http_request_fee {
@@ -863,6 +864,20 @@ impl ExecutionEnvironment {
                 Some(res)
             }
 
+            Ok(Ic00Method::BitcoinGetAddressHistory) => {
+                let cycles = msg.take_cycles();
+                let res =
+                    crate::bitcoin::get_address_history(msg.method_payload(), &mut state, cycles);
+                Some(res)
+            }
+
+            Ok(Ic00Method::BitcoinGetBlockHeaders) => {
+                let cycles = msg.take_cycles();
+                let res =
+                    crate::bitcoin::get_block_headers(msg.method_payload(), &mut state, cycles);
+                Some(res)
+            }
+
             Ok(Ic00Method::BitcoinSendTransaction) => {
                 let cycles = msg.take_cycles();
                 let res =
@@ -1367,7 +1382,7 @@ impl ExecutionEnvironment {
             &self.hypervisor,
             &mut round_limits,
         )
-        .2;
+        .3;
 
         match result {
             Ok(maybe_wasm_result) => match maybe_wasm_result {
@@ -226,7 +226,21 @@ impl Hypervisor {
         embedder_config.feature_flags.rate_limiting_of_debug_prints =
             config.rate_limiting_of_debug_prints;
         embedder_config.feature_flags.module_sharing = config.module_sharing;
+        embedder_config.feature_flags.instruction_profiling = config.instruction_profiling;
         embedder_config.cost_to_compile_wasm_instruction = config.cost_to_compile_wasm_instruction;
+        embedder_config.instruction_cost_schedule = config.instruction_cost_schedule;
+
+        let compilation_cache = match &config.compilation_cache_dir {
+            Some(dir) => CompilationCache::new_persistent(
+                config.module_sharing,
+                dir,
+                config.compilation_cache_capacity,
+                &embedder_config,
+                metrics_registry,
+                log.clone(),
+            ),
+            None => CompilationCache::new(config.module_sharing),
+        };
 
         let wasm_executor: Arc<dyn WasmExecutor> = match config.canister_sandboxing_flag {
             FlagStatus::Enabled => {
@@ -255,7 +269,7 @@ impl Hypervisor {
             own_subnet_type,
             log,
             cycles_account_manager,
-            compilation_cache: Arc::new(CompilationCache::new(config.module_sharing)),
+            compilation_cache: Arc::new(compilation_cache),
             deterministic_time_slicing: config.deterministic_time_slicing,
             cost_to_compile_wasm_instruction: config.cost_to_compile_wasm_instruction,
         }
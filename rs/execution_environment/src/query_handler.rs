@@ -128,16 +128,12 @@ impl InternalHttpQueryHandler {
     }
 }
 
-impl QueryHandler for InternalHttpQueryHandler {
-    type State = ReplicatedState;
-
-    fn query(
+impl InternalHttpQueryHandler {
+    fn query_context(
         &self,
-        query: UserQuery,
         state: Arc<ReplicatedState>,
         data_certificate: Vec<u8>,
-    ) -> Result<WasmResult, UserError> {
-        let measurement_scope = MeasurementScope::root(&self.metrics.query);
+    ) -> query_context::QueryContext<'_> {
         // Note that This assumes that the QueryHandler is always called with the
         // "latest" state.  If and when we start supporting queries against older
         // versions of the state, we will need the caller of the QueryHandler to
@@ -153,7 +149,7 @@ impl QueryHandler for InternalHttpQueryHandler {
         let subnet_available_memory = subnet_memory_capacity(&self.config);
         let max_canister_memory_size = self.config.max_canister_memory_size;
 
-        let mut context = query_context::QueryContext::new(
+        query_context::QueryContext::new(
             &self.log,
             self.hypervisor.as_ref(),
             self.own_subnet_type,
@@ -163,7 +159,21 @@ impl QueryHandler for InternalHttpQueryHandler {
             subnet_available_memory,
             max_canister_memory_size,
             self.max_instructions_per_message,
-        );
+        )
+    }
+}
+
+impl QueryHandler for InternalHttpQueryHandler {
+    type State = ReplicatedState;
+
+    fn query(
+        &self,
+        query: UserQuery,
+        state: Arc<ReplicatedState>,
+        data_certificate: Vec<u8>,
+    ) -> Result<WasmResult, UserError> {
+        let measurement_scope = MeasurementScope::root(&self.metrics.query);
+        let mut context = self.query_context(state, data_certificate);
         context.run(
             query,
             &self.metrics,
@@ -171,6 +181,23 @@ impl QueryHandler for InternalHttpQueryHandler {
             &measurement_scope,
         )
     }
+
+    fn query_with_instruction_profile(
+        &self,
+        query: UserQuery,
+        state: Arc<ReplicatedState>,
+        data_certificate: Vec<u8>,
+    ) -> (Result<WasmResult, UserError>, String) {
+        let measurement_scope = MeasurementScope::root(&self.metrics.query);
+        let mut context = self.query_context(state, data_certificate);
+        let result = context.run(
+            query,
+            &self.metrics,
+            Arc::clone(&self.cycles_account_manager),
+            &measurement_scope,
+        );
+        (result, context.instruction_profile())
+    }
 }
 
 impl HttpQueryHandler {
@@ -202,6 +229,16 @@ impl QueryHandler for HttpQueryHandler {
     ) -> Result<WasmResult, UserError> {
         self.internal.query(query, state, data_certificate)
     }
+
+    fn query_with_instruction_profile(
+        &self,
+        query: UserQuery,
+        state: Arc<Self::State>,
+        data_certificate: Vec<u8>,
+    ) -> (Result<WasmResult, UserError>, String) {
+        self.internal
+            .query_with_instruction_profile(query, state, data_certificate)
+    }
 }
 
 impl Service<(UserQuery, Option<CertificateDelegation>)> for HttpQueryHandler {
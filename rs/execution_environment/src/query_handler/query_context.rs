@@ -48,6 +48,10 @@ use ic_base_types::NumBytes;
 use ic_config::flag_status::FlagStatus;
 use ic_constants::SMALL_APP_SUBNET_MAX_SIZE;
 use ic_cycles_account_manager::CyclesAccountManager;
+use ic_embedders::wasm_utils::{
+    decoding::decode_wasm,
+    profiling::{function_profile, to_folded_stacks},
+};
 use ic_error_types::{ErrorCode, RejectCode, UserError};
 use ic_interfaces::execution_environment::{ExecutionMode, HypervisorError, SubnetAvailableMemory};
 use ic_logger::{debug, error, fatal, warn, ReplicaLogger};
@@ -117,6 +121,9 @@ pub(super) struct QueryContext<'a> {
     max_canister_memory_size: NumBytes,
     max_instructions_per_message: NumInstructions,
     round_limits: RoundLimits,
+    // The instructions executed per function of each canister, only recorded
+    // if instruction profiling is enabled.
+    instruction_profiles: BTreeMap<CanisterId, BTreeMap<u32, u64>>,
 }
 
 impl<'a> QueryContext<'a> {
@@ -151,6 +158,7 @@ impl<'a> QueryContext<'a> {
             max_canister_memory_size,
             max_instructions_per_message,
             round_limits,
+            instruction_profiles: BTreeMap::new(),
         }
     }
 
@@ -278,6 +286,40 @@ impl<'a> QueryContext<'a> {
         }
     }
 
+    /// Returns the instructions executed by each function of the canisters
+    /// involved in the query so far, in the folded stack format understood by
+    /// flamegraph tools. The stacks of each canister are rooted at its ID.
+    ///
+    /// The result is empty unless instruction profiling is enabled.
+    pub(super) fn instruction_profile(&self) -> String {
+        self.instruction_profiles
+            .iter()
+            .filter_map(|(canister_id, counters)| {
+                let execution_state = self
+                    .state
+                    .canister_state(canister_id)?
+                    .execution_state
+                    .as_ref()?;
+                let wasm = decode_wasm(execution_state.wasm_binary.binary.to_shared_vec()).ok()?;
+                let counters: Vec<_> = counters.iter().map(|(f, c)| (*f, *c)).collect();
+                let profile = function_profile(&wasm, &counters).ok()?;
+                Some(to_folded_stacks(&canister_id.to_string(), &profile))
+            })
+            .collect()
+    }
+
+    // Adds the instructions executed per function by a single execution on
+    // the given canister to the profile of the query.
+    fn record_instruction_profile(&mut self, canister_id: CanisterId, profile: &[(u32, u64)]) {
+        if profile.is_empty() {
+            return;
+        }
+        let counters = self.instruction_profiles.entry(canister_id).or_default();
+        for (func_ix, instructions) in profile {
+            *counters.entry(*func_ix).or_default() += instructions;
+        }
+    }
+
     // Keep processing the call graph till a result is achieved or no more
     // outstanding calls are left.
     fn run_loop<'b>(
@@ -418,18 +460,20 @@ impl<'a> QueryContext<'a> {
             InstructionLimits::new(FlagStatus::Disabled, instruction_limit, instruction_limit);
         let execution_parameters = self.execution_parameters(&canister, instruction_limits);
 
-        let (canister, instructions_left, result) = execute_non_replicated_query(
-            query_kind,
-            method_name,
-            method_payload,
-            canister,
-            Some(self.data_certificate.clone()),
-            self.state.time(),
-            execution_parameters,
-            &self.network_topology,
-            self.hypervisor,
-            &mut self.round_limits,
-        );
+        let (canister, instructions_left, instruction_profile, result) =
+            execute_non_replicated_query(
+                query_kind,
+                method_name,
+                method_payload,
+                canister,
+                Some(self.data_certificate.clone()),
+                self.state.time(),
+                execution_parameters,
+                &self.network_topology,
+                self.hypervisor,
+                &mut self.round_limits,
+            );
+        self.record_instruction_profile(canister.canister_id(), &instruction_profile);
         let instructions_executed = instruction_limit - instructions_left;
         measurement_scope.add(instructions_executed, NumMessages::from(1));
         self.query_allocations_used
@@ -534,6 +578,7 @@ impl<'a> QueryContext<'a> {
             &mut self.round_limits,
         );
 
+        self.record_instruction_profile(canister_id, &output.instruction_profile);
         let canister_current_memory_usage = canister.memory_usage(self.own_subnet_type);
         canister.execution_state = Some(output_execution_state);
         execution_parameters
@@ -623,6 +668,10 @@ impl<'a> QueryContext<'a> {
                 &mut self.round_limits,
             );
 
+        self.record_instruction_profile(
+            canister.canister_id(),
+            &cleanup_output.instruction_profile,
+        );
         canister.execution_state = Some(output_execution_state);
         match cleanup_output.wasm_result {
             Ok(_) => {
@@ -390,3 +390,66 @@ fn queries_to_frozen_canisters_are_rejected() {
     );
     assert!(result.is_ok());
 }
+
+#[test]
+fn query_instruction_profile_covers_all_queried_canisters() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_subnet_type(SubnetType::VerifiedApplication)
+        .with_instruction_profiling()
+        .build();
+
+    let canister_a = test.universal_canister_with_cycles(CYCLES_BALANCE).unwrap();
+    let canister_b = test.universal_canister_with_cycles(CYCLES_BALANCE).unwrap();
+
+    let (output, profile) = test.query_with_instruction_profile(
+        UserQuery {
+            source: user_test_id(2),
+            receiver: canister_a,
+            method_name: "query".to_string(),
+            method_payload: wasm()
+                .inter_query(
+                    canister_b,
+                    call_args().other_side(wasm().reply_data(b"pong".as_ref())),
+                )
+                .build(),
+            ingress_expiry: 0,
+            nonce: None,
+        },
+        Arc::new(test.state().clone()),
+        vec![],
+    );
+    assert_eq!(output, Ok(WasmResult::Reply(b"pong".to_vec())));
+
+    // Every line is a stack rooted at a canister followed by a count.
+    let roots: Vec<String> = profile
+        .lines()
+        .map(|line| {
+            let (stack, count) = line.rsplit_once(' ').unwrap();
+            assert!(count.parse::<u64>().unwrap() > 0);
+            stack.split(';').next().unwrap().to_string()
+        })
+        .collect();
+    assert!(roots.contains(&canister_a.to_string()));
+    assert!(roots.contains(&canister_b.to_string()));
+}
+
+#[test]
+fn query_instruction_profile_is_empty_if_profiling_is_disabled() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let canister = test.universal_canister_with_cycles(CYCLES_BALANCE).unwrap();
+
+    let (output, profile) = test.query_with_instruction_profile(
+        UserQuery {
+            source: user_test_id(2),
+            receiver: canister,
+            method_name: "query".to_string(),
+            method_payload: wasm().reply_data(b"pong".as_ref()).build(),
+            ingress_expiry: 0,
+            nonce: None,
+        },
+        Arc::new(test.state().clone()),
+        vec![],
+    );
+    assert_eq!(output, Ok(WasmResult::Reply(b"pong".to_vec())));
+    assert_eq!(profile, "");
+}
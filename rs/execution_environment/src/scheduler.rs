@@ -1740,6 +1740,8 @@ fn get_instructions_limit_for_subnet_message(
             | BitcoinGetUtxos
             | BitcoinSendTransaction
             | BitcoinGetCurrentFeePercentiles
+            | BitcoinGetAddressHistory
+            | BitcoinGetBlockHeaders
             | ProvisionalCreateCanisterWithCycles
             | ProvisionalTopUpCanister => config.max_instructions_per_message,
             InstallCode => match InstallCodeArgs::decode(payload) {
@@ -1764,7 +1766,9 @@ fn is_bitcoin_request(msg: &CanisterInputMessage) -> bool {
                 BitcoinGetBalance
                 | BitcoinGetUtxos
                 | BitcoinSendTransaction
-                | BitcoinGetCurrentFeePercentiles => true,
+                | BitcoinGetCurrentFeePercentiles
+                | BitcoinGetAddressHistory
+                | BitcoinGetBlockHeaders => true,
                 CanisterStatus
                 | CreateCanister
                 | DeleteCanister
@@ -887,6 +887,7 @@ impl TestWasmExecutorCore {
                     accessed_pages: 0,
                     dirty_pages: 0,
                 },
+                instruction_profile: vec![],
             };
             self.schedule
                 .push((self.round, canister_id, instructions_to_execute));
@@ -925,6 +926,7 @@ impl TestWasmExecutorCore {
             allocated_message_bytes: NumBytes::from(0),
             num_instructions_left: instructions_left,
             instance_stats,
+            instruction_profile: vec![],
         };
         self.schedule
             .push((self.round, canister_id, instructions_to_execute));
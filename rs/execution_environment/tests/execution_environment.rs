@@ -31,7 +31,7 @@ use ic_test_utilities::{
 };
 use ic_test_utilities_metrics::{fetch_histogram_vec_count, metric_vec};
 use ic_types::{
-    canister_http::CanisterHttpMethod,
+    canister_http::{CanisterHttpMethod, MAX_CANISTER_HTTP_REDIRECTS},
     ingress::{IngressState, IngressStatus, WasmResult},
     messages::{
         CallbackId, Payload, RejectContext, RequestOrResponse, Response, MAX_RESPONSE_COUNT_BYTES,
@@ -1478,6 +1478,7 @@ fn execute_canister_http_request() {
     let args = CanisterHttpRequestArgs {
         url: url.clone(),
         max_response_bytes: Some(response_size_limit),
+        max_redirects: None,
         headers: Vec::new(),
         body: None,
         http_method: HttpMethod::GET,
@@ -1513,11 +1514,54 @@ fn execute_canister_http_request() {
         payment
             - test.http_request_fee(
                 NumBytes::from(Method::HttpRequest.to_string().len() as u64) + request_payload_size,
-                Some(NumBytes::from(response_size_limit))
+                Some(NumBytes::from(response_size_limit)),
+                0
             )
     );
 }
 
+#[test]
+fn execute_canister_http_request_charges_for_redirects() {
+    let own_subnet = subnet_test_id(1);
+    let caller_canister = canister_test_id(10);
+    let mut test = ExecutionTestBuilder::new()
+        .with_own_subnet_id(own_subnet)
+        .with_caller(own_subnet, caller_canister)
+        .build();
+    test.state_mut().metadata.own_subnet_features.http_requests = true;
+
+    let response_size_limit = 1000u64;
+    let args = CanisterHttpRequestArgs {
+        url: "https://".to_string(),
+        max_response_bytes: Some(response_size_limit),
+        max_redirects: Some(3),
+        headers: Vec::new(),
+        body: None,
+        http_method: HttpMethod::GET,
+        transform_method_name: None,
+    };
+
+    let payment = Cycles::new(1_000_000_000);
+    let payload = args.encode();
+    let request_size =
+        NumBytes::from((Method::HttpRequest.to_string().len() + payload.len()) as u64);
+    test.inject_call_to_ic00(Method::HttpRequest, payload, payment);
+    test.execute_all();
+
+    let http_request_context = test
+        .state()
+        .metadata
+        .subnet_call_context_manager
+        .canister_http_request_contexts
+        .get(&CallbackId::from(0))
+        .unwrap();
+    let fee = test.http_request_fee(request_size, Some(NumBytes::from(response_size_limit)), 3);
+    assert!(
+        fee > test.http_request_fee(request_size, Some(NumBytes::from(response_size_limit)), 0)
+    );
+    assert_eq!(http_request_context.request.payment, payment - fee);
+}
+
 #[test]
 fn execute_canister_http_request_disabled() {
     let own_subnet = subnet_test_id(1);
@@ -1534,6 +1578,7 @@ fn execute_canister_http_request_disabled() {
     let args = CanisterHttpRequestArgs {
         url,
         max_response_bytes: None,
+        max_redirects: None,
         headers: Vec::new(),
         body: None,
         http_method: HttpMethod::GET,
@@ -1552,6 +1597,49 @@ fn execute_canister_http_request_disabled() {
     assert_eq!(canister_http_request_contexts.len(), 0);
 }
 
+#[test]
+fn execute_canister_http_request_with_too_many_redirects() {
+    let own_subnet = subnet_test_id(1);
+    let caller_canister = canister_test_id(10);
+    let mut test = ExecutionTestBuilder::new()
+        .with_own_subnet_id(own_subnet)
+        .with_caller(own_subnet, caller_canister)
+        .build();
+    test.state_mut().metadata.own_subnet_features.http_requests = true;
+
+    let args = CanisterHttpRequestArgs {
+        url: "https://".to_string(),
+        max_response_bytes: None,
+        max_redirects: Some(MAX_CANISTER_HTTP_REDIRECTS + 1),
+        headers: Vec::new(),
+        body: None,
+        http_method: HttpMethod::PUT,
+        transform_method_name: None,
+    };
+
+    test.inject_call_to_ic00(
+        Method::HttpRequest,
+        args.encode(),
+        Cycles::new(1_000_000_000),
+    );
+    test.execute_all();
+    let canister_http_request_contexts = &test
+        .state()
+        .metadata
+        .subnet_call_context_manager
+        .canister_http_request_contexts;
+    assert_eq!(canister_http_request_contexts.len(), 0);
+    let response = test.xnet_messages()[0].clone();
+    assert_eq!(
+        get_reject_message(response),
+        format!(
+            "max_redirects expected to be at most {}, got {}",
+            MAX_CANISTER_HTTP_REDIRECTS,
+            MAX_CANISTER_HTTP_REDIRECTS + 1
+        )
+    );
+}
+
 fn get_reject_message(response: RequestOrResponse) -> String {
     match response {
         RequestOrResponse::Request(_) => panic!("Expected Response"),
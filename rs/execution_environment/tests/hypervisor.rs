@@ -1,6 +1,10 @@
 use assert_matches::assert_matches;
 use candid::{Decode, Encode};
-use ic_config::{embedders::Config as EmbeddersConfig, flag_status::FlagStatus};
+use ic_base_types::NumSeconds;
+use ic_config::{
+    embedders::{Config as EmbeddersConfig, InstructionCostSchedule},
+    flag_status::FlagStatus,
+};
 use ic_error_types::{ErrorCode, RejectCode};
 use ic_execution_environment::CompilationCostHandling;
 use ic_ic00_types::{
@@ -27,7 +31,7 @@ use ic_test_utilities_metrics::{fetch_histogram_stats, HistogramStats};
 use ic_types::ingress::{IngressState, IngressStatus};
 use ic_types::{
     ingress::WasmResult, messages::MAX_INTER_CANISTER_PAYLOAD_IN_BYTES, methods::WasmMethod,
-    CanisterId, Cycles, NumBytes, NumInstructions,
+    CanisterId, Cycles, NumBytes, NumInstructions, PrincipalId,
 };
 use proptest::prelude::*;
 use proptest::test_runner::{TestRng, TestRunner};
@@ -1211,6 +1215,105 @@ fn ic0_canister_self_copy_works() {
     assert_eq!(WasmResult::Reply(canister_id.get().into_vec()), result);
 }
 
+#[test]
+fn ic0_is_controller_works() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let wat = r#"
+        (module
+            (import "ic0" "msg_caller_size" (func $msg_caller_size (result i32)))
+            (import "ic0" "msg_caller_copy"
+                (func $msg_caller_copy (param i32 i32 i32))
+            )
+            (import "ic0" "is_controller"
+                (func $is_controller (param i32 i32) (result i32))
+            )
+            (import "ic0" "msg_reply" (func $msg_reply))
+            (import "ic0" "msg_reply_data_append"
+                (func $msg_reply_data_append (param i32 i32))
+            )
+            (func (export "canister_update test")
+                (call $msg_caller_copy (i32.const 0) (i32.const 0) (call $msg_caller_size))
+                (i32.store8
+                    (i32.const 100)
+                    (call $is_controller (i32.const 0) (call $msg_caller_size))
+                )
+                (call $msg_reply_data_append (i32.const 100) (i32.const 1))
+                (call $msg_reply)
+            )
+            (memory 1 1)
+        )"#;
+    let canister_id = test.canister_from_wat(wat).unwrap();
+    let result = test.ingress(canister_id, "test", vec![]).unwrap();
+    assert_eq!(WasmResult::Reply(vec![1]), result);
+
+    test.set_controller(canister_id, PrincipalId::new_user_test_id(42))
+        .unwrap();
+    let result = test.ingress(canister_id, "test", vec![]).unwrap();
+    assert_eq!(WasmResult::Reply(vec![0]), result);
+}
+
+#[test]
+fn ic0_is_controller_traps_on_invalid_principal() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let wat = r#"
+        (module
+            (import "ic0" "is_controller"
+                (func $is_controller (param i32 i32) (result i32))
+            )
+            (func (export "canister_update test")
+                ;; Principals are at most 29 bytes long.
+                (drop (call $is_controller (i32.const 0) (i32.const 30)))
+            )
+            (memory 1 1)
+        )"#;
+    let canister_id = test.canister_from_wat(wat).unwrap();
+    let err = test.ingress(canister_id, "test", vec![]).unwrap_err();
+    assert_eq!(ErrorCode::CanisterTrapped, err.code());
+}
+
+#[test]
+fn ic0_canister_version_is_incremented_by_code_and_settings_changes() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let wat = r#"
+        (module
+            (import "ic0" "canister_version" (func $canister_version (result i64)))
+            (import "ic0" "msg_reply" (func $msg_reply))
+            (import "ic0" "msg_reply_data_append"
+                (func $msg_reply_data_append (param i32 i32))
+            )
+            (func (export "canister_update test")
+                (i64.store (i32.const 0) (call $canister_version))
+                (call $msg_reply_data_append (i32.const 0) (i32.const 8))
+                (call $msg_reply)
+            )
+            (memory 1 1)
+        )"#;
+    fn canister_version(test: &mut ExecutionTest, canister_id: CanisterId) -> u64 {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&get_reply(test.ingress(canister_id, "test", vec![])));
+        u64::from_le_bytes(bytes)
+    }
+    let canister_id = test.canister_from_wat(wat).unwrap();
+    assert_eq!(canister_version(&mut test, canister_id), 1);
+
+    test.update_freezing_threshold(canister_id, NumSeconds::from(1))
+        .unwrap();
+    assert_eq!(canister_version(&mut test, canister_id), 2);
+
+    test.upgrade_canister(canister_id, wabt::wat2wasm(wat).unwrap())
+        .unwrap();
+    assert_eq!(canister_version(&mut test, canister_id), 3);
+
+    // Executing messages does not change the version.
+    assert_eq!(canister_version(&mut test, canister_id), 3);
+    assert_eq!(
+        test.canister_state(canister_id)
+            .system_state
+            .canister_version,
+        3
+    );
+}
+
 #[test]
 fn ic0_call_simple_has_no_effect_on_trap() {
     let mut test = ExecutionTestBuilder::new().build();
@@ -3001,6 +3104,174 @@ fn memory_fill_can_trigger_out_of_instructions() {
     assert_eq!(ErrorCode::CanisterInstructionLimitExceeded, err.code());
 }
 
+// Verify that bulk memory instructions are charged exactly one instruction per
+// byte on top of their static cost.
+#[test]
+fn bulk_memory_instructions_cost_one_instruction_per_byte() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let wat = |size: u32| {
+        format!(
+            r#"
+            (module
+                (memory 1)
+                (data "{data}")
+                (func (;0;)
+                    (memory.fill (i32.const 0) (i32.const 1) (i32.const {size}))
+                    (memory.copy (i32.const 1000) (i32.const 0) (i32.const {size}))
+                    (memory.init 0 (i32.const 2000) (i32.const 0) (i32.const {size})))
+                (start 0)
+            )"#,
+            data = "x".repeat(500),
+            size = size,
+        )
+    };
+    let empty = test.canister_from_wat(wat(0)).unwrap();
+    let full = test.canister_from_wat(wat(500)).unwrap();
+    assert_eq!(
+        test.canister_executed_instructions(full) - test.canister_executed_instructions(empty),
+        NumInstructions::from(500 + 500 + 500)
+    );
+}
+
+// Verify that `memory.init` copies the bytes of a passive data segment.
+#[test]
+fn memory_init_copies_passive_data_segment() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let wat = r#"
+        (module
+            (import "ic0" "msg_reply" (func $msg_reply))
+            (import "ic0" "msg_reply_data_append"
+                (func $msg_reply_data_append (param i32 i32))
+            )
+            (memory 1)
+            (data (i32.const 0) "active")
+            (data "passive")
+            (func (export "canister_update test")
+                (memory.init 1 (i32.const 6) (i32.const 0) (i32.const 7))
+                (call $msg_reply_data_append (i32.const 0) (i32.const 13))
+                (call $msg_reply)
+            )
+        )"#;
+    let canister_id = test.canister_from_wat(wat).unwrap();
+    let result = test.ingress(canister_id, "test", vec![]).unwrap();
+    assert_eq!(WasmResult::Reply(b"activepassive".to_vec()), result);
+}
+
+// Returns the static costs of `memory.fill`, `memory.copy`, `memory.init` and
+// `table.copy` in the given cost schedule.
+fn bulk_memory_static_costs(schedule: InstructionCostSchedule) -> Vec<NumInstructions> {
+    let mut test = ExecutionTestBuilder::new()
+        .with_instruction_cost_schedule(schedule)
+        .build();
+    let mut executed_instructions = |body: &str| {
+        let wat = format!(
+            r#"
+            (module
+                (memory 1)
+                (table 1 funcref)
+                (data "")
+                (func (;0;) {body})
+                (start 0)
+            )"#,
+            body = body,
+        );
+        let canister_id = test.canister_from_wat(wat).unwrap();
+        test.canister_executed_instructions(canister_id)
+    };
+    // The baseline executes the same three constants and three drops.
+    let baseline =
+        executed_instructions("(drop (i32.const 0)) (drop (i32.const 0)) (drop (i32.const 0))");
+    ["memory.fill", "memory.copy", "memory.init 0", "table.copy"]
+        .iter()
+        .map(|instruction| {
+            executed_instructions(&format!(
+                "({} (i32.const 0) (i32.const 0) (i32.const 0))",
+                instruction
+            )) + NumInstructions::from(3)
+                - baseline
+        })
+        .collect()
+}
+
+// Verify that the static cost of bulk memory instructions depends on the
+// instruction in the `V2` cost schedule.
+#[test]
+fn bulk_memory_instructions_have_instruction_specific_static_costs() {
+    assert_eq!(
+        bulk_memory_static_costs(InstructionCostSchedule::V2),
+        vec![
+            NumInstructions::from(8),
+            NumInstructions::from(10),
+            NumInstructions::from(12),
+            NumInstructions::from(16),
+        ]
+    );
+}
+
+// Verify that the `V1` cost schedule keeps charging one instruction for every
+// bulk memory instruction.
+#[test]
+fn bulk_memory_instructions_have_unit_static_cost_in_v1_schedule() {
+    assert_eq!(
+        bulk_memory_static_costs(InstructionCostSchedule::V1),
+        vec![NumInstructions::from(1); 4]
+    );
+}
+
+// Verify that SIMD instructions are executed. The module is assembled by hand
+// because `wabt` only knows a pre-standard encoding of the SIMD instructions.
+#[test]
+fn simd_instructions_are_executed() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_instruction_cost_schedule(InstructionCostSchedule::V2)
+        .build();
+    let section = |id: u8, content: &[u8]| {
+        let mut section = vec![id, content.len() as u8];
+        section.extend_from_slice(content);
+        section
+    };
+    let mut wasm = b"\0asm".to_vec();
+    wasm.extend_from_slice(&1_u32.to_le_bytes());
+    // (type (func)) (type (func (param i32 i32)))
+    wasm.extend(section(
+        1,
+        &[0x02, 0x60, 0x00, 0x00, 0x60, 0x02, 0x7f, 0x7f, 0x00],
+    ));
+    // (import "ic0" "msg_reply_data_append" (func (type 1)))
+    // (import "ic0" "msg_reply" (func (type 0)))
+    let mut imports = vec![0x02];
+    for (name, type_index) in [("msg_reply_data_append", 1), ("msg_reply", 0)] {
+        imports.extend_from_slice(&[0x03, b'i', b'c', b'0', name.len() as u8]);
+        imports.extend_from_slice(name.as_bytes());
+        imports.extend_from_slice(&[0x00, type_index]);
+    }
+    wasm.extend(section(2, &imports));
+    wasm.extend(section(3, &[0x01, 0x00]));
+    // (memory 1)
+    wasm.extend(section(5, &[0x01, 0x00, 0x01]));
+    // (export "canister_update test" (func 2))
+    let mut export = vec![0x01, 20];
+    export.extend_from_slice(b"canister_update test");
+    export.extend_from_slice(&[0x00, 0x02]);
+    wasm.extend(section(7, &export));
+    // (func
+    //   (v128.store (i32.const 0)
+    //     (i32x4.add (i32x4.splat (i32.const 1)) (i32x4.splat (i32.const 2))))
+    //   (call $msg_reply_data_append (i32.const 0) (i32.const 16))
+    //   (call $msg_reply))
+    wasm.extend(section(
+        10,
+        &[
+            0x01, 0x1b, 0x00, 0x41, 0x00, 0x41, 0x01, 0xfd, 0x11, 0x41, 0x02, 0xfd, 0x11, 0xfd,
+            0xae, 0x01, 0xfd, 0x0b, 0x04, 0x00, 0x41, 0x00, 0x41, 0x10, 0x10, 0x00, 0x10, 0x01,
+            0x0b,
+        ],
+    ));
+    let canister_id = test.canister_from_binary(wasm).unwrap();
+    let result = test.ingress(canister_id, "test", vec![]).unwrap();
+    assert_eq!(WasmResult::Reply([3, 0, 0, 0].repeat(4)), result);
+}
+
 #[test]
 fn broken_wasm_results_in_compilation_error() {
     let mut test = ExecutionTestBuilder::new().build();
@@ -28,6 +28,7 @@ struct Dashboard<'a> {{
     canisters: &'a Vec<&'a ic_replicated_state::CanisterState>,
     cow_memory_manager_enabled: bool,
     replica_version: ic_types::ReplicaVersion,
+    height_timings: &'a [ic_interfaces::consensus_pool::HeightTimings],
 }}
     "#,
             std::fs::read_to_string("templates/dashboard.html").unwrap()
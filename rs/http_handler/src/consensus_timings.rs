@@ -0,0 +1,113 @@
+//! Module that serves the consensus timings of the most recent heights as
+//! JSON, which helps to diagnose slow finalization.
+
+use crate::{
+    common::{make_plaintext_response, CONTENT_TYPE_JSON},
+    EndpointService,
+};
+use hyper::{Body, Response, StatusCode};
+use ic_interfaces::consensus_pool::ConsensusPoolCache;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{
+    limit::concurrency::GlobalConcurrencyLimitLayer, util::BoxCloneService, BoxError, Service,
+    ServiceBuilder,
+};
+
+const MAX_CONSENSUS_TIMINGS_CONCURRENT_REQUESTS: usize = 100;
+
+#[derive(Clone)]
+pub(crate) struct ConsensusTimingsService {
+    consensus_pool_cache: Arc<dyn ConsensusPoolCache>,
+}
+
+impl ConsensusTimingsService {
+    pub(crate) fn new_service(
+        consensus_pool_cache: Arc<dyn ConsensusPoolCache>,
+    ) -> EndpointService {
+        BoxCloneService::new(
+            ServiceBuilder::new()
+                .layer(GlobalConcurrencyLimitLayer::new(
+                    MAX_CONSENSUS_TIMINGS_CONCURRENT_REQUESTS,
+                ))
+                .service(Self {
+                    consensus_pool_cache,
+                }),
+        )
+    }
+}
+
+impl Service<Body> for ConsensusTimingsService {
+    type Response = Response<Body>;
+    type Error = BoxError;
+    #[allow(clippy::type_complexity)]
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + Sync>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _unused: Body) -> Self::Future {
+        use hyper::header;
+        let height_timings = self.consensus_pool_cache.height_timings();
+        let res = match serde_json::to_vec(&height_timings) {
+            Ok(content) => {
+                let mut response = Response::new(Body::from(content));
+                *response.status_mut() = StatusCode::OK;
+                response.headers_mut().insert(
+                    header::CONTENT_TYPE,
+                    header::HeaderValue::from_static(CONTENT_TYPE_JSON),
+                );
+                response
+            }
+            Err(e) => make_plaintext_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Internal error: {}", e),
+            ),
+        };
+        Box::pin(async move { Ok(res) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_interfaces::consensus_pool::{HeightTimings, PayloadSizes};
+    use ic_test_utilities::{consensus::MockConsensusCache, mock_time};
+    use ic_types::Height;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn consensus_timings_are_served_as_json() {
+        let mut timings = HeightTimings::new(Height::from(5));
+        timings.block_proposal_received = Some(mock_time());
+        timings.finalized = Some(mock_time() + Duration::from_millis(300));
+        timings.block_maker_rank = Some(1);
+        timings.payload_sizes = Some(PayloadSizes {
+            ingress_messages: 2,
+            ingress_bytes: 100,
+            xnet_bytes: 0,
+        });
+        let expected = vec![HeightTimings::new(Height::from(4)), timings];
+        let mut consensus_pool_cache = MockConsensusCache::new();
+        consensus_pool_cache
+            .expect_height_timings()
+            .return_const(expected.clone());
+
+        let response = ConsensusTimingsService::new_service(Arc::new(consensus_pool_cache))
+            .oneshot(Body::empty())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[hyper::header::CONTENT_TYPE],
+            CONTENT_TYPE_JSON
+        );
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let served: Vec<HeightTimings> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(served, expected);
+    }
+}
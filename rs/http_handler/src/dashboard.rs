@@ -9,10 +9,12 @@ use crate::{
 use askama::Template;
 use hyper::{Body, Response, StatusCode};
 use ic_config::http_handler::Config;
+use ic_interfaces::consensus_pool::ConsensusPoolCache;
 use ic_registry_subnet_type::SubnetType;
-use ic_types::{Height, ReplicaVersion};
+use ic_types::{Height, ReplicaVersion, Time};
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 use tower::{
     limit::concurrency::GlobalConcurrencyLimitLayer, util::BoxCloneService, BoxError, Service,
@@ -24,11 +26,31 @@ include!(concat!(env!("OUT_DIR"), "/dashboard.rs"));
 
 const MAX_DASHBOARD_CONCURRENT_REQUESTS: usize = 100;
 
+// The number of most recent heights whose consensus timings are shown.
+const DASHBOARD_HEIGHT_TIMINGS: usize = 50;
+
+impl Dashboard<'_> {
+    // Format the time elapsed between `start` and `end` in milliseconds, if
+    // both are known.
+    fn elapsed(&self, start: &Option<Time>, end: &Option<Time>) -> String {
+        match (start, end) {
+            (Some(start), Some(end)) => format!(
+                "{}",
+                end.as_nanos_since_unix_epoch()
+                    .saturating_sub(start.as_nanos_since_unix_epoch())
+                    / 1_000_000
+            ),
+            _ => "-".to_string(),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct DashboardService {
     config: Config,
     subnet_type: SubnetType,
     state_reader_executor: StateReaderExecutor,
+    consensus_pool_cache: Arc<dyn ConsensusPoolCache>,
 }
 
 impl DashboardService {
@@ -36,11 +58,13 @@ impl DashboardService {
         config: Config,
         subnet_type: SubnetType,
         state_reader_executor: StateReaderExecutor,
+        consensus_pool_cache: Arc<dyn ConsensusPoolCache>,
     ) -> EndpointService {
         let base_service = Self {
             config,
             subnet_type,
             state_reader_executor,
+            consensus_pool_cache,
         };
         BoxCloneService::new(
             ServiceBuilder::new()
@@ -67,6 +91,9 @@ impl Service<Body> for DashboardService {
         let http_config = self.config.clone();
         let subnet_type = self.subnet_type;
         let state_reader_executor = self.state_reader_executor.clone();
+        let mut height_timings = self.consensus_pool_cache.height_timings();
+        height_timings.reverse();
+        height_timings.truncate(DASHBOARD_HEIGHT_TIMINGS);
         Box::pin(async move {
             let labeled_state = match state_reader_executor.get_latest_state().await {
                 Ok(ls) => ls,
@@ -86,6 +113,7 @@ impl Service<Body> for DashboardService {
                 // TODO(EXC-750): Remove this field.
                 cow_memory_manager_enabled: false,
                 replica_version: ReplicaVersion::default(),
+                height_timings: &height_timings,
             };
 
             let res = match dashboard.render() {
@@ -110,3 +138,53 @@ impl Service<Body> for DashboardService {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_interfaces::consensus_pool::{HeightTimings, PayloadSizes};
+    use ic_test_utilities::{mock_time, state::ReplicatedStateBuilder};
+    use std::time::Duration;
+
+    #[test]
+    fn dashboard_renders_consensus_timings() {
+        let replicated_state = ReplicatedStateBuilder::new().build();
+        let mut timings = HeightTimings::new(Height::from(7));
+        timings.block_proposal_received = Some(mock_time());
+        timings.notarized = Some(mock_time() + Duration::from_millis(120));
+        timings.finalized = Some(mock_time() + Duration::from_millis(250));
+        timings.block_maker_rank = Some(2);
+        timings.payload_sizes = Some(PayloadSizes {
+            ingress_messages: 3,
+            ingress_bytes: 1024,
+            xnet_bytes: 64,
+        });
+        let height_timings = vec![timings, HeightTimings::new(Height::from(6))];
+        let dashboard = Dashboard {
+            subnet_type: SubnetType::Application,
+            http_config: &Config::default(),
+            height: Height::from(7),
+            replicated_state: &replicated_state,
+            canisters: &Vec::new(),
+            cow_memory_manager_enabled: false,
+            replica_version: ReplicaVersion::default(),
+            height_timings: &height_timings,
+        };
+
+        let html = dashboard.render().unwrap();
+        let start = html.find("<h2>Consensus Timings</h2>").unwrap();
+        let end = start + html[start..].find("</table>").unwrap();
+        let cells: Vec<&str> = html[start..end]
+            .split("<td class=\"number\">")
+            .skip(1)
+            .map(|cell| cell.split("</td>").next().unwrap())
+            .collect();
+        assert_eq!(
+            cells,
+            vec![
+                "7", "2", "3", "1024", "64", "120", "250", "-", "-", // height 7
+                "6", "-", "-", "-", "-", "-", "-", "-", "-", // height 6
+            ]
+        );
+    }
+}
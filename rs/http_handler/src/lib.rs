@@ -9,6 +9,7 @@ mod body;
 mod call;
 mod catch_up_package;
 mod common;
+mod consensus_timings;
 mod dashboard;
 mod metrics;
 mod pprof;
@@ -23,6 +24,7 @@ use crate::{
     call::CallService,
     catch_up_package::CatchUpPackageService,
     common::{get_cors_headers, make_plaintext_response, map_box_error_to_response},
+    consensus_timings::ConsensusTimingsService,
     dashboard::DashboardService,
     metrics::{
         LABEL_REQUEST_TYPE, LABEL_STATUS, LABEL_TYPE, REQUESTS_LABEL_NAMES, REQUESTS_NUM_LABELS,
@@ -127,6 +129,7 @@ pub(crate) const MAX_REQUEST_SIZE_BYTES: Byte = Byte::from_bytes(5 * 1024 * 1024
 pub(crate) const MAX_REQUEST_RECEIVE_DURATION: Duration = Duration::from_secs(300); // 5 min
 
 const HTTP_DASHBOARD_URL_PATH: &str = "/_/dashboard";
+const HTTP_CONSENSUS_TIMINGS_URL_PATH: &str = "/_/consensus_timings";
 const CONTENT_TYPE_CBOR: &str = "application/cbor";
 
 // Placeholder used when we can't determine the approriate prometheus label.
@@ -161,6 +164,7 @@ struct HttpHandler {
     query_service: EndpointService,
     catchup_service: EndpointService,
     dashboard_service: EndpointService,
+    consensus_timings_service: EndpointService,
     status_service: EndpointService,
     read_state_service: EndpointService,
 
@@ -332,7 +336,10 @@ pub fn start_server(
             config.clone(),
             subnet_type,
             state_reader_executor.clone(),
+            Arc::clone(&consensus_pool_cache),
         );
+        let consensus_timings_service =
+            ConsensusTimingsService::new_service(Arc::clone(&consensus_pool_cache));
         let catchup_service =
             CatchUpPackageService::new_service(metrics.clone(), consensus_pool_cache);
 
@@ -346,6 +353,7 @@ pub fn start_server(
             status_service,
             catchup_service,
             dashboard_service,
+            consensus_timings_service,
             read_state_service,
             delegation_from_nns,
             health_status,
@@ -580,6 +588,7 @@ async fn make_router(
     let status_service = http_handler.status_service.clone();
     let catch_up_package_service = http_handler.catchup_service.clone();
     let dashboard_service = http_handler.dashboard_service.clone();
+    let consensus_timings_service = http_handler.consensus_timings_service.clone();
     let read_state_service = http_handler.read_state_service.clone();
 
     metrics
@@ -654,6 +663,10 @@ async fn make_router(
                 set_timer_labels(&mut timer, ApiReqType::Dashboard);
                 dashboard_service
             }
+            HTTP_CONSENSUS_TIMINGS_URL_PATH => {
+                set_timer_labels(&mut timer, ApiReqType::ConsensusTimings);
+                consensus_timings_service
+            }
             "/_/pprof" => {
                 set_timer_labels(&mut timer, ApiReqType::PprofHome);
                 return (pprof::home(), timer);
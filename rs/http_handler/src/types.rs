@@ -18,6 +18,7 @@ pub(crate) enum ApiReqType {
     CatchUpPackage,
     Status,
     Dashboard,
+    ConsensusTimings,
     RedirectToDashboard,
     Options,
     PprofHome,
@@ -66,6 +67,10 @@ mod tests {
         );
         assert_eq!(StaticStr::from(ApiReqType::Options), "options");
         assert_eq!(StaticStr::from(ApiReqType::Dashboard), "dashboard");
+        assert_eq!(
+            StaticStr::from(ApiReqType::ConsensusTimings),
+            "consensus_timings"
+        );
         assert_eq!(
             StaticStr::from(ApiReqType::RedirectToDashboard),
             "redirect_to_dashboard"
@@ -482,6 +482,7 @@ impl IcManager {
             legacy_flow_tag: 1234,
             listening_port: p2p_port,
             send_queue_size: 256,
+            send_hello: false,
         });
         replica_config.state_manager = Some(StateManagerConfig::new(state_manager_root));
         replica_config.http_handler = Some(http_handler::ExternalConfig {
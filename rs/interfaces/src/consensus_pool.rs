@@ -185,6 +185,20 @@ pub trait PoolSection<T> {
         )
     }
 
+    /// Return the CatchUpPackages in the given height range in protobuf form.
+    /// Pools that keep the original protobuf of a CatchUpPackage return it
+    /// as is, so that its signature remains verifiable.
+    fn catch_up_package_protos(
+        &self,
+        range: HeightRange,
+    ) -> Box<dyn Iterator<Item = pb::CatchUpPackage>> {
+        Box::new(
+            self.catch_up_package()
+                .get_by_height_range(range)
+                .map(|cup| pb::CatchUpPackage::from(&cup)),
+        )
+    }
+
     fn size(&self) -> u64;
 }
 
@@ -260,6 +274,55 @@ pub trait HeightIndexedPool<T> {
 }
 // end::interface[]
 
+/// The times at which a node observed the progress of a single height,
+/// together with information about the block finalized at that height.
+///
+/// All times are taken from the node's local clock, so they are only
+/// comparable between nodes up to clock skew.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeightTimings {
+    pub height: Height,
+    /// When the first block proposal at this height was received or made.
+    pub block_proposal_received: Option<Time>,
+    /// When the first notarization at this height was validated.
+    pub notarized: Option<Time>,
+    /// When the finalization at this height was validated.
+    pub finalized: Option<Time>,
+    /// When the finalized block at this height was delivered as a batch to
+    /// message routing.
+    pub batch_delivered: Option<Time>,
+    /// When the state at this height was certified.
+    pub certified: Option<Time>,
+    /// The rank of the block maker of the finalized block.
+    pub block_maker_rank: Option<u64>,
+    /// The payload sizes of the finalized block.
+    pub payload_sizes: Option<PayloadSizes>,
+}
+
+impl HeightTimings {
+    pub fn new(height: Height) -> Self {
+        Self {
+            height,
+            block_proposal_received: None,
+            notarized: None,
+            finalized: None,
+            batch_delivered: None,
+            certified: None,
+            block_maker_rank: None,
+            payload_sizes: None,
+        }
+    }
+}
+
+/// The sizes of the batch payload of a block. They are all zero for summary
+/// blocks.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PayloadSizes {
+    pub ingress_messages: usize,
+    pub ingress_bytes: usize,
+    pub xnet_bytes: usize,
+}
+
 /// Reader of consensus related states.
 pub trait ConsensusPoolCache: Send + Sync {
     /// Return the latest/highest finalized block.
@@ -307,6 +370,23 @@ pub trait ConsensusPoolCache: Send + Sync {
         let catchup_package_height = self.catch_up_package().height();
         certified_height.max(catchup_package_height)
     }
+
+    /// Return the timings of the most recent heights observed by this node,
+    /// in ascending order of height.
+    fn height_timings(&self) -> Vec<HeightTimings> {
+        Vec::new()
+    }
+}
+
+/// Records the progress of a height that is not visible in the consensus
+/// pool, to be reported in [ConsensusPoolCache::height_timings].
+pub trait ConsensusTimingsRecorder: Send + Sync {
+    /// Record the time at which the batch at the given height was delivered
+    /// to message routing.
+    fn record_batch_delivered(&self, height: Height, time: Time);
+
+    /// Record the time at which the state at the given height was certified.
+    fn record_certified(&self, height: Height, time: Time);
 }
 
 /// Cache of blocks from the block chain.
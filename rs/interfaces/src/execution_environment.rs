@@ -283,6 +283,17 @@ pub trait QueryHandler: Send + Sync {
         state: Arc<Self::State>,
         data_certificate: Vec<u8>,
     ) -> Result<WasmResult, UserError>;
+
+    /// Same as `query`, but additionally returns the instructions executed by
+    /// each function of the canisters involved in the query, in the folded
+    /// stack format understood by flamegraph tools. The profile is empty
+    /// unless instruction profiling is enabled.
+    fn query_with_instruction_profile(
+        &self,
+        query: UserQuery,
+        state: Arc<Self::State>,
+        data_certificate: Vec<u8>,
+    ) -> (Result<WasmResult, UserError>, String);
 }
 
 /// Errors that can be returned when reading/writing from/to ingress history.
@@ -494,6 +505,16 @@ pub trait SystemApi {
         heap: &mut [u8],
     ) -> HypervisorResult<()>;
 
+    /// Returns 1 if the principal given by `size` bytes starting at `src` on
+    /// the heap is a controller of the canister, and 0 otherwise.
+    ///
+    /// Traps if the bytes do not form a valid principal.
+    fn ic0_is_controller(&self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<u32>;
+
+    /// Returns the version of the canister, which is incremented on every
+    /// successful code installation and settings change.
+    fn ic0_canister_version(&self) -> HypervisorResult<u64>;
+
     /// Outputs the specified bytes on the heap as a string on STDOUT.
     fn ic0_debug_print(&self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<()>;
 
@@ -922,6 +943,10 @@ pub struct WasmExecutionOutput {
     pub allocated_bytes: NumBytes,
     pub allocated_message_bytes: NumBytes,
     pub instance_stats: InstanceStats,
+    /// Pairs of a function index and the number of instructions the function
+    /// executed during this execution. Only filled in if instruction
+    /// profiling is enabled.
+    pub instruction_profile: Vec<(u32, u64)>,
 }
 
 impl fmt::Display for WasmExecutionOutput {
@@ -449,6 +449,7 @@ mod tests {
             legacy_flow_tag: 1337,
             listening_port: 23,
             send_queue_size: 1,
+            send_hello: false,
         };
 
         with_test_replica_logger(|log| {
@@ -0,0 +1,39 @@
+//! Replays a recorded advert stream against all download policies and prints
+//! the resulting download latency and redundancy.
+//!
+//! Usage: `simulate_download_policy <recording> [max_duplicity]`
+//!
+//! See `ic_p2p::download_policy::simulator` for the format of the recording.
+
+use ic_config::p2p::DownloadPolicyType;
+use ic_p2p::download_policy::{
+    new_download_policy,
+    simulator::{parse_recording, simulate, SimulationConfig},
+};
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 || args.len() > 3 {
+        eprintln!("Usage: {} <recording> [max_duplicity]", args[0]);
+        std::process::exit(1);
+    }
+
+    let recording = std::fs::read_to_string(&args[1])
+        .unwrap_or_else(|err| panic!("Failed to read {}: {}", args[1], err));
+    let adverts = parse_recording(&recording).unwrap_or_else(|err| panic!("{}", err));
+
+    let mut config = SimulationConfig::default();
+    if let Some(max_duplicity) = args.get(2) {
+        config.max_duplicity = max_duplicity.parse().expect("Invalid max_duplicity");
+    }
+
+    for policy_type in [
+        DownloadPolicyType::FirstAvailable,
+        DownloadPolicyType::RoundRobin,
+        DownloadPolicyType::LatencyAware,
+        DownloadPolicyType::BandwidthWeighted,
+    ] {
+        let report = simulate(new_download_policy(policy_type).as_ref(), &adverts, &config);
+        println!("{:?}: {}", policy_type, report);
+    }
+}
@@ -68,6 +68,7 @@ extern crate lru;
 
 use crate::{
     artifact_download_list::{ArtifactDownloadList, ArtifactDownloadListImpl},
+    download_policy::{new_download_policy, DownloadCandidate, DownloadPolicy},
     download_prioritization::{
         AdvertTracker, AdvertTrackerFinalAction, DownloadAttemptTracker, DownloadPrioritizer,
         DownloadPrioritizerImpl,
@@ -81,6 +82,7 @@ use crate::{
     utils::FlowMapper,
     P2PError, P2PErrorCode, P2PResult,
 };
+use ic_config::p2p::Config as P2PConfig;
 use ic_interfaces::{
     artifact_pool::ArtifactPoolError::ArtifactReplicaVersionError,
     consensus_pool::ConsensusPoolCache,
@@ -111,7 +113,7 @@ use std::{
     error::Error,
     ops::DerefMut,
     sync::{Arc, Mutex, RwLock},
-    time::{Instant, SystemTime},
+    time::{Duration, Instant, SystemTime},
 };
 
 /// The download manager maintains data structures on adverts and download state
@@ -200,6 +202,8 @@ pub(crate) struct DownloadManagerImpl {
     metrics: DownloadManagementMetrics,
     /// The *Gossip* configuration.
     gossip_config: GossipConfig,
+    /// The policy that chooses the peers from which chunks are downloaded.
+    download_policy: Box<dyn DownloadPolicy>,
     /// The cache that is used to check if an artifact has been downloaded
     /// recently.
     receive_check_caches: RwLock<HashMap<NodeId, ReceiveCheckCache>>,
@@ -328,6 +332,7 @@ impl DownloadManager for DownloadManagerImpl {
         );
 
         // Remove the chunk request tracker.
+        let mut delivery_time = None;
         let mut current_peers = self.peer_manager.current_peers().lock().unwrap();
         if let Some(peer_context) = current_peers.get_mut(&peer_id) {
             if let Some(tracker) = peer_context.requested.remove(&GossipRequestTrackerKey {
@@ -335,12 +340,14 @@ impl DownloadManager for DownloadManagerImpl {
                 integrity_hash: gossip_chunk.integrity_hash.clone(),
                 chunk_id: gossip_chunk.chunk_id,
             }) {
+                let elapsed = tracker.requested_instant.elapsed();
                 let artifact_tag: &'static str =
                     ArtifactTag::from(&gossip_chunk.artifact_id).into();
                 self.metrics
                     .chunk_delivery_time
                     .with_label_values(&[artifact_tag])
-                    .observe(tracker.requested_instant.elapsed().as_millis() as f64);
+                    .observe(elapsed.as_millis() as f64);
+                delivery_time = Some(elapsed);
             } else {
                 trace!(
                     self.log,
@@ -420,6 +427,17 @@ impl DownloadManager for DownloadManagerImpl {
         }
         let artifact_tracker = artifact_tracker.unwrap();
 
+        // Let the download policy learn from the delivery.
+        if let Some(delivery_time) = delivery_time {
+            self.download_policy.on_chunk_delivered(
+                peer_id,
+                artifact_tracker
+                    .chunkable
+                    .get_chunk_size(gossip_chunk.chunk_id),
+                delivery_time,
+            );
+        }
+
         // Feed the chunk to the tracker.
         let completed_artifact = match artifact_tracker
             .chunkable
@@ -773,6 +791,7 @@ impl DownloadManagerImpl {
         artifact_manager: Arc<dyn ArtifactManager>,
         transport: Arc<dyn Transport>,
         flow_mapper: Arc<FlowMapper>,
+        p2p_config: P2PConfig,
         log: ReplicaLogger,
         metrics_registry: &MetricsRegistry,
     ) -> Self {
@@ -804,6 +823,7 @@ impl DownloadManagerImpl {
             log,
             metrics: DownloadManagementMetrics::new(metrics_registry),
             gossip_config,
+            download_policy: new_download_policy(p2p_config.download_policy),
             receive_check_caches: RwLock::new(HashMap::new()),
             pfn_invocation_instant: Mutex::new(Instant::now()),
             registry_refresh_instant: Mutex::new(Instant::now()),
@@ -1090,6 +1110,28 @@ impl DownloadManagerImpl {
             None?
         }
 
+        // Skip if the download policy leaves the chunk to another advertiser
+        // that has not attempted it in this round yet.
+        let candidates: Vec<_> = advert_tracker
+            .peers
+            .iter()
+            .filter(|advertiser| !advert_tracker.peer_attempted(chunk_id, advertiser))
+            .filter_map(|advertiser| {
+                peers.get(advertiser).map(|context| DownloadCandidate {
+                    peer_id: *advertiser,
+                    in_flight: context.requested.len(),
+                })
+            })
+            .collect();
+        if !self.download_policy.should_request(
+            peer_id,
+            &advert_tracker.advert.integrity_hash,
+            chunk_id,
+            &candidates,
+        ) {
+            None?
+        }
+
         // Since the peer has not attempted a chunk download in this round and will not
         // violate duplicity constraints, a gossip chunk request is returned.
         Some(GossipChunkRequest {
@@ -1253,6 +1295,10 @@ impl DownloadManagerImpl {
                 >= self.gossip_config.max_chunk_wait_ms as u128;
             if timed_out {
                 self.metrics.chunks_timed_out.inc();
+                self.download_policy.on_chunk_timed_out(
+                    *node_id,
+                    Duration::from_millis(self.gossip_config.max_chunk_wait_ms as u64),
+                );
                 timed_out_chunks.push((
                     *node_id,
                     key.chunk_id,
@@ -1552,6 +1598,7 @@ pub mod tests {
             artifact_manager,
             tp,
             flow_mapper,
+            P2PConfig::default(),
             log,
             &metrics_registry,
         )
@@ -0,0 +1,289 @@
+//! Download policies choose from which of the peers that advertised an
+//! artifact each chunk of the artifact is downloaded.
+//!
+//! The download manager asks the policy for every chunk it considers
+//! requesting from a peer in `download_next()`. The policy is given the
+//! *candidates* for the chunk: the connected advertisers of the artifact that
+//! did not attempt the chunk in the current attempt round, together with the
+//! number of requests they have in flight. If the policy declines the request,
+//! the chunk is left for another candidate, which picks it up the next time it
+//! is asked for work, e.g., after one of its requests completed. The limits on
+//! download capacity and duplicity are enforced by the download manager,
+//! independently of the policy.
+//!
+//! The policy to use is selected with the `download_policy` field of the P2P
+//! config. The [`simulator`] replays recorded advert streams against a policy,
+//! so that policies can be compared without a testnet.
+
+use ic_config::p2p::DownloadPolicyType;
+use ic_types::{chunkable::ChunkId, crypto::CryptoHash, NodeId};
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+pub mod simulator;
+
+/// The weight of a new sample in the moving averages kept per peer.
+const EWMA_WEIGHT: f64 = 0.2;
+
+/// A peer that could request a chunk.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DownloadCandidate {
+    pub peer_id: NodeId,
+    /// The number of chunk requests the peer has in flight.
+    pub in_flight: usize,
+}
+
+/// A strategy to choose from which peers chunks are downloaded.
+pub trait DownloadPolicy: Send + Sync {
+    /// Returns whether `peer_id`, which has free capacity, should request the
+    /// chunk `chunk_id` of the artifact with the given integrity hash now.
+    /// `candidates` contains all the peers that could request the chunk,
+    /// including `peer_id`.
+    fn should_request(
+        &self,
+        peer_id: NodeId,
+        integrity_hash: &CryptoHash,
+        chunk_id: ChunkId,
+        candidates: &[DownloadCandidate],
+    ) -> bool;
+
+    /// Reports that a chunk of `size_bytes` bytes was delivered by `peer_id`,
+    /// `delivery_time` after it was requested.
+    fn on_chunk_delivered(&self, _peer_id: NodeId, _size_bytes: usize, _delivery_time: Duration) {}
+
+    /// Reports that a chunk request to `peer_id` timed out.
+    fn on_chunk_timed_out(&self, _peer_id: NodeId, _timeout: Duration) {}
+}
+
+/// Creates the download policy of the given type.
+pub fn new_download_policy(policy_type: DownloadPolicyType) -> Box<dyn DownloadPolicy> {
+    match policy_type {
+        DownloadPolicyType::FirstAvailable => Box::new(FirstAvailable),
+        DownloadPolicyType::RoundRobin => Box::new(RoundRobin),
+        DownloadPolicyType::LatencyAware => Box::new(LatencyAware::default()),
+        DownloadPolicyType::BandwidthWeighted => Box::new(BandwidthWeighted::default()),
+    }
+}
+
+/// Any candidate requests the chunk as soon as it is asked for work.
+pub struct FirstAvailable;
+
+impl DownloadPolicy for FirstAvailable {
+    fn should_request(
+        &self,
+        _peer_id: NodeId,
+        _integrity_hash: &CryptoHash,
+        _chunk_id: ChunkId,
+        _candidates: &[DownloadCandidate],
+    ) -> bool {
+        true
+    }
+}
+
+/// The consecutive chunks of an artifact are assigned to the candidates in
+/// turn, so that the downloads are spread evenly over the advertisers. A chunk
+/// waits for the candidate it is assigned to, even if that candidate is busy.
+pub struct RoundRobin;
+
+impl DownloadPolicy for RoundRobin {
+    fn should_request(
+        &self,
+        peer_id: NodeId,
+        integrity_hash: &CryptoHash,
+        chunk_id: ChunkId,
+        candidates: &[DownloadCandidate],
+    ) -> bool {
+        if candidates.is_empty() {
+            return false;
+        }
+        let mut peers: Vec<_> = candidates.iter().map(|c| c.peer_id).collect();
+        peers.sort();
+        // Start the rotation at a different peer for every artifact.
+        let offset = integrity_hash
+            .0
+            .iter()
+            .fold(0usize, |sum, byte| sum.wrapping_add(*byte as usize));
+        let turn = offset.wrapping_add(chunk_id.get() as usize) % peers.len();
+        peers[turn] == peer_id
+    }
+}
+
+/// Maintains an exponentially weighted moving average of a value per peer.
+#[derive(Default)]
+struct PeerAverages(Mutex<HashMap<NodeId, f64>>);
+
+impl PeerAverages {
+    fn update(&self, peer_id: NodeId, sample: f64) {
+        let mut averages = self.0.lock().unwrap();
+        let average = averages.entry(peer_id).or_insert(sample);
+        *average += EWMA_WEIGHT * (sample - *average);
+    }
+
+    fn get(&self, peer_id: &NodeId) -> Option<f64> {
+        self.0.lock().unwrap().get(peer_id).copied()
+    }
+}
+
+/// The chunk is requested by the candidates with the lowest average delivery
+/// time, even if they are busy, as long as other candidates are slower.
+/// Timeouts count as deliveries that took as long as the timeout. Peers that
+/// did not deliver any chunk yet are preferred, so that they are probed.
+#[derive(Default)]
+pub struct LatencyAware {
+    latency_ms: PeerAverages,
+}
+
+impl DownloadPolicy for LatencyAware {
+    fn should_request(
+        &self,
+        peer_id: NodeId,
+        _integrity_hash: &CryptoHash,
+        _chunk_id: ChunkId,
+        candidates: &[DownloadCandidate],
+    ) -> bool {
+        let latency = |peer_id: &NodeId| self.latency_ms.get(peer_id).unwrap_or(0.0);
+        let own_latency = latency(&peer_id);
+        candidates
+            .iter()
+            .all(|candidate| own_latency <= latency(&candidate.peer_id))
+    }
+
+    fn on_chunk_delivered(&self, peer_id: NodeId, _size_bytes: usize, delivery_time: Duration) {
+        self.latency_ms
+            .update(peer_id, delivery_time.as_secs_f64() * 1000.0);
+    }
+
+    fn on_chunk_timed_out(&self, peer_id: NodeId, timeout: Duration) {
+        self.latency_ms
+            .update(peer_id, timeout.as_secs_f64() * 1000.0);
+    }
+}
+
+/// The chunk is requested by the candidates with the highest average
+/// throughput per request in flight, so that peers with more bandwidth serve
+/// proportionally more requests. Timeouts count as deliveries without any
+/// throughput. Peers that did not deliver any chunk yet are preferred, so
+/// that they are probed.
+#[derive(Default)]
+pub struct BandwidthWeighted {
+    bytes_per_sec: PeerAverages,
+}
+
+impl DownloadPolicy for BandwidthWeighted {
+    fn should_request(
+        &self,
+        peer_id: NodeId,
+        _integrity_hash: &CryptoHash,
+        _chunk_id: ChunkId,
+        candidates: &[DownloadCandidate],
+    ) -> bool {
+        let score = |candidate: &DownloadCandidate| {
+            self.bytes_per_sec
+                .get(&candidate.peer_id)
+                .unwrap_or(f64::INFINITY)
+                / (candidate.in_flight + 1) as f64
+        };
+        match candidates.iter().find(|c| c.peer_id == peer_id) {
+            Some(own) => {
+                let own_score = score(own);
+                candidates.iter().all(|c| score(c) <= own_score)
+            }
+            None => false,
+        }
+    }
+
+    fn on_chunk_delivered(&self, peer_id: NodeId, size_bytes: usize, delivery_time: Duration) {
+        // Guard against a zero duration, e.g., due to the timer resolution.
+        let secs = delivery_time.as_secs_f64().max(1e-6);
+        self.bytes_per_sec.update(peer_id, size_bytes as f64 / secs);
+    }
+
+    fn on_chunk_timed_out(&self, peer_id: NodeId, _timeout: Duration) {
+        self.bytes_per_sec.update(peer_id, 0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_test_utilities::types::ids::node_test_id;
+
+    fn candidates(ids: &[u64]) -> Vec<DownloadCandidate> {
+        ids.iter()
+            .map(|id| DownloadCandidate {
+                peer_id: node_test_id(*id),
+                in_flight: 0,
+            })
+            .collect()
+    }
+
+    fn chosen(policy: &dyn DownloadPolicy, candidates: &[DownloadCandidate]) -> Vec<NodeId> {
+        candidates
+            .iter()
+            .map(|c| c.peer_id)
+            .filter(|peer_id| {
+                policy.should_request(*peer_id, &CryptoHash(vec![]), ChunkId::new(1), candidates)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_robin_takes_turns() {
+        let policy = RoundRobin;
+        let candidates = candidates(&[1, 2, 3]);
+        let assigned: Vec<_> = (0..6)
+            .map(|chunk| {
+                candidates
+                    .iter()
+                    .map(|c| c.peer_id)
+                    .find(|peer_id| {
+                        policy.should_request(
+                            *peer_id,
+                            &CryptoHash(vec![]),
+                            ChunkId::new(chunk),
+                            &candidates,
+                        )
+                    })
+                    .unwrap()
+            })
+            .collect();
+        for id in 1..=3 {
+            assert_eq!(
+                assigned.iter().filter(|p| **p == node_test_id(id)).count(),
+                2
+            );
+        }
+    }
+
+    #[test]
+    fn latency_aware_prefers_fast_and_unknown_peers() {
+        let policy = LatencyAware::default();
+        let mut candidates = candidates(&[1, 2, 3]);
+        assert_eq!(chosen(&policy, &candidates).len(), 3);
+
+        policy.on_chunk_delivered(node_test_id(1), 1024, Duration::from_millis(100));
+        policy.on_chunk_delivered(node_test_id(2), 1024, Duration::from_millis(10));
+        policy.on_chunk_timed_out(node_test_id(3), Duration::from_secs(15));
+        assert_eq!(chosen(&policy, &candidates), vec![node_test_id(2)]);
+
+        // Peers without measurements are probed.
+        candidates.push(DownloadCandidate {
+            peer_id: node_test_id(4),
+            in_flight: 0,
+        });
+        assert_eq!(chosen(&policy, &candidates), vec![node_test_id(4)]);
+    }
+
+    #[test]
+    fn bandwidth_weighted_accounts_for_load() {
+        let policy = BandwidthWeighted::default();
+        policy.on_chunk_delivered(node_test_id(1), 3000, Duration::from_secs(1));
+        policy.on_chunk_delivered(node_test_id(2), 1000, Duration::from_secs(1));
+        let mut candidates = candidates(&[1, 2]);
+        assert_eq!(chosen(&policy, &candidates), vec![node_test_id(1)]);
+
+        // With 3 requests in flight, peer 1 is less attractive than the idle
+        // peer 2.
+        candidates[0].in_flight = 3;
+        assert_eq!(chosen(&policy, &candidates), vec![node_test_id(2)]);
+    }
+}
@@ -0,0 +1,581 @@
+//! A discrete-event simulator that replays a recorded advert stream against a
+//! download policy, and reports the resulting download latency and
+//! redundancy.
+//!
+//! The simulator models the parts of the download manager that interact with
+//! the policy: every peer has a limited number of request streams, every
+//! chunk is downloaded from at most `max_duplicity` peers in parallel, every
+//! peer is requested at most once per attempt round, and requests time out
+//! after `max_chunk_wait_ms`. The network of every peer is modelled by a
+//! latency and a bandwidth, which is shared by all the requests to the peer.
+//! Artifacts are downloaded in the order in which they were first advertised.
+//!
+//! A recording is a text file with one advert per line:
+//!
+//! ```text
+//! # time_ms peer artifact size_bytes
+//! 0 1 block_proposal_17 250000
+//! 12 2 block_proposal_17 250000
+//! ```
+//!
+//! `time_ms` is the time the advert was received, relative to the start of the
+//! recording, `peer` the number of the advertising peer, and `artifact` any
+//! string identifying the artifact. Empty lines and lines starting with `#` are
+//! ignored.
+
+use super::{DownloadCandidate, DownloadPolicy};
+use ic_types::{chunkable::ChunkId, crypto::CryptoHash, NodeId, PrincipalId};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap, HashMap, HashSet},
+    fmt,
+    time::Duration,
+};
+
+/// An advert of a recorded advert stream.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordedAdvert {
+    pub time_ms: u64,
+    pub peer: u64,
+    pub artifact: String,
+    pub size_bytes: u64,
+}
+
+/// Parses a recording in the format described in the module documentation.
+pub fn parse_recording(recording: &str) -> Result<Vec<RecordedAdvert>, String> {
+    let mut adverts = vec![];
+    for (i, line) in recording.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<_> = line.split_whitespace().collect();
+        if fields.len() != 4 {
+            return Err(format!(
+                "line {}: expected 4 fields, found {}",
+                i + 1,
+                fields.len()
+            ));
+        }
+        let number = |field: &str| {
+            field
+                .parse::<u64>()
+                .map_err(|err| format!("line {}: invalid number {}: {}", i + 1, field, err))
+        };
+        adverts.push(RecordedAdvert {
+            time_ms: number(fields[0])?,
+            peer: number(fields[1])?,
+            artifact: fields[2].to_string(),
+            size_bytes: number(fields[3])?,
+        });
+    }
+    adverts.sort_by_key(|advert| advert.time_ms);
+    Ok(adverts)
+}
+
+/// The network connection to a peer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PeerModel {
+    /// The round trip time of a request.
+    pub latency_ms: u64,
+    /// The bandwidth available for downloads from the peer.
+    pub bytes_per_sec: u64,
+}
+
+/// The parameters of a simulation. The defaults match the default gossip
+/// config.
+#[derive(Clone, Debug)]
+pub struct SimulationConfig {
+    pub max_artifact_streams_per_peer: usize,
+    pub max_duplicity: usize,
+    pub max_chunk_size: u64,
+    pub max_chunk_wait_ms: u64,
+    /// The period in which all peers are asked for work, like the download
+    /// manager does when it re-evaluates the priority function.
+    pub timer_period_ms: u64,
+    /// How long the simulation continues after the last advert.
+    pub horizon_ms: u64,
+    /// The network connections of individual peers.
+    pub peers: HashMap<u64, PeerModel>,
+    /// The network connection of the peers not listed in `peers`.
+    pub default_peer: PeerModel,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            max_artifact_streams_per_peer: 20,
+            max_duplicity: 1,
+            max_chunk_size: 4096,
+            max_chunk_wait_ms: 15_000,
+            timer_period_ms: 3_000,
+            horizon_ms: 60_000,
+            peers: HashMap::new(),
+            default_peer: PeerModel {
+                latency_ms: 50,
+                bytes_per_sec: 10_000_000,
+            },
+        }
+    }
+}
+
+/// The outcome of a simulation.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SimulationReport {
+    /// The number of distinct artifacts advertised.
+    pub artifacts: usize,
+    /// The number of artifacts downloaded completely.
+    pub completed: usize,
+    /// The times from the first advert of an artifact until its download
+    /// completed, over all completed artifacts.
+    pub mean_latency_ms: f64,
+    pub p50_latency_ms: u64,
+    pub p99_latency_ms: u64,
+    pub max_latency_ms: u64,
+    /// The number of bytes downloaded, including duplicates.
+    pub downloaded_bytes: u64,
+    /// The number of bytes of the completed artifacts.
+    pub unique_bytes: u64,
+    /// The number of chunk requests that timed out.
+    pub timed_out_chunks: usize,
+}
+
+impl SimulationReport {
+    /// The ratio of downloaded bytes to the bytes that were needed.
+    pub fn redundancy(&self) -> f64 {
+        if self.unique_bytes == 0 {
+            0.0
+        } else {
+            self.downloaded_bytes as f64 / self.unique_bytes as f64
+        }
+    }
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "completed {}/{} artifacts, latency mean {:.1} ms, p50 {} ms, p99 {} ms, max {} ms, \
+             downloaded {} bytes, redundancy {:.3}, {} timed out chunks",
+            self.completed,
+            self.artifacts,
+            self.mean_latency_ms,
+            self.p50_latency_ms,
+            self.p99_latency_ms,
+            self.max_latency_ms,
+            self.downloaded_bytes,
+            self.redundancy(),
+            self.timed_out_chunks
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Event {
+    Advert(usize),
+    ChunkDelivered(Request),
+    ChunkTimedOut(Request),
+    Timer,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Request {
+    peer: u64,
+    artifact: usize,
+    chunk: usize,
+    requested_at_ms: u64,
+}
+
+#[derive(Default)]
+struct Chunk {
+    size_bytes: u64,
+    received: bool,
+    in_flight: Vec<u64>,
+    attempted: HashSet<u64>,
+}
+
+struct Artifact {
+    integrity_hash: CryptoHash,
+    size_bytes: u64,
+    first_advert_ms: u64,
+    advertisers: Vec<u64>,
+    chunks: Vec<Chunk>,
+    missing_chunks: usize,
+}
+
+#[derive(Default)]
+struct Peer {
+    in_flight: usize,
+    link_busy_until_ms: u64,
+    /// The artifacts advertised by the peer, in the order of their first
+    /// advert.
+    adverts: Vec<usize>,
+}
+
+struct Simulation<'a> {
+    policy: &'a dyn DownloadPolicy,
+    config: &'a SimulationConfig,
+    now_ms: u64,
+    events: BinaryHeap<Reverse<(u64, Event)>>,
+    artifacts: Vec<Artifact>,
+    peers: BTreeMap<u64, Peer>,
+    latencies_ms: Vec<u64>,
+    report: SimulationReport,
+}
+
+fn node_id(peer: u64) -> NodeId {
+    NodeId::from(PrincipalId::new_node_test_id(peer))
+}
+
+/// Replays `adverts` against `policy` and reports the download latency and
+/// redundancy.
+pub fn simulate(
+    policy: &dyn DownloadPolicy,
+    adverts: &[RecordedAdvert],
+    config: &SimulationConfig,
+) -> SimulationReport {
+    let mut simulation = Simulation {
+        policy,
+        config,
+        now_ms: 0,
+        events: BinaryHeap::new(),
+        artifacts: vec![],
+        peers: BTreeMap::new(),
+        latencies_ms: vec![],
+        report: SimulationReport::default(),
+    };
+    for (i, advert) in adverts.iter().enumerate() {
+        simulation.schedule(advert.time_ms, Event::Advert(i));
+    }
+    let end_ms = adverts.iter().map(|a| a.time_ms).max().unwrap_or(0) + config.horizon_ms;
+    simulation.schedule(config.timer_period_ms, Event::Timer);
+
+    let mut artifact_index = HashMap::new();
+    while let Some(Reverse((time_ms, event))) = simulation.events.pop() {
+        if time_ms > end_ms {
+            break;
+        }
+        simulation.now_ms = time_ms;
+        match event {
+            Event::Advert(i) => {
+                let advert = &adverts[i];
+                let next_index = simulation.artifacts.len();
+                let artifact = *artifact_index
+                    .entry(advert.artifact.clone())
+                    .or_insert(next_index);
+                if artifact == next_index {
+                    simulation.add_artifact(advert);
+                }
+                simulation.on_advert(artifact, advert.peer);
+            }
+            Event::ChunkDelivered(request) => simulation.on_chunk_delivered(request),
+            Event::ChunkTimedOut(request) => simulation.on_chunk_timed_out(request),
+            Event::Timer => {
+                let peers: Vec<_> = simulation.peers.keys().copied().collect();
+                for peer in peers {
+                    simulation.download_next(peer);
+                }
+                simulation.schedule(time_ms + config.timer_period_ms, Event::Timer);
+            }
+        }
+    }
+    simulation.finish()
+}
+
+impl<'a> Simulation<'a> {
+    fn schedule(&mut self, time_ms: u64, event: Event) {
+        self.events.push(Reverse((time_ms, event)));
+    }
+
+    fn add_artifact(&mut self, advert: &RecordedAdvert) {
+        let chunk_size = self.config.max_chunk_size.max(1);
+        let mut chunks = vec![];
+        let mut remaining = advert.size_bytes;
+        loop {
+            let size_bytes = remaining.min(chunk_size);
+            chunks.push(Chunk {
+                size_bytes,
+                ..Default::default()
+            });
+            remaining -= size_bytes;
+            if remaining == 0 {
+                break;
+            }
+        }
+        self.report.artifacts += 1;
+        self.artifacts.push(Artifact {
+            integrity_hash: CryptoHash(advert.artifact.as_bytes().to_vec()),
+            size_bytes: advert.size_bytes,
+            first_advert_ms: self.now_ms,
+            advertisers: vec![],
+            missing_chunks: chunks.len(),
+            chunks,
+        });
+    }
+
+    fn on_advert(&mut self, artifact: usize, peer: u64) {
+        if !self.artifacts[artifact].advertisers.contains(&peer) {
+            self.artifacts[artifact].advertisers.push(peer);
+            self.peers.entry(peer).or_default().adverts.push(artifact);
+        }
+        self.download_next(peer);
+    }
+
+    fn peer_model(&self, peer: u64) -> PeerModel {
+        *self
+            .config
+            .peers
+            .get(&peer)
+            .unwrap_or(&self.config.default_peer)
+    }
+
+    fn has_capacity(&self, peer: u64) -> bool {
+        self.peers[&peer].in_flight < self.config.max_artifact_streams_per_peer
+    }
+
+    /// Requests chunks from `peer` as long as it has free capacity, like
+    /// `DownloadManagerImpl::download_next()`.
+    fn download_next(&mut self, peer: u64) {
+        let adverts = self.peers[&peer].adverts.clone();
+        for artifact in adverts {
+            for chunk in 0..self.artifacts[artifact].chunks.len() {
+                if !self.has_capacity(peer) {
+                    return;
+                }
+                let c = &self.artifacts[artifact].chunks[chunk];
+                if c.received
+                    || c.attempted.contains(&peer)
+                    || c.in_flight.len() >= self.config.max_duplicity
+                {
+                    continue;
+                }
+                let candidates: Vec<_> = self.artifacts[artifact]
+                    .advertisers
+                    .iter()
+                    .filter(|p| !c.attempted.contains(p))
+                    .map(|p| DownloadCandidate {
+                        peer_id: node_id(*p),
+                        in_flight: self.peers[p].in_flight,
+                    })
+                    .collect();
+                if self.policy.should_request(
+                    node_id(peer),
+                    &self.artifacts[artifact].integrity_hash,
+                    ChunkId::new(chunk as u32),
+                    &candidates,
+                ) {
+                    self.request(peer, artifact, chunk);
+                }
+            }
+        }
+    }
+
+    fn request(&mut self, peer: u64, artifact: usize, chunk: usize) {
+        let model = self.peer_model(peer);
+        let c = &mut self.artifacts[artifact].chunks[chunk];
+        c.in_flight.push(peer);
+        c.attempted.insert(peer);
+        let transfer_ms = c.size_bytes * 1000 / model.bytes_per_sec.max(1);
+
+        let p = self.peers.get_mut(&peer).unwrap();
+        p.in_flight += 1;
+        p.link_busy_until_ms = p.link_busy_until_ms.max(self.now_ms) + transfer_ms;
+        let delivered_at_ms = p.link_busy_until_ms + model.latency_ms;
+
+        let request = Request {
+            peer,
+            artifact,
+            chunk,
+            requested_at_ms: self.now_ms,
+        };
+        let timeout_at_ms = self.now_ms + self.config.max_chunk_wait_ms;
+        if delivered_at_ms < timeout_at_ms {
+            self.schedule(delivered_at_ms, Event::ChunkDelivered(request));
+        } else {
+            self.schedule(timeout_at_ms, Event::ChunkTimedOut(request));
+        }
+    }
+
+    fn complete_request(&mut self, request: &Request) {
+        self.peers.get_mut(&request.peer).unwrap().in_flight -= 1;
+        self.artifacts[request.artifact].chunks[request.chunk]
+            .in_flight
+            .retain(|p| *p != request.peer);
+    }
+
+    fn on_chunk_delivered(&mut self, request: Request) {
+        self.complete_request(&request);
+        let size_bytes = self.artifacts[request.artifact].chunks[request.chunk].size_bytes;
+        self.report.downloaded_bytes += size_bytes;
+        self.policy.on_chunk_delivered(
+            node_id(request.peer),
+            size_bytes as usize,
+            Duration::from_millis(self.now_ms - request.requested_at_ms),
+        );
+
+        let artifact = &mut self.artifacts[request.artifact];
+        let chunk = &mut artifact.chunks[request.chunk];
+        if !chunk.received {
+            chunk.received = true;
+            artifact.missing_chunks -= 1;
+            if artifact.missing_chunks == 0 {
+                self.report.completed += 1;
+                self.report.unique_bytes += artifact.size_bytes;
+                self.latencies_ms
+                    .push(self.now_ms - artifact.first_advert_ms);
+            }
+        }
+        self.download_next(request.peer);
+    }
+
+    fn on_chunk_timed_out(&mut self, request: Request) {
+        self.complete_request(&request);
+        self.report.timed_out_chunks += 1;
+        self.policy.on_chunk_timed_out(
+            node_id(request.peer),
+            Duration::from_millis(self.config.max_chunk_wait_ms),
+        );
+
+        // Once every advertiser was attempted, a new attempt round starts.
+        let artifact = &mut self.artifacts[request.artifact];
+        let chunk = &mut artifact.chunks[request.chunk];
+        if artifact
+            .advertisers
+            .iter()
+            .all(|p| chunk.attempted.contains(p))
+        {
+            chunk.attempted.clear();
+        }
+        self.download_next(request.peer);
+    }
+
+    fn finish(mut self) -> SimulationReport {
+        let mut latencies = self.latencies_ms;
+        latencies.sort_unstable();
+        if !latencies.is_empty() {
+            let percentile = |p: usize| latencies[(latencies.len() - 1) * p / 100];
+            self.report.mean_latency_ms =
+                latencies.iter().sum::<u64>() as f64 / latencies.len() as f64;
+            self.report.p50_latency_ms = percentile(50);
+            self.report.p99_latency_ms = percentile(99);
+            self.report.max_latency_ms = *latencies.last().unwrap();
+        }
+        self.report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::download_policy::{BandwidthWeighted, FirstAvailable, LatencyAware, RoundRobin};
+
+    /// A recording of `count` artifacts of `size_bytes` bytes, advertised
+    /// every `spacing_ms` by the given `peers`, 10 ms apart.
+    fn recording(
+        count: u64,
+        spacing_ms: u64,
+        size_bytes: u64,
+        peers: &[u64],
+    ) -> Vec<RecordedAdvert> {
+        let mut recording = String::new();
+        for i in 0..count {
+            for (j, peer) in peers.iter().enumerate() {
+                recording.push_str(&format!(
+                    "{} {} artifact_{} {}\n",
+                    i * spacing_ms + 10 * j as u64,
+                    peer,
+                    i,
+                    size_bytes
+                ));
+            }
+        }
+        parse_recording(&recording).unwrap()
+    }
+
+    /// Peer 1 is much slower than the others.
+    fn config_with_slow_peer() -> SimulationConfig {
+        let mut config = SimulationConfig::default();
+        config.peers.insert(
+            1,
+            PeerModel {
+                latency_ms: 500,
+                bytes_per_sec: 100_000,
+            },
+        );
+        config
+    }
+
+    #[test]
+    fn parse_recording_skips_comments_and_sorts() {
+        let adverts = parse_recording("# time peer artifact size\n\n5 2 b 10\n1 1 a 20\n").unwrap();
+        assert_eq!(
+            adverts,
+            vec![
+                RecordedAdvert {
+                    time_ms: 1,
+                    peer: 1,
+                    artifact: "a".to_string(),
+                    size_bytes: 20
+                },
+                RecordedAdvert {
+                    time_ms: 5,
+                    peer: 2,
+                    artifact: "b".to_string(),
+                    size_bytes: 10
+                },
+            ]
+        );
+        assert!(parse_recording("1 2 a").is_err());
+        assert!(parse_recording("1 x a 10").is_err());
+    }
+
+    #[test]
+    fn all_policies_complete_all_artifacts() {
+        let adverts = recording(100, 100, 65536, &[1, 2, 3, 4]);
+        let policies: Vec<Box<dyn DownloadPolicy>> = vec![
+            Box::new(FirstAvailable),
+            Box::new(RoundRobin),
+            Box::new(LatencyAware::default()),
+            Box::new(BandwidthWeighted::default()),
+        ];
+        for policy in policies {
+            let report = simulate(policy.as_ref(), &adverts, &config_with_slow_peer());
+            assert_eq!(report.artifacts, 100);
+            assert_eq!(report.completed, 100, "{}", report);
+            assert_eq!(report.unique_bytes, 100 * 65536);
+            assert_eq!(report.timed_out_chunks, 0, "{}", report);
+        }
+    }
+
+    #[test]
+    fn latency_aware_avoids_slow_peer() {
+        // Once peer 1 is known to be slow, peer 2 downloads the artifacts on
+        // its own.
+        let adverts = recording(10, 2000, 1 << 20, &[2, 1]);
+        let first_available = simulate(&FirstAvailable, &adverts, &config_with_slow_peer());
+        let latency_aware = simulate(&LatencyAware::default(), &adverts, &config_with_slow_peer());
+        assert_eq!(latency_aware.completed, 10);
+        assert!(
+            latency_aware.mean_latency_ms < first_available.mean_latency_ms,
+            "latency aware: {}, first available: {}",
+            latency_aware,
+            first_available
+        );
+    }
+
+    #[test]
+    fn duplicity_causes_redundancy() {
+        let adverts = recording(100, 100, 65536, &[1, 2, 3, 4]);
+        let mut config = SimulationConfig::default();
+        let report = simulate(&FirstAvailable, &adverts, &config);
+        assert_eq!(report.downloaded_bytes, report.unique_bytes);
+
+        // With a high latency, the chunks are in flight long enough for the
+        // other advertisers to request them as well.
+        config.max_duplicity = 2;
+        config.default_peer.latency_ms = 1_000;
+        let report = simulate(&FirstAvailable, &adverts, &config);
+        assert_eq!(report.completed, 100);
+        assert!(report.redundancy() > 1.0, "{}", report);
+    }
+}
@@ -58,6 +58,7 @@ use crate::{
     utils::FlowMapper,
     P2PError, P2PErrorCode, P2PResult,
 };
+use ic_config::p2p::Config as P2PConfig;
 use ic_interfaces::artifact_manager::ArtifactManager;
 use ic_interfaces::registry::RegistryClient;
 use ic_interfaces_transport::{FlowTag, Transport};
@@ -250,6 +251,7 @@ impl GossipImpl {
         artifact_manager: Arc<dyn ArtifactManager>,
         transport: Arc<dyn Transport>,
         flow_tags: Vec<FlowTag>,
+        p2p_config: P2PConfig,
         log: ReplicaLogger,
         metrics_registry: &MetricsRegistry,
         malicious_flags: MaliciousFlags,
@@ -262,6 +264,7 @@ impl GossipImpl {
             artifact_manager.clone(),
             transport.clone(),
             Arc::new(FlowMapper::new(flow_tags)),
+            p2p_config,
             log.clone(),
             metrics_registry,
         );
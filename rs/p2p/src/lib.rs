@@ -94,7 +94,7 @@
 //! <img src="../../../../../docs/assets/p2p.png" height="960"
 //! width="540"/> </div> <hr/>
 
-use ic_config::transport::TransportConfig;
+use ic_config::{p2p::Config as P2PConfig, transport::TransportConfig};
 use ic_interfaces::{
     artifact_manager::ArtifactManager, consensus_pool::ConsensusPoolCache, registry::RegistryClient,
 };
@@ -115,6 +115,7 @@ use tower::util::BoxCloneService;
 
 mod artifact_download_list;
 mod download_management;
+pub mod download_policy;
 mod download_prioritization;
 mod event_handler;
 mod gossip_protocol;
@@ -161,6 +162,7 @@ pub fn start_p2p(
     subnet_id: SubnetId,
     transport_config: TransportConfig,
     gossip_config: GossipConfig,
+    p2p_config: P2PConfig,
     registry_client: Arc<dyn RegistryClient>,
     transport: Arc<dyn Transport>,
     consensus_pool_cache: Arc<dyn ConsensusPoolCache>,
@@ -177,6 +179,7 @@ pub fn start_p2p(
         artifact_manager.clone(),
         transport.clone(),
         p2p_flow_tags,
+        p2p_config,
         log.clone(),
         &metrics_registry,
         malicious_flags,
@@ -104,6 +104,7 @@ fn execute_test(
             transport_config,
             Default::default(),
             Default::default(),
+            Default::default(),
             node_id,
             subnet_id,
             Some(transport),
@@ -265,6 +266,7 @@ fn execute_test_chunking_pool(
             transport_config,
             Default::default(),
             Default::default(),
+            Default::default(),
             node_id,
             subnet_id,
             Some(transport),
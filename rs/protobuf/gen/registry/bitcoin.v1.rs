@@ -158,6 +158,21 @@ pub struct AdapterQueues {
     #[prost(uint32, tag = "4")]
     pub requests_queue_capacity: u32,
 }
+/// A transaction sent with `bitcoin_send_transaction` that is rebroadcast until
+/// it is mined or expires.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct OutgoingTransaction {
+    /// The serialized transaction.
+    #[prost(bytes = "vec", tag = "1")]
+    pub transaction: ::prost::alloc::vec::Vec<u8>,
+    /// The height of the main chain at which the transaction is dropped.
+    #[prost(uint32, tag = "2")]
+    pub expiry_height: u32,
+    /// The height of the main chain when the transaction was last sent to the
+    /// Bitcoin Adapter.
+    #[prost(uint32, tag = "3")]
+    pub last_broadcast_height: u32,
+}
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct UnstableBlocks {
     #[prost(uint32, tag = "1")]
@@ -196,6 +211,12 @@ pub struct BitcoinStateBits {
     pub network: i32,
     #[prost(message, repeated, tag = "5")]
     pub utxos_large: ::prost::alloc::vec::Vec<Utxo>,
+    /// Whether the index of transactions by address is maintained.
+    #[prost(bool, tag = "6")]
+    pub address_history_enabled: bool,
+    /// The transactions that are rebroadcast until they are mined or expire.
+    #[prost(message, repeated, tag = "7")]
+    pub outgoing_transactions: ::prost::alloc::vec::Vec<OutgoingTransaction>,
 }
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
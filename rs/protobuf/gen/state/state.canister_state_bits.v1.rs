@@ -317,6 +317,9 @@ pub struct CanisterStateBits {
     /// canister.
     #[prost(message, repeated, tag = "30")]
     pub task_queue: ::prost::alloc::vec::Vec<ExecutionTask>,
+    /// The number of code installations and settings changes of this canister.
+    #[prost(uint64, tag = "31")]
+    pub canister_version: u64,
     #[prost(oneof = "canister_state_bits::CanisterStatus", tags = "11, 12, 13")]
     pub canister_status: ::core::option::Option<canister_state_bits::CanisterStatus>,
 }
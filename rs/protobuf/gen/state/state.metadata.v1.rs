@@ -127,6 +127,8 @@ pub struct CanisterHttpRequestContext {
     pub headers: ::prost::alloc::vec::Vec<HttpHeader>,
     #[prost(uint64, optional, tag = "9")]
     pub max_response_bytes: ::core::option::Option<u64>,
+    #[prost(uint32, tag = "10")]
+    pub max_redirects: u32,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct CanisterHttpRequestContextTree {
@@ -238,4 +240,7 @@ pub enum HttpMethod {
     Get = 1,
     Post = 2,
     Head = 3,
+    Put = 4,
+    Patch = 5,
+    Delete = 6,
 }
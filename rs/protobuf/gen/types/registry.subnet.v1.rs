@@ -304,6 +304,9 @@ pub struct BitcoinFeatureInfo {
     /// The status of the feature.
     #[prost(enumeration = "BitcoinFeatureStatus", tag = "2")]
     pub status: i32,
+    /// Whether the bitcoin canister maintains the index of transactions by address.
+    #[prost(bool, tag = "3")]
+    pub address_history: bool,
 }
 #[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, ::prost::Message)]
 pub struct SubnetFeatures {
@@ -404,6 +404,77 @@ pub struct KeyTranscriptCreation {
     #[prost(message, optional, tag = "9")]
     pub created: ::core::option::Option<UnmaskedTranscript>,
 }
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, ::prost::Message)]
+pub struct EcdsaDealingSupport {
+    #[prost(message, optional, tag = "1")]
+    pub transcript_id: ::core::option::Option<super::super::registry::subnet::v1::IDkgTranscriptId>,
+    #[prost(message, optional, tag = "2")]
+    pub dealer: ::core::option::Option<NodeId>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub dealing_hash: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub sig_share: ::prost::alloc::vec::Vec<u8>,
+    #[prost(message, optional, tag = "5")]
+    pub signer: ::core::option::Option<NodeId>,
+}
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, ::prost::Message)]
+pub struct EcdsaSigShare {
+    #[prost(message, optional, tag = "1")]
+    pub signer: ::core::option::Option<NodeId>,
+    #[prost(message, optional, tag = "2")]
+    pub request_id: ::core::option::Option<RequestId>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub sig_share_raw: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, ::prost::Message)]
+pub struct EcdsaComplaint {
+    #[prost(message, optional, tag = "1")]
+    pub transcript_id: ::core::option::Option<super::super::registry::subnet::v1::IDkgTranscriptId>,
+    #[prost(message, optional, tag = "2")]
+    pub dealer: ::core::option::Option<NodeId>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub raw_complaint: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: ::prost::alloc::vec::Vec<u8>,
+    #[prost(message, optional, tag = "5")]
+    pub signer: ::core::option::Option<NodeId>,
+}
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, ::prost::Message)]
+pub struct EcdsaOpening {
+    #[prost(message, optional, tag = "1")]
+    pub transcript_id: ::core::option::Option<super::super::registry::subnet::v1::IDkgTranscriptId>,
+    #[prost(message, optional, tag = "2")]
+    pub dealer: ::core::option::Option<NodeId>,
+    #[prost(message, optional, tag = "3")]
+    pub complainer: ::core::option::Option<NodeId>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub raw_opening: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: ::prost::alloc::vec::Vec<u8>,
+    #[prost(message, optional, tag = "6")]
+    pub signer: ::core::option::Option<NodeId>,
+}
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, ::prost::Message)]
+pub struct EcdsaMessage {
+    #[prost(oneof = "ecdsa_message::Msg", tags = "1, 2, 3, 4, 5")]
+    pub msg: ::core::option::Option<ecdsa_message::Msg>,
+}
+/// Nested message and enum types in `EcdsaMessage`.
+pub mod ecdsa_message {
+    #[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, ::prost::Oneof)]
+    pub enum Msg {
+        #[prost(message, tag = "1")]
+        SignedDealing(super::super::super::registry::subnet::v1::IDkgSignedDealingTuple),
+        #[prost(message, tag = "2")]
+        DealingSupport(super::EcdsaDealingSupport),
+        #[prost(message, tag = "3")]
+        SigShare(super::EcdsaSigShare),
+        #[prost(message, tag = "4")]
+        Complaint(super::EcdsaComplaint),
+        #[prost(message, tag = "5")]
+        Opening(super::EcdsaOpening),
+    }
+}
 #[derive(
     serde::Serialize,
     serde::Deserialize,
@@ -588,3 +659,132 @@ pub struct IngressPayload {
     #[prost(bytes = "vec", tag = "2")]
     pub buffer: ::prost::alloc::vec::Vec<u8>,
 }
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, ::prost::Message)]
+pub struct RandomBeaconShare {
+    #[prost(string, tag = "1")]
+    pub version: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub height: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub parent: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signer: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, ::prost::Message)]
+pub struct RandomTapeShare {
+    #[prost(string, tag = "1")]
+    pub version: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub height: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signature: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, ::prost::Message)]
+pub struct NotarizationShare {
+    #[prost(string, tag = "1")]
+    pub version: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub height: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub block: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signer: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, ::prost::Message)]
+pub struct FinalizationShare {
+    #[prost(string, tag = "1")]
+    pub version: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub height: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub block: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub signer: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, ::prost::Message)]
+pub struct CatchUpPackageShare {
+    #[prost(string, tag = "1")]
+    pub version: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub random_beacon: ::core::option::Option<RandomBeacon>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub state_hash: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub block_hash: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub random_beacon_hash: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "6")]
+    pub signature: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "7")]
+    pub signer: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, ::prost::Message)]
+pub struct ConsensusMessage {
+    #[prost(
+        oneof = "consensus_message::Msg",
+        tags = "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11"
+    )]
+    pub msg: ::core::option::Option<consensus_message::Msg>,
+}
+/// Nested message and enum types in `ConsensusMessage`.
+pub mod consensus_message {
+    #[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, ::prost::Oneof)]
+    pub enum Msg {
+        #[prost(message, tag = "1")]
+        RandomBeacon(super::RandomBeacon),
+        #[prost(message, tag = "2")]
+        Finalization(super::Finalization),
+        #[prost(message, tag = "3")]
+        Notarization(super::Notarization),
+        #[prost(message, tag = "4")]
+        BlockProposal(super::BlockProposal),
+        #[prost(message, tag = "5")]
+        RandomBeaconShare(super::RandomBeaconShare),
+        #[prost(message, tag = "6")]
+        NotarizationShare(super::NotarizationShare),
+        #[prost(message, tag = "7")]
+        FinalizationShare(super::FinalizationShare),
+        #[prost(message, tag = "8")]
+        RandomTape(super::RandomTape),
+        #[prost(message, tag = "9")]
+        RandomTapeShare(super::RandomTapeShare),
+        #[prost(message, tag = "10")]
+        Cup(super::CatchUpPackage),
+        #[prost(message, tag = "11")]
+        CupShare(super::CatchUpPackageShare),
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, ::prost::Message)]
+pub struct CertificationShare {
+    #[prost(uint64, tag = "1")]
+    pub height: u64,
+    #[prost(bytes = "vec", tag = "2")]
+    pub hash: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub signature: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub signer: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, ::prost::Message)]
+pub struct CertificationMessage {
+    #[prost(oneof = "certification_message::Msg", tags = "1, 2")]
+    pub msg: ::core::option::Option<certification_message::Msg>,
+}
+/// Nested message and enum types in `CertificationMessage`.
+pub mod certification_message {
+    #[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, ::prost::Oneof)]
+    pub enum Msg {
+        #[prost(message, tag = "1")]
+        Certification(super::super::super::messaging::xnet::v1::Certification),
+        #[prost(message, tag = "2")]
+        CertificationShare(super::CertificationShare),
+    }
+}
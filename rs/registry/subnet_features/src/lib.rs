@@ -26,6 +26,7 @@ impl SubnetFeatures {
         self.bitcoin.unwrap_or(BitcoinFeature {
             network: BitcoinNetwork::Mainnet,
             status: BitcoinFeatureStatus::Disabled,
+            address_history: false,
         })
     }
 }
@@ -45,6 +46,7 @@ impl From<SubnetFeatures> for pb::SubnetFeatures {
                         BitcoinNetwork::Regtest => 3,
                     },
                     status: bitcoin_feature.status.into(),
+                    address_history: bitcoin_feature.address_history,
                 }),
         }
     }
@@ -65,6 +67,7 @@ impl From<pb::SubnetFeatures> for SubnetFeatures {
                         _ => BitcoinNetwork::Mainnet,
                     },
                     status: bitcoin.status.into(),
+                    address_history: bitcoin.address_history,
                 }),
                 None => {
                     // For backward-compatibility, check if the legacy `bitcoin_testnet_feature`
@@ -75,6 +78,7 @@ impl From<pb::SubnetFeatures> for SubnetFeatures {
                         .map(|legacy_bitcoin_feature| BitcoinFeature {
                             network: BitcoinNetwork::Testnet,
                             status: BitcoinFeatureStatus::from(legacy_bitcoin_feature),
+                            address_history: false,
                         })
                 }
             },
@@ -93,6 +97,7 @@ impl FromStr for SubnetFeatures {
             return Ok(features);
         }
 
+        let mut address_history = false;
         for feature in string.split(',') {
             match feature {
                 "canister_sandboxing" => features.canister_sandboxing = true,
@@ -106,6 +111,7 @@ impl FromStr for SubnetFeatures {
                     features.bitcoin = Some(BitcoinFeature {
                         network: BitcoinNetwork::Testnet,
                         status: BitcoinFeatureStatus::Enabled,
+                        address_history: false,
                     });
                 }
                 "bitcoin_testnet_syncing" => {
@@ -117,6 +123,7 @@ impl FromStr for SubnetFeatures {
                     features.bitcoin = Some(BitcoinFeature {
                         network: BitcoinNetwork::Testnet,
                         status: BitcoinFeatureStatus::Syncing,
+                        address_history: false,
                     });
                 }
                 "bitcoin_testnet_paused" => {
@@ -128,6 +135,7 @@ impl FromStr for SubnetFeatures {
                     features.bitcoin = Some(BitcoinFeature {
                         network: BitcoinNetwork::Testnet,
                         status: BitcoinFeatureStatus::Paused,
+                        address_history: false,
                     });
                 }
                 "bitcoin_mainnet" => {
@@ -139,6 +147,7 @@ impl FromStr for SubnetFeatures {
                     features.bitcoin = Some(BitcoinFeature {
                         network: BitcoinNetwork::Mainnet,
                         status: BitcoinFeatureStatus::Enabled,
+                        address_history: false,
                     });
                 }
                 "bitcoin_mainnet_syncing" => {
@@ -150,6 +159,7 @@ impl FromStr for SubnetFeatures {
                     features.bitcoin = Some(BitcoinFeature {
                         network: BitcoinNetwork::Mainnet,
                         status: BitcoinFeatureStatus::Syncing,
+                        address_history: false,
                     });
                 }
                 "bitcoin_mainnet_paused" => {
@@ -161,12 +171,25 @@ impl FromStr for SubnetFeatures {
                     features.bitcoin = Some(BitcoinFeature {
                         network: BitcoinNetwork::Mainnet,
                         status: BitcoinFeatureStatus::Paused,
+                        address_history: false,
                     });
                 }
+                "bitcoin_address_history" => address_history = true,
                 _ => return Err(format!("Unknown feature {:?} in {:?}", feature, string)),
             }
         }
 
+        if address_history {
+            match features.bitcoin.as_mut() {
+                Some(bitcoin) => bitcoin.address_history = true,
+                None => {
+                    return Err(String::from(
+                        "Cannot enable the bitcoin address history without the bitcoin feature",
+                    ))
+                }
+            }
+        }
+
         Ok(features)
     }
 }
@@ -175,6 +198,11 @@ impl FromStr for SubnetFeatures {
 pub struct BitcoinFeature {
     pub network: BitcoinNetwork,
     pub status: BitcoinFeatureStatus,
+    /// Whether the Bitcoin canister maintains the index of transactions by
+    /// address. Toggling it resets the Bitcoin state so that the index is
+    /// complete from genesis.
+    #[serde(default)]
+    pub address_history: bool,
 }
 
 #[derive(CandidType, Clone, Copy, Deserialize, Debug, Eq, PartialEq, Serialize)]
@@ -278,7 +306,8 @@ mod tests {
                 http_requests: true,
                 bitcoin: Some(BitcoinFeature {
                     network: BitcoinNetwork::Testnet,
-                    status: BitcoinFeatureStatus::Enabled
+                    status: BitcoinFeatureStatus::Enabled,
+                    address_history: false,
                 })
             }
         );
@@ -296,7 +325,8 @@ mod tests {
                 http_requests: true,
                 bitcoin: Some(BitcoinFeature {
                     network: BitcoinNetwork::Mainnet,
-                    status: BitcoinFeatureStatus::Paused
+                    status: BitcoinFeatureStatus::Paused,
+                    address_history: false,
                 })
             }
         );
@@ -313,12 +343,41 @@ mod tests {
                 http_requests: true,
                 bitcoin: Some(BitcoinFeature {
                     network: BitcoinNetwork::Mainnet,
-                    status: BitcoinFeatureStatus::Enabled
+                    status: BitcoinFeatureStatus::Enabled,
+                    address_history: false,
                 })
             }
         );
     }
 
+    #[test]
+    fn test_bitcoin_address_history() {
+        let result =
+            SubnetFeatures::from_str("bitcoin_address_history,bitcoin_testnet_syncing").unwrap();
+        assert_eq!(
+            result.bitcoin,
+            Some(BitcoinFeature {
+                network: BitcoinNetwork::Testnet,
+                status: BitcoinFeatureStatus::Syncing,
+                address_history: true,
+            })
+        );
+        assert_eq!(
+            SubnetFeatures::from(pb::SubnetFeatures::from(result)),
+            result
+        );
+    }
+
+    #[test]
+    fn test_bitcoin_address_history_requires_bitcoin_feature() {
+        assert_eq!(
+            SubnetFeatures::from_str("http_requests,bitcoin_address_history"),
+            Err(String::from(
+                "Cannot enable the bitcoin address history without the bitcoin feature"
+            ))
+        );
+    }
+
     #[test]
     fn test_set_bitcoin_multiple_times_returns_error() {
         for feature in [
@@ -1,10 +1,12 @@
-use ic_artifact_pool::consensus_pool::ConsensusPoolImpl;
-use ic_config::artifact_pool::BACKUP_GROUP_SIZE;
+use ic_artifact_pool::{
+    backup::{list_artifacts, ArtifactLocation},
+    consensus_pool::ConsensusPoolImpl,
+};
 use ic_consensus::consensus::{dkg_key_manager::DkgKeyManager, pool_reader::PoolReader};
 use ic_consensus_message::ConsensusMessageHashable;
 use ic_interfaces::{
     artifact_pool::UnvalidatedArtifact,
-    consensus_pool::{ChangeAction, MutableConsensusPool},
+    consensus_pool::{ChangeAction, HeightRange, MutableConsensusPool},
     crypto::MultiSigVerifier,
     registry::RegistryClient,
     time_source::SysTimeSource,
@@ -21,9 +23,7 @@ use prost::Message;
 use std::{
     collections::{BTreeMap, BTreeSet},
     convert::TryFrom,
-    fs,
-    io::Read,
-    path::{Path, PathBuf},
+    path::Path,
     sync::Arc,
 };
 
@@ -32,21 +32,44 @@ use crate::validator::{InvalidArtifact, ReplayValidator};
 
 // A set of backup artifacts corresponding to a single height.
 pub(super) struct HeightArtifacts {
-    path: PathBuf,
+    // The locations of all artifacts at this height, keyed by their file names.
+    artifacts: BTreeMap<String, ArtifactLocation>,
     contains_cup: bool,
     proposals: Vec<String>,
     finalizations: Vec<String>,
     notarizations: Vec<String>,
 }
 
-// Reads the file at `path` and the returns the content as bytes.
-fn read_file(path: &Path) -> Vec<u8> {
-    let mut buffer = Vec::new();
-    let mut file = fs::File::open(path)
-        .unwrap_or_else(|err| panic!("Couldn't open file {:?}: {:?}", path, err));
-    file.read_to_end(&mut buffer)
-        .unwrap_or_else(|err| panic!("Couldn't read file {:?}: {:?}", path, err));
-    buffer
+impl HeightArtifacts {
+    fn new(artifacts: BTreeMap<String, ArtifactLocation>) -> Self {
+        let get_files = |s| {
+            artifacts
+                .keys()
+                .filter(|file| file.starts_with(s))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        Self {
+            contains_cup: !get_files("catch_up_package").is_empty(),
+            proposals: get_files("block_proposal"),
+            finalizations: get_files("finalization"),
+            notarizations: get_files("notarization"),
+            artifacts,
+        }
+    }
+
+    // Reads the artifact with the given file name and returns its content as
+    // bytes, or `None` if there is no such artifact.
+    fn read(&self, file_name: &str) -> Option<Vec<u8>> {
+        self.artifacts.get(file_name).map(read_artifact)
+    }
+}
+
+// Reads the artifact at `location` and the returns the content as bytes.
+fn read_artifact(location: &ArtifactLocation) -> Vec<u8> {
+    location
+        .read()
+        .unwrap_or_else(|err| panic!("Couldn't read artifact {}: {:?}", location, err))
 }
 
 /// All possible exits from the deserialization loop of the artifacts. All
@@ -86,13 +109,13 @@ pub(crate) fn insert_cup_at_height(
 
 /// Deserializes the CUP at the given height and returns it.
 pub(crate) fn read_cup_at_height(backup_dir: &Path, height: Height) -> CatchUpPackage {
-    let group_key = (height.get() / BACKUP_GROUP_SIZE) * BACKUP_GROUP_SIZE;
-    let buffer = read_file(
-        &backup_dir
-            .join(group_key.to_string())
-            .join(height.to_string())
-            .join("catch_up_package.bin"),
-    );
+    let artifacts = list_artifacts(backup_dir, &HeightRange::new(height, height))
+        .unwrap_or_else(|err| panic!("Couldn't read backup at {:?}: {:?}", backup_dir, err));
+    let buffer = artifacts
+        .get(&height)
+        .and_then(|artifacts| artifacts.get("catch_up_package.bin"))
+        .map(read_artifact)
+        .unwrap_or_else(|| panic!("Couldn't find a CUP at height {:?}", height));
 
     let protobuf = ic_protobuf::types::v1::CatchUpPackage::decode(buffer.as_slice())
         .expect("Protobuf decoding failed");
@@ -101,61 +124,17 @@ pub(crate) fn read_cup_at_height(backup_dir: &Path, height: Height) -> CatchUpPa
         .unwrap_or_else(|_| panic!("{}", deserialization_error(height)))
 }
 
-/// Read all files from the backup folder starting from the `start_height` and
-/// convert them into batches.
+/// Read all artifacts from the backup folder starting from the `start_height`
+/// and convert them into batches.
 pub(super) fn heights_to_artifacts_metadata(
     backup_dir: &Path,
     start_height: Height,
 ) -> Result<BTreeMap<Height, HeightArtifacts>, std::io::Error> {
-    let mut results = Vec::new();
-    for group_dir in fs::read_dir(backup_dir)? {
-        for height_dir in fs::read_dir(group_dir?.path())? {
-            let path = height_dir?.path();
-            let height = Height::from(
-                path.file_name()
-                    .unwrap_or_default()
-                    .to_str()
-                    .unwrap_or_default()
-                    .parse::<u64>()
-                    .expect("Couldn't parse the height directory name"),
-            );
-
-            // Skip all height folders below the start height,
-            if height < start_height {
-                continue;
-            }
-            let mut files = Vec::new();
-            for file in fs::read_dir(&path)? {
-                let file_path = file?.path();
-                files.push(
-                    file_path
-                        .file_name()
-                        .unwrap_or_default()
-                        .to_str()
-                        .unwrap_or_default()
-                        .to_string(),
-                );
-            }
-            let get_files = |s| {
-                files
-                    .iter()
-                    .filter(|file| file.starts_with(s))
-                    .cloned()
-                    .collect::<Vec<_>>()
-            };
-            results.push((
-                height,
-                HeightArtifacts {
-                    path,
-                    contains_cup: !get_files("catch_up_package").is_empty(),
-                    proposals: get_files("block_proposal"),
-                    finalizations: get_files("finalization"),
-                    notarizations: get_files("notarization"),
-                },
-            ));
-        }
-    }
-    Ok(results.into_iter().collect())
+    let range = HeightRange::new(start_height, Height::from(u64::MAX));
+    Ok(list_artifacts(backup_dir, &range)?
+        .into_iter()
+        .map(|(height, artifacts)| (height, HeightArtifacts::new(artifacts)))
+        .collect())
 }
 
 /// Deserializes consensus artifacts, reading them from the backup spool height
@@ -194,7 +173,6 @@ pub(crate) fn deserialize_consensus_artifacts(
             last_cup_height = Some(height);
         }
 
-        let path = &height_artifacts.path;
         let mut artifacts = Vec::new();
 
         if height_artifacts.proposals.is_empty() {
@@ -217,7 +195,7 @@ pub(crate) fn deserialize_consensus_artifacts(
         if let Some(file_name) = &height_artifacts.finalizations.get(0) {
             // Save the hash of the finalized block proposal.
             finalized_block_hash = file_name.split('_').nth(1);
-            let buffer = read_file(&path.join(file_name));
+            let buffer = read_artifact(&height_artifacts.artifacts[*file_name]);
             let finalization = Finalization::try_from(
                 pb::Finalization::decode(buffer.as_slice()).expect("Protobuf decoding failed"),
             )
@@ -248,7 +226,7 @@ pub(crate) fn deserialize_consensus_artifacts(
             // Otherwise, insert all.
             .filter(|name| name.contains(finalized_block_hash.unwrap_or("")))
         {
-            let buffer = read_file(&path.join(file_name));
+            let buffer = read_artifact(&height_artifacts.artifacts[file_name]);
             let proposal = BlockProposal::try_from(
                 pb::BlockProposal::decode(buffer.as_slice()).expect("Protobuf decoding failed"),
             )
@@ -288,15 +266,16 @@ pub(crate) fn deserialize_consensus_artifacts(
         }
 
         // Insert the random beacon and the random tape.
-        let rb_path = path.join("random_beacon.bin");
-        if !rb_path.exists() {
-            println!(
-                "Stopping deserialization at height {:?} as this height contains no random beacon.",
-                height,
-            );
-            return ExitPoint::Done;
-        }
-        let buffer = read_file(&rb_path);
+        let buffer = match height_artifacts.read("random_beacon.bin") {
+            Some(buffer) => buffer,
+            None => {
+                println!(
+                    "Stopping deserialization at height {:?} as this height contains no random beacon.",
+                    height,
+                );
+                return ExitPoint::Done;
+            }
+        };
         artifacts.push(
             RandomBeacon::try_from(
                 pb::RandomBeacon::decode(buffer.as_slice()).expect("Protobuf decoding failed"),
@@ -305,15 +284,16 @@ pub(crate) fn deserialize_consensus_artifacts(
             .into_message(),
         );
 
-        let rt_path = path.join("random_tape.bin");
-        if !rt_path.exists() {
-            println!(
-                "Stopping deserialization at height {:?} as this height contains no random tape.",
-                height,
-            );
-            return ExitPoint::Done;
-        }
-        let buffer = read_file(&rt_path);
+        let buffer = match height_artifacts.read("random_tape.bin") {
+            Some(buffer) => buffer,
+            None => {
+                println!(
+                    "Stopping deserialization at height {:?} as this height contains no random tape.",
+                    height,
+                );
+                return ExitPoint::Done;
+            }
+        };
         artifacts.push(
             RandomTape::try_from(
                 pb::RandomTape::decode(buffer.as_slice()).expect("Protobuf decoding failed"),
@@ -324,7 +304,7 @@ pub(crate) fn deserialize_consensus_artifacts(
 
         // Insert the notarizations.
         for file_name in &height_artifacts.notarizations {
-            let buffer = read_file(&path.join(file_name));
+            let buffer = read_artifact(&height_artifacts.artifacts[file_name]);
             artifacts.push(
                 Notarization::try_from(
                     pb::Notarization::decode(buffer.as_slice()).expect("Protobuf decoding failed"),
@@ -361,11 +341,11 @@ pub(crate) fn deserialize_consensus_artifacts(
             };
         invalid.iter().for_each(|i| match i.get_file_name() {
             Some(name) => {
-                assert!(
-                    path.join(&name).exists(),
-                    "Path to invalid artifact doesn't exist."
-                );
-                println!("Invalid artifact detected: {:?}", path.join(name));
+                let location = height_artifacts
+                    .artifacts
+                    .get(&name)
+                    .expect("Path to invalid artifact doesn't exist.");
+                println!("Invalid artifact detected: {}", location);
             }
             None => println!("Failed to get path for invalid artifact: {:?}", i),
         });
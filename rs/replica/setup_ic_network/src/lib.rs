@@ -12,7 +12,8 @@ use ic_artifact_pool::{
     ensure_persistent_pool_replica_version_compatibility, ingress_pool::IngressPoolImpl,
 };
 use ic_config::{
-    artifact_pool::ArtifactPoolConfig, consensus::ConsensusConfig, transport::TransportConfig,
+    artifact_pool::ArtifactPoolConfig, consensus::ConsensusConfig, p2p::Config as P2PConfig,
+    transport::TransportConfig,
 };
 use ic_consensus::{
     canister_http, certification,
@@ -24,7 +25,7 @@ use ic_cycles_account_manager::CyclesAccountManager;
 use ic_ingress_manager::IngressManager;
 use ic_interfaces::{
     artifact_manager::{ArtifactClient, ArtifactManager, ArtifactProcessor},
-    consensus_pool::ConsensusPoolCache,
+    consensus_pool::{ConsensusPoolCache, ConsensusTimingsRecorder},
     crypto::{Crypto, IngressSigVerifier},
     execution_environment::IngressHistoryReader,
     messaging::{MessageRouting, XNetPayloadBuilder},
@@ -73,6 +74,7 @@ pub struct ArtifactPools {
     pub ingress_pool: Arc<RwLock<IngressPoolImpl>>,
     pub consensus_pool: Arc<RwLock<ConsensusPoolImpl>>,
     pub consensus_pool_cache: Arc<dyn ConsensusPoolCache>,
+    pub consensus_timings_recorder: Arc<dyn ConsensusTimingsRecorder>,
     pub certification_pool: Arc<RwLock<CertificationPoolImpl>>,
     pub dkg_pool: Arc<RwLock<DkgPoolImpl>>,
     pub ecdsa_pool: Arc<RwLock<EcdsaPoolImpl>>,
@@ -93,6 +95,7 @@ pub fn create_networking_stack(
     rt_handle: tokio::runtime::Handle,
     transport_config: TransportConfig,
     consensus_config: ConsensusConfig,
+    p2p_config: P2PConfig,
     malicious_flags: MaliciousFlags,
     node_id: NodeId,
     subnet_id: SubnetId,
@@ -179,6 +182,7 @@ pub fn create_networking_stack(
         subnet_id,
         transport_config,
         gossip_config,
+        p2p_config,
         registry_client,
         transport,
         artifact_pools.consensus_pool_cache.clone(),
@@ -321,6 +325,7 @@ fn setup_artifact_manager(
                     Arc::clone(&dkg_key_manager) as Arc<_>,
                     Arc::clone(&message_router) as Arc<_>,
                     Arc::clone(&state_manager) as Arc<_>,
+                    Arc::clone(&artifact_pools.consensus_timings_recorder),
                     Arc::clone(&time_source) as Arc<_>,
                     malicious_flags.clone(),
                     metrics_registry.clone(),
@@ -364,6 +369,8 @@ fn setup_artifact_manager(
                     Arc::clone(&membership) as Arc<_>,
                     Arc::clone(&certifier_crypto),
                     Arc::clone(&state_manager) as Arc<_>,
+                    Arc::clone(&artifact_pools.consensus_timings_recorder),
+                    Arc::clone(&time_source) as Arc<_>,
                     metrics_registry.clone(),
                     replica_logger.clone(),
                 )
@@ -510,6 +517,7 @@ pub fn init_artifact_pools(
         log.clone(),
     )));
     let consensus_pool_cache = consensus_pool.read().unwrap().get_cache();
+    let consensus_timings_recorder = consensus_pool.read().unwrap().get_timings_recorder();
     let certification_pool = Arc::new(RwLock::new(CertificationPoolImpl::new(
         config,
         log,
@@ -521,6 +529,7 @@ pub fn init_artifact_pools(
         ingress_pool,
         consensus_pool,
         consensus_pool_cache,
+        consensus_timings_recorder,
         certification_pool,
         dkg_pool,
         ecdsa_pool,
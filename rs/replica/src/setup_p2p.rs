@@ -151,13 +151,19 @@ pub fn construct_ic_stack(
         Some(artifact_pools.consensus_pool_cache.starting_height()),
         config.malicious_behaviour.malicious_flags.clone(),
     ));
+    // Unless configured otherwise, compiled canister modules are persisted
+    // next to the replicated state so that they survive replica restarts.
+    let mut hypervisor_config = config.hypervisor.clone();
+    hypervisor_config
+        .compilation_cache_dir
+        .get_or_insert_with(|| config.state_manager.state_root().join("compilation_cache"));
     let execution_services = ExecutionServices::setup_execution(
         replica_logger.clone(),
         &metrics_registry,
         subnet_id,
         subnet_type,
         subnet_config.scheduler_config,
-        config.hypervisor.clone(),
+        hypervisor_config,
         Arc::clone(&cycles_account_manager),
         Arc::clone(&state_manager) as Arc<_>,
     );
@@ -254,6 +260,7 @@ pub fn construct_ic_stack(
         rt_handle,
         config.transport,
         config.consensus,
+        config.p2p,
         config.malicious_behaviour.malicious_flags,
         node_id,
         subnet_id,
@@ -386,6 +386,7 @@ where
             legacy_flow_tag: 0,
             listening_port: 1234,
             send_queue_size: 0,
+            send_hello: false,
         };
         let temp_node = node_id;
         let (
@@ -172,6 +172,21 @@ impl UtxoSet {
     }
 }
 
+/// Indexes over the stable blocks that are only needed to answer queries.
+/// See `ic_btc_canister::state` for more documentation.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BlockIndex {
+    /// PageMap storing the headers of all stable blocks by height.
+    pub block_headers: PageMap,
+
+    /// PageMap storing an index mapping a Bitcoin address to the transactions
+    /// that pay to or spend from it.
+    pub address_history: PageMap,
+
+    /// Whether `address_history` is maintained.
+    pub address_history_enabled: bool,
+}
+
 /// A data structure for maintaining all unstable blocks.
 ///
 /// A block `b` is considered stable if:
@@ -224,6 +239,8 @@ pub struct BitcoinState {
     pub unstable_blocks: UnstableBlocks,
     pub stable_height: u32,
     pub fee_percentiles_cache: Option<FeePercentilesCache>,
+    pub block_index: BlockIndex,
+    pub outgoing_transactions: Vec<OutgoingTransaction>,
 }
 
 impl Default for BitcoinState {
@@ -247,6 +264,8 @@ impl BitcoinState {
             ),
             stable_height: 0,
             fee_percentiles_cache: None,
+            block_index: BlockIndex::default(),
+            outgoing_transactions: Vec::new(),
         }
     }
 
@@ -258,6 +277,8 @@ impl BitcoinState {
             unstable_blocks: UnstableBlocks::default(),
             stable_height: 0,
             fee_percentiles_cache: None,
+            block_index: BlockIndex::default(),
+            outgoing_transactions: Vec::new(),
         }
     }
 
@@ -396,6 +417,36 @@ impl TryFrom<pb_bitcoin::UnstableBlocks> for UnstableBlocks {
     }
 }
 
+/// A transaction sent with `bitcoin_send_transaction` that is rebroadcast
+/// until it is mined or expires.
+/// See `ic_protobuf::bitcoin::v1` for documentation of the fields.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutgoingTransaction {
+    pub transaction: Vec<u8>,
+    pub expiry_height: u32,
+    pub last_broadcast_height: u32,
+}
+
+impl From<&OutgoingTransaction> for pb_bitcoin::OutgoingTransaction {
+    fn from(tx: &OutgoingTransaction) -> Self {
+        pb_bitcoin::OutgoingTransaction {
+            transaction: tx.transaction.clone(),
+            expiry_height: tx.expiry_height,
+            last_broadcast_height: tx.last_broadcast_height,
+        }
+    }
+}
+
+impl From<pb_bitcoin::OutgoingTransaction> for OutgoingTransaction {
+    fn from(tx: pb_bitcoin::OutgoingTransaction) -> Self {
+        Self {
+            transaction: tx.transaction,
+            expiry_height: tx.expiry_height,
+            last_broadcast_height: tx.last_broadcast_height,
+        }
+    }
+}
+
 /// Cache for storing last calculated fee percentiles
 ///
 /// Stores last tip block hash and fee percentiles associated with it.
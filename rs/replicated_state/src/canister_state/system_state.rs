@@ -96,6 +96,11 @@ pub struct SystemState {
     /// Tasks to execute before processing input messages.
     /// Currently the task queue is empty outside of execution rounds.
     pub task_queue: VecDeque<ExecutionTask>,
+
+    /// The number of successful code installations (install, reinstall,
+    /// upgrade and uninstall) and settings changes of the canister. Exposed to
+    /// the canister via `ic0.canister_version`.
+    pub canister_version: u64,
 }
 
 /// A wrapper around the different canister statuses.
@@ -358,6 +363,7 @@ impl SystemState {
             certified_data: Default::default(),
             canister_metrics: CanisterMetrics::default(),
             task_queue: Default::default(),
+            canister_version: 0,
         }
     }
 
@@ -389,6 +395,7 @@ impl SystemState {
         canister_metrics: CanisterMetrics,
         cycles_balance: Cycles,
         task_queue: VecDeque<ExecutionTask>,
+        canister_version: u64,
     ) -> Self {
         Self {
             controllers,
@@ -401,6 +408,7 @@ impl SystemState {
             canister_metrics,
             cycles_balance,
             task_queue,
+            canister_version,
         }
     }
 
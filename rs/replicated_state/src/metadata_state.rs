@@ -21,7 +21,7 @@ use ic_registry_routing_table::{
     canister_id_into_u64, difference, intersection, CanisterIdRanges, CanisterMigrations,
     RoutingTable, CANISTER_IDS_PER_SUBNET,
 };
-use ic_registry_subnet_features::{BitcoinFeature, BitcoinFeatureStatus, SubnetFeatures};
+use ic_registry_subnet_features::{BitcoinFeatureStatus, SubnetFeatures};
 use ic_registry_subnet_type::SubnetType;
 use ic_types::nominal_cycles::NominalCycles;
 use ic_types::{
@@ -186,11 +186,9 @@ impl NetworkTopology {
         self.subnets
             .iter()
             .filter(|(_, subnet_topology)| {
-                subnet_topology.subnet_features.bitcoin()
-                    == BitcoinFeature {
-                        network: BitcoinNetwork::Testnet,
-                        status: BitcoinFeatureStatus::Enabled,
-                    }
+                let bitcoin = subnet_topology.subnet_features.bitcoin();
+                bitcoin.network == BitcoinNetwork::Testnet
+                    && bitcoin.status == BitcoinFeatureStatus::Enabled
             })
             .map(|(subnet_id, _)| *subnet_id)
             .collect()
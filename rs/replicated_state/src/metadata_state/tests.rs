@@ -474,9 +474,10 @@ fn subnet_call_contexts_deserialization() {
             .build(),
         url: url.clone(),
         max_response_bytes: None,
+        max_redirects: 3,
         headers: Vec::new(),
         body: None,
-        http_method: CanisterHttpMethod::GET,
+        http_method: CanisterHttpMethod::PATCH,
         transform_method_name: transform_method_name.clone(),
         time: mock_time(),
     };
@@ -500,8 +501,9 @@ fn subnet_call_contexts_deserialization() {
     assert_eq!(deserialized_http_request_context.url, url);
     assert_eq!(
         deserialized_http_request_context.http_method,
-        CanisterHttpMethod::GET
+        CanisterHttpMethod::PATCH
     );
+    assert_eq!(deserialized_http_request_context.max_redirects, 3);
     assert_eq!(
         deserialized_http_request_context.transform_method_name,
         transform_method_name
@@ -316,6 +316,7 @@ struct CliArgs {
             "bitcoin_regtest",
             "bitcoin_regtest_syncing",
             "bitcoin_regtest_paused",
+            "bitcoin_address_history",
         ],
         multiple_values(true))]
     subnet_features: Vec<String>,
@@ -546,10 +547,14 @@ impl CliArgs {
 fn to_subnet_features(features: &[String]) -> SubnetFeatures {
     let canister_sandboxing = features.iter().any(|s| s.as_str() == "canister_sandboxing");
     let http_requests = features.iter().any(|s| s.as_str() == "http_requests");
+    let address_history = features
+        .iter()
+        .any(|s| s.as_str() == "bitcoin_address_history");
     let bitcoin = if features.iter().any(|s| s.as_str() == "bitcoin_testnet") {
         Some(BitcoinFeatureInfo {
             network: BitcoinNetwork::Testnet.into(),
             status: BitcoinFeatureStatus::Enabled.into(),
+            address_history,
         })
     } else if features
         .iter()
@@ -558,6 +563,7 @@ fn to_subnet_features(features: &[String]) -> SubnetFeatures {
         Some(BitcoinFeatureInfo {
             network: BitcoinNetwork::Testnet.into(),
             status: BitcoinFeatureStatus::Paused.into(),
+            address_history,
         })
     } else if features
         .iter()
@@ -566,11 +572,13 @@ fn to_subnet_features(features: &[String]) -> SubnetFeatures {
         Some(BitcoinFeatureInfo {
             network: BitcoinNetwork::Testnet.into(),
             status: BitcoinFeatureStatus::Syncing.into(),
+            address_history,
         })
     } else if features.iter().any(|s| s.as_str() == "bitcoin_mainnet") {
         Some(BitcoinFeatureInfo {
             network: BitcoinNetwork::Mainnet.into(),
             status: BitcoinFeatureStatus::Enabled.into(),
+            address_history,
         })
     } else if features
         .iter()
@@ -579,6 +587,7 @@ fn to_subnet_features(features: &[String]) -> SubnetFeatures {
         Some(BitcoinFeatureInfo {
             network: BitcoinNetwork::Mainnet.into(),
             status: BitcoinFeatureStatus::Paused.into(),
+            address_history,
         })
     } else if features
         .iter()
@@ -587,11 +596,13 @@ fn to_subnet_features(features: &[String]) -> SubnetFeatures {
         Some(BitcoinFeatureInfo {
             network: BitcoinNetwork::Mainnet.into(),
             status: BitcoinFeatureStatus::Syncing.into(),
+            address_history,
         })
     } else if features.iter().any(|s| s.as_str() == "bitcoin_regtest") {
         Some(BitcoinFeatureInfo {
             network: BitcoinNetwork::Regtest.into(),
             status: BitcoinFeatureStatus::Enabled.into(),
+            address_history,
         })
     } else if features
         .iter()
@@ -600,6 +611,7 @@ fn to_subnet_features(features: &[String]) -> SubnetFeatures {
         Some(BitcoinFeatureInfo {
             network: BitcoinNetwork::Regtest.into(),
             status: BitcoinFeatureStatus::Syncing.into(),
+            address_history,
         })
     } else if features
         .iter()
@@ -608,6 +620,7 @@ fn to_subnet_features(features: &[String]) -> SubnetFeatures {
         Some(BitcoinFeatureInfo {
             network: BitcoinNetwork::Regtest.into(),
             status: BitcoinFeatureStatus::Paused.into(),
+            address_history,
         })
     } else {
         None
@@ -693,6 +706,7 @@ impl ValidatedConfig {
             legacy_flow_tag: 1234,
             listening_port: 0,
             send_queue_size: 1024,
+            send_hello: false,
         });
 
         let hypervisor_config = HypervisorConfig {
@@ -189,6 +189,7 @@ pub struct CanisterStateBits {
     pub heap_delta_debit: NumBytes,
     pub install_code_debit: NumInstructions,
     pub task_queue: Vec<ExecutionTask>,
+    pub canister_version: u64,
 }
 
 /// This struct contains bits of the `BitcoinState` that are not already
@@ -200,6 +201,8 @@ pub struct BitcoinStateBits {
     pub stable_height: u32,
     pub network: Network,
     pub utxos_large: BTreeMap<OutPoint, (TxOut, u32)>,
+    pub address_history_enabled: bool,
+    pub outgoing_transactions: Vec<bitcoin_state::OutgoingTransaction>,
 }
 
 impl Default for BitcoinStateBits {
@@ -210,6 +213,8 @@ impl Default for BitcoinStateBits {
             unstable_blocks: bitcoin_state::UnstableBlocks::default(),
             stable_height: 0,
             utxos_large: BTreeMap::default(),
+            address_history_enabled: false,
+            outgoing_transactions: Vec::new(),
         }
     }
 }
@@ -236,6 +241,8 @@ impl Default for BitcoinStateBits {
 /// |   |       └── utxos_small.bin
 /// |   |       └── utxos_medium.bin
 /// |   |       └── address_outpoints.bin
+/// |   |       └── block_headers.bin
+/// |   |       └── address_history.bin
 /// │   └── canister_states
 /// │       └── <hex(canister_id)>
 /// │           ├── queues.pbuf
@@ -254,6 +261,8 @@ impl Default for BitcoinStateBits {
 /// |      |       └── utxos_small.bin
 /// |      |       └── utxos_medium.bin
 /// |      |       └── address_outpoints.bin
+/// |      |       └── block_headers.bin
+/// |      |       └── address_history.bin
 /// │      └── canister_states
 /// │          └── <hex(canister_id)>
 /// │              ├── queues.pbuf
@@ -287,7 +296,8 @@ impl StateLayout {
     }
 
     /// Returns the path to the temporary directory.
-    /// This directory is cleaned during restart of a node.
+    /// This directory is cleaned during restart of a node, except for the
+    /// files of a statesync that can be resumed.
     pub fn tmp(&self) -> Result<PathBuf, LayoutError> {
         let tmp = self.cp_manager.raw_path().join("tmp");
         WriteOnly::check_dir(&tmp)?;
@@ -325,6 +335,94 @@ impl StateLayout {
         Ok(tmp.join(format!("state_sync_scratchpad_{:016x}", height.get())))
     }
 
+    /// Returns the path of the journal of the statesync at `height`. The
+    /// journal records the chunks written to the scratchpad, so that the
+    /// statesync can be resumed after a restart.
+    pub fn state_sync_journal(&self, height: Height) -> Result<PathBuf, LayoutError> {
+        let tmp = self.tmp()?;
+        Ok(tmp.join(format!("state_sync_journal_{:016x}", height.get())))
+    }
+
+    /// Removes the contents of the tmp directory, except for the scratchpad
+    /// and the journal of the journaled statesync at the greatest height, so
+    /// that this statesync can be resumed. Returns the height of the kept
+    /// statesync, if any.
+    pub fn remove_tmp_except_resumable_state_sync(&self) -> Result<Option<Height>, LayoutError> {
+        let tmp = self.tmp()?;
+        let io_error = |path: &Path, message: &str, io_err| LayoutError::IoError {
+            path: path.to_path_buf(),
+            message: message.to_string(),
+            io_err,
+        };
+        let mut paths = vec![];
+        for entry in std::fs::read_dir(&tmp)
+            .map_err(|err| io_error(&tmp, "Failed to list temporary directory", err))?
+        {
+            let entry =
+                entry.map_err(|err| io_error(&tmp, "Failed to list temporary directory", err))?;
+            paths.push(entry.path());
+        }
+
+        let mut resumable_height = None;
+        for path in paths.iter() {
+            let height = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix("state_sync_journal_"))
+                .and_then(|hex| u64::from_str_radix(hex, 16).ok())
+                .map(Height::from);
+            if let Some(height) = height {
+                if self.state_sync_scratchpad(height)?.is_dir()
+                    && resumable_height.map_or(true, |h| h < height)
+                {
+                    resumable_height = Some(height);
+                }
+            }
+        }
+
+        let keep = match resumable_height {
+            Some(height) => vec![
+                self.state_sync_scratchpad(height)?,
+                self.state_sync_journal(height)?,
+            ],
+            None => vec![],
+        };
+        for path in paths.into_iter().filter(|path| !keep.contains(path)) {
+            let result = if path.is_dir() {
+                std::fs::remove_dir_all(&path)
+            } else {
+                std::fs::remove_file(&path)
+            };
+            result.map_err(|err| io_error(&path, "Unable to remove temporary file", err))?;
+        }
+        Ok(resumable_height)
+    }
+
+    /// Removes the journal and the scratchpad of the statesync at `height`,
+    /// if they exist.
+    pub fn remove_state_sync_scratchpad(&self, height: Height) -> Result<(), LayoutError> {
+        let journal = self.state_sync_journal(height)?;
+        match std::fs::remove_file(&journal) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                return Err(LayoutError::IoError {
+                    path: journal,
+                    message: "Unable to remove statesync journal".to_string(),
+                    io_err: err,
+                })
+            }
+            _ => {}
+        }
+        let scratchpad = self.state_sync_scratchpad(height)?;
+        match std::fs::remove_dir_all(&scratchpad) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(LayoutError::IoError {
+                path: scratchpad,
+                message: "Unable to remove statesync scratchpad".to_string(),
+                io_err: err,
+            }),
+            _ => Ok(()),
+        }
+    }
+
     /// Returns the path to cache an unfinished statesync at `height`
     pub fn state_sync_cache(&self, height: Height) -> Result<PathBuf, LayoutError> {
         let tmp = self.tmp()?;
@@ -844,6 +942,14 @@ impl<Permissions: AccessPolicy> BitcoinStateLayout<Permissions> {
     pub fn address_outpoints(&self) -> PathBuf {
         self.bitcoin_root.join("address_outpoints.bin")
     }
+
+    pub fn block_headers(&self) -> PathBuf {
+        self.bitcoin_root.join("block_headers.bin")
+    }
+
+    pub fn address_history(&self) -> PathBuf {
+        self.bitcoin_root.join("address_history.bin")
+    }
 }
 
 fn open_for_write(path: &Path) -> Result<std::fs::File, LayoutError> {
@@ -1073,6 +1179,7 @@ impl From<CanisterStateBits> for pb_canister_state_bits::CanisterStateBits {
             heap_delta_debit: item.heap_delta_debit.get(),
             install_code_debit: item.install_code_debit.get(),
             task_queue: item.task_queue.iter().map(|v| v.into()).collect(),
+            canister_version: item.canister_version,
         }
     }
 }
@@ -1145,6 +1252,7 @@ impl TryFrom<pb_canister_state_bits::CanisterStateBits> for CanisterStateBits {
             heap_delta_debit: NumBytes::from(value.heap_delta_debit),
             install_code_debit: NumInstructions::from(value.install_code_debit),
             task_queue,
+            canister_version: value.canister_version,
         })
     }
 }
@@ -1230,6 +1338,12 @@ impl From<&BitcoinStateBits> for pb_bitcoin::BitcoinStateBits {
                     height: *height,
                 })
                 .collect(),
+            address_history_enabled: item.address_history_enabled,
+            outgoing_transactions: item
+                .outgoing_transactions
+                .iter()
+                .map(|tx| tx.into())
+                .collect(),
         }
     }
 }
@@ -1296,6 +1410,12 @@ impl TryFrom<pb_bitcoin::BitcoinStateBits> for BitcoinStateBits {
                     (outpoint, (tx_out, utxo.height))
                 })
                 .collect(),
+            address_history_enabled: value.address_history_enabled,
+            outgoing_transactions: value
+                .outgoing_transactions
+                .into_iter()
+                .map(|tx| tx.into())
+                .collect(),
         })
     }
 }
@@ -1333,9 +1453,52 @@ mod test {
             heap_delta_debit: NumBytes::from(0),
             install_code_debit: NumInstructions::from(0),
             task_queue: vec![],
+            canister_version: 0,
         }
     }
 
+    #[test]
+    fn test_remove_tmp_keeps_resumable_state_sync() {
+        let tmpdir = tempfile::Builder::new().prefix("test").tempdir().unwrap();
+        let layout = StateLayout::new(
+            ic_logger::replica_logger::no_op_logger(),
+            tmpdir.path().to_path_buf(),
+        );
+        for h in [1, 2, 3] {
+            std::fs::create_dir(layout.state_sync_scratchpad(Height::new(h)).unwrap()).unwrap();
+        }
+        // The journaled scratchpad at the greatest height is kept, even if
+        // there is a journal of a greater height without a scratchpad.
+        for h in [1, 2, 4] {
+            std::fs::write(layout.state_sync_journal(Height::new(h)).unwrap(), b"").unwrap();
+        }
+        std::fs::write(layout.tmp().unwrap().join("other"), b"").unwrap();
+
+        assert_eq!(
+            layout.remove_tmp_except_resumable_state_sync().unwrap(),
+            Some(Height::new(2))
+        );
+        let mut remaining: Vec<_> = std::fs::read_dir(layout.tmp().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        remaining.sort();
+        assert_eq!(
+            remaining,
+            vec![
+                layout.state_sync_journal(Height::new(2)).unwrap(),
+                layout.state_sync_scratchpad(Height::new(2)).unwrap(),
+            ]
+        );
+
+        std::fs::remove_dir(layout.state_sync_scratchpad(Height::new(2)).unwrap()).unwrap();
+        assert_eq!(
+            layout.remove_tmp_except_resumable_state_sync().unwrap(),
+            None
+        );
+        assert_eq!(std::fs::read_dir(layout.tmp().unwrap()).unwrap().count(), 0);
+    }
+
     #[test]
     fn test_encode_decode_empty_controllers() {
         // A canister state with empty controllers.
@@ -1394,4 +1557,16 @@ mod test {
         let canister_state_bits = CanisterStateBits::try_from(pb_bits).unwrap();
         assert_eq!(canister_state_bits.task_queue, task_queue);
     }
+
+    #[test]
+    fn test_encode_decode_canister_version() {
+        let canister_state_bits = CanisterStateBits {
+            canister_version: 42,
+            ..default_canister_state_bits()
+        };
+
+        let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
+        let canister_state_bits = CanisterStateBits::try_from(pb_bits).unwrap();
+        assert_eq!(canister_state_bits.canister_version, 42);
+    }
 }
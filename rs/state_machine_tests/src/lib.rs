@@ -8,6 +8,10 @@ use ic_crypto_internal_threshold_sig_bls12381::types::SecretKeyBytes;
 use ic_crypto_internal_types::sign::threshold_sig::public_key::CspThresholdSigPublicKey;
 use ic_crypto_tree_hash::{flatmap, Label, LabeledTree, LabeledTree::SubTree};
 use ic_cycles_account_manager::CyclesAccountManager;
+use ic_embedders::wasm_utils::{
+    decoding::decode_wasm,
+    profiling::{instruction_profile, to_folded_stacks},
+};
 pub use ic_error_types::{ErrorCode, UserError};
 use ic_execution_environment::ExecutionServices;
 use ic_ic00_types::{self as ic00, CanisterIdRecord, InstallCodeArgs, Method, Payload};
@@ -287,6 +291,9 @@ impl StateMachine {
         if !(std::env::var("SANDBOX_BINARY").is_ok() && std::env::var("LAUNCHER_BINARY").is_ok()) {
             hypervisor_config.canister_sandboxing_flag = FlagStatus::Disabled;
         }
+        if std::env::var("INSTRUCTION_PROFILING").is_ok() {
+            hypervisor_config.instruction_profiling = FlagStatus::Enabled;
+        }
 
         let cycles_account_manager = Arc::new(CyclesAccountManager::new(
             subnet_config.scheduler_config.max_instructions_per_message,
@@ -845,6 +852,44 @@ impl StateMachine {
         method: impl ToString,
         method_payload: Vec<u8>,
     ) -> Result<WasmResult, UserError> {
+        let (query, state, data_certificate) =
+            self.certified_query(sender, receiver, method, method_payload);
+        self.query_handler.query(query, state, data_certificate)
+    }
+
+    /// Queries the canister with the specified ID using the anonymous principal
+    /// and returns the instructions executed by each function of the queried
+    /// canisters in the folded stack format understood by flamegraph tools.
+    ///
+    /// Unlike [`StateMachine::instruction_profile`], the profile only covers
+    /// this query. It is empty unless profiling is enabled by setting the
+    /// `INSTRUCTION_PROFILING` environment variable before the state machine
+    /// is created.
+    pub fn query_with_instruction_profile(
+        &self,
+        receiver: CanisterId,
+        method: impl ToString,
+        method_payload: Vec<u8>,
+    ) -> (Result<WasmResult, UserError>, String) {
+        let (query, state, data_certificate) = self.certified_query(
+            PrincipalId::new_anonymous(),
+            receiver,
+            method,
+            method_payload,
+        );
+        self.query_handler
+            .query_with_instruction_profile(query, state, data_certificate)
+    }
+
+    // Certifies the latest state if needed and returns the query together with
+    // the state and data certificate to execute it against.
+    fn certified_query(
+        &self,
+        sender: PrincipalId,
+        receiver: CanisterId,
+        method: impl ToString,
+        method_payload: Vec<u8>,
+    ) -> (UserQuery, Arc<ReplicatedState>, Vec<u8>) {
         if self.state_manager.latest_state_height() > self.state_manager.latest_certified_height() {
             let state_hashes = self.state_manager.list_state_hashes_to_certify();
             let (height, hash) = state_hashes.last().unwrap();
@@ -867,18 +912,15 @@ impl StateMachine {
             signature: Blob(certification.signed.signature.signature.get().0),
             delegation: None,
         });
-        self.query_handler.query(
-            UserQuery {
-                receiver,
-                source: UserId::from(sender),
-                method_name: method.to_string(),
-                method_payload,
-                ingress_expiry: 0,
-                nonce: None,
-            },
-            state,
-            data_certificate,
-        )
+        let query = UserQuery {
+            receiver,
+            source: UserId::from(sender),
+            method_name: method.to_string(),
+            method_payload,
+            ingress_expiry: 0,
+            nonce: None,
+        };
+        (query, state, data_certificate)
     }
 
     fn certify_hash(&self, height: &Height, hash: &CryptoHashOfPartialState) -> Certification {
@@ -924,6 +966,34 @@ impl StateMachine {
         )
     }
 
+    /// Returns the number of instructions executed by each function of the
+    /// specified canister since its module was installed, in the folded stack
+    /// format understood by flamegraph tools.
+    ///
+    /// Profiling must be enabled by setting the `INSTRUCTION_PROFILING`
+    /// environment variable before the state machine is created, otherwise
+    /// the result is empty.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if:
+    ///   * The specified canister does not exist.
+    ///   * The specified canister does not have a module installed.
+    pub fn instruction_profile(&self, canister_id: CanisterId) -> String {
+        let state = self.state_manager.get_latest_state().take();
+        let execution_state = state
+            .canister_state(&canister_id)
+            .unwrap_or_else(|| panic!("Canister {} does not exist", canister_id))
+            .execution_state
+            .as_ref()
+            .unwrap_or_else(|| panic!("Canister {} has no module", canister_id));
+        let wasm = decode_wasm(execution_state.wasm_binary.binary.to_shared_vec())
+            .expect("failed to decode canister module");
+        let profile = instruction_profile(&wasm, &execution_state.exported_globals)
+            .expect("failed to instrument canister module");
+        to_folded_stacks(&canister_id.to_string(), &profile)
+    }
+
     /// Executes an ingress message on the canister with the specified ID.
     ///
     /// This function is synchronous, it blocks until the result of the ingress
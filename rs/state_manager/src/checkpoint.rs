@@ -7,7 +7,7 @@ use ic_logger::ReplicaLogger;
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::Memory;
 use ic_replicated_state::{
-    bitcoin_state::{BitcoinState, UtxoSet},
+    bitcoin_state::{BitcoinState, BlockIndex, UtxoSet},
     canister_state::execution_state::WasmBinary,
     page_map::PageMap,
     CanisterMetrics, CanisterState, ExecutionState, NumWasmPages, ReplicatedState, SchedulerState,
@@ -223,6 +223,7 @@ fn serialize_canister_to_tip(
                     .clone()
                     .into_iter()
                     .collect(),
+                canister_version: canister_state.system_state.canister_version,
             }
             .into(),
         )
@@ -248,6 +249,16 @@ fn serialize_bitcoin_state_to_tip(
         .address_outpoints
         .persist_and_sync_delta(&layout.address_outpoints())?;
 
+    state
+        .block_index
+        .block_headers
+        .persist_and_sync_delta(&layout.block_headers())?;
+
+    state
+        .block_index
+        .address_history
+        .persist_and_sync_delta(&layout.address_history())?;
+
     layout
         .bitcoin_state()
         .serialize(
@@ -258,6 +269,8 @@ fn serialize_bitcoin_state_to_tip(
                 stable_height: state.stable_height,
                 network: state.utxo_set.network,
                 utxos_large: state.utxo_set.utxos_large.clone(),
+                address_history_enabled: state.block_index.address_history_enabled,
+                outgoing_transactions: state.outgoing_transactions.clone(),
             })
                 .into(),
         )
@@ -293,6 +306,8 @@ fn defrag_tip(
         PageMapType::Bitcoin(BitcoinPageMap::UtxosSmall),
         PageMapType::Bitcoin(BitcoinPageMap::UtxosMedium),
         PageMapType::Bitcoin(BitcoinPageMap::AddressOutpoints),
+        PageMapType::Bitcoin(BitcoinPageMap::BlockHeaders),
+        PageMapType::Bitcoin(BitcoinPageMap::AddressHistory),
     ];
     let path_with_sizes: Vec<(PathBuf, u64)> = bitcoin_files
         .iter()
@@ -552,6 +567,7 @@ pub fn load_canister_state<P: ReadPolicy>(
         canister_metrics,
         canister_state_bits.cycles_balance,
         canister_state_bits.task_queue.into_iter().collect(),
+        canister_state_bits.canister_version,
     );
 
     let canister_state = CanisterState {
@@ -605,6 +621,8 @@ fn load_bitcoin_state<P: ReadPolicy>(
     let utxos_small = load_or_create_pagemap(&layout.utxos_small(), height)?;
     let utxos_medium = load_or_create_pagemap(&layout.utxos_medium(), height)?;
     let address_outpoints = load_or_create_pagemap(&layout.address_outpoints(), height)?;
+    let block_headers = load_or_create_pagemap(&layout.block_headers(), height)?;
+    let address_history = load_or_create_pagemap(&layout.address_history(), height)?;
 
     Ok(BitcoinState {
         adapter_queues: bitcoin_state_bits.adapter_queues,
@@ -618,6 +636,12 @@ fn load_bitcoin_state<P: ReadPolicy>(
             address_outpoints,
         },
         fee_percentiles_cache: None,
+        block_index: BlockIndex {
+            block_headers,
+            address_history,
+            address_history_enabled: bitcoin_state_bits.address_history_enabled,
+        },
+        outgoing_transactions: bitcoin_state_bits.outgoing_transactions,
     })
 }
 
@@ -1220,6 +1244,7 @@ mod tests {
             state.metadata.own_subnet_features.bitcoin = Some(BitcoinFeature {
                 network: BitcoinNetwork::Testnet,
                 status: BitcoinFeatureStatus::Enabled,
+                address_history: false,
             });
 
             // Make some change in the Bitcoin state to later verify that it gets recovered.
@@ -1265,6 +1290,8 @@ mod tests {
             state.bitcoin_mut().utxo_set.utxos_small = PageMap::from(&[1, 2, 3, 4][..]);
             state.bitcoin_mut().utxo_set.utxos_medium = PageMap::from(&[5, 6, 7, 8][..]);
             state.bitcoin_mut().utxo_set.address_outpoints = PageMap::from(&[9, 10, 11, 12][..]);
+            state.bitcoin_mut().block_index.block_headers = PageMap::from(&[13, 14, 15, 16][..]);
+            state.bitcoin_mut().block_index.address_history = PageMap::from(&[17, 18, 19, 20][..]);
 
             let original_state = state.clone();
             let _state = make_checkpoint_and_get_state(&log, &state, HEIGHT, &layout);
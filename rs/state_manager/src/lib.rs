@@ -543,13 +543,19 @@ pub struct StateSyncRefs {
     /// can take chunks from the cache instead of fetching them from other nodes
     /// when possible.
     cache: Arc<parking_lot::RwLock<StateSyncCache>>,
+    /// The height of the state sync interrupted by the last restart, whose
+    /// scratchpad and journal were kept in tmp to resume it. Reset once a
+    /// state sync at this height takes the files over, or once the files are
+    /// removed because they became obsolete.
+    resumable: Arc<parking_lot::Mutex<Option<Height>>>,
 }
 
 impl StateSyncRefs {
-    fn new(log: ReplicaLogger) -> Self {
+    fn new(log: ReplicaLogger, resumable: Option<Height>) -> Self {
         Self {
             active: Arc::new(parking_lot::RwLock::new(BTreeMap::new())),
             cache: Arc::new(parking_lot::RwLock::new(StateSyncCache::new(log))),
+            resumable: Arc::new(parking_lot::Mutex::new(resumable)),
         }
     }
 
@@ -578,6 +584,39 @@ impl StateSyncRefs {
     }
 }
 
+/// CheckpointManifests gives state syncs access to the checkpoints on disk.
+/// A state sync only holds refs to the checkpoints it copies chunks from, and
+/// only while it copies them, so that it doesn't keep checkpoints from being
+/// removed until it completes.
+#[derive(Clone)]
+pub struct CheckpointManifests {
+    states: Arc<parking_lot::RwLock<SharedState>>,
+}
+
+impl CheckpointManifests {
+    /// Returns the heights and manifests of the checkpoints on disk, latest
+    /// first. Checkpoints whose manifest is not computed yet are skipped.
+    fn manifests(&self) -> Vec<(Height, Manifest)> {
+        let states = self.states.read();
+        states
+            .states_metadata
+            .iter()
+            .rev()
+            .filter_map(|(height, metadata)| {
+                metadata.checkpoint_ref.as_ref()?;
+                Some((*height, metadata.manifest.clone()?))
+            })
+            .collect()
+    }
+
+    /// Returns a ref to the checkpoint at `height`, or `None` if the
+    /// checkpoint was removed in the meantime.
+    fn checkpoint_ref(&self, height: Height) -> Option<CheckpointRef> {
+        let states = self.states.read();
+        states.states_metadata.get(&height)?.checkpoint_ref.clone()
+    }
+}
+
 /// SharedState is mutable state that can be accessed from multiple threads.
 struct SharedState {
     /// Certifications metadata kept for all states
@@ -810,6 +849,8 @@ pub enum BitcoinPageMap {
     UtxosSmall,
     UtxosMedium,
     AddressOutpoints,
+    BlockHeaders,
+    AddressHistory,
 }
 
 impl PageMapType {
@@ -826,6 +867,8 @@ impl PageMapType {
         result.push(Self::Bitcoin(BitcoinPageMap::UtxosSmall));
         result.push(Self::Bitcoin(BitcoinPageMap::UtxosMedium));
         result.push(Self::Bitcoin(BitcoinPageMap::AddressOutpoints));
+        result.push(Self::Bitcoin(BitcoinPageMap::BlockHeaders));
+        result.push(Self::Bitcoin(BitcoinPageMap::AddressHistory));
 
         result
     }
@@ -845,6 +888,12 @@ impl PageMapType {
             PageMapType::Bitcoin(BitcoinPageMap::AddressOutpoints) => {
                 Ok(layout.bitcoin()?.address_outpoints())
             }
+            PageMapType::Bitcoin(BitcoinPageMap::BlockHeaders) => {
+                Ok(layout.bitcoin()?.block_headers())
+            }
+            PageMapType::Bitcoin(BitcoinPageMap::AddressHistory) => {
+                Ok(layout.bitcoin()?.address_history())
+            }
         }
     }
 
@@ -870,6 +919,12 @@ impl PageMapType {
             PageMapType::Bitcoin(BitcoinPageMap::AddressOutpoints) => {
                 Some(&state.bitcoin().utxo_set.address_outpoints)
             }
+            PageMapType::Bitcoin(BitcoinPageMap::BlockHeaders) => {
+                Some(&state.bitcoin().block_index.block_headers)
+            }
+            PageMapType::Bitcoin(BitcoinPageMap::AddressHistory) => {
+                Some(&state.bitcoin().block_index.address_history)
+            }
         }
     }
 
@@ -895,6 +950,12 @@ impl PageMapType {
             PageMapType::Bitcoin(BitcoinPageMap::AddressOutpoints) => {
                 Some(&mut state.bitcoin_mut().utxo_set.address_outpoints)
             }
+            PageMapType::Bitcoin(BitcoinPageMap::BlockHeaders) => {
+                Some(&mut state.bitcoin_mut().block_index.block_headers)
+            }
+            PageMapType::Bitcoin(BitcoinPageMap::AddressHistory) => {
+                Some(&mut state.bitcoin_mut().block_index.address_history)
+            }
         }
     }
 }
@@ -1130,8 +1191,8 @@ impl StateManagerImpl {
         );
         let state_layout = StateLayout::new(log.clone(), config.state_root());
 
-        state_layout
-            .remove_tmp()
+        let mut resumable_state_sync = state_layout
+            .remove_tmp_except_resumable_state_sync()
             .unwrap_or_else(|err| fatal!(log, "{:?}", err));
 
         let starting_time = Instant::now();
@@ -1207,6 +1268,29 @@ impl StateManagerImpl {
             starting_time.elapsed()
         );
 
+        // A state sync interrupted by the restart is only worth resuming if
+        // it would bring us past the state we start from.  Its files are the
+        // only ones left in tmp at this point.
+        if let Some(height) = resumable_state_sync {
+            if last_checkpoint.map_or(false, |start| height <= start) {
+                info!(
+                    log,
+                    "Removing interrupted state sync @{} (starting checkpoint = {:?})",
+                    height,
+                    last_checkpoint
+                );
+                state_layout
+                    .remove_tmp()
+                    .unwrap_or_else(|err| fatal!(log, "{:?}", err));
+                resumable_state_sync = None;
+            } else {
+                info!(
+                    log,
+                    "Keeping interrupted state sync @{} to resume it", height
+                );
+            }
+        }
+
         let starting_time = Instant::now();
         state_layout
             .cleanup_tip()
@@ -1403,7 +1487,7 @@ impl StateManagerImpl {
             deallocation_sender,
             latest_state_height,
             latest_certified_height,
-            state_sync_refs: StateSyncRefs::new(log),
+            state_sync_refs: StateSyncRefs::new(log, resumable_state_sync),
             checkpoint_thread_pool,
             _state_hasher_handle,
             _deallocation_handle,
@@ -1425,12 +1509,18 @@ impl StateManagerImpl {
     ) -> Box<dyn Chunkable + Send + Sync> {
         info!(self.log, "Starting state sync @{}", id.height);
 
+        // The files of the interrupted state sync are only kept for a state
+        // sync at the same height. The lock is held until the new state sync
+        // is registered as active, so that its files aren't removed under it.
+        let mut resumable = self.state_sync_refs.resumable.lock();
+        self.remove_obsolete_state_sync(&mut resumable, |height| height != id.height);
+
         Box::new(crate::state_sync::chunkable::IncompleteState::new(
             self.log.clone(),
             id.height,
             id.hash.clone(),
             self.state_layout.clone(),
-            self.latest_manifest(),
+            self.checkpoint_manifests(),
             self.metrics.clone(),
             self.own_subnet_type,
             Arc::clone(&self.checkpoint_thread_pool),
@@ -1438,6 +1528,32 @@ impl StateManagerImpl {
         ))
     }
 
+    /// Removes the scratchpad and the journal of the state sync interrupted by
+    /// the last restart if `is_obsolete` returns true for its height. If a
+    /// state sync at that height is running, the files are left to it, and
+    /// it removes them when it is dropped.
+    fn remove_obsolete_state_sync(
+        &self,
+        resumable: &mut Option<Height>,
+        is_obsolete: impl FnOnce(Height) -> bool,
+    ) {
+        let height = match *resumable {
+            Some(height) if is_obsolete(height) => height,
+            _ => return,
+        };
+        *resumable = None;
+        if self.state_sync_refs.get(&height).is_some() {
+            return;
+        }
+        info!(self.log, "Removing interrupted state sync @{}", height);
+        if let Err(err) = self.state_layout.remove_state_sync_scratchpad(height) {
+            warn!(
+                self.log,
+                "Failed to remove interrupted state sync @{}: {}", height, err
+            );
+        }
+    }
+
     /// Constructs a new ref-counted checkpoint token.
     ///
     /// NOTE: this function should not be called twice with the same value of
@@ -1648,19 +1764,11 @@ impl StateManagerImpl {
         Some((state, certification, hash_tree))
     }
 
-    /// Returns the manifest of the latest checkpoint on disk with its
-    /// checkpoint ref.
-    fn latest_manifest(&self) -> Option<(Manifest, CheckpointRef)> {
-        self.checkpoint_heights()
-            .iter()
-            .rev()
-            .find_map(|checkpointed_height| {
-                let states = self.states.read();
-                let metadata = states.states_metadata.get(checkpointed_height)?;
-                let manifest = metadata.manifest.clone()?;
-                let checkpoint_ref = metadata.checkpoint_ref.clone()?;
-                Some((manifest, checkpoint_ref))
-            })
+    /// Returns the checkpoints on disk that state syncs can copy chunks from.
+    fn checkpoint_manifests(&self) -> CheckpointManifests {
+        CheckpointManifests {
+            states: Arc::clone(&self.states),
+        }
     }
 
     fn compute_certification_metadata(
@@ -2527,6 +2635,13 @@ impl StateManager for StateManagerImpl {
 
         let checkpoint_heights: BTreeSet<Height> = self.checkpoint_heights().drain(..).collect();
 
+        // The interrupted state sync is obsolete once we have a checkpoint at
+        // its height or above.
+        if let Some(latest_checkpoint) = checkpoint_heights.iter().next_back() {
+            let mut resumable = self.state_sync_refs.resumable.lock();
+            self.remove_obsolete_state_sync(&mut resumable, |height| height <= *latest_checkpoint);
+        }
+
         // The latest state must be kept.
         let latest_state_height = self.latest_state_height();
         let last_height_to_keep = latest_state_height.min(requested_height);
@@ -29,8 +29,16 @@ use std::sync::{Arc, Mutex, Weak};
 
 pub const STATE_SYNC_V1: u32 = 1;
 
+/// Chunks of states with this version are sent in the sparse encoding of
+/// `state_sync::chunkable::encoding`.
+pub const STATE_SYNC_V2: u32 = 2;
+
 /// The version of StateSync protocol that should be used for all newly produced
 /// states.
+///
+/// Replicas decode `STATE_SYNC_V2` chunks, but this stays at `STATE_SYNC_V1`
+/// until all replicas run a version that does, so that the encoding is only
+/// used once every peer understands it.
 pub const CURRENT_STATE_SYNC_VERSION: u32 = STATE_SYNC_V1;
 
 pub const DEFAULT_CHUNK_SIZE: u32 = 1 << 20; // 1 MiB.
@@ -941,17 +949,7 @@ pub fn diff_manifest(
     let mut copy_chunks: HashMap<NewIndex, OldIndex> = Default::default();
     let mut fetch_chunks: HashSet<NewIndex> = Default::default();
 
-    let mut hasher = chunk_hasher();
-    let mut bytes_left = DEFAULT_CHUNK_SIZE as i64;
-    let zeros_1kib: [u8; 1024] = [0; 1024];
-
-    while bytes_left > 0 {
-        let n = 1024.min(bytes_left);
-        hasher.write(&zeros_1kib[0..n as usize]);
-        bytes_left -= n;
-    }
-    let zeros_hash = hasher.finish();
-
+    let mut zero_chunks = ZeroChunks::default();
     let mut zeros_chunks: u32 = 0;
 
     let chunk_index_to_file_index = |chunk_index: &usize| {
@@ -998,7 +996,7 @@ pub fn diff_manifest(
 
         // All-zero chunks do not need to be explicitly persisted as pre-allocation
         // already truncates the file to all zeros.
-        if chunk_info.hash == zeros_hash {
+        if zero_chunks.is_all_zeros(chunk_info) {
             zeros_chunks += 1;
             continue;
         }
@@ -1018,25 +1016,70 @@ pub fn diff_manifest(
     }
 }
 
+/// Returns the chunks of `manifest_new` listed in `wanted_chunks` that have the
+/// same content as a chunk of `manifest_old`, which may belong to any file and
+/// be at any offset.
+/// Keys are indices of the chunk table in the new manifest file,
+/// values are indices of the chunk table in the old manifest file.
+pub fn find_reusable_chunks(
+    manifest_old: &Manifest,
+    wanted_chunks: &HashSet<NewIndex>,
+    manifest_new: &Manifest,
+) -> HashMap<NewIndex, OldIndex> {
+    let chunk_hash_to_index: HashMap<[u8; 32], OldIndex> = manifest_old
+        .chunk_table
+        .iter()
+        .enumerate()
+        .map(|(chunk_index, chunk_info)| (chunk_info.hash, chunk_index))
+        .collect();
+
+    wanted_chunks
+        .iter()
+        .filter_map(|chunk_index| {
+            let hash = &manifest_new.chunk_table[*chunk_index].hash;
+            chunk_hash_to_index
+                .get(hash)
+                .map(|old_index| (*chunk_index, *old_index))
+        })
+        .collect()
+}
+
+/// Recognizes all-zero chunks by their hash. The hash of a chunk of zeros is
+/// computed once per chunk size, so that the last chunk of a file, which is
+/// usually smaller than `DEFAULT_CHUNK_SIZE`, is recognized too.
+#[derive(Default)]
+struct ZeroChunks {
+    hashes: HashMap<u32, [u8; 32]>,
+}
+
+impl ZeroChunks {
+    fn is_all_zeros(&mut self, chunk_info: &ChunkInfo) -> bool {
+        let zeros_hash = self.hashes.entry(chunk_info.size_bytes).or_insert_with(|| {
+            let mut hasher = chunk_hasher();
+            let mut bytes_left = chunk_info.size_bytes as i64;
+            let zeros_1kib: [u8; 1024] = [0; 1024];
+
+            while bytes_left > 0 {
+                let n = 1024.min(bytes_left);
+                hasher.write(&zeros_1kib[0..n as usize]);
+                bytes_left -= n;
+            }
+            hasher.finish()
+        });
+        chunk_info.hash == *zeros_hash
+    }
+}
+
 /// Filters out all-zero chunks in the manifest chunk table and returns the set
 /// of remaining chunks indices.
 pub fn filter_out_zero_chunks(manifest: &Manifest) -> HashSet<usize> {
-    let mut hasher = chunk_hasher();
-    let mut bytes_left = DEFAULT_CHUNK_SIZE as i64;
-    let zeros_1kib: [u8; 1024] = [0; 1024];
-
-    while bytes_left > 0 {
-        let n = 1024.min(bytes_left);
-        hasher.write(&zeros_1kib[0..n as usize]);
-        bytes_left -= n;
-    }
-    let zeros_hash = hasher.finish();
+    let mut zero_chunks = ZeroChunks::default();
 
     let fetch_chunks: HashSet<usize> = manifest
         .chunk_table
         .iter()
         .enumerate()
-        .filter(|(_index, chunk_info)| chunk_info.hash != zeros_hash)
+        .filter(|(_index, chunk_info)| !zero_chunks.is_all_zeros(chunk_info))
         .map(|(index, _chunk_info)| index)
         .collect();
     fetch_chunks
@@ -1,7 +1,8 @@
 use super::{
-    compute_manifest, diff_manifest, file_chunk_range, filter_out_zero_chunks, hash::ManifestHash,
-    manifest_hash, validate_chunk, validate_manifest, ChunkValidationError, DiffScript,
-    ManifestValidationError, CURRENT_STATE_SYNC_VERSION, STATE_SYNC_V1,
+    compute_manifest, diff_manifest, file_chunk_range, filter_out_zero_chunks,
+    find_reusable_chunks, hash::ManifestHash, manifest_hash, validate_chunk, validate_manifest,
+    ChunkValidationError, DiffScript, ManifestValidationError, CURRENT_STATE_SYNC_VERSION,
+    STATE_SYNC_V1,
 };
 use crate::ManifestMetrics;
 
@@ -490,7 +491,8 @@ fn test_filter_all_zero_chunks() {
     )
     .expect("failed to compute manifest");
 
-    let fetch_chunks: HashSet<usize> = maplit::hashset! {0, 3, 4, 6};
+    // The last chunk of 'queue' is smaller than the others, but all zeros too.
+    let fetch_chunks: HashSet<usize> = maplit::hashset! {0, 3, 4};
 
     assert_eq!(filter_out_zero_chunks(&manifest), fetch_chunks);
 }
@@ -556,6 +558,28 @@ fn test_missing_simple_manifest() {
     );
 }
 
+#[test]
+fn test_find_reusable_chunks() {
+    let (_, manifest_old) = simple_manifest();
+    let mut manifest_new = manifest_old.clone();
+    // Both chunks of 'subdir/metadata' change, the first one to new content and
+    // the second one to the content of a chunk of 'subdir/memory'.
+    manifest_new.chunk_table[3].hash =
+        hash_concat!(14u8, b"ic-state-chunk", vec![255u8; 1024].as_slice());
+    manifest_new.chunk_table[4].hash = manifest_old.chunk_table[1].hash;
+
+    let reusable = find_reusable_chunks(&manifest_old, &maplit::hashset! {3, 4}, &manifest_new);
+    assert_eq!(reusable.len(), 1);
+    // Chunks 1 and 2 of the old manifest have the same content.
+    assert!(matches!(reusable.get(&4), Some(1) | Some(2)));
+
+    // Only the wanted chunks are looked up.
+    assert_eq!(
+        find_reusable_chunks(&manifest_old, &maplit::hashset! {0}, &manifest_new),
+        maplit::hashmap! {0 => 0}
+    );
+}
+
 #[test]
 fn test_simple_manifest_encoding_roundtrip() {
     let (_hash, manifest) = simple_manifest();
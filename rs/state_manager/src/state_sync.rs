@@ -97,7 +97,7 @@ impl ArtifactClient<StateSyncArtifact> for StateManagerImpl {
                         checkpoint_root: checkpoint_root.raw_path().to_path_buf(),
                         manifest: manifest.clone(),
                         get_state_sync_chunk: Some(
-                            crate::state_sync::chunkable::get_state_sync_chunk,
+                            crate::state_sync::chunkable::state_sync_chunk_getter(manifest),
                         ),
                     })
                 } else {
@@ -147,7 +147,7 @@ impl ArtifactClient<StateSyncArtifact> for StateManagerImpl {
                         checkpoint_root: checkpoint_root.raw_path().to_path_buf(),
                         manifest: manifest.clone(),
                         get_state_sync_chunk: Some(
-                            crate::state_sync::chunkable::get_state_sync_chunk,
+                            crate::state_sync::chunkable::state_sync_chunk_getter(manifest),
                         ),
                     };
                     Some(StateSyncArtifact::message_to_advert(&msg))
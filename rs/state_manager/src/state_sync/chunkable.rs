@@ -1,6 +1,6 @@
 use crate::{
-    manifest::{filter_out_zero_chunks, DiffScript},
-    CheckpointRef, StateManagerMetrics, StateSyncMetrics, StateSyncRefs,
+    manifest::{filter_out_zero_chunks, DiffScript, STATE_SYNC_V2},
+    CheckpointManifests, StateManagerMetrics, StateSyncMetrics, StateSyncRefs,
     CRITICAL_ERROR_STATE_SYNC_CORRUPTED_CHUNKS, LABEL_COPY_CHUNKS, LABEL_COPY_FILES, LABEL_FETCH,
     LABEL_PREALLOCATE,
 };
@@ -20,7 +20,8 @@ use ic_types::{
     state_sync::{decode_manifest, Manifest, MANIFEST_CHUNK},
     CryptoHashOfState, Height,
 };
-use std::os::unix::fs::FileExt;
+use journal::StateSyncJournal;
+use std::os::unix::fs::{FileExt, PermissionsExt};
 use std::path::{Path, PathBuf};
 use std::time::Instant;
 use std::{
@@ -29,6 +30,8 @@ use std::{
 };
 
 pub mod cache;
+mod encoding;
+mod journal;
 
 // If set to true, we validate chunks even in situations where it might not be
 // necessary.
@@ -67,12 +70,19 @@ pub struct IncompleteState {
     height: Height,
     root_hash: CryptoHashOfState,
     state: DownloadState,
-    manifest_with_checkpoint_ref: Option<(Manifest, CheckpointRef)>,
+    /// The local checkpoints that chunks can be copied from. Checkpoint refs
+    /// are only taken while chunks are copied, when the manifest is received.
+    checkpoint_manifests: CheckpointManifests,
     metrics: StateManagerMetrics,
     started_at: Instant,
     own_subnet_type: SubnetType,
     thread_pool: Arc<Mutex<scoped_threadpool::Pool>>,
     state_sync_refs: StateSyncRefs,
+    journal_path: PathBuf,
+    /// The journal of the chunks written to the scratchpad, which lets the
+    /// state sync resume after a restart. `None` before the manifest is
+    /// received, or if the journal couldn't be written.
+    journal: Option<StateSyncJournal>,
 }
 
 impl Drop for IncompleteState {
@@ -131,6 +141,16 @@ impl Drop for IncompleteState {
 
         info!(self.log, "State sync @{} {}", self.height, description);
 
+        match self.state {
+            DownloadState::Blank if *self.state_sync_refs.resumable.lock() == Some(self.height) => {
+                // The scratchpad was left behind by the state sync interrupted
+                // by the last restart and was not touched yet, so keep it to
+                // resume the state sync later.
+                return;
+            }
+            _ => Self::remove_journal(&self.log, &self.journal_path, &mut self.journal),
+        }
+
         // Pass self to the cache, taking ownership of chunks on disk
         let cache = Arc::clone(&self.state_sync_refs.cache);
         cache.write().push(self);
@@ -148,6 +168,28 @@ pub(crate) fn get_state_sync_chunk(
     Ok(buf)
 }
 
+/// Like `get_state_sync_chunk`, but returns the chunk in the sparse encoding.
+pub(crate) fn get_encoded_state_sync_chunk(
+    file_path: PathBuf,
+    offset: u64,
+    len: u32,
+) -> std::io::Result<Vec<u8>> {
+    get_state_sync_chunk(file_path, offset, len).map(encoding::encode_chunk)
+}
+
+/// Returns the function that reads the chunks of the state with the given
+/// manifest. Chunks are only encoded if the manifest version says that the
+/// receivers can decode them.
+pub(crate) fn state_sync_chunk_getter(
+    manifest: &Manifest,
+) -> fn(PathBuf, u64, u32) -> std::io::Result<Vec<u8>> {
+    if manifest.version >= STATE_SYNC_V2 {
+        get_encoded_state_sync_chunk
+    } else {
+        get_state_sync_chunk
+    }
+}
+
 impl IncompleteState {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
@@ -155,7 +197,7 @@ impl IncompleteState {
         height: Height,
         root_hash: CryptoHashOfState,
         state_layout: StateLayout,
-        manifest_with_checkpoint_ref: Option<(Manifest, CheckpointRef)>,
+        checkpoint_manifests: CheckpointManifests,
         metrics: StateManagerMetrics,
         own_subnet_type: SubnetType,
         thread_pool: Arc<Mutex<scoped_threadpool::Pool>>,
@@ -174,22 +216,28 @@ impl IncompleteState {
             root: state_layout
                 .state_sync_scratchpad(height)
                 .expect("failed to create directory for state sync scratchpad"),
+            journal_path: state_layout
+                .state_sync_journal(height)
+                .expect("failed to create directory for state sync journal"),
             state_layout,
             height,
             root_hash,
             state: DownloadState::Blank,
-            manifest_with_checkpoint_ref,
+            checkpoint_manifests,
             metrics,
             started_at: Instant::now(),
             own_subnet_type,
             thread_pool,
             state_sync_refs,
+            journal: None,
         }
     }
 
     /// Creates all the files listed in the manifest and resizes them to their
     /// expected sizes.  This way we won't have to worry about creating parent
-    /// directories when we receive chunks.
+    /// directories when we receive chunks.  Files that already exist keep
+    /// their contents, so that a resumed state sync keeps the chunks written
+    /// before.
     pub(crate) fn preallocate_layout(log: &ReplicaLogger, root: &Path, manifest: &Manifest) {
         for file_info in manifest.file_table.iter() {
             let path = root.join(&file_info.relative_path);
@@ -207,9 +255,29 @@ impl IncompleteState {
                 )
             });
 
-            let f = std::fs::File::create(&path).unwrap_or_else(|err| {
-                fatal!(log, "Failed to create file {}: {}", path.display(), err)
-            });
+            // A resumed state sync may find files that were copied from a
+            // read-only checkpoint.
+            if let Ok(metadata) = std::fs::metadata(&path) {
+                if metadata.permissions().readonly() {
+                    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644))
+                        .unwrap_or_else(|err| {
+                            fatal!(
+                                log,
+                                "Failed to make file {} writable: {}",
+                                path.display(),
+                                err
+                            )
+                        });
+                }
+            }
+
+            let f = std::fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .open(&path)
+                .unwrap_or_else(|err| {
+                    fatal!(log, "Failed to create file {}: {}", path.display(), err)
+                });
             f.set_len(file_info.size_bytes).unwrap_or_else(|err| {
                 fatal!(
                     log,
@@ -731,7 +799,7 @@ impl IncompleteState {
                 .raw_path()
                 .to_path_buf(),
             manifest: manifest.clone(),
-            get_state_sync_chunk: Some(get_state_sync_chunk),
+            get_state_sync_chunk: Some(state_sync_chunk_getter(manifest)),
         })
     }
 
@@ -825,6 +893,156 @@ impl IncompleteState {
         }
     }
 
+    /// Opens the journal left behind by an earlier run of this state sync and
+    /// returns the chunks recorded in it. If there is no such journal, wipes
+    /// the scratchpad and starts a new journal.
+    fn open_journal(&mut self) -> HashSet<usize> {
+        // From now on, the files of the interrupted state sync at this height
+        // belong to this state sync.
+        {
+            let mut resumable = self.state_sync_refs.resumable.lock();
+            if *resumable == Some(self.height) {
+                *resumable = None;
+            }
+        }
+
+        match StateSyncJournal::open(&self.journal_path, &self.root_hash) {
+            Ok(Some((journal, journaled_chunks))) => {
+                info!(
+                    self.log,
+                    "Resuming state sync @{} with {} chunks written before a restart",
+                    self.height,
+                    journaled_chunks.len()
+                );
+                self.journal = Some(journal);
+                return journaled_chunks;
+            }
+            Ok(None) => {}
+            Err(err) => warn!(
+                self.log,
+                "Failed to open state sync journal {}: {}",
+                self.journal_path.display(),
+                err
+            ),
+        }
+
+        // Without a journal nothing is known about the data in the scratchpad.
+        if self.root.exists() {
+            std::fs::remove_dir_all(&self.root).unwrap_or_else(|err| {
+                fatal!(
+                    self.log,
+                    "Failed to remove state sync scratchpad {}: {}",
+                    self.root.display(),
+                    err
+                )
+            });
+        }
+        match StateSyncJournal::create(&self.journal_path, &self.root_hash) {
+            Ok(journal) => self.journal = Some(journal),
+            Err(err) => warn!(
+                self.log,
+                "Failed to create state sync journal {}, the state sync @{} can't be \
+                 resumed after a restart: {}",
+                self.journal_path.display(),
+                self.height,
+                err
+            ),
+        }
+        HashSet::new()
+    }
+
+    /// Validates the chunks that an earlier run of this state sync wrote to
+    /// the scratchpad, according to its journal, and removes the valid ones
+    /// from `fetch_chunks`.
+    fn restore_journaled_chunks(
+        &self,
+        manifest: &Manifest,
+        journaled_chunks: HashSet<usize>,
+        fetch_chunks: &mut HashSet<usize>,
+    ) {
+        // Group chunks by the file index to lower cost of opening files.
+        let mut chunk_groups: HashMap<usize, Vec<usize>> = HashMap::default();
+        for ix in journaled_chunks {
+            // Chunks copied from local checkpoints are not in `fetch_chunks`
+            // anymore, and the ids of the other chunks are known to be valid.
+            if fetch_chunks.contains(&ix) {
+                let file_index = manifest.chunk_table[ix - 1].file_index as usize;
+                chunk_groups.entry(file_index).or_default().push(ix);
+            }
+        }
+        let num_journaled: usize = chunk_groups.values().map(|group| group.len()).sum();
+        if num_journaled == 0 {
+            return;
+        }
+
+        let restored_chunks = Arc::new(Mutex::new(Vec::new()));
+        let log = &self.log;
+        let mut thread_pool = self.thread_pool.lock().unwrap();
+        thread_pool.scoped(|scope| {
+            for (file_index, chunk_group) in chunk_groups.iter() {
+                let path = self
+                    .root
+                    .join(&manifest.file_table[*file_index].relative_path);
+                let restored_chunks = Arc::clone(&restored_chunks);
+                scope.execute(move || {
+                    let f = match std::fs::File::open(&path) {
+                        Ok(f) => f,
+                        Err(err) => {
+                            warn!(log, "Failed to open file {}: {}", path.display(), err);
+                            return;
+                        }
+                    };
+                    for ix in chunk_group {
+                        let chunk = &manifest.chunk_table[ix - 1];
+                        let mut buf = vec![0; chunk.size_bytes as usize];
+                        if f.read_exact_at(&mut buf, chunk.offset).is_ok()
+                            && crate::manifest::validate_chunk(ix - 1, &buf, manifest).is_ok()
+                        {
+                            restored_chunks.lock().unwrap().push(*ix);
+                        }
+                    }
+                });
+            }
+        });
+
+        let restored_chunks = restored_chunks.lock().unwrap();
+        for ix in restored_chunks.iter() {
+            fetch_chunks.remove(ix);
+        }
+        self.metrics
+            .state_sync_metrics
+            .remaining
+            .sub(restored_chunks.len() as i64);
+        info!(
+            self.log,
+            "Restored {} of {} journaled chunks of state {}, {} chunks left to fetch",
+            restored_chunks.len(),
+            num_journaled,
+            self.height,
+            fetch_chunks.len()
+        );
+    }
+
+    /// Closes and removes the journal of a state sync that is completed or
+    /// handed over to the cache.
+    fn remove_journal(
+        log: &ReplicaLogger,
+        journal_path: &Path,
+        journal: &mut Option<StateSyncJournal>,
+    ) {
+        *journal = None;
+        match std::fs::remove_file(journal_path) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => warn!(
+                log,
+                "Failed to remove state sync journal {}: {}",
+                journal_path.display(),
+                err
+            ),
+        }
+    }
+
     /// Preallocates the files listed in the manifest and copies the chunks
     /// that we have locally.
     /// Returns a set of chunks that still need to be fetched
@@ -868,12 +1086,21 @@ impl IncompleteState {
             missing_chunks: HashSet<usize>,
             root_old: PathBuf,
             height_old: Height,
+            from_checkpoint: bool,
             validate_data: bool,
         }
 
+        // The ref to the latest checkpoint keeps it on disk while chunks are
+        // copied from it, and is released once the copying is done.
+        let checkpoint_manifests = self.checkpoint_manifests.manifests();
+        let manifest_with_checkpoint_ref =
+            checkpoint_manifests.first().and_then(|(height, manifest)| {
+                Some((manifest, self.checkpoint_manifests.checkpoint_ref(*height)?))
+            });
+
         // Get a DiffData from the cache or checkpoint_ref, or neither
         let diff_data: Option<DiffData> =
-            match (cache.as_ref(), self.manifest_with_checkpoint_ref.as_ref()) {
+            match (cache.as_ref(), manifest_with_checkpoint_ref.as_ref()) {
                 (Some(cache_entry), Some((checkpoint_manifest, checkpoint_ref))) => {
                     let cache_height = cache_entry.height;
                     let checkpoint_height = checkpoint_ref.0.height;
@@ -890,6 +1117,7 @@ impl IncompleteState {
                             // StateSyncCacheEntry, so cloning the path is safe
                             root_old: cache_entry.path().to_path_buf(),
                             height_old: cache_entry.height,
+                            from_checkpoint: false,
                             validate_data: false,
                         })
                     } else {
@@ -913,6 +1141,7 @@ impl IncompleteState {
                             missing_chunks: Default::default(),
                             root_old: checkpoint_old.raw_path().to_path_buf(),
                             height_old: checkpoint_height,
+                            from_checkpoint: true,
                             validate_data: true,
                         })
                     }
@@ -922,6 +1151,7 @@ impl IncompleteState {
                     missing_chunks: cache_entry.missing_chunks.clone(),
                     root_old: cache_entry.path().to_path_buf(),
                     height_old: cache_entry.height,
+                    from_checkpoint: false,
                     validate_data: false,
                 }),
                 (None, Some((checkpoint_manifest, checkpoint_ref))) => {
@@ -941,10 +1171,11 @@ impl IncompleteState {
                     Some(DiffData {
                         manifest_old: checkpoint_manifest,
                         missing_chunks: Default::default(),
-                        // The data in root_old will live at least as long as self.checkpoint_ref,
+                        // The data in root_old will live at least as long as checkpoint_ref,
                         // so cloning here is safe
                         root_old: checkpoint_old.raw_path().to_path_buf(),
                         height_old: checkpoint_height,
+                        from_checkpoint: true,
                         validate_data: !self
                             .state_sync_refs
                             .cache
@@ -955,11 +1186,12 @@ impl IncompleteState {
                 (None, None) => None,
             };
 
-        if let Some(DiffData {
+        let (mut fetch_chunks, fetch_bytes, checkpoint_used) = if let Some(DiffData {
             manifest_old,
             missing_chunks,
             root_old,
             height_old,
+            from_checkpoint,
             validate_data,
         }) = diff_data
         {
@@ -992,19 +1224,21 @@ impl IncompleteState {
                 .map(|i| manifest_new.chunk_table[*i].size_bytes as u64)
                 .sum();
 
-            let preallocate_bytes: u64 =
-                (diff_script.zeros_chunks * crate::manifest::DEFAULT_CHUNK_SIZE) as u64;
-
             let copy_files_bytes: u64 = diff_script
                 .copy_files
                 .iter()
                 .map(|(i, _)| manifest_new.file_table[*i].size_bytes as u64)
                 .sum();
 
-            let copy_chunks_bytes: u64 =
-                total_bytes - diff_bytes - preallocate_bytes - copy_files_bytes;
+            let copy_chunks_bytes: u64 = diff_script
+                .copy_chunks
+                .keys()
+                .map(|i| manifest_new.chunk_table[*i].size_bytes as u64)
+                .sum();
+
+            let preallocate_bytes: u64 =
+                total_bytes - diff_bytes - copy_files_bytes - copy_chunks_bytes;
 
-            state_sync_size_fetch.inc_by(diff_bytes);
             state_sync_size_preallocate.inc_by(preallocate_bytes);
             state_sync_size_copy_files.inc_by(copy_files_bytes);
             state_sync_size_copy_chunks.inc_by(copy_chunks_bytes);
@@ -1041,7 +1275,15 @@ impl IncompleteState {
                 &mut fetch_chunks,
             );
 
-            fetch_chunks
+            (
+                fetch_chunks,
+                diff_bytes,
+                if from_checkpoint {
+                    Some(height_old)
+                } else {
+                    None
+                },
+            )
         } else {
             info!(
                 self.log,
@@ -1053,7 +1295,6 @@ impl IncompleteState {
                 .iter()
                 .map(|i| manifest_new.chunk_table[*i].size_bytes as u64)
                 .sum();
-            state_sync_size_fetch.inc_by(diff_bytes);
             state_sync_size_preallocate.inc_by(total_bytes - diff_bytes);
 
             let zeros_chunks = manifest_new.chunk_table.len() - non_zero_chunks.len();
@@ -1063,8 +1304,113 @@ impl IncompleteState {
                 .remaining
                 .sub(zeros_chunks as i64);
 
-            non_zero_chunks.iter().map(|i| *i + 1).collect()
+            (
+                non_zero_chunks.iter().map(|i| *i + 1).collect(),
+                diff_bytes,
+                None,
+            )
+        };
+
+        // Chunks that are neither in the cache nor in the latest checkpoint may
+        // still be found in older checkpoints.
+        let copied_bytes = self.copy_chunks_from_checkpoints(
+            manifest_new,
+            &checkpoint_manifests,
+            checkpoint_used,
+            &mut fetch_chunks,
+        );
+        state_sync_size_copy_chunks.inc_by(copied_bytes);
+        state_sync_size_fetch.inc_by(fetch_bytes.saturating_sub(copied_bytes));
+
+        fetch_chunks
+    }
+
+    /// Copies the chunks in `fetch_chunks` that have the same content as a
+    /// chunk of a local checkpoint, at any path and offset, from that
+    /// checkpoint. The checkpoint at `skip_height`, which was already used as
+    /// the base of the sync, is skipped. A checkpoint is only referenced while
+    /// chunks are copied from it. Returns the number of bytes copied.
+    fn copy_chunks_from_checkpoints(
+        &self,
+        manifest_new: &Manifest,
+        checkpoint_manifests: &[(Height, Manifest)],
+        skip_height: Option<Height>,
+        fetch_chunks: &mut HashSet<usize>,
+    ) -> u64 {
+        let mut copied_bytes = 0;
+        for (height_old, manifest_old) in checkpoint_manifests.iter() {
+            let height_old = *height_old;
+            if fetch_chunks.is_empty() {
+                break;
+            }
+            if Some(height_old) == skip_height {
+                continue;
+            }
+
+            // Chunk 0 is the manifest, so the indices into the chunk table are
+            // shifted by 1.
+            let wanted_chunks = fetch_chunks.iter().map(|i| *i - 1).collect();
+            let copy_chunks =
+                crate::manifest::find_reusable_chunks(manifest_old, &wanted_chunks, manifest_new);
+            if copy_chunks.is_empty() {
+                continue;
+            }
+            let checkpoint_ref = match self.checkpoint_manifests.checkpoint_ref(height_old) {
+                Some(checkpoint_ref) => checkpoint_ref,
+                // The checkpoint was removed since its manifest was read.
+                None => continue,
+            };
+
+            let root_old = checkpoint_ref
+                .0
+                .state_layout
+                .checkpoint(height_old)
+                .unwrap_or_else(|err| {
+                    fatal!(
+                        &self.log,
+                        "Failed to get checkpoint path for height {}: {}",
+                        height_old,
+                        err
+                    );
+                });
+            info!(
+                self.log,
+                "Copying {} chunks of state {} from checkpoint at height {}",
+                copy_chunks.len(),
+                self.height,
+                height_old
+            );
+            for new_index in copy_chunks.keys() {
+                fetch_chunks.remove(&(new_index + 1));
+                copied_bytes += manifest_new.chunk_table[*new_index].size_bytes as u64;
+            }
+
+            let diff_script = DiffScript {
+                copy_files: Default::default(),
+                copy_chunks,
+                fetch_chunks: Default::default(),
+                zeros_chunks: 0,
+            };
+            let validate_data = !self
+                .state_sync_refs
+                .cache
+                .read()
+                .state_is_fetched(height_old);
+            let mut thread_pool = self.thread_pool.lock().unwrap();
+            Self::copy_chunks(
+                &self.log,
+                &self.metrics.state_sync_metrics,
+                &mut thread_pool,
+                root_old.raw_path(),
+                &self.root,
+                manifest_old,
+                manifest_new,
+                &diff_script,
+                validate_data,
+                fetch_chunks,
+            );
         }
+        copied_bytes
     }
 }
 
@@ -1142,7 +1488,9 @@ impl Chunkable for IncompleteState {
 
                     trace!(self.log, "Received manifest:\n{}", manifest);
 
-                    let fetch_chunks = self.initialize_state_on_disk(&manifest);
+                    let journaled_chunks = self.open_journal();
+                    let mut fetch_chunks = self.initialize_state_on_disk(&manifest);
+                    self.restore_journaled_chunks(&manifest, journaled_chunks, &mut fetch_chunks);
 
                     if fetch_chunks.is_empty() {
                         debug!(
@@ -1159,6 +1507,7 @@ impl Chunkable for IncompleteState {
                             &self.state_layout,
                             self.own_subnet_type,
                         );
+                        Self::remove_journal(&self.log, &self.journal_path, &mut self.journal);
 
                         let artifact = Self::build_artifact(
                             &self.state_layout,
@@ -1213,7 +1562,22 @@ impl Chunkable for IncompleteState {
 
                 let log = &self.log;
                 let metrics = &self.metrics;
-                crate::manifest::validate_chunk(chunk_table_index, payload, manifest).map_err(
+                let size_bytes = manifest.chunk_table[chunk_table_index].size_bytes as usize;
+                let decoded = if manifest.version >= STATE_SYNC_V2 {
+                    encoding::decode_chunk(payload, size_bytes)
+                } else {
+                    Ok(payload.to_vec())
+                };
+                let payload = decoded.map_err(|err| {
+                    warn!(log, "Failed to decode chunk {}: {}", ix, err);
+                    metrics
+                        .state_sync_metrics
+                        .corrupted_chunks
+                        .with_label_values(&[LABEL_FETCH])
+                        .inc();
+                    ChunkVerificationFailed
+                })?;
+                crate::manifest::validate_chunk(chunk_table_index, &payload, manifest).map_err(
                     |err| {
                         warn!(log, "Received invalid chunk: {}", err);
                         metrics
@@ -1230,9 +1594,25 @@ impl Chunkable for IncompleteState {
                     &self.metrics.state_sync_metrics,
                     &self.root,
                     chunk_table_index,
-                    payload,
+                    &payload,
                     manifest,
                 );
+                let recorded = self
+                    .journal
+                    .as_mut()
+                    .map(|journal| journal.record_chunk(ix));
+                if let Some(Err(err)) = recorded {
+                    warn!(
+                        self.log,
+                        "Failed to record chunk {} in state sync journal {}, the state sync @{} \
+                         can't be resumed after a restart: {}",
+                        ix,
+                        self.journal_path.display(),
+                        self.height,
+                        err
+                    );
+                    Self::remove_journal(&self.log, &self.journal_path, &mut self.journal);
+                }
 
                 fetch_chunks.remove(&ix);
 
@@ -1253,6 +1633,7 @@ impl Chunkable for IncompleteState {
                         &self.state_layout,
                         self.own_subnet_type,
                     );
+                    Self::remove_journal(&self.log, &self.journal_path, &mut self.journal);
 
                     let artifact = Self::build_artifact(
                         &self.state_layout,
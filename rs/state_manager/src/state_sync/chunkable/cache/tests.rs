@@ -66,6 +66,20 @@ fn fake_complete() -> DownloadState {
     DownloadState::Complete(Box::new(artifact))
 }
 
+/// Creates a `CheckpointManifests` without any checkpoints.
+fn no_checkpoints() -> CheckpointManifests {
+    CheckpointManifests {
+        states: Arc::new(parking_lot::RwLock::new(crate::SharedState {
+            certifications_metadata: Default::default(),
+            states_metadata: Default::default(),
+            snapshots: Default::default(),
+            last_advertised: Height::new(0),
+            fetch_state: None,
+            tip: None,
+        })),
+    }
+}
+
 /// Creates an `IncompleteState` at `height` with download state `state`.
 fn incomplete_state_for_tests(
     env: &TestEnvironment,
@@ -76,13 +90,14 @@ fn incomplete_state_for_tests(
     let state_sync_refs = StateSyncRefs {
         active: Arc::new(parking_lot::RwLock::new(Default::default())),
         cache: Arc::clone(&env.cache),
+        resumable: Arc::new(parking_lot::Mutex::new(None)),
     };
     let mut result = IncompleteState::new(
         env.log.clone(),
         height,
         hash,
         env.state_layout.clone(),
-        None,
+        no_checkpoints(),
         env.metrics.clone(),
         SubnetType::Application,
         Arc::new(Mutex::new(scoped_threadpool::Pool::new(NUM_THREADS))),
@@ -0,0 +1,193 @@
+//! A compact wire encoding for state sync chunks.
+//!
+//! Canister memory is often sparse, so chunks tend to contain long runs of
+//! zeros. A chunk with such runs is sent as a sequence of segments, each
+//! consisting of
+//!
+//! ```text
+//! · number of zeros as u32 (little endian)
+//! · number of data bytes as u32 (little endian)
+//! · data bytes
+//! ```
+//!
+//! The encoding is only used if it is shorter than the chunk, so the receiver
+//! can tell an encoded chunk from a raw one by comparing its length with the
+//! chunk size in the manifest.
+
+use std::convert::TryInto;
+
+/// Runs of zeros shorter than this are not worth a segment of their own.
+const MIN_ZERO_RUN: usize = 64;
+
+const SEGMENT_HEADER_SIZE: usize = 8;
+
+/// Returns the bytes to send for a chunk: the encoding of `bytes` if it is
+/// shorter, or else `bytes` itself.
+pub(crate) fn encode_chunk(bytes: Vec<u8>) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let zeros = bytes[pos..].iter().take_while(|b| **b == 0).count();
+        let data_start = pos + zeros;
+        let data_end = next_zero_run(&bytes, data_start);
+
+        encoded.extend_from_slice(&(zeros as u32).to_le_bytes());
+        encoded.extend_from_slice(&((data_end - data_start) as u32).to_le_bytes());
+        encoded.extend_from_slice(&bytes[data_start..data_end]);
+        if encoded.len() >= bytes.len() {
+            return bytes;
+        }
+        pos = data_end;
+    }
+    if bytes.is_empty() {
+        bytes
+    } else {
+        encoded
+    }
+}
+
+/// Returns the start of the first run of at least `MIN_ZERO_RUN` zeros at or
+/// after `from`, or the length of `bytes` if there is none.
+fn next_zero_run(bytes: &[u8], from: usize) -> usize {
+    let mut run = 0;
+    for (i, b) in bytes[from..].iter().enumerate() {
+        if *b == 0 {
+            run += 1;
+            if run == MIN_ZERO_RUN {
+                return from + i + 1 - MIN_ZERO_RUN;
+            }
+        } else {
+            run = 0;
+        }
+    }
+    bytes.len()
+}
+
+/// Decodes a chunk received from a peer, given the chunk size from the
+/// manifest. Chunks that have the expected size are raw and returned as is.
+pub(crate) fn decode_chunk(payload: &[u8], size_bytes: usize) -> Result<Vec<u8>, String> {
+    if payload.len() == size_bytes {
+        return Ok(payload.to_vec());
+    }
+
+    let mut decoded = Vec::with_capacity(size_bytes);
+    let mut rest = payload;
+    while !rest.is_empty() {
+        if rest.len() < SEGMENT_HEADER_SIZE {
+            return Err(format!(
+                "truncated segment header at offset {}",
+                payload.len() - rest.len()
+            ));
+        }
+        let zeros = u32::from_le_bytes(rest[0..4].try_into().unwrap()) as usize;
+        let len = u32::from_le_bytes(rest[4..8].try_into().unwrap()) as usize;
+        rest = &rest[SEGMENT_HEADER_SIZE..];
+
+        if decoded.len() + zeros + len > size_bytes {
+            return Err(format!(
+                "encoded chunk is larger than the expected {} bytes",
+                size_bytes
+            ));
+        }
+        if rest.len() < len {
+            return Err(format!(
+                "segment of {} bytes, but only {} bytes left",
+                len,
+                rest.len()
+            ));
+        }
+        decoded.resize(decoded.len() + zeros, 0);
+        decoded.extend_from_slice(&rest[..len]);
+        rest = &rest[len..];
+    }
+
+    if decoded.len() != size_bytes {
+        return Err(format!(
+            "encoded chunk has {} bytes, expected {}",
+            decoded.len(),
+            size_bytes
+        ));
+    }
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(bytes: Vec<u8>) -> usize {
+        let encoded = encode_chunk(bytes.clone());
+        assert!(encoded.len() <= bytes.len());
+        assert_eq!(decode_chunk(&encoded, bytes.len()).unwrap(), bytes);
+        encoded.len()
+    }
+
+    #[test]
+    fn sparse_chunk_is_encoded_compactly() {
+        let mut bytes = vec![0u8; 1 << 20];
+        bytes[100] = 1;
+        bytes[5000..5100].copy_from_slice(&[7; 100]);
+        bytes[(1 << 20) - 1] = 2;
+        assert!(roundtrip(bytes) < 200);
+
+        assert_eq!(roundtrip(vec![0; 4096]), SEGMENT_HEADER_SIZE);
+    }
+
+    #[test]
+    fn dense_chunk_is_sent_raw() {
+        let bytes: Vec<u8> = (0..4096).map(|i| (i % 255 + 1) as u8).collect();
+        assert_eq!(encode_chunk(bytes.clone()), bytes);
+        assert_eq!(roundtrip(bytes), 4096);
+
+        // Short runs of zeros don't make the chunk compressible.
+        let bytes: Vec<u8> = (0..4096).map(|i| (i % 32) as u8).collect();
+        assert_eq!(roundtrip(bytes), 4096);
+
+        assert_eq!(roundtrip(vec![]), 0);
+    }
+
+    #[test]
+    fn invalid_encodings_are_rejected() {
+        let mut bytes = vec![0u8; 4096];
+        bytes[10] = 1;
+        let encoded = encode_chunk(bytes);
+        assert!(encoded.len() < 4096);
+
+        // Wrong size.
+        assert!(decode_chunk(&encoded, 4095).is_err());
+        assert!(decode_chunk(&encoded, 4097).is_err());
+        // Truncated.
+        assert!(decode_chunk(&encoded[..encoded.len() - 1], 4096).is_err());
+        assert!(decode_chunk(&encoded[..3], 4096).is_err());
+        // A segment that claims more zeros than the chunk has.
+        let mut huge = encoded.clone();
+        huge[0..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(decode_chunk(&huge, 4096).is_err());
+    }
+
+    #[test]
+    fn chunks_are_only_encoded_for_v2_manifests() {
+        use crate::{
+            manifest::{STATE_SYNC_V1, STATE_SYNC_V2},
+            state_sync::chunkable::state_sync_chunk_getter,
+        };
+        use ic_types::state_sync::Manifest;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file");
+        std::fs::write(&path, vec![0u8; 4096]).unwrap();
+
+        let manifest = |version| Manifest {
+            version,
+            file_table: vec![],
+            chunk_table: vec![],
+        };
+        let get_chunk = state_sync_chunk_getter(&manifest(STATE_SYNC_V1));
+        assert_eq!(get_chunk(path.clone(), 0, 4096).unwrap(), vec![0u8; 4096]);
+        let get_chunk = state_sync_chunk_getter(&manifest(STATE_SYNC_V2));
+        assert_eq!(
+            get_chunk(path, 0, 4096).unwrap(),
+            encode_chunk(vec![0u8; 4096])
+        );
+    }
+}
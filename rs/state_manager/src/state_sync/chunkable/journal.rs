@@ -0,0 +1,161 @@
+//! A journal of the chunks written to the scratchpad of a state sync, which
+//! allows resuming the sync after a restart of the replica.
+//!
+//! The journal is a file consisting of
+//!
+//! ```text
+//! · length of the root hash as u32 (little endian)
+//! · root hash of the state being synced
+//! · ids of the chunks written to the scratchpad, each as u32 (little endian)
+//! ```
+//!
+//! Chunk ids are appended after the chunk was written, without syncing the
+//! file, so after a crash the journal may lack some chunks that were written,
+//! end in a partial chunk id, or list chunks whose data didn't make it to
+//! disk. The chunks listed in the journal therefore must be validated again
+//! before the sync is resumed.
+
+use ic_types::CryptoHashOfState;
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+const ID_SIZE: usize = 4;
+
+pub(crate) struct StateSyncJournal {
+    file: File,
+}
+
+impl StateSyncJournal {
+    /// Creates a new journal for the state with the given root hash,
+    /// replacing the file at `path` if it exists.
+    pub(crate) fn create(path: &Path, root_hash: &CryptoHashOfState) -> io::Result<Self> {
+        let hash = &root_hash.get_ref().0;
+        let mut file = File::create(path)?;
+        file.write_all(&(hash.len() as u32).to_le_bytes())?;
+        file.write_all(hash)?;
+        file.sync_all()?;
+        Ok(Self { file })
+    }
+
+    /// Opens the journal at `path` and returns it together with the chunks
+    /// recorded in it. Returns `None` if there is no journal at `path`, or if
+    /// it belongs to a state with a different root hash.
+    pub(crate) fn open(
+        path: &Path,
+        root_hash: &CryptoHashOfState,
+    ) -> io::Result<Option<(Self, HashSet<usize>)>> {
+        let mut file = match OpenOptions::new().read(true).append(true).open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes)?;
+
+        let hash = &root_hash.get_ref().0;
+        let header_len = ID_SIZE + hash.len();
+        if bytes.len() < header_len
+            || bytes[..ID_SIZE] != (hash.len() as u32).to_le_bytes()
+            || bytes[ID_SIZE..header_len] != hash[..]
+        {
+            return Ok(None);
+        }
+
+        let chunks = bytes[header_len..]
+            .chunks_exact(ID_SIZE)
+            .map(|id| u32::from_le_bytes(id.try_into().unwrap()) as usize)
+            .collect();
+        // Drop a partially written id, so that new ids are appended at the
+        // right offset.
+        let complete_len = bytes.len() - (bytes.len() - header_len) % ID_SIZE;
+        file.set_len(complete_len as u64)?;
+        Ok(Some((Self { file }, chunks)))
+    }
+
+    /// Records that chunk `ix` was written to the scratchpad.
+    pub(crate) fn record_chunk(&mut self, ix: usize) -> io::Result<()> {
+        self.file.write_all(&(ix as u32).to_le_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_types::crypto::CryptoHash;
+
+    fn root_hash(byte: u8) -> CryptoHashOfState {
+        CryptoHashOfState::from(CryptoHash(vec![byte; 32]))
+    }
+
+    #[test]
+    fn recorded_chunks_are_returned() {
+        let tmp = tempfile::Builder::new().prefix("test").tempdir().unwrap();
+        let path = tmp.path().join("journal");
+        assert!(StateSyncJournal::open(&path, &root_hash(1))
+            .unwrap()
+            .is_none());
+
+        let mut journal = StateSyncJournal::create(&path, &root_hash(1)).unwrap();
+        journal.record_chunk(3).unwrap();
+        journal.record_chunk(1).unwrap();
+        drop(journal);
+
+        let (mut journal, chunks) = StateSyncJournal::open(&path, &root_hash(1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(chunks, maplit::hashset! {1, 3});
+        journal.record_chunk(7).unwrap();
+        drop(journal);
+
+        let (_, chunks) = StateSyncJournal::open(&path, &root_hash(1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(chunks, maplit::hashset! {1, 3, 7});
+    }
+
+    #[test]
+    fn journal_of_other_state_is_ignored() {
+        let tmp = tempfile::Builder::new().prefix("test").tempdir().unwrap();
+        let path = tmp.path().join("journal");
+        StateSyncJournal::create(&path, &root_hash(1))
+            .unwrap()
+            .record_chunk(1)
+            .unwrap();
+        assert!(StateSyncJournal::open(&path, &root_hash(2))
+            .unwrap()
+            .is_none());
+
+        std::fs::write(&path, [32, 0]).unwrap();
+        assert!(StateSyncJournal::open(&path, &root_hash(1))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn partial_chunk_id_is_dropped() {
+        let tmp = tempfile::Builder::new().prefix("test").tempdir().unwrap();
+        let path = tmp.path().join("journal");
+        StateSyncJournal::create(&path, &root_hash(1))
+            .unwrap()
+            .record_chunk(2)
+            .unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[5, 0]).unwrap();
+        drop(file);
+
+        let (mut journal, chunks) = StateSyncJournal::open(&path, &root_hash(1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(chunks, maplit::hashset! {2});
+        journal.record_chunk(4).unwrap();
+        drop(journal);
+
+        let (_, chunks) = StateSyncJournal::open(&path, &root_hash(1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(chunks, maplit::hashset! {2, 4});
+    }
+}
@@ -1509,7 +1509,7 @@ fn state_sync_message_contains_manifest() {
             .get_validated_by_identifier(&id)
             .expect("failed to get state sync messages");
 
-        // Expecting 6 files, as we don't have canisters in the default state.
+        // Expecting 8 files, as we don't have canisters in the default state.
         //
         // 1. "system_metadata.pbuf"
         // 2. "subnet_queues.pbuf"
@@ -1517,7 +1517,9 @@ fn state_sync_message_contains_manifest() {
         // 4. "bitcoin/testnet/utxos_small.pbuf"
         // 5. "bitcoin/testnet/utxos_medium.pbuf"
         // 6. "bitcoin/testnet/address_outpoints.pbuf"
-        assert_eq!(6, msg.manifest.file_table.len());
+        // 7. "bitcoin/testnet/block_headers.pbuf"
+        // 8. "bitcoin/testnet/address_history.pbuf"
+        assert_eq!(8, msg.manifest.file_table.len());
 
         // Check that all the files are accessible
         for file_info in msg.manifest.file_table.iter() {
@@ -1815,6 +1817,136 @@ fn can_state_sync_from_cache() {
     })
 }
 
+#[test]
+fn can_resume_state_sync_after_restart() {
+    state_manager_test(|src_metrics, src_state_manager| {
+        let (_height, mut state) = src_state_manager.take_tip();
+        insert_dummy_canister(&mut state, canister_test_id(100));
+
+        src_state_manager.commit_and_certify(state, height(1), CertificationScope::Full);
+        let hash = wait_for_checkpoint(&src_state_manager, height(1));
+        let id = StateSyncArtifactId {
+            height: height(1),
+            hash,
+        };
+
+        let state = src_state_manager.get_latest_state().take();
+
+        let msg = src_state_manager
+            .get_validated_by_identifier(&id)
+            .expect("failed to get state sync messages");
+
+        assert_error_counters(src_metrics);
+
+        state_manager_restart_test(|dst_state_manager, restart_fn| {
+            let omit: HashSet<ChunkId> = maplit::hashset! {ChunkId::new(1)};
+
+            let mut chunkable = dst_state_manager.create_chunkable_state(&id);
+            let completion = pipe_partial_state_sync(&msg, &mut *chunkable, &omit);
+            assert!(completion.is_none(), "Unexpectedly completed state sync");
+            // Simulate a crash, which leaves the state sync behind on disk.
+            std::mem::forget(chunkable);
+
+            let dst_state_manager = restart_fn(dst_state_manager, None);
+
+            let mut chunkable = dst_state_manager.create_chunkable_state(&id);
+            let result = pipe_manifest(&msg, &mut *chunkable);
+            assert!(result.is_none());
+
+            // Only the chunks not fetched before the restart should be requested
+            assert_eq!(omit, chunkable.chunks_to_download().collect());
+
+            let dst_msg = pipe_state_sync(msg.clone(), chunkable);
+            dst_state_manager
+                .check_artifact_acceptance(dst_msg, &node_test_id(0))
+                .expect("Failed to process state sync artifact");
+
+            let recovered_state = dst_state_manager
+                .get_state_at(height(1))
+                .expect("Destination state manager didn't receive the state")
+                .take();
+            assert_eq!(state, recovered_state);
+        })
+    })
+}
+
+/// Leaves a state sync @1 behind on disk, as if the replica crashed during the
+/// state sync, and calls `test` with the restarted replica.
+fn interrupted_state_sync_test<Test>(test: Test)
+where
+    Test: FnOnce(StateSyncArtifactId, StateManagerImpl),
+{
+    state_manager_test(|_src_metrics, src_state_manager| {
+        let (_height, mut state) = src_state_manager.take_tip();
+        insert_dummy_canister(&mut state, canister_test_id(100));
+
+        src_state_manager.commit_and_certify(state, height(1), CertificationScope::Full);
+        let hash = wait_for_checkpoint(&src_state_manager, height(1));
+        let id = StateSyncArtifactId {
+            height: height(1),
+            hash,
+        };
+
+        let msg = src_state_manager
+            .get_validated_by_identifier(&id)
+            .expect("failed to get state sync messages");
+
+        state_manager_restart_test(|dst_state_manager, restart_fn| {
+            let omit: HashSet<ChunkId> = maplit::hashset! {ChunkId::new(1)};
+
+            let mut chunkable = dst_state_manager.create_chunkable_state(&id);
+            let completion = pipe_partial_state_sync(&msg, &mut *chunkable, &omit);
+            assert!(completion.is_none(), "Unexpectedly completed state sync");
+            // Simulate a crash, which leaves the state sync behind on disk.
+            std::mem::forget(chunkable);
+
+            let dst_state_manager = restart_fn(dst_state_manager, None);
+            test(id, dst_state_manager);
+        })
+    })
+}
+
+#[test]
+fn removes_interrupted_state_sync_when_syncing_another_height() {
+    interrupted_state_sync_test(|id, dst_state_manager| {
+        let state_layout = dst_state_manager.state_layout();
+        let journal = state_layout.state_sync_journal(height(1)).unwrap();
+        let scratchpad = state_layout.state_sync_scratchpad(height(1)).unwrap();
+
+        // A state sync at the same height that doesn't get any chunks keeps
+        // the files to resume the state sync later.
+        drop(dst_state_manager.create_chunkable_state(&id));
+        assert!(journal.exists());
+        assert!(scratchpad.exists());
+
+        let other_id = StateSyncArtifactId {
+            height: height(2),
+            hash: id.hash,
+        };
+        let _chunkable = dst_state_manager.create_chunkable_state(&other_id);
+        assert!(!journal.exists());
+        assert!(!scratchpad.exists());
+    })
+}
+
+#[test]
+fn removes_interrupted_state_sync_below_checkpoint() {
+    interrupted_state_sync_test(|_id, dst_state_manager| {
+        let state_layout = dst_state_manager.state_layout();
+        let journal = state_layout.state_sync_journal(height(1)).unwrap();
+        let scratchpad = state_layout.state_sync_scratchpad(height(1)).unwrap();
+
+        let (_height, state) = dst_state_manager.take_tip();
+        dst_state_manager.commit_and_certify(state, height(1), CertificationScope::Full);
+        wait_for_checkpoint(&dst_state_manager, height(1));
+        assert!(journal.exists());
+
+        dst_state_manager.remove_states_below(height(1));
+        assert!(!journal.exists());
+        assert!(!scratchpad.exists());
+    })
+}
+
 #[test]
 fn can_state_sync_into_existing_checkpoint() {
     state_manager_test(|src_metrics, src_state_manager| {
@@ -2269,6 +2401,16 @@ fn can_get_dirty_pages() {
                 )),
                 page_delta_indices: vec![PageIndex::new(3), PageIndex::new(300)],
             },
+            DirtyPageMap {
+                height: height(1),
+                file_type: FileType::PageMap(PageMapType::Bitcoin(BitcoinPageMap::BlockHeaders)),
+                page_delta_indices: vec![],
+            },
+            DirtyPageMap {
+                height: height(1),
+                file_type: FileType::PageMap(PageMapType::Bitcoin(BitcoinPageMap::AddressHistory)),
+                page_delta_indices: vec![],
+            },
             DirtyPageMap {
                 height: height(1),
                 file_type: FileType::WasmBinary(canister_test_id(80)),
@@ -2347,6 +2489,16 @@ fn can_get_dirty_pages() {
                 )),
                 page_delta_indices: vec![],
             },
+            DirtyPageMap {
+                height: height(2),
+                file_type: FileType::PageMap(PageMapType::Bitcoin(BitcoinPageMap::BlockHeaders)),
+                page_delta_indices: vec![],
+            },
+            DirtyPageMap {
+                height: height(2),
+                file_type: FileType::PageMap(PageMapType::Bitcoin(BitcoinPageMap::AddressHistory)),
+                page_delta_indices: vec![],
+            },
             DirtyPageMap {
                 height: height(2),
                 file_type: FileType::WasmBinary(canister_test_id(80)),
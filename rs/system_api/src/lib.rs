@@ -1683,6 +1683,57 @@ impl SystemApi for SystemApiImpl {
         result
     }
 
+    fn ic0_is_controller(&self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<u32> {
+        let result = match &self.api_type {
+            ApiType::Start {} => Err(self.error_for("ic0_is_controller")),
+            ApiType::Init { .. }
+            | ApiType::Heartbeat { .. }
+            | ApiType::Update { .. }
+            | ApiType::Cleanup { .. }
+            | ApiType::ReplicatedQuery { .. }
+            | ApiType::NonReplicatedQuery { .. }
+            | ApiType::PreUpgrade { .. }
+            | ApiType::ReplyCallback { .. }
+            | ApiType::RejectCallback { .. }
+            | ApiType::InspectMessage { .. } => {
+                let id_bytes = valid_subslice("ic0.is_controller src", src, size, heap)?;
+                let principal_id =
+                    PrincipalId::try_from(id_bytes).map_err(HypervisorError::InvalidPrincipalId)?;
+                Ok(self
+                    .sandbox_safe_system_state
+                    .controllers
+                    .contains(&principal_id) as u32)
+            }
+        };
+        trace_syscall!(
+            self,
+            ic0_is_controller,
+            result,
+            src,
+            size,
+            summarize(heap, src, size)
+        );
+        result
+    }
+
+    fn ic0_canister_version(&self) -> HypervisorResult<u64> {
+        let result = match &self.api_type {
+            ApiType::Start {} => Err(self.error_for("ic0_canister_version")),
+            ApiType::Init { .. }
+            | ApiType::Heartbeat { .. }
+            | ApiType::Update { .. }
+            | ApiType::Cleanup { .. }
+            | ApiType::ReplicatedQuery { .. }
+            | ApiType::NonReplicatedQuery { .. }
+            | ApiType::PreUpgrade { .. }
+            | ApiType::ReplyCallback { .. }
+            | ApiType::RejectCallback { .. }
+            | ApiType::InspectMessage { .. } => Ok(self.sandbox_safe_system_state.canister_version),
+        };
+        trace_syscall!(self, ic0_canister_version, result);
+        result
+    }
+
     fn ic0_call_simple(
         &mut self,
         callee_src: u32,
@@ -107,7 +107,9 @@ pub(super) fn resolve_destination(
         Ok(Ic00Method::BitcoinGetBalance)
         | Ok(Ic00Method::BitcoinGetUtxos)
         | Ok(Ic00Method::BitcoinSendTransaction)
-        | Ok(Ic00Method::BitcoinGetCurrentFeePercentiles) => {
+        | Ok(Ic00Method::BitcoinGetCurrentFeePercentiles)
+        | Ok(Ic00Method::BitcoinGetAddressHistory)
+        | Ok(Ic00Method::BitcoinGetBlockHeaders) => {
             // TODO(EXC-939): Route requests across all the bitcoin subnets, not only
             // the first subnet.
             Ok(*network_topology
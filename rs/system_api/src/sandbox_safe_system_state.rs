@@ -217,6 +217,8 @@ pub struct SandboxSafeSystemState {
     pub system_state_changes: SystemStateChanges,
     pub(super) canister_id: CanisterId,
     pub(super) controller: PrincipalId,
+    pub(super) controllers: BTreeSet<PrincipalId>,
+    pub(super) canister_version: u64,
     pub(super) status: CanisterStatusView,
     pub(super) subnet_type: SubnetType,
     pub(super) subnet_size: usize,
@@ -252,10 +254,14 @@ impl SandboxSafeSystemState {
         ic00_available_request_slots: usize,
         ic00_aliases: BTreeSet<CanisterId>,
         subnet_size: usize,
+        controllers: BTreeSet<PrincipalId>,
+        canister_version: u64,
     ) -> Self {
         Self {
             canister_id,
             controller,
+            controllers,
+            canister_version,
             status,
             subnet_type: cycles_account_manager.subnet_type(),
             subnet_size,
@@ -325,6 +331,8 @@ impl SandboxSafeSystemState {
             ic00_available_request_slots,
             ic00_aliases,
             subnet_size,
+            system_state.controllers.clone(),
+            system_state.canister_version,
         )
     }
 
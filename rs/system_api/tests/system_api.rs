@@ -89,6 +89,8 @@ fn test_canister_init_support() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
+    assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
@@ -152,6 +154,8 @@ fn test_canister_update_support() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
+    assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
@@ -215,6 +219,8 @@ fn test_canister_replicated_query_support() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
+    assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
@@ -278,6 +284,8 @@ fn test_canister_pure_query_support() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
+    assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
@@ -351,6 +359,8 @@ fn test_canister_stateful_query_support() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
+    assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
@@ -414,6 +424,8 @@ fn test_reply_api_support_on_nns() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
+    assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
@@ -477,6 +489,8 @@ fn test_reply_api_support_non_nns() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
+    assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
@@ -543,6 +557,8 @@ fn test_reject_api_support_on_nns() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
+    assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
@@ -609,6 +625,8 @@ fn test_reject_api_support_non_nns() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
+    assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
@@ -672,6 +690,8 @@ fn test_pre_upgrade_support() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
+    assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
@@ -735,6 +755,8 @@ fn test_start_support() {
     assert_api_not_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_controller_size());
     assert_api_not_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_not_supported(api.ic0_is_controller(0, 0, &[]));
+    assert_api_not_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
@@ -798,6 +820,8 @@ fn test_cleanup_support() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
+    assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
@@ -866,6 +890,8 @@ fn test_inspect_message_support() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
+    assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
@@ -930,6 +956,8 @@ fn test_canister_heartbeat_support() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
+    assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
@@ -993,6 +1021,8 @@ fn test_canister_heartbeat_support_nns() {
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_controller_size());
     assert_api_supported(api.ic0_controller_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_is_controller(0, 0, &[]));
+    assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_supported(api.ic0_call_simple(0, 0, 0, 0, 0, 0, 0, 0, 0, 0, &[]));
@@ -6,7 +6,7 @@ use ic_consensus_message::ConsensusMessageHashable;
 use ic_interfaces::{
     consensus_pool::{
         ChangeAction, ChangeSet, ConsensusBlockCache, ConsensusPool, ConsensusPoolCache,
-        MutableConsensusPool, PoolSection, UnvalidatedConsensusArtifact,
+        ConsensusTimingsRecorder, MutableConsensusPool, PoolSection, UnvalidatedConsensusArtifact,
         ValidatedConsensusArtifact,
     },
     crypto::{MultiSigner, ThresholdSigner},
@@ -668,6 +668,10 @@ impl TestConsensusPool {
     pub fn get_block_cache(&self) -> Arc<dyn ConsensusBlockCache> {
         self.pool.get_block_cache()
     }
+
+    pub fn get_timings_recorder(&self) -> Arc<dyn ConsensusTimingsRecorder> {
+        self.pool.get_timings_recorder()
+    }
 }
 
 impl ConsensusPool for TestConsensusPool {
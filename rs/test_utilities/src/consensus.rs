@@ -9,7 +9,7 @@ use ic_consensus_message::ConsensusMessageHashable;
 use ic_crypto::crypto_hash;
 use ic_interfaces::{
     consensus::*,
-    consensus_pool::{ChangeAction, ChangeSet, ConsensusPool, ConsensusPoolCache},
+    consensus_pool::{ChangeAction, ChangeSet, ConsensusPool, ConsensusPoolCache, HeightTimings},
     registry::RegistryClient,
     validation::*,
 };
@@ -98,6 +98,8 @@ mock! {
         fn cup_with_protobuf(&self) -> CUPWithOriginalProtobuf;
 
         fn get_oldest_registry_version_in_use(&self) -> RegistryVersion;
+
+        fn height_timings(&self) -> Vec<HeightTimings>;
     }
 }
 
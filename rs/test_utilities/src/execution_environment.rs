@@ -4,7 +4,7 @@ use std::sync::Arc;
 use std::{collections::BTreeSet, convert::TryFrom};
 
 use ic_base_types::{NumBytes, NumSeconds, PrincipalId, SubnetId};
-use ic_config::embedders::Config as EmbeddersConfig;
+use ic_config::embedders::{Config as EmbeddersConfig, InstructionCostSchedule};
 use ic_config::execution_environment::Config;
 use ic_config::flag_status::FlagStatus;
 use ic_config::subnet_config::SubnetConfigs;
@@ -260,10 +260,12 @@ impl ExecutionTest {
         &self,
         request_size: NumBytes,
         response_size_limit: Option<NumBytes>,
+        max_redirects: u32,
     ) -> Cycles {
         self.cycles_account_manager.http_request_fee(
             request_size,
             response_size_limit,
+            max_redirects,
             self.subnet_size(),
         )
     }
@@ -1122,6 +1124,18 @@ impl ExecutionTest {
         self.query_handler.query(query, state, data_certificate)
     }
 
+    /// Executes a query call on the given state and returns its instruction
+    /// profile together with the result.
+    pub fn query_with_instruction_profile(
+        &self,
+        query: UserQuery,
+        state: Arc<ReplicatedState>,
+        data_certificate: Vec<u8>,
+    ) -> (Result<WasmResult, UserError>, String) {
+        self.query_handler
+            .query_with_instruction_profile(query, state, data_certificate)
+    }
+
     /// Returns a reference to the query handler of this test.
     ///
     /// Note that the return type is `Any` so that the caller is forced to
@@ -1161,8 +1175,10 @@ pub struct ExecutionTestBuilder {
     manual_execution: bool,
     rate_limiting_of_instructions: bool,
     deterministic_time_slicing: bool,
+    instruction_profiling: bool,
     allocatable_compute_capacity_in_percent: usize,
     subnet_features: String,
+    instruction_cost_schedule: InstructionCostSchedule,
 }
 
 impl Default for ExecutionTestBuilder {
@@ -1196,8 +1212,10 @@ impl Default for ExecutionTestBuilder {
             manual_execution: false,
             rate_limiting_of_instructions: false,
             deterministic_time_slicing: false,
+            instruction_profiling: false,
             allocatable_compute_capacity_in_percent: 100,
             subnet_features: String::default(),
+            instruction_cost_schedule: InstructionCostSchedule::V1,
         }
     }
 }
@@ -1336,6 +1354,13 @@ impl ExecutionTestBuilder {
         }
     }
 
+    pub fn with_instruction_profiling(self) -> Self {
+        Self {
+            instruction_profiling: true,
+            ..self
+        }
+    }
+
     pub fn with_allocatable_compute_capacity_in_percent(
         self,
         allocatable_compute_capacity_in_percent: usize,
@@ -1346,6 +1371,16 @@ impl ExecutionTestBuilder {
         }
     }
 
+    pub fn with_instruction_cost_schedule(
+        self,
+        instruction_cost_schedule: InstructionCostSchedule,
+    ) -> Self {
+        Self {
+            instruction_cost_schedule,
+            ..self
+        }
+    }
+
     pub fn with_provisional_whitelist_all(mut self) -> Self {
         self.registry_settings.provisional_whitelist = ProvisionalWhitelist::All;
         self
@@ -1459,12 +1494,19 @@ impl ExecutionTestBuilder {
         } else {
             FlagStatus::Disabled
         };
+        let instruction_profiling = if self.instruction_profiling {
+            FlagStatus::Enabled
+        } else {
+            FlagStatus::Disabled
+        };
         let config = Config {
             rate_limiting_of_instructions,
             deterministic_time_slicing,
+            instruction_profiling,
             allocatable_compute_capacity_in_percent: self.allocatable_compute_capacity_in_percent,
             subnet_memory_capacity: NumBytes::from(self.subnet_total_memory as u64),
             subnet_message_memory_capacity: NumBytes::from(self.subnet_message_memory as u64),
+            instruction_cost_schedule: self.instruction_cost_schedule,
             ..Config::default()
         };
         let hypervisor = Hypervisor::new(
@@ -15,6 +15,7 @@ pub mod notification;
 pub mod p2p;
 pub mod port_allocation;
 pub mod self_validating_payload_builder;
+pub mod sim_transport;
 pub mod stable_memory_reader;
 pub mod state;
 pub mod state_manager;
@@ -327,6 +327,7 @@ pub fn get_replica_transport_config(
         legacy_flow_tag: 0,
         listening_port: port,
         send_queue_size: 8,
+        send_hello: false,
     }
 }
 
@@ -0,0 +1,681 @@
+//! An in-process simulated network, for deterministic multi-node tests.
+//!
+//! A `SimNetwork` connects the `SimTransport`s of any number of nodes. Sent
+//! messages are not delivered right away, but when the test advances the
+//! network's virtual clock past their delivery time. The delivery time of a
+//! message depends on the latency and bandwidth of the link it is sent on, and
+//! each message may be lost with the loss rate of the link. Nodes can be
+//! partitioned from each other, in which case their connections go down and
+//! the messages in flight between them are lost.
+//!
+//! Events are delivered one at a time, in the order of their delivery time, and
+//! packet loss is decided by a seeded RNG. The same test thus always sees the
+//! same events in the same order.
+//!
+//! Example:
+//!
+//! ```ignore
+//! let network = SimNetwork::new(seed);
+//! let transport_1 = network.add_node(node_1);
+//! let transport_2 = network.add_node(node_2);
+//! network.set_default_link(LinkConfig {
+//!     latency: Duration::from_millis(50),
+//!     ..LinkConfig::default()
+//! });
+//! // Pass the transports and `network.time_source()` to the components under
+//! // test, then drive the network:
+//! network.advance(Duration::from_secs(1)).await;
+//! network.partition(&[&[node_1], &[node_2]]);
+//! network.advance(Duration::from_secs(1)).await;
+//! network.heal();
+//! ```
+use crate::FastForwardTimeSource;
+use ic_interfaces_transport::{
+    FlowTag, Transport, TransportError, TransportEvent, TransportEventHandler, TransportMessage,
+    TransportPayload,
+};
+use ic_types::{time::UNIX_EPOCH, NodeId, RegistryVersion};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaChaRng;
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tower::ServiceExt;
+
+/// The properties of a link, in one direction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkConfig {
+    /// The time it takes a message to reach the other end once it has been
+    /// transmitted.
+    pub latency: Duration,
+    /// The bytes per second that can be transmitted. Messages are transmitted
+    /// one after the other, so a large message delays the ones sent after it.
+    /// `None` means unlimited.
+    pub bandwidth: Option<u64>,
+    /// The probability with which a message is lost, between 0 and 1.
+    pub loss_rate: f64,
+}
+
+impl Default for LinkConfig {
+    fn default() -> Self {
+        Self {
+            latency: Duration::from_millis(0),
+            bandwidth: None,
+            loss_rate: 0.0,
+        }
+    }
+}
+
+/// Counters of the messages sent over a `SimNetwork`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SimNetworkStats {
+    pub sent: u64,
+    pub delivered: u64,
+    /// Messages lost to packet loss or partitions, or because the connection
+    /// was stopped before they were delivered.
+    pub dropped: u64,
+}
+
+/// An in-memory network connecting `SimTransport`s.
+pub struct SimNetwork {
+    inner: Mutex<NetworkState>,
+    time_source: Arc<FastForwardTimeSource>,
+}
+
+/// An event waiting to be delivered to a node.
+struct PendingEvent {
+    to: NodeId,
+    from: NodeId,
+    kind: PendingEventKind,
+}
+
+enum PendingEventKind {
+    PeerUp,
+    PeerDown,
+    Message {
+        payload: TransportPayload,
+        /// The time at which the transmission of the message starts. Until
+        /// then, the message is still in the send queue.
+        transmission_start: Duration,
+        /// The time at which the transmission of the message ends.
+        transmission_end: Duration,
+    },
+}
+
+#[derive(Default)]
+struct NodeState {
+    event_handler: Option<TransportEventHandler>,
+    /// The peers `start_connection()` was called for.
+    peers: BTreeSet<NodeId>,
+}
+
+struct NetworkState {
+    /// The virtual time since the network was created.
+    now: Duration,
+    nodes: BTreeMap<NodeId, NodeState>,
+    default_link: LinkConfig,
+    /// Links that don't use the default config, keyed by (sender, receiver).
+    links: BTreeMap<(NodeId, NodeId), LinkConfig>,
+    /// The time until which each link is busy transmitting messages.
+    busy_until: BTreeMap<(NodeId, NodeId), Duration>,
+    /// The partition each node is in, if the network is partitioned.
+    partition: Option<BTreeMap<NodeId, usize>>,
+    /// The (node, peer) pairs for which the node has seen a `PeerUp` event and
+    /// no `PeerDown` event since.
+    connected: BTreeSet<(NodeId, NodeId)>,
+    /// Pending events, keyed by delivery time and then by the order in which
+    /// they were created.
+    pending: BTreeMap<(Duration, u64), PendingEvent>,
+    next_seq: u64,
+    rng: ChaChaRng,
+    stats: SimNetworkStats,
+}
+
+impl SimNetwork {
+    /// Creates an empty network. The seed determines which messages are lost.
+    pub fn new(seed: u64) -> Arc<Self> {
+        Arc::new(Self {
+            inner: Mutex::new(NetworkState {
+                now: Duration::from_secs(0),
+                nodes: BTreeMap::new(),
+                default_link: LinkConfig::default(),
+                links: BTreeMap::new(),
+                busy_until: BTreeMap::new(),
+                partition: None,
+                connected: BTreeSet::new(),
+                pending: BTreeMap::new(),
+                next_seq: 0,
+                rng: ChaChaRng::seed_from_u64(seed),
+                stats: SimNetworkStats::default(),
+            }),
+            time_source: FastForwardTimeSource::new(),
+        })
+    }
+
+    /// Adds a node to the network and returns its transport.
+    ///
+    /// Panics if the node was added before.
+    pub fn add_node(self: &Arc<Self>, node_id: NodeId) -> Arc<SimTransport> {
+        let mut inner = self.inner.lock().unwrap();
+        assert!(
+            inner.nodes.insert(node_id, NodeState::default()).is_none(),
+            "Node {} was already added",
+            node_id
+        );
+        Arc::new(SimTransport {
+            node_id,
+            network: self.clone(),
+        })
+    }
+
+    /// Returns a time source that follows the virtual clock of the network,
+    /// starting at the UNIX epoch.
+    pub fn time_source(&self) -> Arc<FastForwardTimeSource> {
+        self.time_source.clone()
+    }
+
+    /// Returns the virtual time since the network was created.
+    pub fn now(&self) -> Duration {
+        self.inner.lock().unwrap().now
+    }
+
+    /// Sets the config of all links without a config of their own.
+    pub fn set_default_link(&self, config: LinkConfig) {
+        self.inner.lock().unwrap().default_link = config;
+    }
+
+    /// Sets the config of the link from `from` to `to`. The link in the other
+    /// direction is not affected.
+    pub fn set_link(&self, from: NodeId, to: NodeId, config: LinkConfig) {
+        self.inner.lock().unwrap().links.insert((from, to), config);
+    }
+
+    /// Partitions the network into the given groups of nodes. Nodes can only
+    /// communicate with the nodes in their own group, and nodes that are not
+    /// in any group are isolated. Replaces any previous partition.
+    pub fn partition(&self, groups: &[&[NodeId]]) {
+        let mut inner = self.inner.lock().unwrap();
+        let mut partition = BTreeMap::new();
+        for (i, group) in groups.iter().enumerate() {
+            for node_id in group.iter() {
+                partition.insert(*node_id, i);
+            }
+        }
+        inner.partition = Some(partition);
+        inner.update_connections();
+    }
+
+    /// Removes the partition, so all nodes can communicate again.
+    pub fn heal(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.partition = None;
+        inner.update_connections();
+    }
+
+    /// Returns the counters of the messages sent so far.
+    pub fn stats(&self) -> SimNetworkStats {
+        self.inner.lock().unwrap().stats.clone()
+    }
+
+    /// Advances the virtual clock by `duration`, delivering all events that
+    /// are due until then.
+    pub async fn advance(&self, duration: Duration) {
+        let target = self.now() + duration;
+        self.advance_to(target).await;
+    }
+
+    /// Advances the virtual clock to `target`, delivering all events that are
+    /// due until then. The events are delivered one at a time, and the clock
+    /// is set to the delivery time of each event before it is delivered.
+    /// Events created while delivering are delivered too, if they are due
+    /// before `target`.
+    pub async fn advance_to(&self, target: Duration) {
+        while self.deliver_next(Some(target)).await {}
+        let mut inner = self.inner.lock().unwrap();
+        if inner.now < target {
+            inner.now = target;
+        }
+        self.sync_time_source(inner.now);
+    }
+
+    /// Delivers events until there are none left, advancing the virtual clock
+    /// to the delivery time of the last one.
+    ///
+    /// Does not return if the nodes keep sending each other messages.
+    pub async fn run_until_idle(&self) {
+        while self.deliver_next(None).await {}
+    }
+
+    /// Delivers the next event that is due before `deadline`, if any. Returns
+    /// false if there was no such event.
+    async fn deliver_next(&self, deadline: Option<Duration>) -> bool {
+        let (now, delivery) = {
+            let mut inner = self.inner.lock().unwrap();
+            let key = match inner.pending.keys().next() {
+                Some(key) if deadline.map_or(true, |deadline| key.0 <= deadline) => *key,
+                _ => return false,
+            };
+            let event = inner.pending.remove(&key).unwrap();
+            inner.now = key.0;
+            (key.0, inner.resolve(event))
+        };
+        self.sync_time_source(now);
+        // The lock must not be held while delivering, as the event handler may
+        // send messages.
+        if let Some((event_handler, event)) = delivery {
+            if let Err(err) = event_handler.oneshot(event).await {
+                match err {}
+            }
+        }
+        true
+    }
+
+    fn sync_time_source(&self, now: Duration) {
+        self.time_source
+            .set_time(UNIX_EPOCH + now)
+            .expect("The virtual clock went backwards");
+    }
+}
+
+impl NetworkState {
+    fn link(&self, from: NodeId, to: NodeId) -> &LinkConfig {
+        self.links.get(&(from, to)).unwrap_or(&self.default_link)
+    }
+
+    fn is_partitioned(&self, a: NodeId, b: NodeId) -> bool {
+        match &self.partition {
+            Some(partition) => match (partition.get(&a), partition.get(&b)) {
+                (Some(group_a), Some(group_b)) => group_a != group_b,
+                _ => true,
+            },
+            None => false,
+        }
+    }
+
+    fn push(&mut self, at: Duration, event: PendingEvent) {
+        self.pending.insert((at, self.next_seq), event);
+        self.next_seq += 1;
+    }
+
+    /// Brings the connections in line with the connection requests of the
+    /// nodes and the partition, notifying the nodes of every change.
+    fn update_connections(&mut self) {
+        let mut changes = vec![];
+        for (node_id, node) in self.nodes.iter() {
+            for peer_id in node.peers.iter() {
+                let up = self
+                    .nodes
+                    .get(peer_id)
+                    .map_or(false, |peer| peer.peers.contains(node_id))
+                    && !self.is_partitioned(*node_id, *peer_id);
+                if up != self.connected.contains(&(*node_id, *peer_id)) {
+                    changes.push((*node_id, *peer_id, up));
+                }
+            }
+        }
+        // Nodes that stopped the connection are notified by nobody, but the
+        // connection is down nevertheless.
+        let stopped: Vec<_> = self
+            .connected
+            .iter()
+            .filter(|(node_id, peer_id)| !self.nodes[node_id].peers.contains(peer_id))
+            .cloned()
+            .collect();
+        for pair in stopped {
+            self.connected.remove(&pair);
+        }
+
+        for (node_id, peer_id, up) in changes {
+            let kind = if up {
+                self.connected.insert((node_id, peer_id));
+                PendingEventKind::PeerUp
+            } else {
+                self.connected.remove(&(node_id, peer_id));
+                PendingEventKind::PeerDown
+            };
+            self.push(
+                self.now,
+                PendingEvent {
+                    to: node_id,
+                    from: peer_id,
+                    kind,
+                },
+            );
+        }
+    }
+
+    fn send(&mut self, from: NodeId, to: NodeId, payload: TransportPayload) {
+        self.stats.sent += 1;
+        let link = self.link(from, to).clone();
+        if link.loss_rate > 0.0 && self.rng.gen_bool(link.loss_rate.min(1.0)) {
+            self.stats.dropped += 1;
+            return;
+        }
+
+        let busy_until = self.busy_until.entry((from, to)).or_default();
+        let transmission_start = (*busy_until).max(self.now);
+        let transmission_end = transmission_start
+            + link.bandwidth.map_or(Duration::from_secs(0), |bandwidth| {
+                Duration::from_nanos(
+                    (payload.0.len() as u128 * 1_000_000_000 / bandwidth.max(1) as u128) as u64,
+                )
+            });
+        *busy_until = transmission_end;
+        self.push(
+            transmission_end + link.latency,
+            PendingEvent {
+                to,
+                from,
+                kind: PendingEventKind::Message {
+                    payload,
+                    transmission_start,
+                    transmission_end,
+                },
+            },
+        );
+    }
+
+    /// Drops the messages from `from` to `to` whose transmission has not
+    /// started yet.
+    fn clear_send_queue(&mut self, from: NodeId, to: NodeId) {
+        let now = self.now;
+        let mut cleared = 0;
+        let mut busy_until = now;
+        self.pending.retain(|_, event| match &event.kind {
+            PendingEventKind::Message {
+                transmission_start,
+                transmission_end,
+                ..
+            } if event.from == from && event.to == to => {
+                if *transmission_start > now {
+                    cleared += 1;
+                    false
+                } else {
+                    busy_until = busy_until.max(*transmission_end);
+                    true
+                }
+            }
+            _ => true,
+        });
+        self.busy_until.insert((from, to), busy_until);
+        self.stats.dropped += cleared;
+    }
+
+    /// Returns the event handler to deliver the event to, and the event, or
+    /// `None` if the event is dropped.
+    fn resolve(&mut self, event: PendingEvent) -> Option<(TransportEventHandler, TransportEvent)> {
+        let event_handler = self.nodes[&event.to].event_handler.clone();
+        let transport_event = match event.kind {
+            PendingEventKind::PeerUp => TransportEvent::PeerUp(event.from),
+            PendingEventKind::PeerDown => TransportEvent::PeerDown(event.from),
+            PendingEventKind::Message { payload, .. } => {
+                // Messages in flight are lost when the connection goes down.
+                if !self.connected.contains(&(event.to, event.from)) || event_handler.is_none() {
+                    self.stats.dropped += 1;
+                    return None;
+                }
+                self.stats.delivered += 1;
+                TransportEvent::Message(TransportMessage {
+                    peer_id: event.from,
+                    payload,
+                })
+            }
+        };
+        event_handler.map(|event_handler| (event_handler, transport_event))
+    }
+}
+
+/// The transport of one node of a `SimNetwork`.
+pub struct SimTransport {
+    node_id: NodeId,
+    network: Arc<SimNetwork>,
+}
+
+impl Transport for SimTransport {
+    fn set_event_handler(&self, event_handler: TransportEventHandler) {
+        let mut inner = self.network.inner.lock().unwrap();
+        inner.nodes.get_mut(&self.node_id).unwrap().event_handler = Some(event_handler);
+    }
+
+    fn start_connection(
+        &self,
+        peer_id: &NodeId,
+        _peer_addr: SocketAddr,
+        _registry_version: RegistryVersion,
+    ) -> Result<(), TransportError> {
+        let mut inner = self.network.inner.lock().unwrap();
+        let node = inner.nodes.get_mut(&self.node_id).unwrap();
+        if !node.peers.insert(*peer_id) {
+            return Err(TransportError::AlreadyExists);
+        }
+        inner.update_connections();
+        Ok(())
+    }
+
+    fn stop_connection(&self, peer_id: &NodeId) {
+        let mut inner = self.network.inner.lock().unwrap();
+        let node = inner.nodes.get_mut(&self.node_id).unwrap();
+        if node.peers.remove(peer_id) {
+            inner.clear_send_queue(self.node_id, *peer_id);
+            inner.update_connections();
+        }
+    }
+
+    fn send(
+        &self,
+        peer_id: &NodeId,
+        _flow_tag: FlowTag,
+        message: TransportPayload,
+    ) -> Result<(), TransportError> {
+        let mut inner = self.network.inner.lock().unwrap();
+        if !inner.nodes[&self.node_id].peers.contains(peer_id) {
+            return Err(TransportError::NotFound);
+        }
+        inner.send(self.node_id, *peer_id, message);
+        Ok(())
+    }
+
+    fn clear_send_queues(&self, peer_id: &NodeId) {
+        self.network
+            .inner
+            .lock()
+            .unwrap()
+            .clear_send_queue(self.node_id, *peer_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_interfaces::time_source::TimeSource;
+    use ic_types_test_utils::ids::node_test_id;
+    use tower::util::BoxCloneService;
+
+    /// The events a node received, with the time they were received at.
+    type Received = Arc<Mutex<Vec<(Duration, String)>>>;
+
+    fn add_node(network: &Arc<SimNetwork>, id: u64) -> (Arc<SimTransport>, Received) {
+        let transport = network.add_node(node_test_id(id));
+        let received = Received::default();
+        let (network_clone, received_clone) = (network.clone(), received.clone());
+        transport.set_event_handler(BoxCloneService::new(tower::service_fn(
+            move |event: TransportEvent| {
+                let description = match event {
+                    TransportEvent::PeerUp(peer_id) => format!("up {}", peer_id),
+                    TransportEvent::PeerDown(peer_id) => format!("down {}", peer_id),
+                    TransportEvent::Message(message) => format!("{:?}", message.payload.0),
+                };
+                received_clone
+                    .lock()
+                    .unwrap()
+                    .push((network_clone.now(), description));
+                async { Ok(()) }
+            },
+        )));
+        (transport, received)
+    }
+
+    fn connect(transports: &[&Arc<SimTransport>]) {
+        for transport in transports {
+            for peer in transports {
+                if transport.node_id != peer.node_id {
+                    transport
+                        .start_connection(
+                            &peer.node_id,
+                            "127.0.0.1:0".parse().unwrap(),
+                            RegistryVersion::from(1),
+                        )
+                        .unwrap();
+                }
+            }
+        }
+    }
+
+    fn messages(received: &Received) -> Vec<(Duration, String)> {
+        received
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, description)| description.starts_with('['))
+            .cloned()
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_latency_and_bandwidth() {
+        let network = SimNetwork::new(0);
+        let (transport_1, _) = add_node(&network, 1);
+        let (transport_2, received_2) = add_node(&network, 2);
+        connect(&[&transport_1, &transport_2]);
+        network.set_default_link(LinkConfig {
+            latency: Duration::from_millis(100),
+            bandwidth: Some(1000),
+            loss_rate: 0.0,
+        });
+
+        let flow_tag = FlowTag::from(0);
+        transport_1
+            .send(
+                &transport_2.node_id,
+                flow_tag,
+                TransportPayload(vec![1; 500]),
+            )
+            .unwrap();
+        transport_1
+            .send(&transport_2.node_id, flow_tag, TransportPayload(vec![2]))
+            .unwrap();
+        network.advance(Duration::from_millis(599)).await;
+        assert!(messages(&received_2).is_empty());
+        assert_eq!(
+            network.time_source().get_relative_time(),
+            UNIX_EPOCH + Duration::from_millis(599)
+        );
+
+        network.run_until_idle().await;
+        let received = messages(&received_2);
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].0, Duration::from_millis(600));
+        assert_eq!(received[1], (Duration::from_millis(601), "[2]".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_partition_and_heal() {
+        let network = SimNetwork::new(0);
+        let (transport_1, received_1) = add_node(&network, 1);
+        let (transport_2, received_2) = add_node(&network, 2);
+        connect(&[&transport_1, &transport_2]);
+        network.set_default_link(LinkConfig {
+            latency: Duration::from_secs(1),
+            ..LinkConfig::default()
+        });
+        let flow_tag = FlowTag::from(0);
+
+        // A message in flight when the partition starts is lost.
+        transport_1
+            .send(&transport_2.node_id, flow_tag, TransportPayload(vec![1]))
+            .unwrap();
+        network.partition(&[&[transport_1.node_id], &[transport_2.node_id]]);
+        network.run_until_idle().await;
+        assert!(messages(&received_2).is_empty());
+        let down = format!("down {}", transport_2.node_id);
+        assert_eq!(received_1.lock().unwrap().last().unwrap().1, down);
+
+        network.heal();
+        transport_1
+            .send(&transport_2.node_id, flow_tag, TransportPayload(vec![2]))
+            .unwrap();
+        network.run_until_idle().await;
+        assert_eq!(
+            messages(&received_2),
+            vec![(Duration::from_secs(2), "[2]".to_string())]
+        );
+        assert_eq!(
+            network.stats(),
+            SimNetworkStats {
+                sent: 2,
+                delivered: 1,
+                dropped: 1
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_loss_is_deterministic() {
+        async fn delivered(seed: u64) -> Vec<(Duration, String)> {
+            let network = SimNetwork::new(seed);
+            let (transport_1, _) = add_node(&network, 1);
+            let (transport_2, received_2) = add_node(&network, 2);
+            connect(&[&transport_1, &transport_2]);
+            network.set_link(
+                transport_1.node_id,
+                transport_2.node_id,
+                LinkConfig {
+                    loss_rate: 0.5,
+                    ..LinkConfig::default()
+                },
+            );
+            for i in 0..100 {
+                transport_1
+                    .send(
+                        &transport_2.node_id,
+                        FlowTag::from(0),
+                        TransportPayload(vec![i]),
+                    )
+                    .unwrap();
+            }
+            network.run_until_idle().await;
+            messages(&received_2)
+        }
+
+        let received = delivered(42).await;
+        assert!(!received.is_empty() && received.len() < 100);
+        assert_eq!(received, delivered(42).await);
+    }
+
+    #[tokio::test]
+    async fn test_send_requires_connection() {
+        let network = SimNetwork::new(0);
+        let (transport_1, _) = add_node(&network, 1);
+        let (transport_2, received_2) = add_node(&network, 2);
+        let flow_tag = FlowTag::from(0);
+        assert_eq!(
+            transport_1.send(&transport_2.node_id, flow_tag, TransportPayload(vec![1])),
+            Err(TransportError::NotFound)
+        );
+
+        // Messages are lost until the peer connects too.
+        transport_1
+            .start_connection(
+                &transport_2.node_id,
+                "127.0.0.1:0".parse().unwrap(),
+                RegistryVersion::from(1),
+            )
+            .unwrap();
+        transport_1
+            .send(&transport_2.node_id, flow_tag, TransportPayload(vec![1]))
+            .unwrap();
+        network.run_until_idle().await;
+        assert!(received_2.lock().unwrap().is_empty());
+    }
+}
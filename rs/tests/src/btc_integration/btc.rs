@@ -102,6 +102,7 @@ pub fn config(env: TestEnv) {
                     bitcoin: Some(BitcoinFeature {
                         network: Network::Regtest,
                         status: BitcoinFeatureStatus::Enabled,
+                        address_history: false,
                     }),
                     ..SubnetFeatures::default()
                 })
@@ -65,6 +65,7 @@ async fn test_proxy_canister(proxy_canister: &Canister<'_>, url: String, logger:
                             transform_method_name: Some("transform".to_string()),
                             http_method: HttpMethod::GET,
                             max_response_bytes: None,
+                            max_redirects: None,
                         },
                         cycles: 500_000_000_000,
                     },
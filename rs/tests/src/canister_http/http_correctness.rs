@@ -54,6 +54,7 @@ pub fn test(env: TestEnv) {
                         body: Some("".as_bytes().to_vec()),
                         transform_method_name: Some("transform".to_string()),
                         max_response_bytes: None,
+                        max_redirects: None,
                     },
                     cycles: 500_000_000_000,
                 },
@@ -75,6 +76,7 @@ pub fn test(env: TestEnv) {
                         body: Some("".as_bytes().to_vec()),
                         transform_method_name: Some("transform".to_string()),
                         max_response_bytes: None,
+                        max_redirects: None,
                     },
                     cycles: 0,
                 },
@@ -91,6 +93,7 @@ pub fn test(env: TestEnv) {
             body: Some("".as_bytes().to_vec()),
             transform_method_name: Some("transform".to_string()),
             max_response_bytes: None,
+            max_redirects: None,
         };
         let cycle_cost = 400_000_000
             + 100_000 * (2 * 1024 * 1024 + request.encode().len() + "http_request".len()) as u64;
@@ -129,6 +132,7 @@ pub fn test(env: TestEnv) {
             body: Some("".as_bytes().to_vec()),
             transform_method_name: Some("transform".to_string()),
             max_response_bytes: Some(16384),
+            max_redirects: None,
         };
         let cycle_cost = 400_000_000
             + 100_000
@@ -175,6 +179,7 @@ pub fn test(env: TestEnv) {
                         body: Some("".as_bytes().to_vec()),
                         transform_method_name: Some("transform".to_string()),
                         max_response_bytes: Some(4 * 1024 * 1024),
+                        max_redirects: None,
                     },
                     cycles: 0,
                 },
@@ -196,6 +201,7 @@ pub fn test(env: TestEnv) {
                         body: Some("".as_bytes().to_vec()),
                         transform_method_name: Some("bloat_transform".to_string()),
                         max_response_bytes: None,
+                        max_redirects: None,
                     },
                     cycles: 500_000_000_000,
                 },
@@ -217,6 +223,7 @@ pub fn test(env: TestEnv) {
                         body: Some("".as_bytes().to_vec()),
                         transform_method_name: Some("idontexist".to_string()),
                         max_response_bytes: None,
+                        max_redirects: None,
                     },
                     cycles: 500_000_000_000,
                 },
@@ -241,6 +248,7 @@ pub fn test(env: TestEnv) {
                         body: Some("satoshi=me".as_bytes().to_vec()),
                         transform_method_name: Some("transform".to_string()),
                         max_response_bytes: None,
+                        max_redirects: None,
                     },
                     cycles: 500_000_000_000,
                 },
@@ -262,6 +270,7 @@ pub fn test(env: TestEnv) {
                         body: Some("".as_bytes().to_vec()),
                         transform_method_name: Some("transform".to_string()),
                         max_response_bytes: Some(8 * 1024),
+                        max_redirects: None,
                     },
                     cycles: 500_000_000_000,
                 },
@@ -284,6 +293,7 @@ pub fn test(env: TestEnv) {
                         body: Some("".as_bytes().to_vec()),
                         transform_method_name: Some("transform".to_string()),
                         max_response_bytes: None,
+                        max_redirects: None,
                     },
                     cycles: 500_000_000_000,
                 },
@@ -305,6 +315,7 @@ pub fn test(env: TestEnv) {
                         body: Some("".as_bytes().to_vec()),
                         transform_method_name: Some("transform".to_string()),
                         max_response_bytes: None,
+                        max_redirects: None,
                     },
                     cycles: 500_000_000_000,
                 },
@@ -328,6 +339,7 @@ pub fn test(env: TestEnv) {
                         body: Some("".as_bytes().to_vec()),
                         transform_method_name: Some("transform".to_string()),
                         max_response_bytes: None,
+                        max_redirects: None,
                     },
                     cycles: 500_000_000_000,
                 },
@@ -349,6 +361,7 @@ pub fn test(env: TestEnv) {
                         body: Some("".as_bytes().to_vec()),
                         transform_method_name: Some("transform".to_string()),
                         max_response_bytes: None,
+                        max_redirects: None,
                     },
                     cycles: 500_000_000_000,
                 },
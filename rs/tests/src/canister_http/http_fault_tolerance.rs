@@ -104,6 +104,7 @@ pub fn test(env: TestEnv) {
                             body: Some("".as_bytes().to_vec()),
                             transform_method_name: Some("transform".to_string()),
                             max_response_bytes: None,
+                            max_redirects: None,
                         },
                         cycles: 500_000_000_000,
                     },
@@ -0,0 +1,239 @@
+//! Payload compression - negotiated per connection
+//!
+//! A node advertises the compression codecs it can decode in the hello it may
+//! send right after the TLS handshake (see `TransportHello`). Payloads are
+//! only compressed with a codec both sides support, and never for peers that
+//! did not send a hello, so each direction of a flow is negotiated separately.
+//!
+//! Small payloads, and payloads that don't get smaller when compressed, are
+//! always sent uncompressed. A compressed payload is marked with
+//! `TRANSPORT_FLAGS_IS_COMPRESSED` and starts with its uncompressed length. The
+//! receiver checks the length before decompressing: it must not exceed
+//! `TRANSPORT_MAX_PAYLOAD_SIZE`, nor what the compressed bytes can expand to.
+//!
+//! Compressing and decompressing is CPU bound, so it is done on the blocking
+//! thread pool of the runtime rather than in the read and write tasks.
+
+use crate::types::TRANSPORT_MAX_PAYLOAD_SIZE;
+use ic_interfaces_transport::TransportPayload;
+use std::convert::TryInto;
+use std::io;
+
+/// Codec: LZ4 block format
+pub(crate) const COMPRESSION_CODEC_LZ4: u16 = 1;
+
+/// The codecs this node can decode, advertised to peers
+pub(crate) const SUPPORTED_COMPRESSION_CODECS: u16 = COMPRESSION_CODEC_LZ4;
+
+/// Payloads smaller than this are not worth the CPU time of compressing them
+pub(crate) const MIN_COMPRESSION_SIZE: usize = 4 * 1024;
+
+/// The size of the uncompressed length prefix of a compressed payload
+const LENGTH_PREFIX_SIZE: usize = 4;
+
+/// Each byte of an LZ4 block expands to at most this many bytes
+const LZ4_MAX_EXPANSION: usize = 255;
+
+/// The compression of the payloads sent on a connected flow, as negotiated
+/// with the hello of the peer
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct FlowCompression {
+    /// Whether payloads are compressed with LZ4
+    lz4: bool,
+}
+
+impl FlowCompression {
+    /// Returns the compression to use for a peer that advertised `peer_codecs`
+    pub(crate) fn negotiate(peer_codecs: u16) -> Self {
+        Self {
+            lz4: SUPPORTED_COMPRESSION_CODECS & peer_codecs & COMPRESSION_CODEC_LZ4 != 0,
+        }
+    }
+
+    /// Returns the payloads to send, each with whether it is compressed
+    pub(crate) async fn encode_all(
+        self,
+        payloads: Vec<TransportPayload>,
+    ) -> Vec<(TransportPayload, bool)> {
+        if !self.lz4
+            || payloads
+                .iter()
+                .all(|payload| payload.0.len() < MIN_COMPRESSION_SIZE)
+        {
+            return payloads
+                .into_iter()
+                .map(|payload| (payload, false))
+                .collect();
+        }
+        tokio::task::spawn_blocking(move || {
+            payloads
+                .into_iter()
+                .map(|payload| self.encode(payload))
+                .collect()
+        })
+        .await
+        .expect("Payload compression panicked")
+    }
+
+    /// Returns the payload to send, and whether it is compressed
+    fn encode(&self, payload: TransportPayload) -> (TransportPayload, bool) {
+        if !self.lz4 || payload.0.len() < MIN_COMPRESSION_SIZE {
+            return (payload, false);
+        }
+        match compress(&payload.0) {
+            Some(compressed) if compressed.len() < payload.0.len() => {
+                (TransportPayload(compressed), true)
+            }
+            _ => (payload, false),
+        }
+    }
+}
+
+/// Compresses a payload, prefixed with its uncompressed length
+fn compress(payload: &[u8]) -> Option<Vec<u8>> {
+    let len: u32 = payload.len().try_into().ok()?;
+    let mut compressed = len.to_le_bytes().to_vec();
+    compressed.append(&mut lz4_flex::block::compress(payload));
+    Some(compressed)
+}
+
+/// Decompresses a payload created by `FlowCompression::encode_all()` on the
+/// blocking thread pool
+pub(crate) async fn decompress_payload(payload: TransportPayload) -> io::Result<TransportPayload> {
+    tokio::task::spawn_blocking(move || decompress(&payload.0))
+        .await
+        .expect("Payload decompression panicked")
+}
+
+/// Decompresses a payload created by `FlowCompression::encode_all()`
+fn decompress(payload: &[u8]) -> io::Result<TransportPayload> {
+    if payload.len() < LENGTH_PREFIX_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "compressed payload too short",
+        ));
+    }
+    let (len_bytes, compressed) = payload.split_at(LENGTH_PREFIX_SIZE);
+    let len = u32::from_le_bytes(len_bytes.try_into().unwrap()) as usize;
+    // The length is untrusted and the buffer is allocated up front, so it is
+    // checked first.
+    let max_len = std::cmp::min(
+        TRANSPORT_MAX_PAYLOAD_SIZE,
+        compressed.len().saturating_mul(LZ4_MAX_EXPANSION),
+    );
+    if len > max_len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "decompressed payload would have {} bytes, at most {} are allowed",
+                len, max_len
+            ),
+        ));
+    }
+    lz4_flex::block::decompress(compressed, len)
+        .map(TransportPayload)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", err)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compressible_payload(len: usize) -> TransportPayload {
+        TransportPayload((0..len).map(|i| (i % 16) as u8).collect())
+    }
+
+    fn negotiated() -> FlowCompression {
+        FlowCompression::negotiate(SUPPORTED_COMPRESSION_CODECS)
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let payload = compressible_payload(100 * 1024);
+        let (encoded, compressed) = negotiated().encode(payload.clone());
+        assert!(compressed);
+        assert!(encoded.0.len() < payload.0.len());
+        assert_eq!(decompress(&encoded.0).unwrap(), payload);
+    }
+
+    #[tokio::test]
+    async fn test_encode_all_on_blocking_pool() {
+        let large = compressible_payload(100 * 1024);
+        let small = compressible_payload(16);
+        let encoded = negotiated()
+            .encode_all(vec![large.clone(), small.clone()])
+            .await;
+        assert!(encoded[0].1);
+        assert_eq!(encoded[1], (small, false));
+        assert_eq!(
+            decompress_payload(encoded[0].0.clone()).await.unwrap(),
+            large
+        );
+    }
+
+    #[test]
+    fn test_not_compressed_without_peer_support() {
+        let payload = compressible_payload(100 * 1024);
+        assert_eq!(
+            FlowCompression::default().encode(payload.clone()),
+            (payload.clone(), false)
+        );
+
+        // Peers that don't support any codec advertise none.
+        assert_eq!(
+            FlowCompression::negotiate(0).encode(payload.clone()),
+            (payload, false)
+        );
+    }
+
+    #[test]
+    fn test_small_payload_not_compressed() {
+        let payload = compressible_payload(MIN_COMPRESSION_SIZE - 1);
+        assert_eq!(negotiated().encode(payload.clone()), (payload, false));
+    }
+
+    #[test]
+    fn test_incompressible_payload_not_compressed() {
+        // A sequence of pseudo-random bytes.
+        let mut x: u32 = 1;
+        let payload = TransportPayload(
+            (0..MIN_COMPRESSION_SIZE)
+                .map(|_| {
+                    x = x.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                    (x >> 16) as u8
+                })
+                .collect(),
+        );
+        assert_eq!(negotiated().encode(payload.clone()), (payload, false));
+    }
+
+    #[test]
+    fn test_decompress_rejects_wrong_length() {
+        let payload = compressible_payload(MIN_COMPRESSION_SIZE);
+        let (mut encoded, _) = negotiated().encode(payload);
+        encoded.0[0] = encoded.0[0].wrapping_sub(1);
+        assert!(decompress(&encoded.0).is_err());
+        encoded.0[0] = encoded.0[0].wrapping_add(2);
+        assert!(decompress(&encoded.0).is_err());
+        assert!(decompress(&[1, 2]).is_err());
+    }
+
+    #[test]
+    fn test_decompress_rejects_length_above_maximum() {
+        let payload = compressible_payload(MIN_COMPRESSION_SIZE);
+        let (mut encoded, _) = negotiated().encode(payload);
+        let len = (TRANSPORT_MAX_PAYLOAD_SIZE + 1) as u32;
+        encoded.0[..LENGTH_PREFIX_SIZE].copy_from_slice(&len.to_le_bytes());
+        let err = decompress(&encoded.0).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_decompress_rejects_length_above_expansion() {
+        // 8 compressed bytes can't expand to 1 MiB.
+        let mut encoded = ((1024 * 1024) as u32).to_le_bytes().to_vec();
+        encoded.extend_from_slice(&[0; 8]);
+        let err = decompress(&encoded).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
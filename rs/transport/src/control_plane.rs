@@ -3,13 +3,16 @@
 //! The control plane handles tokio/TLS related details of connection
 //! management. This component establishes/accepts connections to/from subnet
 //! peers. The component also manages re-establishment of severed connections.
+//! Right after the TLS handshake, a node may send a hello that lets the peer
+//! compress the payloads it sends (see `TransportHello`).
 
 use crate::{
+    compression::SUPPORTED_COMPRESSION_CODECS,
     data_plane::create_connected_state,
     metrics::{IntGaugeResource, STATUS_ERROR, STATUS_SUCCESS},
     types::{
         Connecting, ConnectionRole, ConnectionState, FlowState, PeerState, QueueSize,
-        ServerPortState, TransportImpl,
+        ServerPortState, TransportHello, TransportImpl,
     },
     utils::get_flow_label,
 };
@@ -20,6 +23,7 @@ use ic_logger::{error, warn};
 use std::{collections::HashMap, net::SocketAddr, time::Duration};
 use strum::AsRefStr;
 use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
     net::{TcpListener, TcpSocket, TcpStream},
     sync::RwLock,
     task::JoinHandle,
@@ -36,12 +40,22 @@ enum TransportTlsHandshakeError {
     InvalidArgument,
 }
 
+#[derive(Debug, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+enum TransportHelloError {
+    DeadlineExceeded,
+    Io(String),
+}
+
 /// Time to wait before retrying an unsuccessful connection attempt
 const CONNECT_RETRY_SECONDS: u64 = 3;
 
 /// Time to wait for the TLS handshake (for both client/server sides)
 const TLS_HANDSHAKE_TIMEOUT_SECONDS: u64 = 30;
 
+/// Time to wait for sending the hello that follows the TLS handshake
+const HELLO_TIMEOUT_SECONDS: u64 = 10;
+
 const CONNECT_TASK_NAME: &str = "connect";
 const ACCEPT_TASK_NAME: &str = "accept";
 const TRANSITION_FROM_ACCEPT_TASK_NAME: &str = "transition_from_accept";
@@ -168,7 +182,7 @@ impl TransportImpl {
                         rt_handle.spawn(async move {
                             let task_gauge = arc_self.control_plane_metrics.async_tasks.with_label_values(&[TRANSITION_FROM_ACCEPT_TASK_NAME]);
                             let _gauge_guard = IntGaugeResource::new(task_gauge);
-                            let (peer_id, tls_stream) = match arc_self.tls_server_handshake(stream).await {
+                            let (peer_id, mut tls_stream) = match arc_self.tls_server_handshake(stream).await {
                                 Ok((peer_id, tls_stream)) => {
                                     arc_self.control_plane_metrics
                                         .tls_handshakes
@@ -192,6 +206,31 @@ impl TransportImpl {
                                     return;
                                 }
                             };
+                            if arc_self.config.send_hello {
+                                match send_hello(&mut tls_stream).await {
+                                    Ok(()) => {
+                                        arc_self.control_plane_metrics
+                                            .hellos_sent
+                                            .with_label_values(&[ConnectionRole::Server.as_ref(), STATUS_SUCCESS])
+                                            .inc();
+                                    }
+                                    Err(err) => {
+                                        arc_self.control_plane_metrics
+                                            .hellos_sent
+                                            .with_label_values(&[ConnectionRole::Server.as_ref(), err.as_ref()])
+                                            .inc();
+                                        warn!(
+                                            arc_self.log,
+                                            "ControlPlane::spawn_accept_task(): send_hello failed: error = {:?},
+                                            local_addr = {:?}, peer_addr = {:?}",
+                                            err,
+                                            local_addr,
+                                            peer_addr,
+                                        );
+                                        return;
+                                    }
+                                }
+                            }
 
                             let peer_map = arc_self.peer_map.read().await;
                             let peer_state = match peer_map.get(&peer_id) {
@@ -297,7 +336,7 @@ impl TransportImpl {
                             // TODO: P2P-516
                             continue;
                         }
-                        let tls_stream = match arc_self.tls_client_handshake(peer_id, stream).await {
+                        let mut tls_stream = match arc_self.tls_client_handshake(peer_id, stream).await {
                             Ok(tls_stream) => {
                                 arc_self.control_plane_metrics
                                 .tls_handshakes
@@ -321,6 +360,31 @@ impl TransportImpl {
                                 continue;
                             }
                         };
+                        if arc_self.config.send_hello {
+                            match send_hello(&mut tls_stream).await {
+                                Ok(()) => {
+                                    arc_self.control_plane_metrics
+                                        .hellos_sent
+                                        .with_label_values(&[ConnectionRole::Client.as_ref(), STATUS_SUCCESS])
+                                        .inc();
+                                }
+                                Err(err) => {
+                                    arc_self.control_plane_metrics
+                                        .hellos_sent
+                                        .with_label_values(&[ConnectionRole::Client.as_ref(), err.as_ref()])
+                                        .inc();
+                                    warn!(
+                                        arc_self.log,
+                                        "ControlPlane::spawn_connect_task(): send_hello failed: error = {:?},
+                                        local_addr = {:?}, peer_addr = {:?}",
+                                        err,
+                                        local_addr,
+                                        peer_addr,
+                                    );
+                                    continue;
+                                }
+                            }
+                        }
 
                         let mut event_handler = match arc_self.event_handler.lock().await.as_ref() {
                             Some(event_handler) => event_handler.clone(),
@@ -528,6 +592,26 @@ impl TransportImpl {
     }
 }
 
+/// Sends the hello of this node right after the TLS handshake, so that the
+/// peer compresses the payloads it sends
+async fn send_hello<S>(stream: &mut S) -> Result<(), TransportHelloError>
+where
+    S: AsyncWrite + Unpin,
+{
+    let hello = TransportHello {
+        compression_codecs: SUPPORTED_COMPRESSION_CODECS,
+    };
+    let send = async {
+        stream.write_all(&hello.encode()).await?;
+        stream.flush().await
+    };
+    match tokio::time::timeout(Duration::from_secs(HELLO_TIMEOUT_SECONDS), send).await {
+        Err(_) => Err(TransportHelloError::DeadlineExceeded),
+        Ok(Err(err)) => Err(TransportHelloError::Io(format!("{:?}", err))),
+        Ok(Ok(())) => Ok(()),
+    }
+}
+
 /// Set up the client socket, and connect to the specified server peer
 async fn connect_to_server(
     local_addr: SocketAddr,
@@ -570,7 +654,10 @@ fn connection_role(my_id: &NodeId, peer: &NodeId) -> ConnectionRole {
 
 #[cfg(test)]
 mod tests {
+    use super::send_hello;
+    use crate::compression::SUPPORTED_COMPRESSION_CODECS;
     use crate::transport::create_transport;
+    use crate::types::{TransportHello, TRANSPORT_HELLO_SIZE};
     use async_trait::async_trait;
     use ic_base_types::{NodeId, RegistryVersion};
     use ic_config::transport::TransportConfig;
@@ -589,6 +676,7 @@ mod tests {
     use std::str::FromStr;
     use std::sync::Arc;
     use std::task::{Context, Poll};
+    use tokio::io::AsyncReadExt;
     use tokio::sync::mpsc::{channel, Sender};
     use tower::{util::BoxCloneService, Service};
 
@@ -644,6 +732,7 @@ mod tests {
                 legacy_flow_tag: FLOW_TAG_1,
                 listening_port: PORT_1,
                 send_queue_size: 10,
+                send_hello: true,
             };
             let control_plane_1 = create_transport(
                 NODE_ID_1,
@@ -660,6 +749,8 @@ mod tests {
                 legacy_flow_tag: FLOW_TAG_2,
                 listening_port: PORT_2,
                 send_queue_size: 10,
+                // Peers connect whether or not they send a hello
+                send_hello: false,
             };
             let control_plane_2 = create_transport(
                 NODE_ID_2,
@@ -694,6 +785,20 @@ mod tests {
         });
     }
 
+    #[tokio::test]
+    async fn test_send_hello() {
+        let (mut stream_1, mut stream_2) = tokio::io::duplex(64);
+        send_hello(&mut stream_1).await.unwrap();
+        let mut hello = [0u8; TRANSPORT_HELLO_SIZE];
+        stream_2.read_exact(&mut hello).await.unwrap();
+        assert_eq!(
+            TransportHello::decode(&hello),
+            Some(TransportHello {
+                compression_codecs: SUPPORTED_COMPRESSION_CODECS
+            })
+        );
+    }
+
     struct RegistryAndDataProvider {
         data_provider: Arc<ProtoRegistryDataProvider>,
         registry: Arc<FakeRegistryClient>,
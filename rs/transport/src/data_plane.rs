@@ -18,10 +18,12 @@
 //! [`TransportImpl`](../types/struct.TransportImpl.html).
 
 use crate::{
+    compression::{decompress_payload, FlowCompression},
     metrics::{DataPlaneMetrics, IntGaugeResource},
     types::{
-        Connected, ConnectionRole, SendQueueReader, TransportHeader, TransportImpl,
-        TRANSPORT_FLAGS_IS_HEARTBEAT, TRANSPORT_HEADER_SIZE,
+        Connected, ConnectionRole, SendQueueReader, TransportHeader, TransportHello, TransportImpl,
+        TRANSPORT_FLAGS_IS_COMPRESSED, TRANSPORT_FLAGS_IS_HEARTBEAT, TRANSPORT_HEADER_SIZE,
+        TRANSPORT_HELLO_SIZE, TRANSPORT_MAX_PAYLOAD_SIZE,
     },
 };
 use ic_base_types::NodeId;
@@ -33,7 +35,8 @@ use ic_logger::warn;
 use std::convert::TryInto;
 use std::net::SocketAddr;
 use std::sync::Weak;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::sync::watch;
 use tokio::task::JoinHandle;
 use tokio::time::{Duration, Instant};
 use tower::Service;
@@ -67,10 +70,18 @@ const TRANSPORT_HEARTBEAT_WAIT_INTERVAL_MS: u64 = 5000;
 enum ReadError {
     SocketReadFailed(std::io::Error),
     SocketReadTimeOut,
+    PayloadTooLarge(usize),
+}
+
+/// What was read from the socket
+enum Received {
+    /// The hello of the peer, which can only be its first message
+    Hello(TransportHello),
+    Message(TransportHeader, Option<TransportPayload>),
 }
 
 /// Create header bytes to send with payload.
-fn pack_header(payload: Option<&TransportPayload>, heartbeat: bool) -> Vec<u8> {
+fn pack_header(payload: Option<&TransportPayload>, heartbeat: bool, compressed: bool) -> Vec<u8> {
     let mut result = Vec::<u8>::new();
     let mut header = TransportHeader {
         version: 0,
@@ -84,6 +95,9 @@ fn pack_header(payload: Option<&TransportPayload>, heartbeat: bool) -> Vec<u8> {
     if heartbeat {
         header.flags |= TRANSPORT_FLAGS_IS_HEARTBEAT;
     }
+    if compressed {
+        header.flags |= TRANSPORT_FLAGS_IS_COMPRESSED;
+    }
     result.append(&mut header.version.to_le_bytes().to_vec());
     result.append(&mut header.flags.to_le_bytes().to_vec());
     result.append(&mut header.reserved.to_le_bytes().to_vec());
@@ -122,6 +136,7 @@ fn spawn_write_task(
     flow_label: String,
     mut send_queue_reader: Box<dyn SendQueueReader + Send + Sync>,
     mut writer: Box<TlsWriteHalf>,
+    compression: watch::Receiver<FlowCompression>,
     data_plane_metrics: DataPlaneMetrics,
     weak_self: Weak<TransportImpl>,
     rt_handle: tokio::runtime::Handle,
@@ -147,20 +162,35 @@ fn spawn_write_task(
                 let mut to_send = Vec::<u8>::new();
                 if dequeued.is_empty() {
                     // There is nothing to send, so issue a heartbeat message
-                    to_send.append(&mut pack_header(None, true));
+                    to_send.append(&mut pack_header(None, true, false));
                     arc_self
                         .data_plane_metrics
                         .heart_beats_sent
                         .with_label_values(&[&flow_label, &flow_tag_str])
                         .inc();
                 } else {
-                    for mut payload in dequeued {
+                    let input_bytes: usize = dequeued.iter().map(|payload| payload.0.len()).sum();
+                    let mut output_bytes = 0;
+                    let compression = *compression.borrow();
+                    for (mut payload, compressed) in compression.encode_all(dequeued).await {
+                        output_bytes += payload.0.len();
                         to_send.append(&mut pack_header(
                             Some(&payload),
                             false,
+                            compressed,
                         ));
                         to_send.append(&mut payload.0);
                     }
+                    arc_self
+                        .data_plane_metrics
+                        .compression_input_bytes
+                        .with_label_values(&[&flow_label, &flow_tag_str])
+                        .inc_by(input_bytes as u64);
+                    arc_self
+                        .data_plane_metrics
+                        .compression_output_bytes
+                        .with_label_values(&[&flow_label, &flow_tag_str])
+                        .inc_by(output_bytes as u64);
                 }
                 arc_self
                     .data_plane_metrics
@@ -218,6 +248,7 @@ fn spawn_read_task(
     flow_label: String,
     mut event_handler: TransportEventHandler,
     mut reader: Box<TlsReadHalf>,
+    compression: watch::Sender<FlowCompression>,
     data_plane_metrics: DataPlaneMetrics,
     weak_self: Weak<TransportImpl>,
     rt_handle: tokio::runtime::Handle,
@@ -226,6 +257,7 @@ fn spawn_read_task(
     let flow_tag_str = flow_tag.to_string();
     rt_handle.spawn(async move {
             let _raii_gauge = IntGaugeResource::new(data_plane_metrics.read_tasks.clone());
+            let mut first_message = true;
             loop {
                 // If the TransportImpl has been deleted, abort.
                 let arc_self = match weak_self.upgrade() {
@@ -234,7 +266,8 @@ fn spawn_read_task(
                 };
 
                 // Read the next message from the socket
-                let ret = read_one_message(&mut reader, heartbeat_timeout).await;
+                let ret = read_one_message(&mut reader, heartbeat_timeout, first_message).await;
+                first_message = false;
                 if ret.is_err() {
                     warn!(
                         arc_self.log,
@@ -255,7 +288,19 @@ fn spawn_read_task(
                 }
 
                 // Process the received message
-                let (header, payload) = ret.unwrap();
+                let (header, payload) = match ret.unwrap() {
+                    Received::Hello(hello) => {
+                        // The peer can decode compressed payloads from now on
+                        arc_self.data_plane_metrics
+                            .hellos_received
+                            .with_label_values(&[&flow_label, &flow_tag_str])
+                            .inc();
+                        let negotiated = FlowCompression::negotiate(hello.compression_codecs);
+                        let _ = compression.send(negotiated);
+                        continue;
+                    }
+                    Received::Message(header, payload) => (header, payload),
+                };
                 if header.flags & TRANSPORT_FLAGS_IS_HEARTBEAT != 0 {
                     // It's an empty heartbeat message -- do nothing
                     arc_self.data_plane_metrics
@@ -273,6 +318,28 @@ fn spawn_read_task(
                     .socket_read_bytes
                     .with_label_values(&[&flow_label, &flow_tag_str])
                     .inc_by(payload.0.len() as u64);
+                let payload = if header.flags & TRANSPORT_FLAGS_IS_COMPRESSED != 0 {
+                    match decompress_payload(payload).await {
+                        Ok(payload) => payload,
+                        Err(e) => {
+                            warn!(
+                                arc_self.log,
+                                "DataPlane::flow_read_task(): failed to decompress message: peer_id: {:?}, flow_tag: {:?}, {:?}",
+                                peer_id,
+                                flow_tag,
+                                e,
+                            );
+                            arc_self.data_plane_metrics
+                                .decompression_failures
+                                .with_label_values(&[&flow_label, &flow_tag_str])
+                                .inc();
+                            arc_self.on_disconnect(peer_id, flow_tag).await;
+                            return;
+                        }
+                    }
+                } else {
+                    payload
+                };
                 let start_time = Instant::now();
                 event_handler
                     .call(TransportEvent::Message(TransportMessage {
@@ -290,19 +357,33 @@ fn spawn_read_task(
 }
 
 /// Reads and returns the next <message hdr, message payload> from the
-/// socket. The timeout is for each socket read (header, payload chunks)
-/// and not the full message.
-async fn read_one_message(
-    reader: &mut Box<TlsReadHalf>,
+/// socket, or the hello of the peer if `first_message` is set. The timeout is
+/// for each socket read (header, payload chunks) and not the full message.
+async fn read_one_message<R: AsyncRead + Unpin>(
+    reader: &mut R,
     timeout: Duration,
-) -> Result<(TransportHeader, Option<TransportPayload>), ReadError> {
+    first_message: bool,
+) -> Result<Received, ReadError> {
     // Read the hdr
     let mut header_buffer = vec![0u8; TRANSPORT_HEADER_SIZE];
     read_from_socket(reader, &mut header_buffer, timeout).await?;
 
+    // A hello has the size of a header, and its magic bytes never start a
+    // header, so a peer that doesn't send one starts with a header.
+    if first_message {
+        let hello_bytes: &[u8; TRANSPORT_HELLO_SIZE] = header_buffer.as_slice().try_into().unwrap();
+        if let Some(hello) = TransportHello::decode(hello_bytes) {
+            return Ok(Received::Hello(hello));
+        }
+    }
+
     let header = unpack_header(header_buffer);
     if header.flags & TRANSPORT_FLAGS_IS_HEARTBEAT != 0 {
-        return Ok((header, None));
+        return Ok(Received::Message(header, None));
+    }
+
+    if header.payload_length as usize > TRANSPORT_MAX_PAYLOAD_SIZE {
+        return Err(ReadError::PayloadTooLarge(header.payload_length as usize));
     }
 
     // Read the payload in chunks
@@ -324,12 +405,12 @@ async fn read_one_message(
     }
 
     let payload = TransportPayload(payload_buffer);
-    Ok((header, Some(payload)))
+    Ok(Received::Message(header, Some(payload)))
 }
 
 /// Reads the requested bytes from the socket with a timeout
-async fn read_from_socket(
-    reader: &mut Box<TlsReadHalf>,
+async fn read_from_socket<R: AsyncRead + Unpin>(
+    reader: &mut R,
     buf: &mut [u8],
     timeout: Duration,
 ) -> Result<(), ReadError> {
@@ -356,6 +437,8 @@ pub(crate) fn create_connected_state(
     rt_handle: tokio::runtime::Handle,
 ) -> Connected {
     let (tls_reader, tls_writer) = tls_stream.split();
+    // Payloads are sent uncompressed until the peer's hello is received
+    let (compression_sender, compression_receiver) = watch::channel(FlowCompression::default());
     // Spawn write task
     let write_task = spawn_write_task(
         peer_id,
@@ -363,6 +446,7 @@ pub(crate) fn create_connected_state(
         flow_label.clone(),
         send_queue_reader,
         Box::new(tls_writer),
+        compression_receiver,
         data_plane_metrics.clone(),
         weak_self.clone(),
         rt_handle.clone(),
@@ -374,6 +458,7 @@ pub(crate) fn create_connected_state(
         flow_label,
         event_handler,
         Box::new(tls_reader),
+        compression_sender,
         data_plane_metrics,
         weak_self,
         rt_handle,
@@ -386,3 +471,57 @@ pub(crate) fn create_connected_state(
         role,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{read_one_message, Received};
+    use crate::types::{TransportHello, TRANSPORT_FLAGS_IS_HEARTBEAT};
+    use tokio::io::AsyncWriteExt;
+    use tokio::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+    const HEARTBEAT: [u8; 8] = [0, TRANSPORT_FLAGS_IS_HEARTBEAT, 0, 0, 0, 0, 0, 0];
+
+    #[tokio::test]
+    async fn test_read_hello_before_first_message() {
+        let (mut reader, mut writer) = tokio::io::duplex(64);
+        let hello = TransportHello {
+            compression_codecs: 1,
+        };
+        writer.write_all(&hello.encode()).await.unwrap();
+        writer.write_all(&HEARTBEAT).await.unwrap();
+        assert!(matches!(
+            read_one_message(&mut reader, TIMEOUT, true).await,
+            Ok(Received::Hello(received)) if received == hello
+        ));
+        assert!(matches!(
+            read_one_message(&mut reader, TIMEOUT, false).await,
+            Ok(Received::Message(header, None)) if header.flags == TRANSPORT_FLAGS_IS_HEARTBEAT
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_read_first_message_without_hello() {
+        let (mut reader, mut writer) = tokio::io::duplex(64);
+        writer.write_all(&HEARTBEAT).await.unwrap();
+        assert!(matches!(
+            read_one_message(&mut reader, TIMEOUT, true).await,
+            Ok(Received::Message(header, None)) if header.flags == TRANSPORT_FLAGS_IS_HEARTBEAT
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_hello_is_only_accepted_first() {
+        let (mut reader, mut writer) = tokio::io::duplex(64);
+        writer.write_all(&HEARTBEAT).await.unwrap();
+        let hello = TransportHello {
+            compression_codecs: 1,
+        };
+        writer.write_all(&hello.encode()).await.unwrap();
+        read_one_message(&mut reader, TIMEOUT, true).await.unwrap();
+        assert!(matches!(
+            read_one_message(&mut reader, TIMEOUT, false).await,
+            Ok(Received::Message(..))
+        ));
+    }
+}
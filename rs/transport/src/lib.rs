@@ -32,6 +32,7 @@
 //! certification) and state sync. Thus, Transport has to handle 3 x 3 flows per
 //! peer for Gossip.
 
+mod compression;
 mod control_plane;
 mod data_plane;
 mod metrics;
@@ -32,6 +32,7 @@ pub(crate) struct ControlPlaneMetrics {
     pub(crate) tcp_conn_to_server_success: IntCounterVec,
     pub(crate) retry_connection: IntCounterVec,
     pub(crate) tls_handshakes: IntCounterVec,
+    pub(crate) hellos_sent: IntCounterVec,
     pub(crate) async_tasks: IntGaugeVec,
 }
 
@@ -78,6 +79,11 @@ impl ControlPlaneMetrics {
                 "TLS handshakes in Transport",
                 &["role", "status"],
             ),
+            hellos_sent: metrics_registry.int_counter_vec(
+                "transport_hellos_sent_total",
+                "Hellos sent after the TLS handshakes in Transport",
+                &["role", "status"],
+            ),
         }
     }
 }
@@ -92,9 +98,13 @@ pub(crate) struct DataPlaneMetrics {
     pub(crate) socket_heart_beat_timeouts: IntCounterVec,
     pub(crate) heart_beats_sent: IntCounterVec,
     pub(crate) heart_beats_received: IntCounterVec,
+    pub(crate) hellos_received: IntCounterVec,
     pub(crate) write_tasks: IntGauge,
     pub(crate) read_tasks: IntGauge,
     pub(crate) write_task_overhead_time_msec: HistogramVec,
+    pub(crate) compression_input_bytes: IntCounterVec,
+    pub(crate) compression_output_bytes: IntCounterVec,
+    pub(crate) decompression_failures: IntCounterVec,
 }
 
 impl DataPlaneMetrics {
@@ -159,6 +169,11 @@ impl DataPlaneMetrics {
                 "Number of heart beats as seen by receiver",
                 &["flow_peer_id", "flow_tag"],
             ),
+            hellos_received: metrics_registry.int_counter_vec(
+                "transport_hellos_received",
+                "Number of hellos received from peers",
+                &["flow_peer_id", "flow_tag"],
+            ),
             heart_beats_sent: metrics_registry.int_counter_vec(
                 "transport_heart_beats_sent",
                 "Number of heart beats sent by sender",
@@ -181,6 +196,21 @@ impl DataPlaneMetrics {
                 &["flow_peer_id", "flow_tag"],
             )
             .unwrap(),
+            compression_input_bytes: metrics_registry.int_counter_vec(
+                "transport_compression_input_bytes",
+                "Payload bytes to send, before compression",
+                &["flow_peer_id", "flow_tag"],
+            ),
+            compression_output_bytes: metrics_registry.int_counter_vec(
+                "transport_compression_output_bytes",
+                "Payload bytes to send, after compression",
+                &["flow_peer_id", "flow_tag"],
+            ),
+            decompression_failures: metrics_registry.int_counter_vec(
+                "transport_decompression_failures",
+                "Received payloads that could not be decompressed",
+                &["flow_peer_id", "flow_tag"],
+            ),
         }
     }
 }
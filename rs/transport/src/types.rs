@@ -32,6 +32,10 @@ pub type QueueSize = AmountOf<QueueSizeTag, usize>;
 /// The size (in bytes) of the transport header
 pub const TRANSPORT_HEADER_SIZE: usize = 8;
 
+/// The maximum size (in bytes) of a payload received from a peer, before or
+/// after decompression
+pub const TRANSPORT_MAX_PAYLOAD_SIZE: usize = 128 * 1024 * 1024;
+
 /// Flag: message is a heartbeat
 ///
 /// When a message has this flag on, it means that the message contains no
@@ -39,6 +43,22 @@ pub const TRANSPORT_HEADER_SIZE: usize = 8;
 /// connection alive.
 pub const TRANSPORT_FLAGS_IS_HEARTBEAT: u8 = 2;
 
+/// Flag: payload is compressed
+///
+/// When a message has this flag on, its payload is compressed with a codec
+/// that the receiver advertised in its hello. See the `compression` module.
+pub const TRANSPORT_FLAGS_IS_COMPRESSED: u8 = 4;
+
+/// The size (in bytes) of the transport hello
+pub const TRANSPORT_HELLO_SIZE: usize = 8;
+
+/// Identifies the transport hello
+const TRANSPORT_HELLO_MAGIC: [u8; 4] = *b"ICTP";
+
+/// The version of the transport hello sent by this node. Hellos of newer
+/// versions are accepted too.
+const TRANSPORT_HELLO_VERSION: u16 = 1;
+
 /// The transport header format.
 ///
 /// A message is sent on the wire as two writes:
@@ -67,6 +87,43 @@ pub(crate) struct TransportHeader {
     pub(crate) payload_length: u32, // Serialized little endian.
 }
 
+/// The message a node sends right after the TLS handshake, before any
+/// transport header, if `TransportConfig::send_hello` is enabled. Peers only
+/// compress the payloads they send to nodes that sent a hello.
+///
+/// It is serialized as the magic bytes `ICTP`, followed by the version and
+/// the compression codecs, both little endian. Newer versions must keep this
+/// layout and may only add codecs, so that older nodes can still decode them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct TransportHello {
+    /// The compression codecs the sender can decode: defined by the
+    /// constants named `COMPRESSION_CODEC_*` in the `compression` module
+    pub(crate) compression_codecs: u16,
+}
+
+impl TransportHello {
+    /// Serializes the hello
+    pub(crate) fn encode(&self) -> [u8; TRANSPORT_HELLO_SIZE] {
+        let mut bytes = [0; TRANSPORT_HELLO_SIZE];
+        bytes[..4].copy_from_slice(&TRANSPORT_HELLO_MAGIC);
+        bytes[4..6].copy_from_slice(&TRANSPORT_HELLO_VERSION.to_le_bytes());
+        bytes[6..].copy_from_slice(&self.compression_codecs.to_le_bytes());
+        bytes
+    }
+
+    /// Deserializes a hello, or returns `None` if the bytes are not a hello
+    pub(crate) fn decode(bytes: &[u8; TRANSPORT_HELLO_SIZE]) -> Option<Self> {
+        if bytes[..4] != TRANSPORT_HELLO_MAGIC
+            || u16::from_le_bytes([bytes[4], bytes[5]]) < TRANSPORT_HELLO_VERSION
+        {
+            return None;
+        }
+        Some(Self {
+            compression_codecs: u16::from_le_bytes([bytes[6], bytes[7]]),
+        })
+    }
+}
+
 /// Transport implementation state struct. The control and data planes provide
 /// implementations for this struct.
 pub(crate) struct TransportImpl {
@@ -453,4 +510,42 @@ mod tests {
         ];
         verify_state_transitions(state, expected);
     }
+
+    #[test]
+    fn test_transport_hello_roundtrip() {
+        let hello = TransportHello {
+            compression_codecs: 0x0102,
+        };
+        let bytes = hello.encode();
+        assert_eq!(&bytes[..4], b"ICTP");
+        assert_eq!(TransportHello::decode(&bytes), Some(hello));
+    }
+
+    #[test]
+    fn test_transport_hello_accepts_newer_versions() {
+        let mut bytes = TransportHello {
+            compression_codecs: 0x0103,
+        }
+        .encode();
+        bytes[4..6].copy_from_slice(&2_u16.to_le_bytes());
+        assert_eq!(
+            TransportHello::decode(&bytes),
+            Some(TransportHello {
+                compression_codecs: 0x0103
+            })
+        );
+    }
+
+    #[test]
+    fn test_transport_hello_rejects_other_messages() {
+        let mut bytes = TransportHello {
+            compression_codecs: 1,
+        }
+        .encode();
+        bytes[4..6].copy_from_slice(&0_u16.to_le_bytes());
+        assert_eq!(TransportHello::decode(&bytes), None);
+        // A transport header, as sent by a peer that doesn't send a hello.
+        let header = [0, 2, 0, 0, 0, 0, 0, 0];
+        assert_eq!(TransportHello::decode(&header), None);
+    }
 }
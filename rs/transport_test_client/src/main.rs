@@ -438,6 +438,7 @@ fn generate_config_and_registry(node_id: &NodeId) -> ConfigAndPeerSockets {
                 legacy_flow_tag: FLOW_TAG,
                 listening_port: n.2,
                 send_queue_size: 1024,
+                send_hello: false,
             });
         }
 
@@ -6,8 +6,9 @@ use serde::Serialize;
 /// `(http_request : (record {
 //     url : text;
 //     max_response_bytes: opt nat64;
+//     max_redirects: opt nat32;
 //     headers : vec http_header;
-//     method : variant { get };
+//     method : variant { get; post; head; put; patch; delete };
 //     body : opt blob;
 //     transform : opt variant { function: func (http_response) -> (http_response) query };
 //   })`
@@ -15,6 +16,8 @@ use serde::Serialize;
 pub struct CanisterHttpRequestArgs {
     pub url: String,
     pub max_response_bytes: Option<u64>,
+    /// Maximum number of redirects to follow. Redirects are not followed if unset.
+    pub max_redirects: Option<u32>,
     pub headers: Vec<HttpHeader>,
     pub body: Option<Vec<u8>>,
     pub http_method: HttpMethod,
@@ -41,6 +44,9 @@ pub enum HttpMethod {
     GET,
     POST,
     HEAD,
+    PUT,
+    PATCH,
+    DELETE,
 }
 
 /// Represents the response for a canister http request.
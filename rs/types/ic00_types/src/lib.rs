@@ -45,6 +45,8 @@ pub enum Method {
     BitcoinGetUtxos,
     BitcoinSendTransaction,
     BitcoinGetCurrentFeePercentiles,
+    BitcoinGetAddressHistory,
+    BitcoinGetBlockHeaders,
 
     // These methods are added for the Mercury I release.
     // They should be removed afterwards.
@@ -968,7 +970,9 @@ impl ComputeInitialEcdsaDealingsResponse {
 
 // Export the bitcoin types.
 pub use ic_btc_types::{
+    GetAddressHistoryRequest as BitcoinGetAddressHistoryArgs,
     GetBalanceRequest as BitcoinGetBalanceArgs,
+    GetBlockHeadersRequest as BitcoinGetBlockHeadersArgs,
     GetCurrentFeePercentilesRequest as BitcoinGetCurrentFeePercentilesArgs,
     GetUtxosRequest as BitcoinGetUtxosArgs, Network as BitcoinNetwork,
     SendTransactionRequest as BitcoinSendTransactionArgs,
@@ -978,3 +982,5 @@ impl Payload<'_> for BitcoinGetBalanceArgs {}
 impl Payload<'_> for BitcoinGetUtxosArgs {}
 impl Payload<'_> for BitcoinSendTransactionArgs {}
 impl Payload<'_> for BitcoinGetCurrentFeePercentilesArgs {}
+impl Payload<'_> for BitcoinGetAddressHistoryArgs {}
+impl Payload<'_> for BitcoinGetBlockHeadersArgs {}
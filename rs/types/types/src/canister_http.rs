@@ -41,6 +41,9 @@ use std::{
 // TODO: Make this amount configurable
 pub const CANISTER_HTTP_TIMEOUT_INTERVAL: Duration = Duration::from_secs(60);
 
+/// Upper bound on the number of redirects a canister http request may follow.
+pub const MAX_CANISTER_HTTP_REDIRECTS: u32 = 10;
+
 pub type CanisterHttpRequestId = CallbackId;
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -48,6 +51,8 @@ pub struct CanisterHttpRequestContext {
     pub request: Request,
     pub url: String,
     pub max_response_bytes: Option<NumBytes>,
+    /// Number of redirects to follow. Zero means redirects are returned to the canister as is.
+    pub max_redirects: u32,
     pub headers: Vec<CanisterHttpHeader>,
     pub body: Option<Vec<u8>>,
     pub http_method: CanisterHttpMethod,
@@ -63,6 +68,7 @@ impl From<&CanisterHttpRequestContext> for pb_metadata::CanisterHttpRequestConte
             max_response_bytes: context
                 .max_response_bytes
                 .map(|max_response_bytes| max_response_bytes.get()),
+            max_redirects: context.max_redirects,
             headers: context
                 .headers
                 .clone()
@@ -92,6 +98,7 @@ impl TryFrom<pb_metadata::CanisterHttpRequestContext> for CanisterHttpRequestCon
             request,
             url: context.url,
             max_response_bytes: context.max_response_bytes.map(NumBytes::from),
+            max_redirects: context.max_redirects,
             headers: context
                 .headers
                 .into_iter()
@@ -139,10 +146,21 @@ impl TryFrom<(Time, &Request, CanisterHttpRequestArgs)> for CanisterHttpRequestC
             None => Ok(None),
         }?;
 
+        let max_redirects = args.max_redirects.unwrap_or(0);
+        if max_redirects > MAX_CANISTER_HTTP_REDIRECTS {
+            return Err(CanisterHttpRequestContextError::MaxRedirects(
+                InvalidMaxRedirects {
+                    max: MAX_CANISTER_HTTP_REDIRECTS,
+                    given: max_redirects,
+                },
+            ));
+        }
+
         Ok(CanisterHttpRequestContext {
             request: request.clone(),
             url: args.url,
             max_response_bytes,
+            max_redirects,
             headers: args
                 .headers
                 .clone()
@@ -157,6 +175,9 @@ impl TryFrom<(Time, &Request, CanisterHttpRequestArgs)> for CanisterHttpRequestC
                 HttpMethod::GET => CanisterHttpMethod::GET,
                 HttpMethod::POST => CanisterHttpMethod::POST,
                 HttpMethod::HEAD => CanisterHttpMethod::HEAD,
+                HttpMethod::PUT => CanisterHttpMethod::PUT,
+                HttpMethod::PATCH => CanisterHttpMethod::PATCH,
+                HttpMethod::DELETE => CanisterHttpMethod::DELETE,
             },
             transform_method_name: args.transform_method_name,
             time,
@@ -172,10 +193,18 @@ pub struct InvalidMaxResponseBytes {
     given: u64,
 }
 
+/// The error that occurs when an end-user specifies an invalid
+/// [`max_redirects`].
+pub struct InvalidMaxRedirects {
+    max: u32,
+    given: u32,
+}
+
 /// Errors that can occur when converting from (time, request, [`CanisterHttpRequestArgs`]) to
 /// an [`CanisterHttpRequestContext`].
 pub enum CanisterHttpRequestContextError {
     MaxResponseBytes(InvalidMaxResponseBytes),
+    MaxRedirects(InvalidMaxRedirects),
 }
 
 impl From<CanisterHttpRequestContextError> for UserError {
@@ -188,6 +217,13 @@ impl From<CanisterHttpRequestContextError> for UserError {
                     err.min, err.max, err.given
                 ),
             ),
+            CanisterHttpRequestContextError::MaxRedirects(err) => UserError::new(
+                ErrorCode::CanisterRejectedMessage,
+                format!(
+                    "max_redirects expected to be at most {}, got {}",
+                    err.max, err.given
+                ),
+            ),
         }
     }
 }
@@ -258,6 +294,9 @@ pub enum CanisterHttpMethod {
     GET,
     POST,
     HEAD,
+    PUT,
+    PATCH,
+    DELETE,
 }
 
 impl From<&CanisterHttpMethod> for pb_metadata::HttpMethod {
@@ -266,6 +305,9 @@ impl From<&CanisterHttpMethod> for pb_metadata::HttpMethod {
             CanisterHttpMethod::GET => pb_metadata::HttpMethod::Get,
             CanisterHttpMethod::POST => pb_metadata::HttpMethod::Post,
             CanisterHttpMethod::HEAD => pb_metadata::HttpMethod::Head,
+            CanisterHttpMethod::PUT => pb_metadata::HttpMethod::Put,
+            CanisterHttpMethod::PATCH => pb_metadata::HttpMethod::Patch,
+            CanisterHttpMethod::DELETE => pb_metadata::HttpMethod::Delete,
         }
     }
 }
@@ -278,6 +320,9 @@ impl TryFrom<pb_metadata::HttpMethod> for CanisterHttpMethod {
             pb_metadata::HttpMethod::Get => Ok(CanisterHttpMethod::GET),
             pb_metadata::HttpMethod::Post => Ok(CanisterHttpMethod::POST),
             pb_metadata::HttpMethod::Head => Ok(CanisterHttpMethod::HEAD),
+            pb_metadata::HttpMethod::Put => Ok(CanisterHttpMethod::PUT),
+            pb_metadata::HttpMethod::Patch => Ok(CanisterHttpMethod::PATCH),
+            pb_metadata::HttpMethod::Delete => Ok(CanisterHttpMethod::DELETE),
             pb_metadata::HttpMethod::Unspecified => Err(ProxyDecodeError::ValueOutOfRange {
                 typ: "ic_protobuf::state::system_metadata::v1::HttpMethod",
                 err: "Unspecified HttpMethod".to_string(),
@@ -420,6 +420,41 @@ impl TryFrom<pb::Notarization> for Notarization {
 /// aggregated into a full notarization.
 pub type NotarizationShare = Signed<NotarizationContent, MultiSignatureShare<NotarizationContent>>;
 
+impl From<&NotarizationShare> for pb::NotarizationShare {
+    fn from(notarization: &NotarizationShare) -> Self {
+        Self {
+            version: notarization.content.version.to_string(),
+            height: notarization.content.height.get(),
+            block: notarization.content.block.clone().get().0,
+            signature: notarization.signature.signature.clone().get().0,
+            signer: notarization.signature.signer.get().into_vec(),
+        }
+    }
+}
+
+impl TryFrom<pb::NotarizationShare> for NotarizationShare {
+    type Error = String;
+    fn try_from(notarization: pb::NotarizationShare) -> Result<Self, Self::Error> {
+        Ok(Signed {
+            content: NotarizationContent {
+                version: ReplicaVersion::try_from(notarization.version.as_str()).map_err(|e| {
+                    format!("NotarizationShare replica version failed to parse {:?}", e)
+                })?,
+                height: Height::from(notarization.height),
+                block: CryptoHashOf::from(CryptoHash(notarization.block)),
+            },
+            signature: MultiSignatureShare {
+                signature: IndividualMultiSigOf::new(IndividualMultiSig(notarization.signature)),
+                signer: NodeId::from(
+                    PrincipalId::try_from(notarization.signer).map_err(|e| {
+                        format!("Unable to decode NotarizationShare signer {:?}", e)
+                    })?,
+                ),
+            },
+        })
+    }
+}
+
 /// FinalizationContent holds the values that are signed in a finalization
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct FinalizationContent {
@@ -493,6 +528,41 @@ impl TryFrom<pb::Finalization> for Finalization {
 /// aggregated into a full finalization.
 pub type FinalizationShare = Signed<FinalizationContent, MultiSignatureShare<FinalizationContent>>;
 
+impl From<&FinalizationShare> for pb::FinalizationShare {
+    fn from(finalization: &FinalizationShare) -> Self {
+        Self {
+            version: finalization.content.version.to_string(),
+            height: finalization.content.height.get(),
+            block: finalization.content.block.clone().get().0,
+            signature: finalization.signature.signature.clone().get().0,
+            signer: finalization.signature.signer.get().into_vec(),
+        }
+    }
+}
+
+impl TryFrom<pb::FinalizationShare> for FinalizationShare {
+    type Error = String;
+    fn try_from(finalization: pb::FinalizationShare) -> Result<Self, Self::Error> {
+        Ok(Signed {
+            content: FinalizationContent {
+                version: ReplicaVersion::try_from(finalization.version.as_str()).map_err(|e| {
+                    format!("FinalizationShare replica version failed to parse {:?}", e)
+                })?,
+                height: Height::from(finalization.height),
+                block: CryptoHashOf::from(CryptoHash(finalization.block)),
+            },
+            signature: MultiSignatureShare {
+                signature: IndividualMultiSigOf::new(IndividualMultiSig(finalization.signature)),
+                signer: NodeId::from(
+                    PrincipalId::try_from(finalization.signer).map_err(|e| {
+                        format!("Unable to decode FinalizationShare signer {:?}", e)
+                    })?,
+                ),
+            },
+        })
+    }
+}
+
 /// RandomBeaconContent holds the content that is signed in the random beacon,
 /// which is the previous random beacon, the height, and the replica version
 /// used to create the random beacon.
@@ -584,6 +654,41 @@ impl TryFrom<pb::RandomBeacon> for RandomBeacon {
 pub type RandomBeaconShare =
     Signed<RandomBeaconContent, ThresholdSignatureShare<RandomBeaconContent>>;
 
+impl From<&RandomBeaconShare> for pb::RandomBeaconShare {
+    fn from(random_beacon: &RandomBeaconShare) -> Self {
+        Self {
+            version: random_beacon.content.version.to_string(),
+            height: random_beacon.content.height.get(),
+            parent: random_beacon.content.parent.clone().get().0,
+            signature: random_beacon.signature.signature.clone().get().0,
+            signer: random_beacon.signature.signer.get().into_vec(),
+        }
+    }
+}
+
+impl TryFrom<pb::RandomBeaconShare> for RandomBeaconShare {
+    type Error = String;
+    fn try_from(beacon: pb::RandomBeaconShare) -> Result<Self, Self::Error> {
+        Ok(Signed {
+            content: RandomBeaconContent {
+                version: ReplicaVersion::try_from(beacon.version.as_str()).map_err(|e| {
+                    format!("RandomBeaconShare replica version failed to parse {:?}", e)
+                })?,
+                height: Height::from(beacon.height),
+                parent: CryptoHashOf::from(CryptoHash(beacon.parent)),
+            },
+            signature: ThresholdSignatureShare {
+                signature: ThresholdSigShareOf::new(ThresholdSigShare(beacon.signature)),
+                signer: NodeId::from(
+                    PrincipalId::try_from(beacon.signer).map_err(|e| {
+                        format!("Unable to decode RandomBeaconShare signer {:?}", e)
+                    })?,
+                ),
+            },
+        })
+    }
+}
+
 /// RandomTapeContent holds the content that is signed in the random tape,
 /// which is the height and the replica version used to create the random
 /// tape.
@@ -651,6 +756,38 @@ impl TryFrom<pb::RandomTape> for RandomTape {
 /// aggregated into a RandomTape.
 pub type RandomTapeShare = Signed<RandomTapeContent, ThresholdSignatureShare<RandomTapeContent>>;
 
+impl From<&RandomTapeShare> for pb::RandomTapeShare {
+    fn from(random_tape: &RandomTapeShare) -> Self {
+        Self {
+            version: random_tape.content.version.to_string(),
+            height: random_tape.content.height.get(),
+            signature: random_tape.signature.signature.clone().get().0,
+            signer: random_tape.signature.signer.get().into_vec(),
+        }
+    }
+}
+
+impl TryFrom<pb::RandomTapeShare> for RandomTapeShare {
+    type Error = String;
+    fn try_from(tape: pb::RandomTapeShare) -> Result<Self, Self::Error> {
+        Ok(Signed {
+            content: RandomTapeContent {
+                version: ReplicaVersion::try_from(tape.version.as_str()).map_err(|e| {
+                    format!("RandomTapeShare replica version failed to parse {:?}", e)
+                })?,
+                height: Height::from(tape.height),
+            },
+            signature: ThresholdSignatureShare {
+                signature: ThresholdSigShareOf::new(ThresholdSigShare(tape.signature)),
+                signer: NodeId::from(
+                    PrincipalId::try_from(tape.signer)
+                        .map_err(|e| format!("Unable to decode RandomTapeShare signer {:?}", e))?,
+                ),
+            },
+        })
+    }
+}
+
 /// The enum encompassing all of the consensus artifacts exchanged between
 /// replicas.
 #[allow(clippy::large_enum_variant)]
@@ -669,6 +806,50 @@ pub enum ConsensusMessage {
     CatchUpPackageShare(CatchUpPackageShare),
 }
 
+impl From<&ConsensusMessage> for pb::ConsensusMessage {
+    fn from(msg: &ConsensusMessage) -> Self {
+        use pb::consensus_message::Msg;
+        Self {
+            msg: Some(match msg {
+                ConsensusMessage::RandomBeacon(x) => Msg::RandomBeacon(x.into()),
+                ConsensusMessage::Finalization(x) => Msg::Finalization(x.into()),
+                ConsensusMessage::Notarization(x) => Msg::Notarization(x.into()),
+                ConsensusMessage::BlockProposal(x) => Msg::BlockProposal(x.into()),
+                ConsensusMessage::RandomBeaconShare(x) => Msg::RandomBeaconShare(x.into()),
+                ConsensusMessage::NotarizationShare(x) => Msg::NotarizationShare(x.into()),
+                ConsensusMessage::FinalizationShare(x) => Msg::FinalizationShare(x.into()),
+                ConsensusMessage::RandomTape(x) => Msg::RandomTape(x.into()),
+                ConsensusMessage::RandomTapeShare(x) => Msg::RandomTapeShare(x.into()),
+                ConsensusMessage::CatchUpPackage(x) => Msg::Cup(x.into()),
+                ConsensusMessage::CatchUpPackageShare(x) => Msg::CupShare(x.into()),
+            }),
+        }
+    }
+}
+
+impl TryFrom<pb::ConsensusMessage> for ConsensusMessage {
+    type Error = String;
+    fn try_from(msg: pb::ConsensusMessage) -> Result<Self, Self::Error> {
+        use pb::consensus_message::Msg;
+        let msg = msg
+            .msg
+            .ok_or_else(|| String::from("Error: ConsensusMessage is empty"))?;
+        Ok(match msg {
+            Msg::RandomBeacon(x) => ConsensusMessage::RandomBeacon(x.try_into()?),
+            Msg::Finalization(x) => ConsensusMessage::Finalization(x.try_into()?),
+            Msg::Notarization(x) => ConsensusMessage::Notarization(x.try_into()?),
+            Msg::BlockProposal(x) => ConsensusMessage::BlockProposal(x.try_into()?),
+            Msg::RandomBeaconShare(x) => ConsensusMessage::RandomBeaconShare(x.try_into()?),
+            Msg::NotarizationShare(x) => ConsensusMessage::NotarizationShare(x.try_into()?),
+            Msg::FinalizationShare(x) => ConsensusMessage::FinalizationShare(x.try_into()?),
+            Msg::RandomTape(x) => ConsensusMessage::RandomTape(x.try_into()?),
+            Msg::RandomTapeShare(x) => ConsensusMessage::RandomTapeShare(x.try_into()?),
+            Msg::Cup(x) => ConsensusMessage::CatchUpPackage(CatchUpPackage::try_from(&x)?),
+            Msg::CupShare(x) => ConsensusMessage::CatchUpPackageShare(x.try_into()?),
+        })
+    }
+}
+
 impl TryFrom<ConsensusMessage> for RandomBeacon {
     type Error = ConsensusMessage;
     fn try_from(msg: ConsensusMessage) -> Result<Self, Self::Error> {
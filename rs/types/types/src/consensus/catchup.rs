@@ -7,7 +7,7 @@ use crate::{
     },
     crypto::threshold_sig::ni_dkg::NiDkgId,
     crypto::*,
-    CryptoHashOfState, Height, RegistryVersion, ReplicaVersion,
+    CryptoHashOfState, Height, NodeId, PrincipalId, RegistryVersion, ReplicaVersion,
 };
 use ic_protobuf::types::v1 as pb;
 use prost::Message;
@@ -195,6 +195,55 @@ impl From<&CatchUpContent> for CatchUpShareContent {
 /// committee.
 pub type CatchUpPackageShare = Signed<CatchUpShareContent, ThresholdSignatureShare<CatchUpContent>>;
 
+impl From<&CatchUpPackageShare> for pb::CatchUpPackageShare {
+    fn from(share: &CatchUpPackageShare) -> Self {
+        Self {
+            version: share.content.version.to_string(),
+            random_beacon: Some(pb::RandomBeacon::from(share.content.random_beacon.as_ref())),
+            state_hash: share.content.state_hash.clone().get().0,
+            block_hash: share.content.block.clone().get().0,
+            random_beacon_hash: share.content.random_beacon.get_hash().clone().get().0,
+            signature: share.signature.signature.clone().get().0,
+            signer: share.signature.signer.get().into_vec(),
+        }
+    }
+}
+
+impl TryFrom<pb::CatchUpPackageShare> for CatchUpPackageShare {
+    type Error = String;
+    fn try_from(share: pb::CatchUpPackageShare) -> Result<Self, Self::Error> {
+        let random_beacon = RandomBeacon::try_from(
+            share
+                .random_beacon
+                .ok_or_else(|| String::from("Error: CUP share missing random beacon"))?,
+        )?;
+        Ok(Signed {
+            content: CatchUpShareContent {
+                version: ReplicaVersion::try_from(share.version.as_str()).map_err(|e| {
+                    format!(
+                        "CatchUpPackageShare replica version failed to parse {:?}",
+                        e
+                    )
+                })?,
+                block: CryptoHashOf::from(CryptoHash(share.block_hash)),
+                random_beacon: HashedRandomBeacon {
+                    hash: CryptoHashOf::from(CryptoHash(share.random_beacon_hash)),
+                    value: random_beacon,
+                },
+                state_hash: CryptoHashOf::from(CryptoHash(share.state_hash)),
+            },
+            signature: ThresholdSignatureShare {
+                signature: ThresholdSigShareOf::new(ThresholdSigShare(share.signature)),
+                signer: NodeId::from(
+                    PrincipalId::try_from(share.signer).map_err(|e| {
+                        format!("Unable to decode CatchUpPackageShare signer {:?}", e)
+                    })?,
+                ),
+            },
+        })
+    }
+}
+
 /// The parameters used to request `CatchUpPackage` (by orchestrator).
 ///
 /// We make use of the `Ord` trait to determine if one `CatchUpPackage` is newer
@@ -4,12 +4,16 @@ use crate::{
     consensus::{
         Committee, CountBytes, HasCommittee, HasHeight, ThresholdSignature, ThresholdSignatureShare,
     },
-    crypto::{CryptoHash, CryptoHashOf, Signed, SignedBytesWithoutDomainSeparator},
-    CryptoHashOfPartialState, Height,
+    crypto::{
+        CryptoHash, CryptoHashOf, Signed, SignedBytesWithoutDomainSeparator, ThresholdSigShare,
+        ThresholdSigShareOf,
+    },
+    CryptoHashOfPartialState, Height, NodeId, PrincipalId,
 };
 use ic_protobuf::messaging::xnet::v1 as pb;
+use ic_protobuf::types::v1 as types_pb;
 use serde::{Deserialize, Serialize};
-use std::convert::TryFrom;
+use std::convert::{TryFrom, TryInto};
 
 /// CertificationMessage captures the different types of messages sent around
 /// for the purpose of state certification.
@@ -63,6 +67,37 @@ impl From<CertificationShare> for CertificationMessage {
     }
 }
 
+impl From<&CertificationMessage> for types_pb::CertificationMessage {
+    fn from(msg: &CertificationMessage) -> Self {
+        use types_pb::certification_message::Msg;
+        Self {
+            msg: Some(match msg {
+                CertificationMessage::Certification(x) => {
+                    Msg::Certification(pb::Certification::from(x.clone()))
+                }
+                CertificationMessage::CertificationShare(x) => Msg::CertificationShare(x.into()),
+            }),
+        }
+    }
+}
+
+impl TryFrom<types_pb::CertificationMessage> for CertificationMessage {
+    type Error = String;
+    fn try_from(msg: types_pb::CertificationMessage) -> Result<Self, Self::Error> {
+        use types_pb::certification_message::Msg;
+        let msg = msg
+            .msg
+            .ok_or_else(|| String::from("Error: CertificationMessage is empty"))?;
+        Ok(match msg {
+            Msg::Certification(x) => CertificationMessage::Certification(
+                Certification::try_from(x)
+                    .map_err(|e| format!("Unable to decode Certification {:?}", e))?,
+            ),
+            Msg::CertificationShare(x) => CertificationMessage::CertificationShare(x.try_into()?),
+        })
+    }
+}
+
 /// CertificationMessageHash contains the hash of a CertificationMessage.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
 pub enum CertificationMessageHash {
@@ -158,3 +193,34 @@ impl HasHeight for CertificationShare {
         self.height
     }
 }
+
+impl From<&CertificationShare> for types_pb::CertificationShare {
+    fn from(share: &CertificationShare) -> Self {
+        Self {
+            height: share.height.get(),
+            hash: share.signed.content.hash.clone().get().0,
+            signature: share.signed.signature.signature.clone().get().0,
+            signer: share.signed.signature.signer.get().into_vec(),
+        }
+    }
+}
+
+impl TryFrom<types_pb::CertificationShare> for CertificationShare {
+    type Error = String;
+    fn try_from(share: types_pb::CertificationShare) -> Result<Self, Self::Error> {
+        Ok(Self {
+            height: Height::from(share.height),
+            signed: Signed {
+                content: CertificationContent::new(CryptoHashOfPartialState::from(CryptoHash(
+                    share.hash,
+                ))),
+                signature: ThresholdSignatureShare {
+                    signature: ThresholdSigShareOf::new(ThresholdSigShare(share.signature)),
+                    signer: NodeId::from(PrincipalId::try_from(share.signer).map_err(|e| {
+                        format!("Unable to decode CertificationShare signer {:?}", e)
+                    })?),
+                },
+            },
+        })
+    }
+}
@@ -24,7 +24,8 @@ use crate::crypto::{
         IDkgTranscriptParams, InitialIDkgDealings, SignedIDkgDealing,
     },
     canister_threshold_sig::ThresholdEcdsaSigShare,
-    AlgorithmId, CryptoHash, CryptoHashOf, Signed, SignedBytesWithoutDomainSeparator,
+    AlgorithmId, BasicSig, BasicSigOf, CryptoHash, CryptoHashOf, Signed,
+    SignedBytesWithoutDomainSeparator,
 };
 use crate::{node_id_into_protobuf, node_id_try_from_protobuf};
 use crate::{Height, NodeId, RegistryVersion, SubnetId};
@@ -1044,6 +1045,218 @@ impl TryFrom<EcdsaMessage> for EcdsaOpening {
     }
 }
 
+impl From<&EcdsaMessage> for pb::EcdsaMessage {
+    fn from(msg: &EcdsaMessage) -> Self {
+        use pb::ecdsa_message::Msg;
+        Self {
+            msg: Some(match msg {
+                EcdsaMessage::EcdsaSignedDealing(x) => Msg::SignedDealing(x.into()),
+                EcdsaMessage::EcdsaDealingSupport(x) => Msg::DealingSupport(x.into()),
+                EcdsaMessage::EcdsaSigShare(x) => Msg::SigShare(x.into()),
+                EcdsaMessage::EcdsaComplaint(x) => Msg::Complaint(x.into()),
+                EcdsaMessage::EcdsaOpening(x) => Msg::Opening(x.into()),
+            }),
+        }
+    }
+}
+
+impl TryFrom<pb::EcdsaMessage> for EcdsaMessage {
+    type Error = String;
+    fn try_from(msg: pb::EcdsaMessage) -> Result<Self, Self::Error> {
+        use pb::ecdsa_message::Msg;
+        let msg = msg.msg.ok_or("pb::EcdsaMessage:: Missing message")?;
+        Ok(match msg {
+            Msg::SignedDealing(x) => EcdsaMessage::EcdsaSignedDealing(
+                SignedIDkgDealing::try_from(&x).map_err(|err| {
+                    format!(
+                        "pb::EcdsaMessage:: Failed to convert signed dealing: {:?}",
+                        err
+                    )
+                })?,
+            ),
+            Msg::DealingSupport(x) => EcdsaMessage::EcdsaDealingSupport((&x).try_into()?),
+            Msg::SigShare(x) => EcdsaMessage::EcdsaSigShare((&x).try_into()?),
+            Msg::Complaint(x) => EcdsaMessage::EcdsaComplaint((&x).try_into()?),
+            Msg::Opening(x) => EcdsaMessage::EcdsaOpening((&x).try_into()?),
+        })
+    }
+}
+
+impl From<&IDkgDealingSupport> for pb::EcdsaDealingSupport {
+    fn from(support: &IDkgDealingSupport) -> Self {
+        Self {
+            transcript_id: Some((&support.transcript_id).into()),
+            dealer: Some(node_id_into_protobuf(support.dealer_id)),
+            dealing_hash: support.dealing_hash.clone().get().0,
+            sig_share: support.sig_share.signature.clone().get().0,
+            signer: Some(node_id_into_protobuf(support.sig_share.signer)),
+        }
+    }
+}
+
+impl TryFrom<&pb::EcdsaDealingSupport> for IDkgDealingSupport {
+    type Error = String;
+    fn try_from(support: &pb::EcdsaDealingSupport) -> Result<Self, Self::Error> {
+        Ok(Self {
+            transcript_id: transcript_id_try_from_protobuf(
+                &support.transcript_id,
+                "pb::EcdsaDealingSupport",
+            )?,
+            dealer_id: node_id_try_from_option(
+                &support.dealer,
+                "pb::EcdsaDealingSupport",
+                "dealer",
+            )?,
+            dealing_hash: CryptoHashOf::new(CryptoHash(support.dealing_hash.clone())),
+            sig_share: BasicSignature {
+                signature: BasicSigOf::new(BasicSig(support.sig_share.clone())),
+                signer: node_id_try_from_option(
+                    &support.signer,
+                    "pb::EcdsaDealingSupport",
+                    "signer",
+                )?,
+            },
+        })
+    }
+}
+
+impl From<&EcdsaSigShare> for pb::EcdsaSigShare {
+    fn from(share: &EcdsaSigShare) -> Self {
+        Self {
+            signer: Some(node_id_into_protobuf(share.signer_id)),
+            request_id: Some(share.request_id.clone().into()),
+            sig_share_raw: share.share.sig_share_raw.clone(),
+        }
+    }
+}
+
+impl TryFrom<&pb::EcdsaSigShare> for EcdsaSigShare {
+    type Error = String;
+    fn try_from(share: &pb::EcdsaSigShare) -> Result<Self, Self::Error> {
+        let request_id = share
+            .request_id
+            .as_ref()
+            .ok_or("pb::EcdsaSigShare:: Missing request Id")?;
+        Ok(Self {
+            signer_id: node_id_try_from_option(&share.signer, "pb::EcdsaSigShare", "signer")?,
+            request_id: request_id.try_into().map_err(|err| {
+                format!(
+                    "pb::EcdsaSigShare:: Failed to convert request Id: {:?}",
+                    err
+                )
+            })?,
+            share: ThresholdEcdsaSigShare {
+                sig_share_raw: share.sig_share_raw.clone(),
+            },
+        })
+    }
+}
+
+impl From<&EcdsaComplaint> for pb::EcdsaComplaint {
+    fn from(complaint: &EcdsaComplaint) -> Self {
+        let idkg_complaint = &complaint.content.idkg_complaint;
+        Self {
+            transcript_id: Some((&idkg_complaint.transcript_id).into()),
+            dealer: Some(node_id_into_protobuf(idkg_complaint.dealer_id)),
+            raw_complaint: idkg_complaint.internal_complaint_raw.clone(),
+            signature: complaint.signature.signature.clone().get().0,
+            signer: Some(node_id_into_protobuf(complaint.signature.signer)),
+        }
+    }
+}
+
+impl TryFrom<&pb::EcdsaComplaint> for EcdsaComplaint {
+    type Error = String;
+    fn try_from(complaint: &pb::EcdsaComplaint) -> Result<Self, Self::Error> {
+        Ok(Signed {
+            content: EcdsaComplaintContent {
+                idkg_complaint: IDkgComplaint {
+                    transcript_id: transcript_id_try_from_protobuf(
+                        &complaint.transcript_id,
+                        "pb::EcdsaComplaint",
+                    )?,
+                    dealer_id: node_id_try_from_option(
+                        &complaint.dealer,
+                        "pb::EcdsaComplaint",
+                        "dealer",
+                    )?,
+                    internal_complaint_raw: complaint.raw_complaint.clone(),
+                },
+            },
+            signature: BasicSignature {
+                signature: BasicSigOf::new(BasicSig(complaint.signature.clone())),
+                signer: node_id_try_from_option(&complaint.signer, "pb::EcdsaComplaint", "signer")?,
+            },
+        })
+    }
+}
+
+impl From<&EcdsaOpening> for pb::EcdsaOpening {
+    fn from(opening: &EcdsaOpening) -> Self {
+        let idkg_opening = &opening.content.idkg_opening;
+        Self {
+            transcript_id: Some((&idkg_opening.transcript_id).into()),
+            dealer: Some(node_id_into_protobuf(idkg_opening.dealer_id)),
+            complainer: Some(node_id_into_protobuf(opening.content.complainer_id)),
+            raw_opening: idkg_opening.internal_opening_raw.clone(),
+            signature: opening.signature.signature.clone().get().0,
+            signer: Some(node_id_into_protobuf(opening.signature.signer)),
+        }
+    }
+}
+
+impl TryFrom<&pb::EcdsaOpening> for EcdsaOpening {
+    type Error = String;
+    fn try_from(opening: &pb::EcdsaOpening) -> Result<Self, Self::Error> {
+        Ok(Signed {
+            content: EcdsaOpeningContent {
+                complainer_id: node_id_try_from_option(
+                    &opening.complainer,
+                    "pb::EcdsaOpening",
+                    "complainer",
+                )?,
+                idkg_opening: IDkgOpening {
+                    transcript_id: transcript_id_try_from_protobuf(
+                        &opening.transcript_id,
+                        "pb::EcdsaOpening",
+                    )?,
+                    dealer_id: node_id_try_from_option(
+                        &opening.dealer,
+                        "pb::EcdsaOpening",
+                        "dealer",
+                    )?,
+                    internal_opening_raw: opening.raw_opening.clone(),
+                },
+            },
+            signature: BasicSignature {
+                signature: BasicSigOf::new(BasicSig(opening.signature.clone())),
+                signer: node_id_try_from_option(&opening.signer, "pb::EcdsaOpening", "signer")?,
+            },
+        })
+    }
+}
+
+fn transcript_id_try_from_protobuf(
+    transcript_id: &Option<subnet_pb::IDkgTranscriptId>,
+    message: &str,
+) -> Result<IDkgTranscriptId, String> {
+    transcript_id
+        .try_into()
+        .map_err(|err| format!("{}:: Failed to convert transcript Id: {:?}", message, err))
+}
+
+fn node_id_try_from_option(
+    node_id: &Option<pb::NodeId>,
+    message: &str,
+    field: &str,
+) -> Result<NodeId, String> {
+    let node_id = node_id
+        .clone()
+        .ok_or_else(|| format!("{}:: Missing {}", message, field))?;
+    node_id_try_from_protobuf(node_id)
+        .map_err(|err| format!("{}:: Failed to convert {}: {:?}", message, field, err))
+}
+
 pub type Summary = Option<EcdsaPayload>;
 
 pub type Payload = Option<EcdsaPayload>;
@@ -59,6 +59,20 @@ impl TryFrom<&IDkgTranscriptProto> for IDkgTranscript {
     }
 }
 
+impl From<&SignedIDkgDealing> for IDkgSignedDealingTupleProto {
+    fn from(signed_dealing: &SignedIDkgDealing) -> Self {
+        signed_idkg_dealing_tuple_proto(signed_dealing)
+    }
+}
+
+impl TryFrom<&IDkgSignedDealingTupleProto> for SignedIDkgDealing {
+    type Error = InitialIDkgDealingsValidationError;
+
+    fn try_from(proto: &IDkgSignedDealingTupleProto) -> Result<Self, Self::Error> {
+        signed_idkg_dealing_struct(&Some(proto.clone()))
+    }
+}
+
 impl From<&InitialIDkgDealings> for InitialIDkgDealingsProto {
     fn from(initial_dealings: &InitialIDkgDealings) -> Self {
         let signed_dealings = initial_dealings
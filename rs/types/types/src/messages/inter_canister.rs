@@ -104,7 +104,9 @@ impl Request {
             | Ok(Method::BitcoinGetBalance)
             | Ok(Method::BitcoinGetUtxos)
             | Ok(Method::BitcoinSendTransaction)
-            | Ok(Method::BitcoinGetCurrentFeePercentiles) => {
+            | Ok(Method::BitcoinGetCurrentFeePercentiles)
+            | Ok(Method::BitcoinGetAddressHistory)
+            | Ok(Method::BitcoinGetBlockHeaders) => {
                 // No effective canister id.
                 None
             }
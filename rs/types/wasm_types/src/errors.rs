@@ -42,6 +42,8 @@ pub enum WasmValidationError {
     InvalidDataSection(String),
     /// Module contains an invalid custom section
     InvalidCustomSection(String),
+    /// Module contains an invalid global section
+    InvalidGlobalSection(String),
     /// Module contains too many globals.
     TooManyGlobals { defined: usize, allowed: usize },
     /// Module contains too many functions.
@@ -54,6 +56,8 @@ pub enum WasmValidationError {
     FunctionComplexityTooHigh,
     /// A function was too large.
     FunctionTooLarge,
+    /// Module uses a Wasm proposal that is not supported.
+    UnsupportedWasmFeature(String),
 }
 
 impl std::fmt::Display for WasmValidationError {
@@ -83,6 +87,9 @@ impl std::fmt::Display for WasmValidationError {
             Self::InvalidCustomSection(err) => {
                 write!(f, "Wasm module has an invalid custom section. {}", err)
             }
+            Self::InvalidGlobalSection(err) => {
+                write!(f, "Wasm module has an invalid global section. {}", err)
+            }
             Self::TooManyGlobals { defined, allowed } => write!(
                 f,
                 "Wasm module defined {} globals which exceeds the maximum number allowed {}.",
@@ -108,6 +115,9 @@ impl std::fmt::Display for WasmValidationError {
             ),
             Self::FunctionComplexityTooHigh => write!(f, "Wasm module contains a function that is too complex"),
             Self::FunctionTooLarge => write!(f, "Wasm module contains a function that is too large"),
+            Self::UnsupportedWasmFeature(feature) => {
+                write!(f, "Wasm module uses an unsupported feature: {}", feature)
+            }
         }
     }
 }